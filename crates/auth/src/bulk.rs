@@ -0,0 +1,608 @@
+//! Admin API によるユーザーの一括インポート・エクスポート
+//!
+//! インポートは同時実行数とリクエスト間隔を制限し、429 は `retry-after` だけ待って再試行する。
+//! エクスポートは `list_users_paged` をページ単位でたどり、1ページずつ書き出す。
+
+use crate::{AdminAuth, AuthError, RateLimitInfo, Redacted, User};
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use supabase_rust_core::{Page, RequestIds, SendWithRequestId};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// `create_user_with_params` / `import_users` のユーザー作成パラメータ
+///
+/// パスワードの代わりに bcrypt のハッシュ (`password_hash`) を渡すと、
+/// 他のサービスから移行したユーザーが同じパスワードでそのままサインインできる。
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CreateUserParams {
+    /// 作成するユーザーの ID (省略時はサーバーが生成)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<Redacted<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<Redacted<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_confirm: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phone_confirm: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_metadata: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_metadata: Option<serde_json::Value>,
+}
+
+impl CreateUserParams {
+    /// メールアドレスでユーザーを作成
+    pub fn email(email: &str) -> Self {
+        Self {
+            email: Some(email.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// 電話番号でユーザーを作成
+    pub fn phone(phone: &str) -> Self {
+        Self {
+            phone: Some(phone.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn with_password(mut self, password: &str) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// bcrypt のパスワードハッシュ (`$2a$...`) を設定
+    pub fn with_password_hash(mut self, password_hash: &str) -> Self {
+        self.password_hash = Some(password_hash.into());
+        self
+    }
+
+    pub fn with_email_confirm(mut self, email_confirm: bool) -> Self {
+        self.email_confirm = Some(email_confirm);
+        self
+    }
+
+    pub fn with_phone_confirm(mut self, phone_confirm: bool) -> Self {
+        self.phone_confirm = Some(phone_confirm);
+        self
+    }
+
+    pub fn with_user_metadata(mut self, user_metadata: serde_json::Value) -> Self {
+        self.user_metadata = Some(user_metadata);
+        self
+    }
+
+    pub fn with_app_metadata(mut self, app_metadata: serde_json::Value) -> Self {
+        self.app_metadata = Some(app_metadata);
+        self
+    }
+}
+
+/// `import_users` のオプション
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// 同時に送るリクエスト数の上限
+    pub concurrency: usize,
+    /// リクエストを開始する最小間隔 (全体で共有)
+    pub delay: Duration,
+    /// 429 を受けたときの最大再試行回数
+    pub max_retries: u32,
+    /// `retry-after` ヘッダーがない 429 の待ち時間
+    pub default_retry_after: Duration,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            delay: Duration::ZERO,
+            max_retries: 5,
+            default_retry_after: Duration::from_secs(1),
+        }
+    }
+}
+
+impl ImportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 同時実行数を設定 (0 は 1 として扱う)
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_default_retry_after(mut self, retry_after: Duration) -> Self {
+        self.default_retry_after = retry_after;
+        self
+    }
+}
+
+/// 作成したユーザー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedUser {
+    /// 入力の何番目か
+    pub index: usize,
+    pub id: String,
+}
+
+/// 作成に失敗したユーザー
+#[derive(Debug)]
+pub struct ImportFailure {
+    /// 入力の何番目か
+    pub index: usize,
+    pub email: Option<String>,
+    pub error: AuthError,
+}
+
+/// `import_users` の結果 (`created` / `failed` は入力順)
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub created: Vec<ImportedUser>,
+    pub failed: Vec<ImportFailure>,
+    /// 429 による再試行の合計回数
+    pub retries: u32,
+    pub duration: Duration,
+}
+
+impl ImportReport {
+    /// 失敗がなければ true
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// `export_users` の出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// ヘッダー行付きの CSV (メタデータ列は JSON 文字列)
+    Csv,
+    /// 1行に1ユーザーの JSON
+    Jsonl,
+}
+
+const CSV_COLUMNS: [&str; 10] = [
+    "id",
+    "email",
+    "phone",
+    "role",
+    "created_at",
+    "updated_at",
+    "last_sign_in_at",
+    "confirmed_at",
+    "user_metadata",
+    "app_metadata",
+];
+
+// エクスポート時の1ページあたりの件数
+const EXPORT_PAGE_SIZE: u32 = 500;
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_row(user: &User) -> String {
+    let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+    let fields = [
+        user.id.clone(),
+        optional(&user.email),
+        optional(&user.phone),
+        optional(&user.role),
+        user.created_at.clone(),
+        user.updated_at.clone(),
+        optional(&user.last_sign_in_at),
+        optional(&user.confirmed_at),
+        user.user_metadata.to_string(),
+        user.app_metadata.to_string(),
+    ];
+    let mut row = fields
+        .iter()
+        .map(|f| csv_field(f))
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push('\n');
+    row
+}
+
+// 1ユーザー分の結果
+struct ImportOutcome {
+    index: usize,
+    email: Option<String>,
+    result: Result<User, AuthError>,
+    retries: u32,
+}
+
+// リクエストの開始時刻を `delay` 間隔に揃える
+struct Pacer {
+    delay: Duration,
+    next_start: Mutex<Instant>,
+}
+
+impl Pacer {
+    fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            next_start: Mutex::new(Instant::now()),
+        }
+    }
+
+    async fn wait(&self) {
+        if self.delay.is_zero() {
+            return;
+        }
+        let start = {
+            let mut next_start = self.next_start.lock().await;
+            let start = (*next_start).max(Instant::now());
+            *next_start = start + self.delay;
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+}
+
+impl AdminAuth {
+    /// パラメータを指定してユーザーを作成 (429 は `AuthError::RateLimited` として返す)
+    pub async fn create_user_with_params(
+        &self,
+        params: &CreateUserParams,
+    ) -> Result<User, AuthError> {
+        let response = self
+            .http_client
+            .post(format!("{}/admin/users", self.base_url()))
+            .header("apikey", self.service_role_key.expose())
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.service_role_key.expose()),
+            )
+            .json(params)
+            .send_with_request_id()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let rate_limit = RateLimitInfo::from_headers(response.headers());
+            let error_text = self.scrub_secrets(&response.text().await.unwrap_or_default());
+            let message = format!("Failed to create user: {}", error_text);
+            return Err(if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                AuthError::RateLimited {
+                    message,
+                    rate_limit,
+                    request_ids,
+                }
+            } else {
+                AuthError::ApiError {
+                    message,
+                    status: Some(status.as_u16()),
+                    request_ids,
+                }
+            });
+        }
+
+        Ok(response.json::<User>().await?)
+    }
+
+    /// ユーザーを一括作成
+    ///
+    /// 個々の失敗で中断せず、すべての結果を [`ImportReport`] にまとめて返す。
+    pub async fn import_users(
+        &self,
+        users: Vec<CreateUserParams>,
+        options: ImportOptions,
+    ) -> ImportReport {
+        let started = Instant::now();
+        let pacer = Arc::new(Pacer::new(options.delay));
+        let options = &options;
+
+        let mut results: Vec<ImportOutcome> = stream::iter(users.into_iter().enumerate())
+            .map(|(index, params)| {
+                let pacer = pacer.clone();
+                async move {
+                    let mut retries = 0;
+                    let result = loop {
+                        pacer.wait().await;
+                        match self.create_user_with_params(&params).await {
+                            Err(AuthError::RateLimited { rate_limit, .. })
+                                if retries < options.max_retries =>
+                            {
+                                retries += 1;
+                                let wait = rate_limit
+                                    .reset_after
+                                    .unwrap_or(options.default_retry_after);
+                                log::debug!(
+                                    "User import rate limited, retrying in {:?} (attempt {})",
+                                    wait,
+                                    retries
+                                );
+                                tokio::time::sleep(wait).await;
+                            }
+                            result => break result,
+                        }
+                    };
+                    ImportOutcome {
+                        index,
+                        email: params.email,
+                        result,
+                        retries,
+                    }
+                }
+            })
+            .buffer_unordered(options.concurrency.max(1))
+            .collect()
+            .await;
+        results.sort_by_key(|outcome| outcome.index);
+
+        let mut report = ImportReport::default();
+        for outcome in results {
+            report.retries += outcome.retries;
+            match outcome.result {
+                Ok(user) => report.created.push(ImportedUser {
+                    index: outcome.index,
+                    id: user.id,
+                }),
+                Err(error) => report.failed.push(ImportFailure {
+                    index: outcome.index,
+                    email: outcome.email,
+                    error,
+                }),
+            }
+        }
+        report.duration = started.elapsed();
+        report
+    }
+
+    /// すべてのユーザーを `writer` に書き出し、書き出した件数を返す
+    ///
+    /// ユーザーはページ単位で取得して書き出すため、全件をメモリに保持しない。
+    pub async fn export_users<W>(&self, writer: W, format: ExportFormat) -> Result<u64, AuthError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut writer = writer;
+        if format == ExportFormat::Csv {
+            writer
+                .write_all(format!("{}\n", CSV_COLUMNS.join(",")).as_bytes())
+                .await?;
+        }
+
+        let mut count = 0;
+        let mut page = Page::first(EXPORT_PAGE_SIZE);
+        loop {
+            let paged = self.list_users_paged(page, None).await?;
+            for user in &paged.items {
+                let line = match format {
+                    ExportFormat::Csv => csv_row(user),
+                    ExportFormat::Jsonl => format!("{}\n", serde_json::to_string(user)?),
+                };
+                writer.write_all(line.as_bytes()).await?;
+                count += 1;
+            }
+            if paged.items.len() < page.size as usize {
+                break;
+            }
+            match paged.next_page() {
+                Some(next) => page = next,
+                None => break,
+            }
+        }
+        writer.flush().await?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    fn user_json(id: &str, email: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "email": email,
+            "phone": null,
+            "app_metadata": { "provider": "email" },
+            "user_metadata": {},
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-01T00:00:00Z"
+        })
+    }
+
+    #[tokio::test]
+    async fn test_import_users_retries_rate_limits_and_reports_failures() {
+        let mock_server = MockServer::start().await;
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        Mock::given(method("POST"))
+            .and(path("/auth/v1/admin/users"))
+            .respond_with(move |request: &Request| {
+                let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+                let email = body["email"].as_str().unwrap_or_default().to_string();
+                // 3回に1回は 429 を返す
+                if counter.fetch_add(1, Ordering::SeqCst).is_multiple_of(3) {
+                    return ResponseTemplate::new(429)
+                        .insert_header("retry-after", "0")
+                        .set_body_string("Too many requests");
+                }
+                if email == "taken@example.com" {
+                    return ResponseTemplate::new(422).set_body_string(
+                        r#"{"msg":"A user with this email address has already been registered"}"#,
+                    );
+                }
+                // bcrypt ハッシュはそのまま送られ、平文のパスワードは送られない
+                if email == "hashed@example.com" {
+                    assert_eq!(body["password_hash"], "$2a$10$abcdefghijklmnopqrstuv");
+                    assert!(body.get("password").is_none());
+                }
+                ResponseTemplate::new(200)
+                    .set_body_json(user_json(&format!("id-{}", email), &email))
+            })
+            .mount(&mock_server)
+            .await;
+
+        let admin = AdminAuth::new(&mock_server.uri(), "service-key", reqwest::Client::new());
+        let users = vec![
+            CreateUserParams::email("a@example.com").with_password("password-a"),
+            CreateUserParams::email("taken@example.com"),
+            CreateUserParams::email("hashed@example.com")
+                .with_password_hash("$2a$10$abcdefghijklmnopqrstuv")
+                .with_email_confirm(true),
+            CreateUserParams::email("b@example.com"),
+        ];
+        let report = admin
+            .import_users(
+                users,
+                ImportOptions::new()
+                    .with_concurrency(2)
+                    .with_delay(Duration::from_millis(5)),
+            )
+            .await;
+
+        assert!(!report.is_success());
+        assert_eq!(
+            report.created,
+            vec![
+                ImportedUser {
+                    index: 0,
+                    id: "id-a@example.com".to_string()
+                },
+                ImportedUser {
+                    index: 2,
+                    id: "id-hashed@example.com".to_string()
+                },
+                ImportedUser {
+                    index: 3,
+                    id: "id-b@example.com".to_string()
+                },
+            ]
+        );
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].index, 1);
+        assert_eq!(report.failed[0].email.as_deref(), Some("taken@example.com"));
+        assert!(report.failed[0]
+            .error
+            .to_string()
+            .contains("already been registered"));
+        assert!(report.retries >= 1);
+        assert_eq!(requests.load(Ordering::SeqCst), 4 + report.retries as usize);
+    }
+
+    #[tokio::test]
+    async fn test_import_users_gives_up_after_max_retries() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/auth/v1/admin/users"))
+            .respond_with(ResponseTemplate::new(429).set_body_string("Too many requests"))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let admin = AdminAuth::new(&mock_server.uri(), "service-key", reqwest::Client::new());
+        let options = ImportOptions::new()
+            .with_max_retries(2)
+            .with_default_retry_after(Duration::from_millis(1));
+        let report = admin
+            .import_users(vec![CreateUserParams::email("a@example.com")], options)
+            .await;
+
+        assert_eq!(report.retries, 2);
+        assert!(matches!(
+            report.failed[0].error,
+            AuthError::RateLimited { .. }
+        ));
+    }
+
+    #[test]
+    fn test_create_user_params_debug_hides_passwords() {
+        let params = CreateUserParams::email("a@example.com")
+            .with_password("hunter2-password")
+            .with_password_hash("$2a$10$abcdefghijklmnopqrstuv");
+        let debug = format!("{:?}", params);
+        assert!(!debug.contains("hunter2"));
+        assert!(!debug.contains("$2a$10$"));
+    }
+
+    #[tokio::test]
+    async fn test_export_users_pages_through_all_users() {
+        let mock_server = MockServer::start().await;
+        let first_page: Vec<_> = (0..EXPORT_PAGE_SIZE)
+            .map(|i| user_json(&format!("id-{}", i), &format!("user{}@example.com", i)))
+            .collect();
+        Mock::given(method("GET"))
+            .and(path("/auth/v1/admin/users"))
+            .and(query_param("page", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "users": first_page })))
+            .mount(&mock_server)
+            .await;
+        let mut last = user_json("id-last", "last@example.com");
+        last["user_metadata"] = json!({ "name": "Doe, \"Jane\"" });
+        Mock::given(method("GET"))
+            .and(path("/auth/v1/admin/users"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "users": [last] })))
+            .mount(&mock_server)
+            .await;
+
+        let admin = AdminAuth::new(&mock_server.uri(), "service-key", reqwest::Client::new());
+
+        let mut jsonl = Vec::new();
+        let count = admin
+            .export_users(&mut jsonl, ExportFormat::Jsonl)
+            .await
+            .unwrap();
+        assert_eq!(count, EXPORT_PAGE_SIZE as u64 + 1);
+        let lines: Vec<User> = String::from_utf8(jsonl)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), count as usize);
+        assert_eq!(lines.last().unwrap().id, "id-last");
+
+        let mut csv = Vec::new();
+        admin
+            .export_users(&mut csv, ExportFormat::Csv)
+            .await
+            .unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut rows = csv.lines();
+        assert_eq!(rows.next().unwrap(), CSV_COLUMNS.join(","));
+        assert_eq!(
+            rows.last().unwrap(),
+            r#"id-last,last@example.com,,,2024-01-01T00:00:00Z,2024-01-01T00:00:00Z,,,"{""name"":""Doe, \""Jane\""""}","{""provider"":""email""}""#
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! `@supabase/ssr` 互換のクッキーでセッションを読み書きする (`ssr` フィーチャー)
+//!
+//! ブラウザ (`@supabase/ssr`) と Rust のサーバーで同じセッションを共有するためのヘルパー。
+//! クッキーの形式は `@supabase/ssr` と同じ:
+//!
+//! - 名前は `sb-<プロジェクト ref>-auth-token`
+//! - 値は `base64-` + セッション JSON の base64url (パディングなし)。
+//!   プレフィックスのない値は古い形式 (JSON そのもの、または URL エンコードした JSON) として読む
+//! - 値が `max_chunk` (既定 3180) 文字を超える場合は `<名前>.0`, `<名前>.1`, ... に分割する
+//!
+//! axum での使い方 (`axum-extra` の `CookieJar` を使う場合):
+//!
+//! ```ignore
+//! use axum::{async_trait, extract::FromRequestParts, http::request::Parts, http::StatusCode};
+//! use axum_extra::extract::CookieJar;
+//! use supabase_rust_auth::cookie_helpers::{
+//!     session_from_cookies, session_to_cookies, stale_cookie_names, CookieOptions,
+//! };
+//! use supabase_rust_auth::Session;
+//!
+//! /// リクエストのクッキーから取り出したセッション (なければ `None`)
+//! struct SupabaseSession(Option<Session>);
+//!
+//! #[async_trait]
+//! impl<S: Send + Sync> FromRequestParts<S> for SupabaseSession {
+//!     type Rejection = StatusCode;
+//!
+//!     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+//!         let jar = CookieJar::from_request_parts(parts, state).await.unwrap();
+//!         let options = CookieOptions::for_project("abcdefghijklmnop");
+//!         let cookies = jar.iter().map(|cookie| (cookie.name(), cookie.value()));
+//!         session_from_cookies(cookies, &options)
+//!             .map(SupabaseSession)
+//!             .map_err(|_| StatusCode::BAD_REQUEST)
+//!     }
+//! }
+//!
+//! // リフレッシュしたセッションを書き戻す (古いチャンクも削除する)
+//! fn write_session(jar: CookieJar, session: &Session) -> (CookieJar, Vec<String>) {
+//!     let options = CookieOptions::for_project("abcdefghijklmnop");
+//!     let cookies = session_to_cookies(session, &options);
+//!     let stale = stale_cookie_names(jar.iter().map(|cookie| cookie.name()), &cookies, &options);
+//!     let headers = cookies
+//!         .iter()
+//!         .map(|(name, value)| options.set_cookie_header(name, value))
+//!         .chain(stale.iter().map(|name| options.remove_cookie_header(name)))
+//!         .collect();
+//!     (jar, headers)
+//! }
+//! ```
+
+use crate::{AccessTokenClaims, AuthError, Session};
+use base64::Engine;
+use std::collections::HashMap;
+use url::Url;
+
+/// `@supabase/ssr` の 1 チャンクの最大文字数
+pub const MAX_CHUNK_SIZE: usize = 3180;
+
+/// base64url 形式の値のプレフィックス
+const BASE64_PREFIX: &str = "base64-";
+
+/// `@supabase/ssr` の既定の `Max-Age` (400 日)
+const DEFAULT_MAX_AGE_SECS: u64 = 400 * 24 * 60 * 60;
+
+/// クッキーの `SameSite` 属性
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SameSite {
+    #[default]
+    Lax,
+    Strict,
+    None,
+}
+
+impl SameSite {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lax => "Lax",
+            Self::Strict => "Strict",
+            Self::None => "None",
+        }
+    }
+}
+
+/// セッションのクッキーの名前と属性
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CookieOptions {
+    /// クッキー名 (分割時は `.0`, `.1`, ... が付く)
+    pub name_prefix: String,
+    /// 1 つのクッキーに入れる最大文字数
+    pub max_chunk: usize,
+    pub secure: bool,
+    pub same_site: SameSite,
+}
+
+impl CookieOptions {
+    /// `sb-<project_ref>-auth-token` のクッキー
+    pub fn for_project(project_ref: &str) -> Self {
+        Self {
+            name_prefix: format!("sb-{}-auth-token", project_ref),
+            max_chunk: MAX_CHUNK_SIZE,
+            secure: true,
+            same_site: SameSite::default(),
+        }
+    }
+
+    /// プロジェクト URL のホスト名の先頭ラベルを ref とする (`@supabase/ssr` と同じ)
+    pub fn for_url(supabase_url: &Url) -> Self {
+        let host = supabase_url.host_str().unwrap_or_default();
+        Self::for_project(host.split('.').next().unwrap_or(host))
+    }
+
+    /// `Set-Cookie` ヘッダーの値 (`Path=/`、`Max-Age` は 400 日)
+    pub fn set_cookie_header(&self, name: &str, value: &str) -> String {
+        self.header(name, value, DEFAULT_MAX_AGE_SECS)
+    }
+
+    /// クッキーを削除する `Set-Cookie` ヘッダーの値
+    pub fn remove_cookie_header(&self, name: &str) -> String {
+        self.header(name, "", 0)
+    }
+
+    fn header(&self, name: &str, value: &str, max_age: u64) -> String {
+        let mut header = format!(
+            "{}={}; Path=/; Max-Age={}; SameSite={}",
+            name,
+            value,
+            max_age,
+            self.same_site.as_str()
+        );
+        if self.secure {
+            header.push_str("; Secure");
+        }
+        header
+    }
+}
+
+/// セッションを `(名前, 値)` のクッキーに変換する
+///
+/// アクセストークンの `exp` を読めれば、`@supabase/ssr` と同じく `expires_at` も含める。
+pub fn session_to_cookies(session: &Session, options: &CookieOptions) -> Vec<(String, String)> {
+    let mut value = serde_json::to_value(session).unwrap_or_default();
+    if let Some(exp) = AccessTokenClaims::decode(&session.access_token)
+        .ok()
+        .and_then(|claims| claims.exp)
+    {
+        value["expires_at"] = serde_json::json!(exp);
+    }
+    let encoded = format!(
+        "{}{}",
+        BASE64_PREFIX,
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(value.to_string())
+    );
+
+    if encoded.len() <= options.max_chunk {
+        return vec![(options.name_prefix.clone(), encoded)];
+    }
+    // base64url と `base64-` は ASCII なので文字数単位で分割できる
+    encoded
+        .as_bytes()
+        .chunks(options.max_chunk.max(1))
+        .enumerate()
+        .map(|(index, chunk)| {
+            (
+                format!("{}.{}", options.name_prefix, index),
+                String::from_utf8_lossy(chunk).into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// リクエストのクッキーからセッションを読み出す (セッションのクッキーがなければ `None`)
+///
+/// 分割されたクッキーは `.0` から連番が途切れるまでつなげる。
+/// 値を解釈できない場合は [`AuthError::InvalidInput`] を返す。
+pub fn session_from_cookies<'a>(
+    cookies: impl IntoIterator<Item = (&'a str, &'a str)>,
+    options: &CookieOptions,
+) -> Result<Option<Session>, AuthError> {
+    let cookies = cookies.into_iter().collect::<HashMap<_, _>>();
+    let value = match cookies.get(options.name_prefix.as_str()) {
+        Some(value) => value.to_string(),
+        None => {
+            let chunks = (0..)
+                .map_while(|index| {
+                    cookies.get(format!("{}.{}", options.name_prefix, index).as_str())
+                })
+                .copied()
+                .collect::<Vec<_>>();
+            if chunks.is_empty() {
+                return Ok(None);
+            }
+            chunks.concat()
+        }
+    };
+    if value.is_empty() {
+        return Ok(None);
+    }
+    decode_session(&value, &options.name_prefix).map(Some)
+}
+
+/// `written` で置き換えられずに残る古いチャンクのクッキー名
+///
+/// セッションが短くなってチャンク数が減った場合や、分割の有無が変わった場合に削除が必要になる。
+pub fn stale_cookie_names<'a>(
+    existing: impl IntoIterator<Item = &'a str>,
+    written: &[(String, String)],
+    options: &CookieOptions,
+) -> Vec<String> {
+    let chunk_prefix = format!("{}.", options.name_prefix);
+    existing
+        .into_iter()
+        .filter(|name| {
+            *name == options.name_prefix
+                || name
+                    .strip_prefix(&chunk_prefix)
+                    .is_some_and(|index| index.parse::<u32>().is_ok())
+        })
+        .filter(|name| !written.iter().any(|(written, _)| written == name))
+        .map(str::to_string)
+        .collect()
+}
+
+fn decode_session(value: &str, name: &str) -> Result<Session, AuthError> {
+    let invalid = |reason: String| AuthError::InvalidInput(format!("cookie {}: {}", name, reason));
+    let json = match value.strip_prefix(BASE64_PREFIX) {
+        Some(encoded) => {
+            let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(encoded.trim_end_matches('='))
+                .map_err(|e| invalid(format!("invalid base64url: {}", e)))?;
+            String::from_utf8(bytes).map_err(|e| invalid(e.to_string()))?
+        }
+        // 古い形式: JSON そのもの (クッキーの書き込み側で URL エンコードされている場合がある)
+        None if value.starts_with('{') => value.to_string(),
+        None => urlencoding::decode(value)
+            .map_err(|e| invalid(e.to_string()))?
+            .into_owned(),
+    };
+    serde_json::from_str(&json).map_err(|e| invalid(format!("invalid session: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // @supabase/ssr (createServerClient) が書き込んだクッキーの値
+    const SSR_COOKIE: &str = "base64-eyJhY2Nlc3NfdG9rZW4iOiJleUpoYkdjaU9pSklVekkxTmlJc0luUjVjQ0k2SWtwWFZDSjkuZXlKemRXSWlPaUk0WmpObE1tUTJZeTB4WWpSaExUUmpOV1F0T1dVNFppMHhZVEppTTJNMFpEVmxObVlpTENKbGVIQWlPakUzTXpBd01ETTJNREFzSW5KdmJHVWlPaUpoZFhSb1pXNTBhV05oZEdWa0luMC5jMmxuYm1GMGRYSmwiLCJ0b2tlbl90eXBlIjoiYmVhcmVyIiwiZXhwaXJlc19pbiI6MzYwMCwiZXhwaXJlc19hdCI6MTczMDAwMzYwMCwicmVmcmVzaF90b2tlbiI6InYxclQwazNuIiwidXNlciI6eyJpZCI6IjhmM2UyZDZjLTFiNGEtNGM1ZC05ZThmLTFhMmIzYzRkNWU2ZiIsImF1ZCI6ImF1dGhlbnRpY2F0ZWQiLCJyb2xlIjoiYXV0aGVudGljYXRlZCIsImVtYWlsIjoiYWRhQGV4YW1wbGUuY29tIiwiZW1haWxfY29uZmlybWVkX2F0IjoiMjAyNC0xMC0yN1QwNDowMDowMC4wMDAwMDBaIiwicGhvbmUiOiIiLCJjb25maXJtZWRfYXQiOiIyMDI0LTEwLTI3VDA0OjAwOjAwLjAwMDAwMFoiLCJsYXN0X3NpZ25faW5fYXQiOiIyMDI0LTEwLTI3VDA0OjMzOjIwLjAwMDAwMFoiLCJhcHBfbWV0YWRhdGEiOnsicHJvdmlkZXIiOiJlbWFpbCIsInByb3ZpZGVycyI6WyJlbWFpbCJdfSwidXNlcl9tZXRhZGF0YSI6eyJuYW1lIjoiQWRhIOKckyJ9LCJpZGVudGl0aWVzIjpbXSwiY3JlYXRlZF9hdCI6IjIwMjQtMTAtMjdUMDQ6MDA6MDAuMDAwMDAwWiIsInVwZGF0ZWRfYXQiOiIyMDI0LTEwLTI3VDA0OjMzOjIwLjAwMDAwMFoiLCJpc19hbm9ueW1vdXMiOmZhbHNlfX0";
+
+    fn options() -> CookieOptions {
+        CookieOptions::for_project("abcdefghijklmnop")
+    }
+
+    fn assert_fixture_session(session: &Session) {
+        assert_eq!(session.refresh_token, "v1rT0k3n");
+        assert_eq!(session.expires_in, 3600);
+        assert_eq!(session.user.id, "8f3e2d6c-1b4a-4c5d-9e8f-1a2b3c4d5e6f");
+        assert_eq!(session.user.email.as_deref(), Some("ada@example.com"));
+        assert_eq!(session.user.user_metadata["name"], "Ada ✓");
+        assert_eq!(session.claims().unwrap().exp, Some(1730003600));
+    }
+
+    fn read(cookies: &[(String, String)]) -> Result<Option<Session>, AuthError> {
+        session_from_cookies(
+            cookies
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+            &options(),
+        )
+    }
+
+    #[test]
+    fn test_reads_ssr_base64_cookie() {
+        let session = session_from_cookies(
+            [
+                ("other", "1"),
+                ("sb-abcdefghijklmnop-auth-token", SSR_COOKIE),
+            ],
+            &options(),
+        )
+        .unwrap()
+        .unwrap();
+        assert_fixture_session(&session);
+    }
+
+    #[test]
+    fn test_reads_chunked_cookie() {
+        let (first, second) = SSR_COOKIE.split_at(600);
+        let session = session_from_cookies(
+            [
+                ("sb-abcdefghijklmnop-auth-token.1", second),
+                ("sb-abcdefghijklmnop-auth-token.0", first),
+            ],
+            &options(),
+        )
+        .unwrap()
+        .unwrap();
+        assert_fixture_session(&session);
+    }
+
+    #[test]
+    fn test_reads_legacy_percent_encoded_json() {
+        let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(&SSR_COOKIE[BASE64_PREFIX.len()..])
+            .unwrap();
+        let encoded = urlencoding::encode(std::str::from_utf8(&json).unwrap()).into_owned();
+        let session = read(&[("sb-abcdefghijklmnop-auth-token".to_string(), encoded)])
+            .unwrap()
+            .unwrap();
+        assert_fixture_session(&session);
+    }
+
+    #[test]
+    fn test_missing_cookie_is_none_and_garbage_is_error() {
+        assert!(read(&[("other".to_string(), "1".to_string())])
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            read(&[(
+                "sb-abcdefghijklmnop-auth-token".to_string(),
+                "base64-!!!".to_string()
+            )]),
+            Err(AuthError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_round_trip_and_chunking() {
+        let session = read(&[(
+            "sb-abcdefghijklmnop-auth-token".to_string(),
+            SSR_COOKIE.to_string(),
+        )])
+        .unwrap()
+        .unwrap();
+
+        let cookies = session_to_cookies(&session, &options());
+        assert_eq!(cookies.len(), 1);
+        assert_eq!(cookies[0].0, "sb-abcdefghijklmnop-auth-token");
+        assert!(cookies[0].1.starts_with(BASE64_PREFIX));
+        assert_fixture_session(&read(&cookies).unwrap().unwrap());
+
+        let small = CookieOptions {
+            max_chunk: 400,
+            ..options()
+        };
+        let chunks = session_to_cookies(&session, &small);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|(_, value)| value.len() <= 400));
+        assert_eq!(chunks[0].0, "sb-abcdefghijklmnop-auth-token.0");
+        assert_fixture_session(&read(&chunks).unwrap().unwrap());
+
+        // 分割しない書き込みでは古いチャンクを削除する
+        let stale = stale_cookie_names(
+            chunks
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .chain(["other"]),
+            &cookies,
+            &options(),
+        );
+        assert_eq!(stale.len(), chunks.len());
+        assert!(!stale.contains(&"other".to_string()));
+    }
+
+    #[test]
+    fn test_options_and_headers() {
+        let options =
+            CookieOptions::for_url(&Url::parse("https://abcdefghijklmnop.supabase.co").unwrap());
+        assert_eq!(options.name_prefix, "sb-abcdefghijklmnop-auth-token");
+        assert_eq!(
+            options.set_cookie_header("sb-abcdefghijklmnop-auth-token", "v"),
+            "sb-abcdefghijklmnop-auth-token=v; Path=/; Max-Age=34560000; SameSite=Lax; Secure"
+        );
+        assert_eq!(
+            options.remove_cookie_header("sb-abcdefghijklmnop-auth-token.1"),
+            "sb-abcdefghijklmnop-auth-token.1=; Path=/; Max-Age=0; SameSite=Lax; Secure"
+        );
+    }
+}
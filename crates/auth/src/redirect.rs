@@ -0,0 +1,239 @@
+//! `redirect_to` の許可リストによる検証 (`AuthOptions::allowed_redirect_hosts`)
+//!
+//! GoTrue は許可されていない `redirect_to` をエラーにせずサイト URL に置き換えるため、
+//! 送信前にスキームとホストを許可リストと照合する。
+//!
+//! 許可リストの書式:
+//!
+//! * `example.com` - `https://example.com` (ポート 443) のみ
+//! * `*.example.com` - `example.com` のサブドメイン (`example.com` 自体は含まない)
+//! * `http://localhost:3000` - スキームとポートを指定
+//! * `myapp://callback` - カスタムスキーム (モバイルアプリのディープリンクなど)
+//!
+//! ホストは大文字小文字を区別せず、ポートを省略した場合はスキームのデフォルトポートのみ許可する。
+//! ユーザー情報 (`user@host`)、バックスラッシュ、空白・制御文字、ホスト部分の `%` を含む URL は
+//! パーサーによって解釈が分かれるため、許可リストにかかわらず拒否する。
+
+use crate::AuthError;
+use url::Url;
+
+/// `redirect_to` を許可リストと照合する (許可リストが空なら何もしない)
+pub(crate) fn validate_redirect(
+    redirect_to: &str,
+    allowed_hosts: &[String],
+) -> Result<(), AuthError> {
+    if allowed_hosts.is_empty() {
+        return Ok(());
+    }
+    let invalid = |reason: String| AuthError::InvalidRedirectUrl {
+        url: redirect_to.to_string(),
+        reason,
+    };
+
+    if redirect_to
+        .chars()
+        .any(|c| c == '\\' || c.is_whitespace() || c.is_control())
+    {
+        return Err(invalid(
+            "contains a backslash, whitespace or control character".to_string(),
+        ));
+    }
+    let url =
+        Url::parse(redirect_to).map_err(|e| invalid(format!("not an absolute URL ({})", e)))?;
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(invalid("user info is not allowed".to_string()));
+    }
+    if raw_authority(redirect_to).contains('%') {
+        return Err(invalid("percent-encoded host is not allowed".to_string()));
+    }
+    let Some(host) = url.host_str() else {
+        return Err(invalid("has no host".to_string()));
+    };
+
+    let patterns = allowed_hosts
+        .iter()
+        .map(|entry| HostPattern::parse(entry))
+        .collect::<Result<Vec<_>, _>>()?;
+    if patterns.iter().any(|pattern| pattern.matches(&url)) {
+        return Ok(());
+    }
+    let origin = match url.port() {
+        Some(port) => format!("{}://{}:{}", url.scheme(), host, port),
+        None => format!("{}://{}", url.scheme(), host),
+    };
+    Err(invalid(format!(
+        "{} is not in allowed_redirect_hosts",
+        origin
+    )))
+}
+
+// `scheme://` の後ろからパス・クエリ・フラグメントの手前まで
+fn raw_authority(url: &str) -> &str {
+    let Some((_, rest)) = url.split_once("://") else {
+        return "";
+    };
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    &rest[..end]
+}
+
+/// 許可リストの1項目
+#[derive(Debug)]
+struct HostPattern {
+    scheme: String,
+    /// `*.` で始まる場合はそれを除いたホスト
+    host: String,
+    wildcard: bool,
+    port: Option<u16>,
+}
+
+impl HostPattern {
+    fn parse(entry: &str) -> Result<Self, AuthError> {
+        let invalid =
+            || AuthError::InvalidInput(format!("invalid allowed_redirect_hosts entry {:?}", entry));
+        let entry = entry.trim();
+        let (scheme, rest) = entry.split_once("://").unwrap_or(("https", entry));
+        let (wildcard, host) = match rest.strip_prefix("*.") {
+            Some(host) => (true, host),
+            None => (false, rest),
+        };
+        if host.is_empty() || host.contains(['/', '?', '#', '@', '*', '\\', '%']) {
+            return Err(invalid());
+        }
+        // 照合する URL と同じ規則で正規化する (小文字化、IDN、デフォルトポート)
+        let normalized = Url::parse(&format!("{}://{}", scheme, host)).map_err(|_| invalid())?;
+        let host = normalized
+            .host_str()
+            .ok_or_else(invalid)?
+            .to_ascii_lowercase();
+        Ok(Self {
+            scheme: normalized.scheme().to_string(),
+            host,
+            wildcard,
+            port: normalized.port_or_known_default(),
+        })
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+        let host_matches = if self.wildcard {
+            host.strip_suffix(self.host.as_str())
+                .and_then(|subdomain| subdomain.strip_suffix('.'))
+                .is_some_and(|subdomain| !subdomain.is_empty())
+        } else {
+            host == self.host
+        };
+        host_matches && url.scheme() == self.scheme && url.port_or_known_default() == self.port
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed() -> Vec<String> {
+        [
+            "good.com",
+            "*.example.com",
+            "http://localhost:3000",
+            "myapp://callback",
+        ]
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect()
+    }
+
+    fn check(url: &str) -> Result<(), String> {
+        validate_redirect(url, &allowed()).map_err(|e| match e {
+            AuthError::InvalidRedirectUrl { reason, .. } => reason,
+            other => panic!("unexpected error {:?}", other),
+        })
+    }
+
+    #[test]
+    fn test_allowed_urls() {
+        for url in [
+            "https://good.com",
+            "https://good.com/auth/callback?next=/home#top",
+            "https://GOOD.com/",
+            "HTTPS://Good.Com:443/path",
+            "https://app.example.com/callback",
+            "https://a.b.EXAMPLE.com",
+            "http://localhost:3000/callback",
+            "myapp://callback",
+            "myapp://callback/deep/link",
+        ] {
+            assert_eq!(check(url), Ok(()), "{} should be allowed", url);
+        }
+    }
+
+    #[test]
+    fn test_rejected_urls() {
+        for url in [
+            // ホスト・スキーム・ポートの不一致
+            "https://evil.com",
+            "http://good.com",
+            "https://good.com:8443",
+            "https://good.com.evil.com",
+            "https://evilgood.com",
+            "https://example.com",
+            "https://evilexample.com",
+            "https://example.com.evil.com",
+            "http://localhost:3001",
+            "https://localhost:3000",
+            "otherapp://callback",
+            // パーサーによって解釈が分かれる URL
+            "https://evil.com\\@good.com",
+            "https://good.com\\@evil.com",
+            "https://good.com@evil.com",
+            "https://good.com:pw@evil.com",
+            "https://user@good.com",
+            "https://good.com%2F@evil.com",
+            "https://good%2Ecom",
+            "https://go\nod.com",
+            " https://good.com",
+            // 相対 URL・ホストなし
+            "/auth/callback",
+            "//good.com/callback",
+            "javascript:alert(1)",
+            "",
+        ] {
+            assert!(check(url).is_err(), "{} should be rejected", url);
+        }
+    }
+
+    #[test]
+    fn test_error_carries_url_and_reason() {
+        match validate_redirect("https://good.com:8443/x", &allowed()) {
+            Err(AuthError::InvalidRedirectUrl { url, reason }) => {
+                assert_eq!(url, "https://good.com:8443/x");
+                assert_eq!(
+                    reason,
+                    "https://good.com:8443 is not in allowed_redirect_hosts"
+                );
+            }
+            other => panic!("expected InvalidRedirectUrl, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_empty_list_allows_everything() {
+        assert!(validate_redirect("https://good.com@evil.com", &[]).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_entries_are_reported() {
+        for entry in ["", "*.", "good.com/path", "user@good.com", "*.*.good.com"] {
+            assert!(
+                matches!(
+                    validate_redirect("https://good.com", &[entry.to_string()]),
+                    Err(AuthError::InvalidInput(_))
+                ),
+                "{:?} should be an invalid entry",
+                entry
+            );
+        }
+    }
+}
@@ -0,0 +1,653 @@
+//! セッションの永続化
+//!
+//! [`Auth::with_session_store`](crate::Auth::with_session_store) でストアを設定すると、
+//! セッションの保存・削除のたびにストアへ書き込み、起動時に
+//! [`Auth::restore_session`](crate::Auth::restore_session) で読み戻す。
+//! 壊れたファイルは `<ファイル名>.corrupt` に退避し、セッションなしとして扱う。
+//!
+//! ファイルへの書き込みは同じディレクトリの一時ファイルに書いて fsync した後に rename で
+//! 置き換え、Unix ではディレクトリも fsync する。`Auth` は [`DebouncedStore`] を挟んで保存する
+//! ため、短い間隔で続くセッションの更新は 1 回の書き込みにまとまる。
+//!
+//! [`FileSessionStore`] のファイル形式: `{"checksum":"sha256:<hex>","session":{...}}`
+//! (チェックサムは `session` の JSON のバイト列に対して計算する)。チェックサムのない以前の
+//! 形式 (セッションの JSON のみ) も読み込める。
+//!
+//! [`EncryptedFileStore`] のファイル形式 (バージョン 1):
+//! `SBSS` (4 バイト) | バージョン (1 バイト) | ノンス (12 バイト) | ChaCha20-Poly1305 の暗号文
+//! ヘッダー (先頭 5 バイト) は AAD として認証する。
+
+use crate::{AuthError, Session};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const MAGIC: &[u8; 4] = b"SBSS";
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 1;
+const NONCE_LEN: usize = 12;
+const CHECKSUM_PREFIX: &str = "sha256:";
+
+/// `Auth` がセッションの保存をまとめる間隔の既定値 ([`Auth::with_session_save_debounce`](crate::Auth::with_session_save_debounce))
+pub const DEFAULT_SESSION_SAVE_DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// セッションの保存先
+pub trait SessionStore: Send + Sync {
+    /// 保存されているセッションを読み込む (なければ None)
+    ///
+    /// 内容を解釈できない場合は [`AuthError::SessionStoreCorrupted`] を返す。
+    fn load(&self) -> Result<Option<Session>, AuthError>;
+
+    /// セッションを保存する (None なら削除)
+    fn save(&self, session: Option<&Session>) -> Result<(), AuthError>;
+}
+
+/// 平文の JSON ファイルに保存するストア
+///
+/// チェックサムが一致しないファイル (書き込み途中で切れたファイルなど) は
+/// [`AuthError::SessionStoreCorrupted`] になる。
+#[derive(Debug, Clone)]
+pub struct FileSessionStore {
+    path: PathBuf,
+}
+
+impl FileSessionStore {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn load(&self) -> Result<Option<Session>, AuthError> {
+        let Some(contents) = read(&self.path)? else {
+            return Ok(None);
+        };
+        let session = match serde_json::from_slice::<ChecksummedSession>(&contents) {
+            Ok(file) if file.checksum != checksum(file.session.get().as_bytes()) => {
+                return Err(corrupted(&self.path, "checksum mismatch"));
+            }
+            Ok(file) => serde_json::from_str(file.session.get()),
+            // チェックサムのない以前の形式
+            Err(e) => serde_json::from_slice(&contents).map_err(|_| e),
+        };
+        session.map(Some).map_err(|e| corrupted(&self.path, e))
+    }
+
+    fn save(&self, session: Option<&Session>) -> Result<(), AuthError> {
+        let Some(session) = session else {
+            return remove(&self.path);
+        };
+        let session = RawValue::from_string(serde_json::to_string(session)?)?;
+        let file = ChecksummedSession {
+            checksum: checksum(session.get().as_bytes()),
+            session: &session,
+        };
+        write(&self.path, &serde_json::to_vec(&file)?)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChecksummedSession<'a> {
+    checksum: String,
+    #[serde(borrow)]
+    session: &'a RawValue,
+}
+
+fn checksum(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    let hex = digest
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    format!("{}{}", CHECKSUM_PREFIX, hex)
+}
+
+/// 暗号化キーの取得元 (OS のキーチェーンなど)
+///
+/// キーは読み込み・保存のたびに取得する。
+pub trait KeyProvider: Send + Sync {
+    fn key(&self) -> Result<[u8; 32], AuthError>;
+}
+
+impl KeyProvider for [u8; 32] {
+    fn key(&self) -> Result<[u8; 32], AuthError> {
+        Ok(*self)
+    }
+}
+
+/// ChaCha20-Poly1305 で暗号化したファイルに保存するストア
+///
+/// 書き込みのたびにランダムなノンスを使う。キーが違う場合や改ざんされた場合は
+/// [`AuthError::SessionStoreCorrupted`] になる。
+#[derive(Clone)]
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    key_provider: Arc<dyn KeyProvider>,
+}
+
+impl EncryptedFileStore {
+    pub fn new(path: impl AsRef<Path>, key: [u8; 32]) -> Self {
+        Self::with_key_provider(path, Arc::new(key))
+    }
+
+    pub fn with_key_provider(path: impl AsRef<Path>, key_provider: Arc<dyn KeyProvider>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            key_provider,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn cipher(&self) -> Result<ChaCha20Poly1305, AuthError> {
+        let key = self.key_provider.key()?;
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    fn decrypt(cipher: &ChaCha20Poly1305, contents: &[u8]) -> Result<Vec<u8>, String> {
+        if contents.len() < HEADER_LEN + NONCE_LEN || &contents[..MAGIC.len()] != MAGIC {
+            return Err("not an encrypted session file".to_string());
+        }
+        let (header, rest) = contents.split_at(HEADER_LEN);
+        if header[MAGIC.len()] != FORMAT_VERSION {
+            return Err(format!(
+                "unsupported format version {}",
+                header[MAGIC.len()]
+            ));
+        }
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| "decryption failed (wrong key or tampered file)".to_string())
+    }
+}
+
+impl std::fmt::Debug for EncryptedFileStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedFileStore")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionStore for EncryptedFileStore {
+    fn load(&self) -> Result<Option<Session>, AuthError> {
+        let Some(contents) = read(&self.path)? else {
+            return Ok(None);
+        };
+        // キーが取得できない場合はファイルを退避しない
+        let cipher = self.cipher()?;
+        let plaintext = Self::decrypt(&cipher, &contents).map_err(|e| corrupted(&self.path, e))?;
+        serde_json::from_slice(&plaintext)
+            .map(Some)
+            .map_err(|e| corrupted(&self.path, e))
+    }
+
+    fn save(&self, session: Option<&Session>) -> Result<(), AuthError> {
+        let Some(session) = session else {
+            return remove(&self.path);
+        };
+        let mut header = MAGIC.to_vec();
+        header.push(FORMAT_VERSION);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &serde_json::to_vec(session)?,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| std::io::Error::other("session encryption failed"))?;
+
+        let mut contents = header;
+        contents.extend_from_slice(&nonce);
+        contents.extend_from_slice(&ciphertext);
+        write(&self.path, &contents)
+    }
+}
+
+/// 短い間隔で続く保存を 1 回にまとめるストア
+///
+/// 静かな状態で保存されたセッションは `delay` 後に書き込み、それまでに保存されたセッションは
+/// 待っているセッションを置き換える。`load` は書き込み待ちのセッションがあればそれを返す。
+/// サインアウト (`save(None)`) は待たずにすぐ削除する。Tokio のランタイム外や `delay` が 0 の
+/// 場合もすぐ書き込む。待っているセッションは `flush` か、このストアを破棄したときに書き込む。
+/// 遅れて書き込むときの失敗はログに出すだけで、`save` の戻り値には現れない。
+pub struct DebouncedStore {
+    shared: Arc<DebounceShared>,
+    delay: Duration,
+}
+
+struct DebounceShared {
+    inner: Arc<dyn SessionStore>,
+    // 書き込み待ちのセッション (Some(None) はセッションの削除)
+    pending: Mutex<Option<Option<Session>>>,
+}
+
+impl DebounceShared {
+    // 書き込み待ちのセッションを書き込む (ロック中に書き込むため順序が入れ替わらない)
+    fn flush(&self) -> Result<(), AuthError> {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.take() {
+            Some(session) => self.inner.save(session.as_ref()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl DebouncedStore {
+    pub fn new(inner: Arc<dyn SessionStore>, delay: Duration) -> Self {
+        Self {
+            shared: Arc::new(DebounceShared {
+                inner,
+                pending: Mutex::new(None),
+            }),
+            delay,
+        }
+    }
+
+    /// 包んでいるストア
+    pub fn inner(&self) -> &Arc<dyn SessionStore> {
+        &self.shared.inner
+    }
+
+    /// 書き込みをまとめる間隔
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// 書き込み待ちのセッションをすぐに書き込む (終了前などに呼ぶ)
+    pub fn flush(&self) -> Result<(), AuthError> {
+        self.shared.flush()
+    }
+}
+
+impl std::fmt::Debug for DebouncedStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DebouncedStore")
+            .field("delay", &self.delay)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionStore for DebouncedStore {
+    fn load(&self) -> Result<Option<Session>, AuthError> {
+        if let Some(session) = self.shared.pending.lock().unwrap().as_ref() {
+            return Ok(session.clone());
+        }
+        self.shared.inner.load()
+    }
+
+    fn save(&self, session: Option<&Session>) -> Result<(), AuthError> {
+        let mut pending = self.shared.pending.lock().unwrap();
+        let runtime = match tokio::runtime::Handle::try_current() {
+            Ok(runtime) if session.is_some() && !self.delay.is_zero() => runtime,
+            _ => {
+                *pending = None;
+                return self.shared.inner.save(session);
+            }
+        };
+
+        let scheduled = pending.is_some();
+        *pending = Some(session.cloned());
+        if !scheduled {
+            let shared = self.shared.clone();
+            let delay = self.delay;
+            runtime.spawn(async move {
+                tokio::time::sleep(delay).await;
+                if let Err(e) = shared.flush() {
+                    log::warn!("Failed to persist session: {}", e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Drop for DebouncedStore {
+    fn drop(&mut self) {
+        if let Err(e) = self.shared.flush() {
+            log::warn!("Failed to persist session: {}", e);
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<Option<Vec<u8>>, AuthError> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// 一時ファイルに書いてから置き換える (所有者のみ読み書きできる権限で作成)
+fn write(path: &Path, contents: &[u8]) -> Result<(), AuthError> {
+    let tmp = sibling(path, "tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    sync_parent_dir(path)
+}
+
+// rename を永続化するためにディレクトリを fsync する (Windows ではディレクトリを開けないため省く)
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<(), AuthError> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    fs::File::open(parent)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> Result<(), AuthError> {
+    Ok(())
+}
+
+fn remove(path: &Path) -> Result<(), AuthError> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
+
+// 壊れたファイルを退避してエラーにする
+fn corrupted(path: &Path, reason: impl std::fmt::Display) -> AuthError {
+    let aside = sibling(path, "corrupt");
+    let moved = match fs::rename(path, &aside) {
+        Ok(()) => format!(", moved to {}", aside.display()),
+        Err(e) => format!(", could not move it aside: {}", e),
+    };
+    AuthError::SessionStoreCorrupted(format!("{}: {}{}", path.display(), reason, moved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    fn session() -> Session {
+        serde_json::from_value(serde_json::json!({
+            "access_token": "secret-access-token",
+            "refresh_token": "secret-refresh-token",
+            "expires_in": 3600,
+            "token_type": "bearer",
+            "user": {
+                "id": "user-1",
+                "email": "test@example.com",
+                "phone": null,
+                "app_metadata": {},
+                "user_metadata": {},
+                "created_at": "2024-01-01T00:00:00Z",
+                "updated_at": "2024-01-01T00:00:00Z"
+            }
+        }))
+        .unwrap()
+    }
+
+    fn to_json(session: Option<Session>) -> serde_json::Value {
+        serde_json::to_value(session).unwrap()
+    }
+
+    #[test]
+    fn test_encrypted_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.bin");
+        let store = EncryptedFileStore::new(&path, KEY);
+        assert!(store.load().unwrap().is_none());
+
+        store.save(Some(&session())).unwrap();
+        let contents = fs::read(&path).unwrap();
+        assert_eq!(&contents[..5], b"SBSS\x01");
+        assert!(!String::from_utf8_lossy(&contents).contains("secret-refresh-token"));
+        assert_eq!(to_json(store.load().unwrap()), to_json(Some(session())));
+
+        // 書き込みのたびにノンスが変わる
+        store.save(Some(&session())).unwrap();
+        assert_ne!(fs::read(&path).unwrap(), contents);
+
+        store.save(None).unwrap();
+        assert!(!path.exists());
+        assert!(store.load().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_encrypted_store_detects_tampering_and_wrong_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.bin");
+        let aside = dir.path().join("session.bin.corrupt");
+
+        let store = EncryptedFileStore::new(&path, KEY);
+        store.save(Some(&session())).unwrap();
+        let mut contents = fs::read(&path).unwrap();
+        let last = contents.len() - 1;
+        contents[last] ^= 1;
+        fs::write(&path, &contents).unwrap();
+
+        assert!(matches!(
+            store.load(),
+            Err(AuthError::SessionStoreCorrupted(_))
+        ));
+        assert!(!path.exists());
+        assert_eq!(fs::read(&aside).unwrap(), contents);
+        assert!(store.load().unwrap().is_none());
+
+        store.save(Some(&session())).unwrap();
+        let wrong_key = EncryptedFileStore::new(&path, [8; 32]);
+        assert!(matches!(
+            wrong_key.load(),
+            Err(AuthError::SessionStoreCorrupted(_))
+        ));
+        assert!(!path.exists());
+
+        // 書き換えたヘッダー (未知のバージョン)
+        store.save(Some(&session())).unwrap();
+        let mut contents = fs::read(&path).unwrap();
+        contents[4] = 2;
+        fs::write(&path, &contents).unwrap();
+        match store.load() {
+            Err(AuthError::SessionStoreCorrupted(message)) => {
+                assert!(message.contains("version 2"), "{}", message)
+            }
+            other => panic!("expected SessionStoreCorrupted, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_file_store_round_trip_and_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        let store = FileSessionStore::new(&path);
+
+        store.save(Some(&session())).unwrap();
+        assert_eq!(to_json(store.load().unwrap()), to_json(Some(session())));
+
+        fs::write(&path, b"{ not json").unwrap();
+        assert!(matches!(
+            store.load(),
+            Err(AuthError::SessionStoreCorrupted(_))
+        ));
+        assert!(dir.path().join("session.json.corrupt").exists());
+        assert!(store.load().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_auth_persists_and_restores_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.bin");
+        let auth = || {
+            crate::Auth::new(
+                "http://localhost",
+                "anon",
+                reqwest::Client::new(),
+                Default::default(),
+            )
+            .with_session_store(Arc::new(EncryptedFileStore::new(&path, KEY)))
+        };
+
+        auth().store_session(Some(session()));
+        let restored = auth();
+        assert_eq!(
+            to_json(restored.restore_session().unwrap()),
+            to_json(Some(session()))
+        );
+        assert_eq!(to_json(restored.get_session()), to_json(Some(session())));
+
+        // 壊れたファイルはセッションなしとして起動する
+        fs::write(&path, b"garbage").unwrap();
+        let restored = auth();
+        assert!(restored.restore_session().unwrap().is_none());
+        assert!(restored.get_session().is_none());
+        assert!(dir.path().join("session.bin.corrupt").exists());
+    }
+
+    // 保存の回数と最後に保存したセッションを記録するストア
+    #[derive(Default)]
+    struct CountingStore {
+        saves: Mutex<Vec<Option<String>>>,
+    }
+
+    impl SessionStore for CountingStore {
+        fn load(&self) -> Result<Option<Session>, AuthError> {
+            Ok(None)
+        }
+
+        fn save(&self, session: Option<&Session>) -> Result<(), AuthError> {
+            let token = session.map(|session| session.access_token.clone());
+            self.saves.lock().unwrap().push(token);
+            Ok(())
+        }
+    }
+
+    fn session_with_token(token: &str) -> Session {
+        Session {
+            access_token: token.to_string(),
+            ..session()
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rapid_updates_collapse_into_one_write() {
+        let counting = Arc::new(CountingStore::default());
+        let auth = crate::Auth::new(
+            "http://localhost",
+            "anon",
+            reqwest::Client::new(),
+            Default::default(),
+        )
+        .with_session_store(counting.clone());
+
+        for i in 0..20 {
+            auth.store_session(Some(session_with_token(&format!("token-{}", i))));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(counting.saves.lock().unwrap().is_empty());
+        // 書き込み待ちのセッションも読み込める
+        let pending = auth.session_store.as_ref().unwrap().load().unwrap();
+        assert_eq!(pending.unwrap().access_token, "token-19");
+
+        tokio::time::sleep(DEFAULT_SESSION_SAVE_DEBOUNCE).await;
+        assert_eq!(
+            *counting.saves.lock().unwrap(),
+            vec![Some("token-19".to_string())]
+        );
+
+        // サインアウトはすぐに削除する
+        auth.store_session(Some(session_with_token("token-20")));
+        auth.store_session(None);
+        assert_eq!(counting.saves.lock().unwrap().len(), 2);
+        assert_eq!(counting.saves.lock().unwrap()[1], None);
+        tokio::time::sleep(DEFAULT_SESSION_SAVE_DEBOUNCE * 2).await;
+        assert_eq!(counting.saves.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_debounced_store_flushes_on_drop() {
+        let counting = Arc::new(CountingStore::default());
+        let store = DebouncedStore::new(counting.clone(), Duration::from_secs(60));
+        store.save(Some(&session_with_token("a"))).unwrap();
+        store.save(Some(&session_with_token("b"))).unwrap();
+        drop(store);
+        assert_eq!(*counting.saves.lock().unwrap(), vec![Some("b".to_string())]);
+    }
+
+    #[test]
+    fn test_file_store_detects_torn_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        let aside = dir.path().join("session.json.corrupt");
+        let store = FileSessionStore::new(&path);
+
+        store.save(Some(&session())).unwrap();
+        let contents = fs::read(&path).unwrap();
+        assert!(String::from_utf8_lossy(&contents).starts_with("{\"checksum\":\"sha256:"));
+        assert!(!dir.path().join("session.json.tmp").exists());
+
+        // 書き込みの途中で切れたファイル
+        fs::write(&path, &contents[..contents.len() / 2]).unwrap();
+        assert!(matches!(
+            store.load(),
+            Err(AuthError::SessionStoreCorrupted(_))
+        ));
+        assert_eq!(fs::read(&aside).unwrap(), &contents[..contents.len() / 2]);
+        assert!(store.load().unwrap().is_none());
+
+        // JSON としては正しいがチェックサムが一致しない
+        let tampered = String::from_utf8(contents)
+            .unwrap()
+            .replace("secret-access-token", "secret-access-tokem");
+        fs::write(&path, tampered).unwrap();
+        match store.load() {
+            Err(AuthError::SessionStoreCorrupted(message)) => {
+                assert!(message.contains("checksum mismatch"), "{}", message)
+            }
+            other => panic!("expected SessionStoreCorrupted, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_file_store_reads_files_without_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        fs::write(&path, serde_json::to_vec(&session()).unwrap()).unwrap();
+        let store = FileSessionStore::new(&path);
+        assert_eq!(to_json(store.load().unwrap()), to_json(Some(session())));
+    }
+}
@@ -0,0 +1,94 @@
+// crates/client/examples/e2e_auth_postgrest.rs
+//
+// Sign-up, then RLS-protected insert/select on `e2e_notes` against a local stack.
+// cargo xtask e2e auth_postgrest
+
+#[path = "e2e_common/mod.rs"]
+mod common;
+
+use anyhow::{Context, Result};
+use common::{ensure_eq, Cleanup, E2eEnv};
+use serde::Deserialize;
+use serde_json::json;
+use std::process::ExitCode;
+
+const TABLE: &str = "e2e_notes";
+
+#[derive(Debug, Deserialize)]
+struct Note {
+    id: String,
+    user_id: String,
+    body: String,
+}
+
+async fn scenario(env: &E2eEnv, cleanup: &mut Cleanup) -> Result<()> {
+    let suffix = common::unique_suffix();
+    let owner = common::sign_up(env, cleanup, "owner", &suffix).await?;
+    let other = common::sign_up(env, cleanup, "other", &suffix).await?;
+
+    let body = format!("note {}", suffix);
+    let inserted = env
+        .table_as(TABLE, &owner)?
+        .insert(json!({ "body": body }))
+        .await
+        .with_context(|| format!("POST {}/rest/v1/{} as {}", env.url, TABLE, owner.user.id))?;
+    let inserted: Vec<Note> =
+        serde_json::from_value(inserted).context("decoding the inserted row")?;
+    let note = inserted.first().context("insert returned no rows")?;
+    cleanup.rows.push((TABLE.to_string(), note.id.clone()));
+    ensure_eq(
+        note.user_id.as_str(),
+        owner.user.id.as_str(),
+        "user_id default",
+    )?;
+
+    let own: Vec<Note> = env
+        .table_as(TABLE, &owner)?
+        .eq("body", &body)
+        .execute()
+        .await
+        .with_context(|| {
+            format!(
+                "GET {}/rest/v1/{}?body=eq.{} as owner",
+                env.url, TABLE, body
+            )
+        })?;
+    ensure_eq(own.len(), 1, "rows visible to the owner")?;
+    ensure_eq(own[0].body.as_str(), body.as_str(), "selected body")?;
+
+    let foreign: Vec<Note> = env
+        .table_as(TABLE, &other)?
+        .eq("id", &note.id)
+        .execute()
+        .await
+        .with_context(|| {
+            format!(
+                "GET {}/rest/v1/{}?id=eq.{} as other",
+                env.url, TABLE, note.id
+            )
+        })?;
+    ensure_eq(foreign.len(), 0, "rows visible to another user")?;
+
+    let forged = env
+        .table_as(TABLE, &other)?
+        .insert(json!({ "body": body, "user_id": owner.user.id }))
+        .await;
+    anyhow::ensure!(
+        forged.is_err(),
+        "RLS allowed inserting a row for another user: {:?}",
+        forged
+    );
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let env = match E2eEnv::from_env() {
+        Ok(env) => env,
+        Err(error) => return common::finish("auth_postgrest", Err(error), true),
+    };
+    let mut cleanup = Cleanup::default();
+    let result = scenario(&env, &mut cleanup).await;
+    let cleaned_up = cleanup.run(&env).await;
+    common::finish("auth_postgrest", result, cleaned_up)
+}
@@ -0,0 +1,192 @@
+// crates/client/examples/e2e_common/mod.rs
+//
+// Shared setup for the end-to-end scenarios (`cargo xtask e2e`).
+// Each scenario records what it creates in `Cleanup`, which runs whether the
+// scenario passed or failed, so reruns against the same stack start clean.
+
+#![allow(dead_code)]
+
+use anyhow::{Context, Result};
+use std::process::ExitCode;
+use std::time::{SystemTime, UNIX_EPOCH};
+use supabase_rust_client::auth::{AdminAuth, Auth, AuthOptions, Session};
+use supabase_rust_client::postgrest::PostgrestClient;
+use supabase_rust_client::storage::StorageClient;
+
+/// Connection details for a local stack started with `supabase start`.
+pub struct E2eEnv {
+    pub url: String,
+    pub anon_key: String,
+    pub service_key: String,
+    pub http: reqwest::Client,
+}
+
+impl E2eEnv {
+    pub fn from_env() -> Result<Self> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .with_context(|| format!("{} is not set (see `supabase status`)", name))
+        };
+        Ok(Self {
+            url: var("SUPABASE_URL")?.trim_end_matches('/').to_string(),
+            anon_key: var("SUPABASE_ANON_KEY")?,
+            service_key: var("SUPABASE_SERVICE_KEY")?,
+            http: reqwest::Client::new(),
+        })
+    }
+
+    pub fn auth(&self) -> Auth {
+        let options = AuthOptions {
+            persist_session: false,
+            ..AuthOptions::default()
+        };
+        Auth::new(&self.url, &self.anon_key, self.http.clone(), options)
+    }
+
+    pub fn admin(&self) -> AdminAuth {
+        AdminAuth::new(&self.url, &self.service_key, self.http.clone())
+    }
+
+    /// A table client that runs as the signed-in user, so RLS applies.
+    pub fn table_as(&self, table: &str, session: &Session) -> Result<PostgrestClient> {
+        PostgrestClient::new(&self.url, &self.anon_key, table, self.http.clone())
+            .with_auth(&session.access_token)
+            .context("building an authenticated postgrest client")
+    }
+
+    /// A table client with the service role key, which bypasses RLS.
+    pub fn table_as_service(&self, table: &str) -> Result<PostgrestClient> {
+        PostgrestClient::new(&self.url, &self.service_key, table, self.http.clone())
+            .with_auth(&self.service_key)
+            .context("building a service role postgrest client")
+    }
+
+    pub fn storage_as_service(&self) -> StorageClient {
+        StorageClient::new(&self.url, &self.service_key, self.http.clone())
+    }
+
+    pub fn realtime_url(&self) -> String {
+        let url = self
+            .url
+            .replacen("https://", "wss://", 1)
+            .replacen("http://", "ws://", 1);
+        format!("{}/realtime/v1", url)
+    }
+}
+
+/// A suffix that keeps names unique across runs (`<millis>-<random>`).
+pub fn unique_suffix() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default();
+    let random = uuid::Uuid::new_v4().simple().to_string();
+    format!("{}-{}", millis, &random[..8])
+}
+
+pub const PASSWORD: &str = "e2e-Password-1234";
+
+/// Signs up a fresh user and schedules it for deletion.
+pub async fn sign_up(
+    env: &E2eEnv,
+    cleanup: &mut Cleanup,
+    label: &str,
+    suffix: &str,
+) -> Result<Session> {
+    let email = format!("e2e-{}-{}@example.com", label, suffix);
+    let session = env
+        .auth()
+        .sign_up(&email, PASSWORD)
+        .await
+        .with_context(|| format!("POST {}/auth/v1/signup (email {})", env.url, email))?;
+    cleanup.users.push(session.user.id.clone());
+    Ok(session)
+}
+
+/// Resources created by a scenario, removed with the service role key.
+#[derive(Default)]
+pub struct Cleanup {
+    pub users: Vec<String>,
+    /// `(table, id)` rows, deleted by `id`
+    pub rows: Vec<(String, String)>,
+    /// `(bucket, path)` objects
+    pub objects: Vec<(String, String)>,
+    pub buckets: Vec<String>,
+}
+
+impl Cleanup {
+    // Objects before buckets and rows before users; failures are reported but
+    // do not stop the remaining steps.
+    pub async fn run(self, env: &E2eEnv) -> bool {
+        let mut clean = true;
+        let mut report = |what: String, result: Result<()>| {
+            if let Err(error) = result {
+                eprintln!("  cleanup failed: {}: {:#}", what, error);
+                clean = false;
+            }
+        };
+
+        let storage = env.storage_as_service();
+        for (bucket, path) in &self.objects {
+            let result = storage.from(bucket).remove(vec![path]).await;
+            report(
+                format!("DELETE object {}/{}", bucket, path),
+                result.map_err(Into::into),
+            );
+        }
+        for bucket in &self.buckets {
+            let result = storage.delete_bucket(bucket).await;
+            report(
+                format!("DELETE bucket {}", bucket),
+                result.map_err(Into::into),
+            );
+        }
+        for (table, id) in &self.rows {
+            let result = match env.table_as_service(table) {
+                Ok(client) => client
+                    .eq("id", id)
+                    .delete()
+                    .await
+                    .map(|_| ())
+                    .map_err(Into::into),
+                Err(error) => Err(error),
+            };
+            report(format!("DELETE {} row {}", table, id), result);
+        }
+        let admin = env.admin();
+        for user in &self.users {
+            let result = admin.delete_user(user).await;
+            report(format!("DELETE user {}", user), result.map_err(Into::into));
+        }
+        clean
+    }
+}
+
+/// Prints the outcome and turns it into the process exit code.
+pub fn finish(name: &str, result: Result<()>, cleaned_up: bool) -> ExitCode {
+    match result {
+        Ok(()) if cleaned_up => {
+            println!("PASS {}", name);
+            ExitCode::SUCCESS
+        }
+        Ok(()) => {
+            eprintln!("FAIL {}: scenario passed but cleanup did not finish", name);
+            ExitCode::FAILURE
+        }
+        Err(error) => {
+            eprintln!("FAIL {}: {:#}", name, error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn ensure_eq<T: PartialEq + std::fmt::Debug>(actual: T, expected: T, what: &str) -> Result<()> {
+    anyhow::ensure!(
+        actual == expected,
+        "{}: expected {:?}, got {:?}",
+        what,
+        expected,
+        actual
+    );
+    Ok(())
+}
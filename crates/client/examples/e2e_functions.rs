@@ -0,0 +1,42 @@
+// crates/client/examples/e2e_functions.rs
+//
+// Invokes the `e2e-echo` edge function (supabase/functions/e2e-echo) on a local stack.
+// cargo xtask e2e functions
+
+#[path = "e2e_common/mod.rs"]
+mod common;
+
+use anyhow::{Context, Result};
+use common::{ensure_eq, Cleanup, E2eEnv};
+use serde_json::{json, Value};
+use std::process::ExitCode;
+use supabase_rust_client::functions::FunctionsClient;
+
+const FUNCTION: &str = "e2e-echo";
+
+// Nothing is created here, so `_cleanup` stays empty.
+async fn scenario(env: &E2eEnv, _cleanup: &mut Cleanup) -> Result<()> {
+    let suffix = common::unique_suffix();
+    let functions = FunctionsClient::new(&env.url, &env.anon_key, env.http.clone());
+
+    let payload = json!({ "suffix": suffix });
+    let response: Value = functions
+        .invoke_json(FUNCTION, Some(payload.clone()))
+        .await
+        .with_context(|| format!("POST {}/functions/v1/{}", env.url, FUNCTION))?;
+    ensure_eq(&response["echo"], &payload, "echoed body")?;
+    ensure_eq(&response["method"], &json!("POST"), "request method")?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let env = match E2eEnv::from_env() {
+        Ok(env) => env,
+        Err(error) => return common::finish("functions", Err(error), true),
+    };
+    let mut cleanup = Cleanup::default();
+    let result = scenario(&env, &mut cleanup).await;
+    let cleaned_up = cleanup.run(&env).await;
+    common::finish("functions", result, cleaned_up)
+}
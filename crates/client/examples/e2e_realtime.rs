@@ -0,0 +1,97 @@
+// crates/client/examples/e2e_realtime.rs
+//
+// Subscribes to inserts on `e2e_notes` and checks the change for a row we insert
+// arrives over realtime, on a local stack.
+// cargo xtask e2e realtime
+
+#[path = "e2e_common/mod.rs"]
+mod common;
+
+use anyhow::{Context, Result};
+use common::{Cleanup, E2eEnv};
+use serde_json::json;
+use std::process::ExitCode;
+use std::time::Duration;
+use supabase_rust_client::realtime::{
+    BackpressurePolicy, ChannelEvent, ConnectionState, DatabaseChanges, RealtimeClient, StreamItem,
+};
+
+const TABLE: &str = "e2e_notes";
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+async fn scenario(env: &E2eEnv, cleanup: &mut Cleanup) -> Result<()> {
+    let suffix = common::unique_suffix();
+    let session = common::sign_up(env, cleanup, "realtime", &suffix).await?;
+
+    // Changes are filtered by RLS for the subscriber, so connect as the row owner.
+    let realtime_url = env.realtime_url();
+    let realtime = RealtimeClient::new(&realtime_url, &env.anon_key);
+    realtime.set_auth(Some(session.access_token.clone())).await;
+    let connection = tokio::spawn(realtime.connect());
+    tokio::time::timeout(TIMEOUT, async {
+        while realtime.get_connection_state().await != ConnectionState::Connected {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .with_context(|| format!("connecting to {}", realtime_url))?;
+
+    let topic = format!("e2e-{}", suffix);
+    let (channel, mut events) = realtime.channel(&topic).on_stream(
+        DatabaseChanges::new(TABLE).event(ChannelEvent::Insert),
+        16,
+        BackpressurePolicy::DropOldest,
+    );
+    channel
+        .subscribe()
+        .await
+        .with_context(|| format!("joining {} on {}", topic, realtime_url))?;
+
+    let body = format!("realtime {}", suffix);
+    let inserted = env
+        .table_as(TABLE, &session)?
+        .insert(json!({ "body": body }))
+        .await
+        .with_context(|| format!("POST {}/rest/v1/{}", env.url, TABLE))?;
+    let id = inserted[0]["id"]
+        .as_str()
+        .context("insert returned no id")?
+        .to_string();
+    cleanup.rows.push((TABLE.to_string(), id.clone()));
+
+    let received = tokio::time::timeout(TIMEOUT, async {
+        while let Some(item) = events.recv().await {
+            if let StreamItem::Event { payload, .. } = item {
+                if payload.data.to_string().contains(&id) {
+                    return Some(payload);
+                }
+            }
+        }
+        None
+    })
+    .await;
+
+    let _ = realtime.disconnect().await;
+    connection.abort();
+    match received {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => anyhow::bail!(
+            "realtime stream on {} closed before row {} arrived",
+            topic,
+            id
+        ),
+        Err(_) => anyhow::bail!("no INSERT for row {} on {} within {:?}", id, topic, TIMEOUT),
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let env = match E2eEnv::from_env() {
+        Ok(env) => env,
+        Err(error) => return common::finish("realtime", Err(error), true),
+    };
+    let mut cleanup = Cleanup::default();
+    let result = scenario(&env, &mut cleanup).await;
+    let cleaned_up = cleanup.run(&env).await;
+    common::finish("realtime", result, cleaned_up)
+}
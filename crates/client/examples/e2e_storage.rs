@@ -0,0 +1,76 @@
+// crates/client/examples/e2e_storage.rs
+//
+// Upload, signed URL and download in a throwaway bucket against a local stack.
+// cargo xtask e2e storage
+
+#[path = "e2e_common/mod.rs"]
+mod common;
+
+use anyhow::{Context, Result};
+use common::{ensure_eq, Cleanup, E2eEnv};
+use std::process::ExitCode;
+
+async fn scenario(env: &E2eEnv, cleanup: &mut Cleanup) -> Result<()> {
+    let suffix = common::unique_suffix();
+    let bucket = format!("e2e-{}", suffix);
+    let path = "notes/hello.txt";
+    let content = format!("hello from {}", suffix).into_bytes();
+    let storage = env.storage_as_service();
+
+    storage
+        .create_bucket(&bucket, false)
+        .await
+        .with_context(|| format!("POST {}/storage/v1/bucket (id {})", env.url, bucket))?;
+    cleanup.buckets.push(bucket.clone());
+
+    let objects = storage.from(&bucket);
+    objects
+        .upload(path, content.clone(), None)
+        .await
+        .with_context(|| format!("POST {}/storage/v1/object/{}/{}", env.url, bucket, path))?;
+    cleanup.objects.push((bucket.clone(), path.to_string()));
+
+    let signed_url = objects.create_signed_url(path, 60).await.with_context(|| {
+        format!(
+            "POST {}/storage/v1/object/sign/{}/{}",
+            env.url, bucket, path
+        )
+    })?;
+    // The API returns the signed path relative to `/storage/v1`.
+    let signed_url = if signed_url.starts_with("http") {
+        signed_url
+    } else {
+        format!("{}/storage/v1{}", env.url, signed_url)
+    };
+    let response = env
+        .http
+        .get(&signed_url)
+        .send()
+        .await
+        .with_context(|| format!("GET {}", signed_url))?;
+    ensure_eq(response.status().as_u16(), 200, "signed URL status")?;
+    let fetched = response
+        .bytes()
+        .await
+        .context("reading the signed URL body")?;
+    ensure_eq(fetched.as_ref(), content.as_slice(), "signed URL body")?;
+
+    let downloaded = objects
+        .download(path)
+        .await
+        .with_context(|| format!("GET {}/storage/v1/object/{}/{}", env.url, bucket, path))?;
+    ensure_eq(downloaded.as_ref(), content.as_slice(), "downloaded body")?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let env = match E2eEnv::from_env() {
+        Ok(env) => env,
+        Err(error) => return common::finish("storage", Err(error), true),
+    };
+    let mut cleanup = Cleanup::default();
+    let result = scenario(&env, &mut cleanup).await;
+    let cleaned_up = cleanup.run(&env).await;
+    common::finish("storage", result, cleaned_up)
+}
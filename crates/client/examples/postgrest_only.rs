@@ -0,0 +1,22 @@
+// crates/client/examples/postgrest_only.rs
+//
+// Database-only usage; builds without the auth, storage, realtime and functions crates.
+// cargo run -p supabase-rust-client --no-default-features --features postgrest --example postgrest_only
+// Needs SUPABASE_URL and SUPABASE_ANON_KEY.
+
+use serde_json::Value;
+use supabase_rust_client::prelude::*;
+
+#[tokio::main]
+async fn main() -> Result<(), SupabaseError> {
+    let client = SupabaseClientWrapper::from_env()?;
+    let rows: Vec<Value> = client
+        .from("items")
+        .await?
+        .select("id,name")
+        .limit(5)
+        .execute()
+        .await?;
+    println!("{} rows", rows.len());
+    Ok(())
+}
@@ -0,0 +1,367 @@
+// src/circuit.rs
+
+//! Per-service circuit breakers, enabled with
+//! [`ClientOptions::with_circuit_breaker`](crate::options::ClientOptions::with_circuit_breaker).
+//!
+//! Requests sent by the clients from `from()`, `rpc()`, `storage()` and `functions()`,
+//! and calls made through [`SupabaseClientWrapper::guarded`](crate::client::SupabaseClientWrapper::guarded),
+//! count consecutive service-unavailable failures per [`Service`]. After
+//! `failure_threshold` of them the circuit opens: calls fail without touching the network
+//! until `cool_down` has passed. The next call is then sent as a single probe (half-open);
+//! while it is in flight other calls still fail fast. A probe that reaches the service
+//! closes the circuit, a failed probe opens it for another `cool_down`.
+//!
+//! Service-unavailable means connect errors, timeouts and 502/503/504 responses.
+//! `guarded` fails fast with `SupabaseError::ServiceUnavailable`; the sub-clients fail with
+//! their own `Rejected` error. Auth requests are only counted when made through `guarded`.
+
+use crate::error::{Result, SupabaseError};
+use crate::options::CircuitBreakerOptions;
+use reqwest::StatusCode;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+#[cfg(feature = "auth")]
+use supabase_rust_auth::AuthError;
+use supabase_rust_core::{GatePass, SendGate, SendRejected};
+#[cfg(feature = "functions")]
+use supabase_rust_functions::FunctionsError;
+use supabase_rust_postgrest::PostgrestError;
+#[cfg(feature = "storage")]
+use supabase_rust_storage::StorageError;
+
+/// A service behind the facade, each with its own circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Service {
+    Auth,
+    Rest,
+    Storage,
+    Functions,
+}
+
+impl Service {
+    pub const ALL: [Service; 4] = [
+        Service::Auth,
+        Service::Rest,
+        Service::Storage,
+        Service::Functions,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Service::Auth => "auth",
+            Service::Rest => "rest",
+            Service::Storage => "storage",
+            Service::Functions => "functions",
+        }
+    }
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The state of one service's circuit, as reported by `service_health()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through.
+    Closed,
+    /// Calls fail fast for another `retry_in`.
+    Open { retry_in: Duration },
+    /// The cool-down has passed; the next call is sent as a probe.
+    HalfOpen,
+}
+
+/// Errors that tell whether the service itself was unreachable.
+pub trait ServiceFailure {
+    /// True for connect errors, timeouts and 502/503/504 responses.
+    fn is_service_unavailable(&self) -> bool;
+}
+
+fn is_unavailable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+#[cfg(any(feature = "auth", feature = "storage"))]
+fn is_unavailable_code(status: u16) -> bool {
+    StatusCode::from_u16(status).is_ok_and(is_unavailable_status)
+}
+
+impl ServiceFailure for reqwest::Error {
+    fn is_service_unavailable(&self) -> bool {
+        self.is_connect() || self.is_timeout() || self.status().is_some_and(is_unavailable_status)
+    }
+}
+
+impl ServiceFailure for PostgrestError {
+    fn is_service_unavailable(&self) -> bool {
+        match self {
+            PostgrestError::ApiError { status, .. }
+            | PostgrestError::UnparsedApiError { status, .. } => is_unavailable_status(*status),
+            PostgrestError::NetworkError(e) => e.is_service_unavailable(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "auth")]
+impl ServiceFailure for AuthError {
+    fn is_service_unavailable(&self) -> bool {
+        match self {
+            AuthError::ApiError {
+                status: Some(status),
+                ..
+            } => is_unavailable_code(*status),
+            AuthError::NetworkError(e) => e.is_service_unavailable(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "storage")]
+impl ServiceFailure for StorageError {
+    fn is_service_unavailable(&self) -> bool {
+        match self {
+            StorageError::ApiError {
+                status: Some(status),
+                ..
+            }
+            | StorageError::S3Error { status, .. } => is_unavailable_code(*status),
+            StorageError::NetworkError(e) => e.is_service_unavailable(),
+            StorageError::MultipartPartFailed { source, .. } => source.is_service_unavailable(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "functions")]
+impl ServiceFailure for FunctionsError {
+    fn is_service_unavailable(&self) -> bool {
+        match self {
+            FunctionsError::RequestError(e) => e.is_service_unavailable(),
+            FunctionsError::ServiceUnavailable { .. } | FunctionsError::TimeoutError => true,
+            FunctionsError::FunctionError { status, .. } => is_unavailable_status(*status),
+            _ => false,
+        }
+    }
+}
+
+impl ServiceFailure for SupabaseError {
+    fn is_service_unavailable(&self) -> bool {
+        match self {
+            #[cfg(feature = "auth")]
+            SupabaseError::Auth(e) => e.is_service_unavailable(),
+            SupabaseError::Postgrest(e) => e.is_service_unavailable(),
+            SupabaseError::Network(e) => e.is_service_unavailable(),
+            SupabaseError::Timeout => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probing: bool,
+}
+
+tokio::task_local! {
+    // The service whose permit `guarded` holds, so the sub-client gates don't count the
+    // same call a second time.
+    static GUARDED: Service;
+}
+
+/// Runs `call` with the sub-client gates of `service` passing it through uncounted.
+pub(crate) async fn run_guarded<F: std::future::Future>(service: Service, call: F) -> F::Output {
+    GUARDED.scope(service, call).await
+}
+
+/// The circuits of all services, shared by clones of the facade.
+#[derive(Debug)]
+pub(crate) struct CircuitBreakers {
+    options: CircuitBreakerOptions,
+    circuits: Mutex<HashMap<Service, Circuit>>,
+}
+
+impl CircuitBreakers {
+    pub(crate) fn new(options: CircuitBreakerOptions) -> Self {
+        Self {
+            options,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn health(&self) -> BTreeMap<Service, CircuitState> {
+        let circuits = self.circuits.lock().unwrap();
+        Service::ALL
+            .iter()
+            .map(|service| {
+                let state = circuits
+                    .get(service)
+                    .map_or(CircuitState::Closed, |circuit| self.state(circuit));
+                (*service, state)
+            })
+            .collect()
+    }
+
+    fn state(&self, circuit: &Circuit) -> CircuitState {
+        match circuit.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) => match self.options.cool_down.checked_sub(opened_at.elapsed()) {
+                Some(retry_in) if !retry_in.is_zero() => CircuitState::Open { retry_in },
+                _ => CircuitState::HalfOpen,
+            },
+        }
+    }
+
+    /// Lets a call through, or fails fast while the circuit is open or a probe is in flight.
+    pub(crate) fn acquire(self: &Arc<Self>, service: Service) -> Result<Permit> {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(service).or_default();
+        let probe = match self.state(circuit) {
+            CircuitState::Closed => false,
+            CircuitState::Open { retry_in } => {
+                return Err(SupabaseError::ServiceUnavailable { service, retry_in })
+            }
+            CircuitState::HalfOpen if circuit.probing => {
+                return Err(SupabaseError::ServiceUnavailable {
+                    service,
+                    retry_in: Duration::ZERO,
+                })
+            }
+            CircuitState::HalfOpen => {
+                tracing::debug!(%service, "circuit half-open, sending a probe");
+                circuit.probing = true;
+                true
+            }
+        };
+        Ok(Permit {
+            breakers: self.clone(),
+            service,
+            probe,
+            finished: false,
+        })
+    }
+
+    /// A gate for the sub-clients of `service`.
+    pub(crate) fn gate(self: &Arc<Self>, service: Service) -> Arc<dyn SendGate> {
+        Arc::new(ServiceGate {
+            breakers: self.clone(),
+            service,
+        })
+    }
+
+    fn finish(&self, service: Service, probe: bool, unavailable: bool) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let circuit = circuits.entry(service).or_default();
+        if probe {
+            circuit.probing = false;
+        }
+        if !unavailable {
+            if probe {
+                tracing::info!(%service, "service reachable again, circuit closed");
+                *circuit = Circuit::default();
+            } else {
+                // A call let through before the circuit opened must not close it or free
+                // the half-open slot of a probe still in flight.
+                circuit.consecutive_failures = 0;
+            }
+            return;
+        }
+        circuit.consecutive_failures = circuit.consecutive_failures.saturating_add(1);
+        let threshold_reached = circuit.consecutive_failures >= self.options.failure_threshold;
+        if probe || (circuit.opened_at.is_none() && threshold_reached) {
+            tracing::warn!(
+                %service,
+                consecutive_failures = circuit.consecutive_failures,
+                cool_down = ?self.options.cool_down,
+                "service unavailable, circuit opened"
+            );
+            circuit.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+// Plugs one service's circuit into a sub-client's requests.
+#[derive(Debug)]
+struct ServiceGate {
+    breakers: Arc<CircuitBreakers>,
+    service: Service,
+}
+
+// Used for requests sent inside `guarded`, whose permit records the result.
+struct Uncounted;
+
+impl GatePass for Uncounted {
+    fn finish(self: Box<Self>, _outcome: std::result::Result<StatusCode, &reqwest::Error>) {}
+}
+
+impl SendGate for ServiceGate {
+    fn admit(&self) -> std::result::Result<Box<dyn GatePass>, SendRejected> {
+        if GUARDED
+            .try_with(|service| *service == self.service)
+            .unwrap_or(false)
+        {
+            return Ok(Box::new(Uncounted));
+        }
+        match self.breakers.acquire(self.service) {
+            Ok(permit) => Ok(Box::new(permit)),
+            Err(SupabaseError::ServiceUnavailable { service, retry_in }) => Err(SendRejected {
+                reason: format!("{} circuit is open", service),
+                retry_in,
+            }),
+            Err(e) => Err(SendRejected {
+                reason: e.to_string(),
+                retry_in: Duration::ZERO,
+            }),
+        }
+    }
+}
+
+impl GatePass for Permit {
+    fn finish(self: Box<Self>, outcome: std::result::Result<StatusCode, &reqwest::Error>) {
+        let unavailable = match outcome {
+            Ok(status) => is_unavailable_status(status),
+            Err(e) => e.is_service_unavailable(),
+        };
+        (*self).finish(unavailable);
+    }
+}
+
+/// Permission for one call. A probe dropped without a result frees the half-open slot.
+pub(crate) struct Permit {
+    breakers: Arc<CircuitBreakers>,
+    service: Service,
+    probe: bool,
+    finished: bool,
+}
+
+impl Permit {
+    pub(crate) fn finish(mut self, unavailable: bool) {
+        self.finished = true;
+        self.breakers.finish(self.service, self.probe, unavailable);
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if self.probe && !self.finished {
+            if let Some(circuit) = self
+                .breakers
+                .circuits
+                .lock()
+                .unwrap()
+                .get_mut(&self.service)
+            {
+                circuit.probing = false;
+            }
+        }
+    }
+}
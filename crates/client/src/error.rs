@@ -3,11 +3,18 @@
 // Define custom error types for the Supabase client operations.
 // Use libraries like thiserror for easier error definition.
 
+use crate::circuit::Service;
+use std::time::Duration;
 use thiserror::Error;
 
 // Use correct error path from supabase-rust-auth v0.2.0
+#[cfg(feature = "auth")]
 use supabase_rust_auth::AuthError;
+#[cfg(feature = "functions")]
+use supabase_rust_functions::FunctionsError;
 use supabase_rust_postgrest::PostgrestError;
+#[cfg(feature = "storage")]
+use supabase_rust_storage::StorageError;
 
 /// Universal error type for the Supabase client library operations.
 /// This now mostly wraps or maps errors from the `supabase_rust_gftd` crate.
@@ -19,22 +26,33 @@ pub enum SupabaseError {
     #[error("Initialization failed: {0}")]
     Initialization(String), // For general client setup issues
 
+    /// The URL or key passed to `SupabaseConfig::new` is wrong, e.g. the two were swapped.
+    #[error("Invalid Supabase URL or key: {0}")]
+    InvalidConfig(supabase_rust_core::ConfigIssue),
+
     // Revert to original error wrapping
+    #[cfg(feature = "auth")]
     #[error("Authentication error: {0}")]
     Auth(#[from] AuthError),
 
     #[error("Database error: {0}")]
     Postgrest(#[from] PostgrestError),
 
+    #[cfg(feature = "realtime")]
     #[error("Realtime error: {0}")]
     Realtime(String), // Keep as String for now as realtime code is commented out
 
+    #[cfg(feature = "storage")]
     #[error("Storage error: {0}")]
     Storage(String), // Keep as String
 
+    #[cfg(feature = "functions")]
     #[error("Function error: {0}")]
     Function(String), // Keep as String
 
+    #[error("Offline queue error: {0}")]
+    OfflineQueue(String),
+
     // Keep utility errors
     #[error("Network request error: {0}")]
     Network(#[from] reqwest::Error),
@@ -45,12 +63,29 @@ pub enum SupabaseError {
     #[error("JSON serialization/deserialization error: {0}")]
     Json(#[from] serde_json::Error),
 
+    /// The table is not in the configured `TableRegistry`.
+    #[error(
+        "Unknown table {table:?}{}",
+        .suggestion.as_ref().map(|s| format!(" (did you mean {:?}?)", s)).unwrap_or_default()
+    )]
+    UnknownTable {
+        table: String,
+        suggestion: Option<String>,
+    },
+
     #[error("Invalid input: {0}")]
     InvalidInput(String),
 
     #[error("Operation timed out")]
     Timeout,
 
+    /// The circuit breaker for `service` is open; the call was not sent.
+    #[error("{service} is unavailable (retry in {retry_in:?})")]
+    ServiceUnavailable {
+        service: Service,
+        retry_in: Duration,
+    },
+
     #[error("An unexpected error occurred: {0}")]
     Internal(String),
 
@@ -58,6 +93,20 @@ pub enum SupabaseError {
     Unknown,
 }
 
+#[cfg(feature = "storage")]
+impl From<StorageError> for SupabaseError {
+    fn from(error: StorageError) -> Self {
+        SupabaseError::Storage(error.to_string())
+    }
+}
+
+#[cfg(feature = "functions")]
+impl From<FunctionsError> for SupabaseError {
+    fn from(error: FunctionsError) -> Self {
+        SupabaseError::Function(error.to_string())
+    }
+}
+
 // Optional: Type aliases for convenience if needed elsewhere
 pub type Result<T> = std::result::Result<T, SupabaseError>;
 
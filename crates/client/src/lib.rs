@@ -1,14 +1,51 @@
 // src/lib.rs
 
+//! The Supabase client facade.
+//!
+//! PostgREST is always included. The other services are additive cargo features, all
+//! enabled by default: `auth`, `storage`, `realtime` and `functions`. A database-only
+//! build (for example in a Lambda, where compile and cold-start time matter) uses
+//!
+//! ```toml
+//! supabase-rust-client = { version = "0.4", default-features = false, features = ["postgrest"] }
+//! ```
+
+pub mod circuit;
 pub mod client;
 pub mod error;
+#[cfg(feature = "management")]
+pub mod management;
 pub mod models;
+pub mod offline;
+pub mod options;
+pub mod prelude;
+pub mod registry;
+#[cfg(feature = "realtime")]
+pub mod storage_events;
+#[cfg(feature = "realtime")]
+pub mod synced_table;
+mod tls;
 
 // Re-export key components
 pub use client::SupabaseClientWrapper; // Example, adjust as needed
 pub use error::SupabaseError;
 pub use models::Item; // Example, adjust as needed // Example, adjust as needed
 
+// Re-export the sub-crates so applications only need a single dependency and
+// always get the versions this crate was built against.
+#[cfg(feature = "auth")]
+pub use supabase_rust_auth as auth;
+#[cfg(feature = "functions")]
+pub use supabase_rust_functions as functions;
+pub use supabase_rust_postgrest as postgrest;
+#[cfg(feature = "realtime")]
+pub use supabase_rust_realtime as realtime;
+#[cfg(feature = "storage")]
+pub use supabase_rust_storage as storage;
+
+/// The version of this crate (all workspace crates are released in lockstep).
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 #[cfg(test)]
 mod tests {
     #[test]
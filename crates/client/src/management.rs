@@ -0,0 +1,437 @@
+// src/management.rs
+
+//! Client for the Supabase Management API (`https://api.supabase.com/v1`), enabled by the
+//! `management` feature.
+//!
+//! The Management API manages the platform rather than a single project: it creates and
+//! deletes projects, hands out their API keys and changes project configuration. It is
+//! authenticated with a personal access token, not with a project key.
+//!
+//! ```no_run
+//! use supabase_rust_client::management::{CreateProject, ManagementClient};
+//!
+//! # async fn run() -> Result<(), supabase_rust_client::management::ManagementError> {
+//! let management = ManagementClient::new("sbp_personal_access_token");
+//! let project = management
+//!     .create_project(&CreateProject::new("preview-42", "org-id", "db-password", "us-east-1"))
+//!     .await?;
+//! let keys = management.api_keys(&project.id).await?;
+//! # let _ = keys;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Errors are classified the same way as in the other crates: 401/403 become
+//! [`ManagementError::Unauthorized`], 404 [`ManagementError::NotFound`] and 429
+//! [`ManagementError::RateLimited`] (with the `retry-after` delay). Read-only list requests
+//! wait out a 429 and retry before giving up. Error bodies have the access token removed.
+
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::time::Duration;
+use supabase_rust_core::{Page, Paged, Redacted, SendWithRequestId};
+use thiserror::Error;
+use url::Url;
+
+/// Default base URL of the Management API.
+pub const DEFAULT_MANAGEMENT_URL: &str = "https://api.supabase.com";
+
+/// Wait used when a 429 carries no `retry-after` header.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Errors returned by [`ManagementClient`].
+#[derive(Debug, Error)]
+pub enum ManagementError {
+    #[error("Network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    #[error("URL parsing error: {0}")]
+    UrlParse(#[from] url::ParseError),
+
+    #[error("JSON serialization/deserialization error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// 401 or 403: the access token is missing, expired or lacks the required scope.
+    #[error("Unauthorized (status: {status}): {message}")]
+    Unauthorized { status: u16, message: String },
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    /// 429, after any automatic retries.
+    #[error("Rate limited: {message}")]
+    RateLimited {
+        retry_after: Option<Duration>,
+        message: String,
+    },
+
+    #[error("API error (status: {status}): {message}")]
+    Api { status: u16, message: String },
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+}
+
+pub type Result<T> = std::result::Result<T, ManagementError>;
+
+/// A project as returned by the Management API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    /// The project ref, e.g. `abcdefghijklmnopqrst`.
+    pub id: String,
+    pub organization_id: String,
+    pub name: String,
+    pub region: String,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    /// e.g. `ACTIVE_HEALTHY`, `COMING_UP`.
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+/// Request body for [`ManagementClient::create_project`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CreateProject {
+    pub name: String,
+    pub organization_id: String,
+    pub db_pass: Redacted<String>,
+    pub region: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan: Option<String>,
+}
+
+impl CreateProject {
+    pub fn new(name: &str, organization_id: &str, db_pass: &str, region: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            organization_id: organization_id.to_string(),
+            db_pass: db_pass.into(),
+            region: region.to_string(),
+            plan: None,
+        }
+    }
+
+    pub fn with_plan(mut self, plan: &str) -> Self {
+        self.plan = Some(plan.to_string());
+        self
+    }
+}
+
+/// Response of [`ManagementClient::delete_project`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DeletedProject {
+    pub id: i64,
+    #[serde(rename = "ref")]
+    pub project_ref: String,
+    pub name: String,
+}
+
+/// One of a project's API keys (`anon`, `service_role`, ...).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ApiKey {
+    pub name: String,
+    pub api_key: Redacted<String>,
+}
+
+/// A project's auth configuration.
+///
+/// The commonly used settings are typed; everything else the API returns is kept in `other`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub site_url: Option<String>,
+    #[serde(default)]
+    pub uri_allow_list: Option<String>,
+    #[serde(default)]
+    pub disable_signup: Option<bool>,
+    #[serde(default)]
+    pub jwt_exp: Option<u64>,
+    #[serde(default)]
+    pub external_email_enabled: Option<bool>,
+    #[serde(default)]
+    pub mailer_autoconfirm: Option<bool>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// Partial update for [`ManagementClient::update_auth_config`]. Unset fields are left alone.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AuthConfigUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uri_allow_list: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_signup: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt_exp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_email_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mailer_autoconfirm: Option<bool>,
+    /// Any other setting, sent as is.
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+impl AuthConfigUpdate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_site_url(mut self, url: &str) -> Self {
+        self.site_url = Some(url.to_string());
+        self
+    }
+
+    pub fn with_disable_signup(mut self, disabled: bool) -> Self {
+        self.disable_signup = Some(disabled);
+        self
+    }
+
+    pub fn with_setting(mut self, key: &str, value: Value) -> Self {
+        self.other.insert(key.to_string(), value);
+        self
+    }
+}
+
+// `GET /v1/projects` returns either a bare array or `{ projects, pagination }`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProjectList {
+    Paginated {
+        projects: Vec<Project>,
+        pagination: Option<Pagination>,
+    },
+    Plain(Vec<Project>),
+}
+
+#[derive(Deserialize)]
+struct Pagination {
+    count: Option<u64>,
+}
+
+/// Client for the Supabase Management API.
+#[derive(Debug, Clone)]
+pub struct ManagementClient {
+    base_url: String,
+    access_token: Redacted<String>,
+    http_client: Client,
+    max_rate_limit_retries: u32,
+}
+
+impl ManagementClient {
+    /// Creates a client for `https://api.supabase.com` authenticated with a personal access token.
+    pub fn new(access_token: &str) -> Self {
+        Self {
+            base_url: DEFAULT_MANAGEMENT_URL.to_string(),
+            access_token: access_token.into(),
+            http_client: Client::new(),
+            max_rate_limit_retries: 3,
+        }
+    }
+
+    /// Talks to another Management API host (e.g. a mock server).
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_http_client(mut self, http_client: Client) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    /// How often a rate-limited list request is retried before `RateLimited` is returned (default 3).
+    pub fn with_max_rate_limit_retries(mut self, retries: u32) -> Self {
+        self.max_rate_limit_retries = retries;
+        self
+    }
+
+    /// All projects the token can see, fetching every page.
+    pub async fn list_projects(&self) -> Result<Vec<Project>> {
+        let mut page = Page::first(100);
+        let mut projects = Vec::new();
+        loop {
+            let paged = self.list_projects_page(page).await?;
+            let done = paged.has_more != Some(true);
+            projects.extend(paged.items);
+            if done {
+                return Ok(projects);
+            }
+            page = page.next();
+        }
+    }
+
+    /// One page of projects.
+    ///
+    /// Servers that ignore `limit`/`offset` return every project at once; `has_more` is then `false`.
+    pub async fn list_projects_page(&self, page: Page) -> Result<Paged<Project>> {
+        let mut url = self.url("/v1/projects")?;
+        url.query_pairs_mut()
+            .append_pair("limit", &page.size.to_string())
+            .append_pair("offset", &page.offset().to_string());
+        let list: ProjectList = self.get_with_retry(url).await?;
+        Ok(match list {
+            ProjectList::Paginated {
+                projects,
+                pagination,
+            } => {
+                let total = pagination.and_then(|pagination| pagination.count);
+                let mut paged = Paged::new(projects, page, total);
+                if total.is_none() {
+                    paged.has_more = Some(paged.items.len() as u64 >= page.size as u64);
+                }
+                paged
+            }
+            ProjectList::Plain(projects) => Paged {
+                items: projects,
+                page,
+                total: None,
+                has_more: Some(false),
+            },
+        })
+    }
+
+    /// Creates a project. It is usually still `COMING_UP` when this returns.
+    pub async fn create_project(&self, project: &CreateProject) -> Result<Project> {
+        if project.name.trim().is_empty() {
+            return Err(ManagementError::InvalidInput(
+                "project name must not be empty".to_string(),
+            ));
+        }
+        let request = self
+            .request(Method::POST, self.url("/v1/projects")?)
+            .json(project);
+        self.send_json(request).await
+    }
+
+    /// Deletes a project and all of its data.
+    pub async fn delete_project(&self, project_ref: &str) -> Result<DeletedProject> {
+        let url = self.project_url(project_ref, "")?;
+        self.send_json(self.request(Method::DELETE, url)).await
+    }
+
+    /// The project's API keys (`anon`, `service_role`, ...).
+    pub async fn api_keys(&self, project_ref: &str) -> Result<Vec<ApiKey>> {
+        let url = self.project_url(project_ref, "/api-keys")?;
+        self.get_with_retry(url).await
+    }
+
+    pub async fn auth_config(&self, project_ref: &str) -> Result<AuthConfig> {
+        let url = self.project_url(project_ref, "/config/auth")?;
+        self.send_json(self.request(Method::GET, url)).await
+    }
+
+    /// Changes the given auth settings and returns the resulting configuration.
+    pub async fn update_auth_config(
+        &self,
+        project_ref: &str,
+        update: &AuthConfigUpdate,
+    ) -> Result<AuthConfig> {
+        let url = self.project_url(project_ref, "/config/auth")?;
+        self.send_json(self.request(Method::PATCH, url).json(update))
+            .await
+    }
+
+    /// Runs SQL against the project's database and returns the rows of the last statement.
+    pub async fn run_sql(&self, project_ref: &str, query: &str) -> Result<Vec<Value>> {
+        let url = self.project_url(project_ref, "/database/query")?;
+        let request = self
+            .request(Method::POST, url)
+            .json(&serde_json::json!({ "query": query }));
+        self.send_json(request).await
+    }
+
+    fn url(&self, path: &str) -> Result<Url> {
+        Ok(Url::parse(&format!("{}{}", self.base_url, path))?)
+    }
+
+    fn project_url(&self, project_ref: &str, suffix: &str) -> Result<Url> {
+        let valid = !project_ref.is_empty()
+            && project_ref
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err(ManagementError::InvalidInput(format!(
+                "invalid project ref {:?}",
+                project_ref
+            )));
+        }
+        self.url(&format!("/v1/projects/{}{}", project_ref, suffix))
+    }
+
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        self.http_client
+            .request(method, url)
+            .bearer_auth(self.access_token.expose())
+    }
+
+    // GET that waits out 429 responses up to `max_rate_limit_retries` times.
+    async fn get_with_retry<T: DeserializeOwned>(&self, url: Url) -> Result<T> {
+        let mut retries = 0;
+        loop {
+            match self.send_json(self.request(Method::GET, url.clone())).await {
+                Err(ManagementError::RateLimited { retry_after, .. })
+                    if retries < self.max_rate_limit_retries =>
+                {
+                    let delay = retry_after.unwrap_or(DEFAULT_RETRY_AFTER);
+                    tracing::warn!(
+                        "Management API rate limited, retrying {} in {:?}",
+                        url.path(),
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send_json<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let response = request.send_with_request_id().await?;
+        if !response.status().is_success() {
+            return Err(self.error_from(response).await);
+        }
+        Ok(response.json().await?)
+    }
+
+    async fn error_from(&self, response: Response) -> ManagementError {
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|json| {
+                json.get("message")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+            })
+            .unwrap_or(body);
+        let message = supabase_rust_core::scrub(&message, &[self.access_token.expose().as_str()]);
+
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ManagementError::Unauthorized {
+                status: status.as_u16(),
+                message,
+            },
+            StatusCode::NOT_FOUND => ManagementError::NotFound(message),
+            StatusCode::TOO_MANY_REQUESTS => ManagementError::RateLimited {
+                retry_after,
+                message,
+            },
+            _ => ManagementError::Api {
+                status: status.as_u16(),
+                message,
+            },
+        }
+    }
+}
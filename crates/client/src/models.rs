@@ -57,6 +57,7 @@ pub struct User {
 
 // --- From Trait Implementation ---
 
+#[cfg(feature = "auth")]
 impl From<supabase_rust_auth::User> for User {
     fn from(auth_user: supabase_rust_auth::User) -> Self {
         // Attempt to parse UUID and timestamps, providing defaults on failure
@@ -88,21 +89,28 @@ impl From<supabase_rust_auth::User> for User {
                 Utc::now()
             });
 
+        // Optional timestamps are dropped (None) when they cannot be parsed
+        let parse_optional = |value: &Option<String>| {
+            value
+                .as_deref()
+                .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+                .map(|dt| dt.with_timezone(&Utc))
+        };
+
         User {
             id: parsed_id,
             email: auth_user.email,
             phone: auth_user.phone,
             created_at: parsed_created_at,
             updated_at: parsed_updated_at,
-            // Set missing fields to defaults
-            aud: String::new(), // Or some default? Check Supabase docs
-            role: None,
+            aud: auth_user.aud.unwrap_or_default(),
+            role: auth_user.role,
             confirmation_sent_at: None,
-            confirmed_at: None,
-            email_confirmed_at: None,
-            phone_confirmed_at: None,
+            confirmed_at: parse_optional(&auth_user.confirmed_at),
+            email_confirmed_at: parse_optional(&auth_user.email_confirmed_at),
+            phone_confirmed_at: parse_optional(&auth_user.phone_confirmed_at),
             recovery_sent_at: None,
-            last_sign_in_at: None,
+            last_sign_in_at: parse_optional(&auth_user.last_sign_in_at),
             // app_metadata: Default::default(), // If added back as serde_json::Value
             // user_metadata: Default::default(), // If added back as serde_json::Value
         }
@@ -0,0 +1,309 @@
+// src/offline.rs
+
+//! Opt-in offline queue for writes made while the device has no connectivity.
+//!
+//! Writes go through [`OfflineQueue`]. When a request fails because the server could
+//! not be reached (see [`is_offline_error`]), the operation is stored in a
+//! [`WriteQueue`] instead of being lost, and [`OfflineQueue::flush`] replays stored
+//! operations oldest first once the connection is back. Each operation carries an
+//! idempotency key that is generated when it is first attempted and sent as the
+//! `Idempotency-Key` header on every attempt.
+//!
+//! PostgREST ignores `Idempotency-Key`, so the header alone does not stop a write from
+//! being applied twice. Only writes that never reached the server are queued, but if a
+//! replay times out after the server committed it, the next flush sends it again. Tables
+//! that must not get duplicates need a unique column holding the key (or a proxy or
+//! edge function that deduplicates on the header).
+//!
+//! This first milestone covers PostgREST inserts. [`FileWriteQueue`] persists the
+//! queue as JSON Lines so queued writes survive a process restart.
+
+use crate::client::SupabaseClientWrapper;
+use crate::error::{Result, SupabaseError};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use supabase_rust_postgrest::PostgrestError;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Header carrying the idempotency key of a queued write.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// A write that can be queued and replayed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WriteOp {
+    /// `POST /rest/v1/<table>` with `values` as the body.
+    Insert { table: String, values: Value },
+}
+
+/// A write waiting in a [`WriteQueue`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedOp {
+    pub idempotency_key: String,
+    pub queued_at: DateTime<Utc>,
+    pub op: WriteOp,
+}
+
+/// Storage for queued writes. Implementations must keep insertion order.
+#[async_trait]
+pub trait WriteQueue: Send + Sync {
+    /// Appends an operation to the end of the queue.
+    async fn push(&self, op: QueuedOp) -> Result<()>;
+    /// Returns all queued operations, oldest first.
+    async fn pending(&self) -> Result<Vec<QueuedOp>>;
+    /// Removes the operation with the given idempotency key.
+    async fn remove(&self, idempotency_key: &str) -> Result<()>;
+}
+
+/// A [`WriteQueue`] stored as a JSON Lines file, one operation per line.
+///
+/// Appends are flushed to disk before `push` returns. Removing an operation rewrites
+/// the file through a temporary file and a rename, so a crash never leaves a
+/// half-written queue behind.
+pub struct FileWriteQueue {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileWriteQueue {
+    /// Uses the queue file at `path`, which is created on the first `push`.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn read(&self) -> Result<Vec<QueuedOp>> {
+        let contents = match tokio::fs::read_to_string(&self.path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(queue_error(&self.path, e)),
+        };
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(SupabaseError::Json))
+            .collect()
+    }
+}
+
+fn queue_error(path: &Path, error: std::io::Error) -> SupabaseError {
+    SupabaseError::OfflineQueue(format!("{}: {}", path.display(), error))
+}
+
+#[async_trait]
+impl WriteQueue for FileWriteQueue {
+    async fn push(&self, op: QueuedOp) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let mut line = serde_json::to_string(&op)?;
+        line.push('\n');
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| queue_error(&self.path, e))?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(|e| queue_error(&self.path, e))?;
+        file.sync_data()
+            .await
+            .map_err(|e| queue_error(&self.path, e))
+    }
+
+    async fn pending(&self) -> Result<Vec<QueuedOp>> {
+        let _guard = self.lock.lock().await;
+        self.read().await
+    }
+
+    async fn remove(&self, idempotency_key: &str) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        let mut contents = String::new();
+        for op in self.read().await? {
+            if op.idempotency_key != idempotency_key {
+                contents.push_str(&serde_json::to_string(&op)?);
+                contents.push('\n');
+            }
+        }
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, contents)
+            .await
+            .map_err(|e| queue_error(&tmp, e))?;
+        tokio::fs::rename(&tmp, &self.path)
+            .await
+            .map_err(|e| queue_error(&self.path, e))
+    }
+}
+
+/// Whether `error` means the connection to the server could not be made (including a
+/// connect timeout), so the request was never sent.
+///
+/// Other network errors, such as a timeout while waiting for the response, are not
+/// offline errors: the server may already have committed the write.
+pub fn is_offline_error(error: &PostgrestError) -> bool {
+    match error {
+        PostgrestError::NetworkError(e) => e.is_connect(),
+        _ => false,
+    }
+}
+
+/// Result of a write made through [`OfflineQueue`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum WriteOutcome {
+    /// The server accepted the write; contains the response body.
+    Sent(Value),
+    /// The write was queued and will be sent by [`OfflineQueue::flush`].
+    Queued { idempotency_key: String },
+}
+
+/// What `flush` does with a queued operation the server rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictAction {
+    /// Drop the operation and continue with the next one.
+    Discard,
+    /// Keep the operation and stop flushing, preserving the order of later writes (the default).
+    Halt,
+}
+
+type ConflictHandler = dyn Fn(&PostgrestError, &QueuedOp) -> ConflictAction + Send + Sync;
+
+/// Summary of a [`OfflineQueue::flush`] run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlushReport {
+    /// Idempotency keys of operations the server accepted, in replay order.
+    pub sent: Vec<String>,
+    /// Idempotency keys of rejected operations dropped by the conflict handler.
+    pub discarded: Vec<String>,
+    /// Operations still queued (the server was unreachable or the handler halted).
+    pub remaining: usize,
+}
+
+/// Sends writes through a [`SupabaseClientWrapper`], queueing them while offline.
+/// Created with [`SupabaseClientWrapper::offline_queue`].
+#[derive(Clone)]
+pub struct OfflineQueue {
+    client: SupabaseClientWrapper,
+    queue: Arc<dyn WriteQueue>,
+    on_conflict: Option<Arc<ConflictHandler>>,
+    flushing: Arc<Mutex<()>>,
+}
+
+impl OfflineQueue {
+    pub fn new(client: SupabaseClientWrapper, queue: Arc<dyn WriteQueue>) -> Self {
+        Self {
+            client,
+            queue,
+            on_conflict: None,
+            flushing: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Called by `flush` when the server rejects a queued operation.
+    /// Without a handler the flush halts at the rejected operation.
+    pub fn on_conflict<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&PostgrestError, &QueuedOp) -> ConflictAction + Send + Sync + 'static,
+    {
+        self.on_conflict = Some(Arc::new(handler));
+        self
+    }
+
+    /// Operations waiting to be sent, oldest first.
+    pub async fn pending(&self) -> Result<Vec<QueuedOp>> {
+        self.queue.pending().await
+    }
+
+    /// Inserts `values` into `table`, queueing the insert if the server is unreachable.
+    ///
+    /// While earlier writes are still queued, the insert is queued behind them rather
+    /// than sent, so writes always reach the server in the order they were made.
+    /// Server-side errors and other network errors (such as a response timeout) are
+    /// returned as usual and are not queued.
+    pub async fn insert<T: Serialize>(&self, table: &str, values: T) -> Result<WriteOutcome> {
+        let op = QueuedOp {
+            idempotency_key: Uuid::new_v4().to_string(),
+            queued_at: Utc::now(),
+            op: WriteOp::Insert {
+                table: table.to_string(),
+                values: serde_json::to_value(values)?,
+            },
+        };
+        if !self.queue.pending().await?.is_empty() {
+            return self.enqueue(op).await;
+        }
+        match self.send(&op).await {
+            Ok(body) => Ok(WriteOutcome::Sent(body)),
+            Err(SupabaseError::Postgrest(e)) if is_offline_error(&e) => {
+                tracing::debug!(error = %e, "server unreachable, queueing insert into {}", table);
+                self.enqueue(op).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replays queued operations oldest first.
+    ///
+    /// Stops without error at the first operation the server cannot be reached for;
+    /// call again once connectivity returns. Other errors, including response timeouts,
+    /// go to the conflict handler. Only one flush runs at a time.
+    pub async fn flush(&self) -> Result<FlushReport> {
+        let _guard = self.flushing.lock().await;
+        let mut report = FlushReport::default();
+        for op in self.queue.pending().await? {
+            match self.send(&op).await {
+                Ok(_) => {
+                    self.queue.remove(&op.idempotency_key).await?;
+                    report.sent.push(op.idempotency_key);
+                }
+                Err(SupabaseError::Postgrest(e)) if is_offline_error(&e) => break,
+                Err(SupabaseError::Postgrest(e)) => {
+                    let action = self
+                        .on_conflict
+                        .as_ref()
+                        .map_or(ConflictAction::Halt, |handler| handler(&e, &op));
+                    if action == ConflictAction::Halt {
+                        tracing::warn!(error = %e, "queued write {} rejected, halting flush", op.idempotency_key);
+                        break;
+                    }
+                    self.queue.remove(&op.idempotency_key).await?;
+                    report.discarded.push(op.idempotency_key);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        // Re-read so writes queued by `insert` during the flush are counted too
+        report.remaining = self.queue.pending().await?.len();
+        Ok(report)
+    }
+
+    async fn enqueue(&self, op: QueuedOp) -> Result<WriteOutcome> {
+        let idempotency_key = op.idempotency_key.clone();
+        self.queue.push(op).await?;
+        Ok(WriteOutcome::Queued { idempotency_key })
+    }
+
+    async fn send(&self, op: &QueuedOp) -> Result<Value> {
+        match &op.op {
+            WriteOp::Insert { table, values } => {
+                let client = self
+                    .client
+                    .from(table)
+                    .await?
+                    .with_header(IDEMPOTENCY_KEY_HEADER, &op.idempotency_key)?;
+                Ok(client.insert(values).await?)
+            }
+        }
+    }
+}
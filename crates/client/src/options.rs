@@ -0,0 +1,471 @@
+// src/options.rs
+
+//! Client-wide options applied by [`SupabaseClientWrapper::new`](crate::client::SupabaseClientWrapper::new).
+//!
+//! ```
+//! use std::time::Duration;
+//! use supabase_rust_client::prelude::*;
+//!
+//! let options = ClientOptions::default()
+//!     .with_auto_refresh_token(true)
+//!     .with_persist_session(false)
+//!     .with_db_schema("api")
+//!     .with_global_header("x-tenant", "acme")
+//!     .with_timeout(Duration::from_secs(30));
+//!
+//! let config = SupabaseConfig::new("https://example.supabase.co", "anon-key".to_string())?
+//!     .with_options(options);
+//! let client = SupabaseClientWrapper::new(config)?;
+//! # let _ = client;
+//! # Ok::<(), SupabaseError>(())
+//! ```
+
+use crate::registry::TableRegistry;
+use reqwest::header::HeaderMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(feature = "auth")]
+use supabase_rust_auth::AuthOptions;
+#[cfg(feature = "storage")]
+use supabase_rust_storage::PartRetryPolicy;
+use url::Url;
+
+/// Options shared by all sub-clients created from one `SupabaseConfig`.
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    /// Refresh the access token before it expires.
+    pub auto_refresh_token: bool,
+    /// Keep the session across restarts.
+    pub persist_session: bool,
+    /// Pick up a session from the redirect URL after OAuth / magic links.
+    pub detect_session_in_url: bool,
+    /// Hosts `redirect_to` may point at; empty disables the check
+    /// (see `AuthOptions::allowed_redirect_hosts`).
+    pub allowed_redirect_hosts: Vec<String>,
+    /// Postgres schema for `from()` / `rpc()` (sent as `Accept-Profile` / `Content-Profile`).
+    pub db_schema: Option<String>,
+    /// Headers added to every HTTP request.
+    pub global_headers: HashMap<String, String>,
+    /// Headers every sub-client adds to each request it builds (e.g. `x-tenant-id`).
+    /// Unlike `global_headers` they also apply with `http_client`; Realtime is not covered.
+    pub default_headers: HeaderMap,
+    /// Total timeout for each HTTP request. `None` means no timeout.
+    pub timeout: Option<Duration>,
+    /// Timeout for establishing connections.
+    pub connect_timeout: Option<Duration>,
+    /// Per-service URL overrides (e.g. a self-hosted auth server).
+    pub urls: ServiceUrls,
+    /// Path of the auth server under its URL. `None` uses `/auth/v1`; self-hosted GoTrue
+    /// is often mounted elsewhere, e.g. `/gotrue`.
+    pub auth_base_path: Option<String>,
+    /// Default retry behaviour for operations that retry.
+    pub retry: RetryPolicy,
+    /// Known tables, checked by `from_checked()` (and `from()` when `validate_tables` is set).
+    pub table_registry: Option<Arc<TableRegistry>>,
+    /// Reject unknown tables in `from()` instead of letting the server answer 404.
+    pub validate_tables: bool,
+    /// `limit` added to `from()` reads that set none (see `PostgrestClient::default_max_rows`).
+    pub default_max_rows: Option<u32>,
+    /// Upper bound for `limit` on `from()` reads (see `PostgrestClient::max_rows_ceiling`).
+    pub max_rows_ceiling: Option<u32>,
+    /// Connection pool and HTTP/2 tuning for the HTTP client built by the facade.
+    pub connection: ConnectionOptions,
+    /// Refresh the session and retry once when PostgREST answers 401 `JWT expired`
+    /// to a `from()` / `rpc()` request. Never applies to service-role requests.
+    pub refresh_on_jwt_expired: bool,
+    /// Use this HTTP client for every sub-client instead of building one.
+    /// `global_headers`, the timeouts and `connection` are not applied to it.
+    pub http_client: Option<reqwest::Client>,
+    /// Per-service circuit breakers for calls made through `guarded()` (off by default).
+    pub circuit_breaker: Option<CircuitBreakerOptions>,
+    /// Extra root certificates and pinned fingerprints for the HTTP client built by the facade.
+    pub tls: TlsOptions,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        // The same defaults as `AuthOptions::default()`, which is not available without `auth`
+        Self {
+            auto_refresh_token: true,
+            persist_session: true,
+            detect_session_in_url: true,
+            allowed_redirect_hosts: Vec::new(),
+            db_schema: None,
+            global_headers: HashMap::new(),
+            default_headers: HeaderMap::new(),
+            timeout: None,
+            connect_timeout: None,
+            urls: ServiceUrls::default(),
+            auth_base_path: None,
+            retry: RetryPolicy::default(),
+            table_registry: None,
+            validate_tables: false,
+            default_max_rows: None,
+            max_rows_ceiling: None,
+            connection: ConnectionOptions::default(),
+            refresh_on_jwt_expired: true,
+            http_client: None,
+            circuit_breaker: None,
+            tls: TlsOptions::default(),
+        }
+    }
+}
+
+impl ClientOptions {
+    /// Same as `ClientOptions::default()`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_auto_refresh_token(mut self, enabled: bool) -> Self {
+        self.auto_refresh_token = enabled;
+        self
+    }
+
+    pub fn with_persist_session(mut self, enabled: bool) -> Self {
+        self.persist_session = enabled;
+        self
+    }
+
+    pub fn with_detect_session_in_url(mut self, enabled: bool) -> Self {
+        self.detect_session_in_url = enabled;
+        self
+    }
+
+    /// Rejects `redirect_to` URLs outside these hosts before sending, e.g. `*.example.com`
+    /// or `http://localhost:3000`.
+    pub fn with_allowed_redirect_hosts<I, S>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_redirect_hosts = hosts.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Queries tables and functions in `schema` instead of `public`.
+    pub fn with_db_schema(mut self, schema: &str) -> Self {
+        self.db_schema = Some(schema.to_string());
+        self
+    }
+
+    /// Adds a header sent with every request.
+    pub fn with_global_header(mut self, key: &str, value: &str) -> Self {
+        self.global_headers
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Adds several headers sent with every request.
+    pub fn with_global_headers(mut self, headers: HashMap<String, String>) -> Self {
+        self.global_headers.extend(headers);
+        self
+    }
+
+    /// Adds headers that every sub-client (PostgREST, Auth, Storage, Functions) puts on
+    /// each request, replacing earlier values of the same name. See also
+    /// [`SupabaseClientWrapper::for_tenant`](crate::client::SupabaseClientWrapper::for_tenant).
+    pub fn with_default_headers(mut self, headers: HeaderMap) -> Self {
+        for name in headers.keys() {
+            self.default_headers.remove(name);
+        }
+        for (name, value) in &headers {
+            self.default_headers.append(name, value.clone());
+        }
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Base URL of the auth server (the client appends `/auth/v1` or `auth_base_path`).
+    pub fn with_auth_url(mut self, url: Url) -> Self {
+        self.urls.auth = Some(url);
+        self
+    }
+
+    /// Path appended to the auth URL instead of `/auth/v1`, for user and admin endpoints alike.
+    pub fn with_auth_base_path(mut self, path: &str) -> Self {
+        self.auth_base_path = Some(path.to_string());
+        self
+    }
+
+    /// Base URL of PostgREST (the client appends `/rest/v1`).
+    pub fn with_rest_url(mut self, url: Url) -> Self {
+        self.urls.rest = Some(url);
+        self
+    }
+
+    /// Base URL of a PostgREST read replica (the client appends `/rest/v1`). Reads go there
+    /// only for queries routed with `use_read_replica()` / `route_to(ReadPreference::Replica)`;
+    /// writes always go to the primary.
+    pub fn with_rest_replica_url(mut self, url: Url) -> Self {
+        self.urls.rest_replica = Some(url);
+        self
+    }
+
+    /// Full WebSocket endpoint for Realtime, e.g. `wss://host/realtime/v1`.
+    pub fn with_realtime_url(mut self, url: Url) -> Self {
+        self.urls.realtime = Some(url);
+        self
+    }
+
+    /// Base URL of the storage server (the client appends `/storage/v1`).
+    pub fn with_storage_url(mut self, url: Url) -> Self {
+        self.urls.storage = Some(url);
+        self
+    }
+
+    /// Base URL of the edge functions server (the client appends `/functions/v1`).
+    pub fn with_functions_url(mut self, url: Url) -> Self {
+        self.urls.functions = Some(url);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_table_registry(mut self, registry: TableRegistry) -> Self {
+        self.table_registry = Some(Arc::new(registry));
+        self
+    }
+
+    /// Makes `from()` check table names against the registry (off by default).
+    pub fn with_validate_tables(mut self, enabled: bool) -> Self {
+        self.validate_tables = enabled;
+        self
+    }
+
+    /// Adds `limit=rows` to every `from()` read without an explicit limit.
+    /// Use `PostgrestClient::unlimited()` for intentional full scans.
+    pub fn with_default_max_rows(mut self, rows: u32) -> Self {
+        self.default_max_rows = Some(rows);
+        self
+    }
+
+    /// Clamps explicit limits on `from()` reads to `rows`, logging a warning.
+    pub fn with_max_rows_ceiling(mut self, rows: u32) -> Self {
+        self.max_rows_ceiling = Some(rows);
+        self
+    }
+
+    pub fn with_connection_options(mut self, connection: ConnectionOptions) -> Self {
+        self.connection = connection;
+        self
+    }
+
+    /// How long idle pooled connections are kept open (reqwest's default is 90 seconds).
+    pub fn with_pool_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.connection.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.connection.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Enables TCP keepalive probes at `interval` on every connection.
+    pub fn with_tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.connection.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Lets HTTP/2 size its flow-control windows from measured bandwidth.
+    pub fn with_http2_adaptive_window(mut self, enabled: bool) -> Self {
+        self.connection.http2_adaptive_window = enabled;
+        self
+    }
+
+    /// Talks HTTP/2 without negotiation. Only for servers known to speak it, e.g. internal deployments.
+    pub fn with_http2_prior_knowledge(mut self, enabled: bool) -> Self {
+        self.connection.http2_prior_knowledge = enabled;
+        self
+    }
+
+    /// Retries `from()` / `rpc()` requests once after a session refresh when the
+    /// access token expired in flight (on by default). Concurrent requests share a single
+    /// refresh. Requests sent with the anon or service role key are never retried.
+    pub fn with_refresh_on_jwt_expired(mut self, enabled: bool) -> Self {
+        self.refresh_on_jwt_expired = enabled;
+        self
+    }
+
+    /// Shares an existing HTTP client instead of building one from these options.
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Fails calls to a service fast after repeated outages. See [`crate::circuit`].
+    pub fn with_circuit_breaker(mut self, options: CircuitBreakerOptions) -> Self {
+        self.circuit_breaker = Some(options);
+        self
+    }
+
+    /// Trusts `pem` (e.g. an internal proxy's CA) in addition to the built-in roots.
+    /// May be called several times.
+    pub fn with_root_certificate(mut self, pem: &str) -> Self {
+        self.tls.root_certificates.push(pem.to_string());
+        self
+    }
+
+    /// Only accepts servers whose certificate chain contains a certificate with one of these
+    /// SHA-256 fingerprints (hex, `:` separators allowed). Needs the `tls-pinning` feature;
+    /// without it `SupabaseClientWrapper::new` fails with `SupabaseError::Config`.
+    pub fn with_pinned_cert_sha256<I, S>(mut self, fingerprints: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tls
+            .pinned_cert_sha256
+            .extend(fingerprints.into_iter().map(Into::into));
+        self
+    }
+
+    /// The auth-related options in the form `supabase_rust_auth` expects.
+    #[cfg(feature = "auth")]
+    pub fn auth_options(&self) -> AuthOptions {
+        AuthOptions {
+            auto_refresh_token: self.auto_refresh_token,
+            persist_session: self.persist_session,
+            detect_session_in_url: self.detect_session_in_url,
+            allowed_redirect_hosts: self.allowed_redirect_hosts.clone(),
+        }
+    }
+}
+
+/// TLS trust settings for the HTTP client built by the facade. An injected `http_client`
+/// is used as is; configure it yourself.
+///
+/// Pinning verifies the chain as usual (built-in roots plus `root_certificates`) and then
+/// requires a leaf or intermediate certificate whose DER SHA-256 is in `pinned_cert_sha256`.
+/// It switches the client to rustls. Realtime websockets are not covered.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsOptions {
+    /// PEM-encoded certificates trusted in addition to the built-in roots.
+    pub root_certificates: Vec<String>,
+    /// Hex SHA-256 fingerprints of accepted certificates. Empty disables pinning.
+    pub pinned_cert_sha256: Vec<String>,
+}
+
+/// Connection tuning for the HTTP client built by the facade. `None` keeps reqwest's default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    pub pool_idle_timeout: Option<Duration>,
+    pub pool_max_idle_per_host: Option<usize>,
+    pub tcp_keepalive: Option<Duration>,
+    pub http2_adaptive_window: bool,
+    pub http2_prior_knowledge: bool,
+}
+
+impl ConnectionOptions {
+    pub(crate) fn apply(&self, mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(interval);
+        }
+        if self.http2_adaptive_window {
+            builder = builder.http2_adaptive_window(true);
+        }
+        if self.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        builder
+    }
+}
+
+/// Per-service URL overrides. `None` uses the project URL from `SupabaseConfig`.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceUrls {
+    pub auth: Option<Url>,
+    pub rest: Option<Url>,
+    /// Read replica of PostgREST, used by queries routed with `use_read_replica()`.
+    pub rest_replica: Option<Url>,
+    pub realtime: Option<Url>,
+    pub storage: Option<Url>,
+    pub functions: Option<Url>,
+}
+
+/// Default retry policy (exponential backoff, capped at `max_backoff`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum attempts including the first one.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(feature = "storage")]
+impl From<&RetryPolicy> for PartRetryPolicy {
+    fn from(policy: &RetryPolicy) -> Self {
+        PartRetryPolicy {
+            max_attempts: policy.max_attempts,
+            initial_backoff: policy.initial_backoff,
+            max_backoff: policy.max_backoff,
+        }
+    }
+}
+
+/// Circuit breaker thresholds, applied to each service separately.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitBreakerOptions {
+    /// Consecutive service-unavailable failures that open the circuit.
+    pub failure_threshold: u32,
+    /// How long an open circuit fails fast before letting a probe through.
+    pub cool_down: Duration,
+}
+
+impl Default for CircuitBreakerOptions {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+        }
+    }
+}
+
+impl CircuitBreakerOptions {
+    pub fn new(failure_threshold: u32, cool_down: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cool_down,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+// src/prelude.rs
+
+//! Commonly used types, importable with a single `use`.
+//!
+//! ```
+//! use supabase_rust_client::prelude::*;
+//! ```
+//!
+//! The import story is:
+//! - `supabase_rust_client::prelude::*` for everyday application code.
+//! - `supabase_rust_client::{auth, postgrest, realtime, storage, functions}` for
+//!   anything else in a sub-crate, instead of depending on the sub-crates directly.
+//!
+//! Types of a service whose cargo feature is disabled are left out.
+//!
+//! Names are kept unique across the prelude. Where two crates define a type with
+//! the same name, the sub-crate type is re-exported with a prefix (for example
+//! [`AuthUser`] next to the facade's [`User`]).
+
+pub use crate::circuit::{CircuitState, Service, ServiceFailure};
+pub use crate::client::{SupabaseClientWrapper, SupabaseConfig};
+pub use crate::error::SupabaseError;
+pub use crate::models::{AuthCredentials, Item, User};
+pub use crate::offline::{FileWriteQueue, OfflineQueue, WriteOutcome, WriteQueue};
+pub use crate::options::{
+    CircuitBreakerOptions, ClientOptions, ConnectionOptions, RetryPolicy, ServiceUrls, TlsOptions,
+};
+pub use crate::registry::TableRegistry;
+#[cfg(feature = "realtime")]
+pub use crate::storage_events::{
+    StorageObjectChange, StorageObjectEvent, StorageObjectFilter, StorageObjectSubscription,
+};
+#[cfg(feature = "realtime")]
+pub use crate::synced_table::{SyncedTable, SyncedTableConfig, TableChange};
+
+#[cfg(feature = "auth")]
+pub use supabase_rust_auth::{AuthError, AuthOptions, Session, User as AuthUser};
+pub use supabase_rust_core::{Page, Paged, Redacted};
+#[cfg(feature = "functions")]
+pub use supabase_rust_functions::{FunctionOptions, FunctionSpec, FunctionsError, ResponseType};
+pub use supabase_rust_postgrest::{
+    FilterValue, IsolationLevel, PostgrestError, ReadPreference, SortOrder, TransactionMode,
+};
+#[cfg(feature = "realtime")]
+pub use supabase_rust_realtime::{DatabaseFilter, FilterOperator, RealtimeError};
+#[cfg(feature = "storage")]
+pub use supabase_rust_storage::{FileOptions, ImageTransformOptions, StorageError, UploadMode};
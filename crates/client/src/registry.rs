@@ -0,0 +1,196 @@
+// src/registry.rs
+
+//! Known table names, used to reject typos before a request is sent.
+//!
+//! Validation is opt-in. Build a [`TableRegistry`] by hand or from the PostgREST OpenAPI
+//! document ([`SupabaseClientWrapper::fetch_table_registry`]), put it in
+//! [`ClientOptions::with_table_registry`], and then either call
+//! [`SupabaseClientWrapper::from_checked`] or turn on
+//! [`ClientOptions::with_validate_tables`] so that every `from()` is checked.
+//!
+//! ```
+//! use supabase_rust_client::prelude::*;
+//!
+//! #[derive(serde::Deserialize)]
+//! struct UserRow {
+//!     id: i64,
+//! }
+//!
+//! let registry = TableRegistry::new()
+//!     .register::<UserRow>("user_profiles")
+//!     .register_name("audit_log");
+//! let error = registry.check("user_profils").unwrap_err();
+//! assert_eq!(
+//!     error.to_string(),
+//!     "Unknown table \"user_profils\" (did you mean \"user_profiles\"?)"
+//! );
+//! ```
+//!
+//! [`SupabaseClientWrapper::fetch_table_registry`]: crate::client::SupabaseClientWrapper::fetch_table_registry
+//! [`SupabaseClientWrapper::from_checked`]: crate::client::SupabaseClientWrapper::from_checked
+//! [`ClientOptions::with_table_registry`]: crate::options::ClientOptions::with_table_registry
+//! [`ClientOptions::with_validate_tables`]: crate::options::ClientOptions::with_validate_tables
+
+use crate::error::{Result, SupabaseError};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The set of table (and view) names a client is allowed to query.
+#[derive(Debug, Clone, Default)]
+pub struct TableRegistry {
+    /// Table name to the Rust row type registered for it, if any.
+    tables: BTreeMap<String, Option<&'static str>>,
+}
+
+impl TableRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `table`, recording `T` as its row type.
+    pub fn register<T>(mut self, table: &str) -> Self {
+        self.tables
+            .insert(table.to_string(), Some(std::any::type_name::<T>()));
+        self
+    }
+
+    /// Registers `table` without a row type.
+    pub fn register_name(mut self, table: &str) -> Self {
+        self.tables.entry(table.to_string()).or_insert(None);
+        self
+    }
+
+    /// Builds a registry from the OpenAPI document PostgREST serves at `/rest/v1/`.
+    ///
+    /// Reads the names under `definitions` (Swagger 2, what PostgREST returns) or
+    /// `components.schemas` (OpenAPI 3).
+    pub fn from_openapi(document: &Value) -> Self {
+        let schemas = document
+            .get("definitions")
+            .or_else(|| document.pointer("/components/schemas"))
+            .and_then(Value::as_object);
+        let mut registry = Self::new();
+        for name in schemas.into_iter().flat_map(|schemas| schemas.keys()) {
+            registry = registry.register_name(name);
+        }
+        registry
+    }
+
+    pub fn contains(&self, table: &str) -> bool {
+        self.tables.contains_key(table)
+    }
+
+    /// Registered names, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        self.tables.keys().map(String::as_str).collect()
+    }
+
+    /// The type name recorded by `register::<T>()`, if any.
+    pub fn row_type(&self, table: &str) -> Option<&'static str> {
+        self.tables.get(table).copied().flatten()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Returns `SupabaseError::UnknownTable` for names that are not registered.
+    pub fn check(&self, table: &str) -> Result<()> {
+        if self.contains(table) {
+            return Ok(());
+        }
+        Err(SupabaseError::UnknownTable {
+            table: table.to_string(),
+            suggestion: self.suggest(table).map(str::to_string),
+        })
+    }
+
+    /// The registered name closest to `table`, if it is close enough to be a likely typo.
+    ///
+    /// "Close enough" is an edit distance of at most a third of the name's length (minimum 2).
+    pub fn suggest(&self, table: &str) -> Option<&str> {
+        let max_distance = (table.chars().count() / 3).max(2);
+        self.tables
+            .keys()
+            .map(|name| (levenshtein(table, name), name))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, name)| name.as_str())
+    }
+}
+
+// Edit distance counting insertions, deletions and substitutions of chars.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("user_profils", "user_profiles"), 1);
+        assert_eq!(levenshtein("todos", ""), 5);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        struct UserRow;
+        let registry = TableRegistry::new()
+            .register::<UserRow>("user_profiles")
+            .register_name("users")
+            .register_name("audit_log");
+
+        assert!(registry.check("users").is_ok());
+        assert_eq!(registry.suggest("user_profils"), Some("user_profiles"));
+        assert_eq!(registry.suggest("usres"), Some("users"));
+        assert_eq!(registry.suggest("invoices"), None);
+        assert!(matches!(
+            registry.check("invoices"),
+            Err(SupabaseError::UnknownTable {
+                suggestion: None,
+                ..
+            })
+        ));
+        assert!(registry
+            .row_type("user_profiles")
+            .unwrap()
+            .ends_with("UserRow"));
+        assert_eq!(registry.row_type("users"), None);
+    }
+
+    #[test]
+    fn test_from_openapi() {
+        let registry = TableRegistry::from_openapi(&json!({
+            "swagger": "2.0",
+            "definitions": { "todos": {}, "profiles": {} },
+            "paths": {}
+        }));
+        assert_eq!(registry.names(), vec!["profiles", "todos"]);
+
+        let registry = TableRegistry::from_openapi(&json!({
+            "openapi": "3.0.0",
+            "components": { "schemas": { "todos": {} } }
+        }));
+        assert_eq!(registry.names(), vec!["todos"]);
+    }
+}
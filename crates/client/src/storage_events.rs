@@ -0,0 +1,239 @@
+// src/storage_events.rs
+
+//! Typed Realtime events for objects in a storage bucket.
+//!
+//! Storage keeps object metadata in the `storage.objects` table, so uploads, overwrites and
+//! deletes arrive as `postgres_changes` on that table. [`subscribe`] listens for them and
+//! decodes each one into a [`StorageObjectChange`].
+//!
+//! # Database setup
+//!
+//! `storage.objects` is not part of the `supabase_realtime` publication by default, and
+//! Realtime only delivers rows the subscribing role may `select`:
+//!
+//! ```sql
+//! alter publication supabase_realtime add table storage.objects;
+//! -- delete events only carry bucket_id and name with a full replica identity
+//! alter table storage.objects replica identity full;
+//! create policy "read avatars" on storage.objects for select
+//!   to authenticated using (bucket_id = 'avatars');
+//! ```
+//!
+//! Without this the subscription succeeds but no events arrive. The server's join reply
+//! tells us so: such warnings are logged when subscribing and are available from
+//! [`StorageObjectSubscription::warnings`].
+//!
+//! # Filtering
+//!
+//! The bucket is filtered by the server (`bucket_id=eq.<bucket>`). Realtime filters have no
+//! prefix operator and do not apply to deletes at all, so the name prefix, and the bucket
+//! of deletes, are checked here. Deletes whose `old_record` lacks `bucket_id` and `name`
+//! (no full replica identity) are dropped.
+
+use crate::error::{Result, SupabaseError};
+use serde::Deserialize;
+use serde_json::Value;
+use supabase_rust_realtime::{
+    ChannelEvent, DatabaseChanges, PostgresChangesPayload, RealtimeClient, Subscription,
+    SubscriptionWarning,
+};
+use tokio::sync::{broadcast, mpsc};
+
+/// A row of `storage.objects` as sent by Realtime.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StorageObjectEvent {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub bucket_id: String,
+    /// Path of the object inside the bucket, e.g. `uploads/2024/cat.png`.
+    pub name: String,
+    /// Set by storage: `size`, `mimetype`, `eTag`, `cacheControl`, ...
+    #[serde(default)]
+    pub metadata: Option<Value>,
+    /// User that uploaded the object (`owner` is deprecated in favour of `owner_id`).
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub owner_id: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+impl StorageObjectEvent {
+    /// Size in bytes from `metadata.size`.
+    pub fn size(&self) -> Option<u64> {
+        self.metadata.as_ref()?.get("size")?.as_u64()
+    }
+
+    /// Content type from `metadata.mimetype`.
+    pub fn mime_type(&self) -> Option<&str> {
+        self.metadata.as_ref()?.get("mimetype")?.as_str()
+    }
+}
+
+/// An insert, update or delete of a storage object.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageObjectChange {
+    Created(StorageObjectEvent),
+    /// An overwrite (upsert) or a metadata update.
+    Updated(StorageObjectEvent),
+    /// The object as it was before the delete.
+    Deleted(StorageObjectEvent),
+}
+
+impl StorageObjectChange {
+    /// Decodes a `postgres_changes` payload on `storage.objects`.
+    ///
+    /// `Ok(None)` for events of other tables and deletes without the full old row.
+    pub fn from_payload(payload: &Value) -> Result<Option<Self>> {
+        let change = PostgresChangesPayload::<StorageObjectEvent>::from_value(payload)?;
+        if change.schema != "storage" || change.table != "objects" {
+            return Ok(None);
+        }
+        let change = match change.event_type.as_str() {
+            "INSERT" => change.new.map(Self::Created),
+            "UPDATE" => change.new.map(Self::Updated),
+            "DELETE" => change.old.into_record().map(Self::Deleted),
+            _ => None,
+        };
+        Ok(change)
+    }
+
+    pub fn object(&self) -> &StorageObjectEvent {
+        match self {
+            Self::Created(object) | Self::Updated(object) | Self::Deleted(object) => object,
+        }
+    }
+
+    pub fn into_object(self) -> StorageObjectEvent {
+        match self {
+            Self::Created(object) | Self::Updated(object) | Self::Deleted(object) => object,
+        }
+    }
+}
+
+/// Which object changes to subscribe to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StorageObjectFilter {
+    pub bucket_id: String,
+    /// Only objects whose name starts with this (checked on the client).
+    pub prefix: Option<String>,
+    /// `Insert` / `Update` / `Delete`; empty means all three.
+    pub events: Vec<ChannelEvent>,
+}
+
+impl StorageObjectFilter {
+    /// All changes in `bucket_id`.
+    pub fn new(bucket_id: &str) -> Self {
+        Self {
+            bucket_id: bucket_id.to_string(),
+            prefix: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(prefix.to_string());
+        self
+    }
+
+    pub fn with_event(mut self, event: ChannelEvent) -> Self {
+        if !self.events.contains(&event) {
+            self.events.push(event);
+        }
+        self
+    }
+
+    fn topic(&self) -> String {
+        format!("realtime:storage:objects:{}", self.bucket_id)
+    }
+
+    fn changes(&self) -> DatabaseChanges {
+        let changes = self.events.iter().fold(
+            DatabaseChanges::new("objects").schema("storage"),
+            |changes, event| changes.event(*event),
+        );
+        changes.eq("bucket_id", self.bucket_id.as_str())
+    }
+
+    fn matches(&self, object: &StorageObjectEvent) -> bool {
+        object.bucket_id == self.bucket_id
+            && self
+                .prefix
+                .as_deref()
+                .is_none_or(|prefix| object.name.starts_with(prefix))
+    }
+}
+
+/// A live subscription to storage object changes. Dropping it stops the events.
+pub struct StorageObjectSubscription {
+    subscriptions: Vec<Subscription>,
+    events: mpsc::UnboundedReceiver<StorageObjectChange>,
+}
+
+impl StorageObjectSubscription {
+    /// The next change, or `None` once the Realtime client is gone.
+    pub async fn recv(&mut self) -> Option<StorageObjectChange> {
+        self.events.recv().await
+    }
+
+    /// Warnings from the last join, e.g. `NoBindingsConfirmed` when `storage.objects` is not
+    /// in the publication or RLS hides it.
+    pub fn warnings(&self) -> Vec<SubscriptionWarning> {
+        self.subscriptions
+            .iter()
+            .flat_map(Subscription::warnings)
+            .collect()
+    }
+
+    /// Warnings from later joins (after reconnects).
+    pub fn warning_events(&self) -> Option<broadcast::Receiver<SubscriptionWarning>> {
+        self.subscriptions.first().map(Subscription::warning_events)
+    }
+
+    /// The underlying channel subscriptions (stats, handler errors, ...).
+    pub fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+}
+
+/// Subscribes to changes of objects matching `filter`. See the module docs for the setup.
+pub async fn subscribe(
+    realtime: &RealtimeClient,
+    filter: StorageObjectFilter,
+) -> Result<StorageObjectSubscription> {
+    let (events_tx, events) = mpsc::unbounded_channel();
+    let topic = filter.topic();
+    let changes = filter.changes();
+    let subscriptions = realtime
+        .channel(&topic)
+        .on(changes, move |payload| {
+            match StorageObjectChange::from_payload(&payload.data) {
+                Ok(Some(change)) if filter.matches(change.object()) => {
+                    // The receiver only goes away when the subscription is dropped
+                    let _ = events_tx.send(change);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = %e, "Ignoring malformed storage.objects event"),
+            }
+        })
+        .subscribe()
+        .await
+        .map_err(|e| SupabaseError::Realtime(e.to_string()))?;
+
+    let subscription = StorageObjectSubscription {
+        subscriptions,
+        events,
+    };
+    for warning in subscription.warnings() {
+        tracing::warn!(
+            %topic,
+            %warning,
+            "storage object events may never arrive; storage.objects must be in the \
+             supabase_realtime publication and readable by this role"
+        );
+    }
+    Ok(subscription)
+}
@@ -0,0 +1,396 @@
+// src/synced_table.rs
+
+//! Keeps an in-memory copy of a table current by combining a PostgREST snapshot
+//! with Realtime `postgres_changes` events.
+//!
+//! The channel is subscribed *before* the snapshot is fetched. Events that arrive
+//! while the fetch is in flight are buffered and applied on top of the snapshot,
+//! so nothing that happens during the fetch is lost. After a reconnect the channel
+//! is resubscribed and the snapshot is fetched again.
+
+use crate::error::{Result, SupabaseError};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use supabase_rust_postgrest::PostgrestClient;
+use supabase_rust_realtime::{
+    ConnectionState, DatabaseChanges, Payload, PostgresChangesPayload, RealtimeClient, Subscription,
+};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+/// Decides whether an incoming row replaces the row currently held for the same key.
+pub trait ConflictResolver: Send + Sync {
+    /// `current` is `None` when the key is not in the cache yet.
+    fn should_apply(&self, current: Option<&Value>, incoming: &Value) -> bool;
+}
+
+/// Always applies the latest event (the default).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LatestEventWins;
+
+impl ConflictResolver for LatestEventWins {
+    fn should_apply(&self, _current: Option<&Value>, _incoming: &Value) -> bool {
+        true
+    }
+}
+
+/// Applies a row only if `column` (e.g. `updated_at`) is not older than the cached value.
+///
+/// Numbers are compared numerically and strings lexicographically, which is correct for
+/// timestamps in one consistent ISO 8601 format. Rows missing the column are applied.
+#[derive(Debug, Clone)]
+pub struct NewerColumnWins {
+    column: String,
+}
+
+impl NewerColumnWins {
+    pub fn new(column: &str) -> Self {
+        Self {
+            column: column.to_string(),
+        }
+    }
+}
+
+impl ConflictResolver for NewerColumnWins {
+    fn should_apply(&self, current: Option<&Value>, incoming: &Value) -> bool {
+        let current = current.and_then(|row| row.get(&self.column));
+        match (current, incoming.get(&self.column)) {
+            (Some(current), Some(incoming)) => {
+                compare_values(incoming, current) != Some(Ordering::Less)
+            }
+            _ => true,
+        }
+    }
+}
+
+fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+/// What to subscribe to and how rows are keyed.
+#[derive(Clone)]
+pub struct SyncedTableConfig {
+    pub topic: String,
+    pub changes: DatabaseChanges,
+    /// Column used as the cache key (its value is stringified for non-string keys).
+    pub primary_key: String,
+    pub resolver: Arc<dyn ConflictResolver>,
+}
+
+impl SyncedTableConfig {
+    /// Syncs `public.<table>` on the `realtime:public:<table>` topic.
+    pub fn new(table: &str, primary_key: &str) -> Self {
+        Self {
+            topic: format!("realtime:public:{}", table),
+            changes: DatabaseChanges::new(table),
+            primary_key: primary_key.to_string(),
+            resolver: Arc::new(LatestEventWins),
+        }
+    }
+
+    pub fn with_topic(mut self, topic: &str) -> Self {
+        self.topic = topic.to_string();
+        self
+    }
+
+    /// Replaces the change filter (schema, events, row filters).
+    pub fn with_changes(mut self, changes: DatabaseChanges) -> Self {
+        self.changes = changes;
+        self
+    }
+
+    pub fn with_resolver<R: ConflictResolver + 'static>(mut self, resolver: R) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+}
+
+/// A change applied to the cache.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableChange<T> {
+    Inserted {
+        key: String,
+        row: T,
+    },
+    Updated {
+        key: String,
+        row: T,
+    },
+    Deleted {
+        key: String,
+    },
+    /// The snapshot was (re)fetched and now holds `rows` rows.
+    Resynced {
+        rows: usize,
+    },
+}
+
+struct CachedRow<T> {
+    value: Value,
+    row: T,
+}
+
+struct State<T> {
+    rows: HashMap<String, CachedRow<T>>,
+    // Latest commit timestamp applied per key, to drop stale or duplicate events
+    last_commit: HashMap<String, String>,
+}
+
+struct Shared<T> {
+    state: RwLock<State<T>>,
+    changes: broadcast::Sender<TableChange<T>>,
+    primary_key: String,
+    resolver: Arc<dyn ConflictResolver>,
+}
+
+/// An in-memory copy of a table, kept current by Realtime events.
+///
+/// Dropping the `SyncedTable` stops syncing and unsubscribes from the channel.
+pub struct SyncedTable<T> {
+    shared: Arc<Shared<T>>,
+    task: JoinHandle<()>,
+}
+
+impl<T> SyncedTable<T>
+where
+    T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    /// Subscribes to the channel, fetches `query` and starts applying events.
+    ///
+    /// Returns once the snapshot and the events buffered during the fetch are applied.
+    pub async fn start(
+        realtime: &RealtimeClient,
+        query: PostgrestClient,
+        config: SyncedTableConfig,
+    ) -> Result<Self> {
+        let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+        let mut states = realtime.on_state_change();
+        let subscriptions = subscribe(realtime, &config, events_tx.clone()).await?;
+
+        let (changes, _) = broadcast::channel(256);
+        let shared = Arc::new(Shared {
+            state: RwLock::new(State {
+                rows: HashMap::new(),
+                last_commit: HashMap::new(),
+            }),
+            changes,
+            primary_key: config.primary_key.clone(),
+            resolver: config.resolver.clone(),
+        });
+
+        shared.load_snapshot(&query).await?;
+        while let Ok(payload) = events_rx.try_recv() {
+            shared.apply(&payload);
+        }
+
+        let realtime = realtime.clone();
+        let task_shared = shared.clone();
+        let task = tokio::spawn(async move {
+            let shared = task_shared;
+            let mut subscriptions = subscriptions;
+            let mut gap = false;
+            let mut states_open = true;
+            loop {
+                tokio::select! {
+                    payload = events_rx.recv() => match payload {
+                        Some(payload) => shared.apply(&payload),
+                        None => break,
+                    },
+                    state = states.recv(), if states_open => match state {
+                        Ok(ConnectionState::Connected) if gap => {
+                            gap = false;
+                            tracing::info!(topic = %config.topic, "Reconnected, resyncing table");
+                            drop(std::mem::take(&mut subscriptions));
+                            match subscribe(&realtime, &config, events_tx.clone()).await {
+                                Ok(new_subscriptions) => subscriptions = new_subscriptions,
+                                Err(e) => tracing::warn!(error = %e, "Failed to resubscribe"),
+                            }
+                            if let Err(e) = shared.load_snapshot(&query).await {
+                                tracing::warn!(error = %e, "Failed to refetch snapshot");
+                            }
+                        }
+                        Ok(ConnectionState::Connected) => {}
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => gap = true,
+                        Err(broadcast::error::RecvError::Closed) => states_open = false,
+                    },
+                }
+            }
+        });
+
+        Ok(Self { shared, task })
+    }
+
+    /// The row with primary key `key`.
+    pub fn get(&self, key: &str) -> Option<T> {
+        let state = self.shared.state.read().ok()?;
+        state.rows.get(key).map(|cached| cached.row.clone())
+    }
+
+    /// A copy of all rows with their keys (in no particular order).
+    pub fn iter(&self) -> std::vec::IntoIter<(String, T)> {
+        let rows = match self.shared.state.read() {
+            Ok(state) => state
+                .rows
+                .iter()
+                .map(|(key, cached)| (key.clone(), cached.row.clone()))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        rows.into_iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared
+            .state
+            .read()
+            .map(|state| state.rows.len())
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Notifications for every change applied after this call.
+    pub fn changes(&self) -> broadcast::Receiver<TableChange<T>> {
+        self.shared.changes.subscribe()
+    }
+}
+
+impl<T> Drop for SyncedTable<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn subscribe(
+    realtime: &RealtimeClient,
+    config: &SyncedTableConfig,
+    events: mpsc::UnboundedSender<Payload>,
+) -> Result<Vec<Subscription>> {
+    realtime
+        .channel(&config.topic)
+        .on(config.changes.clone(), move |payload| {
+            // The receiver only goes away when the SyncedTable is dropped
+            let _ = events.send(payload);
+        })
+        .subscribe()
+        .await
+        .map_err(|e| SupabaseError::Realtime(e.to_string()))
+}
+
+impl<T> Shared<T>
+where
+    T: DeserializeOwned + Clone,
+{
+    fn key_of(&self, row: &Value) -> Option<String> {
+        match row.get(&self.primary_key)? {
+            Value::Null => None,
+            Value::String(key) => Some(key.clone()),
+            other => Some(other.to_string()),
+        }
+    }
+
+    async fn load_snapshot(&self, query: &PostgrestClient) -> Result<()> {
+        let values = query.execute::<Value>().await?;
+        let mut rows = HashMap::with_capacity(values.len());
+        for value in values {
+            let Some(key) = self.key_of(&value) else {
+                tracing::warn!(primary_key = %self.primary_key, "Snapshot row without primary key");
+                continue;
+            };
+            let row = serde_json::from_value(value.clone()).map_err(SupabaseError::Json)?;
+            rows.insert(key, CachedRow { value, row });
+        }
+
+        let count = rows.len();
+        if let Ok(mut state) = self.state.write() {
+            state.rows = rows;
+        }
+        let _ = self.changes.send(TableChange::Resynced { rows: count });
+        Ok(())
+    }
+
+    fn apply(&self, payload: &Payload) {
+        let change = match PostgresChangesPayload::<Value>::from_value(&payload.data) {
+            Ok(change) => change,
+            Err(e) => {
+                tracing::warn!(error = %e, "Ignoring malformed postgres_changes payload");
+                return;
+            }
+        };
+        let Ok(mut state) = self.state.write() else {
+            return;
+        };
+
+        let (key, incoming) = match change.event_type.as_str() {
+            "INSERT" | "UPDATE" => {
+                let Some(new) = change.new else { return };
+                match self.key_of(&new) {
+                    Some(key) => (key, Some(new)),
+                    None => return,
+                }
+            }
+            "DELETE" => match change.old.get(&self.primary_key) {
+                Some(Value::String(key)) => (key.clone(), None),
+                Some(Value::Null) | None => return,
+                Some(other) => (other.to_string(), None),
+            },
+            _ => return,
+        };
+
+        if let Some(commit) = &change.commit_timestamp {
+            if let Some(last) = state.last_commit.get(&key) {
+                if commit < last {
+                    tracing::debug!(%key, "Dropping stale event");
+                    return;
+                }
+            }
+            state.last_commit.insert(key.clone(), commit.clone());
+        }
+
+        let notification = match incoming {
+            Some(value) => {
+                let current = state.rows.get(&key).map(|cached| &cached.value);
+                if !self.resolver.should_apply(current, &value) {
+                    return;
+                }
+                let row: T = match serde_json::from_value(value.clone()) {
+                    Ok(row) => row,
+                    Err(e) => {
+                        tracing::warn!(%key, error = %e, "Ignoring row that does not deserialize");
+                        return;
+                    }
+                };
+                let existed = state
+                    .rows
+                    .insert(
+                        key.clone(),
+                        CachedRow {
+                            value,
+                            row: row.clone(),
+                        },
+                    )
+                    .is_some();
+                if existed {
+                    TableChange::Updated { key, row }
+                } else {
+                    TableChange::Inserted { key, row }
+                }
+            }
+            None => match state.rows.remove(&key) {
+                Some(_) => TableChange::Deleted { key },
+                None => return,
+            },
+        };
+        drop(state);
+        let _ = self.changes.send(notification);
+    }
+}
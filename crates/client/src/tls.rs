@@ -0,0 +1,181 @@
+// src/tls.rs
+
+//! Custom root certificates and certificate pinning for the HTTP client built by the facade
+//! (see [`ClientOptions::with_root_certificate`](crate::options::ClientOptions::with_root_certificate)
+//! and [`ClientOptions::with_pinned_cert_sha256`](crate::options::ClientOptions::with_pinned_cert_sha256)).
+//!
+//! Root certificates are added to the platform trust store. Pinning needs the `tls-pinning`
+//! feature, which switches the client to rustls: the chain is verified as usual against the
+//! Mozilla roots plus the added certificates, then one certificate of the chain (leaf or
+//! intermediate) must match a pinned SHA-256 fingerprint of its DER encoding. Without the
+//! feature, building a client with pins fails instead of silently connecting unpinned.
+//!
+//! All sub-clients share this HTTP client. Realtime websockets are not covered.
+
+use crate::error::{Result, SupabaseError};
+use crate::options::TlsOptions;
+use reqwest::ClientBuilder;
+
+/// Parses a SHA-256 fingerprint written as hex, optionally separated by `:`
+/// (`openssl x509 -noout -fingerprint -sha256` output works as is).
+pub(crate) fn parse_fingerprint(fingerprint: &str) -> Result<[u8; 32]> {
+    let invalid =
+        || SupabaseError::Config(format!("invalid SHA-256 fingerprint {:?}", fingerprint));
+    let hex: Vec<u8> = fingerprint.trim().bytes().filter(|b| *b != b':').collect();
+    if hex.len() != 64 {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; 32];
+    for (byte, pair) in bytes.iter_mut().zip(hex.chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+impl TlsOptions {
+    pub(crate) fn apply(&self, mut builder: ClientBuilder) -> Result<ClientBuilder> {
+        if !self.pinned_cert_sha256.is_empty() {
+            let pins = self
+                .pinned_cert_sha256
+                .iter()
+                .map(|pin| parse_fingerprint(pin))
+                .collect::<Result<Vec<_>>>()?;
+            return pinned::apply(builder, &self.root_certificates, pins);
+        }
+        for pem in &self.root_certificates {
+            let certificate = reqwest::Certificate::from_pem(pem.as_bytes())
+                .map_err(|e| SupabaseError::Config(format!("root certificate: {}", e)))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        Ok(builder)
+    }
+}
+
+#[cfg(not(feature = "tls-pinning"))]
+mod pinned {
+    use super::*;
+
+    pub(super) fn apply(
+        _builder: ClientBuilder,
+        _root_certificates: &[String],
+        _pins: Vec<[u8; 32]>,
+    ) -> Result<ClientBuilder> {
+        Err(SupabaseError::Config(
+            "pinned_cert_sha256 requires the `tls-pinning` feature of supabase-rust-client"
+                .to_string(),
+        ))
+    }
+}
+
+#[cfg(feature = "tls-pinning")]
+mod pinned {
+    use super::*;
+    use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+    use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+    use sha2::{Digest, Sha256};
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    pub(super) fn apply(
+        builder: ClientBuilder,
+        root_certificates: &[String],
+        pins: Vec<[u8; 32]>,
+    ) -> Result<ClientBuilder> {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+        for pem in root_certificates {
+            let ders = rustls_pemfile::certs(&mut pem.as_bytes())
+                .map_err(|e| SupabaseError::Config(format!("root certificate: {}", e)))?;
+            if ders.is_empty() {
+                return Err(SupabaseError::Config(
+                    "root certificate: no PEM certificate found".to_string(),
+                ));
+            }
+            for der in ders {
+                roots
+                    .add(&Certificate(der))
+                    .map_err(|e| SupabaseError::Config(format!("root certificate: {}", e)))?;
+            }
+        }
+        let verifier = PinnedVerifier {
+            inner: WebPkiVerifier::new(roots, None),
+            pins,
+        };
+        let mut config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(verifier))
+            .with_no_client_auth();
+        // reqwest leaves ALPN to a preconfigured config
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(builder.use_preconfigured_tls(config))
+    }
+
+    /// Regular WebPKI verification, then a fingerprint check on the presented chain.
+    struct PinnedVerifier {
+        inner: WebPkiVerifier,
+        pins: Vec<[u8; 32]>,
+    }
+
+    impl ServerCertVerifier for PinnedVerifier {
+        fn verify_server_cert(
+            &self,
+            end_entity: &Certificate,
+            intermediates: &[Certificate],
+            server_name: &ServerName,
+            scts: &mut dyn Iterator<Item = &[u8]>,
+            ocsp_response: &[u8],
+            now: SystemTime,
+        ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+            let verified = self.inner.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?;
+            let pinned = std::iter::once(end_entity)
+                .chain(intermediates)
+                .any(|certificate| {
+                    let fingerprint: [u8; 32] = Sha256::digest(&certificate.0).into();
+                    self.pins.contains(&fingerprint)
+                });
+            if !pinned {
+                tracing::warn!(
+                    ?server_name,
+                    "server certificate matches no pinned fingerprint"
+                );
+                return Err(rustls::Error::General(
+                    "server certificate matches no pinned fingerprint".to_string(),
+                ));
+            }
+            Ok(verified)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fingerprint() {
+        let hex = "5E:FF:56:A2:AF:15:88:25:47:A2:7B:F1:6E:A7:B3:E5:64:A3:E9:F3:C0:D2:4E:84:9F:6F:BF:C5:97:46:0E:83";
+        let bytes = parse_fingerprint(hex).unwrap();
+        assert_eq!(bytes[0], 0x5e);
+        assert_eq!(bytes[31], 0x83);
+        assert_eq!(
+            parse_fingerprint(&hex.replace(':', "").to_lowercase()).unwrap(),
+            bytes
+        );
+        assert!(parse_fingerprint("5E:FF").is_err());
+        assert!(parse_fingerprint(&"zz".repeat(32)).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+// crates/client/tests/circuit_breaker_test.rs
+
+use serde_json::{json, Value};
+use std::time::Duration;
+use supabase_rust_client::prelude::*;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const COOL_DOWN: Duration = Duration::from_millis(200);
+
+fn client_with(server: &MockServer, options: ClientOptions) -> SupabaseClientWrapper {
+    let config = SupabaseConfig::new(&server.uri(), "anon-key".to_string())
+        .unwrap()
+        .with_options(options);
+    SupabaseClientWrapper::new(config).unwrap()
+}
+
+async fn fetch_items(client: &SupabaseClientWrapper) -> Result<Vec<Value>, SupabaseError> {
+    let items = client.from("items").await?;
+    client
+        .guarded(Service::Rest, items.execute::<Value>())
+        .await
+}
+
+async fn request_count(server: &MockServer) -> usize {
+    server.received_requests().await.unwrap().len()
+}
+
+#[tokio::test]
+async fn test_circuit_opens_fails_fast_and_recovers_after_probe() {
+    let server = MockServer::start().await;
+    // Scripted outage: three 503s, then the service is back (slowly)
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/items"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("upstream unavailable"))
+        .up_to_n_times(3)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/items"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!([{ "id": 1 }]))
+                .set_delay(Duration::from_millis(100)),
+        )
+        .mount(&server)
+        .await;
+
+    let client = client_with(
+        &server,
+        ClientOptions::new().with_circuit_breaker(CircuitBreakerOptions::new(2, COOL_DOWN)),
+    );
+    // State is shared with clones of the facade
+    let clone = client.clone();
+
+    // closed: failures below the threshold still reach the server
+    assert!(matches!(
+        fetch_items(&client).await,
+        Err(SupabaseError::Postgrest(_))
+    ));
+    assert_eq!(
+        client.service_health()[&Service::Rest],
+        CircuitState::Closed
+    );
+    assert!(fetch_items(&clone).await.is_err());
+
+    // open: calls fail fast without a request
+    assert!(matches!(
+        clone.service_health()[&Service::Rest],
+        CircuitState::Open { .. }
+    ));
+    match fetch_items(&client).await {
+        Err(SupabaseError::ServiceUnavailable { service, retry_in }) => {
+            assert_eq!(service, Service::Rest);
+            assert!(retry_in <= COOL_DOWN);
+        }
+        other => panic!("expected ServiceUnavailable, got {:?}", other),
+    }
+    assert_eq!(request_count(&server).await, 2);
+    // Other services have their own circuits
+    assert_eq!(
+        client.service_health()[&Service::Storage],
+        CircuitState::Closed
+    );
+
+    // half-open: the failed probe opens the circuit again
+    tokio::time::sleep(COOL_DOWN + Duration::from_millis(50)).await;
+    assert_eq!(
+        client.service_health()[&Service::Rest],
+        CircuitState::HalfOpen
+    );
+    assert!(matches!(
+        fetch_items(&client).await,
+        Err(SupabaseError::Postgrest(_))
+    ));
+    assert!(matches!(
+        client.service_health()[&Service::Rest],
+        CircuitState::Open { .. }
+    ));
+    assert_eq!(request_count(&server).await, 3);
+
+    // half-open again: only one probe goes out, concurrent calls fail fast
+    tokio::time::sleep(COOL_DOWN + Duration::from_millis(50)).await;
+    let (probe, concurrent) = tokio::join!(fetch_items(&client), async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        fetch_items(&clone).await
+    });
+    assert_eq!(probe.unwrap(), vec![json!({ "id": 1 })]);
+    assert!(matches!(
+        concurrent,
+        Err(SupabaseError::ServiceUnavailable { .. })
+    ));
+    assert_eq!(request_count(&server).await, 4);
+
+    // closed
+    assert_eq!(clone.service_health()[&Service::Rest], CircuitState::Closed);
+    assert!(fetch_items(&clone).await.is_ok());
+    assert_eq!(request_count(&server).await, 5);
+}
+
+#[tokio::test]
+async fn test_client_errors_do_not_open_the_circuit() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/items"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "code": "42P01",
+            "message": "relation \"public.items\" does not exist"
+        })))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let client = client_with(
+        &server,
+        ClientOptions::new().with_circuit_breaker(CircuitBreakerOptions::new(1, COOL_DOWN)),
+    );
+    for _ in 0..3 {
+        assert!(matches!(
+            fetch_items(&client).await,
+            Err(SupabaseError::Postgrest(_))
+        ));
+    }
+    assert_eq!(
+        client.service_health()[&Service::Rest],
+        CircuitState::Closed
+    );
+}
+
+#[tokio::test]
+async fn test_without_circuit_breaker_calls_always_go_through() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/items"))
+        .respond_with(ResponseTemplate::new(503))
+        .expect(3)
+        .mount(&server)
+        .await;
+
+    let client = client_with(&server, ClientOptions::new());
+    for _ in 0..3 {
+        assert!(fetch_items(&client).await.is_err());
+    }
+    assert!(client
+        .service_health()
+        .values()
+        .all(|state| *state == CircuitState::Closed));
+}
+
+#[tokio::test]
+async fn test_facade_clients_open_the_circuit_without_guarded() {
+    let server = MockServer::start().await;
+    for route in [
+        "/rest/v1/items",
+        "/storage/v1/bucket",
+        "/functions/v1/hello",
+    ] {
+        Mock::given(path(route))
+            .respond_with(ResponseTemplate::new(503).set_body_string("upstream unavailable"))
+            .expect(2)
+            .mount(&server)
+            .await;
+    }
+    let client = client_with(
+        &server,
+        ClientOptions::new().with_circuit_breaker(CircuitBreakerOptions::new(2, COOL_DOWN)),
+    );
+
+    for _ in 0..2 {
+        let items = client.from("items").await.unwrap();
+        assert!(items.execute::<Value>().await.is_err());
+    }
+    let items = client.from("items").await.unwrap();
+    let rejected = items.execute::<Value>().await;
+    assert!(
+        matches!(rejected, Err(PostgrestError::Rejected(_))),
+        "{:?}",
+        rejected
+    );
+
+    #[cfg(feature = "storage")]
+    {
+        for _ in 0..2 {
+            assert!(client.storage().list_buckets().await.is_err());
+        }
+        let rejected = client.storage().list_buckets().await;
+        assert!(
+            matches!(rejected, Err(StorageError::Rejected(_))),
+            "{:?}",
+            rejected
+        );
+    }
+
+    #[cfg(feature = "functions")]
+    {
+        let invoke = || async {
+            client
+                .functions()
+                .invoke_json::<Value, Value>("hello", None)
+                .await
+        };
+        for _ in 0..2 {
+            assert!(invoke().await.is_err());
+        }
+        let rejected = invoke().await;
+        assert!(
+            matches!(rejected, Err(FunctionsError::Rejected(_))),
+            "{:?}",
+            rejected
+        );
+    }
+
+    let health = client.service_health();
+    assert!(matches!(health[&Service::Rest], CircuitState::Open { .. }));
+    #[cfg(feature = "storage")]
+    assert!(matches!(
+        health[&Service::Storage],
+        CircuitState::Open { .. }
+    ));
+    #[cfg(feature = "functions")]
+    assert!(matches!(
+        health[&Service::Functions],
+        CircuitState::Open { .. }
+    ));
+}
+
+#[cfg(feature = "storage")]
+#[tokio::test]
+async fn test_storage_api_errors_with_gateway_status_open_the_circuit() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/storage/v1/bucket"))
+        .respond_with(ResponseTemplate::new(502).set_body_string("bad gateway"))
+        .expect(2)
+        .mount(&server)
+        .await;
+    let client = client_with(
+        &server,
+        ClientOptions::new().with_circuit_breaker(CircuitBreakerOptions::new(2, COOL_DOWN)),
+    );
+
+    for _ in 0..2 {
+        let storage = client.storage();
+        let result = client
+            .guarded(Service::Storage, storage.list_buckets())
+            .await;
+        assert!(
+            matches!(result, Err(SupabaseError::Storage(_))),
+            "{:?}",
+            result
+        );
+    }
+    let storage = client.storage();
+    assert!(matches!(
+        client
+            .guarded(Service::Storage, storage.list_buckets())
+            .await,
+        Err(SupabaseError::ServiceUnavailable {
+            service: Service::Storage,
+            ..
+        })
+    ));
+    // Counted once per call even though the storage client has its own gate
+    assert_eq!(request_count(&server).await, 2);
+}
+
+#[tokio::test]
+async fn test_success_of_an_earlier_call_keeps_the_probe_in_flight() {
+    let server = MockServer::start().await;
+    for (route, status, delay) in [
+        ("/rest/v1/slow", 200, 400),
+        ("/rest/v1/down", 503, 0),
+        ("/rest/v1/probe", 200, 400),
+    ] {
+        Mock::given(path(route))
+            .respond_with(
+                ResponseTemplate::new(status)
+                    .set_body_json(json!([]))
+                    .set_delay(Duration::from_millis(delay)),
+            )
+            .mount(&server)
+            .await;
+    }
+    let cool_down = Duration::from_millis(100);
+    let client = client_with(
+        &server,
+        ClientOptions::new().with_circuit_breaker(CircuitBreakerOptions::new(1, cool_down)),
+    );
+    let select = |table: &'static str| {
+        let client = client.clone();
+        async move { client.from(table).await.unwrap().execute::<Value>().await }
+    };
+
+    let (slow, probe, during_probe) = tokio::join!(
+        // Let through while closed, succeeds after the probe has started
+        select("slow"),
+        async {
+            assert!(select("down").await.is_err());
+            tokio::time::sleep(cool_down + Duration::from_millis(50)).await;
+            select("probe").await
+        },
+        async {
+            tokio::time::sleep(Duration::from_millis(450)).await;
+            select("probe").await
+        },
+    );
+    assert!(slow.is_ok());
+    assert!(probe.is_ok());
+    assert!(
+        matches!(during_probe, Err(PostgrestError::Rejected(_))),
+        "{:?}",
+        during_probe
+    );
+    assert_eq!(
+        client.service_health()[&Service::Rest],
+        CircuitState::Closed
+    );
+}
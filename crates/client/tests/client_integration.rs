@@ -1,4 +1,5 @@
 // crates/supabase_client/tests/client_integration.rs
+#![cfg(feature = "auth")]
 
 // Import the crate itself
 use supabase_rust_client::client::SupabaseClientWrapper;
@@ -13,7 +14,7 @@ use serde_json::json; // To create mock JSON bodies
 use std::env;
 use uuid::Uuid;
 use wiremock::{
-    matchers::{header, method, path_regex}, // Use path_regex
+    matchers::{body_json, header, method, path_regex, query_param}, // Use path_regex
     Mock,
     MockServer,
     ResponseTemplate,
@@ -139,7 +140,7 @@ async fn test_authenticate_failure() {
     // Assert that the specific error occurred
     assert!(auth_result.is_err());
     match auth_result.err().unwrap() {
-        AuthError::ApiError(_) => { /* Expected */ }
+        AuthError::ApiError { .. } => { /* Expected */ }
         e => panic!("Expected ApiError, got {:?}", e),
     }
 }
@@ -167,6 +168,7 @@ async fn test_fetch_items_authenticated() {
             updated_at: Utc::now().to_rfc3339(), // updated_at is string
             app_metadata: json!({}),             // Use json! macro for Value
             user_metadata: json!({ "test_field": "test_value" }),
+            ..Default::default()
         },
     };
 
@@ -187,7 +189,7 @@ async fn test_fetch_items_authenticated() {
     Mock::given(method("GET"))
         .and(wiremock::matchers::path_regex(r"/rest/v1/items(?:\?.*)?"))
         .and(header("Authorization", auth_header_value.as_str()))
-        .and(header("apikey", config.anon_key.as_str()))
+        .and(header("apikey", config.anon_key.expose().as_str()))
         .respond_with(ResponseTemplate::new(200).set_body_json(&mock_items))
         .expect(1)
         .mount(&mock_server)
@@ -230,8 +232,8 @@ async fn test_fetch_items_unauthenticated() {
     let fetch_result = client.fetch_items().await;
     assert!(fetch_result.is_err());
     match fetch_result.err().unwrap() {
-        SupabaseError::Auth(AuthError::ApiError(msg)) => {
-            assert!(msg.contains("Missing session token"))
+        SupabaseError::Auth(AuthError::ApiError { message, .. }) => {
+            assert!(message.contains("Missing session token"))
         }
         e => panic!(
             "Expected AuthError::ApiError(Missing session token), got {:?}",
@@ -263,6 +265,7 @@ async fn test_integration_crud() {
             updated_at: Utc::now().to_rfc3339(),
             app_metadata: json!({}),
             user_metadata: json!({ "crud_test": true }),
+            ..Default::default()
         },
     };
     client.set_session_for_test(Some(mock_session)).await;
@@ -289,11 +292,9 @@ async fn test_integration_crud() {
     Mock::given(method("POST"))
         .and(wiremock::matchers::path_regex(r"/rest/v1/items(?:\?.*)?"))
         .and(header("Authorization", auth_header_value.as_str()))
-        .and(header("apikey", config.anon_key.as_str()))
+        .and(header("apikey", config.anon_key.expose().as_str()))
         .and(header("Prefer", "return=representation"))
-        .respond_with(
-            ResponseTemplate::new(201).set_body_json(vec![expected_created_item.clone()]),
-        )
+        .respond_with(ResponseTemplate::new(201).set_body_json(vec![expected_created_item.clone()]))
         .expect(1)
         .mount(&mock_server)
         .await;
@@ -311,3 +312,365 @@ async fn test_integration_crud() {
 
     // TODO: Add mocks and calls for fetch_item_by_id, update_item, delete_item
 }
+
+#[tokio::test]
+async fn test_rpc_uses_session_token() {
+    let mock_server = MockServer::start().await;
+    let config = setup_mock_config(&mock_server).await;
+    let client = SupabaseClientWrapper::new(config.clone()).unwrap();
+
+    let mock_session = AuthSession {
+        access_token: "user_jwt_for_rpc".to_string(),
+        refresh_token: "mock_refresh_token".to_string(),
+        expires_in: 3600,
+        token_type: "bearer".to_string(),
+        user: AuthUser {
+            id: Uuid::new_v4().to_string(),
+            created_at: Utc::now().to_rfc3339(),
+            updated_at: Utc::now().to_rfc3339(),
+            ..Default::default()
+        },
+    };
+    client.set_session_for_test(Some(mock_session)).await;
+
+    #[derive(serde::Serialize)]
+    struct Args {
+        amount: i32,
+    }
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/rest/v1/rpc/add_credits$"))
+        .and(header("apikey", config.anon_key.expose().as_str()))
+        .and(header("Authorization", "Bearer user_jwt_for_rpc"))
+        .and(body_json(json!({ "amount": 5 })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!(15)))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let balance: i64 = client
+        .rpc_typed("add_credits", &Args { amount: 5 })
+        .await
+        .unwrap()
+        .call_rpc()
+        .await
+        .unwrap();
+    assert_eq!(balance, 15);
+}
+
+#[tokio::test]
+async fn test_rpc_setof_with_filters() {
+    let mock_server = MockServer::start().await;
+    let config = setup_mock_config(&mock_server).await;
+    let client = SupabaseClientWrapper::new(config.clone()).unwrap();
+
+    // No session: the anon key is used as the bearer token
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/rest/v1/rpc/list_items$"))
+        .and(header(
+            "Authorization",
+            format!("Bearer {}", config.anon_key.expose()).as_str(),
+        ))
+        .and(query_param("select", "id,name"))
+        .and(query_param("name", "like.Mock*"))
+        .and(body_json(json!({ "owner": "me" })))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!([{ "id": 1, "name": "Mock 1" }])),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let rows: Vec<serde_json::Value> = client
+        .rpc("list_items", json!({ "owner": "me" }))
+        .await
+        .unwrap()
+        .select("id,name")
+        .like("name", "Mock*")
+        .call_rpc()
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0]["name"], "Mock 1");
+}
+
+#[tokio::test]
+async fn test_as_user_queries_with_impersonated_token() {
+    let mock_server = MockServer::start().await;
+    let config = setup_mock_config(&mock_server)
+        .await
+        .with_service_role_key("mock_service_role_key")
+        .with_jwt_secret("mock_jwt_secret");
+    let client = SupabaseClientWrapper::new(config).unwrap();
+
+    let impersonated = client.as_user("user-42").unwrap();
+    let token = impersonated.session().access_token.clone();
+    let claims = supabase_rust_auth::AccessTokenClaims::decode(&token).unwrap();
+    assert_eq!(claims.sub.as_deref(), Some("user-42"));
+    assert_eq!(claims.role.as_deref(), Some("authenticated"));
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/rest/v1/items$"))
+        .and(header(
+            "Authorization",
+            format!("Bearer {}", token).as_str(),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let rows: Vec<serde_json::Value> = impersonated.from("items").unwrap().execute().await.unwrap();
+    assert!(rows.is_empty());
+
+    // Without admin configuration as_user is rejected
+    let anon_client = SupabaseClientWrapper::new(setup_mock_config(&mock_server).await).unwrap();
+    assert!(matches!(
+        anon_client.as_user("user-42"),
+        Err(SupabaseError::Config(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_client_options_apply_headers_schema_and_urls() {
+    let mock_server = MockServer::start().await;
+    let rest_server = MockServer::start().await;
+    let options = supabase_rust_client::options::ClientOptions::default()
+        .with_db_schema("api")
+        .with_global_header("x-tenant", "acme")
+        .with_rest_url(url::Url::parse(&rest_server.uri()).unwrap());
+    let config = setup_mock_config(&mock_server).await.with_options(options);
+    let client = SupabaseClientWrapper::new(config).unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/rest/v1/items$"))
+        .and(header("x-tenant", "acme"))
+        .and(header("Accept-Profile", "api"))
+        .and(header("Content-Profile", "api"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&rest_server)
+        .await;
+
+    let rows: Vec<serde_json::Value> = client.from("items").await.unwrap().execute().await.unwrap();
+    assert!(rows.is_empty());
+}
+
+#[tokio::test]
+async fn test_connection_options_build_a_working_client() {
+    let mock_server = MockServer::start().await;
+    let options = supabase_rust_client::options::ClientOptions::default()
+        .with_pool_idle_timeout(std::time::Duration::from_secs(300))
+        .with_pool_max_idle_per_host(16)
+        .with_tcp_keepalive(std::time::Duration::from_secs(30))
+        .with_http2_adaptive_window(true)
+        .with_http2_prior_knowledge(true);
+    let config = setup_mock_config(&mock_server).await.with_options(options);
+    let client = SupabaseClientWrapper::new(config).unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/rest/v1/items$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    for _ in 0..2 {
+        let rows: Vec<serde_json::Value> =
+            client.from("items").await.unwrap().execute().await.unwrap();
+        assert!(rows.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_injected_http_client_is_used_as_is() {
+    let mock_server = MockServer::start().await;
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("x-injected", "yes".parse().unwrap());
+    let http_client = ReqwestClient::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap();
+    let options = supabase_rust_client::options::ClientOptions::default()
+        .with_global_header("x-tenant", "acme")
+        .with_http_client(http_client);
+    let config = setup_mock_config(&mock_server).await.with_options(options);
+    let client = SupabaseClientWrapper::new(config).unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/rest/v1/items$"))
+        .and(header("x-injected", "yes"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let rows: Vec<serde_json::Value> = client.from("items").await.unwrap().execute().await.unwrap();
+    assert!(rows.is_empty());
+    let requests = mock_server.received_requests().await.unwrap();
+    assert!(!requests[0].headers.contains_key(&"x-tenant".into()));
+}
+
+#[tokio::test]
+async fn test_row_caps_apply_to_from_queries() {
+    let mock_server = MockServer::start().await;
+    let options = supabase_rust_client::options::ClientOptions::default()
+        .with_default_max_rows(100)
+        .with_max_rows_ceiling(1_000);
+    let config = setup_mock_config(&mock_server).await.with_options(options);
+    let client = SupabaseClientWrapper::new(config).unwrap();
+
+    for limit in ["100", "1000"] {
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/rest/v1/items$"))
+            .and(query_param("limit", limit))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+    }
+
+    let rows: Vec<serde_json::Value> = client.from("items").await.unwrap().execute().await.unwrap();
+    assert!(rows.is_empty());
+    let rows: Vec<serde_json::Value> = client
+        .from("items")
+        .await
+        .unwrap()
+        .limit(10_000)
+        .execute()
+        .await
+        .unwrap();
+    assert!(rows.is_empty());
+}
+
+fn mock_session_body(access_token: &str, refresh_token: &str) -> serde_json::Value {
+    json!({
+        "access_token": access_token,
+        "refresh_token": refresh_token,
+        "expires_in": 3600,
+        "token_type": "bearer",
+        "user": {
+            "id": Uuid::new_v4().to_string(),
+            "app_metadata": {},
+            "user_metadata": {},
+            "created_at": Utc::now().to_rfc3339(),
+            "updated_at": Utc::now().to_rfc3339(),
+        }
+    })
+}
+
+// Signs in through the wrapper so both it and the auth client hold a session.
+async fn sign_in_with_mock(mock_server: &MockServer, client: &SupabaseClientWrapper) {
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/auth/v1/token$"))
+        .and(query_param("grant_type", "password"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(mock_session_body("expired_jwt", "refresh_1")),
+        )
+        .mount(mock_server)
+        .await;
+    client
+        .authenticate(AuthCredentials {
+            email: "test@example.com".to_string(),
+            password: "password".to_string(),
+        })
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_jwt_expired_refreshes_session_and_retries_once() {
+    let mock_server = MockServer::start().await;
+    let config = setup_mock_config(&mock_server).await;
+    let client = SupabaseClientWrapper::new(config).unwrap();
+    sign_in_with_mock(&mock_server, &client).await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/auth/v1/token$"))
+        .and(query_param("grant_type", "refresh_token"))
+        .and(body_json(json!({ "refresh_token": "refresh_1" })))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(mock_session_body("fresh_jwt", "refresh_2")),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/rest/v1/items$"))
+        .and(header("Authorization", "Bearer expired_jwt"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "code": "PGRST303",
+            "message": "JWT expired",
+            "details": null,
+            "hint": null
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/rest/v1/items$"))
+        .and(header("Authorization", "Bearer fresh_jwt"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": 1 }])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let rows: Vec<serde_json::Value> = client.from("items").await.unwrap().execute().await.unwrap();
+    assert_eq!(rows, vec![json!({ "id": 1 })]);
+
+    // Later queries use the refreshed token directly.
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/rest/v1/rpc/ping$"))
+        .and(header("Authorization", "Bearer fresh_jwt"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!("pong")))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    let pong: String = client
+        .rpc("ping", json!({}))
+        .await
+        .unwrap()
+        .call_rpc()
+        .await
+        .unwrap();
+    assert_eq!(pong, "pong");
+}
+
+#[tokio::test]
+async fn test_non_expiry_401_is_returned_without_refresh() {
+    let mock_server = MockServer::start().await;
+    let config = setup_mock_config(&mock_server).await;
+    let client = SupabaseClientWrapper::new(config).unwrap();
+    sign_in_with_mock(&mock_server, &client).await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/auth/v1/token$"))
+        .and(query_param("grant_type", "refresh_token"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/rest/v1/items$"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "code": "42501",
+            "message": "permission denied for table items",
+            "details": null,
+            "hint": null
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let result: Result<Vec<serde_json::Value>, _> =
+        client.from("items").await.unwrap().execute().await;
+    match result {
+        Err(supabase_rust_postgrest::PostgrestError::ApiError {
+            details, status, ..
+        }) => {
+            assert_eq!(status, 401);
+            assert_eq!(details.code.as_deref(), Some("42501"));
+        }
+        other => panic!("expected the 401 to pass through, got {:?}", other),
+    }
+}
@@ -0,0 +1,61 @@
+// crates/client/tests/features.rs
+
+// What the facade exposes under each combination of the service features.
+
+use serde_json::json;
+use supabase_rust_client::prelude::*;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn client_for(server: &MockServer) -> SupabaseClientWrapper {
+    let config = SupabaseConfig::new(&server.uri(), "anon-key".to_string()).unwrap();
+    SupabaseClientWrapper::new(config).unwrap()
+}
+
+#[tokio::test]
+async fn test_postgrest_uses_the_anon_key_without_a_session() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/items"))
+        .and(header("apikey", "anon-key"))
+        .and(header("Authorization", "Bearer anon-key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": 1 }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = client_for(&server);
+    let rows: Vec<serde_json::Value> = client.from("items").await.unwrap().execute().await.unwrap();
+    assert_eq!(rows, vec![json!({ "id": 1 })]);
+}
+
+#[cfg(feature = "auth")]
+#[tokio::test]
+async fn test_auth_feature_exposes_the_auth_client() {
+    let server = MockServer::start().await;
+    let client = client_for(&server);
+    let _: &supabase_rust_client::auth::Auth = &client.auth;
+    assert!(client.as_user("user-1").is_err());
+}
+
+#[cfg(feature = "storage")]
+#[tokio::test]
+async fn test_storage_feature_exposes_the_storage_client() {
+    let server = MockServer::start().await;
+    let _: supabase_rust_client::storage::StorageClient = client_for(&server).storage();
+}
+
+#[cfg(feature = "functions")]
+#[tokio::test]
+async fn test_functions_feature_exposes_the_functions_client() {
+    let server = MockServer::start().await;
+    let _: supabase_rust_client::functions::FunctionsClient = client_for(&server).functions();
+}
+
+#[cfg(feature = "realtime")]
+#[tokio::test]
+async fn test_realtime_feature_exposes_the_realtime_client() {
+    let server = MockServer::start().await;
+    let client = client_for(&server);
+    let _: &supabase_rust_client::realtime::RealtimeClient = &client.realtime;
+}
@@ -0,0 +1,321 @@
+// crates/client/tests/management_test.rs
+
+#![cfg(feature = "management")]
+
+use serde_json::{json, Value};
+use std::time::Duration;
+use supabase_rust_client::management::{
+    AuthConfigUpdate, CreateProject, ManagementClient, ManagementError,
+};
+use supabase_rust_core::Page;
+use wiremock::matchers::{body_json, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const TOKEN: &str = "sbp_0123456789abcdef";
+
+fn client(server: &MockServer) -> ManagementClient {
+    ManagementClient::new(TOKEN).with_base_url(&server.uri())
+}
+
+fn project(index: usize) -> Value {
+    json!({
+        "id": format!("ref{}", index),
+        "organization_id": "org",
+        "name": format!("preview-{}", index),
+        "region": "us-east-1",
+        "created_at": "2024-03-01T12:34:56Z",
+        "status": "ACTIVE_HEALTHY"
+    })
+}
+
+#[tokio::test]
+async fn test_list_projects_follows_pages() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/projects"))
+        .and(query_param("offset", "0"))
+        .and(header(
+            "authorization",
+            format!("Bearer {}", TOKEN).as_str(),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "projects": (0..100).map(project).collect::<Vec<_>>(),
+            "pagination": { "count": 101, "limit": 100, "offset": 0 }
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/projects"))
+        .and(query_param("offset", "100"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "projects": [project(100)],
+            "pagination": { "count": 101, "limit": 100, "offset": 100 }
+        })))
+        .mount(&server)
+        .await;
+
+    let projects = client(&server).list_projects().await.unwrap();
+    assert_eq!(projects.len(), 101);
+    assert_eq!(projects[100].id, "ref100");
+    assert_eq!(projects[0].status.as_deref(), Some("ACTIVE_HEALTHY"));
+}
+
+#[tokio::test]
+async fn test_list_projects_plain_array_is_a_single_page() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/projects"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([project(1), project(2)])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let paged = client(&server)
+        .list_projects_page(Page::first(1))
+        .await
+        .unwrap();
+    assert_eq!(paged.items.len(), 2);
+    assert_eq!(paged.has_more, Some(false));
+}
+
+#[tokio::test]
+async fn test_list_projects_waits_out_rate_limit() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/projects"))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header("retry-after", "0")
+                .set_body_json(json!({ "message": "Too many requests" })),
+        )
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/projects"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([project(1)])))
+        .mount(&server)
+        .await;
+
+    let projects = client(&server).list_projects().await.unwrap();
+    assert_eq!(projects.len(), 1);
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_rate_limit_without_retries_is_reported() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/projects/abcdefghijklmnopqrst/api-keys"))
+        .respond_with(
+            ResponseTemplate::new(429)
+                .insert_header("retry-after", "30")
+                .set_body_json(json!({ "message": "Too many requests" })),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let error = client(&server)
+        .with_max_rate_limit_retries(0)
+        .api_keys("abcdefghijklmnopqrst")
+        .await
+        .unwrap_err();
+    match error {
+        ManagementError::RateLimited {
+            retry_after,
+            message,
+        } => {
+            assert_eq!(retry_after, Some(Duration::from_secs(30)));
+            assert_eq!(message, "Too many requests");
+        }
+        other => panic!("expected RateLimited, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_create_and_delete_project() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/projects"))
+        .and(body_json(json!({
+            "name": "preview-1",
+            "organization_id": "org",
+            "db_pass": "secret-password",
+            "region": "us-east-1"
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(project(1)))
+        .mount(&server)
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path("/v1/projects/ref1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": 7,
+            "ref": "ref1",
+            "name": "preview-1"
+        })))
+        .mount(&server)
+        .await;
+
+    let management = client(&server);
+    let request = CreateProject::new("preview-1", "org", "secret-password", "us-east-1");
+    assert!(!format!("{:?}", request).contains("secret-password"));
+    let created = management.create_project(&request).await.unwrap();
+    assert_eq!(created.id, "ref1");
+
+    let deleted = management.delete_project(&created.id).await.unwrap();
+    assert_eq!(deleted.project_ref, "ref1");
+
+    assert!(matches!(
+        management
+            .create_project(&CreateProject::new(" ", "org", "pw", "us-east-1"))
+            .await,
+        Err(ManagementError::InvalidInput(_))
+    ));
+    assert!(matches!(
+        management.delete_project("../other").await,
+        Err(ManagementError::InvalidInput(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_api_keys() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/projects/ref1/api-keys"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "name": "anon", "api_key": "anon-key-value" },
+            { "name": "service_role", "api_key": "service-key-value" }
+        ])))
+        .mount(&server)
+        .await;
+
+    let keys = client(&server).api_keys("ref1").await.unwrap();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[0].name, "anon");
+    assert_eq!(keys[1].api_key.expose(), "service-key-value");
+    assert!(!format!("{:?}", keys).contains("service-key-value"));
+}
+
+#[tokio::test]
+async fn test_get_and_update_auth_config() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/projects/ref1/config/auth"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "site_url": "http://localhost:3000",
+            "disable_signup": false,
+            "jwt_exp": 3600,
+            "smtp_host": "smtp.example.com"
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path("/v1/projects/ref1/config/auth"))
+        .and(body_json(json!({
+            "site_url": "https://preview.example.com",
+            "disable_signup": true,
+            "mfa_max_enrolled_factors": 3
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "site_url": "https://preview.example.com",
+            "disable_signup": true,
+            "jwt_exp": 3600,
+            "mfa_max_enrolled_factors": 3
+        })))
+        .mount(&server)
+        .await;
+
+    let management = client(&server);
+    let config = management.auth_config("ref1").await.unwrap();
+    assert_eq!(config.site_url.as_deref(), Some("http://localhost:3000"));
+    assert_eq!(config.jwt_exp, Some(3600));
+    assert_eq!(config.other["smtp_host"], "smtp.example.com");
+
+    let update = AuthConfigUpdate::new()
+        .with_site_url("https://preview.example.com")
+        .with_disable_signup(true)
+        .with_setting("mfa_max_enrolled_factors", json!(3));
+    let updated = management
+        .update_auth_config("ref1", &update)
+        .await
+        .unwrap();
+    assert_eq!(updated.disable_signup, Some(true));
+    assert_eq!(updated.other["mfa_max_enrolled_factors"], 3);
+}
+
+#[tokio::test]
+async fn test_run_sql() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/projects/ref1/database/query"))
+        .and(body_json(json!({ "query": "select 1 as one" })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!([{ "one": 1 }])))
+        .mount(&server)
+        .await;
+
+    let rows = client(&server)
+        .run_sql("ref1", "select 1 as one")
+        .await
+        .unwrap();
+    assert_eq!(rows, vec![json!({ "one": 1 })]);
+}
+
+#[tokio::test]
+async fn test_error_classification() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/v1/projects/ref1/config/auth"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "message": format!("Invalid token {}", TOKEN)
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v1/projects/missing/config/auth"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "message": "Project not found"
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/projects/ref1/database/query"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "message": "syntax error at or near \"selec\""
+        })))
+        .mount(&server)
+        .await;
+
+    let management = client(&server);
+    match management.auth_config("ref1").await.unwrap_err() {
+        ManagementError::Unauthorized { status, message } => {
+            assert_eq!(status, 401);
+            assert!(!message.contains(TOKEN), "{}", message);
+        }
+        other => panic!("expected Unauthorized, got {:?}", other),
+    }
+    assert!(matches!(
+        management.auth_config("missing").await,
+        Err(ManagementError::NotFound(message)) if message == "Project not found"
+    ));
+    assert!(matches!(
+        management.run_sql("ref1", "selec 1").await,
+        Err(ManagementError::Api { status: 400, .. })
+    ));
+}
+
+// SUPABASE_ACCESS_TOKEN=sbp_... cargo test -p supabase-rust-client --features management -- --ignored
+#[tokio::test]
+#[ignore]
+async fn test_live_list_projects() {
+    let Ok(token) = std::env::var("SUPABASE_ACCESS_TOKEN") else {
+        eprintln!("SUPABASE_ACCESS_TOKEN is not set; skipping");
+        return;
+    };
+    let management = ManagementClient::new(&token);
+    let projects = management.list_projects().await.unwrap();
+    if let Some(project) = projects.first() {
+        let keys = management.api_keys(&project.id).await.unwrap();
+        assert!(!keys.is_empty());
+    }
+}
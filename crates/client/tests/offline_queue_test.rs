@@ -0,0 +1,233 @@
+// crates/client/tests/offline_queue_test.rs
+
+use serde_json::{json, Value};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use supabase_rust_client::client::{SupabaseClientWrapper, SupabaseConfig};
+use supabase_rust_client::offline::{
+    ConflictAction, FileWriteQueue, WriteOp, WriteOutcome, WriteQueue, IDEMPOTENCY_KEY_HEADER,
+};
+use supabase_rust_client::options::ClientOptions;
+use supabase_rust_client::postgrest::PostgrestError;
+use supabase_rust_client::SupabaseError;
+use wiremock::matchers::{body_json, header_exists, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+// Picks a free port and releases it, so connecting to it fails until a server is started there
+fn offline_addr() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+async fn go_online(addr: SocketAddr) -> MockServer {
+    let listener = TcpListener::bind(addr).unwrap();
+    MockServer::builder().listener(listener).start().await
+}
+
+fn client_for(addr: SocketAddr) -> SupabaseClientWrapper {
+    let config = SupabaseConfig::new(&format!("http://{}", addr), "anon-key".to_string()).unwrap();
+    SupabaseClientWrapper::new(config).unwrap()
+}
+
+fn queued_key(outcome: WriteOutcome) -> String {
+    match outcome {
+        WriteOutcome::Queued { idempotency_key } => idempotency_key,
+        other => panic!("expected the write to be queued, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_offline_inserts_are_persisted_and_replayed_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let queue_path = dir.path().join("writes.jsonl");
+    let addr = offline_addr();
+    let client = client_for(addr);
+
+    let offline = client.offline_queue(Arc::new(FileWriteQueue::new(&queue_path)));
+    let first = queued_key(
+        offline
+            .insert("todos", json!({ "title": "one" }))
+            .await
+            .unwrap(),
+    );
+    let second = queued_key(
+        offline
+            .insert("todos", json!({ "title": "two" }))
+            .await
+            .unwrap(),
+    );
+    assert_ne!(first, second);
+
+    // The queue survives a restart: a fresh queue on the same file sees both writes
+    let reopened = FileWriteQueue::new(&queue_path);
+    let pending = reopened.pending().await.unwrap();
+    let keys: Vec<_> = pending
+        .iter()
+        .map(|op| op.idempotency_key.clone())
+        .collect();
+    assert_eq!(keys, vec![first.clone(), second.clone()]);
+    assert_eq!(
+        pending[0].op,
+        WriteOp::Insert {
+            table: "todos".to_string(),
+            values: json!({ "title": "one" })
+        }
+    );
+
+    // Flushing while still offline keeps everything
+    let offline = client.offline_queue(Arc::new(reopened));
+    let report = offline.flush().await.unwrap();
+    assert!(report.sent.is_empty());
+    assert_eq!(report.remaining, 2);
+
+    let server = go_online(addr).await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/todos"))
+        .and(header_exists(IDEMPOTENCY_KEY_HEADER))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!([{ "id": 1 }])))
+        .expect(4)
+        .mount(&server)
+        .await;
+
+    // Online, but earlier writes are still queued: the new write goes behind them
+    let third = queued_key(
+        offline
+            .insert("todos", json!({ "title": "three" }))
+            .await
+            .unwrap(),
+    );
+
+    let report = offline.flush().await.unwrap();
+    assert_eq!(
+        report.sent,
+        vec![first.clone(), second.clone(), third.clone()]
+    );
+    assert!(report.discarded.is_empty());
+    assert_eq!(report.remaining, 0);
+    assert!(offline.pending().await.unwrap().is_empty());
+
+    let requests = server.received_requests().await.unwrap();
+    let sent: Vec<(String, Value)> = requests
+        .iter()
+        .map(|request| {
+            let key = request.headers.get(&IDEMPOTENCY_KEY_HEADER.into()).unwrap()[0]
+                .as_str()
+                .to_string();
+            (key, serde_json::from_slice(&request.body).unwrap())
+        })
+        .collect();
+    assert_eq!(
+        sent,
+        vec![
+            (first, json!({ "title": "one" })),
+            (second, json!({ "title": "two" })),
+            (third, json!({ "title": "three" })),
+        ]
+    );
+
+    // With an empty queue, writes go straight to the server
+    let outcome = offline
+        .insert("todos", json!({ "title": "four" }))
+        .await
+        .unwrap();
+    assert_eq!(outcome, WriteOutcome::Sent(json!([{ "id": 1 }])));
+}
+
+#[tokio::test]
+async fn test_flush_conflict_handling() {
+    let dir = tempfile::tempdir().unwrap();
+    let queue = Arc::new(FileWriteQueue::new(dir.path().join("writes.jsonl")));
+    let addr = offline_addr();
+    let client = client_for(addr);
+
+    let offline = client.offline_queue(queue.clone());
+    let duplicate = queued_key(offline.insert("todos", json!({ "id": 1 })).await.unwrap());
+    let fresh = queued_key(offline.insert("todos", json!({ "id": 2 })).await.unwrap());
+
+    let server = go_online(addr).await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/todos"))
+        .and(body_json(json!({ "id": 1 })))
+        .respond_with(ResponseTemplate::new(409).set_body_json(json!({
+            "code": "23505",
+            "message": "duplicate key value violates unique constraint \"todos_pkey\""
+        })))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/todos"))
+        .and(body_json(json!({ "id": 2 })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!([{ "id": 2 }])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // Without a handler the rejected write blocks the ones behind it
+    let report = offline.flush().await.unwrap();
+    assert!(report.sent.is_empty());
+    assert_eq!(report.remaining, 2);
+
+    let conflicts = Arc::new(Mutex::new(Vec::new()));
+    let seen = conflicts.clone();
+    let offline = client
+        .offline_queue(queue.clone())
+        .on_conflict(move |error, op| {
+            seen.lock()
+                .unwrap()
+                .push((error.to_string(), op.idempotency_key.clone()));
+            ConflictAction::Discard
+        });
+    let report = offline.flush().await.unwrap();
+    assert_eq!(report.discarded, vec![duplicate.clone()]);
+    assert_eq!(report.sent, vec![fresh]);
+    assert_eq!(report.remaining, 0);
+
+    let conflicts = conflicts.lock().unwrap().clone();
+    assert_eq!(conflicts.len(), 1);
+    assert!(
+        conflicts[0].0.contains("duplicate key"),
+        "{}",
+        conflicts[0].0
+    );
+    assert_eq!(conflicts[0].1, duplicate);
+    assert!(queue.pending().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_timed_out_insert_is_returned_not_queued() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = MockServer::start().await;
+    // The server commits the write but answers after the client gave up
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/todos"))
+        .respond_with(
+            ResponseTemplate::new(201)
+                .set_body_json(json!([{ "id": 1 }]))
+                .set_delay(Duration::from_secs(2)),
+        )
+        .mount(&server)
+        .await;
+
+    let config = SupabaseConfig::new(&server.uri(), "anon-key".to_string())
+        .unwrap()
+        .with_options(ClientOptions::default().with_timeout(Duration::from_millis(200)));
+    let client = SupabaseClientWrapper::new(config).unwrap();
+    let offline = client.offline_queue(Arc::new(FileWriteQueue::new(
+        dir.path().join("writes.jsonl"),
+    )));
+
+    let result = offline.insert("todos", json!({ "title": "one" })).await;
+    assert!(
+        matches!(
+            &result,
+            Err(SupabaseError::Postgrest(PostgrestError::NetworkError(e))) if e.is_timeout()
+        ),
+        "{:?}",
+        result
+    );
+    // Queueing it would send the committed write a second time on flush
+    assert!(offline.pending().await.unwrap().is_empty());
+}
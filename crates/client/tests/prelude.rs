@@ -0,0 +1,83 @@
+// crates/client/tests/prelude.rs
+
+// Compile-time checks that the prelude and the long-standing import paths resolve.
+// Services behind a disabled cargo feature are skipped.
+
+use supabase_rust_client::prelude::*;
+
+#[test]
+fn prelude_exports_common_types() {
+    let _ = SortOrder::Ascending;
+    let _ = IsolationLevel::ReadCommitted;
+    let _ = TransactionMode::ReadOnly;
+
+    fn _assert_error<E: std::error::Error>() {}
+    _assert_error::<SupabaseError>();
+    _assert_error::<PostgrestError>();
+}
+
+#[cfg(feature = "auth")]
+#[test]
+fn prelude_exports_auth_types() {
+    let _ = AuthOptions::default();
+    fn _assert_error<E: std::error::Error>() {}
+    _assert_error::<AuthError>();
+    fn _assert_types(_: Option<Session>, _: Option<User>, _: Option<AuthUser>) {}
+}
+
+#[cfg(feature = "storage")]
+#[test]
+fn prelude_exports_storage_types() {
+    let _ = FileOptions::new().with_mode(UploadMode::Overwrite);
+    let _ = ImageTransformOptions::new();
+    fn _assert_error<E: std::error::Error>() {}
+    _assert_error::<StorageError>();
+}
+
+#[cfg(feature = "functions")]
+#[test]
+fn prelude_exports_functions_types() {
+    let _ = ResponseType::Json;
+    let _ = FunctionOptions::default();
+    fn _assert_error<E: std::error::Error>() {}
+    _assert_error::<FunctionsError>();
+}
+
+#[cfg(feature = "realtime")]
+#[test]
+fn prelude_exports_realtime_types() {
+    let _ = FilterOperator::Eq;
+    fn _assert_error<E: std::error::Error>() {}
+    _assert_error::<RealtimeError>();
+}
+
+#[test]
+fn existing_paths_still_resolve() {
+    use supabase_rust_client::client::{SupabaseClientWrapper as ClientPath, SupabaseConfig};
+    use supabase_rust_client::error::SupabaseError as ErrorPath;
+    use supabase_rust_client::models::{Item as ItemPath, User as UserPath};
+    use supabase_rust_client::{Item, SupabaseClientWrapper, SupabaseError};
+
+    fn _same<T>(_: Option<T>, _: Option<T>) {}
+    _same::<SupabaseClientWrapper>(None, None::<ClientPath>);
+    _same::<SupabaseError>(None, None::<ErrorPath>);
+    _same::<Item>(None, None::<ItemPath>);
+    _same::<User>(None, None::<UserPath>);
+
+    let config = SupabaseConfig::new("http://localhost:54321", "anon".to_string());
+    assert!(config.is_ok());
+}
+
+#[test]
+fn sub_crates_are_reexported() {
+    let _ = supabase_rust_client::postgrest::SortOrder::Descending;
+    #[cfg(feature = "auth")]
+    let _ = supabase_rust_client::auth::AuthOptions::default();
+    #[cfg(feature = "storage")]
+    let _ = supabase_rust_client::storage::FileOptions::new();
+    #[cfg(feature = "functions")]
+    let _ = supabase_rust_client::functions::FunctionOptions::default();
+    #[cfg(feature = "realtime")]
+    let _ = supabase_rust_client::realtime::FilterOperator::In;
+    assert_eq!(supabase_rust_client::VERSION, env!("CARGO_PKG_VERSION"));
+}
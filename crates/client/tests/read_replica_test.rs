@@ -0,0 +1,81 @@
+// crates/client/tests/read_replica_test.rs
+
+use serde_json::{json, Value};
+use supabase_rust_client::prelude::*;
+use url::Url;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn client_with_replica(primary: &MockServer, replica: &MockServer) -> SupabaseClientWrapper {
+    let replica_url = Url::parse(&format!("{}/replica", replica.uri())).unwrap();
+    let config = SupabaseConfig::new(&primary.uri(), "anon-key".to_string())
+        .unwrap()
+        .with_options(ClientOptions::new().with_rest_replica_url(replica_url));
+    SupabaseClientWrapper::new(config).unwrap()
+}
+
+#[tokio::test]
+async fn test_routed_reads_use_the_configured_replica() {
+    let primary = MockServer::start().await;
+    let replica = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/replica/rest/v1/items"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": 1 }])))
+        .expect(1)
+        .mount(&replica)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/items"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&primary)
+        .await;
+
+    let client = client_with_replica(&primary, &replica);
+    let routed: Vec<Value> = client
+        .from("items")
+        .await
+        .unwrap()
+        .use_read_replica()
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(routed, vec![json!({ "id": 1 })]);
+    // Without the opt-in the same client reads from the primary
+    let unrouted: Vec<Value> = client.from("items").await.unwrap().execute().await.unwrap();
+    assert!(unrouted.is_empty());
+}
+
+#[tokio::test]
+async fn test_writes_ignore_routing_and_replica_only_rejects_them() {
+    let primary = MockServer::start().await;
+    let replica = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/rest/v1/items"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!([{ "id": 1 }])))
+        .expect(1)
+        .mount(&primary)
+        .await;
+
+    let client = client_with_replica(&primary, &replica);
+    client
+        .from("items")
+        .await
+        .unwrap()
+        .use_read_replica()
+        .insert(json!({ "name": "a" }))
+        .await
+        .unwrap();
+    let rejected = client
+        .from("items")
+        .await
+        .unwrap()
+        .route_to(ReadPreference::ReplicaOnly)
+        .insert(json!({ "name": "b" }))
+        .await;
+    assert!(matches!(
+        rejected,
+        Err(PostgrestError::WriteOnReadReplica { .. })
+    ));
+    assert!(replica.received_requests().await.unwrap().is_empty());
+}
@@ -0,0 +1,275 @@
+// crates/client/tests/storage_events_test.rs
+#![cfg(feature = "realtime")]
+
+use serde_json::{json, Value};
+use std::time::Duration;
+use supabase_rust_client::storage_events::{
+    self, StorageObjectChange, StorageObjectEvent, StorageObjectFilter,
+};
+use supabase_rust_realtime::transport::memory_socket;
+use supabase_rust_realtime::{
+    ChannelEvent, RealtimeClient, RealtimeClientOptions, SubscriptionWarning,
+};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::timeout;
+
+const TOPIC: &str = "realtime:storage:objects:avatars";
+const WAIT: Duration = Duration::from_secs(3);
+
+const COLUMNS: &str = r#"[
+    {"name": "id", "type": "uuid"},
+    {"name": "bucket_id", "type": "text"},
+    {"name": "name", "type": "text"},
+    {"name": "owner", "type": "uuid"},
+    {"name": "created_at", "type": "timestamptz"},
+    {"name": "updated_at", "type": "timestamptz"},
+    {"name": "last_accessed_at", "type": "timestamptz"},
+    {"name": "metadata", "type": "jsonb"},
+    {"name": "path_tokens", "type": "_text"},
+    {"name": "version", "type": "text"},
+    {"name": "owner_id", "type": "text"},
+    {"name": "user_metadata", "type": "jsonb"}
+]"#;
+
+fn object_row(bucket_id: &str, name: &str) -> Value {
+    json!({
+        "id": "5b0b3c0e-4f4e-4a0e-9d55-0b6a2a0b8f3e",
+        "bucket_id": bucket_id,
+        "name": name,
+        "owner": "d0c3b6a1-9f0e-4c55-8a0e-3f5c2b1e7a90",
+        "owner_id": "d0c3b6a1-9f0e-4c55-8a0e-3f5c2b1e7a90",
+        "created_at": "2024-05-14T09:21:07.498174+00:00",
+        "updated_at": "2024-05-14T09:21:07.498174+00:00",
+        "last_accessed_at": "2024-05-14T09:21:07.498174+00:00",
+        "metadata": {
+            "eTag": "\"c5a4d2a6f0b1\"",
+            "size": 48213,
+            "mimetype": "image/png",
+            "cacheControl": "max-age=3600",
+            "lastModified": "2024-05-14T09:21:08.000Z",
+            "contentLength": 48213,
+            "httpStatusCode": 200
+        },
+        "path_tokens": name.split('/').collect::<Vec<_>>(),
+        "version": "7d3f1a52-1c2b-4f0e-9a51-3e1d2c4b5a60",
+        "user_metadata": null
+    })
+}
+
+// Payload of a `postgres_changes` message on storage.objects as the server sends it
+fn captured(event_type: &str, record: Value, old_record: Value) -> Value {
+    json!({
+        "data": {
+            "columns": serde_json::from_str::<Value>(COLUMNS).unwrap(),
+            "commit_timestamp": "2024-05-14T09:21:07.512Z",
+            "errors": null,
+            "record": record,
+            "old_record": old_record,
+            "schema": "storage",
+            "table": "objects",
+            "type": event_type
+        },
+        "ids": [38_713_247]
+    })
+}
+
+fn message(payload: Value) -> Value {
+    json!({ "topic": TOPIC, "event": "postgres_changes", "payload": payload, "ref": null })
+}
+
+#[test]
+fn test_captured_storage_objects_payloads_decode() {
+    let created = StorageObjectChange::from_payload(&captured(
+        "INSERT",
+        object_row("avatars", "u/1.png"),
+        json!({}),
+    ))
+    .unwrap()
+    .unwrap();
+    let StorageObjectChange::Created(object) = &created else {
+        panic!("expected Created, got {:?}", created);
+    };
+    assert_eq!(object.bucket_id, "avatars");
+    assert_eq!(object.name, "u/1.png");
+    assert_eq!(
+        object.owner.as_deref(),
+        Some("d0c3b6a1-9f0e-4c55-8a0e-3f5c2b1e7a90")
+    );
+    assert_eq!(
+        object.created_at.as_deref(),
+        Some("2024-05-14T09:21:07.498174+00:00")
+    );
+    assert_eq!(object.size(), Some(48213));
+    assert_eq!(object.mime_type(), Some("image/png"));
+
+    let updated = StorageObjectChange::from_payload(&captured(
+        "UPDATE",
+        object_row("avatars", "u/1.png"),
+        json!({ "id": "5b0b3c0e-4f4e-4a0e-9d55-0b6a2a0b8f3e" }),
+    ))
+    .unwrap();
+    assert!(matches!(updated, Some(StorageObjectChange::Updated(_))));
+
+    // With `replica identity full` the old row is complete
+    let deleted = StorageObjectChange::from_payload(&captured(
+        "DELETE",
+        json!({}),
+        object_row("avatars", "u/1.png"),
+    ))
+    .unwrap();
+    assert_eq!(deleted.unwrap().into_object().name, "u/1.png");
+    // Otherwise only the primary key arrives and the delete cannot be attributed
+    let partial = StorageObjectChange::from_payload(&captured(
+        "DELETE",
+        json!({}),
+        json!({ "id": "5b0b3c0e-4f4e-4a0e-9d55-0b6a2a0b8f3e" }),
+    ))
+    .unwrap();
+    assert_eq!(partial, None);
+
+    // Rows of other tables are not storage objects
+    let mut other = captured("INSERT", object_row("avatars", "u/1.png"), json!({}));
+    other["data"]["table"] = json!("buckets");
+    assert_eq!(StorageObjectChange::from_payload(&other).unwrap(), None);
+}
+
+#[test]
+fn test_storage_object_event_tolerates_missing_columns() {
+    let object: StorageObjectEvent =
+        serde_json::from_value(json!({ "bucket_id": "avatars", "name": "a.png" })).unwrap();
+    assert_eq!(object.size(), None);
+    assert_eq!(object.owner, None);
+}
+
+// Replies to the join with `join_response`, reports the join payload, then forwards `live`
+async fn start_realtime(
+    join_response: Value,
+) -> (
+    RealtimeClient,
+    oneshot::Receiver<Value>,
+    mpsc::UnboundedSender<Value>,
+) {
+    let (socket, mut server) = memory_socket();
+    let options = RealtimeClientOptions {
+        auto_reconnect: false,
+        heartbeat_interval: 60_000,
+        ..Default::default()
+    };
+    let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options, socket);
+    client.connect().await.unwrap();
+    let mut connection = timeout(WAIT, server.accept()).await.unwrap().unwrap();
+
+    let (join_tx, join_rx) = oneshot::channel();
+    let (live_tx, mut live_rx) = mpsc::unbounded_channel::<Value>();
+    tokio::spawn(async move {
+        let mut join_tx = Some(join_tx);
+        loop {
+            tokio::select! {
+                message = connection.recv_message() => {
+                    let Some(message) = message else { break };
+                    if message.event != ChannelEvent::PhoenixJoin {
+                        connection.reply_ok(&message).unwrap();
+                        continue;
+                    }
+                    if let Some(join_tx) = join_tx.take() {
+                        let _ = join_tx.send(message.payload.clone());
+                    }
+                    connection
+                        .send_json(&json!({
+                            "topic": message.topic,
+                            "event": "phx_reply",
+                            "payload": { "status": "ok", "response": join_response.clone() },
+                            "ref": message.message_ref,
+                        }))
+                        .unwrap();
+                }
+                event = live_rx.recv() => {
+                    let Some(event) = event else { break };
+                    connection.send_json(&event).unwrap();
+                }
+            }
+        }
+    });
+    (client, join_rx, live_tx)
+}
+
+#[tokio::test]
+async fn test_subscription_delivers_typed_events_for_bucket_and_prefix() {
+    let (realtime, join, live) = start_realtime(json!({
+        "postgres_changes": [{
+            "id": 38_713_247,
+            "event": "*",
+            "schema": "storage",
+            "table": "objects",
+            "filter": "bucket_id=eq.avatars"
+        }]
+    }))
+    .await;
+
+    let filter = StorageObjectFilter::new("avatars").with_prefix("uploads/");
+    let mut subscription = timeout(WAIT, storage_events::subscribe(&realtime, filter))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(subscription.warnings().is_empty());
+
+    let join = join.await.unwrap();
+    assert_eq!(
+        join["config"]["postgres_changes"],
+        json!([{
+            "event": "*",
+            "schema": "storage",
+            "table": "objects",
+            "filter": "bucket_id=eq.avatars"
+        }])
+    );
+
+    for payload in [
+        // Outside the prefix
+        captured("INSERT", object_row("avatars", "thumbs/1.png"), json!({})),
+        captured("INSERT", object_row("avatars", "uploads/1.png"), json!({})),
+        captured("UPDATE", object_row("avatars", "uploads/1.png"), json!({})),
+        // Deletes are not filtered by the server
+        captured(
+            "DELETE",
+            json!({}),
+            object_row("documents", "uploads/1.pdf"),
+        ),
+        captured("DELETE", json!({}), object_row("avatars", "uploads/1.png")),
+    ] {
+        live.send(message(payload)).unwrap();
+    }
+
+    let mut received = Vec::new();
+    for _ in 0..3 {
+        let change = timeout(WAIT, subscription.recv()).await.unwrap().unwrap();
+        received.push(change);
+    }
+    assert!(matches!(&received[0], StorageObjectChange::Created(o) if o.name == "uploads/1.png"));
+    assert!(matches!(&received[1], StorageObjectChange::Updated(_)));
+    assert!(matches!(&received[2], StorageObjectChange::Deleted(o) if o.bucket_id == "avatars"));
+    assert!(timeout(Duration::from_millis(100), subscription.recv())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_unacknowledged_subscription_surfaces_warning() {
+    // Join succeeds, but the server confirms no binding (table not in the publication)
+    let (realtime, _join, _live) = start_realtime(json!({})).await;
+
+    let filter = StorageObjectFilter::new("avatars").with_event(ChannelEvent::Insert);
+    let subscription = timeout(WAIT, storage_events::subscribe(&realtime, filter))
+        .await
+        .unwrap()
+        .unwrap();
+    match subscription.warnings().as_slice() {
+        [SubscriptionWarning::NoBindingsConfirmed { requested }] => {
+            assert_eq!(
+                requested,
+                &["INSERT on storage.objects (filter: bucket_id=eq.avatars)".to_string()]
+            );
+        }
+        other => panic!("expected NoBindingsConfirmed, got {:?}", other),
+    }
+}
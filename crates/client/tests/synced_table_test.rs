@@ -0,0 +1,220 @@
+// crates/client/tests/synced_table_test.rs
+#![cfg(feature = "realtime")]
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use supabase_rust_client::synced_table::{
+    NewerColumnWins, SyncedTable, SyncedTableConfig, TableChange,
+};
+use supabase_rust_postgrest::PostgrestClient;
+use supabase_rust_realtime::transport::memory_socket;
+use supabase_rust_realtime::{ChannelEvent, RealtimeClient, RealtimeClientOptions};
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const TOPIC: &str = "realtime:public:todos";
+const WAIT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct Todo {
+    id: i64,
+    title: String,
+    updated_at: String,
+}
+
+fn change(event_type: &str, commit: &str, record: Value, old_record: Value) -> Value {
+    json!({
+        "topic": TOPIC,
+        "event": "postgres_changes",
+        "payload": {
+            "data": {
+                "type": event_type,
+                "schema": "public",
+                "table": "todos",
+                "commit_timestamp": commit,
+                "record": record,
+                "old_record": old_record,
+                "columns": []
+            },
+            "ids": [1]
+        },
+        "ref": null
+    })
+}
+
+fn todo(id: i64, title: &str, updated_at: &str) -> Value {
+    json!({ "id": id, "title": title, "updated_at": updated_at })
+}
+
+// Replies to every message; after the join sends `on_join`, then forwards `live` events
+async fn start_realtime(on_join: Vec<Value>) -> (RealtimeClient, mpsc::UnboundedSender<Value>) {
+    let (socket, mut server) = memory_socket();
+    let options = RealtimeClientOptions {
+        auto_reconnect: false,
+        heartbeat_interval: 60_000,
+        ..Default::default()
+    };
+    let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options, socket);
+    client.connect().await.unwrap();
+    let mut connection = timeout(WAIT, server.accept()).await.unwrap().unwrap();
+
+    let (live_tx, mut live_rx) = mpsc::unbounded_channel::<Value>();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                message = connection.recv_message() => {
+                    let Some(message) = message else { break };
+                    connection.reply_ok(&message).unwrap();
+                    if message.event == ChannelEvent::PhoenixJoin {
+                        for event in &on_join {
+                            connection.send_json(event).unwrap();
+                        }
+                    }
+                }
+                event = live_rx.recv() => {
+                    let Some(event) = event else { break };
+                    connection.send_json(&event).unwrap();
+                }
+            }
+        }
+    });
+    (client, live_tx)
+}
+
+#[tokio::test]
+async fn test_events_during_snapshot_fetch_are_not_lost() {
+    let mock_server = MockServer::start().await;
+    // Slow snapshot: the realtime events below arrive while this is in flight
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/todos"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!([
+                    todo(1, "write tests", "2024-01-01T00:00:00Z"),
+                    todo(2, "review PR", "2024-01-01T00:00:00Z"),
+                ]))
+                .set_delay(Duration::from_millis(300)),
+        )
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let (realtime, live) = start_realtime(vec![
+        change(
+            "UPDATE",
+            "2024-01-01T00:00:02Z",
+            todo(1, "write more tests", "2024-01-01T00:00:02Z"),
+            json!({ "id": 1 }),
+        ),
+        // Older duplicate delivered late: must not overwrite the newer update
+        change(
+            "UPDATE",
+            "2024-01-01T00:00:01Z",
+            todo(1, "stale", "2024-01-01T00:00:01Z"),
+            json!({ "id": 1 }),
+        ),
+        change(
+            "DELETE",
+            "2024-01-01T00:00:03Z",
+            json!({}),
+            json!({ "id": 2 }),
+        ),
+        change(
+            "INSERT",
+            "2024-01-01T00:00:04Z",
+            todo(3, "ship it", "2024-01-01T00:00:04Z"),
+            json!({}),
+        ),
+    ])
+    .await;
+
+    let query = PostgrestClient::new(&mock_server.uri(), "anon", "todos", reqwest::Client::new())
+        .select("*");
+    let table = timeout(
+        WAIT,
+        SyncedTable::<Todo>::start(&realtime, query, SyncedTableConfig::new("todos", "id")),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(table.len(), 2);
+    assert_eq!(table.get("1").unwrap().title, "write more tests");
+    assert_eq!(table.get("2"), None);
+    assert_eq!(table.get("3").unwrap().title, "ship it");
+
+    let mut changes = table.changes();
+    live.send(change(
+        "DELETE",
+        "2024-01-01T00:00:05Z",
+        json!({}),
+        json!({ "id": 1 }),
+    ))
+    .unwrap();
+    let received = timeout(WAIT, changes.recv()).await.unwrap().unwrap();
+    assert_eq!(
+        received,
+        TableChange::Deleted {
+            key: "1".to_string()
+        }
+    );
+    assert_eq!(
+        table.iter().map(|(key, _)| key).collect::<Vec<_>>(),
+        vec!["3".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn test_newer_column_wins_ignores_older_rows() {
+    let mock_server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/todos"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([todo(
+            1,
+            "current",
+            "2024-01-02T00:00:00Z"
+        )])))
+        .mount(&mock_server)
+        .await;
+
+    let (realtime, live) = start_realtime(Vec::new()).await;
+    let query = PostgrestClient::new(&mock_server.uri(), "anon", "todos", reqwest::Client::new());
+    let config =
+        SyncedTableConfig::new("todos", "id").with_resolver(NewerColumnWins::new("updated_at"));
+    let table = SyncedTable::<Todo>::start(&realtime, query, config)
+        .await
+        .unwrap();
+    let mut changes = table.changes();
+
+    // An offline edit replayed with an older `updated_at` is ignored
+    live.send(change(
+        "UPDATE",
+        "2024-01-03T00:00:00Z",
+        todo(1, "offline edit", "2024-01-01T00:00:00Z"),
+        json!({ "id": 1 }),
+    ))
+    .unwrap();
+    live.send(change(
+        "UPDATE",
+        "2024-01-03T00:00:01Z",
+        todo(1, "newer", "2024-01-03T00:00:01Z"),
+        json!({ "id": 1 }),
+    ))
+    .unwrap();
+
+    let received = timeout(WAIT, changes.recv()).await.unwrap().unwrap();
+    assert_eq!(
+        received,
+        TableChange::Updated {
+            key: "1".to_string(),
+            row: Todo {
+                id: 1,
+                title: "newer".to_string(),
+                updated_at: "2024-01-03T00:00:01Z".to_string(),
+            },
+        }
+    );
+}
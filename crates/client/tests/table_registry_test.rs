@@ -0,0 +1,101 @@
+// crates/client/tests/table_registry_test.rs
+
+use serde_json::json;
+use supabase_rust_client::prelude::*;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+#[derive(Debug, serde::Deserialize)]
+#[allow(dead_code)]
+struct UserRow {
+    id: i64,
+}
+
+fn client_with(server: &MockServer, options: ClientOptions) -> SupabaseClientWrapper {
+    let config = SupabaseConfig::new(&server.uri(), "anon-key".to_string())
+        .unwrap()
+        .with_options(options);
+    SupabaseClientWrapper::new(config).unwrap()
+}
+
+#[tokio::test]
+async fn test_from_checked_rejects_typos_and_passes_registered_tables() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/user_profiles"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": 1 }])))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let registry = TableRegistry::new().register::<UserRow>("user_profiles");
+    let client = client_with(&server, ClientOptions::new().with_table_registry(registry));
+
+    let error = client.from_checked("user_profils").await.err().unwrap();
+    match &error {
+        SupabaseError::UnknownTable { table, suggestion } => {
+            assert_eq!(table, "user_profils");
+            assert_eq!(suggestion.as_deref(), Some("user_profiles"));
+        }
+        other => panic!("expected UnknownTable, got {:?}", other),
+    }
+    assert!(error.to_string().contains("did you mean \"user_profiles\""));
+
+    let rows: Vec<UserRow> = client
+        .from_checked("user_profiles")
+        .await
+        .unwrap()
+        .execute()
+        .await
+        .unwrap();
+    assert_eq!(rows[0].id, 1);
+
+    // Validation is opt-in: without `validate_tables`, `from()` lets any name through
+    assert!(client.from("user_profils").await.is_ok());
+    client
+        .from("user_profiles")
+        .await
+        .unwrap()
+        .execute::<UserRow>()
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_validate_tables_with_introspected_registry() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/rest/v1/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "swagger": "2.0",
+            "definitions": { "todos": {}, "profiles": {} }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let registry = client_with(&server, ClientOptions::new())
+        .fetch_table_registry()
+        .await
+        .unwrap();
+    assert_eq!(registry.names(), vec!["profiles", "todos"]);
+
+    let client = client_with(
+        &server,
+        ClientOptions::new()
+            .with_table_registry(registry)
+            .with_validate_tables(true),
+    );
+    assert!(client.from("todos").await.is_ok());
+    assert!(matches!(
+        client.from("todo").await,
+        Err(SupabaseError::UnknownTable { suggestion: Some(ref s), .. }) if s == "todos"
+    ));
+
+    // `from_checked()` without a registry is a configuration error
+    let client = client_with(&server, ClientOptions::new());
+    assert!(matches!(
+        client.from_checked("todos").await,
+        Err(SupabaseError::Config(_))
+    ));
+}
@@ -0,0 +1,134 @@
+// crates/client/tests/tenant_headers_test.rs
+
+use serde_json::{json, Value};
+use std::future::Future;
+use std::pin::Pin;
+use supabase_rust_client::client::TENANT_HEADER;
+use supabase_rust_client::prelude::*;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+type Operation<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+async fn mock_every_service(server: &MockServer) {
+    Mock::given(method("GET"))
+        .and(path("/auth/v1/settings"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(server)
+        .await;
+    // PostgREST, RPC, the bucket list and the function all accept an empty array
+    Mock::given(wiremock::matchers::any())
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(server)
+        .await;
+}
+
+fn client(server: &MockServer) -> SupabaseClientWrapper {
+    let config = SupabaseConfig::new(&server.uri(), "anon-key".to_string()).unwrap();
+    SupabaseClientWrapper::new(config).unwrap()
+}
+
+#[tokio::test]
+async fn test_tenant_header_reaches_every_service() {
+    let server = MockServer::start().await;
+    mock_every_service(&server).await;
+    let tenant = client(&server).for_tenant("tenant-a").unwrap();
+
+    let operations: Vec<(&str, Operation)> = vec![
+        (
+            "postgrest",
+            Box::pin(async {
+                let rows: Vec<Value> = tenant.from("items").await.unwrap().execute().await.unwrap();
+                assert!(rows.is_empty());
+            }),
+        ),
+        (
+            "rpc",
+            Box::pin(async {
+                let rpc = tenant.rpc("list_items", json!({})).await.unwrap();
+                rpc.call_rpc::<Value>().await.unwrap();
+            }),
+        ),
+        #[cfg(feature = "auth")]
+        (
+            "auth",
+            Box::pin(async {
+                tenant.auth.get_settings().await.unwrap();
+            }),
+        ),
+        #[cfg(feature = "storage")]
+        (
+            "storage",
+            Box::pin(async {
+                tenant.storage().list_buckets().await.unwrap();
+            }),
+        ),
+        #[cfg(feature = "functions")]
+        (
+            "functions",
+            Box::pin(async {
+                tenant
+                    .functions()
+                    .invoke_json::<Value, Value>("hello", None)
+                    .await
+                    .unwrap();
+            }),
+        ),
+    ];
+
+    for (service, operation) in operations {
+        let before = server.received_requests().await.unwrap().len();
+        operation.await;
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests.len() > before, "{service} sent no request");
+        for request in &requests[before..] {
+            let values = request.headers.get(&TENANT_HEADER.into());
+            assert_eq!(
+                values.map(|values| values[0].as_str()),
+                Some("tenant-a"),
+                "{service}: {} {}",
+                request.method,
+                request.url
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_for_tenant_leaves_the_original_client_untouched() {
+    let server = MockServer::start().await;
+    mock_every_service(&server).await;
+    let base = client(&server);
+    let tenant_a = base.for_tenant("tenant-a").unwrap();
+    // A second call replaces the tenant instead of sending both
+    let tenant_b = tenant_a.for_tenant("tenant-b").unwrap();
+
+    for client in [&base, &tenant_a, &tenant_b] {
+        let _: Vec<Value> = client.from("items").await.unwrap().execute().await.unwrap();
+    }
+    let tenants = server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| {
+            request
+                .headers
+                .get(&TENANT_HEADER.into())
+                .map(|values| values.iter().map(|v| v.to_string()).collect::<Vec<_>>())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tenants,
+        vec![
+            None,
+            Some(vec!["tenant-a".to_string()]),
+            Some(vec!["tenant-b".to_string()])
+        ]
+    );
+
+    assert!(matches!(
+        base.for_tenant("bad\ntenant"),
+        Err(SupabaseError::Config(_))
+    ));
+}
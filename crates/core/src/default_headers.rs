@@ -0,0 +1,152 @@
+//! すべてのリクエストに付けるヘッダー
+//!
+//! [`HeaderedClient`] は `reqwest::Client` と同じ名前のメソッドでリクエストを作り、
+//! 作ったリクエストに既定のヘッダー (マルチテナント構成の `x-tenant-id` など) を付ける。
+//! 各クライアントの `with_default_headers` はこれを使うため、呼び出しごとに
+//! ヘッダーを付け忘れることがない。送信のゲート ([`SendGate`]) も同じように持たせる。
+
+use crate::send_gate::SendGate;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, IntoUrl, Method, RequestBuilder};
+use std::sync::Arc;
+
+/// 既定のヘッダーを付けてリクエストを作る HTTP クライアント
+///
+/// ヘッダーはリクエストを作った時点で付くため、呼び出し側が後から `.header()` で
+/// 同じ名前のヘッダーを付けると両方が送られる。既定のヘッダーには、各 API が自分で
+/// 付けるヘッダー (`apikey`・`Authorization` など) 以外を使う。
+#[derive(Debug, Clone, Default)]
+pub struct HeaderedClient {
+    client: Client,
+    headers: HeaderMap,
+    send_gate: Option<Arc<dyn SendGate>>,
+}
+
+impl HeaderedClient {
+    /// 既定のヘッダーなしで包む
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            headers: HeaderMap::new(),
+            send_gate: None,
+        }
+    }
+
+    /// 既定のヘッダーを追加する (同じ名前のヘッダーは置き換える)
+    pub fn with_default_headers(mut self, headers: HeaderMap) -> Self {
+        let mut previous = None;
+        for (name, value) in headers {
+            // 同じ名前の 2 つ目以降の値は name が None になる
+            let name = match name {
+                Some(name) => {
+                    self.headers.remove(&name);
+                    previous = Some(name.clone());
+                    name
+                }
+                None => match &previous {
+                    Some(name) => name.clone(),
+                    None => continue,
+                },
+            };
+            self.headers.append(name, value);
+        }
+        self
+    }
+
+    /// 既定のヘッダー
+    pub fn default_headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// 送信のゲートを設定する (送信側は [`send_gate`](Self::send_gate) を
+    /// [`SendThroughGate::send_through`](crate::SendThroughGate::send_through) に渡す)
+    pub fn with_send_gate(mut self, gate: Arc<dyn SendGate>) -> Self {
+        self.send_gate = Some(gate);
+        self
+    }
+
+    /// 送信のゲート
+    pub fn send_gate(&self) -> Option<&Arc<dyn SendGate>> {
+        self.send_gate.as_ref()
+    }
+
+    /// 包んでいる `reqwest::Client`
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// 既定のヘッダーを付けたリクエストを作る
+    pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
+        self.client
+            .request(method, url)
+            .headers(self.headers.clone())
+    }
+
+    pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.request(Method::POST, url)
+    }
+
+    pub fn put<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.request(Method::PUT, url)
+    }
+
+    pub fn patch<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.request(Method::PATCH, url)
+    }
+
+    pub fn delete<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.request(Method::DELETE, url)
+    }
+
+    pub fn head<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.request(Method::HEAD, url)
+    }
+}
+
+impl From<Client> for HeaderedClient {
+    fn from(client: Client) -> Self {
+        Self::new(client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_default_headers_are_added_to_every_request() {
+        let mut tenant = HeaderMap::new();
+        tenant.insert("x-tenant-id", HeaderValue::from_static("t1"));
+        let client = HeaderedClient::new(Client::new()).with_default_headers(tenant);
+
+        for builder in [
+            client.get("http://localhost/a"),
+            client.post("http://localhost/a"),
+            client.delete("http://localhost/a"),
+            client.request(Method::OPTIONS, "http://localhost/a"),
+        ] {
+            let request = builder.header("apikey", "key").build().unwrap();
+            assert_eq!(request.headers()["x-tenant-id"], "t1");
+            assert_eq!(request.headers()["apikey"], "key");
+        }
+    }
+
+    #[test]
+    fn test_later_default_headers_replace_earlier_ones() {
+        let header = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-tenant-id", HeaderValue::from_static(value));
+            headers
+        };
+        let client = HeaderedClient::new(Client::new())
+            .with_default_headers(header("t1"))
+            .with_default_headers(header("t2"));
+        let values = client.default_headers().get_all("x-tenant-id");
+        assert_eq!(values.iter().collect::<Vec<_>>(), vec!["t2"]);
+    }
+}
@@ -0,0 +1,15 @@
+//! Supabase Rust クライアント間で共有する型
+
+pub mod default_headers;
+pub mod pagination;
+pub mod project;
+pub mod redact;
+pub mod request_id;
+pub mod send_gate;
+
+pub use default_headers::HeaderedClient;
+pub use pagination::{Page, Paged};
+pub use project::{check_api_key, check_project_url, ApiKeyKind, ConfigIssue, ProjectUrl};
+pub use redact::{scrub, Redacted};
+pub use request_id::{RequestIds, SendWithRequestId};
+pub use send_gate::{GatePass, SendError, SendGate, SendRejected, SendThroughGate};
@@ -0,0 +1,144 @@
+//! ページネーション
+//!
+//! PostgREST の `limit`/`offset`、Storage の `ListOptions`、Admin API の
+//! `page`/`per_page` を同じ [`Page`] で指定し、結果を [`Paged`] で受け取る。
+
+use serde::{Deserialize, Serialize};
+
+/// ページ指定 (`number` は 1 始まり)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Page {
+    pub number: u32,
+    pub size: u32,
+}
+
+impl Page {
+    /// ページを作成 (`number` が 0 の場合は 1 として扱う)
+    pub fn new(number: u32, size: u32) -> Self {
+        Self {
+            number: number.max(1),
+            size,
+        }
+    }
+
+    /// 最初のページ
+    pub fn first(size: u32) -> Self {
+        Self::new(1, size)
+    }
+
+    /// 次のページ
+    pub fn next(self) -> Self {
+        Self::new(self.number.saturating_add(1), self.size)
+    }
+
+    /// 前のページ (最初のページの場合は None)
+    pub fn prev(self) -> Option<Self> {
+        (self.number > 1).then(|| Self::new(self.number - 1, self.size))
+    }
+
+    /// 先頭からのオフセット
+    pub fn offset(&self) -> u64 {
+        (self.number.max(1) as u64 - 1) * self.size as u64
+    }
+
+    /// `offset`/`limit` からページを作成 (`offset` は `limit` の倍数に切り捨て)
+    pub fn from_offset(offset: u64, limit: u32) -> Self {
+        let number = if limit == 0 {
+            1
+        } else {
+            (offset / limit as u64).saturating_add(1)
+        };
+        Self::new(u32::try_from(number).unwrap_or(u32::MAX), limit)
+    }
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self::first(50)
+    }
+}
+
+/// ページ単位の取得結果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Paged<T> {
+    pub items: Vec<T>,
+    pub page: Page,
+    /// 全件数 (サーバーが返した場合のみ)
+    pub total: Option<u64>,
+    /// 次のページがあるかどうか (判定できない場合は None)
+    pub has_more: Option<bool>,
+}
+
+impl<T> Paged<T> {
+    /// 結果を作成 (`total` があれば `has_more` も計算する)
+    pub fn new(items: Vec<T>, page: Page, total: Option<u64>) -> Self {
+        let has_more = total.map(|total| page.offset() + (items.len() as u64) < total);
+        Self {
+            items,
+            page,
+            total,
+            has_more,
+        }
+    }
+
+    /// `has_more` を上書き
+    pub fn with_has_more(mut self, has_more: bool) -> Self {
+        self.has_more = Some(has_more);
+        self
+    }
+
+    /// 次のページ (次がないとわかっている場合は None)
+    pub fn next_page(&self) -> Option<Page> {
+        match self.has_more {
+            Some(false) => None,
+            _ => Some(self.page.next()),
+        }
+    }
+
+    /// 全ページ数 (全件数がわかる場合のみ)
+    pub fn total_pages(&self) -> Option<u64> {
+        let size = self.page.size as u64;
+        self.total
+            .map(|total| if size == 0 { 0 } else { total.div_ceil(size) })
+    }
+
+    /// 要素を変換
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Paged<U> {
+        Paged {
+            items: self.items.into_iter().map(f).collect(),
+            page: self.page,
+            total: self.total,
+            has_more: self.has_more,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_offset_round_trip() {
+        let page = Page::new(3, 25);
+        assert_eq!(page.offset(), 50);
+        assert_eq!(Page::from_offset(50, 25), page);
+        assert_eq!(Page::new(0, 10).offset(), 0);
+        assert_eq!(page.next().number, 4);
+        assert_eq!(Page::first(10).prev(), None);
+    }
+
+    #[test]
+    fn test_paged_has_more_from_total() {
+        let paged = Paged::new(vec![1, 2], Page::new(2, 2), Some(5));
+        assert_eq!(paged.has_more, Some(true));
+        assert_eq!(paged.total_pages(), Some(3));
+
+        let last = Paged::new(vec![5], Page::new(3, 2), Some(5));
+        assert_eq!(last.has_more, Some(false));
+        assert_eq!(last.next_page(), None);
+
+        let unknown = Paged::new(vec![1], Page::first(2), None);
+        assert_eq!(unknown.has_more, None);
+        assert_eq!(unknown.next_page(), Some(Page::new(2, 2)));
+    }
+}
@@ -0,0 +1,136 @@
+//! リクエスト ID によるログの突き合わせ
+//!
+//! [`SendWithRequestId::send_with_request_id`] で送ったリクエストには `x-client-request-id`
+//! (UUID v4) が付く。失敗したリクエストのエラーには [`RequestIds`] として、その ID と
+//! サーバーが返した `sb-request-id` / `cf-ray` が残るため、アプリのログと Supabase のログを
+//! どちらの ID でも検索できる。
+
+use reqwest::header::HeaderValue;
+use reqwest::{RequestBuilder, Response};
+use std::fmt;
+use std::future::Future;
+
+/// クライアントが振るリクエスト ID のヘッダー
+pub const CLIENT_REQUEST_ID_HEADER: &str = "x-client-request-id";
+
+/// Supabase が返すリクエスト ID のヘッダー
+pub const SB_REQUEST_ID_HEADER: &str = "sb-request-id";
+
+/// Cloudflare が返すリクエスト ID のヘッダー
+pub const CF_RAY_HEADER: &str = "cf-ray";
+
+/// 1 つのリクエストに紐づく ID
+///
+/// `Display` は ID がなければ空文字列、あれば ` [client_request_id=..., sb-request-id=...]`
+/// (先頭に空白) を出力するため、エラーメッセージの末尾にそのまま付けられる。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestIds {
+    /// 送信時に付けた `x-client-request-id`
+    pub client_request_id: Option<String>,
+    /// レスポンスの `sb-request-id`
+    pub sb_request_id: Option<String>,
+    /// レスポンスの `cf-ray`
+    pub cf_ray: Option<String>,
+}
+
+// レスポンスの拡張領域に保存する、送信時の `x-client-request-id`
+#[derive(Debug, Clone)]
+struct ClientRequestId(String);
+
+impl RequestIds {
+    /// レスポンスから ID を集める
+    ///
+    /// `client_request_id` は [`SendWithRequestId::send_with_request_id`] で送った場合のみ入る。
+    pub fn from_response(response: &Response) -> Self {
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Self {
+            client_request_id: response
+                .extensions()
+                .get::<ClientRequestId>()
+                .map(|id| id.0.clone()),
+            sb_request_id: header(SB_REQUEST_ID_HEADER),
+            cf_ray: header(CF_RAY_HEADER),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.client_request_id.is_none() && self.sb_request_id.is_none() && self.cf_ray.is_none()
+    }
+}
+
+impl fmt::Display for RequestIds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ids = [
+            ("client_request_id", &self.client_request_id),
+            (SB_REQUEST_ID_HEADER, &self.sb_request_id),
+            (CF_RAY_HEADER, &self.cf_ray),
+        ]
+        .into_iter()
+        .filter_map(|(name, id)| id.as_ref().map(|id| format!("{}={}", name, id)))
+        .collect::<Vec<_>>();
+        if ids.is_empty() {
+            return Ok(());
+        }
+        write!(f, " [{}]", ids.join(", "))
+    }
+}
+
+/// `x-client-request-id` を付けてリクエストを送る
+pub trait SendWithRequestId {
+    /// `x-client-request-id` を付けて送信する
+    ///
+    /// 呼び出し側がすでにヘッダーを設定していればその値を使う。
+    /// ID はレスポンスに保存され、[`RequestIds::from_response`] で取り出せる。
+    fn send_with_request_id(self) -> impl Future<Output = reqwest::Result<Response>> + Send;
+}
+
+impl SendWithRequestId for RequestBuilder {
+    fn send_with_request_id(self) -> impl Future<Output = reqwest::Result<Response>> + Send {
+        let (client, request) = self.build_split();
+        async move {
+            let mut request = request?;
+            let existing = request
+                .headers()
+                .get(CLIENT_REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let id = match existing {
+                Some(id) => id,
+                None => {
+                    let id = uuid::Uuid::new_v4().to_string();
+                    request.headers_mut().insert(
+                        CLIENT_REQUEST_ID_HEADER,
+                        HeaderValue::from_str(&id).expect("UUID is a valid header value"),
+                    );
+                    id
+                }
+            };
+            let mut response = client.execute(request).await?;
+            response.extensions_mut().insert(ClientRequestId(id));
+            Ok(response)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_lists_present_ids() {
+        assert_eq!(RequestIds::default().to_string(), "");
+        let ids = RequestIds {
+            client_request_id: Some("c-1".to_string()),
+            sb_request_id: None,
+            cf_ray: Some("8a1b-NRT".to_string()),
+        };
+        assert_eq!(ids.to_string(), " [client_request_id=c-1, cf-ray=8a1b-NRT]");
+        assert!(!ids.is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+//! 送信の前後に呼ばれるゲート
+//!
+//! [`SendGate`] は送信前にリクエストを通すかを決め、通したリクエストの結果
+//! (レスポンスのステータスまたは送信エラー) を受け取る。統合クライアントはこれで
+//! サービスごとのサーキットブレーカーを各クライアントに組み込む。ゲートを設定していない
+//! クライアントは [`SendWithRequestId::send_with_request_id`] と同じように送る。
+
+use crate::request_id::SendWithRequestId;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 送信を通すかを決めるゲート
+pub trait SendGate: Send + Sync + fmt::Debug {
+    /// 送信前に呼ばれる。`Err` なら送信せずにそのエラーを返す
+    fn admit(&self) -> Result<Box<dyn GatePass>, SendRejected>;
+}
+
+/// ゲートを通した 1 回の送信
+///
+/// 結果を渡さずに破棄された場合 (送信中のキャンセルなど) は結果を記録しない。
+pub trait GatePass: Send {
+    /// 送信の結果 (レスポンスのステータスまたは送信エラー)
+    fn finish(self: Box<Self>, outcome: Result<StatusCode, &reqwest::Error>);
+}
+
+/// ゲートが送信を止めた (リクエストは送信していない)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendRejected {
+    /// 止めた理由
+    pub reason: String,
+    /// 再び送信を試せるまでの時間の目安
+    pub retry_in: Duration,
+}
+
+impl fmt::Display for SendRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (retry in {:?})", self.reason, self.retry_in)
+    }
+}
+
+impl Error for SendRejected {}
+
+/// ゲートを通した送信のエラー
+#[derive(Debug)]
+pub enum SendError {
+    /// ゲートが送信を止めた
+    Rejected(SendRejected),
+    /// 送信に失敗した
+    Http(reqwest::Error),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Rejected(rejected) => write!(f, "request not sent: {}", rejected),
+            SendError::Http(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for SendError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SendError::Rejected(rejected) => Some(rejected),
+            SendError::Http(e) => Some(e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for SendError {
+    fn from(error: reqwest::Error) -> Self {
+        SendError::Http(error)
+    }
+}
+
+/// ゲートを通してリクエストを送る
+pub trait SendThroughGate {
+    /// `gate` が通したときだけ `x-client-request-id` を付けて送信し、結果をゲートに渡す
+    ///
+    /// `gate` が `None` なら [`SendWithRequestId::send_with_request_id`] と同じ。
+    fn send_through(
+        self,
+        gate: Option<&Arc<dyn SendGate>>,
+    ) -> impl Future<Output = Result<Response, SendError>> + Send;
+}
+
+impl SendThroughGate for RequestBuilder {
+    fn send_through(
+        self,
+        gate: Option<&Arc<dyn SendGate>>,
+    ) -> impl Future<Output = Result<Response, SendError>> + Send {
+        let pass = gate.map(|gate| gate.admit());
+        async move {
+            let pass = match pass {
+                None => None,
+                Some(Ok(pass)) => Some(pass),
+                Some(Err(rejected)) => return Err(SendError::Rejected(rejected)),
+            };
+            let result = self.send_with_request_id().await;
+            if let Some(pass) = pass {
+                pass.finish(result.as_ref().map(Response::status));
+            }
+            Ok(result?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct RecordingGate {
+        closed: bool,
+        outcomes: Arc<Mutex<Vec<Option<u16>>>>,
+    }
+
+    struct RecordingPass(Arc<Mutex<Vec<Option<u16>>>>);
+
+    impl SendGate for RecordingGate {
+        fn admit(&self) -> Result<Box<dyn GatePass>, SendRejected> {
+            if self.closed {
+                return Err(SendRejected {
+                    reason: "closed".to_string(),
+                    retry_in: Duration::from_secs(1),
+                });
+            }
+            Ok(Box::new(RecordingPass(self.outcomes.clone())))
+        }
+    }
+
+    impl GatePass for RecordingPass {
+        fn finish(self: Box<Self>, outcome: Result<StatusCode, &reqwest::Error>) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(outcome.ok().map(|status| status.as_u16()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_closed_gate_sends_nothing_and_open_gate_records_outcomes() {
+        let client = reqwest::Client::new();
+        let closed: Arc<dyn SendGate> = Arc::new(RecordingGate {
+            closed: true,
+            ..Default::default()
+        });
+        // 接続先がなくても送信しないので、拒否のエラーになる
+        let result = client
+            .get("http://127.0.0.1:1/")
+            .send_through(Some(&closed))
+            .await;
+        assert!(
+            matches!(result, Err(SendError::Rejected(_))),
+            "{:?}",
+            result
+        );
+
+        let gate = RecordingGate::default();
+        let outcomes = gate.outcomes.clone();
+        let gate: Arc<dyn SendGate> = Arc::new(gate);
+        let result = client
+            .get("http://127.0.0.1:1/")
+            .send_through(Some(&gate))
+            .await;
+        assert!(matches!(result, Err(SendError::Http(_))), "{:?}", result);
+        assert_eq!(*outcomes.lock().unwrap(), vec![None]);
+    }
+}
@@ -0,0 +1,156 @@
+//! 関数レスポンスのキャッシュと条件付きリクエスト
+//!
+//! `FunctionsClient::with_cache` でキャッシュを設定すると、`invoke` のレスポンスを
+//! 関数名・リクエスト本文・ヘッダーのハッシュをキーに保存する。
+//! `Cache-Control: max-age` の期間内はリクエストを送らずにキャッシュを返し、
+//! 期限切れ後は `ETag` を `If-None-Match` として送って 304 ならキャッシュを返す。
+//! `Cache-Control: no-store` のレスポンスは保存しない。
+
+use bytes::Bytes;
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// キャッシュされたレスポンス
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HashMap<String, String>,
+    pub body: Bytes,
+    pub etag: Option<String>,
+    /// この時刻まではリクエストを送らずに使う (max-age がなければ None)
+    pub fresh_until: Option<Instant>,
+}
+
+impl CachedResponse {
+    /// max-age の期間内か
+    pub fn is_fresh(&self) -> bool {
+        self.fresh_until
+            .is_some_and(|fresh_until| Instant::now() < fresh_until)
+    }
+}
+
+/// 関数レスポンスのキャッシュ
+pub trait FunctionCache: Send + Sync {
+    fn get(&self, key: &str) -> Option<CachedResponse>;
+    fn put(&self, key: &str, response: CachedResponse);
+    fn remove(&self, key: &str);
+}
+
+/// メモリ上のキャッシュ
+#[derive(Debug, Default)]
+pub struct InMemoryFunctionCache {
+    entries: Mutex<HashMap<String, CachedResponse>>,
+}
+
+impl InMemoryFunctionCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 保存されているエントリ数
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl FunctionCache for InMemoryFunctionCache {
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    fn put(&self, key: &str, response: CachedResponse) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), response);
+    }
+
+    fn remove(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+/// `Cache-Control` ヘッダーのうちキャッシュ判定に使う値
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct CacheControl {
+    pub(crate) no_store: bool,
+    pub(crate) max_age: Option<Duration>,
+}
+
+impl CacheControl {
+    pub(crate) fn parse(value: &str) -> Self {
+        let mut cache_control = Self::default();
+        for directive in value.split(',').map(str::trim) {
+            let (name, argument) = directive
+                .split_once('=')
+                .map_or((directive, None), |(name, argument)| (name, Some(argument)));
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => cache_control.no_store = true,
+                "max-age" => {
+                    cache_control.max_age = argument
+                        .and_then(|seconds| seconds.trim_matches('"').parse().ok())
+                        .map(Duration::from_secs);
+                }
+                _ => {}
+            }
+        }
+        cache_control
+    }
+
+    pub(crate) fn from_headers(headers: &HashMap<String, String>) -> Self {
+        header(headers, "cache-control")
+            .map(Self::parse)
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn fresh_until(&self) -> Option<Instant> {
+        self.max_age
+            .filter(|max_age| !max_age.is_zero())
+            .map(|max_age| Instant::now() + max_age)
+    }
+}
+
+// ヘッダー名は大文字小文字を区別しない
+pub(crate) fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// キャッシュキー (関数名 + 本文とヘッダーの FNV-1a ハッシュ)
+///
+/// `Authorization` などのヘッダーも含めるため、ユーザーごとに別のエントリになる。
+pub(crate) fn cache_key(
+    function_name: &str,
+    body: &[u8],
+    headers: &HashMap<String, String>,
+) -> String {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let mut hash = OFFSET;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes.iter().chain([0u8].iter()) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    };
+
+    write(body);
+    let mut headers: Vec<_> = headers
+        .iter()
+        .map(|(key, value)| (key.to_ascii_lowercase(), value))
+        .collect();
+    headers.sort();
+    for (key, value) in headers {
+        write(key.as_bytes());
+        write(value.as_bytes());
+    }
+    format!("{}:{:016x}", function_name, hash)
+}
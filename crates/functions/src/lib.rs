@@ -5,15 +5,31 @@
 use base64::Engine;
 use bytes::{BufMut, Bytes, BytesMut};
 use futures_util::{Stream, StreamExt};
-use reqwest::{Client, Response, StatusCode};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::pin::Pin;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use supabase_rust_core::{
+    HeaderedClient, RequestIds, SendError, SendGate, SendRejected, SendThroughGate,
+};
 use thiserror::Error;
 use url::Url;
 
+pub use supabase_rust_core::Redacted;
+
+mod cache;
+mod spec;
+mod trace;
+
+use cache::CacheControl;
+pub use cache::{CachedResponse, FunctionCache, InMemoryFunctionCache};
+pub use spec::FunctionSpec;
+use trace::InvokeSpan;
+pub use trace::{TraceContext, TRACEPARENT_HEADER, TRACESTATE_HEADER};
+
 /// エラー型の詳細
 #[derive(Debug, Clone, Deserialize)]
 pub struct FunctionErrorDetails {
@@ -35,16 +51,38 @@ pub enum FunctionsError {
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
 
-    #[error("Function error (status: {status}): {message}")]
+    #[error("Function error (status: {status}): {message}{request_ids}")]
     FunctionError {
         message: String,
         status: StatusCode,
         details: Option<FunctionErrorDetails>,
+        /// `x-client-request-id` とサーバーの `sb-request-id` / `cf-ray`
+        request_ids: Box<RequestIds>,
+    },
+
+    /// 429 Too Many Requests (`retry_after` は `retry-after` ヘッダーの値)
+    #[error("Rate limited: {message}{request_ids}")]
+    RateLimited {
+        retry_after: Option<Duration>,
+        message: String,
+        request_ids: Box<RequestIds>,
+    },
+
+    /// `retry-after` ヘッダー付きの 503 Service Unavailable
+    #[error("Service unavailable (retry after {retry_after:?}): {message}{request_ids}")]
+    ServiceUnavailable {
+        retry_after: Duration,
+        message: String,
+        request_ids: Box<RequestIds>,
     },
 
     #[error("Timeout error: Function execution exceeded timeout limit")]
     TimeoutError,
 
+    /// 送信のゲート (統合クライアントのサーキットブレーカーなど) が止めた (リクエストは送信していない)
+    #[error("Request not sent: {0}")]
+    Rejected(SendRejected),
+
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
 }
@@ -55,6 +93,7 @@ impl FunctionsError {
             message,
             status: StatusCode::INTERNAL_SERVER_ERROR,
             details: None,
+            request_ids: Default::default(),
         }
     }
 
@@ -63,6 +102,7 @@ impl FunctionsError {
             message: format!("Function returned error status: {}", response.status()),
             status: response.status(),
             details: None,
+            request_ids: Box::new(RequestIds::from_response(response)),
         }
     }
 
@@ -74,25 +114,143 @@ impl FunctionsError {
             ),
             status: response.status(),
             details: Some(details),
+            request_ids: Box::new(RequestIds::from_response(response)),
+        }
+    }
+
+    /// サーバーが指定した再試行までの待ち時間
+    ///
+    /// `RateLimited` と `ServiceUnavailable` の場合のみ返す。
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            Self::ServiceUnavailable { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+
+    /// 再試行で成功する可能性があるか (レート制限、タイムアウト、接続エラー、502/503/504)
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::RateLimited { .. } | Self::ServiceUnavailable { .. } | Self::TimeoutError => true,
+            Self::RequestError(e) => e.is_connect(),
+            Self::FunctionError { status, .. } => matches!(
+                *status,
+                StatusCode::BAD_GATEWAY
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            ),
+            _ => false,
+        }
+    }
+
+    // エラーステータスを変換する (429 は `RateLimited`、`retry-after` 付きの 503 は `ServiceUnavailable`)
+    fn from_status(
+        status: StatusCode,
+        retry_after: Option<Duration>,
+        message: String,
+        details: Option<FunctionErrorDetails>,
+        request_ids: Box<RequestIds>,
+    ) -> Self {
+        match (status, retry_after) {
+            (StatusCode::TOO_MANY_REQUESTS, _) => Self::RateLimited {
+                retry_after,
+                message,
+                request_ids,
+            },
+            (StatusCode::SERVICE_UNAVAILABLE, Some(retry_after)) => Self::ServiceUnavailable {
+                retry_after,
+                message,
+                request_ids,
+            },
+            _ => Self::FunctionError {
+                message,
+                status,
+                details,
+                request_ids,
+            },
         }
     }
 }
 
+/// `retry-after` ヘッダーを解釈する (秒数または HTTP-date、過去の日時は 0)
+fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+fn retry_after_header(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_retry_after(value, SystemTime::now()))
+}
+
 pub type Result<T> = std::result::Result<T, FunctionsError>;
 
+/// クライアントのデフォルトタイムアウト (`without_timeout()` で無効化しない限り適用)
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// `invoke_with_retry` / `invoke_batch` の再試行設定
+///
+/// 待ち時間はサーバーの `retry-after` を優先し、なければ指数バックオフ。
+#[derive(Debug, Clone)]
+pub struct InvokeRetryPolicy {
+    /// 最大試行回数 (初回を含む)
+    pub max_attempts: u32,
+    /// 最初のリトライまでの待ち時間 (以降は倍々で増加)
+    pub initial_backoff: Duration,
+    /// 待ち時間の上限 (`retry-after` には適用しない)
+    pub max_backoff: Duration,
+}
+
+impl Default for InvokeRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl InvokeRetryPolicy {
+    /// リトライしない設定
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    fn delay(&self, attempt: u32, error: &FunctionsError) -> Duration {
+        error.retry_after().unwrap_or_else(|| {
+            let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+            self.initial_backoff
+                .saturating_mul(factor)
+                .min(self.max_backoff)
+        })
+    }
+}
+
 /// 関数呼び出しオプション
 #[derive(Clone, Debug)]
 pub struct FunctionOptions {
     /// カスタムHTTPヘッダー
     pub headers: Option<HashMap<String, String>>,
 
-    /// 関数タイムアウト（秒）
+    /// 関数タイムアウト（秒、None の場合はクライアントのデフォルト）
     pub timeout_seconds: Option<u64>,
 
     /// レスポンスのコンテンツタイプを指定（デフォルトはJSONとして処理）
     pub response_type: ResponseType,
 
-    /// リクエストのコンテンツタイプ
+    /// リクエストのコンテンツタイプ (本文がある場合だけ送る。既定は `application/json`)
     pub content_type: Option<String>,
 }
 
@@ -123,9 +281,49 @@ pub enum ResponseType {
     Stream,
 }
 
+/// ウォームアップ (`ping`) に使う HTTP メソッド
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PingMethod {
+    /// OPTIONS (CORS プリフライトと同じ。関数本体の処理をほぼ走らせない)
+    #[default]
+    Options,
+
+    /// GET
+    Get,
+}
+
+/// `ping` の結果
+#[derive(Debug, Clone)]
+pub struct PingResult {
+    pub function_name: String,
+    pub status: StatusCode,
+    /// リクエスト送信からレスポンスヘッダー受信までの時間
+    pub duration: Duration,
+    /// コールドスタートだったか (ヘッダーから判断できない場合は None)
+    pub cold_start: Option<bool>,
+    /// `Server-Timing` ヘッダー (存在する場合)
+    pub server_timing: Option<String>,
+}
+
 /// ストリーミングレスポンス用の型
 pub type ByteStream = Pin<Box<dyn Stream<Item = Result<Bytes>> + Send>>;
 
+/// ストリーミングリクエスト (`invoke_with_body_stream`) のオプション
+#[derive(Clone, Debug, Default)]
+pub struct StreamBodyOptions {
+    /// カスタムHTTPヘッダー
+    pub headers: Option<HashMap<String, String>>,
+
+    /// 本文の長さ (わかっている場合は `Content-Length`、None なら `Transfer-Encoding: chunked`)
+    pub content_length: Option<u64>,
+
+    /// アップロードとレスポンスの受信を含む全体の期限 (None の場合はクライアントのデフォルト)
+    pub timeout: Option<Duration>,
+
+    /// リクエスト本文のチャンクの間隔の上限 (None なら制限しない)
+    pub idle_timeout: Option<Duration>,
+}
+
 /// 関数レスポンス
 #[derive(Debug, Clone)]
 pub struct FunctionResponse<T> {
@@ -142,8 +340,13 @@ pub struct FunctionResponse<T> {
 /// Edge Functions クライアント
 pub struct FunctionsClient {
     base_url: String,
-    api_key: String,
-    http_client: Client,
+    api_key: Redacted<String>,
+    http_client: HeaderedClient,
+    default_headers: HashMap<String, String>,
+    default_content_type: Option<String>,
+    default_timeout: Option<Duration>,
+    cache: Option<Arc<dyn FunctionCache>>,
+    trace_context: Option<TraceContext>,
 }
 
 /// 関数リクエストを表す構造体
@@ -173,50 +376,185 @@ impl FunctionsClient {
     pub fn new(supabase_url: &str, supabase_key: &str, http_client: Client) -> Self {
         Self {
             base_url: supabase_url.to_string(),
-            api_key: supabase_key.to_string(),
-            http_client,
+            api_key: supabase_key.into(),
+            http_client: http_client.into(),
+            default_headers: HashMap::new(),
+            default_content_type: None,
+            default_timeout: Some(DEFAULT_TIMEOUT),
+            cache: None,
+            trace_context: None,
         }
     }
 
-    /// Edge Function を呼び出す
-    pub async fn invoke<T: DeserializeOwned, B: Serialize>(
-        &self,
-        function_name: &str,
-        body: Option<B>,
-        options: Option<FunctionOptions>,
-    ) -> Result<FunctionResponse<T>> {
-        let opts = options.unwrap_or_default();
+    /// すべての呼び出しに適用するデフォルトオプションを設定
+    ///
+    /// 呼び出しごとのオプションが優先される (ヘッダーはマージ、それ以外は上書き)。
+    /// `timeout_seconds` が None の場合は現在のデフォルトタイムアウトを維持する。
+    pub fn with_default_options(mut self, options: FunctionOptions) -> Self {
+        self.default_headers = options.headers.unwrap_or_default();
+        self.default_content_type = options.content_type;
+        if let Some(timeout) = options.timeout_seconds {
+            self.default_timeout = Some(Duration::from_secs(timeout));
+        }
+        self
+    }
 
-        // URLの構築
+    /// すべての呼び出しに付与するヘッダーを追加
+    pub fn with_default_header(mut self, key: &str, value: &str) -> Self {
+        self.default_headers
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// すべてのリクエストに付けるヘッダー (マルチテナント構成の `x-tenant-id` など)
+    ///
+    /// `with_default_header` と違い、`with_default_options` で置き換わらない。
+    pub fn with_default_headers(mut self, headers: reqwest::header::HeaderMap) -> Self {
+        self.http_client = self.http_client.with_default_headers(headers);
+        self
+    }
+
+    /// 送信前にリクエストを通すかを決めるゲート (統合クライアントのサーキットブレーカーなど)
+    ///
+    /// ゲートが止めた呼び出しは送信せず `FunctionsError::Rejected` を返す。
+    pub fn with_send_gate(mut self, gate: Arc<dyn SendGate>) -> Self {
+        self.http_client = self.http_client.with_send_gate(gate);
+        self
+    }
+
+    /// デフォルトのタイムアウトを設定
+    pub fn with_default_timeout(mut self, timeout: Duration) -> Self {
+        self.default_timeout = Some(timeout);
+        self
+    }
+
+    /// デフォルトのタイムアウトを無効化 (呼び出しごとの `timeout_seconds` は引き続き有効)
+    pub fn without_timeout(mut self) -> Self {
+        self.default_timeout = None;
+        self
+    }
+
+    /// デフォルトのタイムアウト
+    pub fn default_timeout(&self) -> Option<Duration> {
+        self.default_timeout
+    }
+
+    /// `invoke` のレスポンスをキャッシュし、条件付きリクエスト (`If-None-Match`) を使う
+    ///
+    /// `max-age` の期間内はリクエストを送らずにキャッシュを返す。`no-store` のレスポンスは保存しない。
+    pub fn with_cache(mut self, cache: Arc<dyn FunctionCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// すべての呼び出しに `traceparent` / `tracestate` を付ける
+    ///
+    /// `tracing-opentelemetry` フィーチャーで現在の span のコンテキストが取れる場合はそちらを優先する。
+    pub fn with_trace_context(mut self, context: TraceContext) -> Self {
+        self.trace_context = Some(context);
+        self
+    }
+
+    // デフォルトと呼び出しごとのヘッダーをマージ (呼び出しごとの値を優先)
+    fn merged_headers(&self, options: &FunctionOptions) -> HashMap<String, String> {
+        let mut headers = self.default_headers.clone();
+        if let Some(call_headers) = &options.headers {
+            for (key, value) in call_headers {
+                headers.retain(|existing, _| !existing.eq_ignore_ascii_case(key));
+                headers.insert(key.clone(), value.clone());
+            }
+        }
+        headers
+    }
+
+    /// エラー本文から API キーと送信したトークン (`Authorization` ヘッダー) を取り除く
+    fn scrub_secrets(&self, text: &str, headers: &HashMap<String, String>) -> String {
+        let mut secrets = vec![self.api_key.expose().as_str()];
+        secrets.extend(
+            headers
+                .iter()
+                .filter(|(key, _)| key.eq_ignore_ascii_case("authorization"))
+                .map(|(_, value)| value.trim_start_matches("Bearer ").trim()),
+        );
+        supabase_rust_core::scrub(text, &secrets)
+    }
+
+    /// `{base_url}/functions/v1/{function_name}`
+    fn function_url(&self, function_name: &str) -> Result<Url> {
         let mut url = Url::parse(&self.base_url)?;
         url.path_segments_mut()
             .map_err(|_| FunctionsError::UrlError(url::ParseError::EmptyHost))?
             .push("functions")
             .push("v1")
             .push(function_name);
+        Ok(url)
+    }
+
+    /// 認証ヘッダー・タイムアウト・コンテンツタイプ (本文がある場合) を設定した POST リクエスト
+    fn post_request(
+        &self,
+        function_name: &str,
+        timeout: Option<Duration>,
+        content_type: Option<&str>,
+    ) -> Result<RequestBuilder> {
+        // URLの構築
+        let url = self.function_url(function_name)?;
 
         // リクエストの構築
         let mut request_builder = self
             .http_client
             .post(url)
-            .header("apikey", &self.api_key)
-            .header("Authorization", format!("Bearer {}", &self.api_key));
+            .header("apikey", self.api_key.expose())
+            .header("Authorization", format!("Bearer {}", self.api_key.expose()));
 
-        // リクエストタイムアウトの設定
-        if let Some(timeout) = opts.timeout_seconds {
-            request_builder = request_builder.timeout(Duration::from_secs(timeout));
+        // リクエストタイムアウトの設定 (レスポンス本文の受信までを含む全体の期限)
+        if let Some(timeout) = timeout.or(self.default_timeout) {
+            request_builder = request_builder.timeout(timeout);
         }
 
-        // コンテンツタイプの設定
-        if let Some(content_type) = opts.content_type {
+        if let Some(content_type) = content_type {
             request_builder = request_builder.header("Content-Type", content_type);
         }
+        Ok(request_builder)
+    }
+
+    /// 関数呼び出しリクエストを送信し、成功レスポンスを返す
+    async fn send_request<B: Serialize>(
+        &self,
+        function_name: &str,
+        body: Option<B>,
+        options: &FunctionOptions,
+        accept: Option<&str>,
+        if_none_match: Option<&str>,
+    ) -> Result<Response> {
+        // コンテンツタイプの設定 (本文を JSON で送る場合だけ。デフォルトはJSON)
+        let content_type = body.as_ref().map(|_| {
+            options
+                .content_type
+                .as_deref()
+                .or(self.default_content_type.as_deref())
+                .unwrap_or("application/json")
+        });
+        let mut request_builder = self.post_request(
+            function_name,
+            options.timeout_seconds.map(Duration::from_secs),
+            content_type,
+        )?;
+
+        // Accept ヘッダーを設定
+        if let Some(accept) = accept {
+            request_builder = request_builder.header("Accept", accept);
+        }
+
+        // キャッシュの ETag (304 はエラーにしない)
+        if let Some(etag) = if_none_match {
+            request_builder = request_builder.header("If-None-Match", etag);
+        }
 
         // カスタムヘッダーの追加
-        if let Some(headers) = opts.headers {
-            for (key, value) in headers {
-                request_builder = request_builder.header(key, value);
-            }
+        let headers = self.merged_headers(options);
+        for (key, value) in &headers {
+            request_builder = request_builder.header(key, value);
         }
 
         // リクエストボディの追加
@@ -224,117 +562,261 @@ impl FunctionsClient {
             request_builder = request_builder.json(&body_data);
         }
 
+        self.send(
+            function_name,
+            request_builder,
+            &headers,
+            if_none_match.is_some(),
+        )
+        .await
+    }
+
+    /// リクエストを送信し、エラーステータスを `FunctionError` に変換する
+    async fn send(
+        &self,
+        function_name: &str,
+        request_builder: RequestBuilder,
+        headers: &HashMap<String, String>,
+        allow_not_modified: bool,
+    ) -> Result<Response> {
+        // トレースコンテキストの伝播
+        let (span, request_builder) =
+            InvokeSpan::start(function_name, self.trace_context.as_ref(), request_builder);
+
         // リクエストの送信
-        let response = request_builder.send().await.map_err(|e| {
-            if e.is_timeout() {
-                FunctionsError::TimeoutError
-            } else {
-                FunctionsError::from(e)
-            }
-        })?;
+        let response = request_builder
+            .send_through(self.http_client.send_gate())
+            .await
+            .map_err(send_error)?;
 
         // ステータスコードの確認
         let status = response.status();
-        if !status.is_success() {
-            // レスポンスのクローンを作成してエラー処理に使用
-            let status_copy = status;
-
+        span.record_status(status);
+        let not_modified = allow_not_modified && status == StatusCode::NOT_MODIFIED;
+        if !status.is_success() && !not_modified {
+            let retry_after = retry_after_header(&response);
+            let request_ids = Box::new(RequestIds::from_response(&response));
             // エラーレスポンスのパース
-            let error_body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            let error_body = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+                headers,
+            );
 
             if let Ok(error_details) = serde_json::from_str::<FunctionErrorDetails>(&error_body) {
-                return Err(FunctionsError::FunctionError {
-                    message: error_details.message.as_ref().map_or_else(
-                        || format!("Function returned error status: {}", status_copy),
+                return Err(FunctionsError::from_status(
+                    status,
+                    retry_after,
+                    error_details.message.as_ref().map_or_else(
+                        || format!("Function returned error status: {}", status),
                         |msg| msg.clone(),
                     ),
-                    status: status_copy,
-                    details: Some(error_details),
-                });
+                    Some(error_details),
+                    request_ids,
+                ));
             } else {
-                return Err(FunctionsError::FunctionError {
-                    message: error_body,
-                    status: status_copy,
-                    details: None,
-                });
+                return Err(FunctionsError::from_status(
+                    status,
+                    retry_after,
+                    error_body,
+                    None,
+                    request_ids,
+                ));
             }
         }
 
-        // レスポンスヘッダーの抽出
-        let headers = response
+        Ok(response)
+    }
+
+    /// レスポンスヘッダーを抽出
+    fn response_headers(response: &Response) -> HashMap<String, String> {
+        response
             .headers()
             .iter()
             .map(|(name, value)| (name.to_string(), value.to_str().unwrap_or("").to_string()))
-            .collect::<HashMap<String, String>>();
-
-        // レスポンスタイプに応じた処理
-        match opts.response_type {
-            ResponseType::Json => {
-                let data = response.json::<T>().await.map_err(|e| {
-                    FunctionsError::JsonError(serde_json::from_str::<T>("{}").err().unwrap_or_else(
-                        || {
-                            serde_json::Error::io(std::io::Error::new(
-                                std::io::ErrorKind::InvalidData,
-                                e.to_string(),
-                            ))
-                        },
-                    ))
-                })?;
+            .collect()
+    }
 
-                Ok(FunctionResponse {
-                    data,
-                    status,
-                    headers,
-                })
-            }
-            ResponseType::Text => {
-                // テキスト処理
-                let text = response.text().await?;
+    /// Edge Function を呼び出す
+    pub async fn invoke<T: DeserializeOwned, B: Serialize>(
+        &self,
+        function_name: &str,
+        body: Option<B>,
+        options: Option<FunctionOptions>,
+    ) -> Result<FunctionResponse<T>> {
+        let opts = options.unwrap_or_default();
 
-                // テキストからデシリアライズを試みる
-                let data: T = serde_json::from_str(&text).unwrap_or_else(|_| {
-                    panic!("Failed to deserialize text response as requested type")
-                });
+        if opts.response_type == ResponseType::Stream {
+            // ストリームレスポンスの場合、通常のデシリアライズではなく
+            // 別のストリーム処理用のメソッドを使用する必要がある
+            return Err(FunctionsError::InvalidResponse(
+                "Stream response type cannot be handled by invoke(). Use invoke_stream() instead."
+                    .to_string(),
+            ));
+        }
 
-                Ok(FunctionResponse {
-                    data,
-                    status,
-                    headers,
-                })
-            }
-            ResponseType::Binary => {
-                // バイナリデータ処理
-                let bytes = response.bytes().await?;
+        if let Some(cache) = &self.cache {
+            return self
+                .invoke_cached(cache.as_ref(), function_name, body, &opts)
+                .await;
+        }
+
+        let response = self
+            .send_request(function_name, body, &opts, None, None)
+            .await?;
+        let status = response.status();
+        let headers = Self::response_headers(&response);
+        let body = response.bytes().await?;
+
+        Ok(FunctionResponse {
+            data: Self::decode_body(opts.response_type, &body)?,
+            status,
+            headers,
+        })
+    }
 
-                // Base64エンコード（非推奨API対応）
-                let binary_str = base64::engine::general_purpose::STANDARD.encode(&bytes);
+    // キャッシュを使って呼び出す (cache モジュールの説明を参照)
+    async fn invoke_cached<T: DeserializeOwned, B: Serialize>(
+        &self,
+        cache: &dyn FunctionCache,
+        function_name: &str,
+        body: Option<B>,
+        opts: &FunctionOptions,
+    ) -> Result<FunctionResponse<T>> {
+        let key = cache::cache_key(
+            function_name,
+            &serde_json::to_vec(&body)?,
+            &self.merged_headers(opts),
+        );
+        let cached = cache.get(&key);
+        if let Some(cached) = cached.as_ref().filter(|cached| cached.is_fresh()) {
+            return Self::cached_response(opts.response_type, cached);
+        }
 
-                // バイナリデータをデシリアライズ
-                let data: T =
-                    serde_json::from_str(&format!("\"{}\"", binary_str)).unwrap_or_else(|_| {
-                        panic!("Failed to deserialize binary response as requested type")
-                    });
+        let etag = cached.as_ref().and_then(|cached| cached.etag.as_deref());
+        let response = self
+            .send_request(function_name, body, opts, None, etag)
+            .await?;
+        let status = response.status();
+        let headers = Self::response_headers(&response);
+        let cache_control = CacheControl::from_headers(&headers);
+
+        if status == StatusCode::NOT_MODIFIED {
+            if let Some(mut cached) = cached {
+                if cache_control.no_store {
+                    cache.remove(&key);
+                } else {
+                    cached.fresh_until = cache_control.fresh_until();
+                    cache.put(&key, cached.clone());
+                }
+                return Self::cached_response(opts.response_type, &cached);
+            }
+        }
 
-                Ok(FunctionResponse {
-                    data,
+        let body = response.bytes().await?;
+        let data = Self::decode_body(opts.response_type, &body)?;
+        let etag = cache::header(&headers, "etag").map(str::to_string);
+        let fresh_until = cache_control.fresh_until();
+        if cache_control.no_store || (etag.is_none() && fresh_until.is_none()) {
+            cache.remove(&key);
+        } else {
+            cache.put(
+                &key,
+                CachedResponse {
                     status,
-                    headers,
-                })
+                    headers: headers.clone(),
+                    body,
+                    etag,
+                    fresh_until,
+                },
+            );
+        }
+
+        Ok(FunctionResponse {
+            data,
+            status,
+            headers,
+        })
+    }
+
+    fn cached_response<T: DeserializeOwned>(
+        response_type: ResponseType,
+        cached: &CachedResponse,
+    ) -> Result<FunctionResponse<T>> {
+        Ok(FunctionResponse {
+            data: Self::decode_body(response_type, &cached.body)?,
+            status: cached.status,
+            headers: cached.headers.clone(),
+        })
+    }
+
+    // レスポンスタイプに応じてレスポンス本文をデシリアライズ
+    fn decode_body<T: DeserializeOwned>(response_type: ResponseType, body: &[u8]) -> Result<T> {
+        let data = match response_type {
+            ResponseType::Json => Self::decode_json(body)?,
+            ResponseType::Text => {
+                // テキスト処理 (JSONとして解釈できなければ文字列として扱う)
+                let text = String::from_utf8_lossy(body).into_owned();
+                serde_json::from_str::<T>(&text)
+                    .or_else(|_| serde_json::from_value::<T>(Value::String(text)))
+                    .map_err(|e| {
+                        FunctionsError::InvalidResponse(format!(
+                            "Failed to deserialize text response as requested type: {}",
+                            e
+                        ))
+                    })?
             }
-            ResponseType::Stream => {
-                // ストリームレスポンスの場合、通常のデシリアライズではなく
-                // 別のストリーム処理用のメソッドを使用する必要がある
-                Err(FunctionsError::InvalidResponse(
-                    "Stream response type cannot be handled by invoke(). Use invoke_stream() instead.".to_string()
-                ))
+            ResponseType::Binary => {
+                // バイナリデータをBase64文字列としてデシリアライズ
+                let binary_str = base64::engine::general_purpose::STANDARD.encode(body);
+                serde_json::from_value::<T>(Value::String(binary_str)).map_err(|e| {
+                    FunctionsError::InvalidResponse(format!(
+                        "Failed to deserialize binary response as requested type: {}",
+                        e
+                    ))
+                })?
             }
+            ResponseType::Stream => unreachable!("stream responses are rejected by invoke()"),
+        };
+        Ok(data)
+    }
+
+    // 空の本文と `null` は null として扱う (`()` / `Option<T>` / `Value` なら成功する)
+    fn decode_json<T: DeserializeOwned>(body: &[u8]) -> Result<T> {
+        let trimmed = body.trim_ascii();
+        if !trimmed.is_empty() && trimmed != b"null" {
+            return Ok(serde_json::from_slice::<T>(body)?);
         }
+        serde_json::from_value::<T>(Value::Null).map_err(|_| {
+            FunctionsError::InvalidResponse(format!(
+                "function returned {} but a value of type {} was expected",
+                if trimmed.is_empty() {
+                    "empty body"
+                } else {
+                    "null"
+                },
+                std::any::type_name::<T>()
+            ))
+        })
+    }
+
+    /// 値を返さないファンクションを呼び出す (成功時の本文は読み捨てる)
+    pub async fn invoke_unit<B: Serialize>(
+        &self,
+        function_name: &str,
+        body: Option<B>,
+    ) -> Result<()> {
+        self.send_request(function_name, body, &FunctionOptions::default(), None, None)
+            .await?;
+        Ok(())
     }
 
     /// JSONを返すファンクションを呼び出す（シンプルなラッパー）
+    ///
+    /// 2xx の空の本文 (204 など) と `null` は `()`・`None`・`Value::Null` になり、
+    /// それ以外の型では `InvalidResponse` になる。
     pub async fn invoke_json<T: DeserializeOwned, B: Serialize>(
         &self,
         function_name: &str,
@@ -351,192 +833,92 @@ impl FunctionsClient {
         Ok(response.data)
     }
 
+    /// [`FunctionSpec`] で定義した関数を呼び出す
+    ///
+    /// 関数名と本文・レスポンスの型は `F` から決まる (`define_function!` を参照)。
+    /// `options` は `invoke` と同じで、レスポンスは既定で JSON として読み込む。
+    pub async fn call<F: FunctionSpec>(
+        &self,
+        request: F::Request,
+        options: Option<FunctionOptions>,
+    ) -> Result<F::Response> {
+        let response = self
+            .invoke::<F::Response, F::Request>(F::NAME, Some(request), options)
+            .await?;
+        Ok(response.data)
+    }
+
     /// テキストを返すファンクションを呼び出す（シンプルなラッパー）
     pub async fn invoke_text<B: Serialize>(
         &self,
         function_name: &str,
         body: Option<B>,
     ) -> Result<String> {
-        let options = FunctionOptions {
+        let response = self
+            .invoke_text_with_response(function_name, body, None)
+            .await?;
+        Ok(response.data)
+    }
+
+    /// テキストを返すファンクションを呼び出し、ステータスとヘッダーも返す
+    pub async fn invoke_text_with_response<B: Serialize>(
+        &self,
+        function_name: &str,
+        body: Option<B>,
+        options: Option<FunctionOptions>,
+    ) -> Result<FunctionResponse<String>> {
+        let options = options.unwrap_or_else(|| FunctionOptions {
             response_type: ResponseType::Text,
             ..Default::default()
-        };
+        });
 
-        // URLの構築 (invokeからコピー＆修正)
-        let mut url = Url::parse(&self.base_url)?;
-        url.path_segments_mut()
-            .map_err(|_| FunctionsError::UrlError(url::ParseError::EmptyHost))?
-            .push("functions")
-            .push("v1")
-            .push(function_name);
+        let response = self
+            .send_request(
+                function_name,
+                body,
+                &options,
+                Some("text/plain, */*;q=0.9"),
+                None,
+            )
+            .await?;
+        let status = response.status();
+        let headers = Self::response_headers(&response);
 
-        // リクエストの構築 (invokeからコピー＆修正)
-        let mut request_builder = self
-            .http_client
-            .post(url)
-            .header("apikey", &self.api_key)
-            .header("Authorization", format!("Bearer {}", &self.api_key));
+        // テキストを直接取得
+        let data = response.text().await?;
 
-        // リクエストタイムアウトの設定
-        if let Some(timeout) = options.timeout_seconds {
-            request_builder = request_builder.timeout(Duration::from_secs(timeout));
-        }
+        Ok(FunctionResponse {
+            data,
+            status,
+            headers,
+        })
+    }
 
-        // コンテンツタイプの設定
-        if let Some(content_type) = options.content_type {
-            request_builder = request_builder.header("Content-Type", content_type);
-        } else {
-            request_builder = request_builder.header("Content-Type", "application/json");
-        }
+    /// バイナリ形式で関数レスポンスを取得
+    pub async fn invoke_binary<B: Serialize>(
+        &self,
+        function_name: &str,
+        body: Option<B>,
+        options: Option<FunctionOptions>,
+    ) -> Result<Bytes> {
+        let options = options.unwrap_or_else(|| FunctionOptions {
+            response_type: ResponseType::Binary,
+            ..Default::default()
+        });
 
-        // Accept ヘッダーを設定 (テキストを期待)
-        request_builder = request_builder.header("Accept", "text/plain, */*;q=0.9");
+        let response = self
+            .send_request(
+                function_name,
+                body,
+                &options,
+                Some("application/octet-stream"),
+                None,
+            )
+            .await?;
 
-        // カスタムヘッダーの追加
-        if let Some(headers) = options.headers {
-            for (key, value) in headers {
-                request_builder = request_builder.header(key, value);
-            }
-        }
-
-        // リクエストボディの追加
-        if let Some(body_data) = body {
-            request_builder = request_builder.json(&body_data);
-        }
-
-        // リクエストの送信
-        let response = request_builder.send().await.map_err(|e| {
-            if e.is_timeout() {
-                FunctionsError::TimeoutError
-            } else {
-                FunctionsError::from(e)
-            }
-        })?;
-
-        // ステータスコードの確認
-        let status = response.status();
-        if !status.is_success() {
-            // エラーレスポンスのパース (invokeからコピー)
-            let error_body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
-            if let Ok(error_details) = serde_json::from_str::<FunctionErrorDetails>(&error_body) {
-                return Err(FunctionsError::FunctionError {
-                    message: error_details.message.as_ref().map_or_else(
-                        || format!("Function returned error status: {}", status),
-                        |msg| msg.clone(),
-                    ),
-                    status,
-                    details: Some(error_details),
-                });
-            } else {
-                return Err(FunctionsError::FunctionError {
-                    message: error_body,
-                    status,
-                    details: None,
-                });
-            }
-        }
-
-        // テキストを直接取得
-        response.text().await.map_err(FunctionsError::from)
-    }
-
-    /// バイナリ形式で関数レスポンスを取得
-    pub async fn invoke_binary<B: Serialize>(
-        &self,
-        function_name: &str,
-        body: Option<B>,
-        options: Option<FunctionOptions>,
-    ) -> Result<Bytes> {
-        let options = options.unwrap_or_else(|| FunctionOptions {
-            response_type: ResponseType::Binary,
-            ..Default::default()
-        });
-
-        // URLの構築
-        let mut url = Url::parse(&self.base_url)?;
-        url.path_segments_mut()
-            .map_err(|_| FunctionsError::UrlError(url::ParseError::EmptyHost))?
-            .push("functions")
-            .push("v1")
-            .push(function_name);
-
-        // リクエストの構築
-        let mut request_builder = self
-            .http_client
-            .post(url)
-            .header("apikey", &self.api_key)
-            .header("Authorization", format!("Bearer {}", &self.api_key));
-
-        // リクエストタイムアウトの設定
-        if let Some(timeout) = options.timeout_seconds {
-            request_builder = request_builder.timeout(Duration::from_secs(timeout));
-        }
-
-        // コンテンツタイプの設定
-        if let Some(content_type) = options.content_type {
-            request_builder = request_builder.header("Content-Type", content_type);
-        } else {
-            // デフォルトはJSON
-            request_builder = request_builder.header("Content-Type", "application/json");
-        }
-
-        // Accept ヘッダーを設定
-        request_builder = request_builder.header("Accept", "application/octet-stream");
-
-        // カスタムヘッダーの追加
-        if let Some(headers) = options.headers {
-            for (key, value) in headers {
-                request_builder = request_builder.header(key, value);
-            }
-        }
-
-        // リクエストボディの追加
-        if let Some(body_data) = body {
-            request_builder = request_builder.json(&body_data);
-        }
-
-        // リクエストの送信
-        let response = request_builder.send().await.map_err(|e| {
-            if e.is_timeout() {
-                FunctionsError::TimeoutError
-            } else {
-                FunctionsError::from(e)
-            }
-        })?;
-
-        // ステータスコードの確認
-        let status = response.status();
-        if !status.is_success() {
-            // エラーレスポンスのパース
-            let error_body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
-
-            if let Ok(error_details) = serde_json::from_str::<FunctionErrorDetails>(&error_body) {
-                return Err(FunctionsError::FunctionError {
-                    message: error_details.message.as_ref().map_or_else(
-                        || format!("Function returned error status: {}", status),
-                        |msg| msg.clone(),
-                    ),
-                    status,
-                    details: Some(error_details),
-                });
-            } else {
-                return Err(FunctionsError::FunctionError {
-                    message: error_body,
-                    status,
-                    details: None,
-                });
-            }
-        }
-
-        // バイナリデータを返す
-        response.bytes().await.map_err(FunctionsError::from)
-    }
+        // バイナリデータを返す
+        response.bytes().await.map_err(FunctionsError::from)
+    }
 
     /// バイナリストリームを取得するメソッド（大きなバイナリデータに最適）
     pub async fn invoke_binary_stream<B: Serialize>(
@@ -621,91 +1003,115 @@ impl FunctionsClient {
             ..Default::default()
         });
 
-        // URLの構築
-        let mut url = Url::parse(&self.base_url)?;
-        url.path_segments_mut()
-            .map_err(|_| FunctionsError::UrlError(url::ParseError::EmptyHost))?
-            .push("functions")
-            .push("v1")
-            .push(function_name);
-
-        // リクエストの構築
-        let mut request_builder = self
-            .http_client
-            .post(url)
-            .header("apikey", &self.api_key)
-            .header("Authorization", format!("Bearer {}", &self.api_key));
-
-        // リクエストタイムアウトの設定
-        if let Some(timeout) = opts.timeout_seconds {
-            request_builder = request_builder.timeout(Duration::from_secs(timeout));
-        }
-
-        // コンテンツタイプの設定
-        if let Some(content_type) = opts.content_type {
-            request_builder = request_builder.header("Content-Type", content_type);
-        } else {
-            // デフォルトはJSON
-            request_builder = request_builder.header("Content-Type", "application/json");
-        }
-
-        // カスタムヘッダーの追加
-        if let Some(headers) = opts.headers {
-            for (key, value) in headers {
-                request_builder = request_builder.header(key, value);
-            }
-        }
+        let response = self
+            .send_request(function_name, body, &opts, None, None)
+            .await?;
 
-        // リクエストボディの追加
-        if let Some(body_data) = body {
-            request_builder = request_builder.json(&body_data);
-        }
+        // ストリームを返す
+        Ok(Box::pin(
+            response
+                .bytes_stream()
+                .map(|result| result.map_err(FunctionsError::from)),
+        ))
+    }
 
-        // リクエストの送信
-        let response = request_builder.send().await.map_err(|e| {
-            if e.is_timeout() {
-                FunctionsError::TimeoutError
-            } else {
-                FunctionsError::from(e)
-            }
-        })?;
+    /// リクエスト本文をストリームで送信し、レスポンス本文をまとめて返す
+    ///
+    /// 本文全体をメモリに載せずに大きなデータ (NDJSON など) を送れる。
+    /// `options.timeout` はアップロードを含む全体の期限で、チャンクの間隔は `idle_timeout` で制限する。
+    pub async fn invoke_with_body_stream<S>(
+        &self,
+        function_name: &str,
+        body: S,
+        content_type: &str,
+        options: Option<StreamBodyOptions>,
+    ) -> Result<FunctionResponse<Bytes>>
+    where
+        S: Stream<Item = std::io::Result<Bytes>> + Send + Sync + 'static,
+    {
+        let response = self
+            .send_body_stream(
+                function_name,
+                body,
+                content_type,
+                options.unwrap_or_default(),
+            )
+            .await?;
+        let status = response.status();
+        let headers = Self::response_headers(&response);
+        let data = response.bytes().await.map_err(request_error)?;
+        Ok(FunctionResponse {
+            data,
+            status,
+            headers,
+        })
+    }
 
-        // ステータスコードの確認
+    /// リクエスト本文をストリームで送信し、レスポンス本文もストリームで返す
+    ///
+    /// `options.timeout` はレスポンスの受信を終えるまでの全体の期限になる。
+    pub async fn invoke_with_body_stream_response<S>(
+        &self,
+        function_name: &str,
+        body: S,
+        content_type: &str,
+        options: Option<StreamBodyOptions>,
+    ) -> Result<FunctionResponse<ByteStream>>
+    where
+        S: Stream<Item = std::io::Result<Bytes>> + Send + Sync + 'static,
+    {
+        let response = self
+            .send_body_stream(
+                function_name,
+                body,
+                content_type,
+                options.unwrap_or_default(),
+            )
+            .await?;
         let status = response.status();
-        if !status.is_success() {
-            // ステータスコードのコピーを保持
-            let status_copy = status;
+        let headers = Self::response_headers(&response);
+        Ok(FunctionResponse {
+            data: Box::pin(
+                response
+                    .bytes_stream()
+                    .map(|result| result.map_err(request_error)),
+            ),
+            status,
+            headers,
+        })
+    }
 
-            // エラーレスポンスのパース
-            let error_body = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+    async fn send_body_stream<S>(
+        &self,
+        function_name: &str,
+        body: S,
+        content_type: &str,
+        options: StreamBodyOptions,
+    ) -> Result<Response>
+    where
+        S: Stream<Item = std::io::Result<Bytes>> + Send + Sync + 'static,
+    {
+        let mut request_builder =
+            self.post_request(function_name, options.timeout, Some(content_type))?;
 
-            if let Ok(error_details) = serde_json::from_str::<FunctionErrorDetails>(&error_body) {
-                return Err(FunctionsError::FunctionError {
-                    message: error_details.message.as_ref().map_or_else(
-                        || format!("Function returned error status: {}", status_copy),
-                        |msg| msg.clone(),
-                    ),
-                    status: status_copy,
-                    details: Some(error_details),
-                });
-            } else {
-                return Err(FunctionsError::FunctionError {
-                    message: error_body,
-                    status: status_copy,
-                    details: None,
-                });
-            }
+        let headers = self.merged_headers(&FunctionOptions {
+            headers: options.headers,
+            ..Default::default()
+        });
+        for (key, value) in &headers {
+            request_builder = request_builder.header(key, value);
+        }
+        // Content-Length がなければ chunked で送られる
+        if let Some(content_length) = options.content_length {
+            request_builder = request_builder.header("Content-Length", content_length);
         }
 
-        // ストリームを返す
-        Ok(Box::pin(
-            response
-                .bytes_stream()
-                .map(|result| result.map_err(FunctionsError::from)),
-        ))
+        let body = match options.idle_timeout {
+            Some(idle_timeout) => reqwest::Body::wrap_stream(with_idle_timeout(body, idle_timeout)),
+            None => reqwest::Body::wrap_stream(body),
+        };
+        self.send(function_name, request_builder.body(body), &headers, false)
+            .await
     }
 
     /// JSONストリームを取得するメソッド（SSE形式のJSONイベントを扱う）
@@ -802,6 +1208,150 @@ impl FunctionsClient {
         })
     }
 
+    /// 関数を OPTIONS で呼び出してウォームアップし、往復時間を計測する
+    pub async fn ping(&self, function_name: &str) -> Result<PingResult> {
+        self.ping_with_method(function_name, PingMethod::default())
+            .await
+    }
+
+    /// メソッドを指定して関数をウォームアップし、往復時間を計測する
+    pub async fn ping_with_method(
+        &self,
+        function_name: &str,
+        method: PingMethod,
+    ) -> Result<PingResult> {
+        let url = self.function_url(function_name)?;
+        let method = match method {
+            PingMethod::Options => reqwest::Method::OPTIONS,
+            PingMethod::Get => reqwest::Method::GET,
+        };
+
+        let mut request_builder = self
+            .http_client
+            .request(method, url)
+            .header("apikey", self.api_key.expose())
+            .header("Authorization", format!("Bearer {}", self.api_key.expose()));
+        if let Some(timeout) = self.default_timeout {
+            request_builder = request_builder.timeout(timeout);
+        }
+        let headers = self.default_headers.clone();
+        for (key, value) in &headers {
+            request_builder = request_builder.header(key, value);
+        }
+
+        let started = Instant::now();
+        let response = request_builder
+            .send_through(self.http_client.send_gate())
+            .await
+            .map_err(send_error)?;
+        let duration = started.elapsed();
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = retry_after_header(&response);
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let message = self.scrub_secrets(&response.text().await.unwrap_or_default(), &headers);
+            return Err(FunctionsError::from_status(
+                status,
+                retry_after,
+                if message.is_empty() {
+                    format!("Function returned error status: {}", status)
+                } else {
+                    message
+                },
+                None,
+                request_ids,
+            ));
+        }
+
+        let server_timing = response
+            .headers()
+            .get("server-timing")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        Ok(PingResult {
+            function_name: function_name.to_string(),
+            status,
+            duration,
+            cold_start: detect_cold_start(response.headers()),
+            server_timing,
+        })
+    }
+
+    /// 複数の関数を最大 `concurrency` 件ずつ並行してウォームアップする
+    ///
+    /// 失敗は関数ごとに返され、一部の失敗で全体が中断されることはない。結果は `function_names` の順。
+    pub async fn warm_up(
+        &self,
+        function_names: &[&str],
+        concurrency: usize,
+    ) -> Vec<(String, Result<PingResult>)> {
+        futures_util::stream::iter(
+            function_names
+                .iter()
+                .map(|name| async move { (name.to_string(), self.ping(name).await) }),
+        )
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+    }
+
+    /// 再試行可能なエラー (`FunctionsError::is_retryable`) の間、`retry` に従って `invoke` を繰り返す
+    ///
+    /// レート制限や 503 の `retry-after` があればその時間だけ待つ。
+    pub async fn invoke_with_retry<T: DeserializeOwned, B: Serialize>(
+        &self,
+        function_name: &str,
+        body: Option<B>,
+        options: Option<FunctionOptions>,
+        retry: &InvokeRetryPolicy,
+    ) -> Result<FunctionResponse<T>> {
+        let max_attempts = retry.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match self
+                .invoke::<T, &B>(function_name, body.as_ref(), options.clone())
+                .await
+            {
+                Err(e) if e.is_retryable() && attempt < max_attempts => {
+                    let delay = retry.delay(attempt, &e);
+                    log::warn!(
+                        "Invocation of {} failed (attempt {}/{}), retrying in {:?}: {}",
+                        function_name,
+                        attempt,
+                        max_attempts,
+                        delay,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// 同じ関数を `bodies` ごとに最大 `concurrency` 件ずつ並行して呼び出す (`invoke_with_retry` を使用)
+    ///
+    /// 結果は `bodies` の順。一部の失敗で全体が中断されることはない。
+    pub async fn invoke_batch<T: DeserializeOwned, B: Serialize>(
+        &self,
+        function_name: &str,
+        bodies: Vec<B>,
+        options: Option<FunctionOptions>,
+        concurrency: usize,
+        retry: &InvokeRetryPolicy,
+    ) -> Vec<Result<FunctionResponse<T>>> {
+        futures_util::stream::iter(
+            bodies.into_iter().map(|body| {
+                self.invoke_with_retry(function_name, Some(body), options.clone(), retry)
+            }),
+        )
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+    }
+
     /// 関数リクエストを作成する
     pub fn create_request<T: DeserializeOwned>(
         &self,
@@ -815,6 +1365,85 @@ impl FunctionsClient {
     }
 }
 
+/// タイムアウト (全体の期限、またはリクエスト本文の `idle_timeout`) は `TimeoutError` にする
+fn send_error(error: SendError) -> FunctionsError {
+    match error {
+        SendError::Rejected(rejected) => FunctionsError::Rejected(rejected),
+        SendError::Http(error) => request_error(error),
+    }
+}
+
+fn request_error(error: reqwest::Error) -> FunctionsError {
+    let mut source = std::error::Error::source(&error);
+    while let Some(inner) = source {
+        if inner
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::TimedOut)
+        {
+            return FunctionsError::TimeoutError;
+        }
+        source = inner.source();
+    }
+    if error.is_timeout() {
+        FunctionsError::TimeoutError
+    } else {
+        FunctionsError::from(error)
+    }
+}
+
+/// 次のチャンクが `idle_timeout` 以内に来なければ `TimedOut` で終わるストリーム
+fn with_idle_timeout<S>(
+    body: S,
+    idle_timeout: Duration,
+) -> impl Stream<Item = std::io::Result<Bytes>> + Send + Sync + 'static
+where
+    S: Stream<Item = std::io::Result<Bytes>> + Send + Sync + 'static,
+{
+    async_stream::stream! {
+        tokio::pin!(body);
+        loop {
+            match tokio::time::timeout(idle_timeout, body.next()).await {
+                Ok(Some(chunk)) => yield chunk,
+                Ok(None) => break,
+                Err(_) => {
+                    yield Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "request body stream was idle for too long",
+                    ));
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// レスポンスヘッダーからコールドスタートかどうかを判断する
+///
+/// `x-sb-edge-cold-start` / `x-sb-cold-start` ヘッダー、または `Server-Timing` の
+/// `cold` / `cold_start` / `boot` メトリクスを参照する。
+fn detect_cold_start(headers: &reqwest::header::HeaderMap) -> Option<bool> {
+    for name in ["x-sb-edge-cold-start", "x-sb-cold-start"] {
+        if let Some(value) = headers.get(name).and_then(|value| value.to_str().ok()) {
+            match value.trim().to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" => return Some(true),
+                "false" | "0" | "no" => return Some(false),
+                _ => {}
+            }
+        }
+    }
+
+    let server_timing = headers.get("server-timing")?.to_str().ok()?;
+    let cold = server_timing.split(',').any(|metric| {
+        let name = metric.split(';').next().unwrap_or_default().trim();
+        matches!(
+            name.to_ascii_lowercase().as_str(),
+            "cold" | "cold_start" | "coldstart" | "boot"
+        )
+    });
+    // メトリクスがなくてもウォームとは限らないため None を返す
+    cold.then_some(true)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*; // Import necessary items from parent module
@@ -877,29 +1506,128 @@ mod tests {
 
     // Test error response with details
     #[tokio::test]
-    async fn test_invoke_json_error_with_details() {
-        // Arrange: Start mock server
+    async fn test_invoke_json_empty_and_null_bodies() {
         let server = MockServer::start().await;
-        let mock_uri = server.uri();
-        let api_key = "test-key";
-        let function_name = "error-func";
-
-        // Arrange: Prepare request and error response
-        let request_body = json!({ "input": "invalid" });
-        let error_response_body = json!({
-            "message": "Something went wrong!",
-            "code": "FUNC_ERROR",
-            "details": { "reason": "Internal failure" }
-        });
-
-        // Arrange: Mock the API endpoint to return an error
         Mock::given(method("POST"))
-            .and(path(format!("/functions/v1/{}", function_name)))
-            .and(header("apikey", api_key))
-            .and(header(
-                "Authorization",
-                format!("Bearer {}", api_key).as_str(),
-            ))
+            .and(path("/functions/v1/no-content"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/null-body"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Type", "application/json")
+                    .set_body_string("null"),
+            )
+            .mount(&server)
+            .await;
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+
+        client
+            .invoke_json::<(), Value>("no-content", None)
+            .await
+            .unwrap();
+        assert_eq!(
+            client
+                .invoke_json::<Value, Value>("no-content", None)
+                .await
+                .unwrap(),
+            Value::Null
+        );
+        match client
+            .invoke_json::<TestPayload, Value>("no-content", None)
+            .await
+        {
+            Err(FunctionsError::InvalidResponse(message)) => {
+                assert!(message.contains("empty body"), "{}", message);
+                assert!(message.contains("TestPayload"), "{}", message);
+            }
+            other => panic!("expected InvalidResponse, got {:?}", other),
+        }
+
+        assert_eq!(
+            client
+                .invoke_json::<Option<TestPayload>, Value>("null-body", None)
+                .await
+                .unwrap(),
+            None
+        );
+        assert!(matches!(
+            client.invoke_json::<TestPayload, Value>("null-body", None).await,
+            Err(FunctionsError::InvalidResponse(message)) if message.contains("returned null")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invoke_unit_ignores_body() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/fire"))
+            .and(body_json(json!({ "id": 1 })))
+            .respond_with(ResponseTemplate::new(200).set_body_string("not json"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/fail"))
+            .respond_with(ResponseTemplate::new(500).set_body_string("boom"))
+            .mount(&server)
+            .await;
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+
+        client
+            .invoke_unit("fire", Some(json!({ "id": 1 })))
+            .await
+            .unwrap();
+        assert!(matches!(
+            client.invoke_unit::<Value>("fail", None).await,
+            Err(FunctionsError::FunctionError { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_default_headers_survive_default_options() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/hello"))
+            .and(header("x-tenant-id", "tenant-a"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut tenant = reqwest::header::HeaderMap::new();
+        tenant.insert("x-tenant-id", "tenant-a".parse().unwrap());
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new())
+            .with_default_headers(tenant)
+            .with_default_options(FunctionOptions::default());
+
+        let _: Value = client.invoke_json("hello", None::<Value>).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_invoke_json_error_with_details() {
+        // Arrange: Start mock server
+        let server = MockServer::start().await;
+        let mock_uri = server.uri();
+        let api_key = "test-key";
+        let function_name = "error-func";
+
+        // Arrange: Prepare request and error response
+        let request_body = json!({ "input": "invalid" });
+        let error_response_body = json!({
+            "message": "Something went wrong!",
+            "code": "FUNC_ERROR",
+            "details": { "reason": "Internal failure" }
+        });
+
+        // Arrange: Mock the API endpoint to return an error
+        Mock::given(method("POST"))
+            .and(path(format!("/functions/v1/{}", function_name)))
+            .and(header("apikey", api_key))
+            .and(header(
+                "Authorization",
+                format!("Bearer {}", api_key).as_str(),
+            ))
             .and(body_json(&request_body))
             .respond_with(
                 ResponseTemplate::new(500)
@@ -924,6 +1652,7 @@ mod tests {
                 message,
                 status,
                 details,
+                ..
             } => {
                 assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
                 assert_eq!(message, "Something went wrong!");
@@ -945,6 +1674,44 @@ mod tests {
         server.verify().await;
     }
 
+    #[tokio::test]
+    async fn test_request_ids_are_sent_and_attached_to_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/error-func"))
+            .and(wiremock::matchers::header_exists("x-client-request-id"))
+            .respond_with(
+                ResponseTemplate::new(500)
+                    .insert_header("sb-request-id", "sb-fn-1")
+                    .insert_header("cf-ray", "8a1b2c3d-NRT")
+                    .set_body_json(json!({ "message": "boom" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+        let error = client
+            .invoke_json::<Value, Value>("error-func", Some(json!({})))
+            .await
+            .unwrap_err();
+
+        let sent = server.received_requests().await.unwrap();
+        let client_request_id = sent[0].headers.get(&"x-client-request-id".into()).unwrap()[0]
+            .as_str()
+            .to_string();
+        let FunctionsError::FunctionError { request_ids, .. } = &error else {
+            panic!("Expected FunctionError, got {:?}", error);
+        };
+        assert_eq!(
+            request_ids.client_request_id.as_deref(),
+            Some(client_request_id.as_str())
+        );
+        assert_eq!(request_ids.sb_request_id.as_deref(), Some("sb-fn-1"));
+        assert_eq!(request_ids.cf_ray.as_deref(), Some("8a1b2c3d-NRT"));
+        assert!(error.to_string().contains(&client_request_id));
+    }
+
     // Test successful text invocation
     #[tokio::test]
     async fn test_invoke_text_success() {
@@ -988,4 +1755,811 @@ mod tests {
         assert_eq!(data, expected_response_text);
         server.verify().await;
     }
+
+    #[tokio::test]
+    async fn test_invoke_text_with_response() {
+        let server = MockServer::start().await;
+        let function_name = "plain-text-func";
+
+        Mock::given(method("POST"))
+            .and(path(format!("/functions/v1/{}", function_name)))
+            .respond_with(
+                ResponseTemplate::new(202)
+                    .set_body_string("queued: not json")
+                    .insert_header("Content-Type", "text/plain")
+                    .insert_header("x-job-id", "job-1"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+        let response = client
+            .invoke_text_with_response::<Value>(function_name, None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.data, "queued: not json");
+        assert_eq!(response.status, StatusCode::ACCEPTED);
+        assert_eq!(response.headers.get("x-job-id"), Some(&"job-1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_invoke_text_response_type_does_not_panic() {
+        let server = MockServer::start().await;
+        let function_name = "plain-text-func";
+
+        Mock::given(method("POST"))
+            .and(path(format!("/functions/v1/{}", function_name)))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("hello, world")
+                    .insert_header("Content-Type", "text/plain"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+        let options = FunctionOptions {
+            response_type: ResponseType::Text,
+            ..Default::default()
+        };
+
+        // String 互換の型ではテキストがそのまま返る
+        let response = client
+            .invoke::<String, Value>(function_name, None, Some(options.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.data, "hello, world");
+        assert_eq!(response.status, StatusCode::OK);
+
+        // String 互換でない型ではパニックせずエラーを返す
+        let result = client
+            .invoke::<TestPayload, Value>(function_name, None, Some(options))
+            .await;
+        assert!(matches!(result, Err(FunctionsError::InvalidResponse(_))));
+    }
+
+    #[tokio::test]
+    async fn test_error_messages_never_contain_secrets() {
+        const KEY: &str = "anon-key-0123456789abcdef";
+        const TOKEN: &str = "user-jwt-0123456789abcdef";
+        // 一部のプロキシは Authorization ヘッダーをそのまま本文に含めて返す
+        let bodies = [
+            json!({
+                "message": format!("bad apikey {}", KEY),
+                "details": { "authorization": format!("Bearer {}", TOKEN) }
+            })
+            .to_string(),
+            format!("apikey={} authorization=Bearer {}", KEY, TOKEN),
+        ];
+
+        for body in bodies {
+            let mock_server = MockServer::start().await;
+            Mock::given(wiremock::matchers::any())
+                .respond_with(ResponseTemplate::new(500).set_body_string(body))
+                .mount(&mock_server)
+                .await;
+
+            let client = FunctionsClient::new(&mock_server.uri(), KEY, reqwest::Client::new())
+                .with_default_header("Authorization", &format!("Bearer {}", TOKEN));
+            let errors = vec![
+                client
+                    .invoke::<Value, Value>("hello", None, None)
+                    .await
+                    .unwrap_err(),
+                client
+                    .invoke_text::<Value>("hello", None)
+                    .await
+                    .unwrap_err(),
+                client
+                    .invoke_binary::<Value>("hello", None, None)
+                    .await
+                    .unwrap_err(),
+            ];
+
+            for error in errors {
+                for output in [error.to_string(), format!("{:?}", error)] {
+                    assert!(!output.contains(KEY), "secret leaked: {}", output);
+                    assert!(!output.contains(TOKEN), "secret leaked: {}", output);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_options_merge_with_call_options() {
+        let server = MockServer::start().await;
+        let function_name = "tenant-func";
+
+        Mock::given(method("POST"))
+            .and(path(format!("/functions/v1/{}", function_name)))
+            .and(header("x-tenant", "acme"))
+            .and(header("x-trace", "call"))
+            .and(header("Content-Type", "application/vnd.api+json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut default_headers = HashMap::new();
+        default_headers.insert("x-tenant".to_string(), "acme".to_string());
+        default_headers.insert("x-trace".to_string(), "default".to_string());
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new())
+            .with_default_options(FunctionOptions {
+                headers: Some(default_headers),
+                timeout_seconds: Some(30),
+                content_type: Some("application/vnd.api+json".to_string()),
+                ..Default::default()
+            });
+        assert_eq!(client.default_timeout(), Some(Duration::from_secs(30)));
+
+        let mut call_headers = HashMap::new();
+        call_headers.insert("X-Trace".to_string(), "call".to_string());
+        let response = client
+            .invoke::<Value, Value>(
+                function_name,
+                Some(json!({})),
+                Some(FunctionOptions {
+                    headers: Some(call_headers),
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.data, json!({ "ok": true }));
+    }
+
+    #[tokio::test]
+    async fn test_invoke_without_body_sends_no_content_type() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/ping"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "ok": true })))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+        client
+            .invoke::<Value, Value>("ping", None, None)
+            .await
+            .unwrap();
+        client
+            .invoke::<Value, Value>("ping", Some(json!({ "a": 1 })), None)
+            .await
+            .unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        let content_types: Vec<_> = requests
+            .iter()
+            .map(|request| {
+                request
+                    .headers
+                    .get(&"content-type".into())
+                    .map(|values| values[0].as_str().to_string())
+            })
+            .collect();
+        assert_eq!(
+            content_types,
+            vec![None, Some("application/json".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_default_timeout_aborts_slow_function() {
+        let server = MockServer::start().await;
+        let function_name = "slow-func";
+
+        Mock::given(method("POST"))
+            .and(path(format!("/functions/v1/{}", function_name)))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "ok": true }))
+                    .set_delay(Duration::from_secs(5)),
+            )
+            .mount(&server)
+            .await;
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+        assert_eq!(client.default_timeout(), Some(DEFAULT_TIMEOUT));
+        let client = client.with_default_timeout(Duration::from_millis(200));
+
+        let started = std::time::Instant::now();
+        let result = client
+            .invoke_json::<Value, Value>(function_name, None)
+            .await;
+        assert!(matches!(result, Err(FunctionsError::TimeoutError)));
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // 呼び出しごとの timeout_seconds が優先される
+        let result = client
+            .invoke::<Value, Value>(
+                function_name,
+                None,
+                Some(FunctionOptions {
+                    timeout_seconds: Some(1),
+                    ..Default::default()
+                }),
+            )
+            .await;
+        assert!(matches!(result, Err(FunctionsError::TimeoutError)));
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_ping_measures_duration_and_detects_cold_start() {
+        let server = MockServer::start().await;
+        Mock::given(method("OPTIONS"))
+            .and(path("/functions/v1/cold"))
+            .and(header("apikey", "test-key"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("server-timing", "boot;dur=180, total;dur=230")
+                    .set_delay(Duration::from_millis(50)),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/functions/v1/warm"))
+            .respond_with(ResponseTemplate::new(200).insert_header("x-sb-edge-cold-start", "false"))
+            .mount(&server)
+            .await;
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+
+        let cold = client.ping("cold").await.unwrap();
+        assert_eq!(cold.function_name, "cold");
+        assert!(cold.duration >= Duration::from_millis(50));
+        assert_eq!(cold.cold_start, Some(true));
+        assert_eq!(
+            cold.server_timing.as_deref(),
+            Some("boot;dur=180, total;dur=230")
+        );
+
+        let warm = client
+            .ping_with_method("warm", PingMethod::Get)
+            .await
+            .unwrap();
+        assert!(warm.duration > Duration::ZERO);
+        assert_eq!(warm.cold_start, Some(false));
+        assert_eq!(warm.server_timing, None);
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_reports_failures_per_function() {
+        let server = MockServer::start().await;
+        for name in ["a", "c"] {
+            Mock::given(method("OPTIONS"))
+                .and(path(format!("/functions/v1/{}", name)))
+                .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(20)))
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("OPTIONS"))
+            .and(path("/functions/v1/b"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("Function not found"))
+            .mount(&server)
+            .await;
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+        let results = client.warm_up(&["a", "b", "c"], 2).await;
+
+        let names = results
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        for (name, result) in &results {
+            match (name.as_str(), result) {
+                (
+                    "b",
+                    Err(FunctionsError::FunctionError {
+                        status, message, ..
+                    }),
+                ) => {
+                    assert_eq!(*status, StatusCode::NOT_FOUND);
+                    assert_eq!(message, "Function not found");
+                }
+                (_, Ok(ping)) => {
+                    assert_ne!(name, "b");
+                    assert!(ping.duration >= Duration::from_millis(20));
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    fn cached_client(server: &MockServer) -> (FunctionsClient, Arc<InMemoryFunctionCache>) {
+        let cache = Arc::new(InMemoryFunctionCache::new());
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new())
+            .with_cache(cache.clone());
+        (client, cache)
+    }
+
+    #[tokio::test]
+    async fn test_invoke_cache_revalidates_with_etag() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/config"))
+            .and(header("If-None-Match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304).insert_header("ETag", "\"v1\""))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/config"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .insert_header("Cache-Control", "no-cache")
+                    .set_body_json(json!({ "message": "config v1" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let (client, cache) = cached_client(&server);
+        let body = json!({ "env": "prod" });
+        let first = client
+            .invoke::<TestPayload, _>("config", Some(&body), None)
+            .await
+            .unwrap();
+        assert_eq!(first.data.message, "config v1");
+        assert_eq!(cache.len(), 1);
+
+        let second = client
+            .invoke::<TestPayload, _>("config", Some(&body), None)
+            .await
+            .unwrap();
+        assert_eq!(second.data, first.data);
+        assert_eq!(second.status, StatusCode::OK);
+        assert_eq!(cache::header(&second.headers, "etag"), Some("\"v1\""));
+    }
+
+    #[tokio::test]
+    async fn test_invoke_cache_serves_fresh_response_without_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/config"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Cache-Control", "public, max-age=300")
+                    .set_body_json(json!({ "message": "fresh" })),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let (client, _cache) = cached_client(&server);
+        for _ in 0..3 {
+            let response = client
+                .invoke::<TestPayload, _>("config", Some(json!({ "env": "prod" })), None)
+                .await
+                .unwrap();
+            assert_eq!(response.data.message, "fresh");
+        }
+
+        // 本文が異なれば別のエントリ
+        client
+            .invoke::<TestPayload, _>("config", Some(json!({ "env": "dev" })), None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_invoke_cache_respects_no_store() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/config"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .insert_header("Cache-Control", "no-store, max-age=300")
+                    .set_body_json(json!({ "message": "secret" })),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let (client, cache) = cached_client(&server);
+        for _ in 0..2 {
+            client
+                .invoke::<TestPayload, _>("config", None::<()>, None)
+                .await
+                .unwrap();
+        }
+        assert!(cache.is_empty());
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(requests
+            .iter()
+            .all(|request| !request.headers.contains_key(&"if-none-match".into())));
+    }
+
+    #[test]
+    fn test_cache_control_parse() {
+        let cache_control = CacheControl::parse("public, Max-Age=60");
+        assert_eq!(cache_control.max_age, Some(Duration::from_secs(60)));
+        assert!(!cache_control.no_store);
+        assert!(CacheControl::parse("no-store").no_store);
+        assert_eq!(CacheControl::parse("max-age=0").fresh_until(), None);
+    }
+
+    fn ndjson_chunks() -> Vec<std::io::Result<Bytes>> {
+        (0..3)
+            .map(|i| Ok(Bytes::from(format!("{{\"id\":{}}}\n", i))))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_invoke_with_body_stream_sends_chunks() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/ingest"))
+            .and(header("content-type", "application/x-ndjson"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("accepted"))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        let client = FunctionsClient::new(&mock_server.uri(), "test_key", Client::new());
+        let expected = b"{\"id\":0}\n{\"id\":1}\n{\"id\":2}\n";
+
+        let response = client
+            .invoke_with_body_stream(
+                "ingest",
+                futures_util::stream::iter(ndjson_chunks()),
+                "application/x-ndjson",
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(response.data, Bytes::from_static(b"accepted"));
+
+        // 長さがわかっている場合は Content-Length で送り、レスポンスもストリームで受け取る
+        let response = client
+            .invoke_with_body_stream_response(
+                "ingest",
+                futures_util::stream::iter(ndjson_chunks()),
+                "application/x-ndjson",
+                Some(StreamBodyOptions {
+                    content_length: Some(expected.len() as u64),
+                    ..Default::default()
+                }),
+            )
+            .await
+            .unwrap();
+        let chunks: Vec<Bytes> = response.data.map(|chunk| chunk.unwrap()).collect().await;
+        assert_eq!(chunks.concat(), b"accepted");
+
+        let requests = mock_server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        for request in &requests {
+            assert_eq!(request.body, expected);
+        }
+        let header_value = |index: usize, name: &str| {
+            requests[index]
+                .headers
+                .get(&name.into())
+                .map(|values| values[0].as_str().to_string())
+        };
+        assert_eq!(
+            header_value(0, "transfer-encoding").as_deref(),
+            Some("chunked")
+        );
+        assert_eq!(header_value(0, "content-length"), None);
+        assert_eq!(
+            header_value(1, "content-length"),
+            Some(expected.len().to_string())
+        );
+        assert_eq!(header_value(1, "transfer-encoding"), None);
+    }
+
+    #[tokio::test]
+    async fn test_invoke_with_body_stream_idle_timeout() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/ingest"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&mock_server)
+            .await;
+        let client = FunctionsClient::new(&mock_server.uri(), "test_key", Client::new());
+
+        // 最初のチャンクのあとで止まるストリーム
+        let stalled = futures_util::stream::iter(ndjson_chunks().into_iter().take(1))
+            .chain(futures_util::stream::pending());
+        let result = client
+            .invoke_with_body_stream(
+                "ingest",
+                stalled,
+                "application/x-ndjson",
+                Some(StreamBodyOptions {
+                    idle_timeout: Some(Duration::from_millis(100)),
+                    ..Default::default()
+                }),
+            )
+            .await;
+        assert!(
+            matches!(result, Err(FunctionsError::TimeoutError)),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_445_412_480); // 2015-10-21T07:28:00Z
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:30:00 GMT", now),
+            Some(Duration::from_secs(120))
+        );
+        // 過去の日時は待たない
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_retry_after() {
+        let server = MockServer::start().await;
+        let retry_at = SystemTime::now() + Duration::from_secs(120);
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/seconds"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", "7")
+                    .set_body_string("Too many requests"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/date"))
+            .respond_with(
+                ResponseTemplate::new(429)
+                    .insert_header("retry-after", httpdate::fmt_http_date(retry_at).as_str()),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/missing"))
+            .respond_with(ResponseTemplate::new(429).set_body_string("Too many requests"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/unavailable"))
+            .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "3"))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/down"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+        let invoke = |name: &'static str| {
+            let client = &client;
+            async move {
+                client
+                    .invoke_json::<Value, Value>(name, None)
+                    .await
+                    .unwrap_err()
+            }
+        };
+
+        match invoke("seconds").await {
+            FunctionsError::RateLimited {
+                retry_after,
+                message,
+                ..
+            } => {
+                assert_eq!(retry_after, Some(Duration::from_secs(7)));
+                assert_eq!(message, "Too many requests");
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+
+        let retry_after = invoke("date").await.retry_after().unwrap();
+        assert!(
+            retry_after > Duration::from_secs(100) && retry_after <= Duration::from_secs(120),
+            "{:?}",
+            retry_after
+        );
+
+        let error = invoke("missing").await;
+        assert!(matches!(
+            error,
+            FunctionsError::RateLimited {
+                retry_after: None,
+                ..
+            }
+        ));
+        assert_eq!(error.retry_after(), None);
+
+        assert_eq!(
+            invoke("unavailable").await.retry_after(),
+            Some(Duration::from_secs(3))
+        );
+        let error = invoke("down").await;
+        assert!(matches!(
+            error,
+            FunctionsError::FunctionError {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                ..
+            }
+        ));
+        assert_eq!(error.retry_after(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_invoke_batch_waits_for_retry_after() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/batch"))
+            .and(body_json(json!({"n": 1})))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "30"))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/batch"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"ok": true})))
+            .mount(&server)
+            .await;
+
+        // 仮想時間が進んでもリクエストがタイムアウトしないようにする
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new())
+            .without_timeout();
+        let started = tokio::time::Instant::now();
+        let results = client
+            .invoke_batch::<Value, Value>(
+                "batch",
+                vec![json!({"n": 1}), json!({"n": 2})],
+                None,
+                2,
+                &InvokeRetryPolicy::default(),
+            )
+            .await;
+
+        assert_eq!(results.len(), 2);
+        for result in &results {
+            assert_eq!(result.as_ref().unwrap().data, json!({"ok": true}));
+        }
+        assert!(started.elapsed() >= Duration::from_secs(30));
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+        // 再試行しない設定ではそのまま返す
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/limited"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "30"))
+            .mount(&server)
+            .await;
+        let result = client
+            .invoke_with_retry::<Value, Value>("limited", None, None, &InvokeRetryPolicy::none())
+            .await;
+        assert!(matches!(result, Err(FunctionsError::RateLimited { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_manual_trace_context_is_sent() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/traced"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .mount(&server)
+            .await;
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+        client
+            .invoke_json::<Value, Value>("traced", None)
+            .await
+            .unwrap();
+
+        let context = TraceContext::new(0x4bf92f3577b34da6a3ce929d0e0e4736, 0x00f067aa0ba902b7)
+            .with_trace_state("congo=t61rcWkgMzE");
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new())
+            .with_trace_context(context.clone());
+        client.invoke_text("traced", None::<Value>).await.unwrap();
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(!requests[0].headers.contains_key(&TRACEPARENT_HEADER.into()));
+        assert_eq!(
+            requests[1].headers.get(&TRACEPARENT_HEADER.into()).unwrap()[0].as_str(),
+            context.traceparent()
+        );
+        assert_eq!(
+            requests[1].headers.get(&TRACESTATE_HEADER.into()).unwrap()[0].as_str(),
+            "congo=t61rcWkgMzE"
+        );
+    }
+
+    #[cfg(feature = "tracing-opentelemetry")]
+    #[tokio::test]
+    async fn test_current_span_context_is_propagated() {
+        use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+        use opentelemetry::Value as AttributeValue;
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+        use tracing::Instrument;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/traced"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .mount(&server)
+            .await;
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+        // span の外では何も付けない
+        client
+            .invoke_json::<Value, Value>("traced", None)
+            .await
+            .unwrap();
+
+        let root = tracing::info_span!("test-root");
+        let trace_id = root.context().span().span_context().trace_id();
+        client
+            .invoke_json::<Value, Value>("traced", None)
+            .instrument(root.clone())
+            .await
+            .unwrap();
+        drop(root);
+
+        let requests = server.received_requests().await.unwrap();
+        assert!(!requests[0].headers.contains_key(&TRACEPARENT_HEADER.into()));
+        let traceparent = requests[1].headers.get(&TRACEPARENT_HEADER.into()).unwrap()[0]
+            .as_str()
+            .to_string();
+        let parts = traceparent.split('-').collect::<Vec<_>>();
+        assert_eq!(
+            parts.iter().map(|part| part.len()).collect::<Vec<_>>(),
+            vec![2, 32, 16, 2],
+            "{}",
+            traceparent
+        );
+        assert_eq!(parts[0], "00");
+        let context = TraceContext::from_traceparent(&traceparent).unwrap();
+        assert_eq!(context.trace_id.to_be_bytes(), trace_id.to_bytes());
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let invoke = spans
+            .iter()
+            .find(|span| span.name == "functions.invoke")
+            .unwrap();
+        assert_eq!(invoke.span_context.trace_id(), trace_id);
+        assert_eq!(
+            context.span_id.to_be_bytes(),
+            invoke.span_context.span_id().to_bytes()
+        );
+        let attribute = |name: &str| {
+            invoke
+                .attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == name)
+                .map(|kv| kv.value.clone())
+        };
+        assert_eq!(
+            attribute("faas.invoked_name"),
+            Some(AttributeValue::from("traced"))
+        );
+        assert_eq!(
+            attribute("http.response.status_code"),
+            Some(AttributeValue::I64(200))
+        );
+    }
 }
@@ -0,0 +1,166 @@
+//! 型付きの関数定義
+//!
+//! 関数ごとに名前・リクエストの型・レスポンスの型を [`FunctionSpec`] として一度だけ定義し、
+//! [`FunctionsClient::call`](crate::FunctionsClient::call) で呼び出す。関数名と本文の形の
+//! 組み合わせをコンパイル時に確認できる。[`define_function!`](crate::define_function) で
+//! 単位構造体と実装をまとめて定義できる。
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//! use supabase_rust_functions::define_function;
+//!
+//! #[derive(Serialize)]
+//! pub struct GreetRequest {
+//!     pub name: String,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! pub struct Greeting {
+//!     pub message: String,
+//! }
+//!
+//! define_function!(pub Greet, "greet", GreetRequest, Greeting);
+//!
+//! # async fn example(client: supabase_rust_functions::FunctionsClient) -> supabase_rust_functions::Result<()> {
+//! let greeting = client
+//!     .call::<Greet>(GreetRequest { name: "Ada".to_string() }, None)
+//!     .await?;
+//! # let _ = greeting.message;
+//! # Ok(())
+//! # }
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// 関数名とリクエスト・レスポンスの型の組
+pub trait FunctionSpec {
+    /// 関数名 (`/functions/v1/{NAME}`)
+    const NAME: &'static str;
+    /// JSON として送る本文の型
+    type Request: Serialize;
+    /// JSON のレスポンスを読み込む型 (空の本文・`null` は `()` や `Option` で受ける)
+    type Response: DeserializeOwned;
+}
+
+/// [`FunctionSpec`] を実装する単位構造体を定義する
+///
+/// `define_function!(Name, "fn-name", Request, Response)` の形で、構造体の前に属性や
+/// `pub` を付けられる。
+#[macro_export]
+macro_rules! define_function {
+    ($(#[$meta:meta])* $vis:vis $name:ident, $function:expr, $request:ty, $response:ty $(,)?) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default)]
+        $vis struct $name;
+
+        impl $crate::FunctionSpec for $name {
+            const NAME: &'static str = $function;
+            type Request = $request;
+            type Response = $response;
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{FunctionOptions, FunctionsClient, FunctionsError};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use wiremock::matchers::{body_json, header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[derive(Debug, Serialize)]
+    struct GreetRequest {
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Greeting {
+        message: String,
+    }
+
+    define_function!(Greet, "greet", GreetRequest, Greeting);
+    define_function!(
+        /// 数値の合計
+        SumNumbers,
+        "sum-numbers",
+        Vec<i64>,
+        i64
+    );
+
+    #[tokio::test]
+    async fn test_call_uses_spec_name_and_types() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/greet"))
+            .and(body_json(json!({ "name": "Ada" })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "message": "Hello, Ada" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/sum-numbers"))
+            .and(body_json(json!([1, 2, 3])))
+            .and(header("x-region", "eu"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!(6)))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+        let greeting = client
+            .call::<Greet>(
+                GreetRequest {
+                    name: "Ada".to_string(),
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            greeting,
+            Greeting {
+                message: "Hello, Ada".to_string()
+            }
+        );
+
+        let options = FunctionOptions {
+            headers: Some(HashMap::from([("x-region".to_string(), "eu".to_string())])),
+            ..Default::default()
+        };
+        let sum: i64 = client
+            .call::<SumNumbers>(vec![1, 2, 3], Some(options))
+            .await
+            .unwrap();
+        assert_eq!(sum, 6);
+    }
+
+    #[tokio::test]
+    async fn test_call_reports_response_of_the_wrong_shape() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/functions/v1/greet"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "greeting": 1 })))
+            .mount(&server)
+            .await;
+
+        let client = FunctionsClient::new(&server.uri(), "test-key", reqwest::Client::new());
+        let result = client
+            .call::<Greet>(
+                GreetRequest {
+                    name: "Ada".to_string(),
+                },
+                None,
+            )
+            .await;
+        assert!(
+            matches!(result, Err(FunctionsError::JsonError(_))),
+            "{:?}",
+            result
+        );
+    }
+}
@@ -0,0 +1,223 @@
+//! W3C Trace Context (`traceparent` / `tracestate`) の伝播
+//!
+//! `tracing-opentelemetry` フィーチャーが有効な場合、呼び出しごとに現在の span の子として
+//! `functions.invoke` span を作り、そのコンテキストをヘッダーで Edge Function に渡す。
+//! 現在の span が OpenTelemetry のコンテキストを持たなければ何も付けない。
+//! OpenTelemetry を使わない場合は [`FunctionsClient::with_trace_context`](crate::FunctionsClient::with_trace_context)
+//! で固定のコンテキストを渡せる。
+
+use reqwest::RequestBuilder;
+
+/// `traceparent` ヘッダー
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// `tracestate` ヘッダー
+pub const TRACESTATE_HEADER: &str = "tracestate";
+
+/// Edge Function に渡すトレースコンテキスト
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// トレースID (0 は無効)
+    pub trace_id: u128,
+    /// 親 span の ID (0 は無効)
+    pub span_id: u64,
+    /// トレースフラグ (`0x01` はサンプリング対象)
+    pub flags: u8,
+    /// `tracestate` ヘッダーの値
+    pub trace_state: Option<String>,
+}
+
+impl TraceContext {
+    /// サンプリング対象のコンテキストを作成
+    pub fn new(trace_id: u128, span_id: u64) -> Self {
+        Self {
+            trace_id,
+            span_id,
+            flags: 0x01,
+            trace_state: None,
+        }
+    }
+
+    /// `tracestate` を設定
+    pub fn with_trace_state(mut self, trace_state: impl Into<String>) -> Self {
+        self.trace_state = Some(trace_state.into());
+        self
+    }
+
+    /// `traceparent` ヘッダーをパースする (`version-traceid-spanid-flags`)
+    ///
+    /// 形式が不正な場合や ID が 0 の場合は None。
+    pub fn from_traceparent(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+        // バージョン 00 は 4 フィールドのみ
+        if version == "00" && parts.next().is_some() {
+            return None;
+        }
+        let is_hex = |text: &str, len: usize| {
+            text.len() == len
+                && text
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        };
+        if !is_hex(version, 2)
+            || version == "ff"
+            || !is_hex(trace_id, 32)
+            || !is_hex(span_id, 16)
+            || !is_hex(flags, 2)
+        {
+            return None;
+        }
+        let context = Self {
+            trace_id: u128::from_str_radix(trace_id, 16).ok()?,
+            span_id: u64::from_str_radix(span_id, 16).ok()?,
+            flags: u8::from_str_radix(flags, 16).ok()?,
+            trace_state: None,
+        };
+        context.is_valid().then_some(context)
+    }
+
+    /// トレースID と span ID がどちらも 0 でない
+    pub fn is_valid(&self) -> bool {
+        self.trace_id != 0 && self.span_id != 0
+    }
+
+    /// `traceparent` ヘッダーの値 (`00-{trace_id}-{span_id}-{flags}`)
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.span_id, self.flags
+        )
+    }
+
+    fn inject(&self, request_builder: RequestBuilder) -> RequestBuilder {
+        let request_builder = request_builder.header(TRACEPARENT_HEADER, self.traceparent());
+        match self
+            .trace_state
+            .as_deref()
+            .filter(|state| !state.is_empty())
+        {
+            Some(state) => request_builder.header(TRACESTATE_HEADER, state),
+            None => request_builder,
+        }
+    }
+}
+
+/// 1 回の呼び出しに対応するクライアント側の span
+pub(crate) struct InvokeSpan {
+    #[cfg(feature = "tracing-opentelemetry")]
+    span: Option<tracing::Span>,
+}
+
+impl InvokeSpan {
+    /// span を開始し、トレースコンテキストのヘッダーを付ける
+    ///
+    /// 現在の span のコンテキストを優先し、なければ `fallback` を使う。
+    #[cfg_attr(
+        not(feature = "tracing-opentelemetry"),
+        allow(unused_variables, unused_mut)
+    )]
+    pub(crate) fn start(
+        function_name: &str,
+        fallback: Option<&TraceContext>,
+        mut request_builder: RequestBuilder,
+    ) -> (Self, RequestBuilder) {
+        #[cfg(feature = "tracing-opentelemetry")]
+        if let Some((span, context)) = otel::start(function_name) {
+            return (Self { span: Some(span) }, context.inject(request_builder));
+        }
+
+        if let Some(context) = fallback.filter(|context| context.is_valid()) {
+            request_builder = context.inject(request_builder);
+        }
+        let span = Self {
+            #[cfg(feature = "tracing-opentelemetry")]
+            span: None,
+        };
+        (span, request_builder)
+    }
+
+    /// レスポンスのステータスを span の属性に記録する
+    #[cfg_attr(not(feature = "tracing-opentelemetry"), allow(unused_variables))]
+    pub(crate) fn record_status(&self, status: reqwest::StatusCode) {
+        #[cfg(feature = "tracing-opentelemetry")]
+        if let Some(span) = &self.span {
+            span.record("http.response.status_code", i64::from(status.as_u16()));
+        }
+    }
+}
+
+#[cfg(feature = "tracing-opentelemetry")]
+mod otel {
+    use super::TraceContext;
+    use opentelemetry::trace::{SpanContext, TraceContextExt};
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    impl From<&SpanContext> for TraceContext {
+        fn from(span_context: &SpanContext) -> Self {
+            let trace_state = span_context.trace_state().header();
+            Self {
+                trace_id: u128::from_be_bytes(span_context.trace_id().to_bytes()),
+                span_id: u64::from_be_bytes(span_context.span_id().to_bytes()),
+                flags: span_context.trace_flags().to_u8(),
+                trace_state: (!trace_state.is_empty()).then_some(trace_state),
+            }
+        }
+    }
+
+    /// 現在の span の子として `functions.invoke` span を作る (現在の span がなければ None)
+    pub(super) fn start(function_name: &str) -> Option<(tracing::Span, TraceContext)> {
+        let parent = tracing::Span::current().context();
+        if !parent.span().span_context().is_valid() {
+            return None;
+        }
+        let span = tracing::info_span!(
+            "functions.invoke",
+            otel.kind = "client",
+            faas.invoked_name = %function_name,
+            http.response.status_code = tracing::field::Empty,
+        );
+        let context = span.context();
+        let span_context = context.span().span_context().clone();
+        if !span_context.is_valid() {
+            return None;
+        }
+        Some((span, TraceContext::from(&span_context)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let context = TraceContext::new(0x4bf92f3577b34da6a3ce929d0e0e4736, 0x00f067aa0ba902b7);
+        assert_eq!(
+            context.traceparent(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert_eq!(
+            TraceContext::from_traceparent(&context.traceparent()),
+            Some(context)
+        );
+    }
+
+    #[test]
+    fn test_from_traceparent_rejects_invalid_values() {
+        for value in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        ] {
+            assert_eq!(TraceContext::from_traceparent(value), None, "{}", value);
+        }
+    }
+}
@@ -86,6 +86,7 @@ mod tests {
                 status,
                 message,
                 details,
+                ..
             } => {
                 assert_eq!(status, 500);
                 assert_eq!(message, "Internal Server Error");
@@ -124,6 +125,7 @@ mod tests {
                 status,
                 message,
                 details,
+                ..
             } => {
                 assert_eq!(status, 400);
                 assert_eq!(message, "Specific error message");
@@ -0,0 +1,85 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::Database;
+use supabase_rust_migration::list;
+use supabase_rust_migration::Migrator;
+
+/// Supabase プロジェクト向けのマイグレーション CLI
+#[derive(Parser, Debug)]
+#[command(name = "supabase-migration", version, about)]
+struct Cli {
+    /// 接続先データベース URL
+    #[arg(long, env = "DATABASE_URL")]
+    database_url: String,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// 適用済み / 未適用のマイグレーションを一覧表示
+    List {
+        /// 出力形式
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        output: OutputFormat,
+    },
+    /// 未適用のマイグレーションを適用
+    Up {
+        /// 適用する件数 (省略時はすべて)
+        #[arg(long)]
+        steps: Option<u32>,
+    },
+    /// 適用済みのマイグレーションを取り消す
+    Down {
+        /// 取り消す件数
+        #[arg(long, default_value_t = 1)]
+        steps: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[tokio::main]
+async fn main() {
+    dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(code) => std::process::exit(code),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<i32, DbErr> {
+    let db = Database::connect(&cli.database_url).await?;
+
+    match cli.command {
+        Commands::List { output } => {
+            let entries = list::collect::<Migrator, _>(&db).await?;
+            match output {
+                OutputFormat::Table => println!("{}", list::render_table(&entries)),
+                OutputFormat::Json => println!(
+                    "{}",
+                    list::render_json(&entries).map_err(|e| DbErr::Custom(e.to_string()))?
+                ),
+            }
+            Ok(list::exit_code(&entries))
+        }
+        Commands::Up { steps } => {
+            Migrator::up(&db, steps).await?;
+            Ok(0)
+        }
+        Commands::Down { steps } => {
+            Migrator::down(&db, Some(steps)).await?;
+            Ok(0)
+        }
+    }
+}
@@ -1,8 +1,14 @@
 #![allow(dead_code)]
 // Migration crate - Currently under development
 
-// Add basic module structure if needed later
-// pub mod commands;
+pub mod list;
+pub mod matview;
+pub mod migrator;
+pub mod storage_policy;
+
+pub use matview::RefreshMatviewMigration;
+pub use migrator::Migrator;
+pub use storage_policy::{StoragePolicy, StoragePolicyMigration};
 
 pub fn placeholder() {
     // Placeholder function to avoid empty crate warnings
@@ -0,0 +1,158 @@
+//! `supabase-migration list` の出力
+//!
+//! 適用済み / 未適用のマイグレーションを集計し、人間向けの表と
+//! 機械向けの JSON (`[{version, name, status, applied_at}]`) に整形する。
+
+use chrono::{DateTime, Utc};
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::MigrationStatus;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// 未適用のマイグレーションがある場合の終了コード
+pub const EXIT_PENDING: i32 = 2;
+
+/// マイグレーションの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+}
+
+impl std::fmt::Display for MigrationState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationState::Applied => write!(f, "applied"),
+            MigrationState::Pending => write!(f, "pending"),
+        }
+    }
+}
+
+/// 一覧の 1 行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationEntry {
+    pub version: String,
+    pub name: String,
+    pub status: MigrationState,
+    /// 適用日時 (RFC 3339, UTC)
+    pub applied_at: Option<String>,
+}
+
+impl MigrationEntry {
+    /// `m20250501_000001_create_table` 形式のマイグレーション名から作成
+    pub fn new(migration_name: &str, status: MigrationState, applied_at: Option<i64>) -> Self {
+        let (version, name) = split_migration_name(migration_name);
+        Self {
+            version,
+            name,
+            status,
+            applied_at: applied_at
+                .and_then(|secs| DateTime::<Utc>::from_timestamp(secs, 0))
+                .map(|dt| dt.to_rfc3339()),
+        }
+    }
+}
+
+// `m{date}_{seq}_{name}` を (`{date}_{seq}`, `{name}`) に分割する
+fn split_migration_name(migration_name: &str) -> (String, String) {
+    let trimmed = migration_name.strip_prefix('m').unwrap_or(migration_name);
+    let mut parts = trimmed.splitn(3, '_');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(date), Some(seq), Some(name))
+            if date.chars().all(|c| c.is_ascii_digit())
+                && seq.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            (format!("{}_{}", date, seq), name.to_string())
+        }
+        _ => (migration_name.to_string(), migration_name.to_string()),
+    }
+}
+
+/// データベースの履歴テーブルとマイグレーター定義から一覧を作成
+pub async fn collect<M, C>(db: &C) -> Result<Vec<MigrationEntry>, DbErr>
+where
+    M: MigratorTrait,
+    C: ConnectionTrait,
+{
+    let applied_at = M::get_migration_models(db)
+        .await?
+        .into_iter()
+        .map(|model| (model.version, model.applied_at))
+        .collect::<HashMap<_, _>>();
+
+    let entries = M::get_migration_with_status(db)
+        .await?
+        .iter()
+        .map(|migration| {
+            let status = match migration.status() {
+                MigrationStatus::Applied => MigrationState::Applied,
+                MigrationStatus::Pending => MigrationState::Pending,
+            };
+            MigrationEntry::new(
+                migration.name(),
+                status,
+                applied_at.get(migration.name()).copied(),
+            )
+        })
+        .collect();
+
+    Ok(entries)
+}
+
+/// 未適用のマイグレーションがあるかどうかに応じた終了コード
+pub fn exit_code(entries: &[MigrationEntry]) -> i32 {
+    if entries
+        .iter()
+        .any(|entry| entry.status == MigrationState::Pending)
+    {
+        EXIT_PENDING
+    } else {
+        0
+    }
+}
+
+/// JSON 形式で出力
+pub fn render_json(entries: &[MigrationEntry]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(entries)
+}
+
+/// 列を揃えた表形式で出力
+pub fn render_table(entries: &[MigrationEntry]) -> String {
+    let headers = ["VERSION", "NAME", "STATUS", "APPLIED AT"];
+    let rows = entries
+        .iter()
+        .map(|entry| {
+            [
+                entry.version.clone(),
+                entry.name.clone(),
+                entry.status.to_string(),
+                entry.applied_at.clone().unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut widths = headers.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let format_row = |cells: [&str; 4]| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut lines = vec![format_row(headers)];
+    for row in &rows {
+        lines.push(format_row([&row[0], &row[1], &row[2], &row[3]]));
+    }
+    lines.join("\n")
+}
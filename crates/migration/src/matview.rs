@@ -0,0 +1,68 @@
+//! マテリアライズドビューのリフレッシュ用 RPC 関数の定義
+//!
+//! `supabase_rust_postgrest::PostgrestClient::refresh_materialized_view` は
+//! PostgREST 経由で `refresh_matview(name)` を呼び出す。この関数をサーバー側に
+//! 用意するための SQL と、sea-orm-migration 用の `MigrationTrait` 実装を提供する。
+//!
+//! 関数は `security definer` で任意のマテリアライズドビューをリフレッシュできるため、
+//! 実行権限は `service_role` にだけ与える。サービスロールキーのクライアントから呼び出す。
+
+use sea_orm_migration::prelude::*;
+
+/// RPC 関数名 (`PostgrestClient::refresh_materialized_view` が呼び出す名前)
+pub const REFRESH_MATVIEW_FUNCTION: &str = "refresh_matview";
+
+/// `refresh_matview(name text)` を作成する SQL (実行できるのは `service_role` のみ)
+///
+/// `search_path` を `public` に固定して `%I` で名前を引用するため、リフレッシュできるのは
+/// `public` スキーマのビューだけ (`analytics.daily_sales` のようなスキーマ付きの名前は
+/// 1つの識別子として扱われ、見つからない)。
+pub const REFRESH_MATVIEW_SQL: &str = r#"
+create or replace function public.refresh_matview(name text)
+returns void
+language plpgsql
+security definer
+set search_path = public
+as $$
+begin
+  execute format('refresh materialized view %I', name);
+end;
+$$;
+
+revoke execute on function public.refresh_matview(text) from public, anon, authenticated;
+grant execute on function public.refresh_matview(text) to service_role;
+"#;
+
+/// `refresh_matview(name text)` を削除する SQL
+pub const DROP_REFRESH_MATVIEW_SQL: &str = r#"
+drop function if exists public.refresh_matview(text);
+"#;
+
+/// `refresh_matview` 関数を作成するマイグレーション
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RefreshMatviewMigration;
+
+impl MigrationName for RefreshMatviewMigration {
+    fn name(&self) -> &str {
+        "m20250501_000001_create_refresh_matview"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for RefreshMatviewMigration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(REFRESH_MATVIEW_SQL)
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(DROP_REFRESH_MATVIEW_SQL)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+//! このワークスペースが提供するマイグレーションの一覧
+
+use sea_orm_migration::prelude::*;
+
+use crate::matview::RefreshMatviewMigration;
+
+/// ワークスペースのマイグレーションを適用するマイグレーター
+pub struct Migrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![Box::new(RefreshMatviewMigration)]
+    }
+}
@@ -0,0 +1,447 @@
+//! `storage.objects` の RLS ポリシーの生成
+//!
+//! よく使うパターン (公開読み取り、`auth.uid()` ごとのフォルダー、認証済みユーザーのみの読み取り、
+//! サイズ・Content-Type を制限したアップロード) のポリシーを SQL として生成する。
+//! バケット名やパスは SQL リテラルとして、ポリシー名は識別子としてエスケープする。
+//!
+//! ```
+//! use supabase_rust_migration::storage_policy::StoragePolicy;
+//!
+//! let sql = StoragePolicy::owner_folder_access("avatars").read().write().to_sql()?;
+//! assert!(sql.contains("(storage.foldername(name))[1] = (select auth.uid()::text)"));
+//! # Ok::<(), supabase_rust_migration::storage_policy::StoragePolicyError>(())
+//! ```
+
+use sea_orm_migration::prelude::*;
+use std::fmt;
+
+/// Postgres の識別子の最大長 (バイト)。これを超える名前は黙って切り詰められる
+const MAX_IDENTIFIER_LEN: usize = 63;
+
+/// ポリシーの生成エラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoragePolicyError {
+    /// バケット名が空
+    EmptyBucket,
+    /// パスのプレフィックスが空、または空のセグメントを含む
+    InvalidPrefix(String),
+    /// `read()` / `write()` のどちらも指定していない
+    NoOperations { bucket: String },
+    /// ポリシー名が 63 バイトを超える (切り詰められると別のポリシーと衝突しうる)
+    NameTooLong(String),
+    /// `content_types()` に空の値が含まれる
+    InvalidContentType(String),
+}
+
+impl fmt::Display for StoragePolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyBucket => write!(f, "bucket id cannot be empty"),
+            Self::InvalidPrefix(prefix) => write!(f, "invalid path prefix: {:?}", prefix),
+            Self::NoOperations { bucket } => {
+                write!(f, "policy for bucket {:?} grants no operations", bucket)
+            }
+            Self::NameTooLong(name) => write!(
+                f,
+                "policy name exceeds {} bytes: {:?}",
+                MAX_IDENTIFIER_LEN, name
+            ),
+            Self::InvalidContentType(content_type) => {
+                write!(f, "invalid content type: {:?}", content_type)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StoragePolicyError {}
+
+/// ポリシーの対象となる操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyOperation {
+    Select,
+    Insert,
+    Update,
+    Delete,
+}
+
+impl PolicyOperation {
+    fn keyword(&self) -> &'static str {
+        match self {
+            Self::Select => "select",
+            Self::Insert => "insert",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Pattern {
+    PublicRead,
+    OwnerFolder,
+    AuthenticatedRead,
+    ConstrainedInsert {
+        max_size: Option<u64>,
+        content_types: Vec<String>,
+    },
+}
+
+impl Pattern {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::PublicRead => "public",
+            Self::OwnerFolder => "owner folder",
+            Self::AuthenticatedRead => "authenticated",
+            Self::ConstrainedInsert { .. } => "constrained",
+        }
+    }
+
+    fn role(&self) -> &'static str {
+        match self {
+            Self::PublicRead => "public",
+            _ => "authenticated",
+        }
+    }
+}
+
+/// `storage.objects` の RLS ポリシー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoragePolicy {
+    bucket: String,
+    prefix: Option<String>,
+    pattern: Pattern,
+    operations: Vec<PolicyOperation>,
+    name: Option<String>,
+}
+
+impl StoragePolicy {
+    fn new(bucket: &str, pattern: Pattern, operations: Vec<PolicyOperation>) -> Self {
+        Self {
+            bucket: bucket.to_string(),
+            prefix: None,
+            pattern,
+            operations,
+            name: None,
+        }
+    }
+
+    /// 誰でも (未ログインの `anon` を含む) オブジェクトを読み取れる
+    pub fn public_read(bucket: &str) -> Self {
+        Self::new(bucket, Pattern::PublicRead, vec![PolicyOperation::Select])
+    }
+
+    /// ログイン中のユーザーは `<auth.uid()>/...` 以下のオブジェクトだけを操作できる
+    ///
+    /// `read()` / `write()` で許可する操作を選ぶ。フォルダーの判定は `storage.foldername(name)` の
+    /// セグメントの完全一致で行うため、`uid` の前方一致で他人のフォルダーが見えることはない。
+    pub fn owner_folder_access(bucket: &str) -> Self {
+        Self::new(bucket, Pattern::OwnerFolder, Vec::new())
+    }
+
+    /// ログイン中のユーザーだけがオブジェクトを読み取れる
+    pub fn authenticated_read(bucket: &str) -> Self {
+        Self::new(
+            bucket,
+            Pattern::AuthenticatedRead,
+            vec![PolicyOperation::Select],
+        )
+    }
+
+    /// ログイン中のユーザーは `max_size()` / `content_types()` を満たすオブジェクトをアップロードできる
+    ///
+    /// サイズと Content-Type は Storage API が `metadata` に書き込む `size` / `mimetype` で判定する。
+    pub fn constrained_insert(bucket: &str) -> Self {
+        Self::new(
+            bucket,
+            Pattern::ConstrainedInsert {
+                max_size: None,
+                content_types: Vec::new(),
+            },
+            vec![PolicyOperation::Insert],
+        )
+    }
+
+    /// 読み取り (`select`) を許可する
+    pub fn read(self) -> Self {
+        self.with_operations(&[PolicyOperation::Select])
+    }
+
+    /// 書き込み (`insert` / `update` / `delete`) を許可する
+    pub fn write(self) -> Self {
+        self.with_operations(&[
+            PolicyOperation::Insert,
+            PolicyOperation::Update,
+            PolicyOperation::Delete,
+        ])
+    }
+
+    fn with_operations(mut self, operations: &[PolicyOperation]) -> Self {
+        for operation in operations {
+            if !self.operations.contains(operation) {
+                self.operations.push(*operation);
+            }
+        }
+        self
+    }
+
+    /// 対象を `prefix/` 以下のオブジェクトに限定する (例: `"users"`、`"public/images"`)
+    ///
+    /// `owner_folder_access` では `prefix/<auth.uid()>/...` が各ユーザーのフォルダーになる。
+    pub fn under_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(prefix.trim_matches('/').to_string());
+        self
+    }
+
+    /// アップロードできる最大サイズ (バイト)
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        if let Pattern::ConstrainedInsert { max_size, .. } = &mut self.pattern {
+            *max_size = Some(bytes);
+        }
+        self
+    }
+
+    /// アップロードできる Content-Type (`image/png` など、完全一致)
+    pub fn content_types(mut self, types: &[&str]) -> Self {
+        if let Pattern::ConstrainedInsert { content_types, .. } = &mut self.pattern {
+            content_types.extend(types.iter().map(|t| t.to_string()));
+        }
+        self
+    }
+
+    /// ポリシー名の接頭辞 (既定は `"<bucket>: <パターン>"`)。操作ごとに ` select` などが付く
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// 操作ごとのポリシー名
+    pub fn policy_names(&self) -> Result<Vec<String>, StoragePolicyError> {
+        self.validate()?;
+        let base = match &self.name {
+            Some(name) => name.clone(),
+            None => match &self.prefix {
+                Some(prefix) => format!("{}/{}: {}", self.bucket, prefix, self.pattern.label()),
+                None => format!("{}: {}", self.bucket, self.pattern.label()),
+            },
+        };
+        self.operations
+            .iter()
+            .map(|operation| {
+                let name = format!("{} {}", base, operation.keyword());
+                if name.len() > MAX_IDENTIFIER_LEN {
+                    Err(StoragePolicyError::NameTooLong(name))
+                } else {
+                    Ok(name)
+                }
+            })
+            .collect()
+    }
+
+    /// `create policy` 文 (操作ごとに 1 文)
+    pub fn to_sql(&self) -> Result<String, StoragePolicyError> {
+        let names = self.policy_names()?;
+        let condition = self.condition();
+        let statements = names
+            .iter()
+            .zip(&self.operations)
+            .map(|(name, operation)| {
+                let clauses = match operation {
+                    PolicyOperation::Select | PolicyOperation::Delete => {
+                        format!("using ({})", condition)
+                    }
+                    PolicyOperation::Insert => format!("with check ({})", self.check_condition()),
+                    PolicyOperation::Update => {
+                        format!("using ({})\nwith check ({})", condition, condition)
+                    }
+                };
+                format!(
+                    "create policy {}\non storage.objects\nfor {}\nto {}\n{};\n",
+                    quote_identifier(name),
+                    operation.keyword(),
+                    self.pattern.role(),
+                    clauses
+                )
+            })
+            .collect::<Vec<_>>();
+        Ok(statements.join("\n"))
+    }
+
+    /// `to_sql()` で作成したポリシーを削除する文
+    pub fn drop_sql(&self) -> Result<String, StoragePolicyError> {
+        Ok(self
+            .policy_names()?
+            .iter()
+            .map(|name| {
+                format!(
+                    "drop policy if exists {} on storage.objects;\n",
+                    quote_identifier(name)
+                )
+            })
+            .collect())
+    }
+
+    fn validate(&self) -> Result<(), StoragePolicyError> {
+        if self.bucket.is_empty() {
+            return Err(StoragePolicyError::EmptyBucket);
+        }
+        if let Some(prefix) = &self.prefix {
+            if prefix.split('/').any(str::is_empty) {
+                return Err(StoragePolicyError::InvalidPrefix(prefix.clone()));
+            }
+        }
+        if self.operations.is_empty() {
+            return Err(StoragePolicyError::NoOperations {
+                bucket: self.bucket.clone(),
+            });
+        }
+        if let Pattern::ConstrainedInsert { content_types, .. } = &self.pattern {
+            if let Some(invalid) = content_types.iter().find(|t| t.trim().is_empty()) {
+                return Err(StoragePolicyError::InvalidContentType(invalid.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    // バケット・プレフィックス・所有者の条件 (`using` 句)
+    fn condition(&self) -> String {
+        let mut predicates = vec![format!("bucket_id = {}", quote_literal(&self.bucket))];
+        let segments = self
+            .prefix
+            .as_deref()
+            .map(|prefix| prefix.split('/').collect::<Vec<_>>())
+            .unwrap_or_default();
+        match self.pattern {
+            // フォルダーはセグメント単位で比較する
+            Pattern::OwnerFolder => {
+                for (index, segment) in segments.iter().enumerate() {
+                    predicates.push(format!(
+                        "(storage.foldername(name))[{}] = {}",
+                        index + 1,
+                        quote_literal(segment)
+                    ));
+                }
+                predicates.push(format!(
+                    "(storage.foldername(name))[{}] = (select auth.uid()::text)",
+                    segments.len() + 1
+                ));
+            }
+            _ => {
+                if let Some(prefix) = &self.prefix {
+                    predicates.push(format!(
+                        "name like {} escape '\\'",
+                        quote_literal(&format!("{}/%", escape_like(prefix)))
+                    ));
+                }
+            }
+        }
+        predicates.join(" and ")
+    }
+
+    // `with check` 句 (アップロード制限を含む)
+    fn check_condition(&self) -> String {
+        let mut condition = self.condition();
+        if let Pattern::ConstrainedInsert {
+            max_size,
+            content_types,
+        } = &self.pattern
+        {
+            if let Some(bytes) = max_size {
+                condition.push_str(&format!(
+                    " and (metadata->>'size')::bigint <= {}",
+                    bytes
+                ));
+            }
+            if !content_types.is_empty() {
+                let types = content_types
+                    .iter()
+                    .map(|t| quote_literal(t))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                condition.push_str(&format!(
+                    " and metadata->>'mimetype' = any (array[{}])",
+                    types
+                ));
+            }
+        }
+        condition
+    }
+}
+
+// `"..."` で囲んだ識別子 (`"` は二重にする)
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// `'...'` で囲んだ文字列リテラル (`'` は二重にする)
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// `like` のワイルドカード (`%`, `_`) とエスケープ文字 (`\`) をエスケープする
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// ポリシーを作成するマイグレーション (`down` で削除する)
+#[derive(Debug, Clone)]
+pub struct StoragePolicyMigration {
+    name: String,
+    policies: Vec<StoragePolicy>,
+}
+
+impl StoragePolicyMigration {
+    /// `name` は `m20250501_000001_storage_policies` 形式のマイグレーション名
+    pub fn new(name: &str, policies: Vec<StoragePolicy>) -> Self {
+        Self {
+            name: name.to_string(),
+            policies,
+        }
+    }
+
+    /// すべてのポリシーの `create policy` 文
+    pub fn up_sql(&self) -> Result<String, StoragePolicyError> {
+        let statements = self
+            .policies
+            .iter()
+            .map(StoragePolicy::to_sql)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(statements.join("\n"))
+    }
+
+    /// すべてのポリシーの `drop policy` 文 (作成と逆順)
+    pub fn down_sql(&self) -> Result<String, StoragePolicyError> {
+        self.policies
+            .iter()
+            .rev()
+            .map(StoragePolicy::drop_sql)
+            .collect()
+    }
+}
+
+impl MigrationName for StoragePolicyMigration {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for StoragePolicyMigration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = self.up_sql().map_err(|e| DbErr::Custom(e.to_string()))?;
+        manager.get_connection().execute_unprepared(&sql).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let sql = self.down_sql().map_err(|e| DbErr::Custom(e.to_string()))?;
+        manager.get_connection().execute_unprepared(&sql).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{Database, DatabaseConnection};
+use serde_json::Value;
+use supabase_rust_migration::list::{self, MigrationEntry, MigrationState, EXIT_PENDING};
+
+struct CreateNotes;
+
+impl MigrationName for CreateNotes {
+    fn name(&self) -> &str {
+        "m20240101_000001_create_notes"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for CreateNotes {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("create table notes (id integer primary key)")
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("drop table notes")
+            .await?;
+        Ok(())
+    }
+}
+
+struct AddNotesTitle;
+
+impl MigrationName for AddNotesTitle {
+    fn name(&self) -> &str {
+        "m20240102_000001_add_notes_title"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for AddNotesTitle {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("alter table notes add column title text")
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        Ok(())
+    }
+}
+
+struct TestMigrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for TestMigrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![Box::new(CreateNotes), Box::new(AddNotesTitle)]
+    }
+}
+
+async fn temp_database(dir: &tempfile::TempDir) -> DatabaseConnection {
+    let url = format!("sqlite://{}?mode=rwc", dir.path().join("test.db").display());
+    Database::connect(&url).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_list_reports_pending_and_applied() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = temp_database(&dir).await;
+
+    TestMigrator::up(&db, Some(1)).await.unwrap();
+
+    let entries = list::collect::<TestMigrator, _>(&db).await.unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].version, "20240101_000001");
+    assert_eq!(entries[0].name, "create_notes");
+    assert_eq!(entries[0].status, MigrationState::Applied);
+    assert!(entries[0].applied_at.is_some());
+    assert_eq!(entries[1].status, MigrationState::Pending);
+    assert_eq!(entries[1].applied_at, None);
+    assert_eq!(list::exit_code(&entries), EXIT_PENDING);
+
+    TestMigrator::up(&db, None).await.unwrap();
+
+    let entries = list::collect::<TestMigrator, _>(&db).await.unwrap();
+    assert!(entries
+        .iter()
+        .all(|entry| entry.status == MigrationState::Applied));
+    assert_eq!(list::exit_code(&entries), 0);
+}
+
+#[test]
+fn test_json_output_schema() {
+    let entries = vec![
+        MigrationEntry::new(
+            "m20240101_000001_create_notes",
+            MigrationState::Applied,
+            Some(1_704_067_200),
+        ),
+        MigrationEntry::new(
+            "m20240102_000001_add_notes_title",
+            MigrationState::Pending,
+            None,
+        ),
+    ];
+
+    let json: Value = serde_json::from_str(&list::render_json(&entries).unwrap()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!([
+            {
+                "version": "20240101_000001",
+                "name": "create_notes",
+                "status": "applied",
+                "applied_at": "2024-01-01T00:00:00+00:00"
+            },
+            {
+                "version": "20240102_000001",
+                "name": "add_notes_title",
+                "status": "pending",
+                "applied_at": null
+            }
+        ])
+    );
+}
+
+#[test]
+fn test_table_output_is_aligned() {
+    let entries = vec![
+        MigrationEntry::new("m20240101_000001_a", MigrationState::Applied, Some(0)),
+        MigrationEntry::new("custom", MigrationState::Pending, None),
+    ];
+
+    let table = list::render_table(&entries);
+    let lines = table.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    let status_column = lines[0].find("STATUS").unwrap();
+    assert_eq!(&lines[1][status_column..status_column + 7], "applied");
+    assert_eq!(&lines[2][status_column..status_column + 7], "pending");
+}
@@ -0,0 +1,16 @@
+use supabase_rust_migration::matview::REFRESH_MATVIEW_SQL;
+
+#[test]
+fn refresh_matview_is_executable_by_service_role_only() {
+    let grants = REFRESH_MATVIEW_SQL
+        .lines()
+        .filter(|line| line.starts_with("grant ") || line.starts_with("revoke "))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        grants,
+        vec![
+            "revoke execute on function public.refresh_matview(text) from public, anon, authenticated;",
+            "grant execute on function public.refresh_matview(text) to service_role;",
+        ]
+    );
+}
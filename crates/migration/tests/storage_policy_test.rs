@@ -0,0 +1,172 @@
+use supabase_rust_migration::storage_policy::{
+    StoragePolicy, StoragePolicyError, StoragePolicyMigration,
+};
+
+#[test]
+fn public_read() {
+    let sql = StoragePolicy::public_read("avatars").to_sql().unwrap();
+    assert_eq!(
+        sql,
+        r#"create policy "avatars: public select"
+on storage.objects
+for select
+to public
+using (bucket_id = 'avatars');
+"#
+    );
+}
+
+#[test]
+fn owner_folder_read_write() {
+    let sql = StoragePolicy::owner_folder_access("avatars")
+        .read()
+        .write()
+        .to_sql()
+        .unwrap();
+    assert_eq!(
+        sql,
+        r#"create policy "avatars: owner folder select"
+on storage.objects
+for select
+to authenticated
+using (bucket_id = 'avatars' and (storage.foldername(name))[1] = (select auth.uid()::text));
+
+create policy "avatars: owner folder insert"
+on storage.objects
+for insert
+to authenticated
+with check (bucket_id = 'avatars' and (storage.foldername(name))[1] = (select auth.uid()::text));
+
+create policy "avatars: owner folder update"
+on storage.objects
+for update
+to authenticated
+using (bucket_id = 'avatars' and (storage.foldername(name))[1] = (select auth.uid()::text))
+with check (bucket_id = 'avatars' and (storage.foldername(name))[1] = (select auth.uid()::text));
+
+create policy "avatars: owner folder delete"
+on storage.objects
+for delete
+to authenticated
+using (bucket_id = 'avatars' and (storage.foldername(name))[1] = (select auth.uid()::text));
+"#
+    );
+}
+
+#[test]
+fn owner_folder_under_prefix_compares_segments() {
+    let sql = StoragePolicy::owner_folder_access("docs")
+        .under_prefix("/users/private/")
+        .read()
+        .to_sql()
+        .unwrap();
+    assert_eq!(
+        sql,
+        r#"create policy "docs/users/private: owner folder select"
+on storage.objects
+for select
+to authenticated
+using (bucket_id = 'docs' and (storage.foldername(name))[1] = 'users' and (storage.foldername(name))[2] = 'private' and (storage.foldername(name))[3] = (select auth.uid()::text));
+"#
+    );
+}
+
+#[test]
+fn authenticated_read_under_prefix_escapes_like_wildcards() {
+    let sql = StoragePolicy::authenticated_read("team's files")
+        .under_prefix("100%_done\\x")
+        .to_sql()
+        .unwrap();
+    assert_eq!(
+        sql,
+        r#"create policy "team's files/100%_done\x: authenticated select"
+on storage.objects
+for select
+to authenticated
+using (bucket_id = 'team''s files' and name like '100\%\_done\\x/%' escape '\');
+"#
+    );
+}
+
+#[test]
+fn constrained_insert() {
+    let sql = StoragePolicy::constrained_insert("uploads")
+        .max_size(5 * 1024 * 1024)
+        .content_types(&["image/png", "image/jpeg"])
+        .to_sql()
+        .unwrap();
+    assert_eq!(
+        sql,
+        r#"create policy "uploads: constrained insert"
+on storage.objects
+for insert
+to authenticated
+with check (bucket_id = 'uploads' and (metadata->>'size')::bigint <= 5242880 and metadata->>'mimetype' = any (array['image/png', 'image/jpeg']));
+"#
+    );
+}
+
+#[test]
+fn policy_names_are_quoted_identifiers() {
+    let sql = StoragePolicy::public_read("b")
+        .named(r#"say "hi""#)
+        .to_sql()
+        .unwrap();
+    assert!(sql.starts_with(r#"create policy "say ""hi"" select""#));
+    assert_eq!(
+        StoragePolicy::public_read("b")
+            .named(r#"say "hi""#)
+            .drop_sql()
+            .unwrap(),
+        "drop policy if exists \"say \"\"hi\"\" select\" on storage.objects;\n"
+    );
+}
+
+#[test]
+fn invalid_policies_are_rejected() {
+    assert_eq!(
+        StoragePolicy::owner_folder_access("avatars").to_sql(),
+        Err(StoragePolicyError::NoOperations {
+            bucket: "avatars".to_string()
+        })
+    );
+    assert_eq!(
+        StoragePolicy::public_read("").to_sql(),
+        Err(StoragePolicyError::EmptyBucket)
+    );
+    assert_eq!(
+        StoragePolicy::public_read("a").under_prefix("x//y").to_sql(),
+        Err(StoragePolicyError::InvalidPrefix("x//y".to_string()))
+    );
+    assert!(matches!(
+        StoragePolicy::public_read(&"b".repeat(60)).to_sql(),
+        Err(StoragePolicyError::NameTooLong(_))
+    ));
+    assert_eq!(
+        StoragePolicy::constrained_insert("u")
+            .content_types(&[" "])
+            .to_sql(),
+        Err(StoragePolicyError::InvalidContentType(" ".to_string()))
+    );
+}
+
+#[test]
+fn migration_drops_policies_in_reverse_order() {
+    let migration = StoragePolicyMigration::new(
+        "m20250601_000001_storage_policies",
+        vec![
+            StoragePolicy::public_read("avatars"),
+            StoragePolicy::owner_folder_access("avatars").write(),
+        ],
+    );
+    let up = migration.up_sql().unwrap();
+    assert_eq!(up.matches("create policy").count(), 4);
+    assert_eq!(
+        migration.down_sql().unwrap(),
+        r#"drop policy if exists "avatars: owner folder insert" on storage.objects;
+drop policy if exists "avatars: owner folder update" on storage.objects;
+drop policy if exists "avatars: owner folder delete" on storage.objects;
+drop policy if exists "avatars: public select" on storage.objects;
+"#
+    );
+}
@@ -0,0 +1,110 @@
+//! `insert` と `insert_from_iter` のピークメモリの比較
+//!
+//! ```sh
+//! cargo run --release -p supabase-rust-postgrest --example insert_memory [rows] [batch_size]
+//! ```
+//!
+//! ローカルのモックサーバーに書き込み、送信中に確保されたヒープの最大量を表示する。
+//! 元データ (`Vec<Row>`) の分は含めない。
+
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use supabase_rust_postgrest::PostgrestClient;
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+struct CountingAllocator;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[derive(Serialize)]
+struct Row {
+    id: u64,
+    user_id: String,
+    title: String,
+    body: String,
+    score: f64,
+}
+
+// 計測を始めてからのピーク (計測開始時点の使用量を差し引く)
+fn reset_peak() -> usize {
+    let current = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(current, Ordering::Relaxed);
+    current
+}
+
+fn mib(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let rows: u64 = args.next().and_then(|n| n.parse().ok()).unwrap_or(100_000);
+    let batch_size: usize = args.next().and_then(|n| n.parse().ok()).unwrap_or(1_000);
+
+    let server = MockServer::builder()
+        .disable_request_recording()
+        .start()
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(201))
+        .mount(&server)
+        .await;
+    let client = PostgrestClient::new(&server.uri(), "anon-key", "rows", reqwest::Client::new());
+
+    let source: Vec<Row> = (0..rows)
+        .map(|id| Row {
+            id,
+            user_id: format!("8f3e2d6c-1b4a-4c5d-9e8f-{:012}", id),
+            title: format!("Row number {}", id),
+            body: "lorem ipsum dolor sit amet ".repeat(4),
+            score: id as f64 / 7.0,
+        })
+        .collect();
+
+    let baseline = reset_peak();
+    let values: Vec<serde_json::Value> = source
+        .iter()
+        .map(|row| serde_json::to_value(row).unwrap())
+        .collect();
+    client.insert(&values).await.unwrap();
+    drop(values);
+    let insert_peak = PEAK.load(Ordering::Relaxed) - baseline;
+
+    let baseline = reset_peak();
+    let report = client
+        .insert_from_iter(source.iter(), batch_size)
+        .await
+        .unwrap();
+    let streamed_peak = PEAK.load(Ordering::Relaxed) - baseline;
+
+    println!("rows: {}, batch_size: {}", rows, batch_size);
+    println!("insert(Vec<Value>):   peak {:>8.1} MiB", mib(insert_peak));
+    println!(
+        "insert_from_iter:     peak {:>8.1} MiB ({} batches)",
+        mib(streamed_peak),
+        report.batches
+    );
+}
@@ -0,0 +1,63 @@
+//! select での集約関数 (PostgREST 12 以降)
+//!
+//! `select=category,price.sum()` のように集約関数と通常の列を並べると、
+//! 集約していない列 (この例では `category`) でグループ化される。
+//! PostgREST 側で `db-aggregates-enabled` を有効にする必要がある。
+
+use std::fmt;
+
+/// select に並べる列または集約関数 ([`crate::PostgrestClient::select_aggregate`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Agg<'a> {
+    /// 集約しない列 (グループ化のキーになる)
+    Col(&'a str),
+    /// `column.count()`
+    Count(&'a str),
+    /// `count()` (行数)
+    CountAll,
+    /// `column.sum()`
+    Sum(&'a str),
+    /// `column.avg()`
+    Avg(&'a str),
+    /// `column.min()`
+    Min(&'a str),
+    /// `column.max()`
+    Max(&'a str),
+    /// 別名を付けた列 (`alias:column.sum()`)
+    Aliased { alias: &'a str, agg: Box<Agg<'a>> },
+}
+
+impl<'a> Agg<'a> {
+    /// 結果のキーを `alias` にする
+    pub fn alias(self, alias: &'a str) -> Self {
+        let agg = match self {
+            Agg::Aliased { agg, .. } => agg,
+            agg => Box::new(agg),
+        };
+        Agg::Aliased { alias, agg }
+    }
+}
+
+impl fmt::Display for Agg<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Agg::Col(column) => write!(f, "{}", column),
+            Agg::Count(column) => write!(f, "{}.count()", column),
+            Agg::CountAll => write!(f, "count()"),
+            Agg::Sum(column) => write!(f, "{}.sum()", column),
+            Agg::Avg(column) => write!(f, "{}.avg()", column),
+            Agg::Min(column) => write!(f, "{}.min()", column),
+            Agg::Max(column) => write!(f, "{}.max()", column),
+            Agg::Aliased { alias, agg } => write!(f, "{}:{}", alias, agg),
+        }
+    }
+}
+
+/// select パラメーターの文字列にする
+pub(crate) fn render(aggregates: &[Agg<'_>]) -> String {
+    aggregates
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
@@ -0,0 +1,118 @@
+//! 行を逐次シリアライズする一括書き込み
+//!
+//! [`PostgrestClient::insert_from_iter`](crate::PostgrestClient::insert_from_iter) などは、
+//! 行を `serde_json::Value` に変換せずにバッチごとの JSON 配列へ直接書き込む。
+//! バッファはバッチ間で使い回すため、メモリ使用量は全行数ではなく 1 バッチ分で済む。
+
+use crate::PostgrestError;
+use bytes::Bytes;
+use serde::Serialize;
+
+/// 一括書き込みの結果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InsertReport {
+    /// 書き込んだ行数
+    pub rows: usize,
+    /// 送信したリクエスト (バッチ) の数
+    pub batches: usize,
+}
+
+// 1 バッチ分の行を `[row,row,...]` として書き込むバッファ
+pub(crate) struct BatchBuffer {
+    buffer: Vec<u8>,
+    rows: usize,
+    first_row: usize,
+}
+
+impl BatchBuffer {
+    pub(crate) fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            rows: 0,
+            first_row: 0,
+        }
+    }
+
+    /// バッファ内の行数
+    pub(crate) fn len(&self) -> usize {
+        self.rows
+    }
+
+    /// バッファ内の最初の行の番号 (全体での 0 始まり)
+    pub(crate) fn first_row(&self) -> usize {
+        self.first_row
+    }
+
+    /// 全体で `index` 番目の行を追加する
+    pub(crate) fn push<T: Serialize>(
+        &mut self,
+        index: usize,
+        row: &T,
+    ) -> Result<(), PostgrestError> {
+        if self.rows == 0 {
+            self.buffer.clear();
+            self.buffer.push(b'[');
+            self.first_row = index;
+        } else {
+            self.buffer.push(b',');
+        }
+        serde_json::to_writer(&mut self.buffer, row).map_err(|e| {
+            PostgrestError::RowSerializationError {
+                index,
+                message: e.to_string(),
+            }
+        })?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// 配列を閉じてリクエスト本文を返し、次のバッチのために空にする (容量は残す)
+    pub(crate) fn take_body(&mut self) -> Bytes {
+        self.buffer.push(b']');
+        self.rows = 0;
+        Bytes::copy_from_slice(&self.buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::ser::Error as _;
+    use serde::Serializer;
+
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(S::Error::custom("not today"))
+        }
+    }
+
+    #[test]
+    fn test_buffer_writes_json_arrays_and_reuses_capacity() {
+        let mut batch = BatchBuffer::new();
+        batch.push(0, &serde_json::json!({ "id": 1 })).unwrap();
+        batch.push(1, &serde_json::json!({ "id": 2 })).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(&batch.take_body()[..], br#"[{"id":1},{"id":2}]"#);
+        let capacity = batch.buffer.capacity();
+
+        batch.push(2, &serde_json::json!({ "id": 3 })).unwrap();
+        assert_eq!(batch.first_row(), 2);
+        assert_eq!(&batch.take_body()[..], br#"[{"id":3}]"#);
+        assert_eq!(batch.buffer.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_serialization_error_names_row_index() {
+        let mut batch = BatchBuffer::new();
+        batch.push(7, &serde_json::json!({ "id": 1 })).unwrap();
+        match batch.push(8, &Unserializable) {
+            Err(PostgrestError::RowSerializationError { index, message }) => {
+                assert_eq!(index, 8);
+                assert!(message.contains("not today"));
+            }
+            other => panic!("expected RowSerializationError, got {:?}", other),
+        }
+    }
+}
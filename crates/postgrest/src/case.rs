@@ -0,0 +1,91 @@
+//! カラム名の大文字小文字の変換
+//!
+//! [`PostgrestClient::column_case`](crate::PostgrestClient::column_case) を指定すると、
+//! フィルター・並び替え・[`select_columns`](crate::PostgrestClient::select_columns) に渡した
+//! snake_case のカラム名をクエリを組み立てる前に変換する。`select()` に渡した文字列は変換しない。
+
+use serde::de::{self, DeserializeOwned, Deserializer, Visitor};
+
+/// テーブルのカラム名の書き方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColumnCase {
+    /// 変換しない
+    #[default]
+    AsIs,
+    /// snake_case を camelCase に変換 (`user_id` → `userId`、Prisma などで作ったスキーマ向け)
+    CamelCase,
+}
+
+impl ColumnCase {
+    /// カラム名を変換 (`->` / `->>` の JSON パスはカラム部分だけを変換する)
+    pub fn apply(self, column: &str) -> String {
+        match self {
+            ColumnCase::AsIs => column.to_string(),
+            ColumnCase::CamelCase => match column.find("->") {
+                Some(index) => format!("{}{}", to_camel_case(&column[..index]), &column[index..]),
+                None => to_camel_case(column),
+            },
+        }
+    }
+}
+
+// 先頭の `_` は残し、以降の `_` の次の文字を大文字にする (camelCase の入力はそのまま)
+fn to_camel_case(column: &str) -> String {
+    let body = column.trim_start_matches('_');
+    let mut converted = column[..column.len() - body.len()].to_string();
+    let mut upper = false;
+    for c in body.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            converted.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            converted.push(c);
+        }
+    }
+    converted
+}
+
+/// `select_columns` で select に並べるカラムを持つ型
+///
+/// `Deserialize` を実装した構造体ではフィールド名 (`#[serde(rename)]` 適用後) になる。
+/// 構造体でない型や `#[serde(flatten)]` を含む構造体では空になる。
+pub trait SelectColumns {
+    fn columns() -> Vec<&'static str>;
+}
+
+impl<T: DeserializeOwned> SelectColumns for T {
+    fn columns() -> Vec<&'static str> {
+        let mut fields = StructFields(None);
+        let _ = T::deserialize(&mut fields);
+        fields.0.map(<[_]>::to_vec).unwrap_or_default()
+    }
+}
+
+// `deserialize_struct` に渡されるフィールド名だけを記録する Deserializer
+struct StructFields(Option<&'static [&'static str]>);
+
+impl<'de> Deserializer<'de> for &mut StructFields {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0 = Some(fields);
+        Err(de::Error::custom("fields recorded"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
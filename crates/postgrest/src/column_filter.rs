@@ -0,0 +1,95 @@
+//! 書き込む行のカラムの絞り込み
+//!
+//! `GENERATED ALWAYS AS IDENTITY` のカラムや生成カラムに値を送ると PostgREST は 400 を返す。
+//! [`PostgrestClient::omit_columns`](crate::PostgrestClient::omit_columns) /
+//! [`only_columns`](crate::PostgrestClient::only_columns) を指定すると、insert・upsert の
+//! 各行を JSON にシリアライズした後、本文を組み立てる前にキーを取り除く。
+
+use crate::{ColumnCase, PostgrestError};
+use serde_json::{Map, Value};
+
+// `only` で残すカラムを絞ってから `omit` のカラムを取り除く
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ColumnFilter {
+    pub(crate) omit: Vec<String>,
+    pub(crate) only: Option<Vec<String>>,
+}
+
+impl ColumnFilter {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.omit.is_empty() && self.only.is_none()
+    }
+
+    /// 単一の行または行の配列の各行に適用する
+    pub(crate) fn apply(&self, body: Value, case: ColumnCase) -> Result<Value, PostgrestError> {
+        match body {
+            Value::Array(rows) => rows
+                .into_iter()
+                .enumerate()
+                .map(|(index, row)| self.apply_row(index, row, case))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::Array),
+            row => self.apply_row(0, row, case),
+        }
+    }
+
+    /// 全体で `index` 番目の行に適用する (オブジェクト以外は `RowNotAnObject`)
+    pub(crate) fn apply_row(
+        &self,
+        index: usize,
+        row: Value,
+        case: ColumnCase,
+    ) -> Result<Value, PostgrestError> {
+        let Value::Object(mut fields) = row else {
+            return Err(PostgrestError::RowNotAnObject {
+                index,
+                found: json_kind(&row),
+            });
+        };
+        if let Some(only) = &self.only {
+            fields = fields
+                .into_iter()
+                .filter(|(key, _)| matches_any(only, key, case))
+                .collect::<Map<_, _>>();
+        }
+        fields.retain(|key, _| !matches_any(&self.omit, key, case));
+        Ok(Value::Object(fields))
+    }
+}
+
+// 指定した名前そのままと `column_case` で変換した名前の両方に一致させる
+fn matches_any(columns: &[String], key: &str, case: ColumnCase) -> bool {
+    columns
+        .iter()
+        .any(|column| column == key || case.apply(column) == key)
+}
+
+fn json_kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_only_then_omit_with_column_case() {
+        let filter = ColumnFilter {
+            omit: vec!["created_at".to_string()],
+            only: Some(vec!["user_id".to_string(), "created_at".to_string()]),
+        };
+        let row = json!({ "id": 1, "userId": 2, "createdAt": "now", "name": "a" });
+        assert_eq!(
+            filter.apply(row, ColumnCase::CamelCase).unwrap(),
+            json!({ "userId": 2 })
+        );
+    }
+}
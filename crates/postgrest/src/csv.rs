@@ -0,0 +1,206 @@
+//! CSV エクスポートの区切り文字・改行コード・BOM の変換
+//!
+//! [`PostgrestClient::export_csv_with_options`](crate::PostgrestClient::export_csv_with_options)
+//! は PostgREST が返す CSV (カンマ区切り・`"` で囲む RFC 4180 形式) を受信しながら 1 フィールドずつ
+//! 読み直し、指定した区切り文字と改行コードで書き出す。区切り文字・`"`・改行を含むフィールドは
+//! `"` で囲み直すので、文字列の単純な置換と違って値は壊れない。
+
+use crate::PostgrestError;
+
+/// レコードの区切りに使う改行コード
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    /// Excel (Windows) 向け
+    Crlf,
+}
+
+impl LineEnding {
+    fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Lf => b"\n",
+            LineEnding::Crlf => b"\r\n",
+        }
+    }
+}
+
+/// `export_csv_with_options` の出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CsvExportOptions {
+    /// フィールドの区切り文字 (`None` はカンマのまま。ロケールによっては Excel が `;` を要求する)
+    pub delimiter: Option<char>,
+    /// 先頭に UTF-8 の BOM を付ける (Excel が UTF-8 として開くようにする)
+    pub include_bom: bool,
+    /// レコードの区切り (`"` で囲まれたフィールド内の改行はそのまま残す)
+    pub line_ending: LineEnding,
+}
+
+impl CsvExportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = Some(delimiter);
+        self
+    }
+
+    pub fn include_bom(mut self, include_bom: bool) -> Self {
+        self.include_bom = include_bom;
+        self
+    }
+
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+}
+
+const BOM: &str = "\u{feff}";
+
+// 受け取ったチャンクを順に変換する (引用符や `\r\n` がチャンクをまたいでもよい)
+pub(crate) struct CsvTranscoder {
+    delimiter: Vec<u8>,
+    line_ending: LineEnding,
+    output: Vec<u8>,
+    // 読み途中のフィールド (引用符を外した値)
+    field: Vec<u8>,
+    state: State,
+    // 元のフィールドが `"` で囲まれていたか (囲みは保つ)
+    quoted: bool,
+    // 現在のレコードに読み途中のフィールドがあるか
+    in_record: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Unquoted,
+    Quoted,
+    // 引用符の中で `"` を読んだ (`""` か閉じ引用符かは次の文字で決まる)
+    QuoteInQuoted,
+    // 引用符の外で `\r` を読んだ
+    CarriageReturn,
+}
+
+impl CsvTranscoder {
+    pub(crate) fn new(options: &CsvExportOptions) -> Result<Self, PostgrestError> {
+        let delimiter = options.delimiter.unwrap_or(',');
+        if matches!(delimiter, '"' | '\r' | '\n') {
+            return Err(PostgrestError::InvalidParameters(format!(
+                "Invalid CSV delimiter: {:?}",
+                delimiter
+            )));
+        }
+        let mut output = Vec::new();
+        if options.include_bom {
+            output.extend_from_slice(BOM.as_bytes());
+        }
+        Ok(Self {
+            delimiter: delimiter.to_string().into_bytes(),
+            line_ending: options.line_ending,
+            output,
+            field: Vec::new(),
+            state: State::Unquoted,
+            quoted: false,
+            in_record: false,
+        })
+    }
+
+    pub(crate) fn push(&mut self, chunk: &[u8]) {
+        for &byte in chunk {
+            self.push_byte(byte);
+        }
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        match self.state {
+            State::Quoted => match byte {
+                b'"' => self.state = State::QuoteInQuoted,
+                _ => self.field.push(byte),
+            },
+            State::QuoteInQuoted => match byte {
+                b'"' => {
+                    self.field.push(b'"');
+                    self.state = State::Quoted;
+                }
+                _ => {
+                    self.state = State::Unquoted;
+                    self.push_byte(byte);
+                }
+            },
+            State::CarriageReturn => {
+                self.state = State::Unquoted;
+                self.end_record();
+                if byte != b'\n' {
+                    self.push_byte(byte);
+                }
+            }
+            State::Unquoted => {
+                self.in_record = true;
+                match byte {
+                    b'"' if self.field.is_empty() && !self.quoted => {
+                        self.quoted = true;
+                        self.state = State::Quoted;
+                    }
+                    b',' => self.end_field(),
+                    b'\n' => self.end_record(),
+                    b'\r' => self.state = State::CarriageReturn,
+                    _ => self.field.push(byte),
+                }
+            }
+        }
+    }
+
+    fn end_field(&mut self) {
+        let needs_quotes = self.quoted
+            || self.field.iter().any(|b| matches!(b, b'"' | b'\r' | b'\n'))
+            || contains(&self.field, &self.delimiter);
+        if needs_quotes {
+            self.output.push(b'"');
+            for &byte in &self.field {
+                if byte == b'"' {
+                    self.output.push(b'"');
+                }
+                self.output.push(byte);
+            }
+            self.output.push(b'"');
+        } else {
+            self.output.extend_from_slice(&self.field);
+        }
+        self.output.extend_from_slice(&self.delimiter);
+        self.field.clear();
+        self.quoted = false;
+    }
+
+    fn end_record(&mut self) {
+        if self.in_record {
+            self.end_field();
+            // `end_field` が付けた区切り文字をレコードの区切りに置き換える
+            self.output
+                .truncate(self.output.len() - self.delimiter.len());
+        }
+        self.output.extend_from_slice(self.line_ending.as_bytes());
+        self.in_record = false;
+    }
+
+    /// 残りを書き出して変換結果を返す (閉じていない引用符は閉じる)
+    pub(crate) fn finish(mut self) -> String {
+        match self.state {
+            State::CarriageReturn => self.end_record(),
+            _ if self.in_record => {
+                self.end_record();
+                self.output
+                    .truncate(self.output.len() - self.line_ending.as_bytes().len());
+            }
+            _ => {}
+        }
+        String::from_utf8_lossy(&self.output).into_owned()
+    }
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
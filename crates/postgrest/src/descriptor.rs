@@ -0,0 +1,586 @@
+//! クエリビルダーの状態のシリアライズ
+//!
+//! [`PostgrestClient::to_descriptor`](crate::PostgrestClient::to_descriptor) で組み立てたクエリを
+//! [`QueryDescriptor`] として JSON などに書き出し、別のプロセス (クライアント側) で
+//! [`PostgrestClient::from_descriptor`](crate::PostgrestClient::from_descriptor) によって復元する。
+//! ヘッダー・API キー・トークン・ベース URL は含めない (復元する側が渡す)。
+//!
+//! 読み込み時には形式のバージョンと演算子を検証し、知らない演算子を含む記述子は拒否する。
+//! 知らないフィールドは無視するので、同じバージョン内でのフィールド追加は古い読み手でも読める。
+
+use crate::prefer::Preferences;
+use crate::{CountMethod, Handling, PostgrestError, ReturnPreference};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 記述子の形式のバージョン (互換性のない変更で上げる)
+pub const QUERY_DESCRIPTOR_VERSION: u32 = 1;
+
+// フィルター以外のクエリパラメーター
+const SELECT: &str = "select";
+const ORDER: &str = "order";
+const LIMIT: &str = "limit";
+const OFFSET: &str = "offset";
+const SCHEMA: &str = "schema";
+const TRANSACTION: &str = "transaction";
+
+// `column=operator.value` の演算子
+const OPERATORS: &[&str] = &[
+    "eq",
+    "neq",
+    "gt",
+    "gte",
+    "lt",
+    "lte",
+    "like",
+    "ilike",
+    "match",
+    "imatch",
+    "is",
+    "isdistinct",
+    "in",
+    "cs",
+    "cd",
+    "ov",
+    "sl",
+    "sr",
+    "nxr",
+    "nxl",
+    "adj",
+    "fts",
+    "plfts",
+    "phfts",
+    "wfts",
+    "st_dwithin",
+];
+
+// `(any)` / `(all)` を付けられる演算子
+const QUANTIFIABLE: &[&str] = &[
+    "eq", "gt", "gte", "lt", "lte", "like", "ilike", "match", "imatch",
+];
+
+// `fts(config)` のように検索設定を付けられる演算子
+const FULL_TEXT: &[&str] = &["fts", "plfts", "phfts", "wfts"];
+
+// カラムを持たない論理演算子 (`or=(...)` / `and=(...)`)
+const GROUPS: &[&str] = &["or", "and"];
+
+/// クエリビルダーの状態 (テーブル・select・フィルター・並び順・件数・Prefer)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryDescriptor {
+    /// 形式のバージョン ([`QUERY_DESCRIPTOR_VERSION`])
+    pub version: u32,
+    pub table: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub select: Option<String>,
+    /// フィルター (`to_descriptor` はキーの順に並べる)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<FilterDescriptor>,
+    /// `order` パラメーターの値 (`column.desc,other.asc` など)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    #[serde(default)]
+    pub prefer: PreferDescriptor,
+}
+
+/// 1つのフィルター
+///
+/// カラムのフィルターは `column=[not.]operator.value`、論理グループは
+/// `[not.]or=value` (`column` は `None`、`value` は `(...)`) として送信される。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterDescriptor {
+    /// クォート・`column_case` 適用済みのカラム名 (論理グループでは `None`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    /// `eq`・`fts(english)`・`like(any)`・`or` など
+    pub operator: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub negated: bool,
+    pub value: String,
+}
+
+/// 記述子に含める `Prefer` の設定
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreferDescriptor {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub returning: Option<ReturnPreference>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<CountMethod>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handling: Option<Handling>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub missing_default: bool,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn invalid(message: String) -> PostgrestError {
+    PostgrestError::InvalidParameters(format!("Invalid query descriptor: {}", message))
+}
+
+impl QueryDescriptor {
+    /// クライアントのクエリパラメーターと Prefer の設定から作る
+    pub(crate) fn capture(
+        table: &str,
+        params: &HashMap<String, String>,
+        preferences: &Preferences,
+    ) -> Result<Self, PostgrestError> {
+        if params.contains_key(TRANSACTION) {
+            return Err(PostgrestError::InvalidParameters(
+                "a query bound to a transaction cannot be turned into a descriptor".to_string(),
+            ));
+        }
+        let mut descriptor = Self {
+            version: QUERY_DESCRIPTOR_VERSION,
+            table: table.to_string(),
+            select: params.get(SELECT).cloned(),
+            filters: Vec::new(),
+            order: params.get(ORDER).cloned(),
+            limit: parse_count(params, LIMIT)?,
+            offset: parse_count(params, OFFSET)?,
+            schema: params.get(SCHEMA).cloned(),
+            prefer: PreferDescriptor {
+                returning: preferences.returning,
+                count: preferences.count,
+                handling: preferences.handling,
+                timezone: preferences.timezone.clone(),
+                missing_default: preferences.missing_default,
+            },
+        };
+
+        let mut keys: Vec<&String> = params
+            .keys()
+            .filter(|key| ![SELECT, ORDER, LIMIT, OFFSET, SCHEMA].contains(&key.as_str()))
+            .collect();
+        keys.sort();
+        for key in keys {
+            let value = &params[key];
+            let (negated, group) = match key.strip_prefix("not.") {
+                Some(rest) => (true, rest),
+                None => (false, key.as_str()),
+            };
+            let filter = if GROUPS.contains(&group) {
+                FilterDescriptor {
+                    column: None,
+                    operator: group.to_string(),
+                    negated,
+                    value: value.clone(),
+                }
+            } else {
+                let (negated, rest) = match value.strip_prefix("not.") {
+                    Some(rest) => (true, rest),
+                    None => (false, value.as_str()),
+                };
+                let (operator, value) = rest.split_once('.').ok_or_else(|| {
+                    PostgrestError::InvalidParameters(format!(
+                        "filter on {} has no operator: {}",
+                        key, value
+                    ))
+                })?;
+                FilterDescriptor {
+                    column: Some(key.clone()),
+                    operator: operator.to_string(),
+                    negated,
+                    value: value.to_string(),
+                }
+            };
+            descriptor.filters.push(filter);
+        }
+        Ok(descriptor)
+    }
+
+    /// 検証してクエリパラメーターに戻す
+    pub(crate) fn query_params(&self) -> Result<HashMap<String, String>, PostgrestError> {
+        if self.version == 0 || self.version > QUERY_DESCRIPTOR_VERSION {
+            return Err(invalid(format!(
+                "unsupported version {} (this library reads up to version {})",
+                self.version, QUERY_DESCRIPTOR_VERSION
+            )));
+        }
+        if self.table.is_empty() {
+            return Err(invalid("table is empty".to_string()));
+        }
+
+        let mut params = HashMap::new();
+        for (key, value) in [
+            (SELECT, self.select.clone()),
+            (ORDER, self.order.clone()),
+            (LIMIT, self.limit.map(|limit| limit.to_string())),
+            (OFFSET, self.offset.map(|offset| offset.to_string())),
+            (SCHEMA, self.schema.clone()),
+        ] {
+            if let Some(value) = value {
+                params.insert(key.to_string(), value);
+            }
+        }
+        for filter in &self.filters {
+            let (key, value) = filter.to_query()?;
+            if params.insert(key.clone(), value).is_some() {
+                return Err(invalid(format!(
+                    "more than one filter or parameter uses the key {}",
+                    key
+                )));
+            }
+        }
+        Ok(params)
+    }
+
+    /// 記述子の Prefer の設定
+    pub(crate) fn preferences(&self) -> Preferences {
+        Preferences {
+            returning: self.prefer.returning,
+            count: self.prefer.count,
+            handling: self.prefer.handling,
+            timezone: self.prefer.timezone.clone(),
+            missing_default: self.prefer.missing_default,
+            ..Preferences::default()
+        }
+    }
+}
+
+impl FilterDescriptor {
+    fn to_query(&self) -> Result<(String, String), PostgrestError> {
+        let not = if self.negated { "not." } else { "" };
+        match &self.column {
+            None => {
+                if !GROUPS.contains(&self.operator.as_str()) {
+                    return Err(invalid(format!(
+                        "filter without a column must use or/and, not {:?}",
+                        self.operator
+                    )));
+                }
+                validate_group(&self.value)?;
+                Ok((format!("{}{}", not, self.operator), self.value.clone()))
+            }
+            Some(column) => {
+                if column.is_empty()
+                    || [SELECT, ORDER, LIMIT, OFFSET, SCHEMA, TRANSACTION]
+                        .contains(&column.as_str())
+                {
+                    return Err(invalid(format!("invalid filter column {:?}", column)));
+                }
+                validate_operator(&self.operator)?;
+                Ok((
+                    column.clone(),
+                    format!("{}{}.{}", not, self.operator, self.value),
+                ))
+            }
+        }
+    }
+}
+
+fn parse_count(params: &HashMap<String, String>, key: &str) -> Result<Option<i64>, PostgrestError> {
+    params
+        .get(key)
+        .map(|value| {
+            value.parse::<i64>().map_err(|_| {
+                PostgrestError::InvalidParameters(format!("{} is not a number: {}", key, value))
+            })
+        })
+        .transpose()
+}
+
+// `eq` / `like(any)` / `fts(english)` などを受け付ける
+fn validate_operator(operator: &str) -> Result<(), PostgrestError> {
+    let (name, modifier) = match operator.split_once('(') {
+        Some((name, rest)) => (name, rest.strip_suffix(')')),
+        None => (operator, None),
+    };
+    let valid = match modifier {
+        None if operator.contains('(') => false,
+        None => OPERATORS.contains(&name),
+        Some(modifier) if QUANTIFIABLE.contains(&name) => modifier == "any" || modifier == "all",
+        Some(modifier) if FULL_TEXT.contains(&name) => {
+            !modifier.is_empty() && !modifier.contains(['(', ')', '.', ','])
+        }
+        Some(_) => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(invalid(format!("unknown operator {:?}", operator)))
+    }
+}
+
+// `(a.eq.1,or(b.gt.2,not.c.is.null))` の各条件の演算子を検証する
+fn validate_group(value: &str) -> Result<(), PostgrestError> {
+    let inner = value
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .ok_or_else(|| invalid(format!("logical filter must be (...): {}", value)))?;
+    let conditions = split_top_level(inner, ',');
+    if conditions.iter().any(|condition| condition.is_empty()) {
+        return Err(invalid(format!("empty condition in {}", value)));
+    }
+    for condition in conditions {
+        let condition = condition.strip_prefix("not.").unwrap_or(condition);
+        if let Some(group) = GROUPS.iter().find_map(|group| {
+            condition
+                .strip_prefix(group)
+                .filter(|rest| rest.starts_with('('))
+        }) {
+            validate_group(group)?;
+            continue;
+        }
+        // `column.[not.]operator.value` (カラムには埋め込みリソースの `.` が含まれうる)
+        let segments = split_top_level(condition, '.');
+        let has_operator = segments
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, segment)| **segment != "not")
+            .take_while(|(index, _)| *index < segments.len() - 1)
+            .any(|(_, segment)| validate_operator(segment).is_ok());
+        if !has_operator {
+            return Err(invalid(format!(
+                "condition {:?} has no known operator",
+                condition
+            )));
+        }
+    }
+    Ok(())
+}
+
+// `"` と括弧の外にある `separator` で分割する
+fn split_top_level(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (index, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '(' if !in_quotes => depth += 1,
+            ')' if !in_quotes => depth = depth.saturating_sub(1),
+            c if c == separator && !in_quotes && depth == 0 => {
+                parts.push(&text[start..index]);
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Filter, FilterOperator, PostgrestClient, SortOrder};
+    use reqwest::Client;
+    use serde_json::json;
+    use url::Url;
+
+    // クエリパラメーターの順は HashMap に依存するので、並べ替えて比較する
+    fn sorted_url(url: &str) -> (String, Vec<(String, String)>) {
+        let url = Url::parse(url).unwrap();
+        let mut pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        pairs.sort();
+        (url.path().to_string(), pairs)
+    }
+
+    fn descriptor(value: serde_json::Value) -> QueryDescriptor {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_through_json() {
+        let original = PostgrestClient::new(
+            "http://localhost:54321",
+            "secret-key",
+            "orders",
+            Client::new(),
+        )
+        .with_auth("user-token")
+        .unwrap()
+        .select("id,total,customer:customers(name)")
+        .eq("status", "paid")
+        .gte("total", "100")
+        .not("note", FilterOperator::Is, None::<i32>)
+        .in_list("region", &["eu", "us"])
+        .text_search("body", "rust & async", Some("english"))
+        .filter(Filter::or([
+            Filter::eq("priority", "high"),
+            Filter::and([Filter::lt("age", 3), Filter::is_null("owner")]),
+        ]))
+        .order("created_at", SortOrder::Descending)
+        .limit(20)
+        .offset(40)
+        .count_method(CountMethod::Exact)
+        .timezone("Asia/Tokyo");
+
+        let json = serde_json::to_string(&original.to_descriptor().unwrap()).unwrap();
+        assert!(!json.contains("secret-key"));
+        assert!(!json.contains("user-token"));
+        assert!(!json.contains("localhost"));
+
+        let loaded: QueryDescriptor = serde_json::from_str(&json).unwrap();
+        let restored = PostgrestClient::from_descriptor(
+            &loaded,
+            "http://localhost:54321",
+            "other-key",
+            Client::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            sorted_url(&restored.build_url().unwrap()),
+            sorted_url(&original.build_url().unwrap())
+        );
+        assert_eq!(restored.preferences.count, Some(CountMethod::Exact));
+        assert_eq!(restored.preferences.timezone.as_deref(), Some("Asia/Tokyo"));
+        assert_eq!(restored.to_descriptor().unwrap(), loaded);
+    }
+
+    #[test]
+    fn test_filters_are_ordered_by_key() {
+        let client = PostgrestClient::new("http://localhost", "key", "items", Client::new())
+            .eq("b", "2")
+            .neq("a", "1")
+            .eq_val("c", None::<i32>);
+        let filters = client.to_descriptor().unwrap().filters;
+        assert_eq!(
+            filters,
+            vec![
+                FilterDescriptor {
+                    column: Some("a".to_string()),
+                    operator: "neq".to_string(),
+                    negated: false,
+                    value: "1".to_string(),
+                },
+                FilterDescriptor {
+                    column: Some("b".to_string()),
+                    operator: "eq".to_string(),
+                    negated: false,
+                    value: "2".to_string(),
+                },
+                FilterDescriptor {
+                    column: Some("c".to_string()),
+                    operator: "is".to_string(),
+                    negated: false,
+                    value: "null".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_unknown_operator() {
+        let bogus = descriptor(json!({
+            "version": 1,
+            "table": "orders",
+            "filters": [{ "column": "id", "operator": "drop_table", "value": "1" }]
+        }));
+        match PostgrestClient::from_descriptor(&bogus, "http://localhost", "key", Client::new()) {
+            Err(PostgrestError::InvalidParameters(message)) => {
+                assert!(message.contains("drop_table"), "{}", message)
+            }
+            _ => panic!("expected InvalidParameters"),
+        }
+
+        for (operator, value) in [
+            ("eq(some)", "1"),
+            ("fts(", "x"),
+            ("id(any)", "1"),
+            ("or", "(a.eq.1,b.bogus.2)"),
+            ("or", "(a.eq.1,and(b.gt.2,c.nope.3))"),
+            ("and", "a.eq.1"),
+            ("or", "(a.eq.1,)"),
+        ] {
+            let column = if operator == "or" || operator == "and" {
+                None
+            } else {
+                Some("id")
+            };
+            let bogus = descriptor(json!({
+                "version": 1,
+                "table": "orders",
+                "filters": [{ "column": column, "operator": operator, "value": value }]
+            }));
+            assert!(
+                bogus.query_params().is_err(),
+                "{} {} should be rejected",
+                operator,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_accepts_modifiers_and_nested_groups() {
+        let valid = descriptor(json!({
+            "version": 1,
+            "table": "posts",
+            "filters": [
+                { "column": "tags", "operator": "like(any)", "value": "{a*,b*}" },
+                { "column": "body", "operator": "wfts(english)", "value": "rust" },
+                { "column": "author.name", "operator": "eq", "negated": true, "value": "x" },
+                {
+                    "operator": "or",
+                    "negated": true,
+                    "value": "(a.in.(1,2),author.name.not.eq.\"a,b\",and(c.is.null,d.gt.1))"
+                }
+            ],
+            "future_field": true
+        }));
+        let params = valid.query_params().unwrap();
+        assert_eq!(params["tags"], "like(any).{a*,b*}");
+        assert_eq!(params["author.name"], "not.eq.x");
+        assert_eq!(
+            params["not.or"],
+            "(a.in.(1,2),author.name.not.eq.\"a,b\",and(c.is.null,d.gt.1))"
+        );
+    }
+
+    #[test]
+    fn test_rejects_unsupported_version_and_duplicates() {
+        for version in [0, QUERY_DESCRIPTOR_VERSION + 1] {
+            let future = descriptor(json!({ "version": version, "table": "orders" }));
+            assert!(future.query_params().is_err());
+        }
+        let duplicate = descriptor(json!({
+            "version": 1,
+            "table": "orders",
+            "filters": [
+                { "column": "id", "operator": "gt", "value": "1" },
+                { "column": "id", "operator": "lt", "value": "9" }
+            ]
+        }));
+        assert!(duplicate.query_params().is_err());
+        let reserved = descriptor(json!({
+            "version": 1,
+            "table": "orders",
+            "filters": [{ "column": "limit", "operator": "eq", "value": "1" }]
+        }));
+        assert!(reserved.query_params().is_err());
+    }
+
+    #[test]
+    fn test_rpc_and_transaction_clients_are_rejected() {
+        let rpc = PostgrestClient::rpc("http://localhost", "key", "f", json!({}), Client::new());
+        assert!(rpc.to_descriptor().is_err());
+        let mut bound = PostgrestClient::new("http://localhost", "key", "t", Client::new());
+        bound
+            .query_params
+            .insert(TRANSACTION.to_string(), "tx-1".to_string());
+        assert!(bound.to_descriptor().is_err());
+    }
+}
@@ -0,0 +1,63 @@
+//! レスポンスの行ごとのデシリアライズ
+//!
+//! 一括で `Vec<T>` に変換すると、失敗時のエラーにはバイト位置しか含まれない。
+//! 診断モードでは一度 `Vec<Value>` として読み込んでから行ごとに変換し、
+//! 失敗した行の番号と主キーらしきフィールドをエラーに含める。
+
+use crate::PostgrestError;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// エラーに含めるスカラーフィールドの最大数
+const SUMMARY_FIELDS: usize = 3;
+/// エラーに含める文字列値の最大文字数
+const SUMMARY_VALUE_CHARS: usize = 32;
+
+pub(crate) fn deserialize_rows<T: for<'de> Deserialize<'de>>(
+    body: &[u8],
+    diagnostic: bool,
+) -> Result<Vec<T>, PostgrestError> {
+    if !diagnostic {
+        return serde_json::from_slice(body)
+            .map_err(|e| PostgrestError::DeserializationError(e.to_string()));
+    }
+
+    let rows: Vec<Value> = serde_json::from_slice(body)
+        .map_err(|e| PostgrestError::DeserializationError(e.to_string()))?;
+    rows.iter()
+        .enumerate()
+        .map(|(index, row)| {
+            T::deserialize(row).map_err(|e| PostgrestError::RowDeserializationError {
+                index,
+                row_summary: row_summary(row),
+                message: e.to_string(),
+            })
+        })
+        .collect()
+}
+
+// `id` と `*_id` を優先し、先頭のスカラーフィールドを `name=value` で並べる
+fn row_summary(row: &Value) -> String {
+    let Some(object) = row.as_object() else {
+        return truncate(&row.to_string());
+    };
+    let mut fields: Vec<_> = object
+        .iter()
+        .filter(|(_, value)| !value.is_array() && !value.is_object())
+        .collect();
+    fields.sort_by_key(|(name, _)| *name != "id" && !name.ends_with("_id"));
+    fields
+        .into_iter()
+        .take(SUMMARY_FIELDS)
+        .map(|(name, value)| format!("{}={}", name, truncate(&value.to_string())))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn truncate(value: &str) -> String {
+    if value.chars().count() <= SUMMARY_VALUE_CHARS {
+        return value.to_string();
+    }
+    let truncated: String = value.chars().take(SUMMARY_VALUE_CHARS).collect();
+    format!("{}...", truncated)
+}
@@ -0,0 +1,85 @@
+//! `Prefer: tx=rollback` による書き込みのドライラン
+//!
+//! [`PostgrestClient::dry_run`] で作成した [`DryRunClient`] は、書き込みを実行してから
+//! ロールバックし、影響を受けるはずだった行を [`DryRunResult`] として返す。
+//! 戻り値の型が通常の書き込みと異なるため、コミットされた結果と取り違えることはない。
+
+use crate::{PostgrestClient, PostgrestError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// ロールバックされた書き込みの結果
+#[must_use]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DryRunResult<T> {
+    rows: T,
+}
+
+impl<T> DryRunResult<T> {
+    /// 書き込みがコミットされていれば返されたはずの行
+    pub fn rows(&self) -> &T {
+        &self.rows
+    }
+
+    pub fn into_rows(self) -> T {
+        self.rows
+    }
+}
+
+impl DryRunResult<Value> {
+    /// 行を任意の型に変換
+    pub fn rows_as<T: for<'de> Deserialize<'de>>(&self) -> Result<Vec<T>, PostgrestError> {
+        // 空のレスポンスは行なしとして扱う
+        if self.rows.is_null() {
+            return Ok(Vec::new());
+        }
+        Vec::<T>::deserialize(&self.rows)
+            .map_err(|e| PostgrestError::DeserializationError(e.to_string()))
+    }
+}
+
+/// 書き込みをロールバックするクライアント
+///
+/// フィルターや `Prefer` の設定は変換前の `PostgrestClient` のものがそのまま使われる。
+pub struct DryRunClient {
+    client: PostgrestClient,
+}
+
+impl DryRunClient {
+    pub(crate) fn new(client: PostgrestClient) -> Self {
+        Self { client }
+    }
+
+    /// 挿入される行を返す
+    pub async fn insert<T: Serialize>(
+        &self,
+        values: T,
+    ) -> Result<DryRunResult<Value>, PostgrestError> {
+        wrap(self.client.insert(values).await)
+    }
+
+    /// アップサートされる行を返す
+    pub async fn upsert<T: Serialize>(
+        &self,
+        values: T,
+    ) -> Result<DryRunResult<Value>, PostgrestError> {
+        wrap(self.client.upsert(values).await)
+    }
+
+    /// 更新される行を返す
+    pub async fn update<T: Serialize>(
+        &self,
+        values: T,
+    ) -> Result<DryRunResult<Value>, PostgrestError> {
+        wrap(self.client.update(values).await)
+    }
+
+    /// 削除される行を返す
+    pub async fn delete(&self) -> Result<DryRunResult<Value>, PostgrestError> {
+        wrap(self.client.delete().await)
+    }
+}
+
+fn wrap(result: Result<Value, PostgrestError>) -> Result<DryRunResult<Value>, PostgrestError> {
+    result.map(|rows| DryRunResult { rows })
+}
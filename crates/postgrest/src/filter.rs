@@ -0,0 +1,377 @@
+//! 論理演算子 (and / or / not) を組み合わせたフィルター
+//!
+//! [`Filter`] の木を PostgREST のクエリに変換する。最上位のグループは `and=(...)` /
+//! `or=(...)` / `not.and=(...)`、グループ内では `and(...)` / `or(...)` / `not.or(...)` になる。
+//! グループ内や `in.(...)` のリストでは、予約文字 (`,` `.` `:` `(` `)`) を含む文字列を
+//! `"` で囲み、`"` と `\` をバックスラッシュでエスケープする。
+
+use crate::FilterValue;
+
+/// 比較演算子
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterOperator {
+    Eq,
+    Neq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Like,
+    Ilike,
+    /// 正規表現 (`~`)
+    Match,
+    /// 大文字小文字を区別しない正規表現 (`~*`)
+    Imatch,
+    /// `null` / `true` / `false` との比較
+    Is,
+}
+
+impl FilterOperator {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Eq => "eq",
+            Self::Neq => "neq",
+            Self::Gt => "gt",
+            Self::Gte => "gte",
+            Self::Lt => "lt",
+            Self::Lte => "lte",
+            Self::Like => "like",
+            Self::Ilike => "ilike",
+            Self::Match => "match",
+            Self::Imatch => "imatch",
+            Self::Is => "is",
+        }
+    }
+}
+
+/// 論理演算子で組み合わせられるフィルター ([`crate::PostgrestClient::filter`])
+///
+/// ```
+/// use supabase_rust_postgrest::Filter;
+///
+/// // NOT (a = 1 AND b IS NULL)
+/// let filter = Filter::not(Filter::and([Filter::eq("a", 1), Filter::is_null("b")]));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    /// `column.operator.value`
+    Compare {
+        column: String,
+        operator: FilterOperator,
+        value: FilterValue,
+    },
+    /// `column.in.(values)`
+    In {
+        column: String,
+        values: Vec<FilterValue>,
+    },
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    /// 演算子を指定した比較
+    pub fn compare<T: Into<FilterValue>>(column: &str, operator: FilterOperator, value: T) -> Self {
+        Self::Compare {
+            column: column.to_string(),
+            operator,
+            value: value.into(),
+        }
+    }
+
+    /// 等価 (`None` は `is.null`)
+    pub fn eq<T: Into<FilterValue>>(column: &str, value: T) -> Self {
+        Self::compare(column, FilterOperator::Eq, value)
+    }
+
+    /// 不等価 (`None` は `not.is.null`)
+    pub fn neq<T: Into<FilterValue>>(column: &str, value: T) -> Self {
+        Self::compare(column, FilterOperator::Neq, value)
+    }
+
+    pub fn gt<T: Into<FilterValue>>(column: &str, value: T) -> Self {
+        Self::compare(column, FilterOperator::Gt, value)
+    }
+
+    pub fn gte<T: Into<FilterValue>>(column: &str, value: T) -> Self {
+        Self::compare(column, FilterOperator::Gte, value)
+    }
+
+    pub fn lt<T: Into<FilterValue>>(column: &str, value: T) -> Self {
+        Self::compare(column, FilterOperator::Lt, value)
+    }
+
+    pub fn lte<T: Into<FilterValue>>(column: &str, value: T) -> Self {
+        Self::compare(column, FilterOperator::Lte, value)
+    }
+
+    pub fn like(column: &str, pattern: &str) -> Self {
+        Self::compare(column, FilterOperator::Like, pattern)
+    }
+
+    pub fn ilike(column: &str, pattern: &str) -> Self {
+        Self::compare(column, FilterOperator::Ilike, pattern)
+    }
+
+    /// `column.is.null`
+    pub fn is_null(column: &str) -> Self {
+        Self::compare(column, FilterOperator::Is, FilterValue::Null)
+    }
+
+    /// `column.in.(values)`
+    pub fn in_list<T: Into<FilterValue>>(
+        column: &str,
+        values: impl IntoIterator<Item = T>,
+    ) -> Self {
+        Self::In {
+            column: column.to_string(),
+            values: values.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// すべてを満たす
+    pub fn and(filters: impl IntoIterator<Item = Filter>) -> Self {
+        Self::And(filters.into_iter().collect())
+    }
+
+    /// いずれかを満たす
+    pub fn or(filters: impl IntoIterator<Item = Filter>) -> Self {
+        Self::Or(filters.into_iter().collect())
+    }
+
+    /// 否定 (二重否定は打ち消す)
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(filter: Filter) -> Self {
+        match filter {
+            Self::Not(inner) => *inner,
+            filter => Self::Not(Box::new(filter)),
+        }
+    }
+
+    /// 最上位のクエリパラメーター (`(キー, 値)`)
+    pub(crate) fn to_query(&self, column_name: &dyn Fn(&str) -> String) -> (String, String) {
+        self.render_query(false, column_name)
+    }
+
+    /// グループ内での表現 (`column.operator.value` / `and(...)` など)
+    pub(crate) fn to_nested(&self, column_name: &dyn Fn(&str) -> String) -> String {
+        self.render_nested(false, column_name)
+    }
+
+    fn render_query(
+        &self,
+        negated: bool,
+        column_name: &dyn Fn(&str) -> String,
+    ) -> (String, String) {
+        match self {
+            Self::Compare { column, .. } | Self::In { column, .. } => {
+                (column_name(column), self.predicate(negated, false))
+            }
+            Self::And(filters) => (
+                not_prefix(negated, "and"),
+                format!("({})", render_list(filters, column_name)),
+            ),
+            Self::Or(filters) => (
+                not_prefix(negated, "or"),
+                format!("({})", render_list(filters, column_name)),
+            ),
+            Self::Not(inner) => inner.render_query(!negated, column_name),
+        }
+    }
+
+    fn render_nested(&self, negated: bool, column_name: &dyn Fn(&str) -> String) -> String {
+        match self {
+            Self::Compare { column, .. } | Self::In { column, .. } => {
+                format!("{}.{}", column_name(column), self.predicate(negated, true))
+            }
+            Self::And(filters) => format!(
+                "{}({})",
+                not_prefix(negated, "and"),
+                render_list(filters, column_name)
+            ),
+            Self::Or(filters) => format!(
+                "{}({})",
+                not_prefix(negated, "or"),
+                render_list(filters, column_name)
+            ),
+            Self::Not(inner) => inner.render_nested(!negated, column_name),
+        }
+    }
+
+    // `operator.value` (`quote` ならグループ内の値として予約文字を含む文字列を囲む)
+    fn predicate(&self, negated: bool, quote: bool) -> String {
+        let (negated, predicate) = match self {
+            Self::Compare {
+                operator, value, ..
+            } => match value {
+                FilterValue::Null if *operator == FilterOperator::Neq => {
+                    (!negated, "is.null".to_string())
+                }
+                FilterValue::Null => (negated, "is.null".to_string()),
+                FilterValue::Bool(value) => (negated, format!("{}.{}", operator.as_str(), value)),
+                FilterValue::Number(value) => (negated, format!("{}.{}", operator.as_str(), value)),
+                FilterValue::Text(value) if quote => (
+                    negated,
+                    format!("{}.{}", operator.as_str(), quote_value(value)),
+                ),
+                FilterValue::Text(value) => (negated, format!("{}.{}", operator.as_str(), value)),
+            },
+            Self::In { values, .. } => {
+                let values = values
+                    .iter()
+                    .map(|value| match value {
+                        FilterValue::Null => "null".to_string(),
+                        FilterValue::Bool(value) => value.to_string(),
+                        FilterValue::Number(value) => value.clone(),
+                        FilterValue::Text(value) => quote_value(value),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",");
+                (negated, format!("in.({})", values))
+            }
+            _ => unreachable!("predicate is only rendered for comparisons"),
+        };
+        not_prefix(negated, &predicate)
+    }
+}
+
+fn not_prefix(negated: bool, text: &str) -> String {
+    if negated {
+        format!("not.{}", text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn render_list(filters: &[Filter], column_name: &dyn Fn(&str) -> String) -> String {
+    filters
+        .iter()
+        .map(|filter| filter.to_nested(column_name))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// 予約文字を含む値を `"` で囲む (`"` と `\` はエスケープ)
+pub(crate) fn quote_value(value: &str) -> String {
+    let reserved = value.is_empty()
+        || value
+            .chars()
+            .any(|c| matches!(c, ',' | '.' | ':' | '(' | ')' | '"' | '\\'));
+    if !reserved {
+        return value.to_string();
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(filter: &Filter) -> (String, String) {
+        filter.to_query(&|column| column.to_string())
+    }
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_single_predicates() {
+        assert_eq!(query(&Filter::eq("age", 18)), pair("age", "eq.18"));
+        assert_eq!(
+            query(&Filter::not(Filter::eq("status", "archived"))),
+            pair("status", "not.eq.archived")
+        );
+        // 最上位の値は囲まない
+        assert_eq!(
+            query(&Filter::eq("email", "a@example.com")),
+            pair("email", "eq.a@example.com")
+        );
+        assert_eq!(query(&Filter::is_null("b")), pair("b", "is.null"));
+        assert_eq!(
+            query(&Filter::not(Filter::is_null("b"))),
+            pair("b", "not.is.null")
+        );
+        assert_eq!(
+            query(&Filter::not(Filter::neq("b", FilterValue::Null))),
+            pair("b", "is.null")
+        );
+        assert_eq!(
+            query(&Filter::not(Filter::in_list("name", ["Hi,there", "yes"]))),
+            pair("name", r#"not.in.("Hi,there",yes)"#)
+        );
+    }
+
+    #[test]
+    fn test_groups_render_postgrest_syntax() {
+        // https://postgrest.org/en/stable/references/api/tables_views.html#logical-operators
+        assert_eq!(
+            query(&Filter::or([Filter::lt("age", 18), Filter::gt("age", 21)])),
+            pair("or", "(age.lt.18,age.gt.21)")
+        );
+        assert_eq!(
+            query(&Filter::and([
+                Filter::gte("grade", 90),
+                Filter::compare("student", FilterOperator::Is, true),
+                Filter::or([Filter::eq("age", 14), Filter::is_null("age")]),
+            ])),
+            pair(
+                "and",
+                "(grade.gte.90,student.is.true,or(age.eq.14,age.is.null))"
+            )
+        );
+        assert_eq!(
+            query(&Filter::not(Filter::and([
+                Filter::gte("a", 0),
+                Filter::lte("a", 100)
+            ]))),
+            pair("not.and", "(a.gte.0,a.lte.100)")
+        );
+        assert_eq!(
+            query(&Filter::or([
+                Filter::eq("age", 14),
+                Filter::not(Filter::and([
+                    Filter::gte("age", 11),
+                    Filter::lte("age", 17)
+                ])),
+            ])),
+            pair("or", "(age.eq.14,not.and(age.gte.11,age.lte.17))")
+        );
+        assert_eq!(
+            query(&Filter::and([
+                Filter::not(Filter::or([Filter::eq("a", 1), Filter::eq("b", 2)])),
+                Filter::not(Filter::is_null("c")),
+                Filter::not(Filter::not(Filter::eq("d", 3))),
+            ])),
+            pair("and", "(not.or(a.eq.1,b.eq.2),c.not.is.null,d.eq.3)")
+        );
+    }
+
+    #[test]
+    fn test_values_are_quoted_inside_groups() {
+        assert_eq!(
+            query(&Filter::or([
+                Filter::eq("name", "Doe, John"),
+                Filter::eq("email", "a@example.com"),
+                Filter::eq("note", r#"say "hi" \o/"#),
+                Filter::eq("title", "plain"),
+                Filter::eq("empty", ""),
+                Filter::eq("price", 1.5),
+            ])),
+            pair(
+                "or",
+                r#"(name.eq."Doe, John",email.eq."a@example.com",note.eq."say \"hi\" \\o/",title.eq.plain,empty.eq."",price.eq.1.5)"#
+            )
+        );
+    }
+}
@@ -0,0 +1,68 @@
+//! GeoJSON (RFC 7946) のレスポンス型
+//!
+//! PostgREST は `Accept: application/geo+json` を指定すると、geometry カラムを持つ
+//! テーブルやビューの結果を FeatureCollection として返す。
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// GeoJSON レスポンスの Content-Type
+pub const GEOJSON_CONTENT_TYPE: &str = "application/geo+json";
+
+/// 座標 (経度, 緯度[, 高度])
+pub type Position = Vec<f64>;
+
+/// ジオメトリ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Geometry {
+    Point {
+        coordinates: Position,
+    },
+    MultiPoint {
+        coordinates: Vec<Position>,
+    },
+    LineString {
+        coordinates: Vec<Position>,
+    },
+    MultiLineString {
+        coordinates: Vec<Vec<Position>>,
+    },
+    Polygon {
+        coordinates: Vec<Vec<Position>>,
+    },
+    MultiPolygon {
+        coordinates: Vec<Vec<Vec<Position>>>,
+    },
+    GeometryCollection {
+        geometries: Vec<Geometry>,
+    },
+}
+
+/// フィーチャー (1行分)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "Feature")]
+pub struct Feature {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+    pub geometry: Option<Geometry>,
+    /// geometry 以外のカラム
+    #[serde(default)]
+    pub properties: Option<Map<String, Value>>,
+}
+
+impl Feature {
+    /// プロパティを取得
+    pub fn property(&self, name: &str) -> Option<&Value> {
+        self.properties.as_ref()?.get(name)
+    }
+}
+
+/// フィーチャーの集まり
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename = "FeatureCollection")]
+pub struct FeatureCollection {
+    pub features: Vec<Feature>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<Vec<f64>>,
+}
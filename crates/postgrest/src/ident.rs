@@ -0,0 +1,142 @@
+//! カラム名・テーブル名のクォート
+//!
+//! PostgREST はクエリパラメーターのキーや `select` / `order` の中の名前を、英数字と `_` 以外を
+//! 含む場合や予約語 (`order`, `select`, `not` など) と同じ場合に誤って解釈する。
+//! そのような名前を `"` で囲む (`"` と `\` はバックスラッシュでエスケープ)。
+//! テーブル名は URL のパスに入るため、クォートではなくパーセントエンコードする。
+//! `select()` に渡した文字列はそのまま送る。
+
+/// PostgREST のクエリパラメーター名と演算子
+const RESERVED: &[&str] = &[
+    "select",
+    "columns",
+    "order",
+    "limit",
+    "offset",
+    "on_conflict",
+    "and",
+    "or",
+    "not",
+    "eq",
+    "neq",
+    "gt",
+    "gte",
+    "lt",
+    "lte",
+    "like",
+    "ilike",
+    "match",
+    "imatch",
+    "is",
+    "in",
+    "cs",
+    "cd",
+    "ov",
+    "sl",
+    "sr",
+    "nxl",
+    "nxr",
+    "adj",
+    "fts",
+    "plfts",
+    "phfts",
+    "wfts",
+    "any",
+    "all",
+];
+
+/// 必要な場合だけ識別子を `"` で囲む (`order` → `"order"`、`user id` → `"user id"`)
+pub fn quote_ident(name: &str) -> String {
+    let plain = !name.is_empty()
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !RESERVED.iter().any(|word| word.eq_ignore_ascii_case(name));
+    if plain {
+        return name.to_string();
+    }
+    let mut quoted = String::with_capacity(name.len() + 2);
+    quoted.push('"');
+    for c in name.chars() {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// フィルターなどに渡されたカラム参照をクォートする
+///
+/// `.` で区切った埋め込みリソースの各部分を個別にクォートし、`->` / `->>` 以降の JSON パスは
+/// そのまま残す。既に `"` で囲まれた部分は変更しない (`.` を含むカラム名はこの形で渡す)。
+pub(crate) fn quote_column(column: &str) -> String {
+    let (path, json_path) = match column.find("->") {
+        Some(index) => column.split_at(index),
+        None => (column, ""),
+    };
+    let mut quoted = split_path(path)
+        .into_iter()
+        .map(|segment| {
+            if segment.len() >= 2 && segment.starts_with('"') && segment.ends_with('"') {
+                segment.to_string()
+            } else {
+                quote_ident(segment)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".");
+    quoted.push_str(json_path);
+    quoted
+}
+
+// `"` の外の `.` で分割する
+fn split_path(path: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (index, c) in path.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '.' if !in_quotes => {
+                segments.push(&path[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    segments.push(&path[start..]);
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_ident_only_when_needed() {
+        assert_eq!(quote_ident("user_id"), "user_id");
+        assert_eq!(quote_ident("userId"), "userId");
+        assert_eq!(quote_ident("名前"), "名前");
+        assert_eq!(quote_ident("order"), r#""order""#);
+        assert_eq!(quote_ident("NOT"), r#""NOT""#);
+        assert_eq!(quote_ident("first name"), r#""first name""#);
+        assert_eq!(quote_ident("a,b"), r#""a,b""#);
+        assert_eq!(quote_ident(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(quote_ident(""), r#""""#);
+    }
+
+    #[test]
+    fn test_quote_column_keeps_paths() {
+        assert_eq!(quote_column("status"), "status");
+        assert_eq!(quote_column("group.order"), r#"group."order""#);
+        assert_eq!(quote_column("line items.id"), r#""line items".id"#);
+        assert_eq!(quote_column("countries.name"), "countries.name");
+        assert_eq!(quote_column("data->>name"), "data->>name");
+        assert_eq!(quote_column("order->items->0"), r#""order"->items->0"#);
+        assert_eq!(quote_column(r#""info.cpe""#), r#""info.cpe""#);
+        assert_eq!(quote_column(r#"t."a.b""#), r#"t."a.b""#);
+    }
+}
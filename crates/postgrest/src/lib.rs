@@ -11,11 +11,15 @@
 //! - Transactions
 //! - RPC function calls
 //! - CSV export
+//! - GeoJSON responses (`execute_geojson`) and other formats (`execute_with_accept`)
 
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt;
 use thiserror::Error;
@@ -24,6 +28,50 @@ use url::Url;
 use serde_json::json;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use supabase_rust_core::{
+    RequestIds, SendError, SendGate, SendRejected, SendThroughGate, SendWithRequestId,
+};
+
+pub use supabase_rust_core::{Page, Paged, Redacted};
+
+mod aggregate;
+mod bulk_insert;
+mod case;
+mod column_filter;
+mod csv;
+mod descriptor;
+mod diagnostics;
+mod dry_run;
+mod filter;
+pub mod geojson;
+mod ident;
+mod prefer;
+mod refresh;
+mod replica;
+mod transaction;
+mod upsert_report;
+
+pub use aggregate::Agg;
+pub use bulk_insert::InsertReport;
+pub use case::{ColumnCase, SelectColumns};
+use column_filter::ColumnFilter;
+pub use csv::{CsvExportOptions, LineEnding};
+pub use descriptor::{
+    FilterDescriptor, PreferDescriptor, QueryDescriptor, QUERY_DESCRIPTOR_VERSION,
+};
+pub use dry_run::{DryRunClient, DryRunResult};
+pub use filter::{Filter, FilterOperator};
+pub use geojson::{Feature, FeatureCollection, Geometry};
+pub use ident::quote_ident;
+use prefer::Preferences;
+pub use prefer::{CountMethod, Handling, ReturnPreference};
+pub use refresh::TokenRefresher;
+pub use replica::ReadPreference;
+pub use transaction::{Operation, SavepointGuard};
+pub use upsert_report::UpsertReport;
+
+/// 単一オブジェクトとして取得する場合の Accept ヘッダー
+const SINGLE_OBJECT_CONTENT_TYPE: &str = "application/vnd.pgrst.object+json";
 
 /// PostgREST APIエラーの詳細情報
 #[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -57,21 +105,29 @@ impl fmt::Display for PostgrestApiErrorDetails {
 /// エラー型
 #[derive(Error, Debug)]
 pub enum PostgrestError {
-    #[error("API error: {details} (Status: {status})")]
+    #[error("API error: {details} (Status: {status}){request_ids}")]
     ApiError {
         details: PostgrestApiErrorDetails,
         status: reqwest::StatusCode,
+        /// `x-client-request-id` とサーバーの `sb-request-id` / `cf-ray`
+        /// (`Result` を小さく保つため Box に入れる)
+        request_ids: Box<RequestIds>,
     },
 
-    #[error("API error (unparsed): {message} (Status: {status})")]
+    #[error("API error (unparsed): {message} (Status: {status}){request_ids}")]
     UnparsedApiError {
         message: String,
         status: reqwest::StatusCode,
+        request_ids: Box<RequestIds>,
     },
 
     #[error("Network error: {0}")]
     NetworkError(#[from] reqwest::Error),
 
+    /// 送信のゲート (統合クライアントのサーキットブレーカーなど) が止めた (リクエストは送信していない)
+    #[error("Request not sent: {0}")]
+    Rejected(SendRejected),
+
     #[error("URL parse error: {0}")]
     UrlParseError(#[from] url::ParseError),
 
@@ -84,10 +140,85 @@ pub enum PostgrestError {
     #[error("Transaction error: {0}")]
     TransactionError(String),
 
+    /// `execute_batch` の `index` 番目 (0 始まり) の操作が失敗した (以降の操作は実行していない)
+    #[error("Batch operation {index} failed: {source}")]
+    BatchOperationFailed {
+        index: usize,
+        source: Box<PostgrestError>,
+    },
+
     #[error("Deserialization error: {0}")]
     DeserializationError(String),
+
+    /// `diagnostic_deserialization` が有効な場合の行単位のデシリアライズエラー
+    #[error("Deserialization error at row {index} ({row_summary}): {message}")]
+    RowDeserializationError {
+        /// 0 始まりの行番号
+        index: usize,
+        /// 主キーらしきフィールドの要約 (`id=3, name="..."`)
+        row_summary: String,
+        message: String,
+    },
+
+    /// `insert_from_iter` などで `index` 番目 (0 始まり) の行をシリアライズできなかった
+    ///
+    /// その行を含むバッチは送信していない (それより前のバッチは書き込み済み)。
+    #[error("Serialization error at row {index}: {message}")]
+    RowSerializationError { index: usize, message: String },
+
+    /// `upsert_report` の入力行 `index` にキーカラム `column` がない (または null)
+    #[error("Row {index} has no value for key column {column}")]
+    MissingKeyColumn { index: usize, column: String },
+
+    /// 一括書き込みで `first_row` 番目の行から始まるバッチが失敗した (`written` 行は書き込み済み)
+    #[error("Batch starting at row {first_row} failed ({written} rows already written): {source}")]
+    BatchWriteFailed {
+        first_row: usize,
+        written: usize,
+        source: Box<PostgrestError>,
+    },
+
+    #[error("RPC function not found: {function} (Hint: {hint}){request_ids}")]
+    FunctionNotFound {
+        function: String,
+        hint: String,
+        request_ids: Box<RequestIds>,
+    },
+
+    #[error("Unexpected content type: expected {expected}, got {actual}{request_ids}")]
+    UnexpectedContentType {
+        expected: String,
+        actual: String,
+        request_ids: Box<RequestIds>,
+    },
+
+    /// `ReadPreference::ReplicaOnly` のクライアントで書き込みを試みた
+    #[error(
+        "{operation}() writes to the database, but this client is routed to the read replica only"
+    )]
+    WriteOnReadReplica { operation: String },
+
+    /// `omit_columns` / `only_columns` を指定した書き込みで、行 `index` が JSON オブジェクトでない
+    #[error(
+        "Row {index} is {found}, not a JSON object; omit_columns / only_columns need object rows"
+    )]
+    RowNotAnObject { index: usize, found: &'static str },
+}
+
+impl From<SendError> for PostgrestError {
+    fn from(error: SendError) -> Self {
+        match error {
+            SendError::Rejected(rejected) => PostgrestError::Rejected(rejected),
+            SendError::Http(e) => PostgrestError::NetworkError(e),
+        }
+    }
 }
 
+/// マテリアライズドビューのリフレッシュに使用する RPC 関数名
+///
+/// サーバー側の定義は `supabase_rust_migration::matview` を参照。
+pub const REFRESH_MATVIEW_FUNCTION: &str = "refresh_matview";
+
 /// ソート方向
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SortOrder {
@@ -95,6 +226,101 @@ pub enum SortOrder {
     Descending,
 }
 
+/// 型付きフィルター値 (`eq_val` などで使用)
+///
+/// 真偽値は小文字 (`true` / `false`)、数値はロケールに依存しない形式で送信される。
+/// `None` は `is.null` 演算子に変換される。
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterValue {
+    Null,
+    Bool(bool),
+    Number(String),
+    Text(String),
+}
+
+impl FilterValue {
+    fn float(value: f64) -> Self {
+        if value.is_nan() {
+            Self::Number("NaN".to_string())
+        } else if value.is_infinite() {
+            Self::Number(if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string())
+        } else {
+            Self::Number(value.to_string())
+        }
+    }
+
+    // `operator.value` 形式のクエリ値に変換 (`Null` は `is.null`)
+    fn to_filter(&self, operator: &str) -> String {
+        match self {
+            Self::Null if operator == "neq" => "not.is.null".to_string(),
+            Self::Null => "is.null".to_string(),
+            Self::Bool(value) => format!("{}.{}", operator, value),
+            Self::Number(value) | Self::Text(value) => format!("{}.{}", operator, value),
+        }
+    }
+}
+
+macro_rules! impl_filter_value_from_int {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for FilterValue {
+                fn from(value: $ty) -> Self {
+                    Self::Number(value.to_string())
+                }
+            }
+        )*
+    };
+}
+
+impl_filter_value_from_int!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl From<f64> for FilterValue {
+    fn from(value: f64) -> Self {
+        Self::float(value)
+    }
+}
+
+impl From<f32> for FilterValue {
+    fn from(value: f32) -> Self {
+        if value.is_finite() {
+            // f64 に変換すると 0.1 が 0.10000000149011612 になるため f32 のまま整形
+            Self::Number(value.to_string())
+        } else {
+            Self::float(f64::from(value))
+        }
+    }
+}
+
+impl From<bool> for FilterValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<&str> for FilterValue {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_string())
+    }
+}
+
+impl From<String> for FilterValue {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<&String> for FilterValue {
+    fn from(value: &String) -> Self {
+        Self::Text(value.clone())
+    }
+}
+
+impl<T: Into<FilterValue>> From<Option<T>> for FilterValue {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Self::Null)
+    }
+}
+
 /// トランザクションの分離レベル
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IsolationLevel {
@@ -143,17 +369,60 @@ enum TransactionState {
 /// PostgreST クライアント
 pub struct PostgrestClient {
     base_url: String,
-    api_key: String,
+    api_key: Redacted<String>,
     table: String,
     http_client: Client,
     headers: HeaderMap,
     query_params: HashMap<String, String>,
     #[allow(dead_code)]
     path: Option<String>,
-    #[allow(dead_code)]
     is_rpc: bool,
-    #[allow(dead_code)]
     rpc_params: Option<Value>,
+    preferences: Preferences,
+    diagnostic_deserialization: bool,
+    row_caps: RowCaps,
+    column_case: ColumnCase,
+    accept_profile: Option<String>,
+    token_refresher: Option<Arc<dyn TokenRefresher>>,
+    send_gate: Option<Arc<dyn SendGate>>,
+    replica_url: Option<String>,
+    read_preference: ReadPreference,
+    column_filter: ColumnFilter,
+}
+
+// 読み取りクエリの件数の上限 (`default_max_rows` / `max_rows_ceiling` / `unlimited`)
+#[derive(Debug, Clone, Copy, Default)]
+struct RowCaps {
+    default_max_rows: Option<u32>,
+    ceiling: Option<u32>,
+    unlimited: bool,
+}
+
+impl RowCaps {
+    // 明示的な limit に上限を適用した値 (None なら limit を付けない)
+    fn apply(&self, explicit: Option<&str>) -> Option<String> {
+        if self.unlimited {
+            return explicit.map(str::to_string);
+        }
+        let Some(explicit) = explicit else {
+            let limit = match (self.default_max_rows, self.ceiling) {
+                (Some(default), Some(ceiling)) => Some(default.min(ceiling)),
+                (default, ceiling) => default.or(ceiling),
+            };
+            return limit.map(|limit| limit.to_string());
+        };
+        match (explicit.parse::<u32>(), self.ceiling) {
+            (Ok(limit), Some(ceiling)) if limit > ceiling => {
+                log::warn!(
+                    "limit={} exceeds max_rows_ceiling; clamping to {} (use unlimited() for full scans)",
+                    limit,
+                    ceiling
+                );
+                Some(ceiling.to_string())
+            }
+            _ => Some(explicit.to_string()),
+        }
+    }
 }
 
 impl PostgrestClient {
@@ -165,7 +434,7 @@ impl PostgrestClient {
 
         Self {
             base_url: base_url.to_string(),
-            api_key: api_key.to_string(),
+            api_key: api_key.into(),
             table: table.to_string(),
             http_client,
             headers,
@@ -173,9 +442,31 @@ impl PostgrestClient {
             path: None,
             is_rpc: false,
             rpc_params: None,
+            preferences: Preferences::default(),
+            diagnostic_deserialization: cfg!(debug_assertions),
+            row_caps: RowCaps::default(),
+            column_case: ColumnCase::default(),
+            accept_profile: None,
+            token_refresher: None,
+            send_gate: None,
+            replica_url: None,
+            read_preference: ReadPreference::Primary,
+            column_filter: ColumnFilter::default(),
         }
     }
 
+    /// エラー本文から API キーと送信したトークン (`Authorization` ヘッダー) を取り除く
+    fn scrub_secrets(&self, text: &str) -> String {
+        let token = self
+            .headers
+            .get(reqwest::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.trim_start_matches("Bearer ").trim());
+        let mut secrets = vec![self.api_key.expose().as_str()];
+        secrets.extend(token);
+        supabase_rust_core::scrub(text, &secrets)
+    }
+
     /// RPCリクエストを作成
     pub fn rpc(
         base_url: &str,
@@ -190,7 +481,7 @@ impl PostgrestClient {
 
         Self {
             base_url: base_url.to_string(),
-            api_key: api_key.to_string(),
+            api_key: api_key.into(),
             table: function_name.to_string(),
             http_client,
             headers,
@@ -198,9 +489,197 @@ impl PostgrestClient {
             path: None,
             is_rpc: true,
             rpc_params: Some(params),
+            preferences: Preferences::default(),
+            diagnostic_deserialization: cfg!(debug_assertions),
+            row_caps: RowCaps::default(),
+            column_case: ColumnCase::default(),
+            accept_profile: None,
+            token_refresher: None,
+            send_gate: None,
+            replica_url: None,
+            read_preference: ReadPreference::Primary,
+            column_filter: ColumnFilter::default(),
+        }
+    }
+
+    /// 型付きパラメータで RPC クライアントを作成
+    pub fn rpc_typed<P: Serialize>(
+        base_url: &str,
+        api_key: &str,
+        function_name: &str,
+        params: &P,
+        http_client: Client,
+    ) -> Result<Self, PostgrestError> {
+        let params = serde_json::to_value(params)?;
+        Ok(Self::rpc(
+            base_url,
+            api_key,
+            function_name,
+            params,
+            http_client,
+        ))
+    }
+
+    /// レスポンスを行ごとにデシリアライズし、失敗した行をエラーに含める
+    ///
+    /// 失敗時は `RowDeserializationError` になる (デバッグビルドではデフォルトで有効)。
+    /// 一度 `serde_json::Value` を経由するため、大きなレスポンスでは変換コストが増える。
+    pub fn diagnostic_deserialization(mut self, enabled: bool) -> Self {
+        self.diagnostic_deserialization = enabled;
+        self
+    }
+
+    /// `limit()` を指定しなかった読み取りクエリに `limit=n` を付ける
+    pub fn default_max_rows(mut self, rows: u32) -> Self {
+        self.row_caps.default_max_rows = Some(rows);
+        self
+    }
+
+    /// 読み取りクエリの件数の上限
+    ///
+    /// これを超える `limit()` / `page()` は上限に切り詰め、警告をログに出す。
+    /// `default_max_rows` がなくても、limit のないクエリにはこの値が付く。
+    pub fn max_rows_ceiling(mut self, rows: u32) -> Self {
+        self.row_caps.ceiling = Some(rows);
+        self
+    }
+
+    /// `default_max_rows` と `max_rows_ceiling` を無視する (意図的な全件取得用)
+    pub fn unlimited(mut self) -> Self {
+        self.row_caps.unlimited = true;
+        self
+    }
+
+    /// フィルター・並び替え・`select_columns` のカラム名の変換方法 (既定は `ColumnCase::AsIs`)
+    ///
+    /// `select()` に渡した文字列はそのまま送信される。
+    pub fn column_case(mut self, case: ColumnCase) -> Self {
+        self.column_case = case;
+        self
+    }
+
+    /// クエリビルダーの状態を [`QueryDescriptor`] として取り出す
+    ///
+    /// テーブル・select・フィルター・並び順・limit/offset・Prefer の設定を含み、ヘッダー・API キー・
+    /// トークン・ベース URL は含まない。RPC クライアントやトランザクション内のクライアントではエラー。
+    pub fn to_descriptor(&self) -> Result<QueryDescriptor, PostgrestError> {
+        self.ensure_table("to_descriptor")?;
+        QueryDescriptor::capture(&self.table, &self.query_params, &self.preferences)
+    }
+
+    /// [`QueryDescriptor`] からクエリを復元する
+    ///
+    /// 形式のバージョンが新しすぎる記述子、知らない演算子を含むフィルター、同じキーのフィルターが
+    /// 複数ある記述子は `InvalidParameters` になる。カラム名は記述子のものをそのまま使う
+    /// (`column_case` は適用しない)。
+    pub fn from_descriptor(
+        descriptor: &QueryDescriptor,
+        base_url: &str,
+        api_key: &str,
+        http_client: Client,
+    ) -> Result<Self, PostgrestError> {
+        let query_params = descriptor.query_params()?;
+        let mut client = Self::new(base_url, api_key, &descriptor.table, http_client);
+        client.query_params = query_params;
+        client.preferences = descriptor.preferences();
+        Ok(client)
+    }
+
+    /// `execute()` / `execute_paged()` で送る `Accept` ヘッダー (ベンダー固有のメディアタイプなど)
+    ///
+    /// 例: `application/vnd.pgrst.array+json;nulls=stripped`。レスポンスは従来どおり行の配列として
+    /// 読み取り、サーバーが別の `Content-Type` を返した場合は `UnexpectedContentType` エラーになる。
+    /// `export_csv()` や `get_by_key()` など独自の `Accept` を送るメソッドには影響しない。
+    pub fn accept_profile(mut self, media_type: &str) -> Self {
+        self.accept_profile = Some(media_type.to_string());
+        self
+    }
+
+    /// `401` / `JWT expired` の応答でトークンを再取得し、リクエストを 1 回だけ再送する
+    ///
+    /// 対象は `execute()` 系・書き込み・RPC・CSV エクスポート。再送も失敗した場合は
+    /// 最初のエラーを返す。それ以外の 401 は再取得せずにそのまま返す。
+    pub fn with_token_refresher(mut self, refresher: Arc<dyn TokenRefresher>) -> Self {
+        self.token_refresher = Some(refresher);
+        self
+    }
+
+    /// 送信前にリクエストを通すかを決めるゲート (統合クライアントのサーキットブレーカーなど)
+    ///
+    /// 対象は `execute()` 系・書き込み・RPC・CSV エクスポート・`refresh_materialized_view`・
+    /// `begin_transaction`。ゲートが止めたリクエストは送信せず `PostgrestError::Rejected` を返す。
+    /// 開始したトランザクション内のリクエストはゲートを通さない。
+    pub fn with_send_gate(mut self, gate: Arc<dyn SendGate>) -> Self {
+        self.send_gate = Some(gate);
+        self
+    }
+
+    /// 読み取りレプリカのベース URL (プライマリと同じく `/rest/v1` はクライアントが付ける)
+    ///
+    /// 設定しただけではルーティングは変わらない。`route_to` / `use_read_replica` と組み合わせる。
+    pub fn with_read_replica(mut self, replica_url: &str) -> Self {
+        self.replica_url = Some(replica_url.to_string());
+        self
+    }
+
+    /// 読み取りクエリの送信先を選ぶ
+    ///
+    /// 対象は `execute()` 系・`export_csv()`・`call_rpc_get()`。挿入・更新・削除・`call_rpc()`・
+    /// トランザクションは常にプライマリに送り、`ReadPreference::ReplicaOnly` では
+    /// `WriteOnReadReplica` エラーにする。レプリカの URL が未設定なら読み取りもプライマリに送る。
+    pub fn route_to(mut self, preference: ReadPreference) -> Self {
+        self.read_preference = preference;
+        self
+    }
+
+    /// `route_to(ReadPreference::Replica)` の短縮形
+    pub fn use_read_replica(self) -> Self {
+        self.route_to(ReadPreference::Replica)
+    }
+
+    // 書き込みの前に呼ぶ (`ReplicaOnly` のクライアントでは書き込みを拒否する)
+    fn ensure_writable(&self, operation: &str) -> Result<(), PostgrestError> {
+        if self.read_preference == ReadPreference::ReplicaOnly {
+            return Err(PostgrestError::WriteOnReadReplica {
+                operation: operation.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    // 読み取りの送信先のベース URL
+    fn read_base_url(&self) -> &str {
+        match &self.replica_url {
+            Some(replica_url) if self.read_preference.reads_from_replica() => replica_url,
+            _ => &self.base_url,
         }
     }
 
+    // `column_case` を適用し、必要ならクォートする
+    fn column_name(&self, column: &str) -> String {
+        ident::quote_column(&self.column_case.apply(column))
+    }
+
+    // `{base_url}/rest/v1/{segments}` (テーブル名・関数名はパーセントエンコードする)
+    fn resource_url(&self, segments: &[&str]) -> Result<Url, PostgrestError> {
+        Self::resource_url_on(&self.base_url, segments)
+    }
+
+    // 読み取り用の `resource_url` (`route_to` の設定によってはレプリカの URL)
+    fn read_resource_url(&self, segments: &[&str]) -> Result<Url, PostgrestError> {
+        Self::resource_url_on(self.read_base_url(), segments)
+    }
+
+    fn resource_url_on(base_url: &str, segments: &[&str]) -> Result<Url, PostgrestError> {
+        let mut url = Url::parse(&format!("{}/rest/v1", base_url.trim_end_matches('/')))?;
+        url.path_segments_mut()
+            .map_err(|_| {
+                PostgrestError::InvalidParameters(format!("Invalid base URL: {}", base_url))
+            })?
+            .extend(segments);
+        Ok(url)
+    }
+
     /// ヘッダーを追加
     pub fn with_header(mut self, key: &str, value: &str) -> Result<Self, PostgrestError> {
         let header_value = HeaderValue::from_str(value).map_err(|_| {
@@ -216,6 +695,15 @@ impl PostgrestClient {
         Ok(self)
     }
 
+    /// すべてのリクエストに付けるヘッダー (マルチテナント構成の `x-tenant-id` など)
+    ///
+    /// `with_header` と同じく同名のヘッダーは置き換える。トランザクションと、
+    /// トランザクション内の `from()` のクライアントにも引き継がれる。
+    pub fn with_default_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
     /// 認証トークンを設定
     pub fn with_auth(self, token: &str) -> Result<Self, PostgrestError> {
         self.with_header("Authorization", &format!("Bearer {}", token))
@@ -228,6 +716,35 @@ impl PostgrestClient {
         self
     }
 
+    /// `T` のフィールド名を `column_case` で変換して select に並べる
+    ///
+    /// フィールド名が取れない型 (構造体でない型や `#[serde(flatten)]` を含む構造体) では `*` になる。
+    pub fn select_columns<T: SelectColumns>(self) -> Self {
+        let columns = T::columns();
+        if columns.is_empty() {
+            log::warn!(
+                "select_columns::<{}>() found no field names; selecting *",
+                std::any::type_name::<T>()
+            );
+            return self.select("*");
+        }
+        let select = columns
+            .iter()
+            .map(|column| self.column_name(column))
+            .collect::<Vec<_>>()
+            .join(",");
+        self.select(&select)
+    }
+
+    /// 集約関数を含む列を選択 (PostgREST 12 以降)
+    ///
+    /// グループ化は集約していない列 ([`Agg::Col`]) で暗黙に行われる。
+    /// 例: `&[Agg::Col("category"), Agg::Sum("price").alias("total")]` は
+    /// `select=category,total:price.sum()` になる。
+    pub fn select_aggregate(self, aggregates: &[Agg<'_>]) -> Self {
+        self.select(&aggregate::render(aggregates))
+    }
+
     /// 結合クエリ: 参照テーブルとの内部結合
     pub fn inner_join(mut self, foreign_table: &str, column: &str, foreign_column: &str) -> Self {
         // 選択列にリレーションを追加
@@ -236,6 +753,11 @@ impl PostgrestClient {
             .get("select")
             .cloned()
             .unwrap_or_else(|| "*".to_string());
+        let (foreign_table, column, foreign_column) = (
+            quote_ident(foreign_table),
+            ident::quote_column(column),
+            ident::quote_column(foreign_column),
+        );
         let new_select = if current_select == "*" {
             format!("*,{}!inner({})", foreign_table, foreign_column)
         } else {
@@ -257,6 +779,11 @@ impl PostgrestClient {
             .get("select")
             .cloned()
             .unwrap_or_else(|| "*".to_string());
+        let (foreign_table, column, foreign_column) = (
+            quote_ident(foreign_table),
+            ident::quote_column(column),
+            ident::quote_column(foreign_column),
+        );
         let new_select = if current_select == "*" {
             format!("*,{}!left({})", foreign_table, foreign_column)
         } else {
@@ -284,6 +811,7 @@ impl PostgrestClient {
             .cloned()
             .unwrap_or_else(|| "*".to_string());
         let columns_str = columns.unwrap_or("*");
+        let foreign_table = quote_ident(foreign_table);
         let new_select = if current_select == "*" {
             format!("*,{}({})", foreign_table, columns_str)
         } else {
@@ -302,6 +830,10 @@ impl PostgrestClient {
             .get("select")
             .cloned()
             .unwrap_or_else(|| "*".to_string());
+        let (foreign_table, foreign_column) = (
+            quote_ident(foreign_table),
+            ident::quote_column(foreign_column),
+        );
         let new_select = if current_select == "*" {
             format!("*,{}!fk({})", foreign_table, foreign_column)
         } else {
@@ -318,64 +850,203 @@ impl PostgrestClient {
     /// 等価フィルター
     pub fn eq(mut self, column: &str, value: &str) -> Self {
         self.query_params
-            .insert(column.to_string(), format!("eq.{}", value));
+            .insert(self.column_name(column), format!("eq.{}", value));
+        self
+    }
+
+    /// 不等価フィルター
+    pub fn neq(mut self, column: &str, value: &str) -> Self {
+        self.query_params
+            .insert(self.column_name(column), format!("neq.{}", value));
+        self
+    }
+
+    /// 複数カラムの等価フィルターをまとめて適用 (supabase-js の `.match()`、複合主キー向け)
+    pub fn match_keys(mut self, pairs: &[(&str, &str)]) -> Self {
+        for (column, value) in pairs {
+            self.query_params
+                .insert(self.column_name(column), format!("eq.{}", value));
+        }
+        self
+    }
+
+    // 複合キーを検証 (空のキーや値で全行が対象になるのを防ぐ)
+    fn check_keys(&self, operation: &str, pairs: &[(&str, &str)]) -> Result<(), PostgrestError> {
+        self.ensure_table(operation)?;
+        if pairs.is_empty() {
+            return Err(PostgrestError::InvalidParameters(format!(
+                "{}() requires at least one key column",
+                operation
+            )));
+        }
+        for (index, (column, value)) in pairs.iter().enumerate() {
+            if column.is_empty() || value.is_empty() {
+                return Err(PostgrestError::InvalidParameters(format!(
+                    "{}() key column {:?} has an empty name or value",
+                    operation, column
+                )));
+            }
+            if pairs[..index].iter().any(|(other, _)| other == column) {
+                return Err(PostgrestError::InvalidParameters(format!(
+                    "{}() key column {:?} is specified more than once",
+                    operation, column
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn filter_val(mut self, column: &str, operator: &str, value: FilterValue) -> Self {
+        self.query_params
+            .insert(self.column_name(column), value.to_filter(operator));
         self
     }
 
+    /// 型付きの等価フィルター (`None` は `is.null`)
+    pub fn eq_val<T: Into<FilterValue>>(self, column: &str, value: T) -> Self {
+        self.filter_val(column, "eq", value.into())
+    }
+
+    /// 型付きの不等価フィルター (`None` は `not.is.null`)
+    pub fn neq_val<T: Into<FilterValue>>(self, column: &str, value: T) -> Self {
+        self.filter_val(column, "neq", value.into())
+    }
+
+    /// 型付きのより大きいフィルター (`None` は `is.null`)
+    pub fn gt_val<T: Into<FilterValue>>(self, column: &str, value: T) -> Self {
+        self.filter_val(column, "gt", value.into())
+    }
+
+    /// 型付きの以上フィルター (`None` は `is.null`)
+    pub fn gte_val<T: Into<FilterValue>>(self, column: &str, value: T) -> Self {
+        self.filter_val(column, "gte", value.into())
+    }
+
+    /// 型付きのより小さいフィルター (`None` は `is.null`)
+    pub fn lt_val<T: Into<FilterValue>>(self, column: &str, value: T) -> Self {
+        self.filter_val(column, "lt", value.into())
+    }
+
+    /// 型付きの以下フィルター (`None` は `is.null`)
+    pub fn lte_val<T: Into<FilterValue>>(self, column: &str, value: T) -> Self {
+        self.filter_val(column, "lte", value.into())
+    }
+
     /// より大きいフィルター
     pub fn gt(mut self, column: &str, value: &str) -> Self {
         self.query_params
-            .insert(column.to_string(), format!("gt.{}", value));
+            .insert(self.column_name(column), format!("gt.{}", value));
         self
     }
 
     /// 以上フィルター
     pub fn gte(mut self, column: &str, value: &str) -> Self {
         self.query_params
-            .insert(column.to_string(), format!("gte.{}", value));
+            .insert(self.column_name(column), format!("gte.{}", value));
         self
     }
 
     /// より小さいフィルター
     pub fn lt(mut self, column: &str, value: &str) -> Self {
         self.query_params
-            .insert(column.to_string(), format!("lt.{}", value));
+            .insert(self.column_name(column), format!("lt.{}", value));
         self
     }
 
     /// 以下フィルター
     pub fn lte(mut self, column: &str, value: &str) -> Self {
         self.query_params
-            .insert(column.to_string(), format!("lte.{}", value));
+            .insert(self.column_name(column), format!("lte.{}", value));
         self
     }
 
     /// LIKE フィルター
     pub fn like(mut self, column: &str, pattern: &str) -> Self {
         self.query_params
-            .insert(column.to_string(), format!("like.{}", pattern));
+            .insert(self.column_name(column), format!("like.{}", pattern));
         self
     }
 
     /// ILIKE フィルター（大文字小文字を区別しない）
     pub fn ilike(mut self, column: &str, pattern: &str) -> Self {
         self.query_params
-            .insert(column.to_string(), format!("ilike.{}", pattern));
+            .insert(self.column_name(column), format!("ilike.{}", pattern));
         self
     }
 
+    /// `input` を含む行を LIKE で検索 (`input` のワイルドカードはエスケープされる)
+    pub fn like_contains(self, column: &str, input: &str) -> Self {
+        let pattern = format!("%{}%", escape_like_pattern(input));
+        self.like(column, &pattern)
+    }
+
+    /// `input` で始まる行を LIKE で検索 (`input` のワイルドカードはエスケープされる)
+    pub fn like_starts_with(self, column: &str, input: &str) -> Self {
+        let pattern = format!("{}%", escape_like_pattern(input));
+        self.like(column, &pattern)
+    }
+
+    /// `input` で終わる行を LIKE で検索 (`input` のワイルドカードはエスケープされる)
+    pub fn like_ends_with(self, column: &str, input: &str) -> Self {
+        let pattern = format!("%{}", escape_like_pattern(input));
+        self.like(column, &pattern)
+    }
+
+    /// `input` を含む行を ILIKE で検索 (`input` のワイルドカードはエスケープされる)
+    pub fn ilike_contains(self, column: &str, input: &str) -> Self {
+        let pattern = format!("%{}%", escape_like_pattern(input));
+        self.ilike(column, &pattern)
+    }
+
+    /// `input` で始まる行を ILIKE で検索 (`input` のワイルドカードはエスケープされる)
+    pub fn ilike_starts_with(self, column: &str, input: &str) -> Self {
+        let pattern = format!("{}%", escape_like_pattern(input));
+        self.ilike(column, &pattern)
+    }
+
+    /// `input` で終わる行を ILIKE で検索 (`input` のワイルドカードはエスケープされる)
+    pub fn ilike_ends_with(self, column: &str, input: &str) -> Self {
+        let pattern = format!("%{}", escape_like_pattern(input));
+        self.ilike(column, &pattern)
+    }
+
     /// IN フィルター
     pub fn in_list(mut self, column: &str, values: &[&str]) -> Self {
         let value_list = values.join(",");
         self.query_params
-            .insert(column.to_string(), format!("in.({})", value_list));
+            .insert(self.column_name(column), format!("in.({})", value_list));
         self
     }
 
-    /// NOT フィルター
-    pub fn not(mut self, column: &str, operator_with_value: &str) -> Self {
-        self.query_params
-            .insert(column.to_string(), format!("not.{}", operator_with_value));
+    /// NOT フィルター (`column=not.operator.value`)
+    ///
+    /// グループの否定は [`Filter::not`] と [`filter`](Self::filter) を使う。
+    pub fn not<T: Into<FilterValue>>(
+        self,
+        column: &str,
+        operator: FilterOperator,
+        value: T,
+    ) -> Self {
+        self.filter(Filter::not(Filter::compare(column, operator, value)))
+    }
+
+    /// [`Filter`] の木を追加する
+    ///
+    /// 同じキー (カラム名や `or` など) が既にある場合は上書きせず、`and=(...)` にまとめる。
+    pub fn filter(mut self, filter: Filter) -> Self {
+        let column_case = self.column_case;
+        let column_name = move |column: &str| ident::quote_column(&column_case.apply(column));
+        let (key, value) = filter.to_query(&column_name);
+        if let Entry::Vacant(entry) = self.query_params.entry(key) {
+            entry.insert(value);
+            return self;
+        }
+        let nested = filter.to_nested(&column_name);
+        let and = match self.query_params.get("and") {
+            Some(group) => format!("{},{})", group.strip_suffix(')').unwrap_or(group), nested),
+            None => format!("({})", nested),
+        };
+        self.query_params.insert("and".to_string(), and);
         self
     }
 
@@ -384,7 +1055,7 @@ impl PostgrestClient {
     pub fn contains(mut self, column: &str, value: &Value) -> Result<Self, PostgrestError> {
         let value_str = serde_json::to_string(value)?;
         self.query_params
-            .insert(column.to_string(), format!("cs.{}", value_str));
+            .insert(self.column_name(column), format!("cs.{}", value_str));
         Ok(self)
     }
 
@@ -393,7 +1064,7 @@ impl PostgrestClient {
     pub fn contained_by(mut self, column: &str, value: &Value) -> Result<Self, PostgrestError> {
         let value_str = serde_json::to_string(value)?;
         self.query_params
-            .insert(column.to_string(), format!("cd.{}", value_str));
+            .insert(self.column_name(column), format!("cd.{}", value_str));
         Ok(self)
     }
 
@@ -403,8 +1074,10 @@ impl PostgrestClient {
             SortOrder::Ascending => "asc",
             SortOrder::Descending => "desc",
         };
-        self.query_params
-            .insert("order".to_string(), format!("{}.{}", column, order_str));
+        self.query_params.insert(
+            "order".to_string(),
+            format!("{}.{}", self.column_name(column), order_str),
+        );
         self
     }
 
@@ -422,6 +1095,12 @@ impl PostgrestClient {
         self
     }
 
+    /// ページを指定 (`limit`/`offset` に変換)
+    pub fn page(self, page: Page) -> Self {
+        self.limit(page.size.min(i32::MAX as u32) as i32)
+            .offset(page.offset().min(i32::MAX as u64) as i32)
+    }
+
     /// 全文検索
     pub fn text_search(mut self, column: &str, query: &str, config: Option<&str>) -> Self {
         let search_param = match config {
@@ -429,7 +1108,8 @@ impl PostgrestClient {
             None => format!("fts.{}", query),
         };
 
-        self.query_params.insert(column.to_string(), search_param);
+        self.query_params
+            .insert(self.column_name(column), search_param);
         self
     }
 
@@ -443,24 +1123,98 @@ impl PostgrestClient {
         unit: &str,
     ) -> Self {
         self.query_params.insert(
-            column.to_string(),
+            self.column_name(column),
             format!("st_dwithin.POINT({} {}).{}.{}", lng, lat, distance, unit),
         );
         self
     }
 
     /// グループ化
+    ///
+    /// PostgREST には `group` パラメーターがないため、`columns` のうち select にない列を
+    /// select に加える (集約していない列でグループ化される)。
+    #[deprecated(
+        note = "PostgREST groups by the non-aggregated columns in select; use select_aggregate with Agg::Col"
+    )]
     pub fn group_by(mut self, columns: &str) -> Self {
+        let mut select: Vec<String> = self
+            .query_params
+            .get("select")
+            .map(|select| select.split(',').map(|c| c.trim().to_string()).collect())
+            .unwrap_or_default();
+        for column in columns.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+            if !select.iter().any(|selected| selected == column) {
+                select.push(column.to_string());
+            }
+        }
         self.query_params
-            .insert("group".to_string(), columns.to_string());
+            .insert("select".to_string(), select.join(","));
         self
     }
 
     /// 行数カウント
-    pub fn count(mut self, exact: bool) -> Self {
-        let count_method = if exact { "exact" } else { "planned" };
-        self.query_params
-            .insert("count".to_string(), count_method.to_string());
+    pub fn count(self, exact: bool) -> Self {
+        self.count_method(if exact {
+            CountMethod::Exact
+        } else {
+            CountMethod::Planned
+        })
+    }
+
+    /// 行数の数え方を指定 (`Prefer: count=...`)
+    pub fn count_method(mut self, method: CountMethod) -> Self {
+        self.preferences.count = Some(method);
+        self
+    }
+
+    /// 書き込み結果の返し方を指定 (`Prefer: return=...`、既定は `representation`)
+    pub fn returning(mut self, returning: ReturnPreference) -> Self {
+        self.preferences.returning = Some(returning);
+        self
+    }
+
+    /// insert・upsert の各行から `columns` のキーを取り除く
+    ///
+    /// identity カラムや生成カラムを含む構造体をそのまま渡すために使う。行をシリアライズした後に
+    /// 適用し、`column_case` を指定している場合は変換後の名前にも一致させる。行が JSON
+    /// オブジェクトでない場合は送信せずに `RowNotAnObject` を返す。複数回呼ぶと追加される。
+    pub fn omit_columns(mut self, columns: &[&str]) -> Self {
+        self.column_filter
+            .omit
+            .extend(columns.iter().map(|column| column.to_string()));
+        self
+    }
+
+    /// insert・upsert の各行に `columns` のキーだけを残す (`omit_columns` を参照)
+    ///
+    /// `omit_columns` と併用すると、残したカラムからさらに取り除く。
+    pub fn only_columns(mut self, columns: &[&str]) -> Self {
+        self.column_filter
+            .only
+            .get_or_insert_with(Vec::new)
+            .extend(columns.iter().map(|column| column.to_string()));
+        self
+    }
+
+    /// 書き込みを実行後にロールバックするクライアントに変換 (`Prefer: tx=rollback`)
+    ///
+    /// 影響を受ける行を確認したり、RLS ポリシーを検証したりするのに使う。
+    /// サーバー側で `db-tx-end = commit-allow-override` (または `rollback-allow-override`) が必要。
+    pub fn dry_run(mut self) -> DryRunClient {
+        self.preferences.rollback = true;
+        self.preferences.returning = Some(ReturnPreference::Representation);
+        DryRunClient::new(self)
+    }
+
+    /// 不正なフィルター値などの扱いを指定 (`Prefer: handling=...`)
+    pub fn handling(mut self, handling: Handling) -> Self {
+        self.preferences.handling = Some(handling);
+        self
+    }
+
+    /// timestamptz を返すタイムゾーンを指定 (`Prefer: timezone=...`)
+    pub fn timezone(mut self, tz: &str) -> Self {
+        self.preferences.timezone = Some(tz.to_string());
         self
     }
 
@@ -480,9 +1234,84 @@ impl PostgrestClient {
         self
     }
 
+    /// 省略されたカラムにデフォルト値を適用 (`Prefer: missing=default`)
+    pub fn missing_default(mut self, enabled: bool) -> Self {
+        if self.is_rpc {
+            log::warn!(
+                "missing_default() has no effect on RPC calls (function: {})",
+                self.table
+            );
+            return self;
+        }
+        self.preferences.missing_default = enabled;
+        self
+    }
+
+    // テーブル向けの操作が RPC クライアントで呼ばれた場合はエラーにする
+    fn ensure_table(&self, operation: &str) -> Result<(), PostgrestError> {
+        if self.is_rpc {
+            return Err(PostgrestError::InvalidParameters(format!(
+                "{}() cannot be used on an RPC client (function: {}). Use call_rpc() or call_rpc_get().",
+                operation, self.table
+            )));
+        }
+        Ok(())
+    }
+
+    // リクエストごとの既定値を加えた Prefer ヘッダーを含むヘッダーを返す
+    fn request_headers(
+        &self,
+        defaults: impl FnOnce(&mut Preferences),
+    ) -> Result<HeaderMap, PostgrestError> {
+        let mut preferences = self.preferences.clone();
+        defaults(&mut preferences);
+        let mut headers = self.headers.clone();
+        preferences.apply(&mut headers)?;
+        Ok(headers)
+    }
+
+    // 書き込み系リクエストのヘッダー (既定で書き込んだ行を返す)
+    fn write_headers(
+        &self,
+        defaults: impl FnOnce(&mut Preferences),
+    ) -> Result<HeaderMap, PostgrestError> {
+        self.request_headers(|preferences| {
+            preferences
+                .returning
+                .get_or_insert(ReturnPreference::Representation);
+            defaults(preferences);
+        })
+    }
+
     /// CSVとしてデータをエクスポート
     pub async fn export_csv(&self) -> Result<String, PostgrestError> {
-        let mut url = self.build_url()?;
+        self.ensure_table("export_csv")?;
+        let response = self.fetch_csv().await?;
+        let csv_data = response.text().await?;
+
+        Ok(csv_data)
+    }
+
+    /// CSVとしてデータをエクスポートし、区切り文字・改行コード・BOM を変換する
+    ///
+    /// 受信しながらフィールド単位で変換し、区切り文字や `"`・改行を含むフィールドは `"` で囲み直す。
+    /// 区切り文字に `"` や改行を指定した場合は `InvalidParameters` エラーになる。
+    pub async fn export_csv_with_options(
+        &self,
+        options: CsvExportOptions,
+    ) -> Result<String, PostgrestError> {
+        self.ensure_table("export_csv_with_options")?;
+        let mut transcoder = csv::CsvTranscoder::new(&options)?;
+        let mut response = self.fetch_csv().await?;
+        while let Some(chunk) = response.chunk().await? {
+            transcoder.push(&chunk);
+        }
+        Ok(transcoder.finish())
+    }
+
+    // `text/csv` で SELECT リクエストを送信し、エラーレスポンスを変換する
+    async fn fetch_csv(&self) -> Result<reqwest::Response, PostgrestError> {
+        let mut url = self.build_read_url()?;
 
         // CSVフォーマットを指定
         if url.contains('?') {
@@ -492,17 +1321,22 @@ impl PostgrestClient {
         }
         url.push_str("accept=text/csv");
 
-        let mut headers = self.headers.clone();
+        let mut headers = self.request_headers(|_| {})?;
         headers.insert(
             reqwest::header::ACCEPT,
             reqwest::header::HeaderValue::from_static("text/csv"),
         );
 
-        let response = self.http_client.get(url).headers(headers).send().await?;
+        let response = self
+            .send_with_refresh(headers, |headers| {
+                self.http_client.get(&url).headers(headers)
+            })
+            .await?;
 
         let status = response.status();
         if !status.is_success() {
-            let error_text = response.text().await?;
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(&response.text().await?);
             let details = serde_json::from_str::<PostgrestApiErrorDetails>(&error_text)
                 .unwrap_or_else(|_| PostgrestApiErrorDetails {
                     code: None,
@@ -510,70 +1344,548 @@ impl PostgrestClient {
                     details: None,
                     hint: None,
                 });
-            return Err(PostgrestError::ApiError { details, status });
+            return Err(PostgrestError::ApiError {
+                details,
+                status,
+                request_ids,
+            });
         }
 
-        let csv_data = response.text().await?;
-
-        Ok(csv_data)
+        Ok(response)
     }
 
     /// データを取得
     pub async fn execute<T: for<'de> Deserialize<'de>>(&self) -> Result<Vec<T>, PostgrestError> {
-        let url = self.build_url()?;
+        self.ensure_table("execute")?;
+        let response = self
+            .fetch_rows(self.profile_headers(self.request_headers(|_| {})?)?)
+            .await?;
+        self.ensure_profile_content_type(&response)?;
+
+        let body = response.bytes().await?;
+        diagnostics::deserialize_rows(&body, self.diagnostic_deserialization)
+    }
+
+    // `accept_profile` を指定していればリクエストごとのヘッダーに `Accept` を入れる
+    fn profile_headers(&self, mut headers: HeaderMap) -> Result<HeaderMap, PostgrestError> {
+        if let Some(media_type) = &self.accept_profile {
+            let value = HeaderValue::from_str(media_type).map_err(|_| {
+                PostgrestError::InvalidParameters(format!("Invalid Accept header: {}", media_type))
+            })?;
+            headers.insert(reqwest::header::ACCEPT, value);
+        }
+        Ok(headers)
+    }
+
+    // `accept_profile` と異なる `Content-Type` が返った場合はエラー
+    fn ensure_profile_content_type(
+        &self,
+        response: &reqwest::Response,
+    ) -> Result<(), PostgrestError> {
+        let Some(expected) = &self.accept_profile else {
+            return Ok(());
+        };
+        let actual = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        if media_type_matches(expected, actual) {
+            Ok(())
+        } else {
+            Err(PostgrestError::UnexpectedContentType {
+                expected: expected.clone(),
+                actual: actual.to_string(),
+                request_ids: Box::new(RequestIds::from_response(response)),
+            })
+        }
+    }
 
+    /// `Accept` ヘッダーを指定してデータを取得し、レスポンスをそのまま返す
+    ///
+    /// JSON 以外の形式 (`text/xml` など) 用。エラーレスポンスは `execute()` と同様に変換する。
+    pub async fn execute_with_accept(
+        &self,
+        accept: &str,
+    ) -> Result<(reqwest::StatusCode, Bytes, HeaderMap), PostgrestError> {
+        self.ensure_table("execute_with_accept")?;
+        let response = self.fetch_with_accept(accept).await?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?;
+        Ok((status, body, headers))
+    }
+
+    // `Accept` ヘッダーを差し替えて取得する (エラーレスポンスは変換済み)
+    async fn fetch_with_accept(&self, accept: &str) -> Result<reqwest::Response, PostgrestError> {
+        let mut headers = self.request_headers(|_| {})?;
+        headers.insert(
+            reqwest::header::ACCEPT,
+            HeaderValue::from_str(accept).map_err(|_| {
+                PostgrestError::InvalidParameters(format!("Invalid Accept header: {}", accept))
+            })?,
+        );
+        self.fetch_rows(headers).await
+    }
+
+    /// データを GeoJSON の FeatureCollection として取得
+    ///
+    /// サーバーが `application/geo+json` 以外 (geometry カラムがない場合の JSON 配列など) を
+    /// 返した場合は `UnexpectedContentType` エラーになる。
+    pub async fn execute_geojson(&self) -> Result<FeatureCollection, PostgrestError> {
+        self.ensure_table("execute_geojson")?;
         let response = self
-            .http_client
-            .get(&url)
-            .headers(self.headers.clone())
-            .send()
-            .await
-            .map_err(PostgrestError::NetworkError)?;
+            .fetch_with_accept(geojson::GEOJSON_CONTENT_TYPE)
+            .await?;
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let mime_type = content_type.split(';').next().unwrap_or_default().trim();
+        if !mime_type.eq_ignore_ascii_case(geojson::GEOJSON_CONTENT_TYPE) {
+            return Err(PostgrestError::UnexpectedContentType {
+                expected: geojson::GEOJSON_CONTENT_TYPE.to_string(),
+                actual: content_type.to_string(),
+                request_ids: Box::new(RequestIds::from_response(&response)),
+            });
+        }
+
+        let body = response.bytes().await?;
+        serde_json::from_slice(&body)
+            .map_err(|e| PostgrestError::DeserializationError(e.to_string()))
+    }
+
+    /// ページ単位でデータを取得
+    ///
+    /// `page()` (または `limit()`/`offset()`) で指定したページを取得し、
+    /// `Content-Range` ヘッダーから全件数を読み取る。
+    pub async fn execute_paged<T: for<'de> Deserialize<'de>>(
+        &self,
+    ) -> Result<Paged<T>, PostgrestError> {
+        self.ensure_table("execute_paged")?;
+        let limit = self
+            .read_limit()
+            .and_then(|limit| limit.parse::<u32>().ok())
+            .ok_or_else(|| {
+                PostgrestError::InvalidParameters(
+                    "execute_paged() requires page() or limit()".to_string(),
+                )
+            })?;
+        let offset = self
+            .query_params
+            .get("offset")
+            .and_then(|offset| offset.parse::<u64>().ok())
+            .unwrap_or(0);
+        let page = Page::from_offset(offset, limit);
+
+        let headers = self.profile_headers(self.request_headers(|preferences| {
+            preferences.count.get_or_insert(CountMethod::Exact);
+        })?)?;
+        let response = self.fetch_rows(headers).await?;
+        self.ensure_profile_content_type(&response)?;
+
+        let total = response
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(content_range_total);
+        let body = response.bytes().await?;
+        let items = diagnostics::deserialize_rows(&body, self.diagnostic_deserialization)?;
+
+        Ok(Paged::new(items, page, total))
+    }
 
+    // リクエストを送信し、`JWT expired` の 401 ならトークンを再取得して 1 回だけ再送する
+    //
+    // 再送しなかった応答はステータスにかかわらずそのまま返す。本文を読んだ 401 と
+    // 失敗した再送は最初の 401 のエラーになる。
+    async fn send_with_refresh(
+        &self,
+        headers: HeaderMap,
+        request: impl Fn(HeaderMap) -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, PostgrestError> {
+        let response = request(headers.clone())
+            .send_through(self.send_gate.as_ref())
+            .await?;
+        let Some(refresher) = &self.token_refresher else {
+            return Ok(response);
+        };
         let status = response.status();
-        if !status.is_success() {
-            let error_text = response
+        if status != reqwest::StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        let response_headers = response.headers().clone();
+        let request_ids = Box::new(RequestIds::from_response(&response));
+        let error_text = self.scrub_secrets(
+            &response
                 .text()
                 .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+                .unwrap_or_else(|_| "Failed to read error response".to_string()),
+        );
+        let expired = refresh::is_jwt_expired(&response_headers, &error_text);
+        let original = match serde_json::from_str::<PostgrestApiErrorDetails>(&error_text) {
+            Ok(details) => PostgrestError::ApiError {
+                details,
+                status,
+                request_ids,
+            },
+            Err(_) => PostgrestError::UnparsedApiError {
+                message: error_text,
+                status,
+                request_ids,
+            },
+        };
+        let Some(expired_token) = refresh::bearer_token(&headers).filter(|_| expired) else {
+            return Err(original);
+        };
+
+        let Some(token) = refresher.refresh(expired_token).await else {
+            log::debug!("JWT expired and no new token was available; not retrying");
+            return Err(original);
+        };
+        let Some(headers) = refresh::with_bearer_token(headers, &token) else {
+            return Err(original);
+        };
+        match request(headers).send_through(self.send_gate.as_ref()).await {
+            Ok(retried) if retried.status().is_success() => Ok(retried),
+            Ok(retried) => {
+                log::debug!("retry after token refresh failed with {}", retried.status());
+                Err(original)
+            }
+            Err(e) => {
+                log::debug!("retry after token refresh failed: {}", e);
+                Err(original)
+            }
+        }
+    }
+
+    // SELECT リクエストを送信し、エラーレスポンスを変換する
+    async fn fetch_rows(&self, headers: HeaderMap) -> Result<reqwest::Response, PostgrestError> {
+        let url = self.build_read_url()?;
+
+        let response = self
+            .send_with_refresh(headers, |headers| {
+                self.http_client.get(&url).headers(headers)
+            })
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
 
             // Attempt to parse specific error details
             if let Ok(details) = serde_json::from_str::<PostgrestApiErrorDetails>(&error_text) {
-                return Err(PostgrestError::ApiError { details, status });
+                return Err(PostgrestError::ApiError {
+                    details,
+                    status,
+                    request_ids,
+                });
             } else {
                 // If parsing fails, return a less specific error with the raw message
                 return Err(PostgrestError::UnparsedApiError {
                     message: error_text,
                     status,
+                    request_ids,
                 });
             }
         }
 
-        response
-            .json::<Vec<T>>()
-            .await
-            .map_err(|e| PostgrestError::DeserializationError(e.to_string()))
+        Ok(response)
     }
 
     /// データを挿入
     pub async fn insert<T: Serialize>(&self, values: T) -> Result<Value, PostgrestError> {
+        self.post_rows(values, None).await
+    }
+
+    /// データをアップサート (主キーが重複する行は更新)
+    pub async fn upsert<T: Serialize>(&self, values: T) -> Result<Value, PostgrestError> {
+        self.post_rows(values, Some("merge-duplicates")).await
+    }
+
+    /// `ON CONFLICT DO NOTHING` でアップサートし、スキップされた行のキーを報告する
+    ///
+    /// `on_conflict` のカラムで衝突を判定し (`resolution=ignore-duplicates`)、返された行と入力行を
+    /// `key_columns` で突き合わせる ([`UpsertReport`] を参照)。突き合わせに表現が必要なため
+    /// `returning()` の設定にかかわらず `return=representation` で送る。`select()` で絞る場合は
+    /// キーカラムを含めること。キーカラムがない入力行があれば送信せずに `MissingKeyColumn` を返す。
+    pub async fn upsert_report<T: Serialize>(
+        &self,
+        rows: &[T],
+        on_conflict: &[&str],
+        key_columns: &[&str],
+    ) -> Result<UpsertReport, PostgrestError> {
+        self.ensure_table("upsert_report")?;
+        self.ensure_writable("upsert_report")?;
+        if on_conflict.is_empty() {
+            return Err(PostgrestError::InvalidParameters(
+                "upsert_report needs at least one on_conflict column".to_string(),
+            ));
+        }
+        let rows = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| {
+                serde_json::to_value(row).map_err(|e| PostgrestError::RowSerializationError {
+                    index,
+                    message: e.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let inputs = upsert_report::input_keys(&rows, key_columns)?;
+        if rows.is_empty() {
+            return Ok(UpsertReport::default());
+        }
+        // キーの突き合わせには取り除く前の行を使う (identity の主キーを省いても照合できる)
+        let rows = rows
+            .into_iter()
+            .enumerate()
+            .map(|(index, row)| self.column_filter.apply_row(index, row, self.column_case))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut url = Url::parse(&self.build_url()?)?;
+        let conflict_columns = on_conflict
+            .iter()
+            .map(|column| self.column_name(column))
+            .collect::<Vec<_>>()
+            .join(",");
+        url.query_pairs_mut()
+            .append_pair("on_conflict", &conflict_columns);
+        let headers = self.request_headers(|preferences| {
+            preferences.returning = Some(ReturnPreference::Representation);
+            preferences.resolution = Some("ignore-duplicates");
+        })?;
+
+        let returned = match self.send_rows(url.as_str(), headers, &rows).await? {
+            Value::Array(returned) => returned,
+            Value::Null => Vec::new(),
+            other => {
+                return Err(PostgrestError::DeserializationError(format!(
+                    "expected an array of rows, got {}",
+                    other
+                )))
+            }
+        };
+        let skipped_keys = upsert_report::skipped_keys(inputs, &returned, key_columns)?;
+        Ok(UpsertReport {
+            inserted: returned,
+            skipped_keys,
+        })
+    }
+
+    /// 行を `batch_size` 行ずつ挿入する
+    ///
+    /// 行は `Value` に変換せずにリクエスト本文へ直接シリアライズし、本文のバッファはバッチ間で
+    /// 使い回す。大量の行でもメモリ使用量は 1 バッチ分で済む。既定では `Prefer: return=minimal`
+    /// で送信する。途中のバッチが失敗した場合は `BatchWriteFailed`、行のシリアライズに失敗した
+    /// 場合は `RowSerializationError` を返す (いずれもそれより前のバッチは書き込み済み)。
+    ///
+    /// 10 万行・`batch_size` 1000 でのピークヒープは `insert(&Vec<Value>)` の 175.1 MiB に対して
+    /// 22.9 MiB (`cargo run --release -p supabase-rust-postgrest --example insert_memory`)。
+    pub async fn insert_from_iter<T: Serialize>(
+        &self,
+        rows: impl IntoIterator<Item = T>,
+        batch_size: usize,
+    ) -> Result<InsertReport, PostgrestError> {
+        self.post_batches(futures_util::stream::iter(rows), batch_size, None)
+            .await
+    }
+
+    /// 行を `batch_size` 行ずつアップサートする (`insert_from_iter` を参照)
+    pub async fn upsert_from_iter<T: Serialize>(
+        &self,
+        rows: impl IntoIterator<Item = T>,
+        batch_size: usize,
+    ) -> Result<InsertReport, PostgrestError> {
+        self.post_batches(
+            futures_util::stream::iter(rows),
+            batch_size,
+            Some("merge-duplicates"),
+        )
+        .await
+    }
+
+    /// 非同期に届く行を `batch_size` 行ずつ挿入する (`insert_from_iter` を参照)
+    ///
+    /// バッチが揃うたびに送信するため、ストリームの読み込みと書き込みが交互に進む。
+    pub async fn insert_from_stream<T: Serialize>(
+        &self,
+        rows: impl Stream<Item = T>,
+        batch_size: usize,
+    ) -> Result<InsertReport, PostgrestError> {
+        self.post_batches(rows, batch_size, None).await
+    }
+
+    /// 非同期に届く行を `batch_size` 行ずつアップサートする (`insert_from_iter` を参照)
+    pub async fn upsert_from_stream<T: Serialize>(
+        &self,
+        rows: impl Stream<Item = T>,
+        batch_size: usize,
+    ) -> Result<InsertReport, PostgrestError> {
+        self.post_batches(rows, batch_size, Some("merge-duplicates"))
+            .await
+    }
+
+    async fn post_batches<T: Serialize>(
+        &self,
+        rows: impl Stream<Item = T>,
+        batch_size: usize,
+        resolution: Option<&'static str>,
+    ) -> Result<InsertReport, PostgrestError> {
+        let operation = if resolution.is_some() {
+            "upsert_from_iter"
+        } else {
+            "insert_from_iter"
+        };
+        self.ensure_table(operation)?;
+        self.ensure_writable(operation)?;
+        if batch_size == 0 {
+            return Err(PostgrestError::InvalidParameters(
+                "batch_size must be at least 1".to_string(),
+            ));
+        }
         let url = self.build_url()?;
+        let headers = self.request_headers(|preferences| {
+            preferences
+                .returning
+                .get_or_insert(ReturnPreference::Minimal);
+            preferences.resolution = resolution;
+        })?;
 
-        // Clone headers and add the Prefer header
-        let mut headers = self.headers.clone();
-        headers.insert(
-            HeaderName::from_static("prefer"),
-            HeaderValue::from_static("return=representation"),
+        let mut rows = std::pin::pin!(rows.enumerate());
+        let mut batch = bulk_insert::BatchBuffer::new();
+        let mut report = InsertReport::default();
+        loop {
+            let next = rows.next().await;
+            if let Some((index, row)) = &next {
+                if self.column_filter.is_empty() {
+                    batch.push(*index, row)?;
+                } else {
+                    let row = serde_json::to_value(row).map_err(|e| {
+                        PostgrestError::RowSerializationError {
+                            index: *index,
+                            message: e.to_string(),
+                        }
+                    })?;
+                    let row = self
+                        .column_filter
+                        .apply_row(*index, row, self.column_case)?;
+                    batch.push(*index, &row)?;
+                }
+                if batch.len() < batch_size {
+                    continue;
+                }
+            }
+            if batch.len() == 0 {
+                return Ok(report);
+            }
+
+            let first_row = batch.first_row();
+            let batch_rows = batch.len();
+            let body = batch.take_body();
+            self.post_batch(&url, headers.clone(), body)
+                .await
+                .map_err(|e| PostgrestError::BatchWriteFailed {
+                    first_row,
+                    written: report.rows,
+                    source: Box::new(e),
+                })?;
+            report.rows += batch_rows;
+            report.batches += 1;
+            if next.is_none() {
+                return Ok(report);
+            }
+        }
+    }
+
+    // 1 バッチ分の本文を POST する (応答の本文は読まない)
+    async fn post_batch(
+        &self,
+        url: &str,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<(), PostgrestError> {
+        let response = self
+            .send_with_refresh(headers, |headers| {
+                self.http_client
+                    .post(url)
+                    .headers(headers)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body.clone())
+            })
+            .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let request_ids = Box::new(RequestIds::from_response(&response));
+        let error_text = self.scrub_secrets(
+            &response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Failed to read error response".to_string()),
         );
+        match serde_json::from_str::<PostgrestApiErrorDetails>(&error_text) {
+            Ok(details) => Err(PostgrestError::ApiError {
+                details,
+                status,
+                request_ids,
+            }),
+            Err(_) => Err(PostgrestError::UnparsedApiError {
+                message: error_text,
+                status,
+                request_ids,
+            }),
+        }
+    }
+
+    // 行を POST する (insert / upsert 共通)
+    async fn post_rows<T: Serialize>(
+        &self,
+        values: T,
+        resolution: Option<&'static str>,
+    ) -> Result<Value, PostgrestError> {
+        let operation = if resolution.is_some() {
+            "upsert"
+        } else {
+            "insert"
+        };
+        self.ensure_table(operation)?;
+        self.ensure_writable(operation)?;
+        let url = self.build_url()?;
+        let headers = self.write_headers(|preferences| preferences.resolution = resolution)?;
+        if self.column_filter.is_empty() {
+            return self.send_rows(&url, headers, &values).await;
+        }
+        let body = self
+            .column_filter
+            .apply(serde_json::to_value(&values)?, self.column_case)?;
+        self.send_rows(&url, headers, &body).await
+    }
 
+    // 行を POST し、返された本文を JSON として返す
+    async fn send_rows<T: Serialize>(
+        &self,
+        url: &str,
+        headers: HeaderMap,
+        values: &T,
+    ) -> Result<Value, PostgrestError> {
         let response = self
-            .http_client
-            .post(&url)
-            .headers(headers) // Use modified headers
-            .json(&values)
-            .send()
-            .await
-            .map_err(PostgrestError::NetworkError)?;
+            .send_with_refresh(headers, |headers| {
+                self.http_client.post(url).headers(headers).json(values)
+            })
+            .await?;
 
         let status = response.status();
 
@@ -596,42 +1908,70 @@ impl PostgrestClient {
             }
         } else {
             // Handle non-success status codes as before
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
 
             let details_result: Result<PostgrestApiErrorDetails, _> =
                 serde_json::from_str(&error_text);
             match details_result {
-                Ok(details) => Err(PostgrestError::ApiError { details, status }),
+                Ok(details) => Err(PostgrestError::ApiError {
+                    details,
+                    status,
+                    request_ids,
+                }),
                 Err(_) => Err(PostgrestError::UnparsedApiError {
                     message: error_text,
                     status,
+                    request_ids,
                 }),
             }
         }
     }
 
+    /// 指定したカラムだけを更新するビルダーを作成
+    pub fn update_fields(&self) -> UpdateBuilder<'_> {
+        UpdateBuilder {
+            client: self,
+            fields: serde_json::Map::new(),
+            error: None,
+        }
+    }
+
+    /// `old` と `new` で値が変わったトップレベルのフィールドだけを更新
+    ///
+    /// 変更がない場合はリクエストを送信せずに空の配列を返す。
+    /// JSON カラムはネストした値の一部だけが変わっていても丸ごと置き換えられる。
+    pub async fn update_changed<T: Serialize>(
+        &self,
+        old: &T,
+        new: &T,
+    ) -> Result<Value, PostgrestError> {
+        let builder = self.update_fields().set_changed(old, new);
+        if builder.error.is_none() && builder.is_empty() {
+            return Ok(Value::Array(Vec::new()));
+        }
+        builder.execute().await
+    }
+
     /// データを更新
     pub async fn update<T: Serialize>(&self, values: T) -> Result<Value, PostgrestError> {
+        self.ensure_table("update")?;
+        self.ensure_writable("update")?;
         let url = self.build_url()?;
 
-        // Clone headers and add the Prefer header
-        let mut headers = self.headers.clone();
-        headers.insert(
-            HeaderName::from_static("prefer"),
-            HeaderValue::from_static("return=representation"),
-        );
+        // missing=default は insert / upsert のみ
+        let headers = self.write_headers(|preferences| preferences.missing_default = false)?;
 
         let response = self
-            .http_client
-            .patch(&url)
-            .headers(headers) // Use modified headers
-            .json(&values)
-            .send()
-            .await
-            .map_err(PostgrestError::NetworkError)?;
+            .send_with_refresh(headers, |headers| {
+                self.http_client.patch(&url).headers(headers).json(&values)
+            })
+            .await?;
 
         let status = response.status();
 
@@ -652,18 +1992,26 @@ impl PostgrestClient {
             }
         } else {
             // Handle non-success status codes
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
 
             let details_result: Result<PostgrestApiErrorDetails, _> =
                 serde_json::from_str(&error_text);
             match details_result {
-                Ok(details) => Err(PostgrestError::ApiError { details, status }),
+                Ok(details) => Err(PostgrestError::ApiError {
+                    details,
+                    status,
+                    request_ids,
+                }),
                 Err(_) => Err(PostgrestError::UnparsedApiError {
                     message: error_text,
                     status,
+                    request_ids,
                 }),
             }
         }
@@ -671,22 +2019,18 @@ impl PostgrestClient {
 
     /// データを削除
     pub async fn delete(&self) -> Result<Value, PostgrestError> {
+        self.ensure_table("delete")?;
+        self.ensure_writable("delete")?;
         let url = self.build_url()?;
 
-        // Clone headers and add the Prefer header
-        let mut headers = self.headers.clone();
-        headers.insert(
-            HeaderName::from_static("prefer"),
-            HeaderValue::from_static("return=representation"),
-        );
+        // missing=default は insert / upsert のみ
+        let headers = self.write_headers(|preferences| preferences.missing_default = false)?;
 
         let response = self
-            .http_client
-            .delete(&url)
-            .headers(headers) // Use modified headers
-            .send()
-            .await
-            .map_err(PostgrestError::NetworkError)?;
+            .send_with_refresh(headers, |headers| {
+                self.http_client.delete(&url).headers(headers)
+            })
+            .await?;
 
         let status = response.status();
 
@@ -707,59 +2051,172 @@ impl PostgrestClient {
             }
         } else {
             // Handle non-success status codes
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
 
             let details_result: Result<PostgrestApiErrorDetails, _> =
                 serde_json::from_str(&error_text);
             match details_result {
-                Ok(details) => Err(PostgrestError::ApiError { details, status }),
+                Ok(details) => Err(PostgrestError::ApiError {
+                    details,
+                    status,
+                    request_ids,
+                }),
                 Err(_) => Err(PostgrestError::UnparsedApiError {
                     message: error_text,
                     status,
+                    request_ids,
                 }),
             }
         }
     }
 
+    /// 複合キーで1行を取得 (該当する行が1行でなければ 406 エラー)
+    pub async fn get_by_key<T: for<'de> Deserialize<'de>>(
+        self,
+        pairs: &[(&str, &str)],
+    ) -> Result<T, PostgrestError> {
+        self.check_keys("get_by_key", pairs)?;
+        let (_, body, _) = self
+            .match_keys(pairs)
+            .execute_with_accept(SINGLE_OBJECT_CONTENT_TYPE)
+            .await?;
+        serde_json::from_slice(&body)
+            .map_err(|e| PostgrestError::DeserializationError(e.to_string()))
+    }
+
+    /// 複合キーで指定した行を更新 (キーの値が空の場合は実行しない)
+    pub async fn update_by_key<T: Serialize>(
+        self,
+        pairs: &[(&str, &str)],
+        values: T,
+    ) -> Result<Value, PostgrestError> {
+        self.check_keys("update_by_key", pairs)?;
+        self.match_keys(pairs).update(values).await
+    }
+
+    /// 複合キーで指定した行を削除 (キーの値が空の場合は実行しない)
+    pub async fn delete_by_key(self, pairs: &[(&str, &str)]) -> Result<Value, PostgrestError> {
+        self.check_keys("delete_by_key", pairs)?;
+        self.match_keys(pairs).delete().await
+    }
+
     /// RPC関数を呼び出す (POSTリクエスト)
+    ///
+    /// `select` / `eq` / `order` / `limit` などのクエリパラメータは、
+    /// 集合を返す関数 (setof) の結果に対して適用される。
     pub async fn call_rpc<T: for<'de> Deserialize<'de>>(&self) -> Result<T, PostgrestError> {
+        self.ensure_writable("call_rpc")?;
+        let params = self.rpc_params()?;
+        let url = self.rpc_url(None)?;
+
+        let response = self
+            .send_with_refresh(self.request_headers(|_| {})?, |headers| {
+                self.http_client.post(&url).headers(headers).json(params)
+            })
+            .await?;
+
+        self.rpc_response(response).await
+    }
+
+    /// 読み取り専用のRPC関数を呼び出す (GET)
+    ///
+    /// 引数はクエリ文字列として送信されるため、`STABLE` / `IMMUTABLE` な関数でのみ使用できる。
+    pub async fn call_rpc_get<T: for<'de> Deserialize<'de>>(&self) -> Result<T, PostgrestError> {
+        let params = self.rpc_params()?;
+        let empty = serde_json::Map::new();
+        let args = match params {
+            Value::Object(map) => map,
+            Value::Null => &empty,
+            _ => {
+                return Err(PostgrestError::InvalidParameters(
+                    "RPC parameters for GET must be a JSON object.".to_string(),
+                ))
+            }
+        };
+        let url = self.rpc_url(Some(args))?;
+
+        let mut headers = self.request_headers(|_| {})?;
+        headers.remove("Content-Type");
+
+        let response = self
+            .send_with_refresh(headers, |headers| {
+                self.http_client.get(&url).headers(headers)
+            })
+            .await?;
+
+        self.rpc_response(response).await
+    }
+
+    fn rpc_params(&self) -> Result<&Value, PostgrestError> {
         if !self.is_rpc {
             return Err(PostgrestError::InvalidParameters(
                 "Client was not created for RPC. Use PostgrestClient::rpc().".to_string(),
             ));
         }
-        // RPCの場合はテーブル名が関数名として扱われる
-        let url = format!("{}/rest/v1/rpc/{}", self.base_url, self.table);
-        let params = self.rpc_params.as_ref().ok_or_else(|| {
+        self.rpc_params.as_ref().ok_or_else(|| {
             PostgrestError::InvalidParameters("RPC parameters are missing.".to_string())
-        })?;
+        })
+    }
 
-        let response = self
-            .http_client
-            .post(&url)
-            .headers(self.headers.clone())
-            .json(params)
-            .send()
-            .await
-            .map_err(PostgrestError::NetworkError)?;
+    // RPCの場合はテーブル名が関数名として扱われる
+    fn rpc_url(
+        &self,
+        args: Option<&serde_json::Map<String, Value>>,
+    ) -> Result<String, PostgrestError> {
+        // 引数をクエリ文字列で送る GET は読み取りなので、`route_to` に従う
+        let mut url = match args {
+            Some(_) => self.read_resource_url(&["rpc", &self.table])?,
+            None => self.resource_url(&["rpc", &self.table])?,
+        };
+
+        if let Some(args) = args {
+            for (key, value) in args {
+                let value = match value {
+                    Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                url.query_pairs_mut().append_pair(key, &value);
+            }
+        }
+        for (key, value) in &self.query_params {
+            url.query_pairs_mut().append_pair(key, value);
+        }
 
+        Ok(url.to_string())
+    }
+
+    async fn rpc_response<T: for<'de> Deserialize<'de>>(
+        &self,
+        response: reqwest::Response,
+    ) -> Result<T, PostgrestError> {
         let status = response.status();
         if !status.is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
 
             let details_result: Result<PostgrestApiErrorDetails, _> =
                 serde_json::from_str(&error_text);
             return match details_result {
-                Ok(details) => Err(PostgrestError::ApiError { details, status }),
+                Ok(details) => Err(PostgrestError::ApiError {
+                    details,
+                    status,
+                    request_ids,
+                }),
                 Err(_) => Err(PostgrestError::UnparsedApiError {
                     message: error_text,
                     status,
+                    request_ids,
                 }),
             };
         }
@@ -772,9 +2229,64 @@ impl PostgrestClient {
         })
     }
 
-    // URLを構築
+    /// マテリアライズドビューをリフレッシュ (`refresh_matview(name)` RPC を呼び出す)
+    ///
+    /// `view_name` は `public` スキーマのビュー名 (スキーマ名は付けない)。
+    pub async fn refresh_materialized_view(&self, view_name: &str) -> Result<(), PostgrestError> {
+        self.ensure_writable("refresh_materialized_view")?;
+        let url = format!("{}/rest/v1/rpc/{}", self.base_url, REFRESH_MATVIEW_FUNCTION);
+
+        let response = self
+            .http_client
+            .post(&url)
+            .headers(self.headers.clone())
+            .json(&json!({ "name": view_name }))
+            .send_through(self.send_gate.as_ref())
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
+
+            let details_result: Result<PostgrestApiErrorDetails, _> =
+                serde_json::from_str(&error_text);
+            return match details_result {
+                // 関数がない (PGRST202)。存在しないビュー (42P01) も 404 になるので区別する
+                Ok(details) if details.code.as_deref() == Some("PGRST202") => {
+                    Err(PostgrestError::FunctionNotFound {
+                        function: REFRESH_MATVIEW_FUNCTION.to_string(),
+                        hint: "apply supabase_rust_migration::RefreshMatviewMigration \
+                               (or run supabase_rust_migration::matview::REFRESH_MATVIEW_SQL) \
+                               and reload the PostgREST schema cache"
+                            .to_string(),
+                        request_ids,
+                    })
+                }
+                Ok(details) => Err(PostgrestError::ApiError {
+                    details,
+                    status,
+                    request_ids,
+                }),
+                Err(_) => Err(PostgrestError::UnparsedApiError {
+                    message: error_text,
+                    status,
+                    request_ids,
+                }),
+            };
+        }
+
+        Ok(())
+    }
+
+    // 書き込み用の URL を構築 (常にプライマリ)
     fn build_url(&self) -> Result<String, PostgrestError> {
-        let mut url = Url::parse(&format!("{}/rest/v1/{}", self.base_url, self.table))?;
+        let mut url = self.resource_url(&[&self.table])?;
 
         for (key, value) in &self.query_params {
             url.query_pairs_mut().append_pair(key, value);
@@ -783,6 +2295,27 @@ impl PostgrestClient {
         Ok(url.to_string())
     }
 
+    fn read_limit(&self) -> Option<String> {
+        self.row_caps
+            .apply(self.query_params.get("limit").map(String::as_str))
+    }
+
+    // 読み取り用の URL (件数の上限を適用した limit を付ける)
+    fn build_read_url(&self) -> Result<String, PostgrestError> {
+        let mut url = self.read_resource_url(&[&self.table])?;
+
+        for (key, value) in &self.query_params {
+            if key != "limit" {
+                url.query_pairs_mut().append_pair(key, value);
+            }
+        }
+        if let Some(limit) = self.read_limit() {
+            url.query_pairs_mut().append_pair("limit", &limit);
+        }
+
+        Ok(url.to_string())
+    }
+
     /// トランザクションを開始
     pub async fn begin_transaction(
         &self,
@@ -790,6 +2323,7 @@ impl PostgrestClient {
         transaction_mode: Option<TransactionMode>,
         timeout_seconds: Option<u64>,
     ) -> Result<PostgrestTransaction, PostgrestError> {
+        self.ensure_writable("begin_transaction")?;
         // トランザクションオプションを構築
         let isolation = isolation_level.unwrap_or(IsolationLevel::ReadCommitted);
         let mode = transaction_mode.unwrap_or(TransactionMode::ReadWrite);
@@ -812,21 +2346,23 @@ impl PostgrestClient {
             .post(&transaction_url)
             .headers(self.headers.clone())
             .json(&request_body)
-            .send()
-            .await
-            .map_err(PostgrestError::NetworkError)?;
+            .send_through(self.send_gate.as_ref())
+            .await?;
 
         let status = response.status();
         if !status.is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
 
             // Transaction begin might not return standard PostgREST JSON error, treat as TransactionError
             return Err(PostgrestError::TransactionError(format!(
-                "Failed to begin transaction: {} (Status: {})",
-                error_text, status
+                "Failed to begin transaction: {} (Status: {}){}",
+                error_text, status, request_ids
             )));
         }
 
@@ -843,7 +2379,7 @@ impl PostgrestClient {
         // トランザクションオブジェクトを作成して返す
         Ok(PostgrestTransaction::new(
             &self.base_url,
-            &self.api_key,
+            self.api_key.expose(),
             self.http_client.clone(),
             self.headers.clone(),
             response_data.transaction_id,
@@ -851,14 +2387,95 @@ impl PostgrestClient {
     }
 }
 
+/// 部分更新 (PATCH) のビルダー (`PostgrestClient::update_fields` で作成)
+///
+/// 指定したカラムだけを含む JSON を送信する。JSON カラムの値はマージされず丸ごと置き換えられる。
+pub struct UpdateBuilder<'a> {
+    client: &'a PostgrestClient,
+    fields: serde_json::Map<String, Value>,
+    error: Option<PostgrestError>,
+}
+
+impl<'a> UpdateBuilder<'a> {
+    /// カラムの値を設定
+    pub fn set<V: Serialize>(mut self, column: &str, value: V) -> Self {
+        match serde_json::to_value(value) {
+            Ok(value) => {
+                self.fields.insert(column.to_string(), value);
+            }
+            Err(e) => {
+                self.error
+                    .get_or_insert(PostgrestError::SerializationError(e));
+            }
+        }
+        self
+    }
+
+    /// カラムに JSON 値を設定 (既存の JSON 値は丸ごと置き換えられる)
+    pub fn set_json(mut self, column: &str, value: Value) -> Self {
+        self.fields.insert(column.to_string(), value);
+        self
+    }
+
+    /// `old` と `new` で値が変わったトップレベルのフィールドを設定
+    ///
+    /// `new` に含まれないフィールド (`skip_serializing_if` など) は変更なしとして扱う。
+    pub fn set_changed<T: Serialize>(mut self, old: &T, new: &T) -> Self {
+        let (old, new) = match (serde_json::to_value(old), serde_json::to_value(new)) {
+            (Ok(Value::Object(old)), Ok(Value::Object(new))) => (old, new),
+            (Err(e), _) | (_, Err(e)) => {
+                self.error
+                    .get_or_insert(PostgrestError::SerializationError(e));
+                return self;
+            }
+            _ => {
+                self.error.get_or_insert(PostgrestError::InvalidParameters(
+                    "update_changed() requires values that serialize to JSON objects".to_string(),
+                ));
+                return self;
+            }
+        };
+        for (key, value) in new {
+            if old.get(&key) != Some(&value) {
+                self.fields.insert(key, value);
+            }
+        }
+        self
+    }
+
+    /// 送信するフィールド
+    pub fn fields(&self) -> &serde_json::Map<String, Value> {
+        &self.fields
+    }
+
+    /// 更新するフィールドがないかどうか
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// 更新を実行
+    pub async fn execute(self) -> Result<Value, PostgrestError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if self.fields.is_empty() {
+            return Err(PostgrestError::InvalidParameters(
+                "update_fields() requires at least one field".to_string(),
+            ));
+        }
+        self.client.update(Value::Object(self.fields)).await
+    }
+}
+
 /// トランザクションクライアント
 pub struct PostgrestTransaction {
     base_url: String,
-    api_key: String,
+    api_key: Redacted<String>,
     http_client: Client,
     headers: HeaderMap,
     transaction_id: String,
     state: Arc<AtomicBool>, // トランザクションがアクティブかどうか
+    savepoints: std::sync::Mutex<transaction::SavepointStack>,
 }
 
 impl PostgrestTransaction {
@@ -872,20 +2489,33 @@ impl PostgrestTransaction {
     ) -> Self {
         Self {
             base_url: base_url.to_string(),
-            api_key: api_key.to_string(),
+            api_key: api_key.into(),
             http_client,
             headers,
             transaction_id,
             state: Arc::new(AtomicBool::new(true)), // トランザクションは初期状態でアクティブ
+            savepoints: Default::default(),
         }
     }
 
+    /// エラー本文から API キーと送信したトークン (`Authorization` ヘッダー) を取り除く
+    fn scrub_secrets(&self, text: &str) -> String {
+        let token = self
+            .headers
+            .get(reqwest::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.trim_start_matches("Bearer ").trim());
+        let mut secrets = vec![self.api_key.expose().as_str()];
+        secrets.extend(token);
+        supabase_rust_core::scrub(text, &secrets)
+    }
+
     /// トランザクション内で指定したテーブルに対するクライアントを取得
     pub fn from(&self, table: &str) -> PostgrestClient {
         // トランザクションIDをクエリパラメータとして追加するクライアントを作成
         let mut client = PostgrestClient::new(
             &self.base_url,
-            &self.api_key,
+            self.api_key.expose(),
             table,
             self.http_client.clone(),
         );
@@ -896,7 +2526,7 @@ impl PostgrestTransaction {
             if let Ok(value_str) = value.to_str() {
                 if let Ok(client_with_header) = PostgrestClient::new(
                     &self.base_url,
-                    &self.api_key,
+                    self.api_key.expose(),
                     table,
                     self.http_client.clone(),
                 )
@@ -936,21 +2566,24 @@ impl PostgrestTransaction {
             .post(&commit_url)
             .headers(self.headers.clone())
             .json(&commit_body)
-            .send()
+            .send_with_request_id()
             .await
             .map_err(PostgrestError::NetworkError)?;
 
         let status = response.status();
         if !status.is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
 
             // Treat transaction commit/rollback errors specifically
             return Err(PostgrestError::TransactionError(format!(
-                "Failed to commit transaction: {} (Status: {})",
-                error_text, status
+                "Failed to commit transaction: {} (Status: {}){}",
+                error_text, status, request_ids
             )));
         }
 
@@ -981,19 +2614,22 @@ impl PostgrestTransaction {
             .post(&rollback_url)
             .headers(self.headers.clone())
             .json(&rollback_body)
-            .send()
+            .send_with_request_id()
             .await
             .map_err(PostgrestError::NetworkError)?;
 
         let status = response.status();
         if !status.is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
             return Err(PostgrestError::TransactionError(format!(
-                "Failed to rollback transaction: {} (Status: {})",
-                error_text, status
+                "Failed to rollback transaction: {} (Status: {}){}",
+                error_text, status, request_ids
             )));
         }
 
@@ -1025,19 +2661,22 @@ impl PostgrestTransaction {
             .post(&savepoint_url)
             .headers(self.headers.clone())
             .json(&savepoint_body)
-            .send()
+            .send_with_request_id()
             .await
             .map_err(PostgrestError::NetworkError)?;
 
         let status = response.status();
         if !status.is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
             return Err(PostgrestError::TransactionError(format!(
-                "Failed to create savepoint '{}': {} (Status: {})",
-                name, error_text, status
+                "Failed to create savepoint '{}': {} (Status: {}){}",
+                name, error_text, status, request_ids
             )));
         }
         Ok(())
@@ -1065,19 +2704,22 @@ impl PostgrestTransaction {
             .post(&rollback_url)
             .headers(self.headers.clone())
             .json(&rollback_body)
-            .send()
+            .send_with_request_id()
             .await
             .map_err(PostgrestError::NetworkError)?;
 
         let status = response.status();
         if !status.is_success() {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Failed to read error response".to_string());
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
             return Err(PostgrestError::TransactionError(format!(
-                "Failed to rollback to savepoint '{}': {} (Status: {})",
-                name, error_text, status
+                "Failed to rollback to savepoint '{}': {} (Status: {}){}",
+                name, error_text, status, request_ids
             )));
         }
         Ok(())
@@ -1085,34 +2727,145 @@ impl PostgrestTransaction {
 }
 
 // デストラクタに相当する実装（トランザクションが終了するとロールバック）
+//
+// ロールバックは注入された `http_client` で送る (別の Client を作るとプロキシ・ルート証明書・
+// 証明書ピンニングなどの設定を迂回してしまう)。Drop では待てないため現在の tokio ランタイムに
+// 送信タスクを投げ、ランタイムの外で破棄された場合は警告だけ記録する。
 impl Drop for PostgrestTransaction {
     fn drop(&mut self) {
         // トランザクションがまだアクティブな場合は自動ロールバック
-        if self.state.load(Ordering::SeqCst) {
-            eprintln!("Warning: Active transaction is being dropped without commit or rollback. Performing automatic rollback.");
-
-            // ブロッキング呼び出しが推奨されませんが、Dropコンテキストでは非同期関数を呼び出せないため
-            let url = format!("{}/rest/v1/rpc/rollback_transaction", self.base_url);
+        if !self.state.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        log::warn!(
+            "transaction {} dropped without commit or rollback; rolling back",
+            self.transaction_id
+        );
+        let request = self
+            .http_client
+            .post(format!("{}/rpc/rollback_transaction", self.base_url))
+            .headers(self.headers.clone())
+            .json(&json!({ "transaction_id": self.transaction_id }));
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                let transaction_id = self.transaction_id.clone();
+                handle.spawn(async move {
+                    if let Err(e) = request.send_with_request_id().await {
+                        log::warn!("automatic rollback of {} failed: {}", transaction_id, e);
+                    }
+                });
+            }
+            Err(_) => log::warn!(
+                "no tokio runtime; transaction {} was not rolled back",
+                self.transaction_id
+            ),
+        }
+    }
+}
 
-            let client = Client::new();
-            // Using drop to explicitly drop the future and avoid the warning
-            let future = client
-                .post(url)
-                .headers(self.headers.clone())
-                .json(&json!({ "transaction_id": self.transaction_id }))
-                .send();
-            std::mem::drop(future);
+/// LIKE/ILIKE パターン内でユーザー入力をそのまま一致させるために `\`、`%`、`_` をエスケープ
+///
+/// PostgREST はパターン中の `*` をすべて `%` に置き換え、エスケープする方法がないため、
+/// `*` はリテラルとしては一致させられない。`*` は任意の 1 文字に一致する `_` にする
+/// (`a*b` は `a*b` のほか `axb` にも一致する)。
+pub fn escape_like_pattern(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '\\' | '%' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '*' => escaped.push('_'),
+            _ => escaped.push(c),
         }
     }
+    escaped
+}
+
+// `Content-Range: 0-24/3573` から全件数を取り出す (`*` の場合は None)
+fn content_range_total(value: &str) -> Option<u64> {
+    value.rsplit_once('/')?.1.trim().parse().ok()
+}
+
+// メディアタイプが一致し、要求したパラメーター (`nulls=stripped` など) がすべて含まれるか
+// (レスポンスにだけある `charset` などのパラメーターは無視する)
+fn media_type_matches(requested: &str, actual: &str) -> bool {
+    fn parse(media_type: &str) -> (String, Vec<String>) {
+        let mut parts = media_type.split(';');
+        let essence = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let parameters = parts
+            .map(|parameter| parameter.replace(' ', "").to_ascii_lowercase())
+            .filter(|parameter| !parameter.is_empty())
+            .collect();
+        (essence, parameters)
+    }
+    let (requested_essence, requested_parameters) = parse(requested);
+    let (actual_essence, actual_parameters) = parse(actual);
+    requested_essence == actual_essence
+        && requested_parameters
+            .iter()
+            .all(|parameter| actual_parameters.contains(parameter))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
-    use wiremock::matchers::{body_json, header, method, path, query_param};
+    use wiremock::matchers::{body_json, header, headers, method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
+    #[tokio::test]
+    async fn test_error_messages_never_contain_secrets() {
+        const KEY: &str = "anon-key-0123456789abcdef";
+        const TOKEN: &str = "user-jwt-0123456789abcdef";
+        // 一部のプロキシは Authorization ヘッダーをそのまま本文に含めて返す
+        let bodies = [
+            format!(
+                r#"{{"message":"bad apikey {}","hint":"authorization: Bearer {}"}}"#,
+                KEY, TOKEN
+            ),
+            format!("<html>apikey={} token={}</html>", KEY, TOKEN),
+        ];
+
+        for body in bodies {
+            let mock_server = MockServer::start().await;
+            Mock::given(wiremock::matchers::any())
+                .respond_with(ResponseTemplate::new(401).set_body_string(body))
+                .mount(&mock_server)
+                .await;
+
+            let client = PostgrestClient::new(&mock_server.uri(), KEY, "items", Client::new())
+                .with_auth(TOKEN)
+                .unwrap();
+            let rpc = PostgrestClient::rpc(&mock_server.uri(), KEY, "fn", json!({}), Client::new())
+                .with_auth(TOKEN)
+                .unwrap();
+
+            let errors = vec![
+                client.execute::<Value>().await.unwrap_err(),
+                client.export_csv().await.unwrap_err(),
+                client.insert(json!({ "id": 1 })).await.unwrap_err(),
+                client.update(json!({ "id": 1 })).await.unwrap_err(),
+                client.delete().await.unwrap_err(),
+                client.refresh_materialized_view("mv").await.unwrap_err(),
+                client
+                    .begin_transaction(None, None, None)
+                    .await
+                    .err()
+                    .unwrap(),
+                rpc.call_rpc::<Value>().await.unwrap_err(),
+            ];
+
+            for error in errors {
+                for output in [error.to_string(), format!("{:?}", error)] {
+                    assert!(!output.contains(KEY), "secret leaked: {}", output);
+                    assert!(!output.contains(TOKEN), "secret leaked: {}", output);
+                }
+            }
+        }
+    }
+
     #[tokio::test]
     async fn test_select() {
         let mock_server = MockServer::start().await;
@@ -1344,6 +3097,269 @@ mod tests {
         assert!(csv_data.contains("User 2"));
     }
 
+    const TRICKY_CSV: &str = concat!(
+        "id,name,note\n",
+        "1,\"Smith, John\",\"He said \"\"hi\"\"\"\n",
+        "2,plain;semi,\"line1\nline2\"\n",
+        "3,,\"tab\there\""
+    );
+
+    fn transcode(input: &str, options: &CsvExportOptions, chunk_size: usize) -> String {
+        let mut transcoder = csv::CsvTranscoder::new(options).unwrap();
+        for chunk in input.as_bytes().chunks(chunk_size) {
+            transcoder.push(chunk);
+        }
+        transcoder.finish()
+    }
+
+    #[test]
+    fn test_csv_transcoder_keeps_tricky_fields() {
+        for delimiter in [None, Some(';'), Some('\t'), Some('|'), Some('§')] {
+            for line_ending in [LineEnding::Lf, LineEnding::Crlf] {
+                for include_bom in [false, true] {
+                    let options = CsvExportOptions {
+                        delimiter,
+                        include_bom,
+                        line_ending,
+                    };
+                    let d = delimiter.unwrap_or(',').to_string();
+                    let e = match line_ending {
+                        LineEnding::Lf => "\n",
+                        LineEnding::Crlf => "\r\n",
+                    };
+                    // 元から引用符で囲まれたフィールドは囲みを保ち、区切り文字を含むようになった
+                    // フィールドだけを新たに囲む (フィールド内の改行はそのまま)
+                    let semi = if d == ";" {
+                        "\"plain;semi\""
+                    } else {
+                        "plain;semi"
+                    };
+                    let expected = format!(
+                        "{bom}id{d}name{d}note{e}\
+                         1{d}\"Smith, John\"{d}\"He said \"\"hi\"\"\"{e}\
+                         2{d}{semi}{d}\"line1\nline2\"{e}\
+                         3{d}{d}\"tab\there\"",
+                        bom = if include_bom { "\u{feff}" } else { "" },
+                    );
+
+                    // 引用符や改行がチャンクの境目にきても結果は同じ
+                    for chunk_size in [1, 2, 7, TRICKY_CSV.len()] {
+                        assert_eq!(
+                            transcode(TRICKY_CSV, &options, chunk_size),
+                            expected,
+                            "{:?} (chunk size {})",
+                            options,
+                            chunk_size
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_csv_transcoder_edge_cases() {
+        let crlf = CsvExportOptions::new().line_ending(LineEnding::Crlf);
+        // 入力の \r\n と末尾の改行はそのまま保つ
+        assert_eq!(transcode("a,b\r\nc,d\r\n", &crlf, 1), "a,b\r\nc,d\r\n");
+        assert_eq!(
+            transcode("a,b\r\nc,d\r\n", &CsvExportOptions::new(), 3),
+            "a,b\nc,d\n"
+        );
+        // 空のフィールド・空の行・空の入力
+        let semicolon = CsvExportOptions::new().delimiter(';');
+        assert_eq!(transcode(",\n\n,x,", &semicolon, 1), ";\n\n;x;");
+        assert_eq!(transcode("", &semicolon.include_bom(true), 1), "\u{feff}");
+        // 引用符で囲まれていない値の途中にある `"` はエスケープして囲む
+        assert_eq!(transcode("a\"b,c", &semicolon, 1), "\"a\"\"b\";c");
+        // 閉じていない引用符は閉じる
+        assert_eq!(transcode("\"open;", &semicolon, 1), "\"open;\"");
+
+        for delimiter in ['"', '\n', '\r'] {
+            assert!(matches!(
+                csv::CsvTranscoder::new(&CsvExportOptions::new().delimiter(delimiter)),
+                Err(PostgrestError::InvalidParameters(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_csv_with_options() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/users"))
+            .and(header("accept", "text/csv"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(TRICKY_CSV, "text/csv"))
+            .mount(&mock_server)
+            .await;
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "users",
+            reqwest::Client::new(),
+        );
+
+        let options = CsvExportOptions::new()
+            .delimiter(';')
+            .include_bom(true)
+            .line_ending(LineEnding::Crlf);
+        let csv_data = client.export_csv_with_options(options).await.unwrap();
+        assert!(csv_data.starts_with("\u{feff}id;name;note\r\n"));
+        assert!(csv_data.contains("\r\n2;\"plain;semi\";\"line1\nline2\"\r\n"));
+
+        // 生の export_csv は変換しない
+        assert_eq!(client.export_csv().await.unwrap(), TRICKY_CSV);
+    }
+
+    fn feature_collection_fixture() -> Value {
+        json!({
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [139.767, 35.681] },
+                    "properties": { "id": 1, "name": "Tokyo Station" }
+                },
+                {
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "LineString",
+                        "coordinates": [[139.767, 35.681], [139.700, 35.690]]
+                    },
+                    "properties": { "id": 2, "name": "Route" }
+                }
+            ]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_execute_geojson() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/places"))
+            .and(query_param("select", "*"))
+            .and(query_param("kind", "eq.station"))
+            .and(header("accept", "application/geo+json"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                feature_collection_fixture().to_string(),
+                "application/geo+json; charset=utf-8",
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "places",
+            reqwest::Client::new(),
+        );
+        let collection = client
+            .select("*")
+            .eq("kind", "station")
+            .execute_geojson()
+            .await
+            .unwrap();
+
+        assert_eq!(collection.features.len(), 2);
+        assert_eq!(
+            collection.features[0].geometry,
+            Some(Geometry::Point {
+                coordinates: vec![139.767, 35.681]
+            })
+        );
+        assert_eq!(
+            collection.features[1].property("name"),
+            Some(&json!("Route"))
+        );
+        assert_eq!(
+            serde_json::to_value(&collection).unwrap(),
+            feature_collection_fixture()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_geojson_rejects_plain_json() {
+        let mock_server = MockServer::start().await;
+        // geometry カラムがないと PostgREST は通常の JSON 配列を返す
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/places"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("sb-request-id", "sb-geo")
+                    .set_body_json(json!([{ "id": 1 }])),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "places",
+            reqwest::Client::new(),
+        );
+        match client.execute_geojson().await {
+            Err(PostgrestError::UnexpectedContentType {
+                expected,
+                actual,
+                request_ids,
+            }) => {
+                assert_eq!(expected, "application/geo+json");
+                assert_eq!(actual, "application/json");
+                assert_eq!(request_ids.sb_request_id.as_deref(), Some("sb-geo"));
+                assert!(request_ids.client_request_id.is_some());
+            }
+            other => panic!("Expected UnexpectedContentType, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_with_accept() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/places"))
+            .and(header("accept", "text/xml"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_raw("<places><place/></places>", "text/xml"),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/places"))
+            .and(header("accept", "application/vnd.pgrst.plan"))
+            .respond_with(ResponseTemplate::new(406).set_body_json(json!({
+                "code": "PGRST107",
+                "message": "None of these media types are available: application/vnd.pgrst.plan",
+                "details": null,
+                "hint": null
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "places",
+            reqwest::Client::new(),
+        );
+        let (status, body, headers) = client.execute_with_accept("text/xml").await.unwrap();
+        assert_eq!(status, reqwest::StatusCode::OK);
+        assert_eq!(&body[..], b"<places><place/></places>");
+        assert_eq!(headers.get("content-type").unwrap(), "text/xml");
+
+        match client
+            .execute_with_accept("application/vnd.pgrst.plan")
+            .await
+        {
+            Err(PostgrestError::ApiError {
+                details, status, ..
+            }) => {
+                assert_eq!(status, reqwest::StatusCode::NOT_ACCEPTABLE);
+                assert_eq!(details.code.as_deref(), Some("PGRST107"));
+            }
+            other => panic!("Expected ApiError, got {:?}", other),
+        }
+    }
+
     #[tokio::test]
     async fn test_transaction() {
         let mock_server = MockServer::start().await;
@@ -1497,6 +3513,47 @@ mod tests {
         assert!(rollback_result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_dropped_transaction_rolls_back_with_injected_client() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/begin_transaction"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "transaction_id": "tx-dropped"
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/rollback_transaction"))
+            .and(header("apikey", "fake-key"))
+            .and(body_json(json!({ "transaction_id": "tx-dropped" })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // 注入した Client だけが名前解決できるホスト (新しく作った Client では到達できない)
+        let addr = *mock_server.address();
+        let http_client = reqwest::Client::builder()
+            .resolve("db.pinned.test", addr)
+            .build()
+            .unwrap();
+        let base_url = format!("http://db.pinned.test:{}", addr.port());
+        let client = PostgrestClient::new(&base_url, "fake-key", "users", http_client);
+
+        let transaction = client.begin_transaction(None, None, None).await.unwrap();
+        drop(transaction);
+
+        for _ in 0..50 {
+            let requests = mock_server.received_requests().await.unwrap();
+            if requests.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        mock_server.verify().await;
+    }
+
     #[tokio::test]
     async fn test_transaction_savepoint() {
         let mock_server = MockServer::start().await;
@@ -1575,6 +3632,176 @@ mod tests {
         assert!(commit_result.is_ok());
     }
 
+    async fn mount_savepoint_rpcs(mock_server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/rpc/begin_transaction"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "transaction_id": "tx-scope"
+            })))
+            .mount(mock_server)
+            .await;
+        for function in [
+            "create_savepoint",
+            "release_savepoint",
+            "rollback_to_savepoint",
+            "rollback_transaction",
+        ] {
+            Mock::given(method("POST"))
+                .and(path(format!("/rpc/{}", function)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "success": true
+                })))
+                .mount(mock_server)
+                .await;
+        }
+    }
+
+    // (RPC 名, セーブポイント名) の順
+    async fn savepoint_calls(mock_server: &MockServer) -> Vec<(String, String)> {
+        mock_server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .filter(|request| request.url.path().contains("savepoint"))
+            .map(|request| {
+                let body: Value = serde_json::from_slice(&request.body).unwrap();
+                (
+                    request.url.path().trim_start_matches("/rpc/").to_string(),
+                    body["name"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_savepoint_scope_ordering() {
+        let mock_server = MockServer::start().await;
+        mount_savepoint_rpcs(&mock_server).await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "users",
+            reqwest::Client::new(),
+        );
+        let transaction = client.begin_transaction(None, None, None).await.unwrap();
+
+        // 外側を解放すると内側も解放済みになる
+        let outer = transaction.savepoint_scope("outer").await.unwrap();
+        let inner = transaction.savepoint_scope("inner").await.unwrap();
+        outer.release().await.unwrap();
+        assert!(matches!(
+            inner.rollback().await,
+            Err(PostgrestError::TransactionError(message)) if message.contains("'inner'")
+        ));
+
+        // 外側へロールバックすると内側は破棄される
+        let first = transaction.savepoint_scope("first").await.unwrap();
+        let second = transaction.savepoint_scope("second").await.unwrap();
+        assert_eq!(second.name(), "second");
+        first.rollback().await.unwrap();
+        assert!(matches!(
+            second.release().await,
+            Err(PostgrestError::TransactionError(_))
+        ));
+
+        // ドロップで解放
+        drop(transaction.savepoint_scope("dropped").await.unwrap());
+        for _ in 0..100 {
+            if savepoint_calls(&mock_server).await.len() == 8 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let calls = savepoint_calls(&mock_server).await;
+        let expected = [
+            ("create_savepoint", "outer"),
+            ("create_savepoint", "inner"),
+            ("release_savepoint", "outer"),
+            ("create_savepoint", "first"),
+            ("create_savepoint", "second"),
+            ("rollback_to_savepoint", "first"),
+            ("create_savepoint", "dropped"),
+            ("release_savepoint", "dropped"),
+        ];
+        assert_eq!(
+            calls,
+            expected
+                .iter()
+                .map(|(function, name)| (function.to_string(), name.to_string()))
+                .collect::<Vec<_>>()
+        );
+
+        transaction.rollback().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_execute_batch_stops_at_first_failure() {
+        let mock_server = MockServer::start().await;
+        mount_savepoint_rpcs(&mock_server).await;
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/users"))
+            .and(query_param("transaction", "tx-scope"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!([{ "id": 1 }])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/rest/v1/users"))
+            .and(query_param("id", "eq.1"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "code": "23514",
+                "message": "new row violates check constraint"
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/rest/v1/users"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "users",
+            reqwest::Client::new(),
+        );
+        let transaction = client.begin_transaction(None, None, None).await.unwrap();
+
+        let ops = vec![
+            Operation::insert("users", &json!({ "name": "a" })).unwrap(),
+            Operation::update("users", &json!({ "age": -1 }))
+                .unwrap()
+                .eq_val("id", 1),
+            Operation::delete("users").eq_val("id", 1),
+        ];
+        match transaction.execute_batch(ops).await {
+            Err(PostgrestError::BatchOperationFailed { index, source }) => {
+                assert_eq!(index, 1);
+                assert!(matches!(*source, PostgrestError::ApiError { .. }));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let requests = mock_server.received_requests().await.unwrap();
+        assert!(!requests
+            .iter()
+            .any(|request| request.method == wiremock::http::Method::Delete));
+
+        // すべて成功すれば結果を順に返す
+        let results = transaction
+            .execute_batch(vec![
+                Operation::insert("users", &json!({ "name": "b" })).unwrap(),
+                Operation::delete("users").eq_val("id", 2),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(results, vec![json!([{ "id": 1 }]), json!([])]);
+
+        transaction.rollback().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_jsonb_filters() {
         let mock_server = MockServer::start().await;
@@ -1979,7 +4206,7 @@ mod tests {
         let client_not =
             PostgrestClient::new(&base_uri, api_key, table_name, reqwest::Client::new());
         let result_not = client_not
-            .not("status", "eq.archived")
+            .not("status", FilterOperator::Eq, "archived")
             .execute::<Value>()
             .await;
         assert!(
@@ -1991,26 +4218,124 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_modifiers() {
+    async fn test_reserved_and_special_identifiers_are_quoted() {
         let mock_server = MockServer::start().await;
-
-        // Mock for ignore_rls
         Mock::given(method("GET"))
-            .and(path("/rest/v1/protected_items"))
-            .and(header("apikey", "fake-key"))
-            .and(header("x-supabase-admin-role", "service_role")) // Expect admin role header
-            .respond_with(
-                ResponseTemplate::new(200).set_body_json(json!([{ "id": 1, "data": "secret" }])),
-            ) // Example response
+            .and(path("/rest/v1/group"))
+            .and(query_param("\"order\"", "gte.2"))
+            .and(query_param("\"first name\"", "eq.Ann"))
+            .and(query_param("order", "\"order\".desc"))
+            .and(query_param("select", "*,\"line items\"!inner(\"order\")"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "order": 3 }])))
+            .expect(1)
             .mount(&mock_server)
             .await;
-
-        // Mock for order
         Mock::given(method("GET"))
-            .and(path("/rest/v1/items"))
-            .and(query_param("order", "name.desc"))
-            .and(header("apikey", "fake-key"))
-            .respond_with(
+            .and(path("/rest/v1/line%20items"))
+            .and(query_param(
+                "or",
+                "(\"order\".eq.1,\"first name\".eq.\"Ann, B.\")",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let rows = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "group",
+            reqwest::Client::new(),
+        )
+        .inner_join("line items", "id", "order")
+        .gte("order", "2")
+        .eq("first name", "Ann")
+        .order("order", SortOrder::Descending)
+        .execute::<Value>()
+        .await
+        .unwrap();
+        assert_eq!(rows, vec![json!({ "order": 3 })]);
+
+        let rows = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "line items",
+            reqwest::Client::new(),
+        )
+        .filter(Filter::or([
+            Filter::eq("order", 1),
+            Filter::eq("first name", "Ann, B."),
+        ]))
+        .execute::<Value>()
+        .await
+        .unwrap();
+        assert!(rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_filter_tree_is_sent_as_query() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/tasks"))
+            .and(query_param(
+                "not.and",
+                "(dueDate.is.null,release.eq.\"v1.2\")",
+            ))
+            .and(query_param("or", "(priority.gt.3,ownerId.in.(1,2))"))
+            .and(query_param(
+                "and",
+                "(or(priority.lt.1,ownerId.not.is.null))",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "tasks",
+            reqwest::Client::new(),
+        )
+        .column_case(ColumnCase::CamelCase)
+        .filter(Filter::not(Filter::and([
+            Filter::is_null("due_date"),
+            Filter::eq("release", "v1.2"),
+        ])))
+        .filter(Filter::or([
+            Filter::gt("priority", 3),
+            Filter::in_list("owner_id", [1, 2]),
+        ]))
+        // 既にある `or` は上書きせず `and` にまとめる
+        .filter(Filter::or([
+            Filter::lt("priority", 1),
+            Filter::not(Filter::eq("owner_id", None::<i64>)),
+        ]));
+        let rows = client.execute::<Value>().await.unwrap();
+        assert!(rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_modifiers() {
+        let mock_server = MockServer::start().await;
+
+        // Mock for ignore_rls
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/protected_items"))
+            .and(header("apikey", "fake-key"))
+            .and(header("x-supabase-admin-role", "service_role")) // Expect admin role header
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!([{ "id": 1, "data": "secret" }])),
+            ) // Example response
+            .mount(&mock_server)
+            .await;
+
+        // Mock for order
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/items"))
+            .and(query_param("order", "name.desc"))
+            .and(header("apikey", "fake-key"))
+            .respond_with(
                 ResponseTemplate::new(200).set_body_json(json!([{ "id": 1, "name": "Zebra" }])),
             )
             .mount(&mock_server)
@@ -2176,11 +4501,15 @@ mod tests {
         let result_401 = client_401.select("*").execute::<Value>().await;
         assert!(result_401.is_err());
         match result_401.err().unwrap() {
-            PostgrestError::ApiError { details, status } => {
+            PostgrestError::ApiError {
+                details, status, ..
+            } => {
                 assert_eq!(status, reqwest::StatusCode::UNAUTHORIZED);
                 assert_eq!(details.message, Some("Invalid API key".to_string()));
             }
-            PostgrestError::UnparsedApiError { message, status } => {
+            PostgrestError::UnparsedApiError {
+                message, status, ..
+            } => {
                 // Handle case where details parsing might fail
                 assert_eq!(status, reqwest::StatusCode::UNAUTHORIZED);
                 assert!(message.contains("Invalid API key"));
@@ -2194,7 +4523,9 @@ mod tests {
         let result_400 = client_400.insert(&insert_bad_data).await;
         assert!(result_400.is_err());
         match result_400.err().unwrap() {
-            PostgrestError::ApiError { details, status } => {
+            PostgrestError::ApiError {
+                details, status, ..
+            } => {
                 assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
                 assert_eq!(details.code, Some("23502".to_string()));
                 assert!(details
@@ -2211,11 +4542,1810 @@ mod tests {
         let result_500 = client_500.select("*").execute::<Value>().await;
         assert!(result_500.is_err());
         match result_500.err().unwrap() {
-            PostgrestError::UnparsedApiError { message, status } => {
+            PostgrestError::UnparsedApiError {
+                message, status, ..
+            } => {
                 assert_eq!(status, reqwest::StatusCode::INTERNAL_SERVER_ERROR);
                 assert_eq!(message, "Internal Server Error");
             }
             e => panic!("Expected UnparsedApiError for 500, got {:?}", e),
         }
     }
+
+    #[tokio::test]
+    async fn test_refresh_materialized_view() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/rpc/refresh_matview"))
+            .and(header("apikey", "fake-key"))
+            .and(body_json(json!({ "name": "daily_sales" })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "daily_sales",
+            reqwest::Client::new(),
+        );
+
+        let result = client.refresh_materialized_view("daily_sales").await;
+        assert!(result.is_ok(), "refresh failed: {:?}", result.err());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_materialized_view_missing_function() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/rpc/refresh_matview"))
+            .respond_with(
+                ResponseTemplate::new(404)
+                    .insert_header("sb-request-id", "sb-pgrst202")
+                    .set_body_json(json!({
+                        "code": "PGRST202",
+                        "message": "Could not find the function public.refresh_matview(name) in the schema cache",
+                        "details": null,
+                        "hint": null
+                    })),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "daily_sales",
+            reqwest::Client::new(),
+        );
+
+        match client.refresh_materialized_view("daily_sales").await {
+            Err(PostgrestError::FunctionNotFound {
+                function,
+                hint,
+                request_ids,
+            }) => {
+                assert_eq!(function, "refresh_matview");
+                assert!(hint.contains("RefreshMatviewMigration"));
+                assert_eq!(request_ids.sb_request_id.as_deref(), Some("sb-pgrst202"));
+            }
+            other => panic!("Expected FunctionNotFound, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_materialized_view_missing_view_is_api_error() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/rpc/refresh_matview"))
+            .respond_with(
+                ResponseTemplate::new(404)
+                    .insert_header("sb-request-id", "req-42p01")
+                    .set_body_json(json!({
+                        "code": "42P01",
+                        "message": "relation \"daily_sale\" does not exist",
+                        "details": null,
+                        "hint": null
+                    })),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "daily_sales",
+            reqwest::Client::new(),
+        );
+
+        match client.refresh_materialized_view("daily_sale").await {
+            Err(PostgrestError::ApiError {
+                details,
+                status,
+                request_ids,
+            }) => {
+                assert_eq!(status, reqwest::StatusCode::NOT_FOUND);
+                assert_eq!(details.code.as_deref(), Some("42P01"));
+                assert_eq!(request_ids.sb_request_id.as_deref(), Some("req-42p01"));
+            }
+            other => panic!("Expected ApiError, got {:?}", other),
+        }
+    }
+
+    fn filter_of(client: &PostgrestClient, column: &str) -> String {
+        client.query_params.get(column).cloned().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_escape_like_pattern() {
+        assert_eq!(escape_like_pattern("plain"), "plain");
+        assert_eq!(escape_like_pattern("100%"), "100\\%");
+        assert_eq!(escape_like_pattern("snake_case"), "snake\\_case");
+        assert_eq!(escape_like_pattern("C:\\dir"), "C:\\\\dir");
+        // `*` は PostgREST で `%` になりエスケープできないので、任意の 1 文字にする
+        assert_eq!(escape_like_pattern("a*b"), "a_b");
+        assert_eq!(escape_like_pattern("%_\\*"), "\\%\\_\\\\_");
+    }
+
+    #[test]
+    fn test_like_helpers_escape_user_input() {
+        let client =
+            PostgrestClient::new("http://localhost", "key", "items", reqwest::Client::new())
+                .ilike_contains("name", "50%_off")
+                .ilike_starts_with("code", "a_b")
+                .ilike_ends_with("path", "\\tmp%");
+        assert_eq!(filter_of(&client, "name"), "ilike.%50\\%\\_off%");
+        assert_eq!(filter_of(&client, "code"), "ilike.a\\_b%");
+        assert_eq!(filter_of(&client, "path"), "ilike.%\\\\tmp\\%");
+
+        let like = PostgrestClient::new("http://localhost", "key", "items", reqwest::Client::new())
+            .like_contains("name", "50%_off")
+            .like_starts_with("code", "a*b")
+            .like_ends_with("path", "\\tmp%");
+        assert_eq!(filter_of(&like, "name"), "like.%50\\%\\_off%");
+        assert_eq!(filter_of(&like, "code"), "like.a_b%");
+        assert_eq!(filter_of(&like, "path"), "like.%\\\\tmp\\%");
+
+        // 生の ilike はパターンをそのまま使う
+        let raw = PostgrestClient::new("http://localhost", "key", "items", reqwest::Client::new())
+            .ilike("name", "%50%");
+        assert_eq!(filter_of(&raw, "name"), "ilike.%50%");
+    }
+
+    #[test]
+    fn test_typed_filter_values() {
+        let client =
+            PostgrestClient::new("http://localhost", "key", "items", reqwest::Client::new())
+                .eq_val("active", true)
+                .neq_val("archived", false)
+                .gt_val("count", 42_i32)
+                .gte_val("big", u64::MAX)
+                .lt_val("price", 19.5_f64)
+                .lte_val("ratio", 0.1_f32)
+                .eq_val("name", "Alice")
+                .eq_val("owner", String::from("bob"));
+
+        assert_eq!(filter_of(&client, "active"), "eq.true");
+        assert_eq!(filter_of(&client, "archived"), "neq.false");
+        assert_eq!(filter_of(&client, "count"), "gt.42");
+        assert_eq!(filter_of(&client, "big"), "gte.18446744073709551615");
+        assert_eq!(filter_of(&client, "price"), "lt.19.5");
+        assert_eq!(filter_of(&client, "ratio"), "lte.0.1");
+        assert_eq!(filter_of(&client, "name"), "eq.Alice");
+        assert_eq!(filter_of(&client, "owner"), "eq.bob");
+    }
+
+    #[test]
+    fn test_typed_filter_special_floats() {
+        assert_eq!(
+            FilterValue::from(1e21_f64).to_filter("eq"),
+            "eq.1000000000000000000000"
+        );
+        assert_eq!(FilterValue::from(-0.25_f64).to_filter("eq"), "eq.-0.25");
+        assert_eq!(FilterValue::from(f64::NAN).to_filter("eq"), "eq.NaN");
+        assert_eq!(
+            FilterValue::from(f64::INFINITY).to_filter("lt"),
+            "lt.Infinity"
+        );
+        assert_eq!(
+            FilterValue::from(f32::NEG_INFINITY).to_filter("gt"),
+            "gt.-Infinity"
+        );
+    }
+
+    #[test]
+    fn test_typed_filter_option_maps_to_is_null() {
+        let client =
+            PostgrestClient::new("http://localhost", "key", "items", reqwest::Client::new())
+                .eq_val("deleted_at", None::<&str>)
+                .neq_val("owner_id", None::<i64>)
+                .eq_val("score", Some(3_i64))
+                .eq_val("flag", Some(false));
+
+        assert_eq!(filter_of(&client, "deleted_at"), "is.null");
+        assert_eq!(filter_of(&client, "owner_id"), "not.is.null");
+        assert_eq!(filter_of(&client, "score"), "eq.3");
+        assert_eq!(filter_of(&client, "flag"), "eq.false");
+
+        // 生の &str メソッドはそのまま送信される
+        let raw = PostgrestClient::new("http://localhost", "key", "items", reqwest::Client::new())
+            .eq("label", "null");
+        assert_eq!(filter_of(&raw, "label"), "eq.null");
+    }
+
+    #[tokio::test]
+    async fn test_rpc_get_with_filters() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/rpc/search_items"))
+            .and(query_param("term", "rust"))
+            .and(query_param("max_price", "100"))
+            .and(query_param("category", "eq.books"))
+            .and(query_param("limit", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "id": 1, "category": "books" },
+                { "id": 2, "category": "books" }
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::rpc_typed(
+            &mock_server.uri(),
+            "fake-key",
+            "search_items",
+            &json!({ "term": "rust", "max_price": 100 }),
+            reqwest::Client::new(),
+        )
+        .unwrap()
+        .eq("category", "books")
+        .limit(2);
+
+        let rows: Vec<Value> = client.call_rpc_get().await.unwrap();
+        assert_eq!(rows.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rpc_client_rejects_table_operations() {
+        let client = PostgrestClient::rpc(
+            "http://localhost:54321",
+            "fake-key",
+            "my_rpc_function",
+            json!({}),
+            reqwest::Client::new(),
+        );
+
+        let result = client.execute::<Value>().await;
+        assert!(matches!(result, Err(PostgrestError::InvalidParameters(_))));
+        let result = client.insert(json!({ "name": "x" })).await;
+        assert!(matches!(result, Err(PostgrestError::InvalidParameters(_))));
+    }
+
+    #[tokio::test]
+    async fn test_insert_missing_default() {
+        let mock_server = MockServer::start().await;
+
+        // 2行目は status (DEFAULT 付きカラム) を省略
+        let rows = json!([
+            { "name": "first", "status": "active" },
+            { "name": "second" }
+        ]);
+
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/items"))
+            .and(headers(
+                "prefer",
+                vec!["return=representation", "missing=default"],
+            ))
+            .and(body_json(&rows))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!([
+                { "id": 1, "name": "first", "status": "active" },
+                { "id": 2, "name": "second", "status": "pending" }
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        )
+        .missing_default(true);
+
+        let result = client.insert(&rows).await.unwrap();
+        assert_eq!(result[1]["status"], "pending");
+    }
+
+    #[tokio::test]
+    async fn test_upsert_missing_default() {
+        let mock_server = MockServer::start().await;
+        let rows = json!([{ "id": 1, "name": "renamed" }]);
+
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/items"))
+            .and(headers(
+                "prefer",
+                vec![
+                    "return=representation",
+                    "resolution=merge-duplicates",
+                    "missing=default",
+                ],
+            ))
+            .and(body_json(&rows))
+            .respond_with(ResponseTemplate::new(201).set_body_json(&rows))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        )
+        .missing_default(true);
+
+        let result = client.upsert(&rows).await;
+        assert!(result.is_ok(), "upsert failed: {:?}", result.err());
+    }
+
+    #[tokio::test]
+    async fn test_upsert_report_single_key() {
+        let mock_server = MockServer::start().await;
+        let rows = json!([
+            { "id": "1", "name": "a" },
+            { "id": 2, "name": "b" },
+            { "id": 3, "name": "c" }
+        ]);
+
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/items"))
+            .and(query_param("on_conflict", "id"))
+            .and(headers(
+                "prefer",
+                vec!["return=representation", "resolution=ignore-duplicates"],
+            ))
+            .and(body_json(&rows))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!([
+                { "id": 1, "name": "a" },
+                { "id": 3, "name": "c" }
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        );
+
+        let report = client
+            .upsert_report(rows.as_array().unwrap(), &["id"], &["id"])
+            .await
+            .unwrap();
+        assert_eq!(report.inserted.len(), 2);
+        assert_eq!(report.skipped_keys, vec![json!(2)]);
+        assert!(!report.all_skipped());
+    }
+
+    #[tokio::test]
+    async fn test_upsert_report_composite_key() {
+        let mock_server = MockServer::start().await;
+        let rows = json!([
+            { "org_id": 1, "sku": "A-1", "qty": 5 },
+            { "org_id": 1, "sku": "A-2", "qty": 1 },
+            { "org_id": 2, "sku": "A-1", "qty": 2 }
+        ]);
+
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/items"))
+            .and(query_param("on_conflict", "org_id,sku"))
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .set_body_json(json!([{ "org_id": 1, "sku": "A-2", "qty": 1 }])),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        );
+
+        let report = client
+            .upsert_report(
+                rows.as_array().unwrap(),
+                &["org_id", "sku"],
+                &["org_id", "sku"],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            report.skipped_keys,
+            vec![
+                json!({ "org_id": 1, "sku": "A-1" }),
+                json!({ "org_id": 2, "sku": "A-1" })
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upsert_report_fully_skipped_batch() {
+        let mock_server = MockServer::start().await;
+        let rows = json!([{ "id": 1 }, { "id": 2 }]);
+
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/items"))
+            .and(query_param("on_conflict", "id"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!([])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        );
+
+        let report = client
+            .upsert_report(rows.as_array().unwrap(), &["id"], &["id"])
+            .await
+            .unwrap();
+        assert!(report.all_skipped());
+        assert_eq!(report.skipped_keys, vec![json!(1), json!(2)]);
+    }
+
+    #[tokio::test]
+    async fn test_upsert_report_missing_key_sends_nothing() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!([])))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        );
+
+        let rows = json!([{ "org_id": 1, "sku": "A-1" }, { "org_id": 1 }]);
+        let result = client
+            .upsert_report(
+                rows.as_array().unwrap(),
+                &["org_id", "sku"],
+                &["org_id", "sku"],
+            )
+            .await;
+        match result {
+            Err(PostgrestError::MissingKeyColumn { index, column }) => {
+                assert_eq!(index, 1);
+                assert_eq!(column, "sku");
+            }
+            other => panic!("expected MissingKeyColumn, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_preferences_render_single_header() {
+        let client = PostgrestClient::new(
+            "http://localhost",
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        )
+        .with_header("Prefer", "count=planned, tx=rollback")
+        .unwrap()
+        .count(true)
+        .handling(Handling::Strict)
+        .timezone("Europe/Oslo");
+
+        let headers = client.write_headers(|_| {}).unwrap();
+        let values: Vec<_> = headers.get_all("prefer").iter().collect();
+        assert_eq!(
+            values,
+            vec!["return=representation, count=exact, handling=strict, timezone=Europe/Oslo, tx=rollback"]
+        );
+
+        // 設定がなければ Prefer ヘッダーは付けない
+        let plain = PostgrestClient::new(
+            "http://localhost",
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        );
+        assert!(plain
+            .request_headers(|_| {})
+            .unwrap()
+            .get("prefer")
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_insert_combined_preferences() {
+        let mock_server = MockServer::start().await;
+        let rows = json!([{ "name": "first" }]);
+
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/items"))
+            .and(headers(
+                "prefer",
+                vec![
+                    "return=representation",
+                    "count=exact",
+                    "handling=strict",
+                    "timezone=Europe/Oslo",
+                ],
+            ))
+            .respond_with(ResponseTemplate::new(201).set_body_json(&rows))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        )
+        .count_method(CountMethod::Exact)
+        .handling(Handling::Strict)
+        .timezone("Europe/Oslo");
+
+        let result = client.insert(&rows).await;
+        assert!(result.is_ok(), "insert failed: {:?}", result.err());
+    }
+
+    #[tokio::test]
+    async fn test_select_handling_and_timezone() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/items"))
+            .and(headers(
+                "prefer",
+                vec!["handling=lenient", "timezone=Asia/Tokyo"],
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "id": 1, "created_at": "2024-01-01T09:00:00+09:00" }
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("PATCH"))
+            .and(path("/rest/v1/items"))
+            .and(headers(
+                "prefer",
+                vec!["return=minimal", "handling=lenient", "timezone=Asia/Tokyo"],
+            ))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        )
+        .handling(Handling::Lenient)
+        .timezone("Asia/Tokyo");
+
+        let rows: Vec<Value> = client.execute().await.unwrap();
+        assert_eq!(rows[0]["created_at"], "2024-01-01T09:00:00+09:00");
+
+        let result = client
+            .returning(ReturnPreference::Minimal)
+            .missing_default(true)
+            .update(json!({ "name": "x" }))
+            .await
+            .unwrap();
+        assert_eq!(result, Value::Null);
+    }
+
+    fn memberships_client(server: &MockServer) -> PostgrestClient {
+        PostgrestClient::new(
+            &server.uri(),
+            "fake-key",
+            "memberships",
+            reqwest::Client::new(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_get_by_composite_key() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/memberships"))
+            .and(query_param("org_id", "eq.10"))
+            .and(query_param("user_id", "eq.20"))
+            .and(header("accept", "application/vnd.pgrst.object+json"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "org_id": 10, "user_id": 20, "role": "admin"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let row: Value = memberships_client(&mock_server)
+            .select("*")
+            .get_by_key(&[("org_id", "10"), ("user_id", "20")])
+            .await
+            .unwrap();
+        assert_eq!(row["role"], "admin");
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete_by_composite_key() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("PATCH"))
+            .and(path("/rest/v1/memberships"))
+            .and(query_param("org_id", "eq.10"))
+            .and(query_param("user_id", "eq.20"))
+            .and(body_json(json!({ "role": "viewer" })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "org_id": 10, "user_id": 20, "role": "viewer" }
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/rest/v1/memberships"))
+            .and(query_param("org_id", "eq.10"))
+            .and(query_param("user_id", "eq.20"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let keys = [("org_id", "10"), ("user_id", "20")];
+        let updated = memberships_client(&mock_server)
+            .update_by_key(&keys, json!({ "role": "viewer" }))
+            .await
+            .unwrap();
+        assert_eq!(updated[0]["role"], "viewer");
+
+        let deleted = memberships_client(&mock_server)
+            .delete_by_key(&keys)
+            .await
+            .unwrap();
+        assert_eq!(deleted, Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_by_key_rejects_empty_values() {
+        let mock_server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let result = memberships_client(&mock_server)
+            .update_by_key(&[("org_id", "10"), ("user_id", "")], json!({ "role": "x" }))
+            .await;
+        assert!(matches!(result, Err(PostgrestError::InvalidParameters(_))));
+
+        let result = memberships_client(&mock_server).delete_by_key(&[]).await;
+        assert!(matches!(result, Err(PostgrestError::InvalidParameters(_))));
+
+        let result = memberships_client(&mock_server)
+            .delete_by_key(&[("org_id", "10"), ("org_id", "11")])
+            .await;
+        assert!(matches!(result, Err(PostgrestError::InvalidParameters(_))));
+
+        let result = memberships_client(&mock_server)
+            .get_by_key::<Value>(&[("", "10")])
+            .await;
+        assert!(matches!(result, Err(PostgrestError::InvalidParameters(_))));
+    }
+
+    #[tokio::test]
+    async fn test_select_aggregate() {
+        assert_eq!(
+            aggregate::render(&[
+                Agg::Col("category"),
+                Agg::Sum("price").alias("total"),
+                Agg::Avg("price"),
+                Agg::Count("id"),
+                Agg::CountAll.alias("rows"),
+                Agg::Min("price").alias("x").alias("cheapest"),
+                Agg::Max("price"),
+            ]),
+            "category,total:price.sum(),price.avg(),id.count(),rows:count(),cheapest:price.min(),price.max()"
+        );
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/products"))
+            .and(query_param(
+                "select",
+                "category,total:price.sum(),id.count()",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                { "category": "books", "total": 42.5, "count": 3 },
+                { "category": "games", "total": 80.0, "count": 2 }
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct CategoryTotal {
+            category: String,
+            total: f64,
+            count: i64,
+        }
+
+        let rows = PostgrestClient::new(&mock_server.uri(), "key", "products", Client::new())
+            .select_aggregate(&[
+                Agg::Col("category"),
+                Agg::Sum("price").alias("total"),
+                Agg::Count("id"),
+            ])
+            .execute::<CategoryTotal>()
+            .await
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                CategoryTotal {
+                    category: "books".to_string(),
+                    total: 42.5,
+                    count: 3
+                },
+                CategoryTotal {
+                    category: "games".to_string(),
+                    total: 80.0,
+                    count: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_group_by_adds_columns_to_select() {
+        let client = PostgrestClient::new("http://localhost", "key", "products", Client::new())
+            .select("category,price.sum()")
+            .group_by("category, region");
+        assert_eq!(
+            client.query_params.get("select").unwrap(),
+            "category,price.sum(),region"
+        );
+        assert!(!client.query_params.contains_key("group"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_paged_reads_content_range() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/items"))
+            .and(query_param("limit", "2"))
+            .and(query_param("offset", "4"))
+            .and(header("prefer", "count=exact"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Range", "4-5/7")
+                    .set_body_json(json!([{ "id": 5 }, { "id": 6 }])),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        )
+        .page(Page::new(3, 2));
+
+        let paged = client.execute_paged::<Value>().await.unwrap();
+        assert_eq!(paged.items.len(), 2);
+        assert_eq!(paged.page, Page::new(3, 2));
+        assert_eq!(paged.total, Some(7));
+        assert_eq!(paged.has_more, Some(true));
+    }
+
+    #[test]
+    fn test_page_to_limit_offset() {
+        let client = PostgrestClient::new(
+            "http://localhost:54321",
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        )
+        .page(Page::new(4, 25));
+        assert_eq!(client.query_params.get("limit").unwrap(), "25");
+        assert_eq!(client.query_params.get("offset").unwrap(), "75");
+        assert_eq!(content_range_total("0-24/3573"), Some(3573));
+        assert_eq!(content_range_total("0-24/*"), None);
+        assert_eq!(content_range_total("*/0"), Some(0));
+    }
+
+    #[derive(Serialize)]
+    struct Order {
+        id: i64,
+        status: String,
+        total: f64,
+        note: Option<String>,
+        meta: Value,
+    }
+
+    fn order(status: &str, note: Option<&str>) -> Order {
+        Order {
+            id: 7,
+            status: status.to_string(),
+            total: 12.5,
+            note: note.map(str::to_string),
+            meta: json!({ "tags": ["a"] }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_changed_sends_only_changed_fields() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("PATCH"))
+            .and(path("/rest/v1/orders"))
+            .and(query_param("id", "eq.7"))
+            .and(body_json(
+                json!({ "status": "shipped", "note": "left at door" }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": 7 }])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "orders",
+            reqwest::Client::new(),
+        )
+        .eq("id", "7");
+
+        let old = order("pending", None);
+        let new = order("shipped", Some("left at door"));
+        let result = client.update_changed(&old, &new).await.unwrap();
+        assert_eq!(result, json!([{ "id": 7 }]));
+
+        // 変更がなければリクエストしない
+        let result = client.update_changed(&new, &new).await.unwrap();
+        assert_eq!(result, json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_update_fields_builds_minimal_body() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("PATCH"))
+            .and(path("/rest/v1/orders"))
+            .and(body_json(json!({
+                "status": "shipped",
+                "meta": { "carrier": "ups" }
+            })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "orders",
+            reqwest::Client::new(),
+        )
+        .eq("id", "7");
+
+        let builder = client
+            .update_fields()
+            .set("status", "shipped")
+            .set_json("meta", json!({ "carrier": "ups" }));
+        assert_eq!(builder.fields().len(), 2);
+        assert_eq!(builder.execute().await.unwrap(), Value::Null);
+
+        let result = client.update_fields().execute().await;
+        assert!(matches!(result, Err(PostgrestError::InvalidParameters(_))));
+    }
+
+    #[tokio::test]
+    async fn test_diagnostic_deserialization_reports_failing_row() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Row {
+            id: i64,
+            name: String,
+            created_at: String,
+        }
+
+        let mock_server = MockServer::start().await;
+        let rows: Vec<Value> = (0..5)
+            .map(|id| {
+                let mut row = json!({ "id": id, "name": format!("row {}", id), "tags": ["a"] });
+                if id != 3 {
+                    row["created_at"] = json!("2024-01-01T00:00:00Z");
+                }
+                row
+            })
+            .collect();
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/items"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&rows))
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(&mock_server.uri(), "key", "items", Client::new());
+        let error = client
+            .diagnostic_deserialization(true)
+            .execute::<Row>()
+            .await
+            .unwrap_err();
+        match &error {
+            PostgrestError::RowDeserializationError {
+                index,
+                row_summary,
+                message,
+            } => {
+                assert_eq!(*index, 3);
+                assert_eq!(row_summary, r#"id=3, name="row 3""#);
+                assert!(
+                    message.contains("missing field `created_at`"),
+                    "{}",
+                    message
+                );
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(error.to_string().contains("at row 3"), "{}", error);
+
+        // 無効にすると一括でデシリアライズする
+        let client = PostgrestClient::new(&mock_server.uri(), "key", "items", Client::new());
+        let error = client
+            .diagnostic_deserialization(false)
+            .execute::<Row>()
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, PostgrestError::DeserializationError(ref message) if message.contains("created_at")),
+            "{:?}",
+            error
+        );
+        let client = PostgrestClient::new(&mock_server.uri(), "key", "items", Client::new());
+        assert_eq!(client.execute::<Value>().await.unwrap(), rows);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_delete_returns_rows_that_would_be_deleted() {
+        let mock_server = MockServer::start().await;
+        let rows = json!([{ "id": 1, "status": "stale" }, { "id": 2, "status": "stale" }]);
+        Mock::given(method("DELETE"))
+            .and(path("/rest/v1/items"))
+            .and(query_param("status", "eq.stale"))
+            .and(headers(
+                "prefer",
+                vec![
+                    "return=representation",
+                    "count=exact",
+                    "tx=rollback",
+                    "handling=strict",
+                ],
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&rows))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // 利用者が指定した tx=commit や return=minimal より dry_run が優先される
+        let result = PostgrestClient::new(&mock_server.uri(), "key", "items", Client::new())
+            .with_header("Prefer", "tx=commit, handling=strict")
+            .unwrap()
+            .returning(ReturnPreference::Minimal)
+            .count(true)
+            .eq("status", "stale")
+            .dry_run()
+            .delete()
+            .await
+            .unwrap();
+
+        assert_eq!(result.rows(), &rows);
+        #[derive(Deserialize)]
+        struct Item {
+            id: i64,
+        }
+        let ids: Vec<i64> = result
+            .rows_as::<Item>()
+            .unwrap()
+            .into_iter()
+            .map(|item| item.id)
+            .collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(result.into_rows(), rows);
+    }
+
+    // log::warn! を記録するロガー (テストバイナリ全体で一度だけ設定する)
+    static WARNINGS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    struct CaptureLogger;
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                WARNINGS.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[tokio::test]
+    async fn test_row_caps() {
+        static LOGGER: CaptureLogger = CaptureLogger;
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(log::LevelFilter::Warn);
+        }
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/items"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&mock_server)
+            .await;
+        let client = || {
+            PostgrestClient::new(
+                &mock_server.uri(),
+                "fake-key",
+                "items",
+                reqwest::Client::new(),
+            )
+        };
+
+        // limit なし: default_max_rows が付く (ceiling より大きければ ceiling)
+        client()
+            .default_max_rows(50)
+            .select("*")
+            .execute::<Value>()
+            .await
+            .unwrap();
+        client()
+            .default_max_rows(5_000)
+            .max_rows_ceiling(1_000)
+            .execute::<Value>()
+            .await
+            .unwrap();
+        // ceiling を超える明示的な limit は切り詰めて警告
+        client()
+            .max_rows_ceiling(1_000)
+            .limit(10_000)
+            .execute::<Value>()
+            .await
+            .unwrap();
+        client()
+            .default_max_rows(50)
+            .max_rows_ceiling(1_000)
+            .limit(200)
+            .execute::<Value>()
+            .await
+            .unwrap();
+        // ページサイズも ceiling で切り詰める
+        let paged = client()
+            .max_rows_ceiling(1_000)
+            .page(Page::new(0, 5_000))
+            .execute_paged::<Value>()
+            .await
+            .unwrap();
+        assert_eq!(paged.page.size, 1_000);
+        // unlimited() はどちらも無視する
+        client()
+            .default_max_rows(50)
+            .max_rows_ceiling(1_000)
+            .limit(10_000)
+            .unlimited()
+            .execute::<Value>()
+            .await
+            .unwrap();
+        client()
+            .default_max_rows(50)
+            .unlimited()
+            .execute::<Value>()
+            .await
+            .unwrap();
+
+        let limits = mock_server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| {
+                request
+                    .url
+                    .query_pairs()
+                    .find(|(key, _)| key == "limit")
+                    .map(|(_, value)| value.into_owned())
+            })
+            .collect::<Vec<_>>();
+        let expected = ["50", "1000", "1000", "200", "1000", "10000"];
+        assert_eq!(
+            limits,
+            expected
+                .iter()
+                .map(|limit| Some(limit.to_string()))
+                .chain([None])
+                .collect::<Vec<_>>()
+        );
+        assert!(WARNINGS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|warning| warning.contains("limit=10000 exceeds max_rows_ceiling")));
+    }
+
+    #[tokio::test]
+    async fn test_accept_profile_checks_content_type() {
+        #[derive(Debug, Deserialize)]
+        struct Row {
+            id: i64,
+            note: Option<String>,
+        }
+
+        const PROFILE: &str = "application/vnd.pgrst.array+json;nulls=stripped";
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/items"))
+            .and(header("accept", PROFILE))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"[{"id":1},{"id":2,"note":"b"}]"#,
+                "application/vnd.pgrst.array+json; nulls=stripped; charset=utf-8",
+            ))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/legacy"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(r#"[{"id":1,"note":null}]"#, "application/json"),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/items"))
+            .and(header("accept", "text/csv"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("id\n1\n"))
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        )
+        .accept_profile(PROFILE);
+        let rows: Vec<Row> = client.execute().await.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].id, 1);
+        assert!(rows[0].note.is_none());
+        assert_eq!(rows[1].note.as_deref(), Some("b"));
+
+        // 他の Accept を送るメソッドとは干渉しない
+        assert_eq!(client.export_csv().await.unwrap(), "id\n1\n");
+        let _: Vec<Row> = client.execute().await.unwrap();
+        let accepts = mock_server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| {
+                request.headers.get(&"accept".into()).unwrap()[0]
+                    .as_str()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(accepts, [PROFILE, "text/csv", PROFILE]);
+
+        let result = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "legacy",
+            reqwest::Client::new(),
+        )
+        .accept_profile(PROFILE)
+        .execute::<Row>()
+        .await;
+        match result {
+            Err(PostgrestError::UnexpectedContentType {
+                expected, actual, ..
+            }) => {
+                assert_eq!(expected, PROFILE);
+                assert_eq!(actual, "application/json");
+            }
+            other => panic!("Expected UnexpectedContentType, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_refresher_retry_failure_returns_original_error() {
+        struct FixedRefresher(std::sync::atomic::AtomicUsize);
+
+        #[async_trait::async_trait]
+        impl TokenRefresher for FixedRefresher {
+            async fn refresh(&self, expired_token: &str) -> Option<String> {
+                assert_eq!(expired_token, "old-jwt");
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Some("new-jwt".to_string())
+            }
+        }
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/rest/v1/items"))
+            .and(header("authorization", "Bearer old-jwt"))
+            .respond_with(
+                ResponseTemplate::new(401)
+                    .insert_header(
+                        "www-authenticate",
+                        r#"Bearer error="invalid_token", error_description="JWT expired""#,
+                    )
+                    .set_body_json(json!({ "code": "PGRST303", "message": "JWT expired" })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/rest/v1/items"))
+            .and(header("authorization", "Bearer new-jwt"))
+            .respond_with(
+                ResponseTemplate::new(403).set_body_json(json!({ "message": "forbidden" })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let refresher = Arc::new(FixedRefresher(Default::default()));
+        let result = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        )
+        .with_auth("old-jwt")
+        .unwrap()
+        .with_token_refresher(refresher.clone())
+        .eq("id", "1")
+        .update(json!({ "name": "x" }))
+        .await;
+
+        // 再送は 1 回だけで、返るのは最初の 401
+        assert_eq!(refresher.0.load(Ordering::SeqCst), 1);
+        match result {
+            Err(PostgrestError::ApiError {
+                details, status, ..
+            }) => {
+                assert_eq!(status, reqwest::StatusCode::UNAUTHORIZED);
+                assert_eq!(details.message.as_deref(), Some("JWT expired"));
+            }
+            other => panic!("Expected the original 401, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_ids_are_sent_and_attached_to_errors() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/items"))
+            .and(wiremock::matchers::header_exists("x-client-request-id"))
+            .respond_with(
+                ResponseTemplate::new(400)
+                    .insert_header("sb-request-id", "sb-123")
+                    .insert_header("cf-ray", "8a1b2c3d-NRT")
+                    .set_body_json(json!({ "code": "22P02", "message": "invalid input" })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let result = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        )
+        .execute::<Value>()
+        .await;
+
+        let sent = mock_server.received_requests().await.unwrap();
+        let client_request_id =
+            sent[0].headers.get(&"x-client-request-id".into()).unwrap()[0].as_str();
+        assert_eq!(client_request_id.len(), 36);
+        let error = result.unwrap_err();
+        let PostgrestError::ApiError { request_ids, .. } = &error else {
+            panic!("Expected ApiError, got {:?}", error);
+        };
+        assert_eq!(
+            request_ids.client_request_id.as_deref(),
+            Some(client_request_id)
+        );
+        assert_eq!(request_ids.sb_request_id.as_deref(), Some("sb-123"));
+        assert_eq!(request_ids.cf_ray.as_deref(), Some("8a1b2c3d-NRT"));
+        let message = error.to_string();
+        assert!(message.contains(client_request_id), "{}", message);
+        assert!(message.contains("sb-request-id=sb-123"), "{}", message);
+    }
+
+    #[derive(Serialize)]
+    struct BulkRow {
+        id: u32,
+        name: String,
+    }
+
+    fn bulk_rows(count: u32) -> impl Iterator<Item = BulkRow> {
+        (0..count).map(|id| BulkRow {
+            id,
+            name: format!("row-{}", id),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_insert_from_iter_sends_rows_in_batches() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/items"))
+            .and(header("prefer", "return=minimal"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        );
+        let report = client.insert_from_iter(bulk_rows(5), 2).await.unwrap();
+        assert_eq!(
+            report,
+            InsertReport {
+                rows: 5,
+                batches: 3
+            }
+        );
+
+        let batches: Vec<Value> = mock_server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|request| serde_json::from_slice(&request.body).unwrap())
+            .collect();
+        assert_eq!(
+            batches,
+            vec![
+                json!([{ "id": 0, "name": "row-0" }, { "id": 1, "name": "row-1" }]),
+                json!([{ "id": 2, "name": "row-2" }, { "id": 3, "name": "row-3" }]),
+                json!([{ "id": 4, "name": "row-4" }]),
+            ]
+        );
+
+        // 空の入力はリクエストしない
+        let report = client
+            .insert_from_iter(std::iter::empty::<BulkRow>(), 2)
+            .await
+            .unwrap();
+        assert_eq!(report, InsertReport::default());
+    }
+
+    #[tokio::test]
+    async fn test_insert_from_stream_upserts_and_reports_failed_batch() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/items"))
+            .and(headers(
+                "prefer",
+                vec!["return=minimal", "resolution=merge-duplicates"],
+            ))
+            .and(body_json(
+                json!([{ "id": 2, "name": "row-2" }, { "id": 3, "name": "row-3" }]),
+            ))
+            .respond_with(
+                ResponseTemplate::new(409)
+                    .set_body_json(json!({ "code": "23505", "message": "duplicate key" })),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/items"))
+            .respond_with(ResponseTemplate::new(201))
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        );
+        let result = client
+            .upsert_from_stream(futures_util::stream::iter(bulk_rows(6)), 2)
+            .await;
+        match result {
+            Err(PostgrestError::BatchWriteFailed {
+                first_row,
+                written,
+                source,
+            }) => {
+                assert_eq!(first_row, 2);
+                assert_eq!(written, 2);
+                assert!(matches!(*source, PostgrestError::ApiError { .. }));
+            }
+            other => panic!("Expected BatchWriteFailed, got {:?}", other),
+        }
+        // 失敗したバッチ以降は送信しない
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_insert_from_iter_reports_unserializable_row_index() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/items"))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // 非有限の浮動小数点数はエラーにならず null になるため、キーが文字列でないマップを使う
+        let rows = (0..4).map(|i| {
+            let mut row = std::collections::BTreeMap::new();
+            if i == 3 {
+                row.insert(vec![i], i);
+            }
+            row
+        });
+        let result = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "items",
+            reqwest::Client::new(),
+        )
+        .insert_from_iter(rows, 2)
+        .await;
+
+        match result {
+            Err(PostgrestError::RowSerializationError { index, .. }) => assert_eq!(index, 3),
+            other => panic!("Expected RowSerializationError, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_column_case_maps_snake_case_names() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Order {
+            order_id: i64,
+            user_id: String,
+            created_at: String,
+        }
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/orders"))
+            .and(query_param("select", "orderId,userId,createdAt"))
+            .and(query_param("userId", "eq.u1"))
+            .and(query_param("totalCents", "gt.100"))
+            .and(query_param("order", "createdAt.desc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/orders"))
+            .and(query_param("select", "user_id,orderId"))
+            .and(query_param("userId", "eq.u2"))
+            .and(query_param("order", "createdAt.asc"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let client = || {
+            PostgrestClient::new(
+                &mock_server.uri(),
+                "fake-key",
+                "orders",
+                reqwest::Client::new(),
+            )
+            .column_case(ColumnCase::CamelCase)
+        };
+
+        client()
+            .select_columns::<Order>()
+            .eq("user_id", "u1")
+            .gt_val("total_cents", 100)
+            .order("created_at", SortOrder::Descending)
+            .execute::<Value>()
+            .await
+            .unwrap();
+        // camelCase の名前はそのまま、select() の文字列は変換しない
+        client()
+            .select("user_id,orderId")
+            .eq("userId", "u2")
+            .order("createdAt", SortOrder::Ascending)
+            .execute::<Value>()
+            .await
+            .unwrap();
+
+        assert_eq!(ColumnCase::CamelCase.apply("_row_version"), "_rowVersion");
+        assert_eq!(
+            ColumnCase::CamelCase.apply("extra_data->>owner_id"),
+            "extraData->>owner_id"
+        );
+        assert_eq!(ColumnCase::AsIs.apply("user_id"), "user_id");
+    }
+
+    #[tokio::test]
+    async fn test_routed_read_goes_to_the_replica_under_its_path_prefix() {
+        let primary = MockServer::start().await;
+        let replica = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/pg-replica/rest/v1/items"))
+            .and(query_param("select", "id"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": 1 }])))
+            .expect(1)
+            .mount(&replica)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/pg-replica/rest/v1/rpc/lookup"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!(1)))
+            .expect(1)
+            .mount(&replica)
+            .await;
+
+        let replica_url = format!("{}/pg-replica/", replica.uri());
+        let client = PostgrestClient::new(&primary.uri(), "fake-key", "items", Client::new())
+            .with_read_replica(&replica_url)
+            .use_read_replica();
+        let rows: Vec<Value> = client.select("id").execute().await.unwrap();
+        assert_eq!(rows, vec![json!({ "id": 1 })]);
+
+        let rpc = PostgrestClient::rpc(
+            &primary.uri(),
+            "fake-key",
+            "lookup",
+            json!({}),
+            Client::new(),
+        )
+        .with_read_replica(&replica_url)
+        .use_read_replica();
+        assert_eq!(rpc.call_rpc_get::<i64>().await.unwrap(), 1);
+        assert!(primary.received_requests().await.unwrap().is_empty());
+
+        // ルーティングを指定しなければレプリカの URL を設定してもプライマリに送る
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/items"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .expect(1)
+            .mount(&primary)
+            .await;
+        let client = PostgrestClient::new(&primary.uri(), "fake-key", "items", Client::new())
+            .with_read_replica(&replica_url);
+        client.execute::<Value>().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_writes_go_to_the_primary_when_reads_are_routed() {
+        let primary = MockServer::start().await;
+        let replica = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/items"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!([{ "id": 1 }])))
+            .expect(1)
+            .mount(&primary)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/rest/v1/items"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .expect(1)
+            .mount(&primary)
+            .await;
+
+        let client = || {
+            PostgrestClient::new(&primary.uri(), "fake-key", "items", Client::new())
+                .with_read_replica(&replica.uri())
+                .route_to(ReadPreference::Replica)
+        };
+        client().insert(json!({ "name": "a" })).await.unwrap();
+        client()
+            .eq("id", "1")
+            .update(json!({ "name": "b" }))
+            .await
+            .unwrap();
+        assert!(replica.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_replica_only_client_rejects_writes() {
+        let primary = MockServer::start().await;
+        let replica = MockServer::start().await;
+        let client = || {
+            PostgrestClient::new(&primary.uri(), "fake-key", "items", Client::new())
+                .with_read_replica(&replica.uri())
+                .route_to(ReadPreference::ReplicaOnly)
+        };
+
+        let error = client().insert(json!({ "name": "a" })).await.unwrap_err();
+        assert!(
+            matches!(&error, PostgrestError::WriteOnReadReplica { operation } if operation == "insert"),
+            "{:?}",
+            error
+        );
+        assert!(matches!(
+            client().eq("id", "1").delete().await,
+            Err(PostgrestError::WriteOnReadReplica { .. })
+        ));
+        assert!(matches!(
+            client().begin_transaction(None, None, None).await,
+            Err(PostgrestError::WriteOnReadReplica { .. })
+        ));
+        let rpc =
+            PostgrestClient::rpc(&primary.uri(), "fake-key", "bump", json!({}), Client::new())
+                .route_to(ReadPreference::ReplicaOnly);
+        assert!(matches!(
+            rpc.call_rpc::<Value>().await,
+            Err(PostgrestError::WriteOnReadReplica { .. })
+        ));
+        assert!(primary.received_requests().await.unwrap().is_empty());
+        assert!(replica.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_default_headers_reach_queries_and_transactions() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/v1/items"))
+            .and(header("x-tenant-id", "tenant-a"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/begin_transaction"))
+            .and(header("x-tenant-id", "tenant-a"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "transaction_id": "tx-1" })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rpc/commit_transaction"))
+            .and(header("x-tenant-id", "tenant-a"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let mut tenant = HeaderMap::new();
+        tenant.insert("x-tenant-id", HeaderValue::from_static("tenant-a"));
+        let client = PostgrestClient::new(&mock_server.uri(), "fake-key", "items", Client::new())
+            .with_default_headers(tenant);
+        client.execute::<Value>().await.unwrap();
+        let transaction = client.begin_transaction(None, None, None).await.unwrap();
+        transaction.commit().await.unwrap();
+    }
+
+    #[derive(Serialize)]
+    struct DocumentRow {
+        id: u32,
+        title: String,
+        search_vector: Option<String>,
+    }
+
+    fn document(id: u32) -> DocumentRow {
+        DocumentRow {
+            id,
+            title: format!("doc-{}", id),
+            search_vector: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_omit_columns_strips_single_rows_and_batches() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/documents"))
+            .and(body_json(json!({ "title": "doc-1" })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!([{ "id": 1 }])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/documents"))
+            .and(body_json(
+                json!([{ "title": "doc-2" }, { "title": "doc-3" }]),
+            ))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!([])))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "documents",
+            reqwest::Client::new(),
+        )
+        .omit_columns(&["id", "search_vector"]);
+        client.insert(document(1)).await.unwrap();
+        client.upsert(vec![document(2), document(3)]).await.unwrap();
+        let report = client
+            .insert_from_iter([document(2), document(3)], 10)
+            .await
+            .unwrap();
+        assert_eq!(report.rows, 2);
+    }
+
+    #[tokio::test]
+    async fn test_only_columns_keeps_listed_keys() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/rest/v1/documents"))
+            .and(body_json(json!([{ "id": 1, "title": "doc-1" }])))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!([])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "documents",
+            reqwest::Client::new(),
+        )
+        .only_columns(&["id", "title"])
+        .insert(vec![document(1)])
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_omit_columns_rejects_non_object_rows() {
+        let mock_server = MockServer::start().await;
+        let client = PostgrestClient::new(
+            &mock_server.uri(),
+            "fake-key",
+            "documents",
+            reqwest::Client::new(),
+        )
+        .omit_columns(&["id"]);
+
+        let error = client
+            .insert(json!([{ "title": "a" }, "b"]))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            PostgrestError::RowNotAnObject {
+                index: 1,
+                found: "a string"
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Row 1 is a string, not a JSON object; omit_columns / only_columns need object rows"
+        );
+        assert!(matches!(
+            client.insert(json!([[1, 2]])).await,
+            Err(PostgrestError::RowNotAnObject {
+                index: 0,
+                found: "an array"
+            })
+        ));
+        assert!(mock_server.received_requests().await.unwrap().is_empty());
+    }
 }
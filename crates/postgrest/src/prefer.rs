@@ -0,0 +1,144 @@
+//! `Prefer` ヘッダーの組み立て
+//!
+//! 各設定はフィールドとして保持し、リクエスト時に1つのヘッダーにまとめる。
+//! `with_header("Prefer", ...)` で指定された値は、同じキーの設定がない場合のみ後ろに加える。
+
+use crate::PostgrestError;
+use reqwest::header::{HeaderMap, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+const PREFER: &str = "prefer";
+
+/// 書き込み結果の返し方 (`Prefer: return=...`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReturnPreference {
+    /// 書き込んだ行を返す
+    Representation,
+    /// 本文を返さない
+    Minimal,
+    /// `Location` ヘッダーのみ返す
+    HeadersOnly,
+}
+
+impl ReturnPreference {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReturnPreference::Representation => "representation",
+            ReturnPreference::Minimal => "minimal",
+            ReturnPreference::HeadersOnly => "headers-only",
+        }
+    }
+}
+
+/// 行数の数え方 (`Prefer: count=...`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CountMethod {
+    Exact,
+    Planned,
+    Estimated,
+}
+
+impl CountMethod {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CountMethod::Exact => "exact",
+            CountMethod::Planned => "planned",
+            CountMethod::Estimated => "estimated",
+        }
+    }
+}
+
+/// 不正な設定やフィルター値の扱い (`Prefer: handling=...`、PostgREST 12 以降)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Handling {
+    /// エラーにする
+    Strict,
+    /// 無視する
+    Lenient,
+}
+
+impl Handling {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Handling::Strict => "strict",
+            Handling::Lenient => "lenient",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Preferences {
+    pub(crate) returning: Option<ReturnPreference>,
+    pub(crate) count: Option<CountMethod>,
+    pub(crate) resolution: Option<&'static str>,
+    pub(crate) missing_default: bool,
+    pub(crate) handling: Option<Handling>,
+    pub(crate) timezone: Option<String>,
+    /// `tx=rollback` (実行後にロールバックする)
+    pub(crate) rollback: bool,
+}
+
+impl Preferences {
+    fn entries(&self) -> Vec<String> {
+        let mut entries = Vec::new();
+        if let Some(returning) = self.returning {
+            entries.push(format!("return={}", returning.as_str()));
+        }
+        if let Some(count) = self.count {
+            entries.push(format!("count={}", count.as_str()));
+        }
+        if let Some(resolution) = self.resolution {
+            entries.push(format!("resolution={}", resolution));
+        }
+        if self.missing_default {
+            entries.push("missing=default".to_string());
+        }
+        if let Some(handling) = self.handling {
+            entries.push(format!("handling={}", handling.as_str()));
+        }
+        if let Some(timezone) = &self.timezone {
+            entries.push(format!("timezone={}", timezone));
+        }
+        if self.rollback {
+            entries.push("tx=rollback".to_string());
+        }
+        entries
+    }
+
+    /// `headers` の Prefer ヘッダーを設定値で置き換える
+    pub(crate) fn apply(&self, headers: &mut HeaderMap) -> Result<(), PostgrestError> {
+        let mut entries = self.entries();
+        let keys: Vec<String> = entries
+            .iter()
+            .map(|entry| key_of(entry).to_string())
+            .collect();
+        for value in headers.get_all(PREFER) {
+            let value = value.to_str().map_err(|_| {
+                PostgrestError::InvalidParameters("Invalid Prefer header".to_string())
+            })?;
+            for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                if !keys.iter().any(|key| key == key_of(entry)) {
+                    entries.push(entry.to_string());
+                }
+            }
+        }
+
+        headers.remove(PREFER);
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let value = entries.join(", ");
+        let value = HeaderValue::from_str(&value).map_err(|_| {
+            PostgrestError::InvalidParameters(format!("Invalid Prefer header: {}", value))
+        })?;
+        headers.insert(PREFER, value);
+        Ok(())
+    }
+}
+
+fn key_of(entry: &str) -> &str {
+    entry.split('=').next().unwrap_or_default().trim()
+}
@@ -0,0 +1,48 @@
+//! 有効期限切れトークンの再取得
+//!
+//! [`PostgrestClient::with_token_refresher`](crate::PostgrestClient::with_token_refresher) を
+//! 設定したクライアントは、`401` と `JWT expired` を受け取るとトークンを再取得し、
+//! 新しい `Authorization` ヘッダーでリクエストを 1 回だけ再送する。
+//! 再送も失敗した場合は最初のエラーを返す。
+
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderValue};
+
+/// 期限切れのアクセストークンに代わるトークンを返す
+#[async_trait]
+pub trait TokenRefresher: Send + Sync {
+    /// `expired_token` で送ったリクエストが `JWT expired` で拒否された
+    ///
+    /// 新しいトークンを取得できなければ `None` を返す (最初のエラーがそのまま返る)。
+    async fn refresh(&self, expired_token: &str) -> Option<String>;
+}
+
+// 401 の応答がトークンの有効期限切れによるものか
+//
+// PostgREST はメッセージに `JWT expired` を入れ、`WWW-Authenticate` の
+// `error_description` にも同じ文言を返す。
+pub(crate) fn is_jwt_expired(headers: &HeaderMap, body: &str) -> bool {
+    let www_authenticate = headers
+        .get(reqwest::header::WWW_AUTHENTICATE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    [www_authenticate, body]
+        .iter()
+        .any(|text| text.to_ascii_lowercase().contains("jwt expired"))
+}
+
+// `Authorization: Bearer ...` からトークンを取り出す
+pub(crate) fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(reqwest::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+// `Authorization` ヘッダーを新しいトークンに差し替える (不正な値なら `None`)
+pub(crate) fn with_bearer_token(mut headers: HeaderMap, token: &str) -> Option<HeaderMap> {
+    let value = HeaderValue::from_str(&format!("Bearer {}", token)).ok()?;
+    headers.insert(reqwest::header::AUTHORIZATION, value);
+    Some(headers)
+}
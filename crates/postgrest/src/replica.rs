@@ -0,0 +1,23 @@
+//! 読み取りレプリカへのルーティング
+//!
+//! [`PostgrestClient::with_read_replica`](crate::PostgrestClient::with_read_replica) で
+//! レプリカのベース URL を設定し、[`PostgrestClient::route_to`](crate::PostgrestClient::route_to)
+//! で読み取りクエリの送信先を選ぶ。書き込み・RPC (POST)・トランザクションは常にプライマリに送る。
+
+/// 読み取りクエリの送信先
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadPreference {
+    /// すべてのリクエストをプライマリに送る (既定)
+    #[default]
+    Primary,
+    /// 読み取りはレプリカ、書き込みはプライマリに送る
+    Replica,
+    /// 読み取りはレプリカに送り、書き込みは `WriteOnReadReplica` エラーにする
+    ReplicaOnly,
+}
+
+impl ReadPreference {
+    pub(crate) fn reads_from_replica(self) -> bool {
+        !matches!(self, ReadPreference::Primary)
+    }
+}
@@ -0,0 +1,291 @@
+//! トランザクション内のセーブポイントのスコープと一括実行
+//!
+//! [`PostgrestTransaction::savepoint_scope`] が返す [`SavepointGuard`] は作成順を記録し、
+//! 解放済み (または外側のセーブポイントへのロールバックで破棄された) セーブポイントへの
+//! 操作を HTTP リクエストの前にローカルで拒否する。
+//! [`PostgrestTransaction::execute_batch`] は [`Operation`] を順に実行し、最初の失敗で止まる。
+
+use crate::{FilterValue, PostgrestError, PostgrestTransaction};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::atomic::Ordering;
+use supabase_rust_core::{RequestIds, SendWithRequestId};
+
+/// 有効なセーブポイント (作成順)
+#[derive(Debug, Default)]
+pub(crate) struct SavepointStack {
+    next_id: u64,
+    live: Vec<(u64, String)>,
+}
+
+impl SavepointStack {
+    fn push(&mut self, name: &str) -> u64 {
+        self.next_id += 1;
+        self.live.push((self.next_id, name.to_string()));
+        self.next_id
+    }
+
+    fn position(&self, id: u64) -> Option<usize> {
+        self.live.iter().position(|(live_id, _)| *live_id == id)
+    }
+
+    // `id` とそれ以降に作成したセーブポイントを破棄する (破棄したら true)
+    fn truncate_from(&mut self, id: u64) -> bool {
+        match self.position(id) {
+            Some(index) => {
+                self.live.truncate(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// `savepoint_scope` で作成したセーブポイント
+///
+/// `rollback` も `release` もせずにドロップすると解放する (tokio ランタイム内でドロップした場合のみ送信)。
+#[must_use = "dropping the guard releases the savepoint immediately"]
+pub struct SavepointGuard<'a> {
+    transaction: &'a PostgrestTransaction,
+    name: String,
+    id: u64,
+    finished: bool,
+}
+
+impl SavepointGuard<'_> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// セーブポイントまでロールバック (以降に作成したセーブポイントも破棄される)
+    pub async fn rollback(mut self) -> Result<(), PostgrestError> {
+        self.ensure_live("rollback to")?;
+        self.transaction
+            .savepoint_rpc("rollback_to_savepoint", &self.name, "rollback to")
+            .await?;
+        self.finish();
+        Ok(())
+    }
+
+    /// セーブポイントを解放 (以降に作成したセーブポイントも解放される)
+    pub async fn release(mut self) -> Result<(), PostgrestError> {
+        self.ensure_live("release")?;
+        self.transaction
+            .savepoint_rpc("release_savepoint", &self.name, "release")
+            .await?;
+        self.finish();
+        Ok(())
+    }
+
+    fn ensure_live(&mut self, action: &str) -> Result<(), PostgrestError> {
+        let live = self
+            .transaction
+            .savepoints
+            .lock()
+            .unwrap()
+            .position(self.id)
+            .is_some();
+        if live {
+            return Ok(());
+        }
+        self.finished = true;
+        Err(PostgrestError::TransactionError(format!(
+            "Cannot {} savepoint '{}': it was already released or rolled back",
+            action, self.name
+        )))
+    }
+
+    fn finish(&mut self) {
+        self.transaction
+            .savepoints
+            .lock()
+            .unwrap()
+            .truncate_from(self.id);
+        self.finished = true;
+    }
+}
+
+impl Drop for SavepointGuard<'_> {
+    fn drop(&mut self) {
+        if self.finished
+            || !self
+                .transaction
+                .savepoints
+                .lock()
+                .unwrap()
+                .truncate_from(self.id)
+        {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            log::warn!(
+                "Savepoint '{}' dropped outside a tokio runtime; it stays open until the transaction ends",
+                self.name
+            );
+            return;
+        };
+        let request = self
+            .transaction
+            .savepoint_request("release_savepoint", &self.name);
+        let name = self.name.clone();
+        runtime.spawn(async move {
+            if let Err(e) = request.send_with_request_id().await {
+                log::warn!("Failed to release savepoint '{}': {}", name, e);
+            }
+        });
+    }
+}
+
+/// `execute_batch` で実行する書き込み
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    Insert {
+        table: String,
+        values: Value,
+    },
+    Update {
+        table: String,
+        values: Value,
+        /// (カラム, `operator.value`)
+        filters: Vec<(String, String)>,
+    },
+    Delete {
+        table: String,
+        filters: Vec<(String, String)>,
+    },
+}
+
+impl Operation {
+    pub fn insert<T: Serialize>(table: &str, values: &T) -> Result<Self, PostgrestError> {
+        Ok(Self::Insert {
+            table: table.to_string(),
+            values: serde_json::to_value(values)?,
+        })
+    }
+
+    pub fn update<T: Serialize>(table: &str, values: &T) -> Result<Self, PostgrestError> {
+        Ok(Self::Update {
+            table: table.to_string(),
+            values: serde_json::to_value(values)?,
+            filters: Vec::new(),
+        })
+    }
+
+    pub fn delete(table: &str) -> Self {
+        Self::Delete {
+            table: table.to_string(),
+            filters: Vec::new(),
+        }
+    }
+
+    /// 型付きの等価フィルターを追加 (`Insert` では無視される)
+    pub fn eq_val<T: Into<FilterValue>>(mut self, column: &str, value: T) -> Self {
+        if let Self::Update { filters, .. } | Self::Delete { filters, .. } = &mut self {
+            filters.push((column.to_string(), value.into().to_filter("eq")));
+        }
+        self
+    }
+}
+
+impl PostgrestTransaction {
+    /// 作成順を追跡するセーブポイントを作成
+    pub async fn savepoint_scope(&self, name: &str) -> Result<SavepointGuard<'_>, PostgrestError> {
+        self.savepoint(name).await?;
+        let id = self.savepoints.lock().unwrap().push(name);
+        Ok(SavepointGuard {
+            transaction: self,
+            name: name.to_string(),
+            id,
+            finished: false,
+        })
+    }
+
+    /// セーブポイントを解放
+    pub async fn release_savepoint(&self, name: &str) -> Result<(), PostgrestError> {
+        self.savepoint_rpc("release_savepoint", name, "release")
+            .await
+    }
+
+    /// 操作を順に実行し、最初に失敗した操作で止まる
+    ///
+    /// 成功した場合は各操作の結果を返す。失敗した場合は `BatchOperationFailed` に
+    /// 操作の位置 (0 始まり) が入る。ロールバックするかどうかは呼び出し側が決める。
+    pub async fn execute_batch(&self, ops: Vec<Operation>) -> Result<Vec<Value>, PostgrestError> {
+        let mut results = Vec::with_capacity(ops.len());
+        for (index, op) in ops.into_iter().enumerate() {
+            let result = match op {
+                Operation::Insert { table, values } => self.from(&table).insert(values).await,
+                Operation::Update {
+                    table,
+                    values,
+                    filters,
+                } => {
+                    let mut client = self.from(&table);
+                    client.query_params.extend(filters);
+                    client.update(values).await
+                }
+                Operation::Delete { table, filters } => {
+                    let mut client = self.from(&table);
+                    client.query_params.extend(filters);
+                    client.delete().await
+                }
+            };
+            match result {
+                Ok(value) => results.push(value),
+                Err(e) => {
+                    return Err(PostgrestError::BatchOperationFailed {
+                        index,
+                        source: Box::new(e),
+                    })
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    fn savepoint_request(&self, function: &str, name: &str) -> reqwest::RequestBuilder {
+        self.http_client
+            .post(format!("{}/rpc/{}", self.base_url, function))
+            .headers(self.headers.clone())
+            .json(&json!({
+                "transaction_id": self.transaction_id,
+                "name": name
+            }))
+    }
+
+    async fn savepoint_rpc(
+        &self,
+        function: &str,
+        name: &str,
+        action: &str,
+    ) -> Result<(), PostgrestError> {
+        if !self.state.load(Ordering::SeqCst) {
+            return Err(PostgrestError::TransactionError(format!(
+                "Cannot {} savepoint: transaction is no longer active",
+                action
+            )));
+        }
+
+        let response = self
+            .savepoint_request(function, name)
+            .send_with_request_id()
+            .await
+            .map_err(PostgrestError::NetworkError)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let request_ids = RequestIds::from_response(&response);
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Failed to read error response".to_string()),
+            );
+            return Err(PostgrestError::TransactionError(format!(
+                "Failed to {} savepoint '{}': {} (Status: {}){}",
+                action, name, error_text, status, request_ids
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+//! `resolution=ignore-duplicates` のアップサートでスキップされた行の特定
+//!
+//! PostgREST は `ON CONFLICT DO NOTHING` でスキップした行を表現 (`return=representation`)
+//! から省くだけなので、
+//! [`PostgrestClient::upsert_report`](crate::PostgrestClient::upsert_report) は返された行と
+//! 入力行をキーカラムで突き合わせ、返されなかった入力行のキーを [`UpsertReport`] にまとめる。
+//!
+//! キーの値は正規化して比較する。数値とその10進表記の文字列 (`42`・`42.0` と `"42"`) は
+//! 同じキーとみなす (bigint の ID を文字列で送っても、PostgREST は数値で返すため)。
+
+use crate::PostgrestError;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// `upsert_report` の結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UpsertReport {
+    /// 挿入された行 (サーバーが返した表現)
+    pub inserted: Vec<Value>,
+    /// 既存の行と衝突してスキップされた入力行のキー (入力の順)
+    ///
+    /// キーカラムが1つならその値、複数なら `{"カラム": 値, ...}` のオブジェクト。
+    /// 値は入力行のものをそのまま使う。
+    pub skipped_keys: Vec<Value>,
+}
+
+impl UpsertReport {
+    /// すべての入力行がスキップされたかどうか
+    pub fn all_skipped(&self) -> bool {
+        self.inserted.is_empty() && !self.skipped_keys.is_empty()
+    }
+}
+
+/// 入力行のキー
+pub(crate) struct InputKey {
+    /// `skipped_keys` に入れる値
+    value: Value,
+    normalized: Vec<String>,
+}
+
+/// 入力行からキーを取り出す (キーカラムがない、または null の行はエラー)
+pub(crate) fn input_keys(
+    rows: &[Value],
+    key_columns: &[&str],
+) -> Result<Vec<InputKey>, PostgrestError> {
+    if key_columns.is_empty() {
+        return Err(PostgrestError::InvalidParameters(
+            "upsert_report needs at least one key column".to_string(),
+        ));
+    }
+    rows.iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let Value::Object(fields) = row else {
+                return Err(PostgrestError::RowSerializationError {
+                    index,
+                    message: "row is not a JSON object".to_string(),
+                });
+            };
+            let mut key = Map::new();
+            let mut normalized = Vec::with_capacity(key_columns.len());
+            for column in key_columns {
+                match fields.get(*column) {
+                    Some(value) if !value.is_null() => {
+                        normalized.push(normalize(value));
+                        key.insert(column.to_string(), value.clone());
+                    }
+                    _ => {
+                        return Err(PostgrestError::MissingKeyColumn {
+                            index,
+                            column: column.to_string(),
+                        })
+                    }
+                }
+            }
+            let value = match key_columns {
+                [column] => key.remove(*column).unwrap_or(Value::Null),
+                _ => Value::Object(key),
+            };
+            Ok(InputKey { value, normalized })
+        })
+        .collect()
+}
+
+/// 返された行と突き合わせ、返されなかった入力行のキーを返す
+///
+/// 同じキーの入力行が複数ある場合、返された行の数だけを挿入済みとみなし、残りはスキップとする。
+pub(crate) fn skipped_keys(
+    inputs: Vec<InputKey>,
+    returned: &[Value],
+    key_columns: &[&str],
+) -> Result<Vec<Value>, PostgrestError> {
+    let mut remaining: HashMap<Vec<String>, usize> = HashMap::new();
+    for (index, row) in returned.iter().enumerate() {
+        let normalized = key_columns
+            .iter()
+            .map(|column| match row.get(*column) {
+                Some(value) if !value.is_null() => Ok(normalize(value)),
+                _ => Err(PostgrestError::DeserializationError(format!(
+                    "returned row {} has no value for key column {} \
+                     (include the key columns in select())",
+                    index, column
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        *remaining.entry(normalized).or_default() += 1;
+    }
+
+    let mut skipped = Vec::new();
+    for input in inputs {
+        match remaining.get_mut(&input.normalized) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => skipped.push(input.value),
+        }
+    }
+    Ok(skipped)
+}
+
+// 整数値の数値を `"42"` の形にそろえる (文字列はそのまま。`"0042"` は 42 と一致しない)
+fn normalize(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Number(number) => match (number.as_i64(), number.as_u64(), number.as_f64()) {
+            (Some(number), _, _) => number.to_string(),
+            (_, Some(number), _) => number.to_string(),
+            (_, _, Some(number)) if number.fract() == 0.0 && number.abs() < 1e18 => {
+                (number as i64).to_string()
+            }
+            _ => number.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn skipped(rows: Value, returned: Value, key_columns: &[&str]) -> Vec<Value> {
+        let rows = rows.as_array().unwrap();
+        let inputs = input_keys(rows, key_columns).unwrap();
+        skipped_keys(inputs, returned.as_array().unwrap(), key_columns).unwrap()
+    }
+
+    #[test]
+    fn test_single_key_normalizes_strings_and_numbers() {
+        let rows = json!([
+            { "id": "1", "name": "a" },
+            { "id": 2, "name": "b" },
+            { "id": 3.0, "name": "c" },
+            { "id": "0042", "name": "d" }
+        ]);
+        let returned = json!([{ "id": 1, "name": "a" }, { "id": "3" }]);
+        assert_eq!(
+            skipped(rows, returned, &["id"]),
+            vec![json!(2), json!("0042")]
+        );
+    }
+
+    #[test]
+    fn test_composite_keys_and_duplicates() {
+        let rows = json!([
+            { "org": "acme", "sku": 1, "qty": 5 },
+            { "org": "acme", "sku": 2, "qty": 1 },
+            { "org": "acme", "sku": 1, "qty": 7 },
+            { "org": "other", "sku": 1, "qty": 2 }
+        ]);
+        let returned = json!([
+            { "org": "acme", "sku": 1, "qty": 5 },
+            { "org": "other", "sku": "1", "qty": 2 }
+        ]);
+        assert_eq!(
+            skipped(rows, returned, &["org", "sku"]),
+            vec![
+                json!({ "org": "acme", "sku": 2 }),
+                json!({ "org": "acme", "sku": 1 })
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_keys_are_compared_as_is() {
+        let rows = json!([{ "code": "AB" }, { "code": "ab" }]);
+        let returned = json!([{ "code": "ab" }]);
+        assert_eq!(skipped(rows, returned, &["code"]), vec![json!("AB")]);
+    }
+
+    #[test]
+    fn test_missing_or_null_key_is_an_error() {
+        let rows = json!([{ "id": 1 }, { "name": "no id" }]);
+        match input_keys(rows.as_array().unwrap(), &["id"]) {
+            Err(PostgrestError::MissingKeyColumn { index, column }) => {
+                assert_eq!(index, 1);
+                assert_eq!(column, "id");
+            }
+            _ => panic!("expected MissingKeyColumn"),
+        }
+        let rows = json!([{ "org": "acme", "sku": null }]);
+        assert!(matches!(
+            input_keys(rows.as_array().unwrap(), &["org", "sku"]),
+            Err(PostgrestError::MissingKeyColumn { index: 0, .. })
+        ));
+        assert!(matches!(
+            input_keys(rows.as_array().unwrap(), &[]),
+            Err(PostgrestError::InvalidParameters(_))
+        ));
+    }
+
+    #[test]
+    fn test_returned_rows_without_keys_are_an_error() {
+        let rows = json!([{ "id": 1 }]);
+        let inputs = input_keys(rows.as_array().unwrap(), &["id"]).unwrap();
+        assert!(matches!(
+            skipped_keys(
+                inputs,
+                json!([{ "name": "a" }]).as_array().unwrap(),
+                &["id"]
+            ),
+            Err(PostgrestError::DeserializationError(_))
+        ));
+    }
+}
@@ -0,0 +1,163 @@
+//! join の応答に含まれる postgres_changes バインディングの確認
+//!
+//! サーバーは join の `phx_reply` の `response.postgres_changes` で、受け付けたバインディングを返す。
+//! RLS やパブリケーションの設定が原因で受け付けられなかった場合でも join 自体は成功するため、
+//! 要求したバインディングと突き合わせて [`SubscriptionWarning`] にする。
+
+use crate::limits::DroppedMessage;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+
+/// postgres_changes のトラブルシューティング
+pub const POSTGRES_CHANGES_DOCS_URL: &str =
+    "https://supabase.com/docs/guides/realtime/postgres-changes";
+
+/// サーバーが受け付けた postgres_changes のバインディング
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ConfirmedBinding {
+    /// サーバーが振ったバインディングID (イベントの `ids` と対応)
+    pub id: Option<u64>,
+    pub event: String,
+    pub schema: String,
+    pub table: String,
+    pub filter: Option<String>,
+}
+
+/// 購読がイベントを受け取れない可能性があることを示す警告
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubscriptionWarning {
+    /// サーバーが postgres_changes のバインディングを1つも受け付けなかった
+    NoBindingsConfirmed {
+        /// 要求したバインディング (`INSERT on public.todos (filter: ...)` 形式)
+        requested: Vec<String>,
+    },
+    /// 要求したバインディングの一部が受け付けられなかった
+    BindingNotConfirmed { binding: String },
+    /// サーバーがバインディングにエラーを返した
+    BindingError { binding: String, message: String },
+    /// 上限を超えたメッセージを捨てた (このチャンネル宛てだったとは限らない)
+    MessageDropped(DroppedMessage),
+}
+
+impl fmt::Display for SubscriptionWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoBindingsConfirmed { requested } => write!(
+                f,
+                "server acknowledged none of the postgres_changes bindings [{}]; \
+                 no events will be delivered. Check that the table is in the \
+                 supabase_realtime publication and that RLS allows select (see {})",
+                requested.join(", "),
+                POSTGRES_CHANGES_DOCS_URL
+            ),
+            Self::BindingNotConfirmed { binding } => write!(
+                f,
+                "server did not acknowledge postgres_changes binding {}; \
+                 it will not deliver events (see {})",
+                binding, POSTGRES_CHANGES_DOCS_URL
+            ),
+            Self::BindingError { binding, message } => write!(
+                f,
+                "server rejected postgres_changes binding {}: {} (see {})",
+                binding, message, POSTGRES_CHANGES_DOCS_URL
+            ),
+            Self::MessageDropped(dropped) => dropped.fmt(f),
+        }
+    }
+}
+
+// `INSERT on public.todos (filter: user_id=eq.1)`
+fn describe(binding: &Value) -> String {
+    let field = |name: &str| binding.get(name).and_then(Value::as_str);
+    let mut text = format!(
+        "{} on {}.{}",
+        field("event").unwrap_or("*"),
+        field("schema").unwrap_or("public"),
+        field("table").unwrap_or("*")
+    );
+    if let Some(filter) = field("filter") {
+        text.push_str(&format!(" (filter: {})", filter));
+    }
+    text
+}
+
+fn same_binding(requested: &Value, confirmed: &ConfirmedBinding) -> bool {
+    let field = |name: &str| requested.get(name).and_then(Value::as_str);
+    field("event").is_some_and(|event| event.eq_ignore_ascii_case(&confirmed.event))
+        && field("schema") == Some(confirmed.schema.as_str())
+        && field("table") == Some(confirmed.table.as_str())
+        && field("filter") == confirmed.filter.as_deref()
+}
+
+/// join の応答 (`phx_reply` の payload) と要求したバインディングを突き合わせる
+pub(crate) fn check_join_reply(
+    requested: &[Value],
+    reply: &Value,
+) -> (Vec<ConfirmedBinding>, Vec<SubscriptionWarning>) {
+    let entries = reply
+        .pointer("/response/postgres_changes")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    let mut confirmed = Vec::new();
+    let mut warnings = Vec::new();
+    for entry in &entries {
+        if let Some(message) = entry.get("error") {
+            warnings.push(SubscriptionWarning::BindingError {
+                binding: describe(entry),
+                message: message
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| message.to_string()),
+            });
+            continue;
+        }
+        match serde_json::from_value::<ConfirmedBinding>(entry.clone()) {
+            Ok(binding) => confirmed.push(binding),
+            Err(e) => log::debug!("ignoring unrecognized postgres_changes entry: {}", e),
+        }
+    }
+
+    if requested.is_empty() {
+        return (confirmed, warnings);
+    }
+    if confirmed.is_empty() && warnings.is_empty() {
+        warnings.push(SubscriptionWarning::NoBindingsConfirmed {
+            requested: requested.iter().map(describe).collect(),
+        });
+        return (confirmed, warnings);
+    }
+    for binding in requested {
+        let acknowledged = confirmed.iter().any(|c| same_binding(binding, c))
+            || entries
+                .iter()
+                .any(|entry| entry.get("error").is_some() && describe(entry) == describe(binding));
+        if !acknowledged {
+            warnings.push(SubscriptionWarning::BindingNotConfirmed {
+                binding: describe(binding),
+            });
+        }
+    }
+    (confirmed, warnings)
+}
+
+/// `system` メッセージや `phx_reply` の payload から購読エラーの内容を取り出す
+///
+/// `{"status": "error", "extension": "postgres_changes", "message": "..."}` や
+/// `{"status": "error", "response": {"reason": "..."}}` の形式。
+pub(crate) fn subscribe_error(payload: &Value) -> Option<String> {
+    if payload.get("status").and_then(Value::as_str) != Some("error") {
+        return None;
+    }
+    let message = payload
+        .get("message")
+        .or_else(|| payload.pointer("/response/reason"))
+        .or_else(|| payload.get("response"))
+        .map(|message| match message {
+            Value::String(text) => text.clone(),
+            other => other.to_string(),
+        });
+    Some(message.unwrap_or_else(|| "unknown error".to_string()))
+}
@@ -0,0 +1,160 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// カラム情報 (postgres_changes ペイロードの `columns`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnInfo {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub column_type: String,
+}
+
+/// 一部のカラムしか含まれない可能性があるレコード
+///
+/// UPDATE/DELETE の `old` は、テーブルが `REPLICA IDENTITY FULL` でない場合は
+/// 主キーのカラムしか含まない。そのため `T` へのデシリアライズに失敗しても
+/// エラーにはせず、届いたカラムをそのまま保持する。
+#[derive(Debug, Clone)]
+pub struct PartialRecord<T> {
+    fields: Map<String, Value>,
+    record: Option<T>,
+    missing: Vec<String>,
+}
+
+impl<T: DeserializeOwned> PartialRecord<T> {
+    /// JSON 値からレコードを作成 (`expected_columns` は本来あるべきカラム名)
+    pub fn from_value(value: Value, expected_columns: &[String]) -> Self {
+        let fields = match value {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        };
+        let missing = expected_columns
+            .iter()
+            .filter(|column| !fields.contains_key(column.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+        let record = if fields.is_empty() {
+            None
+        } else {
+            serde_json::from_value(Value::Object(fields.clone())).ok()
+        };
+
+        Self {
+            fields,
+            record,
+            missing,
+        }
+    }
+}
+
+impl<T> PartialRecord<T> {
+    /// 一部のカラムしか届いていないかどうか
+    pub fn is_partial(&self) -> bool {
+        self.record.is_none() || !self.missing.is_empty()
+    }
+
+    /// カラムが一つも届いていないかどうか (INSERT の `old` など)
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// 完全なレコード (`T` にデシリアライズできた場合のみ)
+    pub fn record(&self) -> Option<&T> {
+        self.record.as_ref()
+    }
+
+    /// 完全なレコードを取り出す
+    pub fn into_record(self) -> Option<T> {
+        self.record
+    }
+
+    /// 指定したカラムの値を取得
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        self.fields.get(column)
+    }
+
+    /// 届いたカラムの一覧
+    pub fn fields(&self) -> &Map<String, Value> {
+        &self.fields
+    }
+
+    /// 届かなかったカラムの一覧 (`REPLICA IDENTITY FULL` でないテーブルで発生)
+    pub fn missing_columns(&self) -> &[String] {
+        &self.missing
+    }
+}
+
+#[derive(Deserialize)]
+struct RawPostgresChanges {
+    schema: String,
+    table: String,
+    #[serde(default)]
+    commit_timestamp: Option<String>,
+    #[serde(rename = "type", alias = "eventType")]
+    event_type: String,
+    #[serde(default, alias = "new")]
+    record: Value,
+    #[serde(default, alias = "old")]
+    old_record: Value,
+    #[serde(default)]
+    columns: Vec<ColumnInfo>,
+    #[serde(default)]
+    errors: Option<Vec<String>>,
+}
+
+/// 型付きの postgres_changes ペイロード
+#[derive(Debug, Clone)]
+pub struct PostgresChangesPayload<T> {
+    pub schema: String,
+    pub table: String,
+    pub commit_timestamp: Option<String>,
+    /// `INSERT` / `UPDATE` / `DELETE`
+    pub event_type: String,
+    pub new: Option<T>,
+    pub old: PartialRecord<T>,
+    pub columns: Vec<ColumnInfo>,
+    /// RLS などによりカラムデータを配信できなかった場合のサーバーエラー
+    pub errors: Vec<String>,
+}
+
+impl<T> PostgresChangesPayload<T> {
+    /// サーバーがエラーを含めて配信したかどうか
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+}
+
+impl<T: DeserializeOwned> PostgresChangesPayload<T> {
+    /// チャンネルのペイロードから変換 (`{"data": {...}, "ids": [...]}` 形式にも対応)
+    pub fn from_value(value: &Value) -> Result<Self, serde_json::Error> {
+        let data = match value.get("data") {
+            Some(inner) if inner.get("table").is_some() => inner,
+            _ => value,
+        };
+        let raw: RawPostgresChanges = serde_json::from_value(data.clone())?;
+
+        let expected_columns = raw
+            .columns
+            .iter()
+            .map(|column| column.name.clone())
+            .collect::<Vec<_>>();
+        let new = match raw.record {
+            Value::Object(map) if !map.is_empty() => {
+                Some(serde_json::from_value(Value::Object(map))?)
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            schema: raw.schema,
+            table: raw.table,
+            commit_timestamp: raw.commit_timestamp,
+            event_type: raw.event_type,
+            new,
+            old: PartialRecord::from_value(raw.old_record, &expected_columns),
+            columns: raw.columns,
+            errors: raw.errors.unwrap_or_default(),
+        })
+    }
+}
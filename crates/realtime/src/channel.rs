@@ -1,14 +1,25 @@
+use crate::bindings::{self, ConfirmedBinding, SubscriptionWarning};
 use crate::client::RealtimeClient; // Removed unused ConnectionState
-use crate::error::RealtimeError;
+use crate::error::{HandlerError, RealtimeError};
 use crate::filters::{DatabaseFilter, FilterOperator};
-use crate::message::{ChannelEvent, Payload, PresenceChange, RealtimeMessage};
-use log::{debug, error, info, trace}; // Removed unused warn
+use crate::limits::DroppedMessage;
+use crate::message::{
+    ChannelEvent, Payload, PresenceChange, PresenceEvent, PresenceState, RealtimeMessage,
+};
+use crate::stats::{ChannelMetrics, ChannelStats};
+use crate::stream::{
+    BackpressurePolicy, EventStream, StreamShared, SubscriptionStream, DEFAULT_STREAM_CAPACITY,
+};
+use futures_util::future::BoxFuture;
+use log::{debug, error, info, trace, warn};
 use serde::Serialize;
 use serde_json::json;
+use std::any::Any;
 use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
-// use tokio::sync::mpsc; // Unused import after commenting out `socket` field
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio::time::{timeout, Duration};
 // use tokio_tungstenite::tungstenite::Message; // Removed unused import
 
@@ -39,6 +50,9 @@ impl DatabaseChanges {
     }
 
     /// イベントを追加
+    ///
+    /// join で送る postgres_changes のバインディングも指定したイベント (`INSERT` など) に
+    /// 絞り込み、コールバックには一致するイベントだけが届く。指定しなければ `*` になる。
     pub fn event(mut self, event: ChannelEvent) -> Self {
         if !self.events.contains(&event) {
             self.events.push(event);
@@ -118,10 +132,63 @@ impl DatabaseChanges {
 
     // --- Internal methods ---
 
-    // /// Convert config to JSON for the websocket message
-    // pub(crate) fn to_channel_config(&self) -> serde_json::Value {
-    //     // ... implementation ...
-    // }
+    // サーバー側で絞り込むイベント名 (指定なし、`All`、`PostgresChanges` は `*`)
+    fn event_names(&self) -> Vec<&'static str> {
+        let names = self
+            .events
+            .iter()
+            .map(|event| match event {
+                ChannelEvent::Insert => Some("INSERT"),
+                ChannelEvent::Update => Some("UPDATE"),
+                ChannelEvent::Delete => Some("DELETE"),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        match names {
+            Some(names) if !names.is_empty() => names,
+            _ => vec!["*"],
+        }
+    }
+
+    /// join メッセージの `config.postgres_changes` に入れるバインディング
+    ///
+    /// サーバーは1つのバインディングに1つのイベントしか指定できないため、イベントごとに作る。
+    pub(crate) fn bindings(&self) -> Vec<serde_json::Value> {
+        let filter = self.filter.as_deref().and_then(|filters| {
+            if filters.len() > 1 {
+                warn!(
+                    "Realtime supports one filter per binding; only '{}' is sent for table '{}'",
+                    filters[0].to_filter_string(),
+                    self.table
+                );
+            }
+            filters.first().map(DatabaseFilter::to_filter_string)
+        });
+        self.event_names()
+            .into_iter()
+            .map(|event| {
+                let mut binding = json!({
+                    "event": event,
+                    "schema": self.schema,
+                    "table": self.table,
+                });
+                if let Some(filter) = &filter {
+                    binding["filter"] = json!(filter);
+                }
+                binding
+            })
+            .collect()
+    }
+
+    fn matches(&self, change_type: Option<&str>) -> bool {
+        let names = self.event_names();
+        names == ["*"]
+            || change_type.is_some_and(|change_type| {
+                names
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(change_type))
+            })
+    }
 }
 
 /// ブロードキャストイベント監視設定
@@ -137,27 +204,226 @@ impl BroadcastChanges {
         }
     }
 
-    #[allow(dead_code)] // Mark as allowed since it might be useful later
     pub(crate) fn get_event_name(&self) -> &str {
         &self.event
     }
 }
 
-/// プレゼンスイベント監視設定 (シンプルなマーカー型)
-#[derive(Debug, Clone, Default, Serialize)]
-pub struct PresenceChanges;
+type PresenceDeltaFn =
+    Arc<dyn Fn(String, Vec<serde_json::Value>, Vec<serde_json::Value>) + Send + Sync>;
+type PresenceSyncFn = Arc<dyn Fn(&PresenceState) + Send + Sync>;
+
+/// プレゼンスイベント監視設定
+///
+/// `ChannelBuilder::on_presence_changes` で登録する。join / leave は Phoenix と同じく
+/// `phx_ref` 単位で metas をマージした差分で呼ばれる。
+#[derive(Clone, Default, Serialize)]
+pub struct PresenceChanges {
+    #[serde(skip)]
+    on_join: Option<PresenceDeltaFn>,
+    #[serde(skip)]
+    on_leave: Option<PresenceDeltaFn>,
+    #[serde(skip)]
+    on_sync: Option<PresenceSyncFn>,
+}
 
 impl PresenceChanges {
     pub fn new() -> Self {
-        // Self::default() // Clippy: default_constructed_unit_structs
-        PresenceChanges // Create directly
+        Self::default()
+    }
+
+    /// 参加時のコールバック `(key, 参加前の metas, 参加した metas)`
+    pub fn on_join<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(String, Vec<serde_json::Value>, Vec<serde_json::Value>) + Send + Sync + 'static,
+    {
+        self.on_join = Some(Arc::new(callback));
+        self
+    }
+
+    /// 離脱時のコールバック `(key, 残っている metas, 離脱した metas)`
+    pub fn on_leave<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(String, Vec<serde_json::Value>, Vec<serde_json::Value>) + Send + Sync + 'static,
+    {
+        self.on_leave = Some(Arc::new(callback));
+        self
+    }
+
+    /// `presence_state` / `presence_diff` を適用し終えるたびに呼ばれるコールバック
+    pub fn on_sync<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&PresenceState) + Send + Sync + 'static,
+    {
+        self.on_sync = Some(Arc::new(callback));
+        self
+    }
+
+    fn dispatch(&self, events: &[PresenceEvent], state: &PresenceState) {
+        for event in events {
+            match event {
+                PresenceEvent::Join {
+                    key,
+                    current_metas,
+                    new_metas,
+                } => {
+                    if let Some(on_join) = &self.on_join {
+                        on_join(key.clone(), current_metas.clone(), new_metas.clone());
+                    }
+                }
+                PresenceEvent::Leave {
+                    key,
+                    current_metas,
+                    left_metas,
+                } => {
+                    if let Some(on_leave) = &self.on_leave {
+                        on_leave(key.clone(), current_metas.clone(), left_metas.clone());
+                    }
+                }
+            }
+        }
+        if let Some(on_sync) = &self.on_sync {
+            on_sync(state);
+        }
     }
 }
 
+impl std::fmt::Debug for PresenceChanges {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PresenceChanges")
+            .field("on_join", &self.on_join.is_some())
+            .field("on_leave", &self.on_leave.is_some())
+            .field("on_sync", &self.on_sync.is_some())
+            .finish()
+    }
+}
+
+/// 非同期ハンドラーの実行方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DispatchMode {
+    /// ハンドラーの完了を待ってから次のイベントを処理する (イベントの順序を保証)
+    #[default]
+    Sequential,
+    /// ハンドラーを並行に実行する (順序は保証されない)
+    Concurrent,
+}
+
 /// アクティブなチャンネル購読を表す
+///
+/// 破棄すると購読を解除する。チャンネルの最後の購読が解除されると `phx_leave` を送って
+/// チャンネルから退出する。
 pub struct Subscription {
     id: String, // Internal subscription identifier
     channel: Arc<Channel>,
+    // プレゼンスの購読はイベントをストリームにできない
+    presence: bool,
+}
+
+impl Subscription {
+    /// 購読ID (`HandlerError::subscription_id` と対応)
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// チャンネルのハンドラーエラーを受信する
+    ///
+    /// 非同期ハンドラーが返したエラーと、ハンドラー内のパニックが通知される。
+    pub fn handler_errors(&self) -> broadcast::Receiver<HandlerError> {
+        self.channel.handler_errors.subscribe()
+    }
+
+    /// 最後の join でサーバーが受け付けた postgres_changes のバインディング (チャンネル単位)
+    pub fn confirmed_bindings(&self) -> Vec<ConfirmedBinding> {
+        self.channel
+            .confirmed_bindings
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// 最後の join で見つかった警告 (バインディングが受け付けられなかったなど)
+    pub fn warnings(&self) -> Vec<SubscriptionWarning> {
+        self.channel
+            .warnings
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// 以降の join (再接続後の再参加を含む) で見つかった警告を受信する
+    pub fn warning_events(&self) -> broadcast::Receiver<SubscriptionWarning> {
+        self.channel.warning_events.subscribe()
+    }
+
+    /// サーバーが購読を拒否した場合のエラー (`RealtimeError::SubscribeRejected`)
+    ///
+    /// join の後に `system` メッセージで拒否された場合も含む。
+    pub fn subscribe_error(&self) -> Option<RealtimeError> {
+        self.channel.subscribe_error()
+    }
+
+    /// チャンネルの統計 (同じチャンネルの購読はすべて同じ値を返す)
+    pub fn stats(&self) -> ChannelStats {
+        self.channel.metrics.snapshot()
+    }
+
+    /// チャンネルの現在のプレゼンス状態
+    pub fn presence_state(&self) -> PresenceState {
+        self.channel
+            .presence
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// `on_stream` の購読で最後に振った連番 (それ以外の購読やイベント未着は `None`)
+    pub fn last_sequence(&self) -> Option<u64> {
+        self.channel
+            .streams
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&self.id)
+            .and_then(|streams| streams.first())
+            .and_then(|stream| stream.last_sequence())
+    }
+
+    /// チャンネルのトピック
+    pub fn topic(&self) -> &str {
+        &self.channel.topic
+    }
+
+    /// この購読のイベントを `Stream` で受け取る
+    ///
+    /// コールバック (あれば) に加えて、同じイベントを `DEFAULT_STREAM_CAPACITY` 件までの
+    /// バッファに溜める (超えたら古いものから捨てる)。ストリームを破棄すると購読を解除する。
+    /// プレゼンスの購読はエラーを1件返して終わる。
+    pub fn into_stream(self) -> SubscriptionStream {
+        self.into_stream_with_capacity(DEFAULT_STREAM_CAPACITY, BackpressurePolicy::default())
+    }
+
+    /// バッファの容量と溢れたときの方針を指定してストリームにする
+    pub fn into_stream_with_capacity(
+        self,
+        capacity: usize,
+        policy: BackpressurePolicy,
+    ) -> SubscriptionStream {
+        if self.presence {
+            let error = RealtimeError::SubscriptionError(format!(
+                "presence subscription {} on '{}' cannot be streamed",
+                self.id, self.channel.topic
+            ));
+            return SubscriptionStream::failed(self, error);
+        }
+        let shared = Arc::new(StreamShared::new(capacity, policy));
+        self.channel
+            .streams
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(self.id.clone())
+            .or_default()
+            .push(shared.clone());
+        SubscriptionStream::new(self, EventStream::new(shared))
+    }
 }
 
 impl Drop for Subscription {
@@ -174,14 +440,144 @@ impl Drop for Subscription {
 }
 
 type CallbackFn = Box<dyn Fn(Payload) + Send + Sync>;
+
+// 購読で受け取るイベントの条件
+enum Binding {
+    Postgres(DatabaseChanges),
+    Broadcast(BroadcastChanges),
+    // `on_all`: チャンネルのすべてのイベント
+    All,
+}
+
+impl Binding {
+    fn matches(&self, event: ChannelEvent, payload: &Payload) -> bool {
+        match self {
+            Binding::Postgres(changes) => {
+                event == ChannelEvent::PostgresChanges && changes.matches(payload.change_type())
+            }
+            Binding::Broadcast(changes) => {
+                event == ChannelEvent::Broadcast
+                    && payload.data.get("event").and_then(|e| e.as_str())
+                        == Some(changes.get_event_name())
+            }
+            Binding::All => true,
+        }
+    }
+}
+
+// 登録順を保つためにベクターで持つ
+struct Handler<F> {
+    id: String,
+    binding: Binding,
+    callback: F,
+}
+
+impl<F> Handler<F> {
+    fn new(binding: Binding, callback: F) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            binding,
+            callback,
+        }
+    }
+}
+
+// 種別ごとのハンドラーを登録順に、その後にワイルドカードのハンドラーを登録順に並べる
+fn handlers_for<'h, F>(
+    handlers: &'h [Handler<F>],
+    event: ChannelEvent,
+    payload: &'h Payload,
+) -> impl Iterator<Item = &'h Handler<F>> {
+    let (wildcard, specific): (Vec<_>, Vec<_>) = handlers
+        .iter()
+        .filter(move |handler| handler.binding.matches(event, payload))
+        .partition(|handler| matches!(handler.binding, Binding::All));
+    specific.into_iter().chain(wildcard)
+}
+type AsyncCallbackFn = Arc<dyn Fn(Payload) -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
 type PresenceCallbackFn = Box<dyn Fn(PresenceChange) + Send + Sync>;
+type CatchUpFn = Arc<dyn Fn(Option<String>) -> BoxFuture<'static, Vec<Payload>> + Send + Sync>;
+
+// 再接続時のキャッチアップ状態 (`ChannelBuilder::on_with_catch_up` の購読ごと)
+struct CatchUp {
+    hook: CatchUpFn,
+    state: std::sync::Mutex<CatchUpState>,
+}
+
+#[derive(Default)]
+struct CatchUpState {
+    // 最後に配信したイベントの commit_timestamp
+    last_seen: Option<String>,
+    // キャッチアップ中は届いたライブイベントを溜めておく
+    replaying: bool,
+    buffered: Vec<Payload>,
+}
+
+impl CatchUp {
+    fn state(&self) -> std::sync::MutexGuard<'_, CatchUpState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn boxed_async_callback<F, Fut, E>(callback: F) -> AsyncCallbackFn
+where
+    F: Fn(Payload) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: std::fmt::Display,
+{
+    Arc::new(move |payload| {
+        let fut = callback(payload);
+        Box::pin(async move { fut.await.map_err(|e| e.to_string()) })
+    })
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        format!("handler panicked: {}", message)
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        format!("handler panicked: {}", message)
+    } else {
+        "handler panicked".to_string()
+    }
+}
+
+fn report_handler_error(
+    errors: &broadcast::Sender<HandlerError>,
+    metrics: &ChannelMetrics,
+    topic: &str,
+    handler_error: HandlerError,
+) {
+    error!("Channel '{}': {}", topic, handler_error);
+    metrics.record_error();
+    // 受信者がいない場合はログのみ
+    let _ = errors.send(handler_error);
+}
 
 /// 内部チャンネル表現
 pub(crate) struct Channel {
     topic: String,
     client: Arc<RealtimeClient>, // Store Arc<RealtimeClient> for sending messages
-    callbacks: Arc<RwLock<HashMap<String, CallbackFn>>>,
+    callbacks: Arc<RwLock<Vec<Handler<CallbackFn>>>>,
     presence_callbacks: Arc<RwLock<Vec<PresenceCallbackFn>>>,
+    presence_handlers: Arc<RwLock<HashMap<String, PresenceChanges>>>,
+    presence: std::sync::Mutex<PresenceState>,
+    async_callbacks: Arc<RwLock<Vec<Handler<AsyncCallbackFn>>>>,
+    catch_ups: Arc<RwLock<HashMap<String, Arc<CatchUp>>>>,
+    // 購読ID ごとのストリームのバッファ (`on_stream` と `Subscription::into_stream`)
+    streams: std::sync::RwLock<HashMap<String, Vec<Arc<StreamShared>>>>,
+    dispatch_mode: Arc<std::sync::RwLock<DispatchMode>>,
+    // 非同期ハンドラー用のディスパッチタスク (最初のイベントで起動)
+    dispatcher: std::sync::Mutex<Option<mpsc::UnboundedSender<(ChannelEvent, Payload)>>>,
+    handler_errors: broadcast::Sender<HandlerError>,
+    pub(crate) metrics: Arc<ChannelMetrics>,
+    // 最後の join の応答で確認したバインディングと警告
+    confirmed_bindings: std::sync::RwLock<Vec<ConfirmedBinding>>,
+    warnings: std::sync::RwLock<Vec<SubscriptionWarning>>,
+    warning_events: broadcast::Sender<SubscriptionWarning>,
+    // サーバーが購読を拒否した理由 (join のたびにリセット)
+    rejection: std::sync::Mutex<Option<String>>,
     // Add channel state
     state: Arc<RwLock<ChannelState>>,
 }
@@ -199,14 +595,111 @@ impl Channel {
     pub(crate) fn new(topic: String, client: Arc<RealtimeClient>) -> Self {
         debug!("Channel::new created for topic: {}", topic);
         Self {
+            metrics: Arc::new(ChannelMetrics::new(&topic)),
             topic,
             client,
-            callbacks: Arc::new(RwLock::new(HashMap::new())),
+            callbacks: Arc::new(RwLock::new(Vec::new())),
             presence_callbacks: Arc::new(RwLock::new(Vec::new())),
+            presence_handlers: Arc::new(RwLock::new(HashMap::new())),
+            presence: std::sync::Mutex::new(PresenceState::new()),
+            async_callbacks: Arc::new(RwLock::new(Vec::new())),
+            catch_ups: Arc::new(RwLock::new(HashMap::new())),
+            streams: std::sync::RwLock::new(HashMap::new()),
+            dispatch_mode: Arc::new(std::sync::RwLock::new(DispatchMode::default())),
+            dispatcher: std::sync::Mutex::new(None),
+            handler_errors: broadcast::channel(64).0,
+            confirmed_bindings: std::sync::RwLock::new(Vec::new()),
+            warnings: std::sync::RwLock::new(Vec::new()),
+            warning_events: broadcast::channel(64).0,
+            rejection: std::sync::Mutex::new(None),
             state: Arc::new(RwLock::new(ChannelState::Closed)),
         }
     }
 
+    fn set_dispatch_mode(&self, mode: DispatchMode) {
+        *self
+            .dispatch_mode
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = mode;
+    }
+
+    // 非同期ハンドラーへイベントを渡す
+    async fn dispatch_async(&self, event: ChannelEvent, payload: Payload) {
+        if self.async_callbacks.read().await.is_empty() {
+            return;
+        }
+        let mut dispatcher = self
+            .dispatcher
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let sender = dispatcher.get_or_insert_with(|| self.spawn_dispatcher());
+        if sender.send((event, payload)).is_err() {
+            error!(
+                "Channel '{}' dispatcher task has stopped; dropping event",
+                self.topic
+            );
+        }
+    }
+
+    fn spawn_dispatcher(&self) -> mpsc::UnboundedSender<(ChannelEvent, Payload)> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<(ChannelEvent, Payload)>();
+        let callbacks = self.async_callbacks.clone();
+        let dispatch_mode = self.dispatch_mode.clone();
+        let errors = self.handler_errors.clone();
+        let metrics = self.metrics.clone();
+        let topic = self.topic.clone();
+
+        tokio::spawn(async move {
+            while let Some((event, payload)) = rx.recv().await {
+                let handlers = handlers_for(&callbacks.read().await, event, &payload)
+                    .map(|handler| (handler.id.clone(), handler.callback.clone()))
+                    .collect::<Vec<_>>();
+                let mode = *dispatch_mode
+                    .read()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+                for (id, handler) in handlers {
+                    let payload = payload.clone();
+                    // パニックがディスパッチタスクを止めないよう、ハンドラーは別タスクで実行
+                    let task = tokio::spawn(async move { handler(payload).await });
+                    let errors = errors.clone();
+                    let metrics = metrics.clone();
+                    let topic = topic.clone();
+                    let report = async move {
+                        let handler_error = match task.await {
+                            Ok(Ok(())) => return,
+                            Ok(Err(message)) => HandlerError {
+                                subscription_id: id,
+                                message,
+                                panicked: false,
+                            },
+                            Err(join_error) => HandlerError {
+                                subscription_id: id,
+                                panicked: join_error.is_panic(),
+                                message: if join_error.is_panic() {
+                                    panic_message(&*join_error.into_panic())
+                                } else {
+                                    join_error.to_string()
+                                },
+                            },
+                        };
+                        report_handler_error(&errors, &metrics, &topic, handler_error);
+                    };
+
+                    match mode {
+                        DispatchMode::Sequential => report.await,
+                        DispatchMode::Concurrent => {
+                            tokio::spawn(report);
+                        }
+                    }
+                }
+            }
+            debug!("Channel '{}' dispatcher task finished", topic);
+        });
+
+        tx
+    }
+
     async fn set_state(&self, state: ChannelState) {
         let mut current_state = self.state.write().await;
         if *current_state != state {
@@ -224,8 +717,62 @@ impl Channel {
         }
     }
 
+    fn subscribe_error(&self) -> Option<RealtimeError> {
+        self.rejection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+            .map(|message| RealtimeError::SubscribeRejected {
+                topic: self.topic.clone(),
+                message,
+            })
+    }
+
+    // 購読の拒否を記録し、チャンネルを Errored にする
+    async fn reject(&self, message: String) {
+        error!(
+            "Channel '{}' subscription rejected: {} (see {})",
+            self.topic,
+            message,
+            bindings::POSTGRES_CHANGES_DOCS_URL
+        );
+        *self
+            .rejection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(message);
+        self.metrics.record_error();
+        self.set_state(ChannelState::Errored).await;
+    }
+
+    // join の応答のバインディングを要求したものと突き合わせ、警告を記録する
+    async fn confirm_bindings(&self, reply: &serde_json::Value) {
+        let join_config = self.join_config().await;
+        let requested = join_config["config"]["postgres_changes"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let (confirmed, warnings) = bindings::check_join_reply(&requested, reply);
+        for warning in &warnings {
+            warn!("Channel '{}': {}", self.topic, warning);
+            // 受信者がいない場合はログと warnings() のみ
+            let _ = self.warning_events.send(warning.clone());
+        }
+        *self
+            .confirmed_bindings
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = confirmed;
+        *self
+            .warnings
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = warnings;
+    }
+
     // Simplified join - just sends the message
     async fn join(&self) -> Result<(), RealtimeError> {
+        *self
+            .rejection
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
         self.set_state(ChannelState::Joining).await;
         let join_ref = self.client.next_ref();
         info!(
@@ -235,34 +782,293 @@ impl Channel {
         let join_msg = json!({
             "topic": self.topic,
             "event": ChannelEvent::PhoenixJoin,
-            "payload": {},
+            "payload": self.join_config().await,
             "ref": join_ref
         });
+        self.metrics.join_started();
         // TODO: Add timeout for join reply
         self.client.send_message(join_msg).await
         // Need mechanism to wait for phx_reply with matching ref
     }
 
-    // async fn send_message(&self, payload: serde_json::Value) -> Result<(), RealtimeError> {
-    //    // ... implementation ...
-    // }
+    // 登録済みの postgres_changes の購読をサーバーに伝える (同じバインディングは1つにまとめる)
+    async fn join_config(&self) -> serde_json::Value {
+        let callbacks = self.callbacks.read().await;
+        let async_callbacks = self.async_callbacks.read().await;
+        let mut postgres_changes = Vec::new();
+        let bindings = callbacks
+            .iter()
+            .map(|handler| &handler.binding)
+            .chain(async_callbacks.iter().map(|handler| &handler.binding));
+        for binding in bindings {
+            if let Binding::Postgres(changes) = binding {
+                for binding in changes.bindings() {
+                    if !postgres_changes.contains(&binding) {
+                        postgres_changes.push(binding);
+                    }
+                }
+            }
+        }
+        json!({ "config": { "postgres_changes": postgres_changes } })
+    }
+
+    // join を送信し、Joined になるまで待つ
+    async fn join_and_wait(&self) -> Result<(), RealtimeError> {
+        if let Err(e) = self.join().await {
+            error!(
+                "Failed to send join message for channel '{}': {}",
+                self.topic, e
+            );
+            self.metrics.record_error();
+            self.set_state(ChannelState::Errored).await;
+            return Err(e);
+        }
+        // Join message sent, now wait for reply (handled by reader task)
+        debug!(
+            "Join message sent for channel '{}'. Waiting for reply.",
+            self.topic
+        );
+        // We might want a timeout here to ensure the join completes
+        match timeout(Duration::from_secs(10), async {
+            while *self.state.read().await != ChannelState::Joined {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                // Add a check for Errored or Closed state too
+                let check_state = *self.state.read().await;
+                if check_state == ChannelState::Errored || check_state == ChannelState::Closed {
+                    if let Some(rejected) = self.subscribe_error() {
+                        return Err(rejected);
+                    }
+                    return Err(RealtimeError::SubscriptionError(format!(
+                        "Channel '{}' entered state {:?} while waiting for join reply",
+                        self.topic, check_state
+                    )));
+                }
+            }
+            Ok(())
+        })
+        .await
+        {
+            Ok(Ok(_)) => {
+                info!("Channel '{}' successfully joined.", self.topic);
+                Ok(())
+            }
+            Ok(Err(e)) => {
+                error!(
+                    "Error waiting for join confirmation for channel '{}': {:?}",
+                    self.topic, e
+                );
+                Err(e)
+            }
+            Err(_) => {
+                error!(
+                    "Timed out waiting for join confirmation for channel '{}'",
+                    self.topic
+                );
+                self.metrics.record_error();
+                self.set_state(ChannelState::Errored).await;
+                Err(RealtimeError::SubscriptionError(format!(
+                    "Timed out waiting for join confirmation for channel '{}'",
+                    self.topic
+                )))
+            }
+        }
+    }
+
+    /// 上限を超えたメッセージを捨てたことを警告と `on_stream` の購読に通知する
+    pub(crate) fn message_dropped(&self, dropped: DroppedMessage) {
+        for stream in self
+            .streams
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .values()
+            .flatten()
+        {
+            stream.mark_message_dropped();
+        }
+        // 受信者がいない場合はクライアントのログのみ
+        let _ = self
+            .warning_events
+            .send(SubscriptionWarning::MessageDropped(dropped));
+    }
+
+    /// 再接続後にチャンネルへ再参加し、キャッチアップフックで取りこぼしたイベントを配信する
+    ///
+    /// フックの結果は `synthetic: true` として、キャッチアップ中に届いたライブイベントより先に配信される。
+    /// `on_stream` の購読には、再参加後のイベントより前に `GapReason::Reconnect` を通知する。
+    pub(crate) async fn rejoin(&self) -> Result<(), RealtimeError> {
+        for stream in self
+            .streams
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .values()
+            .flatten()
+        {
+            stream.mark_reconnect();
+        }
+        let catch_ups = self
+            .catch_ups
+            .read()
+            .await
+            .iter()
+            .map(|(id, catch_up)| (id.clone(), catch_up.clone()))
+            .collect::<Vec<_>>();
+        for (_, catch_up) in &catch_ups {
+            catch_up.state().replaying = true;
+        }
+
+        info!("Channel '{}' rejoining after reconnect", self.topic);
+        let joined = self.join_and_wait().await;
+        for (id, catch_up) in catch_ups {
+            if joined.is_ok() {
+                let since = catch_up.state().last_seen.clone();
+                debug!(
+                    "Channel '{}' running catch-up for {} since {:?}",
+                    self.topic, id, since
+                );
+                for mut payload in (catch_up.hook)(since).await {
+                    payload.synthetic = true;
+                    self.deliver(&id, &catch_up, payload).await;
+                }
+            }
+            // ライブイベントの配信を再開する前に、溜めておいたイベントを流す
+            loop {
+                let buffered = {
+                    let mut state = catch_up.state();
+                    if state.buffered.is_empty() {
+                        state.replaying = false;
+                        break;
+                    }
+                    std::mem::take(&mut state.buffered)
+                };
+                for payload in buffered {
+                    self.deliver(&id, &catch_up, payload).await;
+                }
+            }
+        }
+        joined
+    }
+
+    // キャッチアップ対象の購読へ1件配信し、commit_timestamp を記録する
+    async fn deliver(&self, id: &str, catch_up: &CatchUp, payload: Payload) {
+        if let Some(commit_timestamp) = payload.commit_timestamp() {
+            catch_up.state().last_seen = Some(commit_timestamp.to_string());
+        }
+        let callbacks = self.callbacks.read().await;
+        if let Some(handler) = callbacks.iter().find(|handler| handler.id == id) {
+            self.invoke(id, &handler.callback, payload);
+        }
+    }
+
+    fn invoke(&self, id: &str, callback: &CallbackFn, payload: Payload) {
+        self.push_to_streams(id, &payload);
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| callback(payload)));
+        if let Err(panic) = result {
+            report_handler_error(
+                &self.handler_errors,
+                &self.metrics,
+                &self.topic,
+                HandlerError {
+                    subscription_id: id.to_string(),
+                    message: panic_message(&*panic),
+                    panicked: true,
+                },
+            );
+        }
+    }
+
+    // 購読のストリームにイベントを入れる
+    fn push_to_streams(&self, id: &str, payload: &Payload) {
+        let streams = self
+            .streams
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for stream in streams.get(id).into_iter().flatten() {
+            stream.push(payload.clone());
+        }
+    }
+
+    pub(crate) async fn needs_rejoin(&self) -> bool {
+        matches!(
+            *self.state.read().await,
+            ChannelState::Joining | ChannelState::Joined | ChannelState::Errored
+        )
+    }
 
     async fn unsubscribe(&self, id: &str) -> Result<(), RealtimeError> {
         // Remove callback
-        self.callbacks.write().await.remove(id);
-        // TODO: Unsubscribe presence if needed
-
-        // Send unsubscribe message if this was the last callback? Requires tracking.
-        // For simplicity, assume client handles full channel leave when all subscriptions drop.
-        println!(
-            "Subscription {} dropped. Channel {} might need explicit leave.",
-            id, self.topic
+        self.callbacks
+            .write()
+            .await
+            .retain(|handler| handler.id != id);
+        self.async_callbacks
+            .write()
+            .await
+            .retain(|handler| handler.id != id);
+        self.presence_handlers.write().await.remove(id);
+        self.catch_ups.write().await.remove(id);
+        let streams = self
+            .streams
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .remove(id);
+        for stream in streams.into_iter().flatten() {
+            stream.close();
+        }
+        debug!("Subscription {} on channel '{}' removed", id, self.topic);
+
+        if self.has_handlers().await {
+            return Ok(());
+        }
+        self.leave().await
+    }
+
+    async fn has_handlers(&self) -> bool {
+        !self.callbacks.read().await.is_empty()
+            || !self.async_callbacks.read().await.is_empty()
+            || !self.presence_callbacks.read().await.is_empty()
+            || !self.presence_handlers.read().await.is_empty()
+    }
+
+    // 最後の購読が解除されたチャンネルから退出し、クライアントから取り除く
+    async fn leave(&self) -> Result<(), RealtimeError> {
+        {
+            let mut channels = self.client.channels.write().await;
+            // 解除の間に同じトピックへ購読が追加されていれば残す
+            if self.has_handlers().await {
+                return Ok(());
+            }
+            if channels
+                .get(&self.topic)
+                .is_some_and(|channel| std::ptr::eq(Arc::as_ptr(channel), self))
+            {
+                channels.remove(&self.topic);
+            }
+        }
+        let state = *self.state.read().await;
+        if matches!(state, ChannelState::Closed | ChannelState::Leaving) {
+            return Ok(());
+        }
+        self.set_state(ChannelState::Leaving).await;
+        info!(
+            "Channel '{}' has no subscriptions left; leaving",
+            self.topic
         );
+        let leave_msg = json!({
+            "topic": self.topic,
+            "event": ChannelEvent::PhoenixLeave,
+            "payload": {},
+            "ref": self.client.next_ref()
+        });
+        if let Err(e) = self.client.send_message(leave_msg).await {
+            // 切断中ならサーバー側のチャンネルも残っていない
+            debug!("Channel '{}' could not send leave: {}", self.topic, e);
+        }
+        self.set_state(ChannelState::Closed).await;
         Ok(())
     }
 
-    // Adjusted to accept RealtimeMessage
-    pub(crate) async fn handle_message(&self, message: RealtimeMessage) {
+    // Adjusted to accept RealtimeMessage (`raw_len` は受信したメッセージのバイト数)
+    pub(crate) async fn handle_message(&self, message: RealtimeMessage, raw_len: usize) {
         debug!(
             "Channel '{}' handling message: event={:?}, ref={:?}",
             self.topic, message.event, message.message_ref
@@ -275,13 +1081,31 @@ impl Channel {
                     "Channel '{}' received PhoenixReply: {:?}",
                     self.topic, message.payload
                 );
-                if *self.state.read().await == ChannelState::Joining {
+                let state = *self.state.read().await;
+                let rejected = bindings::subscribe_error(&message.payload);
+                if rejected.is_some() && state != ChannelState::Joining {
+                    self.metrics.record_error();
+                }
+                if state == ChannelState::Joining {
                     // Basic assumption: any reply means join succeeded for now
+                    if let Some(reason) = rejected {
+                        self.reject(reason).await;
+                        return;
+                    }
+                    self.confirm_bindings(&message.payload).await;
+                    self.metrics.joined();
                     self.set_state(ChannelState::Joined).await;
                 } else if *self.state.read().await == ChannelState::Leaving {
                     self.set_state(ChannelState::Closed).await;
                 }
             }
+            ChannelEvent::System => match bindings::subscribe_error(&message.payload) {
+                Some(reason) => self.reject(reason).await,
+                None => debug!(
+                    "Channel '{}' received system message: {:?}",
+                    self.topic, message.payload
+                ),
+            },
             ChannelEvent::PhoenixClose => {
                 info!(
                     "Channel '{}' received PhoenixClose. Setting state to Closed.",
@@ -294,14 +1118,60 @@ impl Channel {
                     "Channel '{}' received PhoenixError: {:?}",
                     self.topic, message.payload
                 );
+                self.metrics.record_error();
                 self.set_state(ChannelState::Errored).await;
             }
+            ChannelEvent::PresenceState => {
+                self.metrics.record_event(raw_len);
+                match serde_json::from_value(message.payload) {
+                    Ok(new_state) => {
+                        self.apply_presence(|state| state.sync_state(&new_state))
+                            .await
+                    }
+                    Err(e) => error!(
+                        "Channel '{}' received invalid presence_state: {}",
+                        self.topic, e
+                    ),
+                }
+            }
+            ChannelEvent::PresenceDiff => {
+                self.metrics.record_event(raw_len);
+                let diff: PresenceChange = match serde_json::from_value(message.payload) {
+                    Ok(diff) => diff,
+                    Err(e) => {
+                        error!(
+                            "Channel '{}' received invalid presence_diff: {}",
+                            self.topic, e
+                        );
+                        return;
+                    }
+                };
+                self.apply_presence(|state| state.sync_diff(&diff)).await;
+                for callback in self.presence_callbacks.read().await.iter() {
+                    let result =
+                        std::panic::catch_unwind(AssertUnwindSafe(|| callback(diff.clone())));
+                    if let Err(panic) = result {
+                        report_handler_error(
+                            &self.handler_errors,
+                            &self.metrics,
+                            &self.topic,
+                            HandlerError {
+                                subscription_id: format!("presence_{}", self.topic),
+                                message: panic_message(&*panic),
+                                panicked: true,
+                            },
+                        );
+                    }
+                }
+            }
             ChannelEvent::PostgresChanges | ChannelEvent::Broadcast | ChannelEvent::Presence => {
+                self.metrics.record_event(raw_len);
                 // These events have nested data we need to pass to callbacks
                 let payload = Payload {
                     data: message.payload.clone(), // Pass the whole payload as data for now
                     event_type: Some(message.event.to_string()), // Reflect the event type
                     timestamp: None, // Timestamp might be deeper in payload, needs parsing
+                    synthetic: false,
                 };
                 trace!(
                     "Channel '{}' dispatching event {:?} to callbacks",
@@ -309,11 +1179,30 @@ impl Channel {
                     message.event
                 );
                 let callbacks_guard = self.callbacks.read().await;
-                for callback in callbacks_guard.values() {
+                let catch_ups = self.catch_ups.read().await;
+                for handler in handlers_for(&callbacks_guard, message.event, &payload) {
+                    let id = &handler.id;
+                    if let Some(catch_up) = catch_ups.get(id) {
+                        let mut state = catch_up.state();
+                        if state.replaying {
+                            state.buffered.push(payload.clone());
+                            continue;
+                        }
+                        if let Some(commit_timestamp) = payload.commit_timestamp() {
+                            state.last_seen = Some(commit_timestamp.to_string());
+                        }
+                    }
                     // Execute callback - Consider spawning if long-running
-                    callback(payload.clone());
+                    self.invoke(id, &handler.callback, payload.clone());
+                }
+                drop(catch_ups);
+                drop(callbacks_guard);
+                for handler in
+                    handlers_for(&self.async_callbacks.read().await, message.event, &payload)
+                {
+                    self.push_to_streams(&handler.id, &payload);
                 }
-                // TODO: Handle presence callbacks separately if event is Presence
+                self.dispatch_async(message.event, payload).await;
             }
             // Ignore other events like Heartbeat, Insert, Update, Delete, All at the channel level
             // (Those might be relevant *inside* a PostgresChanges payload)
@@ -326,15 +1215,49 @@ impl Channel {
             }
         }
     }
+
+    // プレゼンス状態を更新し、登録されたハンドラーへ join / leave / sync を通知する
+    async fn apply_presence<F>(&self, apply: F)
+    where
+        F: FnOnce(&mut PresenceState) -> Vec<PresenceEvent>,
+    {
+        let (events, state) = {
+            let mut presence = self
+                .presence
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            (apply(&mut presence), presence.clone())
+        };
+        for (id, handler) in self.presence_handlers.read().await.iter() {
+            let result =
+                std::panic::catch_unwind(AssertUnwindSafe(|| handler.dispatch(&events, &state)));
+            if let Err(panic) = result {
+                report_handler_error(
+                    &self.handler_errors,
+                    &self.metrics,
+                    &self.topic,
+                    HandlerError {
+                        subscription_id: id.clone(),
+                        message: panic_message(&*panic),
+                        panicked: true,
+                    },
+                );
+            }
+        }
+    }
 }
 
 /// チャンネル作成と購読設定のためのビルダー
 pub struct ChannelBuilder<'a> {
     client: &'a RealtimeClient,
     topic: String,
-    db_callbacks: HashMap<String, (DatabaseChanges, CallbackFn)>,
-    broadcast_callbacks: HashMap<String, (BroadcastChanges, CallbackFn)>,
+    callbacks: Vec<Handler<CallbackFn>>,
+    async_callbacks: Vec<Handler<AsyncCallbackFn>>,
     presence_callbacks: Vec<PresenceCallbackFn>,
+    presence_changes: HashMap<String, PresenceChanges>,
+    catch_ups: HashMap<String, CatchUpFn>,
+    streams: HashMap<String, Arc<StreamShared>>,
+    dispatch_mode: Option<DispatchMode>,
 }
 
 impl<'a> ChannelBuilder<'a> {
@@ -343,31 +1266,131 @@ impl<'a> ChannelBuilder<'a> {
         Self {
             client,
             topic: topic.to_string(),
-            db_callbacks: HashMap::new(),
-            broadcast_callbacks: HashMap::new(),
+            callbacks: Vec::new(),
+            async_callbacks: Vec::new(),
             presence_callbacks: Vec::new(),
+            presence_changes: HashMap::new(),
+            catch_ups: HashMap::new(),
+            streams: HashMap::new(),
+            dispatch_mode: None,
         }
     }
 
     /// データベース変更イベントのコールバックを登録
+    ///
+    /// `changes` にイベントを指定すると、サーバー側でもそのイベントだけに絞り込まれる
+    /// (指定しなければ `*`)。
     pub fn on<F>(mut self, changes: DatabaseChanges, callback: F) -> Self
     where
         F: Fn(Payload) + Send + Sync + 'static,
     {
-        // Use a unique identifier for the subscription
-        let id = uuid::Uuid::new_v4().to_string();
-        self.db_callbacks.insert(id, (changes, Box::new(callback)));
+        self.callbacks
+            .push(Handler::new(Binding::Postgres(changes), Box::new(callback)));
         self
     }
 
+    /// チャンネルのすべてのイベントで呼ばれるコールバックを登録 (ログや監査向け)
+    ///
+    /// 種類を問わず postgres_changes / broadcast / presence のイベントを受け取る。
+    /// 同じイベントの種類別のコールバックの後に、登録順で呼ばれる。
+    pub fn on_all<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(Payload) + Send + Sync + 'static,
+    {
+        self.callbacks
+            .push(Handler::new(Binding::All, Box::new(callback)));
+        self
+    }
+
+    /// 再接続時のキャッチアップフック付きでデータベース変更イベントのコールバックを登録
+    ///
+    /// 再参加のたびに、最後に配信したイベントの `commit_timestamp` (未配信なら `None`) で
+    /// `catch_up` が呼ばれる。返したイベントは `synthetic: true` としてライブイベントより先に
+    /// `callback` へ配信される (通常は `updated_at` などで PostgREST を問い合わせる)。
+    pub fn on_with_catch_up<F, H, Fut>(
+        mut self,
+        changes: DatabaseChanges,
+        callback: F,
+        catch_up: H,
+    ) -> Self
+    where
+        F: Fn(Payload) + Send + Sync + 'static,
+        H: Fn(Option<String>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Vec<Payload>> + Send + 'static,
+    {
+        let handler = Handler::new(Binding::Postgres(changes), Box::new(callback) as CallbackFn);
+        self.catch_ups.insert(
+            handler.id.clone(),
+            Arc::new(move |since| Box::pin(catch_up(since)) as BoxFuture<'static, _>),
+        );
+        self.callbacks.push(handler);
+        self
+    }
+
+    /// データベース変更イベントを連番付きのストリームで受け取る
+    ///
+    /// 読み出されていないイベントは `capacity` 件まで溜め、超えた分は `policy` に従って捨てる。
+    /// 捨てた場合と再接続した場合は `StreamItem::GapDetected` が通知される
+    /// (再接続では取りこぼしの有無によらず通知する)。
+    pub fn on_stream(
+        mut self,
+        changes: DatabaseChanges,
+        capacity: usize,
+        policy: BackpressurePolicy,
+    ) -> (Self, EventStream) {
+        let shared = Arc::new(StreamShared::new(capacity, policy));
+        // イベントはチャンネルが購読ID のバッファに入れる
+        let handler = Handler::new(Binding::Postgres(changes), Box::new(|_| {}) as CallbackFn);
+        self.streams.insert(handler.id.clone(), shared.clone());
+        self.callbacks.push(handler);
+        (self, EventStream::new(shared))
+    }
+
     /// ブロードキャストイベントのコールバックを登録
     pub fn on_broadcast<F>(mut self, changes: BroadcastChanges, callback: F) -> Self
     where
         F: Fn(Payload) + Send + Sync + 'static,
     {
-        let id = uuid::Uuid::new_v4().to_string();
-        self.broadcast_callbacks
-            .insert(id, (changes, Box::new(callback)));
+        self.callbacks.push(Handler::new(
+            Binding::Broadcast(changes),
+            Box::new(callback),
+        ));
+        self
+    }
+
+    /// データベース変更イベントの非同期コールバックを登録
+    ///
+    /// 返されたエラーやパニックは `Subscription::handler_errors` に通知される。
+    pub fn on_async<F, Fut, E>(mut self, changes: DatabaseChanges, callback: F) -> Self
+    where
+        F: Fn(Payload) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        self.async_callbacks.push(Handler::new(
+            Binding::Postgres(changes),
+            boxed_async_callback(callback),
+        ));
+        self
+    }
+
+    /// ブロードキャストイベントの非同期コールバックを登録
+    pub fn on_broadcast_async<F, Fut, E>(mut self, changes: BroadcastChanges, callback: F) -> Self
+    where
+        F: Fn(Payload) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        self.async_callbacks.push(Handler::new(
+            Binding::Broadcast(changes),
+            boxed_async_callback(callback),
+        ));
+        self
+    }
+
+    /// 非同期コールバックの実行方式を設定 (デフォルトは `DispatchMode::Sequential`)
+    pub fn dispatch_mode(mut self, mode: DispatchMode) -> Self {
+        self.dispatch_mode = Some(mode);
         self
     }
 
@@ -380,6 +1403,13 @@ impl<'a> ChannelBuilder<'a> {
         self
     }
 
+    /// プレゼンスの join / leave / sync コールバックを登録
+    pub fn on_presence_changes(mut self, changes: PresenceChanges) -> Self {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.presence_changes.insert(id, changes);
+        self
+    }
+
     /// チャンネルへの接続と購読を開始
     pub async fn subscribe(self) -> Result<Vec<Subscription>, RealtimeError> {
         info!("ChannelBuilder subscribing for topic: {}", self.topic);
@@ -395,32 +1425,61 @@ impl<'a> ChannelBuilder<'a> {
         debug!("Got or created Channel Arc for topic: {}", self.topic);
 
         let mut subscriptions = Vec::new();
+        let mut catch_ups_guard = channel.catch_ups.write().await;
+        for (id, hook) in self.catch_ups {
+            catch_ups_guard.insert(
+                id,
+                Arc::new(CatchUp {
+                    hook,
+                    state: std::sync::Mutex::new(CatchUpState::default()),
+                }),
+            );
+        }
+        drop(catch_ups_guard);
+        channel
+            .streams
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .extend(
+                self.streams
+                    .into_iter()
+                    .map(|(id, stream)| (id, vec![stream])),
+            );
         let mut callbacks_guard = channel.callbacks.write().await;
         let mut presence_callbacks_guard = channel.presence_callbacks.write().await;
 
-        // Add database change callbacks
-        for (id, (_changes, callback)) in self.db_callbacks {
-            debug!("Adding DB callback ID {} to channel {}", id, self.topic);
-            callbacks_guard.insert(id.clone(), callback);
+        // Add database change / broadcast / wildcard callbacks (登録順)
+        for handler in self.callbacks {
+            debug!(
+                "Adding callback ID {} to channel {}",
+                handler.id, self.topic
+            );
             subscriptions.push(Subscription {
-                id,
+                id: handler.id.clone(),
                 channel: channel.clone(),
+                presence: false,
             });
+            callbacks_guard.push(handler);
         }
 
-        // Add broadcast callbacks
-        for (id, (_changes, callback)) in self.broadcast_callbacks {
+        // Add async callbacks
+        if let Some(mode) = self.dispatch_mode {
+            channel.set_dispatch_mode(mode);
+        }
+        let mut async_callbacks_guard = channel.async_callbacks.write().await;
+        for handler in self.async_callbacks {
             debug!(
-                "Adding Broadcast callback ID {} to channel {}",
-                id, self.topic
+                "Adding async callback ID {} to channel {}",
+                handler.id, self.topic
             );
-            // Assuming broadcast uses the same callback mechanism for now
-            callbacks_guard.insert(id.clone(), callback);
             subscriptions.push(Subscription {
-                id,
+                id: handler.id.clone(),
                 channel: channel.clone(),
+                presence: false,
             });
+            async_callbacks_guard.push(handler);
         }
+        drop(async_callbacks_guard);
 
         // Add presence callbacks
         for callback in self.presence_callbacks {
@@ -431,12 +1490,28 @@ impl<'a> ChannelBuilder<'a> {
             subscriptions.push(Subscription {
                 id,
                 channel: channel.clone(),
+                presence: true,
             });
         }
 
         drop(callbacks_guard);
         drop(presence_callbacks_guard);
 
+        let mut presence_handlers_guard = channel.presence_handlers.write().await;
+        for (id, changes) in self.presence_changes {
+            debug!(
+                "Adding presence handler ID {} to channel {}",
+                id, self.topic
+            );
+            presence_handlers_guard.insert(id.clone(), changes);
+            subscriptions.push(Subscription {
+                id,
+                channel: channel.clone(),
+                presence: true,
+            });
+        }
+        drop(presence_handlers_guard);
+
         // Only send join if channel wasn't already joined/joining
         let current_state = *channel.state.read().await;
         if current_state == ChannelState::Closed || current_state == ChannelState::Errored {
@@ -444,62 +1519,7 @@ impl<'a> ChannelBuilder<'a> {
                 "Channel '{}' is {:?}, attempting to join.",
                 self.topic, current_state
             );
-            match channel.join().await {
-                Ok(_) => {
-                    // Join message sent, now wait for reply (handled by reader task)
-                    debug!(
-                        "Join message sent for channel '{}'. Waiting for reply.",
-                        self.topic
-                    );
-                    // We might want a timeout here to ensure the join completes
-                    match timeout(Duration::from_secs(10), async {
-                        while *channel.state.read().await != ChannelState::Joined {
-                            tokio::time::sleep(Duration::from_millis(50)).await;
-                            // Add a check for Errored or Closed state too
-                            let check_state = *channel.state.read().await;
-                            if check_state == ChannelState::Errored
-                                || check_state == ChannelState::Closed
-                            {
-                                return Err(RealtimeError::SubscriptionError(format!(
-                                    "Channel '{}' entered state {:?} while waiting for join reply",
-                                    self.topic, check_state
-                                )));
-                            }
-                        }
-                        Ok(())
-                    })
-                    .await
-                    {
-                        Ok(Ok(_)) => info!("Channel '{}' successfully joined.", self.topic),
-                        Ok(Err(e)) => {
-                            error!(
-                                "Error waiting for join confirmation for channel '{}': {:?}",
-                                self.topic, e
-                            );
-                            return Err(e);
-                        }
-                        Err(_) => {
-                            error!(
-                                "Timed out waiting for join confirmation for channel '{}'",
-                                self.topic
-                            );
-                            channel.set_state(ChannelState::Errored).await;
-                            return Err(RealtimeError::SubscriptionError(format!(
-                                "Timed out waiting for join confirmation for channel '{}'",
-                                self.topic
-                            )));
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!(
-                        "Failed to send join message for channel '{}': {}",
-                        self.topic, e
-                    );
-                    channel.set_state(ChannelState::Errored).await;
-                    return Err(e);
-                }
-            }
+            channel.join_and_wait().await?;
         } else {
             info!(
                 "Channel '{}' is already {:?}, not sending join message.",
@@ -1,19 +1,21 @@
 use crate::channel::{Channel, ChannelBuilder}; // Added ChannelBuilder import
 use crate::error::RealtimeError;
-use crate::message::RealtimeMessage; // Added ChannelEvent import here
-use futures_util::{SinkExt, StreamExt};
+use crate::limits::{self, DEFAULT_MAX_JSON_DEPTH, DEFAULT_MAX_MESSAGE_SIZE};
+use crate::message::{ChannelEvent, RealtimeMessage};
+use crate::stats::{ConnectionMetrics, RealtimeStats, HEARTBEAT_REF_PREFIX};
+use crate::transport::{Socket, SocketSink, SocketStream, TungsteniteSocket};
 use rand::Rng;
 use serde_json::json;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
+use supabase_rust_core::{scrub, Redacted};
 use tokio::sync::mpsc;
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, watch, RwLock};
 use tokio::time::sleep;
-use tokio_tungstenite::connect_async;
 use tokio_tungstenite::tungstenite::Message;
-use tracing::{debug, error, info, instrument, trace, warn};
+use tracing::{debug, error, info, info_span, instrument, trace, warn, Instrument, Span};
 use url::Url; // Import Rng trait for random number generation
 
 /// 接続状態
@@ -34,6 +36,10 @@ pub struct RealtimeClientOptions {
     pub reconnect_backoff_factor: f64,
     pub max_reconnect_interval: u64,
     pub heartbeat_interval: u64,
+    /// 受け付けるメッセージの最大バイト数 (超えたものはパースせずに捨てる)
+    pub max_message_size: usize,
+    /// 受け付ける JSON の最大ネスト (超えたものはパースせずに捨てる)
+    pub max_json_depth: usize,
 }
 
 impl Default for RealtimeClientOptions {
@@ -45,6 +51,8 @@ impl Default for RealtimeClientOptions {
             reconnect_backoff_factor: 1.5,
             max_reconnect_interval: 30000, // 30 seconds
             heartbeat_interval: 30000,     // 30 seconds
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_json_depth: DEFAULT_MAX_JSON_DEPTH,
         }
     }
 }
@@ -52,7 +60,7 @@ impl Default for RealtimeClientOptions {
 /// Realtimeクライアント本体
 pub struct RealtimeClient {
     pub(crate) url: String,
-    pub(crate) key: String,
+    pub(crate) key: Redacted<String>,
     pub(crate) next_ref: AtomicU32,
     // Shared map of active channels (topic -> Channel)
     pub(crate) channels: Arc<RwLock<HashMap<String, Arc<Channel>>>>,
@@ -66,6 +74,29 @@ pub struct RealtimeClient {
     state_change: broadcast::Sender<ConnectionState>,
     // Make token field accessible within the crate
     pub(crate) access_token: Arc<RwLock<Option<String>>>,
+    transport: Arc<dyn Socket>,
+    metrics: Arc<ConnectionMetrics>,
+    label: Option<String>,
+    // 実行中のバックグラウンドタスク (writer / reader / 再接続) の数
+    tasks: Arc<AtomicUsize>,
+    // 接続ごとの停止シグナル (送信側を落とすと reader が終了する)
+    stop: Arc<std::sync::Mutex<Option<watch::Sender<()>>>>,
+}
+
+// 生存中はタスク数に数えられる
+struct TaskGuard(Arc<AtomicUsize>);
+
+impl TaskGuard {
+    fn new(tasks: &Arc<AtomicUsize>) -> Self {
+        tasks.fetch_add(1, Ordering::SeqCst);
+        Self(tasks.clone())
+    }
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 impl RealtimeClient {
@@ -79,11 +110,22 @@ impl RealtimeClient {
     /// カスタムオプションで新しいクライアントを作成
     #[instrument(skip(key))]
     pub fn new_with_options(url: &str, key: &str, options: RealtimeClientOptions) -> Self {
+        Self::new_with_socket(url, key, options, TungsteniteSocket)
+    }
+
+    /// トランスポートを指定して新しいクライアントを作成 (テストでは `transport::memory_socket` を使用)
+    #[instrument(skip(key, socket))]
+    pub fn new_with_socket<S: Socket + 'static>(
+        url: &str,
+        key: &str,
+        options: RealtimeClientOptions,
+        socket: S,
+    ) -> Self {
         info!("Creating new RealtimeClient with options: {:?}", options);
         let (state_change_tx, _) = broadcast::channel(16); // Channel for state changes
         Self {
             url: url.to_string(),
-            key: key.to_string(),
+            key: key.into(),
             next_ref: AtomicU32::new(1),
             channels: Arc::new(RwLock::new(HashMap::new())),
             socket: Arc::new(RwLock::new(None)),
@@ -95,6 +137,35 @@ impl RealtimeClient {
             state_change: state_change_tx,
             // Initialize token as None
             access_token: Arc::new(RwLock::new(None)),
+            transport: Arc::new(socket),
+            metrics: Arc::new(ConnectionMetrics::new()),
+            label: None,
+            tasks: Arc::new(AtomicUsize::new(0)),
+            stop: Arc::new(std::sync::Mutex::new(None)),
+        }
+    }
+
+    /// ログ (tracing スパンの `client` フィールド) と統計に付けるラベルを設定
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// クライアントのラベル
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// 実行中のバックグラウンドタスクの数 (`disconnect` 後は 0 に戻る)
+    pub fn running_tasks(&self) -> usize {
+        self.tasks.load(Ordering::SeqCst)
+    }
+
+    // バックグラウンドタスクのスパン (ラベルがなければスパンを作らない)
+    fn span(&self) -> Span {
+        match &self.label {
+            Some(label) => info_span!("realtime", client = %label),
+            None => Span::none(),
         }
     }
 
@@ -122,9 +193,22 @@ impl RealtimeClient {
         state
     }
 
+    /// チャンネルごとの統計と接続の統計を取得
+    pub async fn stats(&self) -> RealtimeStats {
+        let mut channels = self
+            .channels
+            .read()
+            .await
+            .values()
+            .map(|channel| channel.metrics.snapshot())
+            .collect::<Vec<_>>();
+        channels.sort_by(|a, b| a.topic.cmp(&b.topic));
+        RealtimeStats::new(self.label.clone(), channels, &self.metrics)
+    }
+
     /// 特定のトピックに対するチャンネルビルダーを作成
     #[instrument(skip(self))]
-    pub fn channel(&self, topic: &str) -> ChannelBuilder {
+    pub fn channel(&self, topic: &str) -> ChannelBuilder<'_> {
         info!(?topic, "Creating channel builder");
         ChannelBuilder::new(self, topic)
     }
@@ -167,6 +251,11 @@ impl RealtimeClient {
         let options = self.options.clone();
         let is_manually_closed_arc = self.is_manually_closed.clone();
         let token_arc = self.access_token.clone(); // Clone token Arc
+        let transport = self.transport.clone();
+        let metrics = self.metrics.clone();
+        let tasks = self.tasks.clone();
+        let stop_arc = self.stop.clone();
+        let span = self.span();
 
         async move {
             info!("Connect task initiated");
@@ -235,9 +324,8 @@ impl RealtimeClient {
                 Ok(mut joined_url) => {
                     joined_url
                         .query_pairs_mut()
-                        .append_pair("apikey", &key)
+                        .append_pair("apikey", key.expose())
                         .append_pair("token", token_param.trim_start_matches("&token=")); // Add token if present
-                    info!(url = %joined_url, "Constructed WebSocket URL");
                     joined_url.to_string()
                 }
                 Err(e) => {
@@ -252,7 +340,10 @@ impl RealtimeClient {
                 }
             };
 
-            info!(url = %ws_url, "Attempting to connect to WebSocket");
+            // apikey と token はログに出さない
+            let token = token_param.trim_start_matches("&token=");
+            let log_url = scrub(&ws_url, &[key.expose(), token]);
+            info!(url = %log_url, "Attempting to connect to WebSocket");
 
             Self::set_connection_state_internal(
                 state_arc.clone(),
@@ -261,14 +352,11 @@ impl RealtimeClient {
             )
             .await;
 
-            let connect_result = connect_async(&ws_url).await; // Store result
-            let ws_stream = match connect_result {
-                Ok((stream, response)) => {
-                    info!(response = ?response, "WebSocket connection successful");
-                    stream
-                }
+            let connect_result = transport.connect(&ws_url).await; // Store result
+            let (write, read) = match connect_result {
+                Ok(halves) => halves,
                 Err(e) => {
-                    error!(error = %e, url = %ws_url, "WebSocket connection failed");
+                    error!(error = %e, url = %log_url, "WebSocket connection failed");
                     // Set state before returning error
                     Self::set_connection_state_internal(
                         state_arc.clone(),
@@ -276,10 +364,7 @@ impl RealtimeClient {
                         ConnectionState::Disconnected,
                     )
                     .await;
-                    return Err(RealtimeError::ConnectionError(format!(
-                        "WebSocket connection failed: {}",
-                        e
-                    )));
+                    return Err(e);
                 }
             };
 
@@ -289,29 +374,32 @@ impl RealtimeClient {
                 ConnectionState::Connected,
             )
             .await;
-
-            let (write, read) = ws_stream.split();
-            debug!("WebSocket stream split into writer and reader");
+            metrics.connected();
 
             let (socket_tx, socket_rx) = mpsc::channel::<Message>(100);
             *socket_arc.write().await = Some(socket_tx.clone()); // Clone for writer task
             debug!("Internal MPSC channel created, sender stored");
+            let (stop_tx, stop_rx) = watch::channel(());
+            *stop_arc.lock().unwrap() = Some(stop_tx);
 
             // --- WebSocket Writer Task ---
             let writer_socket_arc = socket_arc.clone();
             let writer_state_arc = state_arc.clone();
             let writer_state_change_tx = state_change_tx.clone();
+            let writer_metrics = metrics.clone();
+            let writer_guard = TaskGuard::new(&tasks);
             let _writer_handle = tokio::spawn(async move {
+                let _guard = writer_guard;
                 // Add instrument to writer task
                 #[instrument(skip_all, name = "ws_writer")]
                 async fn writer_task(
-                    mut write: impl SinkExt<Message, Error = tokio_tungstenite::tungstenite::Error>
-                        + Unpin,
+                    mut write: Box<dyn SocketSink>,
                     mut socket_rx: mpsc::Receiver<Message>,
                     writer_socket_arc: Arc<RwLock<Option<mpsc::Sender<Message>>>>,
                     writer_state_arc: Arc<RwLock<ConnectionState>>,
                     writer_state_change_tx: broadcast::Sender<ConnectionState>,
                     heartbeat_interval_ms: u64,
+                    metrics: Arc<ConnectionMetrics>,
                 ) {
                     info!("Writer task started");
                     let heartbeat_interval = Duration::from_millis(heartbeat_interval_ms);
@@ -320,7 +408,13 @@ impl RealtimeClient {
                     loop {
                         tokio::select! {
                             // Read from internal MPSC channel
-                            Some(msg) = socket_rx.recv() => {
+                            msg = socket_rx.recv() => {
+                                let Some(msg) = msg else {
+                                    // すべての送信側が落とされた (disconnect)
+                                    info!("Writer: sender dropped, closing WebSocket");
+                                    let _ = write.send(Message::Close(None)).await;
+                                    break;
+                                };
                                 trace!(message = ?msg, "Sending message via WebSocket");
                                 if let Err(e) = write.send(msg).await {
                                     error!(error = %e, "Failed to send message via WebSocket");
@@ -338,7 +432,9 @@ impl RealtimeClient {
                             }
                             // Send heartbeat
                             _ = heartbeat_timer.tick() => {
-                                let heartbeat_ref = format!("hb-{}", rand::thread_rng().gen::<u32>());
+                                let heartbeat_id = rand::thread_rng().gen::<u32>();
+                                let heartbeat_ref = format!("{}{}", HEARTBEAT_REF_PREFIX, heartbeat_id);
+                                metrics.heartbeat_sent(heartbeat_id);
                                 let heartbeat_msg = json!({
                                     "topic": "phoenix",
                                     "event": "heartbeat",
@@ -360,10 +456,6 @@ impl RealtimeClient {
                                     break;
                                 }
                             }
-                            else => {
-                                info!("Writer loop finished (select exhausted)");
-                                break;
-                            }
                         }
                     }
                     info!("Writer task finished");
@@ -377,9 +469,10 @@ impl RealtimeClient {
                     writer_state_arc,
                     writer_state_change_tx,
                     options.heartbeat_interval,
+                    writer_metrics,
                 )
                 .await;
-            });
+            }.instrument(Span::current()));
 
             // --- WebSocket Reader Task ---
             let reader_socket_arc = socket_arc.clone();
@@ -389,14 +482,16 @@ impl RealtimeClient {
             let reader_reconnect_attempts = Arc::new(AtomicU32::new(0)); // Use new Arc for reader's attempts
             let reader_options = options.clone();
             let reader_is_manually_closed = is_manually_closed_arc.clone();
+            let reader_metrics = metrics.clone();
+            let reader_guard = TaskGuard::new(&tasks);
             let _reader_handle = tokio::spawn(async move {
+                let _guard = reader_guard;
                 // Add instrument to reader task
                 // Remove the instrument macro to avoid too_many_arguments error for now
                 // #[instrument(skip_all, name = "ws_reader")]
                 #[allow(clippy::too_many_arguments)] // Allow > 7 arguments for this task
                 async fn reader_task(
-                    mut read: impl StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>>
-                        + Unpin,
+                    mut read: Box<dyn SocketStream>,
                     reader_channels_arc: Arc<RwLock<HashMap<String, Arc<Channel>>>>,
                     reader_socket_arc: Arc<RwLock<Option<mpsc::Sender<Message>>>>, // Need socket to potentially rejoin
                     reader_state_arc: Arc<RwLock<ConnectionState>>,
@@ -404,23 +499,60 @@ impl RealtimeClient {
                     _reader_reconnect_attempts: Arc<AtomicU32>, // Prefix unused parameter
                     reader_options: RealtimeClientOptions,      // Pass options
                     reader_is_manually_closed: Arc<AtomicBool>,
+                    metrics: Arc<ConnectionMetrics>,
+                    mut stop_rx: watch::Receiver<()>,
                 ) {
                     info!("Reader task started");
-                    while let Some(result) = read.next().await {
+                    loop {
+                        let result = tokio::select! {
+                            result = read.recv() => match result {
+                                Some(result) => result,
+                                None => break,
+                            },
+                            // disconnect() で停止シグナルの送信側が落とされた
+                            _ = stop_rx.changed() => {
+                                info!("Reader: stop signal received");
+                                break;
+                            }
+                        };
                         match result {
                             Ok(msg) => {
                                 trace!(message = ?msg, "Received message from WebSocket");
                                 match msg {
                                     Message::Text(text) => {
+                                        if let Err(dropped) = limits::check(
+                                            &text,
+                                            reader_options.max_message_size,
+                                            reader_options.max_json_depth,
+                                        ) {
+                                            warn!("{}", dropped);
+                                            metrics.message_dropped();
+                                            for channel in reader_channels_arc.read().await.values() {
+                                                channel.message_dropped(dropped);
+                                            }
+                                            continue;
+                                        }
                                         match serde_json::from_str::<RealtimeMessage>(&text) {
                                             Ok(parsed_msg) => {
                                                 trace!(message = ?parsed_msg, "Parsed RealtimeMessage");
+                                                if parsed_msg.topic == "phoenix"
+                                                    && parsed_msg.event
+                                                        == ChannelEvent::PhoenixReply
+                                                {
+                                                    if let Some(message_ref) =
+                                                        parsed_msg.message_ref.as_str()
+                                                    {
+                                                        metrics.heartbeat_replied(message_ref);
+                                                    }
+                                                }
                                                 // Route message to appropriate channel
                                                 let channels = reader_channels_arc.read().await;
                                                 if let Some(channel) =
                                                     channels.get(&parsed_msg.topic)
                                                 {
-                                                    channel.handle_message(parsed_msg).await;
+                                                    channel
+                                                        .handle_message(parsed_msg, text.len())
+                                                        .await;
                                                 }
                                                 // TODO: Handle phoenix-level messages (e.g., replies)
                                             }
@@ -520,14 +652,35 @@ impl RealtimeClient {
                     reader_reconnect_attempts,
                     reader_options,
                     reader_is_manually_closed,
+                    reader_metrics,
+                    stop_rx,
                 )
                 .await;
-            });
+            }.instrument(Span::current()));
+
+            // 前の接続で参加していたチャンネルへ再参加 (キャッチアップフックもここで実行)
+            let channels = _channels_arc.read().await.values().cloned().collect::<Vec<_>>();
+            for channel in channels {
+                if !channel.needs_rejoin().await {
+                    continue;
+                }
+                let guard = TaskGuard::new(&tasks);
+                tokio::spawn(
+                    async move {
+                        let _guard = guard;
+                        if let Err(e) = channel.rejoin().await {
+                            warn!(error = %e, "Failed to rejoin channel after reconnect");
+                        }
+                    }
+                    .instrument(Span::current()),
+                );
+            }
 
             info!("Connect task completed successfully (connection established, reader/writer tasks spawned)");
             // Note: The outer future completes here, but the reader/writer tasks continue.
             Ok(())
         }
+        .instrument(span)
     }
 
     /// Helper for setting state internally, avoiding self borrow issues.
@@ -574,6 +727,8 @@ impl RealtimeClient {
             info!("disconnect() called but no active socket sender found (already disconnected?)");
         }
         drop(socket_guard);
+        // reader タスクを停止
+        drop(self.stop.lock().unwrap().take());
 
         // Clean up channels (optional, depends on desired behavior on disconnect)
         // let mut channels = self.channels.write().await;
@@ -637,7 +792,15 @@ impl RealtimeClient {
                             }
                         }
                         warn!("Scheduling next reconnect attempt...");
-                        tokio::spawn(self_clone.reconnect());
+                        let guard = TaskGuard::new(&self_clone.tasks);
+                        let reconnect = self_clone.reconnect();
+                        tokio::spawn(
+                            async move {
+                                let _guard = guard;
+                                reconnect.await;
+                            }
+                            .instrument(self_clone.span()),
+                        );
                     }
                 }
             }
@@ -689,6 +852,11 @@ impl Clone for RealtimeClient {
             is_manually_closed: self.is_manually_closed.clone(),
             state_change: self.state_change.clone(),
             access_token: self.access_token.clone(),
+            transport: self.transport.clone(),
+            metrics: self.metrics.clone(),
+            label: self.label.clone(),
+            tasks: self.tasks.clone(),
+            stop: self.stop.clone(),
         }
     }
 }
@@ -1,3 +1,5 @@
+use crate::bindings::POSTGRES_CHANGES_DOCS_URL;
+use crate::stream::GapReason;
 use thiserror::Error;
 
 /// エラー型
@@ -20,6 +22,36 @@ pub enum RealtimeError {
 
     #[error("Connection error: {0}")]
     ConnectionError(String),
+
+    #[error("Pool error: {0}")]
+    PoolError(String),
+
+    /// サーバーが購読を拒否した (join の `phx_reply` や `system` メッセージのエラー)
+    #[error(
+        "Realtime rejected the subscription to '{topic}': {message} (see {})",
+        POSTGRES_CHANGES_DOCS_URL
+    )]
+    SubscribeRejected { topic: String, message: String },
+
+    /// 購読のストリームがイベントを受け取れなかった (可能性がある、`StreamItem::GapDetected` を参照)
+    #[error("Events {expected}..{got} on '{topic}' may have been missed ({reason:?})")]
+    EventsMissed {
+        topic: String,
+        expected: u64,
+        got: u64,
+        reason: GapReason,
+    },
+}
+
+/// 購読ハンドラーのエラー (非同期ハンドラーが返したエラー、またはパニック)
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Handler error in subscription {subscription_id}: {message}")]
+pub struct HandlerError {
+    /// エラーを起こしたハンドラーの購読ID (`Subscription::id`)
+    pub subscription_id: String,
+    pub message: String,
+    /// ハンドラーがパニックした場合は true
+    pub panicked: bool,
 }
 
 impl RealtimeError {
@@ -11,6 +11,31 @@ pub struct DatabaseFilter {
     pub value: serde_json::Value,
 }
 
+impl DatabaseFilter {
+    /// Realtime の `filter` 形式 (`column=op.value`、`in` は `column=in.(a,b)`)
+    pub(crate) fn to_filter_string(&self) -> String {
+        let value = match &self.value {
+            serde_json::Value::Array(values) => format!(
+                "({})",
+                values
+                    .iter()
+                    .map(filter_value)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            value => filter_value(value),
+        };
+        format!("{}={}.{}", self.column, self.operator, value)
+    }
+}
+
+fn filter_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
 /// フィルター演算子
 #[derive(Debug, Clone, PartialEq, Eq, Serialize)] // Added Eq
 pub enum FilterOperator {
@@ -4,21 +4,1459 @@
 //! allowing for subscribing to database changes in real-time.
 
 // Declare modules
+mod bindings;
+mod changes;
 mod channel;
 mod client;
 mod error;
 mod filters;
+mod limits;
 mod message;
+mod pool;
+mod stats;
+mod stream;
+pub mod transport;
 
 // Re-export key public types
+pub use bindings::{ConfirmedBinding, SubscriptionWarning, POSTGRES_CHANGES_DOCS_URL};
+pub use changes::{ColumnInfo, PartialRecord, PostgresChangesPayload};
 pub use channel::{
-    BroadcastChanges, ChannelBuilder, DatabaseChanges, PresenceChanges, Subscription,
+    BroadcastChanges, ChannelBuilder, DatabaseChanges, DispatchMode, PresenceChanges, Subscription,
 };
 pub use client::{ConnectionState, RealtimeClient, RealtimeClientOptions};
-pub use error::RealtimeError;
+pub use error::{HandlerError, RealtimeError};
 pub use filters::{DatabaseFilter, FilterOperator};
-pub use message::{ChannelEvent, Payload, PresenceChange, PresenceState, RealtimeMessage};
+pub use limits::{DropReason, DroppedMessage, DEFAULT_MAX_JSON_DEPTH, DEFAULT_MAX_MESSAGE_SIZE};
+pub use message::{
+    ChannelEvent, Payload, PresenceChange, PresenceEvent, PresenceState, RealtimeMessage,
+};
+pub use pool::{PoolStats, RealtimeClientPool};
+pub use stats::{ChannelStats, RealtimeStats};
+pub use stream::{
+    merge_subscriptions, BackpressurePolicy, EventStream, GapReason, StreamItem,
+    SubscriptionStream, DEFAULT_STREAM_CAPACITY,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::transport::{memory_socket, MemoryConnection, MemoryServer};
+    use super::*;
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio::time::timeout;
+
+    const WAIT: Duration = Duration::from_secs(2);
+
+    fn options() -> RealtimeClientOptions {
+        RealtimeClientOptions {
+            auto_reconnect: false,
+            heartbeat_interval: 60_000,
+            ..Default::default()
+        }
+    }
+
+    async fn connect(server: &mut MemoryServer, client: &RealtimeClient) -> MemoryConnection {
+        client.connect().await.unwrap();
+        timeout(WAIT, server.accept()).await.unwrap().unwrap()
+    }
+
+    // join に応答し、受信したメッセージを転送するサーバー
+    fn serve(
+        mut connection: MemoryConnection,
+        on_join: Vec<serde_json::Value>,
+    ) -> mpsc::UnboundedReceiver<RealtimeMessage> {
+        let (seen_tx, seen_rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(message) = connection.recv_message().await {
+                connection.reply_ok(&message).unwrap();
+                if message.event == ChannelEvent::PhoenixJoin {
+                    for event in &on_join {
+                        connection.send_json(event).unwrap();
+                    }
+                }
+                if seen_tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        seen_rx
+    }
+
+    async fn next_with(
+        seen: &mut mpsc::UnboundedReceiver<RealtimeMessage>,
+        event: ChannelEvent,
+    ) -> RealtimeMessage {
+        timeout(WAIT, async {
+            loop {
+                let message = seen.recv().await.expect("server stopped");
+                if message.event == event {
+                    return message;
+                }
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("timed out waiting for {:?}", event))
+    }
+
+    #[tokio::test]
+    async fn test_connect_uses_websocket_url() {
+        let (socket, mut server) = memory_socket();
+        let client =
+            RealtimeClient::new_with_socket("ws://localhost:4000", "anon", options(), socket);
+
+        let connection = connect(&mut server, &client).await;
+        assert!(connection
+            .url()
+            .starts_with("ws://localhost:4000/realtime/v1/websocket?vsn=2.0.0"));
+        assert!(connection.url().contains("apikey=anon"));
+        assert_eq!(
+            client.get_connection_state().await,
+            ConnectionState::Connected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_join() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        let mut seen = serve(connection, Vec::new());
+
+        let subscriptions = timeout(
+            WAIT,
+            client
+                .channel("realtime:public:todos")
+                .on(DatabaseChanges::new("todos"), |_| {})
+                .subscribe(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(subscriptions.len(), 1);
+
+        let join = next_with(&mut seen, ChannelEvent::PhoenixJoin).await;
+        assert_eq!(join.topic, "realtime:public:todos");
+    }
+
+    #[tokio::test]
+    async fn test_event_dispatch() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        let _seen = serve(
+            connection,
+            vec![json!({
+                "topic": "realtime:public:todos",
+                "event": "postgres_changes",
+                "payload": { "type": "INSERT", "schema": "public", "table": "todos", "record": { "id": 7 } },
+                "ref": null
+            })],
+        );
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let _subscriptions = client
+            .channel("realtime:public:todos")
+            .on(DatabaseChanges::new("todos"), move |payload| {
+                let _ = tx.send(payload);
+            })
+            .subscribe()
+            .await
+            .unwrap();
+
+        let payload = timeout(WAIT, rx.recv()).await.unwrap().unwrap();
+        assert_eq!(payload.data["record"]["id"], 7);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket(
+            "ws://localhost",
+            "anon",
+            RealtimeClientOptions {
+                heartbeat_interval: 20,
+                ..options()
+            },
+            socket,
+        );
+        let connection = connect(&mut server, &client).await;
+        let mut seen = serve(connection, Vec::new());
+
+        for _ in 0..2 {
+            let heartbeat = next_with(&mut seen, ChannelEvent::Heartbeat).await;
+            assert_eq!(heartbeat.topic, "phoenix");
+            assert!(heartbeat.message_ref.as_str().unwrap().starts_with("hb-"));
+        }
+    }
+
+    fn change_event(id: i64) -> serde_json::Value {
+        json!({
+            "topic": "realtime:public:todos",
+            "event": "postgres_changes",
+            "payload": { "type": "INSERT", "schema": "public", "table": "todos", "record": { "id": id } },
+            "ref": null
+        })
+    }
+
+    #[tokio::test]
+    async fn test_channel_stats() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let mut connection = connect(&mut server, &client).await;
+
+        // join に応答し、その後はテストから渡されたイベントを送る
+        let (live_tx, mut live_rx) = mpsc::unbounded_channel::<serde_json::Value>();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    message = connection.recv_message() => {
+                        let Some(message) = message else { break };
+                        connection.reply_ok(&message).unwrap();
+                    }
+                    event = live_rx.recv() => {
+                        let Some(event) = event else { break };
+                        connection.send_json(&event).unwrap();
+                    }
+                }
+            }
+        });
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let subscriptions = client
+            .channel("realtime:public:todos")
+            .on(DatabaseChanges::new("todos"), move |payload| {
+                let _ = tx.send(payload);
+            })
+            .subscribe()
+            .await
+            .unwrap();
+        let initial = subscriptions[0].stats();
+        assert_eq!(initial.topic, "realtime:public:todos");
+        assert_eq!(initial.events_received, 0);
+        assert_eq!(initial.last_event_at, None);
+        assert!(initial.join_latency.is_some());
+
+        let events: Vec<_> = (1..=3).map(change_event).collect();
+        for event in &events {
+            live_tx.send(event.clone()).unwrap();
+        }
+        for _ in &events {
+            timeout(WAIT, rx.recv()).await.unwrap().unwrap();
+        }
+        let stats = subscriptions[0].stats();
+        assert_eq!(stats.events_received, 3);
+        assert_eq!(
+            stats.bytes_received,
+            events
+                .iter()
+                .map(|e| e.to_string().len() as u64)
+                .sum::<u64>()
+        );
+        let first_last_event_at = stats.last_event_at.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        live_tx.send(change_event(4)).unwrap();
+        timeout(WAIT, rx.recv()).await.unwrap().unwrap();
+        let stats = subscriptions[0].stats();
+        assert_eq!(stats.events_received, 4);
+        assert!(stats.last_event_at.unwrap() > first_last_event_at);
+        assert_eq!(stats.errors, 0);
+
+        live_tx
+            .send(json!({
+                "topic": "realtime:public:todos",
+                "event": "phx_error",
+                "payload": {},
+                "ref": null
+            }))
+            .unwrap();
+        timeout(WAIT, async {
+            while subscriptions[0].stats().errors == 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let client_stats = client.stats().await;
+        assert_eq!(client_stats.channels, vec![subscriptions[0].stats()]);
+        assert_eq!(client_stats.events_received, 4);
+        assert_eq!(client_stats.errors, 1);
+        assert_eq!(client_stats.connections, 1);
+        assert_eq!(client_stats.reconnects, 0);
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_rtt_stats() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket(
+            "ws://localhost",
+            "anon",
+            RealtimeClientOptions {
+                heartbeat_interval: 20,
+                ..options()
+            },
+            socket,
+        );
+        assert_eq!(client.stats().await.heartbeat_rtt, None);
+        let connection = connect(&mut server, &client).await;
+        let _seen = serve(connection, Vec::new());
+
+        timeout(WAIT, async {
+            while client.stats().await.heartbeat_rtt.is_none() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("heartbeat reply was not measured");
+    }
+
+    #[tokio::test]
+    async fn test_pool_rejects_duplicate_names_and_caps_connections() {
+        let (socket_a, mut server_a) = memory_socket();
+        let (socket_b, _server_b) = memory_socket();
+        let pool = RealtimeClientPool::new().with_max_connections(1);
+        pool.add(
+            "a",
+            RealtimeClient::new_with_socket("ws://a", "anon", options(), socket_a.clone()),
+        )
+        .unwrap();
+        pool.add(
+            "b",
+            RealtimeClient::new_with_socket("ws://b", "anon", options(), socket_b),
+        )
+        .unwrap();
+
+        let duplicate = pool.add(
+            "a",
+            RealtimeClient::new_with_socket("ws://a", "anon", options(), socket_a),
+        );
+        assert!(matches!(duplicate, Err(RealtimeError::PoolError(_))));
+        assert_eq!(pool.names(), vec!["a", "b"]);
+        assert_eq!(pool.get("a").unwrap().label(), Some("a"));
+
+        pool.connect("a").await.unwrap();
+        let mut connection = timeout(WAIT, server_a.accept()).await.unwrap().unwrap();
+        assert!(matches!(
+            pool.connect("b").await,
+            Err(RealtimeError::PoolError(_))
+        ));
+        assert!(matches!(
+            pool.connect("c").await,
+            Err(RealtimeError::PoolError(_))
+        ));
+
+        // 切断すると writer が Close を送ってから接続が閉じる
+        pool.shutdown_all(WAIT).await.unwrap();
+        assert_eq!(
+            timeout(WAIT, connection.recv()).await.unwrap(),
+            Some(tokio_tungstenite::tungstenite::Message::Close(None))
+        );
+        assert_eq!(timeout(WAIT, connection.recv()).await.unwrap(), None);
+
+        // 切断後は別のクライアントを接続できる
+        pool.connect("b").await.unwrap();
+        pool.shutdown_all(WAIT).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pool_stats_and_shutdown_all() {
+        let pool = RealtimeClientPool::new();
+        let mut servers = Vec::new();
+        for name in ["project-a", "project-b"] {
+            let (socket, server) = memory_socket();
+            pool.add(
+                name,
+                RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket),
+            )
+            .unwrap();
+            servers.push(server);
+        }
+        pool.connect_all().await.unwrap();
+        // writer と reader がクライアントごとに1つずつ
+        assert_eq!(pool.running_tasks(), 4);
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut subscriptions = Vec::new();
+        for (id, (name, server)) in pool.names().into_iter().zip(&mut servers).enumerate() {
+            let connection = timeout(WAIT, server.accept()).await.unwrap().unwrap();
+            let _seen = serve(
+                connection,
+                vec![json!({
+                    "topic": "realtime:public:todos",
+                    "event": "postgres_changes",
+                    "payload": { "type": "INSERT", "schema": "public", "table": "todos", "record": { "id": id } },
+                    "ref": null
+                })],
+            );
+            let tx = tx.clone();
+            let client = pool.get(&name).unwrap();
+            subscriptions.push(
+                client
+                    .channel("realtime:public:todos")
+                    .on(DatabaseChanges::new("todos"), move |_| {
+                        let _ = tx.send(());
+                    })
+                    .subscribe()
+                    .await
+                    .unwrap(),
+            );
+        }
+        for _ in 0..2 {
+            timeout(WAIT, rx.recv()).await.unwrap().unwrap();
+        }
+
+        let stats = pool.stats().await;
+        assert_eq!(stats.active_connections, 2);
+        assert_eq!(stats.events_received, 2);
+        assert_eq!(
+            stats.bytes_received,
+            stats
+                .clients
+                .values()
+                .map(|c| c.bytes_received)
+                .sum::<u64>()
+        );
+        for (name, client_stats) in &stats.clients {
+            assert_eq!(client_stats.label.as_deref(), Some(name.as_str()));
+            assert_eq!(client_stats.events_received, 1);
+            assert_eq!(client_stats.connections, 1);
+        }
+
+        pool.shutdown_all(WAIT).await.unwrap();
+        assert_eq!(pool.running_tasks(), 0);
+        assert_eq!(pool.stats().await.active_connections, 0);
+    }
+
+    // phoenix.js の Presence テストと同じフィクスチャ
+    fn presence(metas: &[(u64, &str)]) -> serde_json::Value {
+        let metas: Vec<_> = metas
+            .iter()
+            .map(|(id, phx_ref)| json!({ "id": id, "phx_ref": phx_ref }))
+            .collect();
+        json!({ "metas": metas })
+    }
+
+    fn presences(entries: &[(&str, serde_json::Value)]) -> HashMap<String, serde_json::Value> {
+        entries
+            .iter()
+            .map(|(key, presence)| (key.to_string(), presence.clone()))
+            .collect()
+    }
+
+    fn fixture_state() -> HashMap<String, serde_json::Value> {
+        presences(&[
+            ("u1", presence(&[(1, "1")])),
+            ("u2", presence(&[(2, "2")])),
+            ("u3", presence(&[(3, "3")])),
+        ])
+    }
+
+    fn join(key: &str, current: &[(u64, &str)], new: &[(u64, &str)]) -> PresenceEvent {
+        PresenceEvent::Join {
+            key: key.to_string(),
+            current_metas: presence(current)["metas"].as_array().unwrap().clone(),
+            new_metas: presence(new)["metas"].as_array().unwrap().clone(),
+        }
+    }
+
+    fn leave(key: &str, current: &[(u64, &str)], left: &[(u64, &str)]) -> PresenceEvent {
+        PresenceEvent::Leave {
+            key: key.to_string(),
+            current_metas: presence(current)["metas"].as_array().unwrap().clone(),
+            left_metas: presence(left)["metas"].as_array().unwrap().clone(),
+        }
+    }
+
+    #[test]
+    fn test_presence_sync_state() {
+        // 空の状態に同期する
+        let mut state = PresenceState::new();
+        let new_state = presences(&[("u1", presence(&[(1, "1")]))]);
+        state.sync_state(&new_state);
+        assert_eq!(state.state, new_state);
+
+        // 新しいキーは join、消えたキーは leave
+        let mut state = PresenceState {
+            state: presences(&[("u4", presence(&[(4, "4")]))]),
+        };
+        let events = state.sync_state(&fixture_state());
+        assert_eq!(
+            events,
+            vec![
+                join("u1", &[], &[(1, "1")]),
+                join("u2", &[], &[(2, "2")]),
+                join("u3", &[], &[(3, "3")]),
+                leave("u4", &[], &[(4, "4")]),
+            ]
+        );
+        assert_eq!(state.state, fixture_state());
+
+        // 既存のキーには新しく追加された metas だけが join として通知される
+        let mut state = PresenceState {
+            state: presences(&[("u3", presence(&[(3, "3")]))]),
+        };
+        let new_state = presences(&[("u3", presence(&[(3, "3"), (3, "3.new")]))]);
+        let events = state.sync_state(&new_state);
+        assert_eq!(events, vec![join("u3", &[(3, "3")], &[(3, "3.new")])]);
+        assert_eq!(state.state, new_state);
+    }
+
+    #[test]
+    fn test_presence_sync_diff() {
+        // metas が空になったキーは削除し、join した metas は既存の metas に追加する
+        let mut state = PresenceState {
+            state: fixture_state(),
+        };
+        state.sync_diff(&PresenceChange {
+            joins: presences(&[("u1", presence(&[(1, "1.2")]))]),
+            leaves: presences(&[("u2", presence(&[(2, "2")]))]),
+        });
+        assert_eq!(
+            state.state,
+            presences(&[
+                ("u1", presence(&[(1, "1"), (1, "1.2")])),
+                ("u3", presence(&[(3, "3")])),
+            ])
+        );
+
+        // metas が残っている間はキーを削除しない
+        let mut state = PresenceState {
+            state: presences(&[("u1", presence(&[(1, "1"), (1, "1.2")]))]),
+        };
+        let events = state.sync_diff(&PresenceChange {
+            joins: HashMap::new(),
+            leaves: presences(&[("u1", presence(&[(1, "1")]))]),
+        });
+        assert_eq!(events, vec![leave("u1", &[(1, "1.2")], &[(1, "1")])]);
+        assert_eq!(state.state, presences(&[("u1", presence(&[(1, "1.2")]))]));
+
+        // 同じ差分で join と leave の両方に含まれるキー (別タブでの再接続など)
+        let mut state = PresenceState {
+            state: presences(&[("u1", presence(&[(1, "1")]))]),
+        };
+        let events = state.sync_diff(&PresenceChange {
+            joins: presences(&[("u1", presence(&[(1, "1.new")]))]),
+            leaves: presences(&[("u1", presence(&[(1, "1")]))]),
+        });
+        assert_eq!(
+            events,
+            vec![
+                join("u1", &[(1, "1")], &[(1, "1.new")]),
+                leave("u1", &[(1, "1.new")], &[(1, "1")]),
+            ]
+        );
+        assert_eq!(state.state, presences(&[("u1", presence(&[(1, "1.new")]))]));
+    }
+
+    #[tokio::test]
+    async fn test_presence_callbacks() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        let _seen = serve(
+            connection,
+            vec![
+                json!({
+                    "topic": "room:lobby",
+                    "event": "presence_state",
+                    "payload": { "u1": presence(&[(1, "1")]) },
+                    "ref": null
+                }),
+                json!({
+                    "topic": "room:lobby",
+                    "event": "presence_diff",
+                    "payload": {
+                        "joins": { "u2": presence(&[(2, "2")]) },
+                        "leaves": { "u1": presence(&[(1, "1")]) }
+                    },
+                    "ref": null
+                }),
+            ],
+        );
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (join_tx, leave_tx, sync_tx) = (tx.clone(), tx.clone(), tx);
+        let changes = PresenceChanges::new()
+            .on_join(move |key, current, new| {
+                let _ = join_tx.send(json!(["join", key, current, new]));
+            })
+            .on_leave(move |key, current, left| {
+                let _ = leave_tx.send(json!(["leave", key, current, left]));
+            })
+            .on_sync(move |state| {
+                let mut keys: Vec<_> = state.state.keys().cloned().collect();
+                keys.sort();
+                let _ = sync_tx.send(json!(["sync", keys]));
+            });
+        let subscriptions = client
+            .channel("room:lobby")
+            .on_presence_changes(changes)
+            .subscribe()
+            .await
+            .unwrap();
+
+        let mut received = Vec::new();
+        for _ in 0..5 {
+            received.push(timeout(WAIT, rx.recv()).await.unwrap().unwrap());
+        }
+        assert_eq!(
+            received,
+            vec![
+                json!(["join", "u1", [], [{ "id": 1, "phx_ref": "1" }]]),
+                json!(["sync", ["u1"]]),
+                json!(["join", "u2", [], [{ "id": 2, "phx_ref": "2" }]]),
+                json!(["leave", "u1", [], [{ "id": 1, "phx_ref": "1" }]]),
+                json!(["sync", ["u2"]]),
+            ]
+        );
+        assert_eq!(
+            subscriptions[0].presence_state().state,
+            presences(&[("u2", presence(&[(2, "2")]))])
+        );
+    }
+
+    fn change_at(id: i64, commit_timestamp: &str) -> serde_json::Value {
+        json!({
+            "topic": "realtime:public:todos",
+            "event": "postgres_changes",
+            "payload": {
+                "type": "UPDATE",
+                "schema": "public",
+                "table": "todos",
+                "commit_timestamp": commit_timestamp,
+                "record": { "id": id }
+            },
+            "ref": null
+        })
+    }
+
+    #[tokio::test]
+    async fn test_rejoin_replays_missed_changes_before_live_events() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let mut connection = connect(&mut server, &client).await;
+
+        let (since_tx, mut since_rx) = mpsc::unbounded_channel();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let subscribe = client
+            .channel("realtime:public:todos")
+            .on_with_catch_up(
+                DatabaseChanges::new("todos"),
+                move |payload| {
+                    let _ = tx.send(payload);
+                },
+                move |since| {
+                    let _ = since_tx.send(since);
+                    async {
+                        // ライブイベントがキャッチアップ中に届くよう少し待つ
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        vec![
+                            Payload::catch_up(
+                                change_at(2, "2024-01-01T00:00:02Z")["payload"].clone(),
+                            ),
+                            Payload::catch_up(
+                                change_at(3, "2024-01-01T00:00:03Z")["payload"].clone(),
+                            ),
+                        ]
+                    }
+                },
+            )
+            .subscribe();
+        let server_side = async {
+            let join = connection.recv_message().await.unwrap();
+            connection.reply_ok(&join).unwrap();
+            connection
+                .send_json(&change_at(1, "2024-01-01T00:00:01Z"))
+                .unwrap();
+        };
+        let (subscriptions, _) = tokio::join!(subscribe, server_side);
+        let _subscriptions = subscriptions.unwrap();
+        let first = timeout(WAIT, rx.recv()).await.unwrap().unwrap();
+        assert_eq!(first.data["record"]["id"], 1);
+        assert!(!first.synthetic);
+
+        // サーバー側から切断し、再接続する
+        let mut states = client.on_state_change();
+        drop(connection);
+        timeout(WAIT, async {
+            while states.recv().await.unwrap() != ConnectionState::Disconnected {}
+        })
+        .await
+        .unwrap();
+        let connection = connect(&mut server, &client).await;
+        let mut seen = serve(connection, vec![change_at(4, "2024-01-01T00:00:04Z")]);
+        next_with(&mut seen, ChannelEvent::PhoenixJoin).await;
+
+        let since = timeout(WAIT, since_rx.recv()).await.unwrap().unwrap();
+        assert_eq!(since.as_deref(), Some("2024-01-01T00:00:01Z"));
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            let payload = timeout(WAIT, rx.recv()).await.unwrap().unwrap();
+            received.push((
+                payload.data["record"]["id"].as_i64().unwrap(),
+                payload.synthetic,
+            ));
+        }
+        assert_eq!(received, vec![(2, true), (3, true), (4, false)]);
+    }
+
+    fn sequences(items: &[StreamItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| match item {
+                StreamItem::Event { sequence, payload } => {
+                    assert_eq!(payload.data["record"]["id"].as_u64(), Some(*sequence));
+                    format!("event {}", sequence)
+                }
+                StreamItem::GapDetected {
+                    expected,
+                    got,
+                    reason,
+                } => format!("gap {}..{} {:?}", expected, got, reason),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_stream_reports_buffer_overflow_once() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let mut connection = connect(&mut server, &client).await;
+
+        let (builder, mut oldest) = client.channel("realtime:public:todos").on_stream(
+            DatabaseChanges::new("todos"),
+            2,
+            BackpressurePolicy::DropOldest,
+        );
+        let (builder, mut newest) = builder.on_stream(
+            DatabaseChanges::new("todos"),
+            2,
+            BackpressurePolicy::DropNewest,
+        );
+        let server_side = async {
+            let join = connection.recv_message().await.unwrap();
+            connection.reply_ok(&join).unwrap();
+            for id in 1..=5 {
+                connection
+                    .send_json(&change_at(id, "2024-01-01T00:00:00Z"))
+                    .unwrap();
+            }
+        };
+        let (subscriptions, _) = tokio::join!(builder.subscribe(), server_side);
+        let subscriptions = subscriptions.unwrap();
+        timeout(WAIT, async {
+            while subscriptions[1].last_sequence() != Some(5) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(subscriptions[0].last_sequence(), Some(5));
+
+        let drain =
+            |stream: &mut EventStream| std::iter::from_fn(|| stream.try_recv()).collect::<Vec<_>>();
+        assert_eq!(
+            sequences(&drain(&mut oldest)),
+            ["gap 1..4 BufferOverflow", "event 4", "event 5"]
+        );
+        assert_eq!(
+            sequences(&drain(&mut newest)),
+            ["event 1", "event 2", "gap 3..6 BufferOverflow"]
+        );
+        assert_eq!(oldest.last_sequence(), Some(5));
+        assert_eq!(newest.last_sequence(), Some(2));
+
+        // 読み出した後のイベントでは再び通知されない
+        for id in 6..=7 {
+            connection
+                .send_json(&change_at(id, "2024-01-01T00:00:00Z"))
+                .unwrap();
+        }
+        for stream in [&mut oldest, &mut newest] {
+            let mut items = Vec::new();
+            for _ in 0..2 {
+                items.push(timeout(WAIT, stream.recv()).await.unwrap().unwrap());
+            }
+            assert_eq!(sequences(&items), ["event 6", "event 7"]);
+            assert!(stream.try_recv().is_none());
+        }
+    }
+
+    #[test]
+    fn test_drop_newest_gap_is_reported_after_close() {
+        let shared =
+            std::sync::Arc::new(stream::StreamShared::new(1, BackpressurePolicy::DropNewest));
+        let mut events = EventStream::new(shared.clone());
+        for id in 1..=3 {
+            shared.push(Payload::catch_up(json!({ "record": { "id": id } })));
+        }
+        // 捨てた後にイベントが届かないまま購読を解除する
+        shared.close();
+
+        let items = std::iter::from_fn(|| events.try_recv()).collect::<Vec<_>>();
+        assert_eq!(sequences(&items), ["event 1", "gap 2..4 BufferOverflow"]);
+        assert!(events.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_reports_reconnect_without_missed_events() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let mut connection = connect(&mut server, &client).await;
+
+        let (builder, events) = client.channel("realtime:public:todos").on_stream(
+            DatabaseChanges::new("todos"),
+            8,
+            BackpressurePolicy::default(),
+        );
+        let server_side = async {
+            let join = connection.recv_message().await.unwrap();
+            connection.reply_ok(&join).unwrap();
+            connection
+                .send_json(&change_at(1, "2024-01-01T00:00:01Z"))
+                .unwrap();
+        };
+        let (subscriptions, _) = tokio::join!(builder.subscribe(), server_side);
+        let _subscriptions = subscriptions.unwrap();
+        let mut events = Box::pin(events.into_stream());
+        let first = timeout(WAIT, events.next()).await.unwrap().unwrap();
+        assert_eq!(sequences(&[first]), ["event 1"]);
+
+        let mut states = client.on_state_change();
+        drop(connection);
+        timeout(WAIT, async {
+            while states.recv().await.unwrap() != ConnectionState::Disconnected {}
+        })
+        .await
+        .unwrap();
+        let connection = connect(&mut server, &client).await;
+        let mut seen = serve(connection, vec![change_at(2, "2024-01-01T00:00:02Z")]);
+        next_with(&mut seen, ChannelEvent::PhoenixJoin).await;
+
+        let mut items = Vec::new();
+        for _ in 0..2 {
+            items.push(timeout(WAIT, events.next()).await.unwrap().unwrap());
+        }
+        // 取りこぼしがなくても再接続は通知される (expected == got)
+        assert_eq!(sequences(&items), ["gap 2..2 Reconnect", "event 2"]);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_and_deeply_nested_messages_are_dropped() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket(
+            "ws://localhost",
+            "anon",
+            RealtimeClientOptions {
+                max_message_size: 1024,
+                max_json_depth: 16,
+                ..options()
+            },
+            socket,
+        );
+        let mut connection = connect(&mut server, &client).await;
+
+        let (builder, events) = client.channel("realtime:public:todos").on_stream(
+            DatabaseChanges::new("todos"),
+            8,
+            BackpressurePolicy::default(),
+        );
+        let server_side = async {
+            let join = connection.recv_message().await.unwrap();
+            connection.reply_ok(&join).unwrap();
+            connection
+                .send_json(&change_at(1, "2024-01-01T00:00:01Z"))
+                .unwrap();
+        };
+        let (subscriptions, _) = tokio::join!(builder.subscribe(), server_side);
+        let subscriptions = subscriptions.unwrap();
+        let mut warnings = subscriptions[0].warning_events();
+        let mut events = Box::pin(events.into_stream());
+        let first = timeout(WAIT, events.next()).await.unwrap().unwrap();
+        assert_eq!(sequences(&[first]), ["event 1"]);
+
+        let mut oversized = change_at(99, "2024-01-01T00:00:02Z");
+        oversized["payload"]["record"]["body"] = json!("x".repeat(4096));
+        connection.send_json(&oversized).unwrap();
+        let mut nested = change_at(99, "2024-01-01T00:00:02Z");
+        nested["payload"]["record"]["tree"] =
+            serde_json::from_str(&format!("{}{}", "[".repeat(32), "]".repeat(32))).unwrap();
+        connection.send_json(&nested).unwrap();
+        // 接続は切れずに後続のイベントが届く
+        connection
+            .send_json(&change_at(2, "2024-01-01T00:00:03Z"))
+            .unwrap();
+
+        let mut items = Vec::new();
+        for _ in 0..3 {
+            items.push(timeout(WAIT, events.next()).await.unwrap().unwrap());
+        }
+        assert_eq!(
+            sequences(&items),
+            [
+                "gap 2..2 MessageDropped",
+                "gap 2..2 MessageDropped",
+                "event 2"
+            ]
+        );
+
+        let warning = timeout(WAIT, warnings.recv()).await.unwrap().unwrap();
+        match warning {
+            SubscriptionWarning::MessageDropped(dropped) => {
+                assert!(dropped.size > 4096);
+                assert_eq!(dropped.reason, DropReason::TooLarge { limit: 1024 });
+            }
+            other => panic!("unexpected warning: {:?}", other),
+        }
+        let warning = timeout(WAIT, warnings.recv()).await.unwrap().unwrap();
+        assert!(matches!(
+            warning,
+            SubscriptionWarning::MessageDropped(DroppedMessage {
+                reason: DropReason::TooDeep { limit: 16 },
+                ..
+            })
+        ));
+        assert!(warning.to_string().contains("max_json_depth"));
+
+        assert_eq!(client.stats().await.messages_dropped, 2);
+        assert_eq!(
+            client.get_connection_state().await,
+            ConnectionState::Connected
+        );
+    }
+
+    // join などに応答し、受信したメッセージを `seen` に、`live` に送った値をクライアントに流すサーバー
+    fn serve_live(
+        mut connection: MemoryConnection,
+    ) -> (
+        mpsc::UnboundedReceiver<RealtimeMessage>,
+        mpsc::UnboundedSender<serde_json::Value>,
+    ) {
+        let (seen_tx, seen_rx) = mpsc::unbounded_channel();
+        let (live_tx, mut live_rx) = mpsc::unbounded_channel::<serde_json::Value>();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    message = connection.recv_message() => {
+                        let Some(message) = message else { break };
+                        connection.reply_ok(&message).unwrap();
+                        let _ = seen_tx.send(message);
+                    }
+                    event = live_rx.recv() => {
+                        let Some(event) = event else { break };
+                        connection.send_json(&event).unwrap();
+                    }
+                }
+            }
+        });
+        (seen_rx, live_tx)
+    }
+
+    fn change_on(topic: &str, id: i64) -> serde_json::Value {
+        let mut change = change_at(id, "2024-01-01T00:00:00Z");
+        change["topic"] = json!(topic);
+        change
+    }
+
+    fn record_id(payload: &Payload) -> i64 {
+        payload.data["record"]["id"].as_i64().unwrap()
+    }
+
+    // `futures_util::StreamExt` と名前が衝突しないよう tokio_stream の StreamExt だけを使う
+    mod tokio_stream_combinators {
+        use super::*;
+        use tokio_stream::StreamExt;
+
+        #[tokio::test]
+        async fn test_subscription_stream_with_tokio_stream_combinators() {
+            let (socket, mut server) = memory_socket();
+            let client =
+                RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+            let connection = connect(&mut server, &client).await;
+            let (_seen, live) = serve_live(connection);
+
+            let (tx, mut callback_ids) = mpsc::unbounded_channel();
+            let mut subscriptions = client
+                .channel("realtime:public:todos")
+                .on(DatabaseChanges::new("todos"), move |payload| {
+                    let _ = tx.send(record_id(&payload));
+                })
+                .subscribe()
+                .await
+                .unwrap();
+            let stream = subscriptions.remove(0).into_stream();
+            assert_eq!(stream.topic(), "realtime:public:todos");
+
+            for id in 1..=6 {
+                live.send(change_on("realtime:public:todos", id)).unwrap();
+            }
+            let even_ids = stream
+                .filter_map(|item| item.ok())
+                .map(|payload| record_id(&payload))
+                .filter(|id| id % 2 == 0)
+                .timeout(WAIT)
+                .take(3)
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(even_ids, [2, 4, 6]);
+
+            // コールバックにも同じイベントが届く
+            for id in 1..=6 {
+                assert_eq!(timeout(WAIT, callback_ids.recv()).await.unwrap(), Some(id));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dropping_subscription_stream_unsubscribes_and_leaves() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        let (mut seen, live) = serve_live(connection);
+
+        let mut subscriptions = client
+            .channel("realtime:public:todos")
+            .on(DatabaseChanges::new("todos"), |_| {})
+            .on(
+                DatabaseChanges::new("todos").event(ChannelEvent::Insert),
+                |_| {},
+            )
+            .subscribe()
+            .await
+            .unwrap();
+        next_with(&mut seen, ChannelEvent::PhoenixJoin).await;
+        let mut first = subscriptions.remove(0).into_stream();
+        let second = subscriptions.remove(0).into_stream();
+
+        for id in 1..=3 {
+            live.send(change_on("realtime:public:todos", id)).unwrap();
+        }
+        let payload = timeout(WAIT, first.next()).await.unwrap().unwrap().unwrap();
+        assert_eq!(record_id(&payload), 1);
+        // 読み残しがあるまま破棄する。もう1つの購読が残っているのでチャンネルには留まる
+        drop(first);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(client.stats().await.channels.len(), 1);
+
+        drop(second);
+        let leave = next_with(&mut seen, ChannelEvent::PhoenixLeave).await;
+        assert_eq!(leave.topic, "realtime:public:todos");
+        assert!(client.stats().await.channels.is_empty());
+
+        // 退出後のイベントは無視される
+        live.send(change_on("realtime:public:todos", 4)).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    #[tokio::test]
+    async fn test_merge_subscriptions_tags_items_with_topic() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        let (mut seen, live) = serve_live(connection);
+
+        let mut subscriptions = Vec::new();
+        for topic in ["realtime:public:todos", "realtime:public:notes"] {
+            subscriptions.extend(
+                client
+                    .channel(topic)
+                    .on(DatabaseChanges::new("todos"), |_| {})
+                    .subscribe()
+                    .await
+                    .unwrap(),
+            );
+        }
+        let mut merged = merge_subscriptions(subscriptions);
+
+        live.send(change_on("realtime:public:todos", 1)).unwrap();
+        live.send(change_on("realtime:public:notes", 2)).unwrap();
+        live.send(change_on("realtime:public:todos", 3)).unwrap();
+        let mut items = Vec::new();
+        for _ in 0..3 {
+            let (topic, item) = timeout(WAIT, merged.next()).await.unwrap().unwrap();
+            items.push((topic, record_id(&item.unwrap())));
+        }
+        items.sort();
+        assert_eq!(
+            items,
+            [
+                ("realtime:public:notes".to_string(), 2),
+                ("realtime:public:todos".to_string(), 1),
+                ("realtime:public:todos".to_string(), 3),
+            ]
+        );
+
+        drop(merged);
+        let mut left = Vec::new();
+        for _ in 0..2 {
+            left.push(next_with(&mut seen, ChannelEvent::PhoenixLeave).await.topic);
+        }
+        left.sort();
+        assert_eq!(left, ["realtime:public:notes", "realtime:public:todos"]);
+    }
+
+    #[tokio::test]
+    async fn test_subscription_stream_wakes_waiting_consumer_under_backpressure() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        let (_seen, live) = serve_live(connection);
+
+        let mut subscriptions = client
+            .channel("realtime:public:todos")
+            .on(DatabaseChanges::new("todos"), |_| {})
+            .subscribe()
+            .await
+            .unwrap();
+        let mut stream = subscriptions
+            .remove(0)
+            .into_stream_with_capacity(4, BackpressurePolicy::DropOldest);
+
+        // 消費側が待っている状態からイベントを一気に送る
+        let consumer = tokio::spawn(async move {
+            let mut last = 0;
+            let mut missed = 0;
+            while last < 500 {
+                match stream.next().await.expect("stream ended early") {
+                    Ok(payload) => {
+                        let id = record_id(&payload);
+                        assert!(id > last, "{} after {}", id, last);
+                        last = id;
+                    }
+                    Err(RealtimeError::EventsMissed { reason, .. }) => {
+                        assert_eq!(reason, GapReason::BufferOverflow);
+                        missed += 1;
+                    }
+                    Err(other) => panic!("unexpected error {:?}", other),
+                }
+            }
+            missed
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        for id in 1..=500 {
+            live.send(change_on("realtime:public:todos", id)).unwrap();
+            if id % 50 == 0 {
+                tokio::task::yield_now().await;
+            }
+        }
+        timeout(WAIT, consumer).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_presence_subscription_stream_fails() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        let (_seen, _live) = serve_live(connection);
+
+        let mut subscriptions = client
+            .channel("realtime:room")
+            .on_presence(|_| {})
+            .subscribe()
+            .await
+            .unwrap();
+        let mut stream = subscriptions.remove(0).into_stream();
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(RealtimeError::SubscriptionError(_)))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_wildcard_handler_runs_after_specific_handlers() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        let _seen = serve(
+            connection,
+            vec![
+                json!({
+                    "topic": "realtime:public:todos",
+                    "event": "postgres_changes",
+                    "payload": { "type": "DELETE", "schema": "public", "table": "todos", "old_record": { "id": 7 } },
+                    "ref": null
+                }),
+                json!({
+                    "topic": "realtime:public:todos",
+                    "event": "broadcast",
+                    "payload": { "type": "broadcast", "event": "cursor", "payload": { "x": 1 } },
+                    "ref": null
+                }),
+            ],
+        );
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let handler = |label: &'static str| {
+            let tx = tx.clone();
+            move |_: Payload| {
+                let _ = tx.send(label);
+            }
+        };
+        // ワイルドカードを先に登録しても、種類別のハンドラーの後に呼ばれる
+        let _subscriptions = client
+            .channel("realtime:public:todos")
+            .on_all(handler("all"))
+            .on(
+                DatabaseChanges::new("todos").event(ChannelEvent::Delete),
+                handler("delete"),
+            )
+            .on(
+                DatabaseChanges::new("todos").event(ChannelEvent::Insert),
+                handler("insert"),
+            )
+            .on(DatabaseChanges::new("todos"), handler("any"))
+            .on_broadcast(BroadcastChanges::new("other"), handler("other"))
+            .subscribe()
+            .await
+            .unwrap();
+
+        let mut received = Vec::new();
+        for _ in 0..4 {
+            received.push(timeout(WAIT, rx.recv()).await.unwrap().unwrap());
+        }
+        assert_eq!(received, vec!["delete", "any", "all", "all"]);
+        assert!(timeout(Duration::from_millis(50), rx.recv()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_join_payload_narrows_postgres_changes_events() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        let mut seen = serve(connection, Vec::new());
+
+        let _subscriptions = client
+            .channel("realtime:public:todos")
+            .on(
+                DatabaseChanges::new("todos")
+                    .event(ChannelEvent::Delete)
+                    .eq("user_id", 1),
+                |_| {},
+            )
+            .on(
+                DatabaseChanges::new("todos")
+                    .schema("app")
+                    .event(ChannelEvent::Insert)
+                    .event(ChannelEvent::Update)
+                    .in_values("status", vec!["open", "done"]),
+                |_| {},
+            )
+            .on(DatabaseChanges::new("todos"), |_| {})
+            .on_all(|_| {})
+            .subscribe()
+            .await
+            .unwrap();
+
+        let join = next_with(&mut seen, ChannelEvent::PhoenixJoin).await;
+        assert_eq!(
+            join.payload["config"]["postgres_changes"],
+            json!([
+                { "event": "DELETE", "schema": "public", "table": "todos", "filter": "user_id=eq.1" },
+                { "event": "INSERT", "schema": "app", "table": "todos", "filter": "status=in.(open,done)" },
+                { "event": "UPDATE", "schema": "app", "table": "todos", "filter": "status=in.(open,done)" },
+                { "event": "*", "schema": "public", "table": "todos" }
+            ])
+        );
+    }
+
+    // join には `reply` (phx_reply の payload) で応答するサーバー (戻り値でメッセージを後から送れる)
+    fn serve_join_reply(
+        mut connection: MemoryConnection,
+        reply: serde_json::Value,
+    ) -> mpsc::UnboundedSender<serde_json::Value> {
+        let (push_tx, mut push_rx) = mpsc::unbounded_channel::<serde_json::Value>();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Some(event) = push_rx.recv() => connection.send_json(&event).unwrap(),
+                    message = connection.recv_message() => {
+                        let Some(message) = message else { break };
+                        if message.event != ChannelEvent::PhoenixJoin {
+                            connection.reply_ok(&message).unwrap();
+                            continue;
+                        }
+                        connection
+                            .send_json(&json!({
+                                "topic": message.topic,
+                                "event": "phx_reply",
+                                "payload": reply,
+                                "ref": message.message_ref,
+                            }))
+                            .unwrap();
+                    }
+                }
+            }
+        });
+        push_tx
+    }
+
+    fn todos_channel(client: &RealtimeClient) -> ChannelBuilder<'_> {
+        client
+            .channel("realtime:public:todos")
+            .on(
+                DatabaseChanges::new("todos")
+                    .event(ChannelEvent::Insert)
+                    .eq("user_id", 1),
+                |_| {},
+            )
+            .on(
+                DatabaseChanges::new("notes").event(ChannelEvent::Delete),
+                |_| {},
+            )
+    }
+
+    #[tokio::test]
+    async fn test_join_reply_confirms_all_bindings() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        serve_join_reply(
+            connection,
+            json!({ "status": "ok", "response": { "postgres_changes": [
+                { "id": 11, "event": "INSERT", "schema": "public", "table": "todos", "filter": "user_id=eq.1" },
+                { "id": 12, "event": "DELETE", "schema": "public", "table": "notes" }
+            ] } }),
+        );
+
+        let subscriptions = todos_channel(&client).subscribe().await.unwrap();
+        let confirmed = subscriptions[0].confirmed_bindings();
+        assert_eq!(confirmed.len(), 2);
+        assert_eq!(confirmed[0].id, Some(11));
+        assert_eq!(confirmed[0].filter.as_deref(), Some("user_id=eq.1"));
+        assert_eq!(confirmed[1].table, "notes");
+        assert!(subscriptions[0].warnings().is_empty());
+        assert!(subscriptions[0].subscribe_error().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_join_reply_with_partial_confirmation_warns() {
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        serve_join_reply(
+            connection,
+            json!({ "status": "ok", "response": { "postgres_changes": [
+                { "id": 12, "event": "DELETE", "schema": "public", "table": "notes" }
+            ] } }),
+        );
+
+        let subscriptions = todos_channel(&client).subscribe().await.unwrap();
+        assert_eq!(subscriptions[1].confirmed_bindings().len(), 1);
+        let warnings = subscriptions[1].warnings();
+        assert_eq!(
+            warnings,
+            vec![SubscriptionWarning::BindingNotConfirmed {
+                binding: "INSERT on public.todos (filter: user_id=eq.1)".to_string()
+            }]
+        );
+        assert!(warnings[0].to_string().contains(POSTGRES_CHANGES_DOCS_URL));
+    }
+
+    #[tokio::test]
+    async fn test_join_reply_with_no_or_failed_bindings_warns() {
+        for (entries, expected) in [
+            (
+                json!([]),
+                vec![SubscriptionWarning::NoBindingsConfirmed {
+                    requested: vec![
+                        "INSERT on public.todos (filter: user_id=eq.1)".to_string(),
+                        "DELETE on public.notes".to_string(),
+                    ],
+                }],
+            ),
+            (
+                json!([
+                    { "id": 11, "event": "INSERT", "schema": "public", "table": "todos", "filter": "user_id=eq.1" },
+                    { "event": "DELETE", "schema": "public", "table": "notes", "error": "table not in publication" }
+                ]),
+                vec![SubscriptionWarning::BindingError {
+                    binding: "DELETE on public.notes".to_string(),
+                    message: "table not in publication".to_string(),
+                }],
+            ),
+        ] {
+            let (socket, mut server) = memory_socket();
+            let client =
+                RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+            let connection = connect(&mut server, &client).await;
+            serve_join_reply(
+                connection,
+                json!({ "status": "ok", "response": { "postgres_changes": entries } }),
+            );
+
+            let subscriptions = todos_channel(&client).subscribe().await.unwrap();
+            assert_eq!(subscriptions[0].warnings(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_error_payload_is_surfaced() {
+        // join 自体が拒否された場合は subscribe() がエラーになる
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        serve_join_reply(
+            connection,
+            json!({ "status": "error", "response": { "reason": "Invalid JWT" } }),
+        );
+        match todos_channel(&client).subscribe().await {
+            Err(error @ RealtimeError::SubscribeRejected { .. }) => {
+                let text = error.to_string();
+                assert!(text.contains("Invalid JWT"));
+                assert!(text.contains(POSTGRES_CHANGES_DOCS_URL));
+            }
+            other => panic!("expected SubscribeRejected, got {:?}", other.err()),
+        }
 
-// TODO: Move tests from the original lib.rs into integration tests (`tests/`) or inline here.
-// mod tests {
-// }
+        // join の後に system メッセージで拒否された場合は subscribe_error() で分かる
+        let (socket, mut server) = memory_socket();
+        let client = RealtimeClient::new_with_socket("ws://localhost", "anon", options(), socket);
+        let connection = connect(&mut server, &client).await;
+        let push = serve_join_reply(
+            connection,
+            json!({ "status": "ok", "response": { "postgres_changes": [
+                { "id": 11, "event": "INSERT", "schema": "public", "table": "todos", "filter": "user_id=eq.1" },
+                { "id": 12, "event": "DELETE", "schema": "public", "table": "notes" }
+            ] } }),
+        );
+        let subscriptions = todos_channel(&client).subscribe().await.unwrap();
+        assert!(subscriptions[0].subscribe_error().is_none());
+        push.send(json!({
+            "topic": "realtime:public:todos",
+            "event": "system",
+            "payload": {
+                "status": "error",
+                "extension": "postgres_changes",
+                "channel": "public:todos",
+                "message": "Unable to subscribe to changes with given parameters"
+            },
+            "ref": null
+        }))
+        .unwrap();
+        let error = timeout(WAIT, async {
+            loop {
+                if let Some(error) = subscriptions[0].subscribe_error() {
+                    return error;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            error,
+            RealtimeError::SubscribeRejected { ref topic, ref message }
+                if topic == "realtime:public:todos"
+                    && message == "Unable to subscribe to changes with given parameters"
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+//! 受信メッセージのサイズと JSON のネストの上限
+//!
+//! 上限を超えたメッセージは JSON としてパースせずに捨てる。トピックはパースしないと分からないため、
+//! 捨てたことはすべてのチャンネルに [`SubscriptionWarning::MessageDropped`](crate::SubscriptionWarning::MessageDropped)
+//! と (`on_stream` の購読には) [`GapReason::MessageDropped`](crate::GapReason::MessageDropped) で通知する。
+//! WebSocket フレーム自体の上限は tungstenite の設定 (既定 64 MiB) に従うため、
+//! この上限はパースで膨らむメモリ (`serde_json::Value` の木) を抑えるためのもの。
+
+use std::fmt;
+
+/// 受け付けるメッセージの最大バイト数の既定値
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// 受け付ける JSON の最大ネストの既定値
+pub const DEFAULT_MAX_JSON_DEPTH: usize = 64;
+
+/// メッセージを捨てた理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// `max_message_size` を超えた
+    TooLarge { limit: usize },
+    /// `max_json_depth` を超えてネストしていた
+    TooDeep { limit: usize },
+}
+
+/// 上限を超えたため捨てたメッセージ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DroppedMessage {
+    /// メッセージのバイト数
+    pub size: usize,
+    pub reason: DropReason,
+}
+
+impl fmt::Display for DroppedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            DropReason::TooLarge { limit } => write!(
+                f,
+                "dropped a {} byte message exceeding max_message_size ({} bytes)",
+                self.size, limit
+            ),
+            DropReason::TooDeep { limit } => write!(
+                f,
+                "dropped a {} byte message nested deeper than max_json_depth ({})",
+                self.size, limit
+            ),
+        }
+    }
+}
+
+/// パースする前にメッセージが上限内か確認する
+pub(crate) fn check(
+    text: &str,
+    max_message_size: usize,
+    max_json_depth: usize,
+) -> Result<(), DroppedMessage> {
+    let size = text.len();
+    if size > max_message_size {
+        return Err(DroppedMessage {
+            size,
+            reason: DropReason::TooLarge {
+                limit: max_message_size,
+            },
+        });
+    }
+    if exceeds_depth(text.as_bytes(), max_json_depth) {
+        return Err(DroppedMessage {
+            size,
+            reason: DropReason::TooDeep {
+                limit: max_json_depth,
+            },
+        });
+    }
+    Ok(())
+}
+
+// 文字列の外の `{` / `[` のネストが `max_depth` を超えるか (JSON として正しいかは見ない)
+fn exceeds_depth(bytes: &[u8], max_depth: usize) -> bool {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for &byte in bytes {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > max_depth {
+                    return true;
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exceeds_depth_ignores_brackets_in_strings() {
+        assert!(!exceeds_depth(br#"{"a":[{"b":1}]}"#, 3));
+        assert!(exceeds_depth(br#"{"a":[{"b":[1]}]}"#, 3));
+        assert!(!exceeds_depth(br#"{"a":"[[[[{{{{\"[[["}"#, 1));
+        assert!(!exceeds_depth(br#"[1],[2],[3]"#, 1));
+    }
+
+    #[test]
+    fn test_check_reports_reason() {
+        assert_eq!(check("[[1]]", 5, 2), Ok(()));
+        assert_eq!(
+            check("[[1]] ", 5, 2),
+            Err(DroppedMessage {
+                size: 6,
+                reason: DropReason::TooLarge { limit: 5 }
+            })
+        );
+        assert_eq!(
+            check("[[[1]]]", 100, 2),
+            Err(DroppedMessage {
+                size: 7,
+                reason: DropReason::TooDeep { limit: 2 }
+            })
+        );
+    }
+}
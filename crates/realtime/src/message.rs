@@ -1,3 +1,5 @@
+use crate::changes::PostgresChangesPayload;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -29,10 +31,18 @@ pub enum ChannelEvent {
     PhoenixError,
     #[serde(rename = "phx_close")]
     PhoenixClose,
+    #[serde(rename = "phx_leave")]
+    PhoenixLeave,
 
     Heartbeat,
     Presence,
+    /// 参加時に送られるプレゼンス状態全体
+    PresenceState,
+    /// プレゼンスの差分 (joins / leaves)
+    PresenceDiff,
     Broadcast,
+    /// サーバーからの通知 (postgres_changes の購読結果など)
+    System,
     // Add other known events as needed
 }
 
@@ -71,15 +81,76 @@ pub struct Payload {
     #[serde(rename = "type")] // Map 'type' field in JSON
     pub event_type: Option<String>,
     pub timestamp: Option<String>, // Timestamps often come as strings
+    /// 再接続後にキャッチアップフックから配信されたイベントかどうか
+    #[serde(default)]
+    pub synthetic: bool,
+}
+
+impl Payload {
+    /// キャッチアップフックから返す postgres_changes イベントを作成
+    ///
+    /// `data` はサーバーが配信する形式 (`schema` / `table` / `type` / `record` / `commit_timestamp`)。
+    pub fn catch_up(data: serde_json::Value) -> Self {
+        Self {
+            data,
+            event_type: Some(ChannelEvent::PostgresChanges.to_string()),
+            timestamp: None,
+            synthetic: true,
+        }
+    }
+
+    /// postgres_changes の `commit_timestamp` (`{"data": {...}}` 形式にも対応)
+    pub fn commit_timestamp(&self) -> Option<&str> {
+        self.change_data().get("commit_timestamp")?.as_str()
+    }
+
+    /// postgres_changes のイベント種別 (`INSERT` / `UPDATE` / `DELETE`)
+    pub fn change_type(&self) -> Option<&str> {
+        let data = self.change_data();
+        data.get("type").or_else(|| data.get("eventType"))?.as_str()
+    }
+
+    fn change_data(&self) -> &serde_json::Value {
+        match self.data.get("data") {
+            Some(inner) if inner.get("table").is_some() => inner,
+            _ => &self.data,
+        }
+    }
+
+    /// postgres_changes のデータを型付きで取得 (`old` は主キーのみの場合がある)
+    pub fn postgres_changes<T: DeserializeOwned>(
+        &self,
+    ) -> Result<PostgresChangesPayload<T>, serde_json::Error> {
+        PostgresChangesPayload::from_value(&self.data)
+    }
 }
 
 /// プレゼンス変更情報
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PresenceChange {
+    #[serde(default)]
     pub joins: HashMap<String, serde_json::Value>,
+    #[serde(default)]
     pub leaves: HashMap<String, serde_json::Value>,
 }
 
+/// プレゼンス状態の適用で発生した参加・離脱
+///
+/// `current_metas` は適用前 (join) / 適用後 (leave) のそのキーの metas。
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresenceEvent {
+    Join {
+        key: String,
+        current_metas: Vec<serde_json::Value>,
+        new_metas: Vec<serde_json::Value>,
+    },
+    Leave {
+        key: String,
+        current_metas: Vec<serde_json::Value>,
+        left_metas: Vec<serde_json::Value>,
+    },
+}
+
 /// プレゼンス状態全体
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PresenceState {
@@ -87,6 +158,42 @@ pub struct PresenceState {
     pub state: HashMap<String, serde_json::Value>,
 }
 
+fn metas(presence: &serde_json::Value) -> Vec<serde_json::Value> {
+    presence
+        .get("metas")
+        .and_then(|metas| metas.as_array())
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn phx_refs(presence: &serde_json::Value) -> Vec<serde_json::Value> {
+    metas(presence)
+        .iter()
+        .filter_map(|meta| meta.get("phx_ref").cloned())
+        .collect()
+}
+
+fn has_ref(refs: &[serde_json::Value], meta: &serde_json::Value) -> bool {
+    meta.get("phx_ref")
+        .is_some_and(|phx_ref| refs.contains(phx_ref))
+}
+
+// metas 以外のフィールドは残したまま metas を置き換える
+fn with_metas(presence: &serde_json::Value, metas: Vec<serde_json::Value>) -> serde_json::Value {
+    let mut presence = match presence {
+        serde_json::Value::Object(_) => presence.clone(),
+        _ => serde_json::json!({}),
+    };
+    presence["metas"] = serde_json::Value::Array(metas);
+    presence
+}
+
+fn sorted_keys(presences: &HashMap<String, serde_json::Value>) -> Vec<&String> {
+    let mut keys: Vec<_> = presences.keys().collect();
+    keys.sort();
+    keys
+}
+
 impl PresenceState {
     pub fn new() -> Self {
         Self {
@@ -94,16 +201,102 @@ impl PresenceState {
         }
     }
 
-    /// Apply presence diff to update the state
-    pub fn sync(&mut self, presence_diff: &PresenceChange) {
-        for (key, value) in &presence_diff.joins {
-            self.state.insert(key.clone(), value.clone());
+    /// `presence_state` で受け取った状態全体を適用する
+    ///
+    /// 現在の状態との差分を `phx_ref` 単位で求め、`sync_diff` と同じ規則で適用する。
+    pub fn sync_state(
+        &mut self,
+        new_state: &HashMap<String, serde_json::Value>,
+    ) -> Vec<PresenceEvent> {
+        let mut joins = HashMap::new();
+        let mut leaves = HashMap::new();
+
+        for (key, presence) in &self.state {
+            if !new_state.contains_key(key) {
+                leaves.insert(key.clone(), presence.clone());
+            }
+        }
+        for (key, new_presence) in new_state {
+            let Some(current) = self.state.get(key) else {
+                joins.insert(key.clone(), new_presence.clone());
+                continue;
+            };
+            let current_refs = phx_refs(current);
+            let new_refs = phx_refs(new_presence);
+            let joined: Vec<_> = metas(new_presence)
+                .into_iter()
+                .filter(|meta| !has_ref(&current_refs, meta))
+                .collect();
+            let left: Vec<_> = metas(current)
+                .into_iter()
+                .filter(|meta| !has_ref(&new_refs, meta))
+                .collect();
+            if !joined.is_empty() {
+                joins.insert(key.clone(), with_metas(new_presence, joined));
+            }
+            if !left.is_empty() {
+                leaves.insert(key.clone(), with_metas(current, left));
+            }
         }
-        for key in presence_diff.leaves.keys() {
-            self.state.remove(key);
+
+        self.sync_diff(&PresenceChange { joins, leaves })
+    }
+
+    /// `presence_diff` を適用する (joins を先に、leaves を後に処理する)
+    ///
+    /// 既にいるキーへの join は `phx_ref` で metas をマージし、
+    /// leave で metas が空になったキーは状態から削除する。
+    pub fn sync_diff(&mut self, presence_diff: &PresenceChange) -> Vec<PresenceEvent> {
+        let mut events = Vec::new();
+
+        for key in sorted_keys(&presence_diff.joins) {
+            let new_presence = &presence_diff.joins[key];
+            let new_metas = metas(new_presence);
+            let current_metas = self.state.get(key).map(metas).unwrap_or_default();
+            let joined_refs = phx_refs(new_presence);
+            let mut merged: Vec<_> = current_metas
+                .iter()
+                .filter(|meta| !has_ref(&joined_refs, meta))
+                .cloned()
+                .collect();
+            merged.extend(new_metas.iter().cloned());
+            self.state
+                .insert(key.clone(), with_metas(new_presence, merged));
+            events.push(PresenceEvent::Join {
+                key: key.clone(),
+                current_metas,
+                new_metas,
+            });
+        }
+
+        for key in sorted_keys(&presence_diff.leaves) {
+            let Some(current) = self.state.get_mut(key) else {
+                continue;
+            };
+            let left_presence = &presence_diff.leaves[key];
+            let left_refs = phx_refs(left_presence);
+            let remaining: Vec<_> = metas(current)
+                .into_iter()
+                .filter(|meta| !has_ref(&left_refs, meta))
+                .collect();
+            *current = with_metas(current, remaining.clone());
+            if remaining.is_empty() {
+                self.state.remove(key);
+            }
+            events.push(PresenceEvent::Leave {
+                key: key.clone(),
+                current_metas: remaining,
+                left_metas: metas(left_presence),
+            });
         }
+
+        events
     }
 
+    /// Apply presence diff to update the state
+    pub fn sync(&mut self, presence_diff: &PresenceChange) {
+        self.sync_diff(presence_diff);
+    }
     /// List current presence state as key-value pairs
     pub fn list(&self) -> Vec<(String, serde_json::Value)> {
         self.state
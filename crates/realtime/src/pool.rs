@@ -0,0 +1,195 @@
+//! 複数の RealtimeClient をまとめて管理するプール
+//!
+//! 複数の Supabase プロジェクトを1プロセスから購読する場合に、クライアントに名前を付けて登録する。
+//! 登録したクライアントには名前がラベルとして付き、ログと統計で区別できる。
+
+use crate::client::{ConnectionState, RealtimeClient};
+use crate::error::RealtimeError;
+use crate::stats::RealtimeStats;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::{sleep, Instant};
+use tracing::{info, instrument, warn};
+
+/// 実行中のタスクを確認する間隔
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// プール全体の統計 ([`RealtimeClientPool::stats`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    /// クライアント名ごとの統計
+    pub clients: BTreeMap<String, RealtimeStats>,
+    /// 全クライアントの合計
+    pub events_received: u64,
+    pub bytes_received: u64,
+    pub errors: u64,
+    /// 接続中 (切断状態でない) のクライアントの数
+    pub active_connections: usize,
+}
+
+/// 名前付きの RealtimeClient のプール
+#[derive(Default)]
+pub struct RealtimeClientPool {
+    clients: RwLock<BTreeMap<String, Arc<RealtimeClient>>>,
+    max_connections: Option<usize>,
+    // 接続数の確認と接続を直列化する
+    connecting: Mutex<()>,
+}
+
+impl RealtimeClientPool {
+    /// 接続数の上限なしでプールを作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 同時に接続できるクライアント数の上限を設定
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// クライアントを `name` で登録 (`name` がクライアントのラベルになる)
+    ///
+    /// 同じ名前のクライアントが既に登録されている場合はエラー
+    #[allow(clippy::result_large_err)] // RealtimeError は tungstenite のエラーを含むため大きい
+    pub fn add(
+        &self,
+        name: &str,
+        client: RealtimeClient,
+    ) -> Result<Arc<RealtimeClient>, RealtimeError> {
+        let mut clients = self.clients.write().unwrap();
+        if clients.contains_key(name) {
+            return Err(RealtimeError::PoolError(format!(
+                "Client '{}' is already registered",
+                name
+            )));
+        }
+        let client = Arc::new(client.with_label(name));
+        clients.insert(name.to_string(), client.clone());
+        info!(client = %name, "Registered client in pool");
+        Ok(client)
+    }
+
+    /// 登録済みのクライアントを取得
+    pub fn get(&self, name: &str) -> Option<Arc<RealtimeClient>> {
+        self.clients.read().unwrap().get(name).cloned()
+    }
+
+    /// 登録済みのクライアント名 (名前順)
+    pub fn names(&self) -> Vec<String> {
+        self.clients.read().unwrap().keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.clients.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn clients(&self) -> Vec<(String, Arc<RealtimeClient>)> {
+        self.clients
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, client)| (name.clone(), client.clone()))
+            .collect()
+    }
+
+    async fn active_connections(&self) -> usize {
+        let mut active = 0;
+        for (_, client) in self.clients() {
+            if client.get_connection_state().await != ConnectionState::Disconnected {
+                active += 1;
+            }
+        }
+        active
+    }
+
+    /// `name` のクライアントを接続 (接続数が上限に達している場合はエラー)
+    #[instrument(skip(self))]
+    pub async fn connect(&self, name: &str) -> Result<(), RealtimeError> {
+        let client = self
+            .get(name)
+            .ok_or_else(|| RealtimeError::PoolError(format!("Unknown client '{}'", name)))?;
+        let _guard = self.connecting.lock().await;
+        if client.get_connection_state().await != ConnectionState::Disconnected {
+            return Ok(());
+        }
+        if let Some(max_connections) = self.max_connections {
+            if self.active_connections().await >= max_connections {
+                warn!(max_connections, "Connection limit reached");
+                return Err(RealtimeError::PoolError(format!(
+                    "Cannot connect '{}': max_connections ({}) reached",
+                    name, max_connections
+                )));
+            }
+        }
+        client.connect().await
+    }
+
+    /// 登録済みのすべてのクライアントを名前順に接続 (最初のエラーで中断)
+    pub async fn connect_all(&self) -> Result<(), RealtimeError> {
+        for name in self.names() {
+            self.connect(&name).await?;
+        }
+        Ok(())
+    }
+
+    /// クライアントごとの統計と合計
+    pub async fn stats(&self) -> PoolStats {
+        let mut clients = BTreeMap::new();
+        let mut active_connections = 0;
+        for (name, client) in self.clients() {
+            if client.get_connection_state().await != ConnectionState::Disconnected {
+                active_connections += 1;
+            }
+            clients.insert(name, client.stats().await);
+        }
+        PoolStats {
+            events_received: clients.values().map(|s| s.events_received).sum(),
+            bytes_received: clients.values().map(|s| s.bytes_received).sum(),
+            errors: clients.values().map(|s| s.errors).sum(),
+            clients,
+            active_connections,
+        }
+    }
+
+    /// 全クライアントの実行中のバックグラウンドタスクの数
+    pub fn running_tasks(&self) -> usize {
+        self.clients()
+            .iter()
+            .map(|(_, client)| client.running_tasks())
+            .sum()
+    }
+
+    /// すべてのクライアントを切断し、バックグラウンドタスクの終了を `timeout` まで待つ
+    ///
+    /// 期限までにタスクが終了しなければエラー (クライアントは切断済みのまま)
+    #[instrument(skip(self))]
+    pub async fn shutdown_all(&self, timeout: Duration) -> Result<(), RealtimeError> {
+        let deadline = Instant::now() + timeout;
+        for (name, client) in self.clients() {
+            if let Err(e) = client.disconnect().await {
+                warn!(client = %name, error = %e, "Failed to disconnect client");
+            }
+        }
+        loop {
+            let running = self.running_tasks();
+            if running == 0 {
+                info!("All clients shut down");
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(RealtimeError::PoolError(format!(
+                    "{} background tasks still running after {:?}",
+                    running, timeout
+                )));
+            }
+            sleep(SHUTDOWN_POLL_INTERVAL).await;
+        }
+    }
+}
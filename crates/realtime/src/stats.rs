@@ -0,0 +1,246 @@
+//! チャンネルと接続の統計
+//!
+//! カウンターはアトミック変数で更新するため、イベントの受信経路でロックを取らない。
+//! `metrics` フィーチャーを有効にすると、同じ値を `metrics` クレートのファサードにも記録する。
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// 値が未設定であることを表す
+const UNSET: u64 = u64::MAX;
+
+fn duration_from(value: u64, to_duration: fn(u64) -> Duration) -> Option<Duration> {
+    (value != UNSET).then(|| to_duration(value))
+}
+
+/// チャンネルの統計 ([`crate::Subscription::stats`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelStats {
+    pub topic: String,
+    /// 受信したイベント (postgres_changes / broadcast / presence) の数
+    pub events_received: u64,
+    /// 受信したイベントのメッセージのバイト数
+    pub bytes_received: u64,
+    /// 最後にイベントを受信した時刻
+    pub last_event_at: Option<SystemTime>,
+    /// 最後の join からサーバーの応答までの時間
+    pub join_latency: Option<Duration>,
+    /// phx_error・join の失敗・ハンドラーのエラーの数
+    pub errors: u64,
+}
+
+/// クライアント全体の統計 ([`crate::RealtimeClient::stats`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RealtimeStats {
+    /// クライアントのラベル ([`crate::RealtimeClient::with_label`])
+    pub label: Option<String>,
+    pub channels: Vec<ChannelStats>,
+    /// 全チャンネルの合計
+    pub events_received: u64,
+    pub bytes_received: u64,
+    pub errors: u64,
+    /// 接続に成功した回数
+    pub connections: u64,
+    /// 2回目以降の接続の回数
+    pub reconnects: u64,
+    /// 最後のハートビートの往復時間
+    pub heartbeat_rtt: Option<Duration>,
+    /// 上限 (`max_message_size` / `max_json_depth`) を超えたため捨てたメッセージの数
+    pub messages_dropped: u64,
+}
+
+impl RealtimeStats {
+    pub(crate) fn new(
+        label: Option<String>,
+        channels: Vec<ChannelStats>,
+        connection: &ConnectionMetrics,
+    ) -> Self {
+        let connections = connection.connections.load(Ordering::Relaxed);
+        Self {
+            label,
+            events_received: channels.iter().map(|c| c.events_received).sum(),
+            bytes_received: channels.iter().map(|c| c.bytes_received).sum(),
+            errors: channels.iter().map(|c| c.errors).sum(),
+            channels,
+            connections,
+            reconnects: connections.saturating_sub(1),
+            heartbeat_rtt: duration_from(
+                connection.heartbeat_rtt_us.load(Ordering::Relaxed),
+                Duration::from_micros,
+            ),
+            messages_dropped: connection.messages_dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub(crate) struct ChannelMetrics {
+    topic: String,
+    created: Instant,
+    events_received: AtomicU64,
+    bytes_received: AtomicU64,
+    errors: AtomicU64,
+    // UNIX エポックからのマイクロ秒
+    last_event_at_us: AtomicU64,
+    // `created` からのナノ秒
+    join_started_ns: AtomicU64,
+    join_latency_us: AtomicU64,
+}
+
+impl ChannelMetrics {
+    pub(crate) fn new(topic: &str) -> Self {
+        Self {
+            topic: topic.to_string(),
+            created: Instant::now(),
+            events_received: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            last_event_at_us: AtomicU64::new(UNSET),
+            join_started_ns: AtomicU64::new(UNSET),
+            join_latency_us: AtomicU64::new(UNSET),
+        }
+    }
+
+    pub(crate) fn record_event(&self, bytes: usize) {
+        self.events_received.fetch_add(1, Ordering::Relaxed);
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.last_event_at_us
+            .store(now.as_micros() as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        {
+            metrics::counter!("realtime_channel_events_total", "topic" => self.topic.clone())
+                .increment(1);
+            metrics::counter!("realtime_channel_bytes_total", "topic" => self.topic.clone())
+                .increment(bytes as u64);
+        }
+    }
+
+    pub(crate) fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("realtime_channel_errors_total", "topic" => self.topic.clone())
+            .increment(1);
+    }
+
+    pub(crate) fn join_started(&self) {
+        self.join_started_ns
+            .store(self.created.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn joined(&self) {
+        let started = self.join_started_ns.swap(UNSET, Ordering::Relaxed);
+        if started == UNSET {
+            return;
+        }
+        let latency = self
+            .created
+            .elapsed()
+            .saturating_sub(Duration::from_nanos(started));
+        self.join_latency_us
+            .store(latency.as_micros() as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::histogram!("realtime_channel_join_latency_seconds", "topic" => self.topic.clone())
+            .record(latency.as_secs_f64());
+    }
+
+    pub(crate) fn snapshot(&self) -> ChannelStats {
+        ChannelStats {
+            topic: self.topic.clone(),
+            events_received: self.events_received.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            last_event_at: duration_from(
+                self.last_event_at_us.load(Ordering::Relaxed),
+                Duration::from_micros,
+            )
+            .map(|since_epoch| UNIX_EPOCH + since_epoch),
+            join_latency: duration_from(
+                self.join_latency_us.load(Ordering::Relaxed),
+                Duration::from_micros,
+            ),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub(crate) struct ConnectionMetrics {
+    created: Instant,
+    connections: AtomicU64,
+    // 応答待ちのハートビートの ref と送信時刻 (`created` からのナノ秒)
+    pending_heartbeat_ref: AtomicU64,
+    heartbeat_sent_ns: AtomicU64,
+    heartbeat_rtt_us: AtomicU64,
+    messages_dropped: AtomicU64,
+}
+
+impl ConnectionMetrics {
+    pub(crate) fn new() -> Self {
+        Self {
+            created: Instant::now(),
+            connections: AtomicU64::new(0),
+            pending_heartbeat_ref: AtomicU64::new(UNSET),
+            heartbeat_sent_ns: AtomicU64::new(UNSET),
+            heartbeat_rtt_us: AtomicU64::new(UNSET),
+            messages_dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn message_dropped(&self) {
+        self.messages_dropped.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("realtime_messages_dropped_total").increment(1);
+    }
+
+    pub(crate) fn connected(&self) {
+        let previous = self.connections.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        if previous > 0 {
+            metrics::counter!("realtime_reconnects_total").increment(1);
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = previous;
+    }
+
+    pub(crate) fn heartbeat_sent(&self, heartbeat_ref: u32) {
+        self.heartbeat_sent_ns
+            .store(self.created.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        self.pending_heartbeat_ref
+            .store(heartbeat_ref as u64, Ordering::Relaxed);
+    }
+
+    /// `phoenix` トピックへの応答を受け取った (ハートビートの応答なら往復時間を記録)
+    pub(crate) fn heartbeat_replied(&self, message_ref: &str) {
+        let Some(heartbeat_ref) = message_ref
+            .strip_prefix(HEARTBEAT_REF_PREFIX)
+            .and_then(|r| r.parse::<u32>().ok())
+        else {
+            return;
+        };
+        if self
+            .pending_heartbeat_ref
+            .compare_exchange(
+                heartbeat_ref as u64,
+                UNSET,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            return;
+        }
+        let sent = self.heartbeat_sent_ns.load(Ordering::Relaxed);
+        let rtt = self
+            .created
+            .elapsed()
+            .saturating_sub(Duration::from_nanos(sent));
+        self.heartbeat_rtt_us
+            .store(rtt.as_micros() as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::histogram!("realtime_heartbeat_rtt_seconds").record(rtt.as_secs_f64());
+    }
+}
+
+/// ハートビートの ref の接頭辞
+pub(crate) const HEARTBEAT_REF_PREFIX: &str = "hb-";
@@ -0,0 +1,415 @@
+//! 連番付きのイベントストリームと取りこぼしの検出
+//!
+//! [`ChannelBuilder::on_stream`](crate::ChannelBuilder::on_stream) で登録した購読は、
+//! 届いたイベントに購読ごとの連番 (1 始まり) を振ってバッファに溜め、容量を超えた分は
+//! [`BackpressurePolicy`] に従って捨てる。捨てた場合や再接続した場合は
+//! [`StreamItem::GapDetected`] を次の項目として返す。
+//!
+//! 切断中にサーバーが送ったイベントはクライアントに届かず連番も振られないため、
+//! 再接続で取りこぼしたかどうかはクライアントでは判断できない。そのため再接続のたびに、
+//! 実際には何も失っていなくても `GapReason::Reconnect` を通知する (このとき `expected == got`)。
+//!
+//! [`Subscription::into_stream`](crate::Subscription::into_stream) は同じバッファを使い、
+//! 項目を `Result<Payload, RealtimeError>` として返す [`SubscriptionStream`] を作る。
+
+use crate::channel::Subscription;
+use crate::error::RealtimeError;
+use crate::message::Payload;
+use futures_util::{Stream, StreamExt};
+use log::trace;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+
+/// `Subscription::into_stream` のバッファのデフォルトの容量
+pub const DEFAULT_STREAM_CAPACITY: usize = 1024;
+
+/// バッファが一杯のときにどのイベントを捨てるか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackpressurePolicy {
+    /// 最も古いイベントを捨てる
+    #[default]
+    DropOldest,
+    /// 新しく届いたイベントを捨てる
+    DropNewest,
+}
+
+/// 取りこぼしの理由
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapReason {
+    /// バッファが一杯になりイベントを捨てた
+    BufferOverflow,
+    /// 再接続した (取りこぼしがあったとは限らない)
+    Reconnect,
+    /// 上限を超えたメッセージを捨てた (このチャンネル宛てだったとは限らない)
+    MessageDropped,
+}
+
+/// [`EventStream`] が返す項目
+#[derive(Debug, Clone)]
+pub enum StreamItem {
+    /// 届いたイベントと購読内の連番
+    Event { sequence: u64, payload: Payload },
+    /// 連番 `expected` から `got - 1` までのイベントを受け取れなかった (可能性がある)
+    GapDetected {
+        expected: u64,
+        got: u64,
+        reason: GapReason,
+    },
+}
+
+enum Entry {
+    Event(u64, Payload),
+    // 再接続・メッセージの破棄・DropNewest でのイベントの破棄の時点で次に振る連番
+    Marker(u64, GapReason),
+}
+
+struct Buffer {
+    entries: VecDeque<Entry>,
+    // `entries` のうちイベントの数 (再接続の印は容量に数えない)
+    events: usize,
+    next_sequence: u64,
+    closed: bool,
+    // 空のバッファを待っている読み手 (バッファのロック中に登録するので起こし損ねない)
+    waker: Option<Waker>,
+}
+
+/// 購読側 (チャンネル) と [`EventStream`] で共有するバッファ
+pub(crate) struct StreamShared {
+    capacity: usize,
+    policy: BackpressurePolicy,
+    buffer: Mutex<Buffer>,
+}
+
+impl StreamShared {
+    pub(crate) fn new(capacity: usize, policy: BackpressurePolicy) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            policy,
+            buffer: Mutex::new(Buffer {
+                entries: VecDeque::new(),
+                events: 0,
+                next_sequence: 1,
+                closed: false,
+                waker: None,
+            }),
+        }
+    }
+
+    fn buffer(&self) -> MutexGuard<'_, Buffer> {
+        self.buffer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // 連番を振ってバッファに入れる (一杯なら方針に従って捨てる)
+    pub(crate) fn push(&self, payload: Payload) {
+        let mut buffer = self.buffer();
+        if buffer.closed {
+            return;
+        }
+        let sequence = buffer.next_sequence;
+        buffer.next_sequence += 1;
+        if buffer.events >= self.capacity {
+            match self.policy {
+                BackpressurePolicy::DropNewest => {
+                    trace!("Stream buffer full; dropping event {}", sequence);
+                    // 後続のイベントが届かなくても (購読を解除しても) 読み手が取りこぼしを
+                    // 知れるように印を残す (続けて捨てた分は1つの印にまとめる)
+                    match buffer.entries.back_mut() {
+                        Some(Entry::Marker(got, GapReason::BufferOverflow)) => *got = sequence + 1,
+                        _ => buffer
+                            .entries
+                            .push_back(Entry::Marker(sequence + 1, GapReason::BufferOverflow)),
+                    }
+                    Self::wake(buffer);
+                    return;
+                }
+                BackpressurePolicy::DropOldest => {
+                    if let Some(index) = buffer
+                        .entries
+                        .iter()
+                        .position(|entry| matches!(entry, Entry::Event(..)))
+                    {
+                        buffer.entries.remove(index);
+                        buffer.events -= 1;
+                    }
+                    trace!("Stream buffer full; dropped oldest event");
+                }
+            }
+        }
+        buffer.entries.push_back(Entry::Event(sequence, payload));
+        buffer.events += 1;
+        Self::wake(buffer);
+    }
+
+    pub(crate) fn mark_reconnect(&self) {
+        self.mark_gap(GapReason::Reconnect);
+    }
+
+    pub(crate) fn mark_message_dropped(&self) {
+        self.mark_gap(GapReason::MessageDropped);
+    }
+
+    fn mark_gap(&self, reason: GapReason) {
+        let mut buffer = self.buffer();
+        let next_sequence = buffer.next_sequence;
+        buffer
+            .entries
+            .push_back(Entry::Marker(next_sequence, reason));
+        Self::wake(buffer);
+    }
+
+    pub(crate) fn close(&self) {
+        let mut buffer = self.buffer();
+        buffer.closed = true;
+        Self::wake(buffer);
+    }
+
+    // 待っている読み手を起こす (ロックを外してから)
+    fn wake(mut buffer: MutexGuard<'_, Buffer>) {
+        let waker = buffer.waker.take();
+        drop(buffer);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    // 最後に振った連番
+    pub(crate) fn last_sequence(&self) -> Option<u64> {
+        Some(self.buffer().next_sequence - 1).filter(|sequence| *sequence > 0)
+    }
+}
+
+enum Ready {
+    Item(StreamItem),
+    Empty,
+    Closed,
+}
+
+/// `ChannelBuilder::on_stream` の購読が配信するイベントのストリーム
+///
+/// 項目は届いた順に返り、取りこぼしは `GapDetected` として1回だけ通知される。
+/// 購読を解除するとバッファに残った項目を返した後に終わる。
+pub struct EventStream {
+    shared: Arc<StreamShared>,
+    // 次に受け取るはずの連番
+    expected: u64,
+    last_sequence: Option<u64>,
+    pending: Option<StreamItem>,
+}
+
+impl EventStream {
+    pub(crate) fn new(shared: Arc<StreamShared>) -> Self {
+        Self {
+            shared,
+            expected: 1,
+            last_sequence: None,
+            pending: None,
+        }
+    }
+
+    /// 次の項目を待つ (購読が解除されていれば `None`)
+    pub async fn recv(&mut self) -> Option<StreamItem> {
+        std::future::poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// 次の項目をポーリングする (空なら `cx` の waker を登録して `Pending`)
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<StreamItem>> {
+        match self.next_ready(Some(cx.waker())) {
+            Ready::Item(item) => Poll::Ready(Some(item)),
+            Ready::Closed => Poll::Ready(None),
+            Ready::Empty => Poll::Pending,
+        }
+    }
+
+    /// 待たずに次の項目を取り出す
+    pub fn try_recv(&mut self) -> Option<StreamItem> {
+        match self.next_ready(None) {
+            Ready::Item(item) => Some(item),
+            Ready::Empty | Ready::Closed => None,
+        }
+    }
+
+    /// 最後に返したイベントの連番
+    pub fn last_sequence(&self) -> Option<u64> {
+        self.last_sequence
+    }
+
+    /// `futures` の `Stream` に変換 (`EventStream` 自体も `Stream` を実装している)
+    pub fn into_stream(self) -> impl Stream<Item = StreamItem> + Send {
+        self
+    }
+
+    fn next_ready(&mut self, waker: Option<&Waker>) -> Ready {
+        if let Some(item) = self.pending.take() {
+            return Ready::Item(self.yielded(item));
+        }
+        let mut buffer = self.shared.buffer();
+        match buffer.entries.pop_front() {
+            Some(Entry::Event(sequence, payload)) => {
+                buffer.events -= 1;
+                drop(buffer);
+                let event = StreamItem::Event { sequence, payload };
+                if sequence > self.expected {
+                    self.pending = Some(event);
+                    return Ready::Item(self.gap(sequence, GapReason::BufferOverflow));
+                }
+                Ready::Item(self.yielded(event))
+            }
+            Some(Entry::Marker(got, reason)) => {
+                drop(buffer);
+                // 印より前に捨てたイベントは BufferOverflow として先に通知する
+                if got > self.expected && reason != GapReason::BufferOverflow {
+                    self.pending = Some(StreamItem::GapDetected {
+                        expected: got,
+                        got,
+                        reason,
+                    });
+                    return Ready::Item(self.gap(got, GapReason::BufferOverflow));
+                }
+                Ready::Item(self.gap(got, reason))
+            }
+            None if buffer.closed => Ready::Closed,
+            None => {
+                if let Some(waker) = waker {
+                    match &mut buffer.waker {
+                        Some(registered) if registered.will_wake(waker) => {}
+                        registered => *registered = Some(waker.clone()),
+                    }
+                }
+                Ready::Empty
+            }
+        }
+    }
+
+    fn gap(&mut self, got: u64, reason: GapReason) -> StreamItem {
+        let item = StreamItem::GapDetected {
+            expected: self.expected,
+            got,
+            reason,
+        };
+        self.expected = got;
+        item
+    }
+
+    fn yielded(&mut self, item: StreamItem) -> StreamItem {
+        if let StreamItem::Event { sequence, .. } = &item {
+            self.expected = sequence + 1;
+            self.last_sequence = Some(*sequence);
+        }
+        item
+    }
+}
+
+impl Stream for EventStream {
+    type Item = StreamItem;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<StreamItem>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+impl std::fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventStream")
+            .field("expected", &self.expected)
+            .field("last_sequence", &self.last_sequence)
+            .finish()
+    }
+}
+
+/// `Subscription::into_stream` が返すストリーム
+///
+/// 取りこぼし (`StreamItem::GapDetected`) は `Err(RealtimeError::EventsMissed)` として返す。
+/// ストリームを破棄すると購読を解除し、チャンネルの最後の購読ならチャンネルから退出する。
+pub struct SubscriptionStream {
+    events: Option<EventStream>,
+    // 破棄されると購読を解除する
+    subscription: Subscription,
+    // ストリームにできない購読のエラー (最初の項目として返す)
+    error: Option<RealtimeError>,
+}
+
+impl SubscriptionStream {
+    pub(crate) fn new(subscription: Subscription, events: EventStream) -> Self {
+        Self {
+            events: Some(events),
+            subscription,
+            error: None,
+        }
+    }
+
+    pub(crate) fn failed(subscription: Subscription, error: RealtimeError) -> Self {
+        Self {
+            events: None,
+            subscription,
+            error: Some(error),
+        }
+    }
+
+    /// 元の購読
+    pub fn subscription(&self) -> &Subscription {
+        &self.subscription
+    }
+
+    /// 購読のチャンネルのトピック
+    pub fn topic(&self) -> &str {
+        self.subscription.topic()
+    }
+}
+
+impl Stream for SubscriptionStream {
+    type Item = Result<Payload, RealtimeError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(error) = this.error.take() {
+            return Poll::Ready(Some(Err(error)));
+        }
+        let Some(events) = &mut this.events else {
+            return Poll::Ready(None);
+        };
+        let item = match events.poll_recv(cx) {
+            Poll::Ready(Some(StreamItem::Event { payload, .. })) => Ok(payload),
+            Poll::Ready(Some(StreamItem::GapDetected {
+                expected,
+                got,
+                reason,
+            })) => Err(RealtimeError::EventsMissed {
+                topic: this.subscription.topic().to_string(),
+                expected,
+                got,
+                reason,
+            }),
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => return Poll::Pending,
+        };
+        Poll::Ready(Some(item))
+    }
+}
+
+impl std::fmt::Debug for SubscriptionStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubscriptionStream")
+            .field("topic", &self.topic())
+            .field("subscription_id", &self.subscription.id())
+            .field("events", &self.events)
+            .finish()
+    }
+}
+
+/// 複数の購読を1つのストリームにまとめる (項目は届いた順に、トピックと組にして返す)
+///
+/// 各購読は [`Subscription::into_stream`] でストリームにする。まとめたストリームを破棄すると
+/// すべての購読を解除する。
+pub fn merge_subscriptions(
+    subscriptions: Vec<Subscription>,
+) -> impl Stream<Item = (String, Result<Payload, RealtimeError>)> + Send + Unpin {
+    futures_util::stream::select_all(subscriptions.into_iter().map(|subscription| {
+        let stream = subscription.into_stream();
+        let topic = stream.topic().to_string();
+        stream.map(move |item| (topic.clone(), item))
+    }))
+}
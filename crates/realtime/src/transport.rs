@@ -0,0 +1,224 @@
+//! WebSocket トランスポートの抽象化
+//!
+//! `RealtimeClient` は [`Socket`] を通して接続を確立する。デフォルトは
+//! tokio-tungstenite を使う [`TungsteniteSocket`]。`test-util` フィーチャーを
+//! 有効にすると、ネットワークを使わない [`memory_socket`] が利用できる。
+
+use crate::error::RealtimeError;
+use async_trait::async_trait;
+use futures_util::stream::{SplitSink, SplitStream};
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+pub use tokio_tungstenite::tungstenite::Message;
+
+/// 送信側のソケット
+#[async_trait]
+pub trait SocketSink: Send {
+    /// メッセージを送信
+    async fn send(&mut self, message: Message) -> Result<(), RealtimeError>;
+}
+
+/// 受信側のソケット
+#[async_trait]
+pub trait SocketStream: Send {
+    /// 次のメッセージを受信 (接続が閉じられた場合は None)
+    async fn recv(&mut self) -> Option<Result<Message, RealtimeError>>;
+}
+
+/// 接続を確立するトランスポート
+#[async_trait]
+pub trait Socket: Send + Sync {
+    /// `url` に接続し、送信側と受信側を返す
+    async fn connect(
+        &self,
+        url: &str,
+    ) -> Result<(Box<dyn SocketSink>, Box<dyn SocketStream>), RealtimeError>;
+}
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// tokio-tungstenite によるトランスポート (デフォルト)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TungsteniteSocket;
+
+struct TungsteniteSink(SplitSink<WsStream, Message>);
+
+struct TungsteniteStream(SplitStream<WsStream>);
+
+#[async_trait]
+impl SocketSink for TungsteniteSink {
+    async fn send(&mut self, message: Message) -> Result<(), RealtimeError> {
+        self.0.send(message).await.map_err(RealtimeError::from)
+    }
+}
+
+#[async_trait]
+impl SocketStream for TungsteniteStream {
+    async fn recv(&mut self) -> Option<Result<Message, RealtimeError>> {
+        match self.0.next().await? {
+            Ok(message) => Some(Ok(message)),
+            Err(e) => Some(Err(RealtimeError::from(e))),
+        }
+    }
+}
+
+#[async_trait]
+impl Socket for TungsteniteSocket {
+    async fn connect(
+        &self,
+        url: &str,
+    ) -> Result<(Box<dyn SocketSink>, Box<dyn SocketStream>), RealtimeError> {
+        let (stream, response) = connect_async(url).await.map_err(|e| {
+            RealtimeError::ConnectionError(format!("WebSocket connection failed: {}", e))
+        })?;
+        tracing::info!(response = ?response, "WebSocket connection successful");
+        let (write, read) = stream.split();
+        Ok((
+            Box::new(TungsteniteSink(write)),
+            Box::new(TungsteniteStream(read)),
+        ))
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+pub use memory::{memory_socket, MemoryConnection, MemoryServer, MemorySocket};
+
+#[cfg(any(test, feature = "test-util"))]
+mod memory {
+    use super::{Message, Socket, SocketSink, SocketStream};
+    use crate::error::RealtimeError;
+    use crate::message::RealtimeMessage;
+    use async_trait::async_trait;
+    use tokio::sync::mpsc;
+    use tokio::sync::mpsc::error::SendError;
+
+    /// インメモリのトランスポートを作成 (クライアント側とサーバー側のペア)
+    pub fn memory_socket() -> (MemorySocket, MemoryServer) {
+        let (connections_tx, connections_rx) = mpsc::unbounded_channel();
+        (
+            MemorySocket {
+                connections: connections_tx,
+            },
+            MemoryServer {
+                connections: connections_rx,
+            },
+        )
+    }
+
+    /// インメモリのトランスポート (`RealtimeClient::new_with_socket` に渡す)
+    #[derive(Debug, Clone)]
+    pub struct MemorySocket {
+        connections: mpsc::UnboundedSender<MemoryConnection>,
+    }
+
+    /// テスト用のサーバー側ハンドル
+    #[derive(Debug)]
+    pub struct MemoryServer {
+        connections: mpsc::UnboundedReceiver<MemoryConnection>,
+    }
+
+    impl MemoryServer {
+        /// クライアントからの次の接続を待つ
+        pub async fn accept(&mut self) -> Option<MemoryConnection> {
+            self.connections.recv().await
+        }
+    }
+
+    /// サーバー側から見た1つの接続
+    #[derive(Debug)]
+    pub struct MemoryConnection {
+        url: String,
+        to_client: mpsc::UnboundedSender<Message>,
+        from_client: mpsc::UnboundedReceiver<Message>,
+    }
+
+    impl MemoryConnection {
+        /// クライアントが接続に使った URL
+        pub fn url(&self) -> &str {
+            &self.url
+        }
+
+        /// クライアントが送信した次のメッセージを受信
+        pub async fn recv(&mut self) -> Option<Message> {
+            self.from_client.recv().await
+        }
+
+        /// クライアントが送信した次の Realtime メッセージを受信 (テキスト以外は読み飛ばす)
+        pub async fn recv_message(&mut self) -> Option<RealtimeMessage> {
+            while let Some(message) = self.recv().await {
+                if let Message::Text(text) = message {
+                    if let Ok(parsed) = serde_json::from_str(&text) {
+                        return Some(parsed);
+                    }
+                }
+            }
+            None
+        }
+
+        /// クライアントへメッセージを送信 (クライアント側が閉じている場合はエラー)
+        pub fn send(&self, message: Message) -> Result<(), SendError<Message>> {
+            self.to_client.send(message)
+        }
+
+        /// クライアントへ JSON メッセージを送信
+        pub fn send_json(&self, value: &serde_json::Value) -> Result<(), SendError<Message>> {
+            self.send(Message::Text(value.to_string()))
+        }
+
+        /// `message` への `phx_reply` (status: ok) を送信
+        pub fn reply_ok(&self, message: &RealtimeMessage) -> Result<(), SendError<Message>> {
+            self.send_json(&serde_json::json!({
+                "topic": message.topic,
+                "event": "phx_reply",
+                "payload": { "status": "ok", "response": {} },
+                "ref": message.message_ref,
+            }))
+        }
+    }
+
+    struct MemorySink(mpsc::UnboundedSender<Message>);
+
+    struct MemoryStream(mpsc::UnboundedReceiver<Message>);
+
+    #[async_trait]
+    impl SocketSink for MemorySink {
+        async fn send(&mut self, message: Message) -> Result<(), RealtimeError> {
+            self.0.send(message).map_err(|_| {
+                RealtimeError::ConnectionError("Server side of the connection is closed".into())
+            })
+        }
+    }
+
+    #[async_trait]
+    impl SocketStream for MemoryStream {
+        async fn recv(&mut self) -> Option<Result<Message, RealtimeError>> {
+            self.0.recv().await.map(Ok)
+        }
+    }
+
+    #[async_trait]
+    impl Socket for MemorySocket {
+        async fn connect(
+            &self,
+            url: &str,
+        ) -> Result<(Box<dyn SocketSink>, Box<dyn SocketStream>), RealtimeError> {
+            let (to_client, client_rx) = mpsc::unbounded_channel();
+            let (client_tx, from_client) = mpsc::unbounded_channel();
+            self.connections
+                .send(MemoryConnection {
+                    url: url.to_string(),
+                    to_client,
+                    from_client,
+                })
+                .map_err(|_| {
+                    RealtimeError::ConnectionError("Memory server has been dropped".into())
+                })?;
+            Ok((
+                Box::new(MemorySink(client_tx)),
+                Box::new(MemoryStream(client_rx)),
+            ))
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use supabase_rust_realtime::{
+    ChannelEvent, DatabaseChanges, HandlerError, RealtimeClient, RealtimeMessage,
+};
+use tokio::sync::{broadcast, Mutex};
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+
+// Mock server that replies to the join and then sends `event_count` postgres_changes events
+async fn start_server(topic: &str, event_count: i64) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let topic = topic.to_string();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws_stream = tokio_tungstenite::accept_async(stream).await.unwrap();
+        while let Some(Ok(msg)) = ws_stream.next().await {
+            let Ok(text) = msg.to_text() else { continue };
+            let Ok(parsed) = serde_json::from_str::<RealtimeMessage>(text) else {
+                continue;
+            };
+            let reply = json!({
+                "event": ChannelEvent::PhoenixReply,
+                "payload": { "status": "ok", "response": {} },
+                "ref": parsed.message_ref,
+                "topic": parsed.topic
+            });
+            if ws_stream
+                .send(Message::Text(reply.to_string()))
+                .await
+                .is_err()
+            {
+                break;
+            }
+
+            if parsed.event == ChannelEvent::PhoenixJoin && parsed.topic == topic {
+                // Give the test time to grab `handler_errors()` after subscribe returns
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                for id in 1..=event_count {
+                    let event = json!({
+                        "topic": topic,
+                        "event": ChannelEvent::PostgresChanges,
+                        "payload": {
+                            "type": "INSERT",
+                            "schema": "public",
+                            "table": "messages",
+                            "record": { "id": id }
+                        },
+                        "ref": null
+                    });
+                    if ws_stream
+                        .send(Message::Text(event.to_string()))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+
+    format!("ws://{}", addr)
+}
+
+async fn wait_for_ids(seen: &Arc<Mutex<Vec<i64>>>, expected: &[i64]) {
+    timeout(Duration::from_secs(3), async {
+        while seen.lock().await.as_slice() != expected {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("expected events {:?}, got {:?}", expected, seen));
+}
+
+async fn next_error(errors: &mut broadcast::Receiver<HandlerError>) -> HandlerError {
+    timeout(Duration::from_secs(3), errors.recv())
+        .await
+        .expect("timed out waiting for handler error")
+        .expect("handler error stream closed")
+}
+
+#[tokio::test]
+async fn test_async_handler_error_is_reported_and_later_events_deliver() {
+    let topic = "public:messages";
+    let url = start_server(topic, 3).await;
+    let client = RealtimeClient::new(&url, "mock_api_key");
+    client.connect().await.expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let handler_seen = seen.clone();
+    let subscriptions = client
+        .channel(topic)
+        .on_async(DatabaseChanges::new("messages"), move |payload| {
+            let seen = handler_seen.clone();
+            async move {
+                let id = payload.data["record"]["id"].as_i64().unwrap_or_default();
+                // Slow handler: events must still be handled in order
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                if id == 2 {
+                    return Err(format!("failed to store message {}", id));
+                }
+                seen.lock().await.push(id);
+                Ok(())
+            }
+        })
+        .subscribe()
+        .await
+        .expect("subscribe failed");
+    let mut errors = subscriptions[0].handler_errors();
+
+    let handler_error = next_error(&mut errors).await;
+    assert_eq!(handler_error.subscription_id, subscriptions[0].id());
+    assert_eq!(handler_error.message, "failed to store message 2");
+    assert!(!handler_error.panicked);
+
+    wait_for_ids(&seen, &[1, 3]).await;
+    client.disconnect().await.ok();
+}
+
+#[tokio::test]
+async fn test_handler_panic_does_not_stop_delivery() {
+    let topic = "public:panics";
+    let url = start_server(topic, 3).await;
+    let client = RealtimeClient::new(&url, "mock_api_key");
+    client.connect().await.expect("connect failed");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let handler_seen = seen.clone();
+    let subscriptions = client
+        .channel(topic)
+        .on_async(DatabaseChanges::new("messages"), move |payload| {
+            let seen = handler_seen.clone();
+            async move {
+                let id = payload.data["record"]["id"].as_i64().unwrap_or_default();
+                if id == 2 {
+                    panic!("boom");
+                }
+                seen.lock().await.push(id);
+                Ok::<(), String>(())
+            }
+        })
+        .subscribe()
+        .await
+        .expect("subscribe failed");
+    let mut errors = subscriptions[0].handler_errors();
+
+    let handler_error = next_error(&mut errors).await;
+    assert!(handler_error.panicked);
+    assert!(handler_error.message.contains("boom"));
+
+    wait_for_ids(&seen, &[1, 3]).await;
+    client.disconnect().await.ok();
+}
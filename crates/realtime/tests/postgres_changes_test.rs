@@ -0,0 +1,96 @@
+use serde::Deserialize;
+use serde_json::json;
+use supabase_rust_realtime::{Payload, PostgresChangesPayload};
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+struct Todo {
+    id: i64,
+    title: String,
+    done: bool,
+}
+
+fn columns() -> serde_json::Value {
+    json!([
+        { "name": "id", "type": "int8" },
+        { "name": "title", "type": "text" },
+        { "name": "done", "type": "bool" }
+    ])
+}
+
+#[test]
+fn test_update_with_key_only_old_record() {
+    let payload = Payload {
+        data: json!({
+            "data": {
+                "schema": "public",
+                "table": "todos",
+                "commit_timestamp": "2024-01-01T00:00:00Z",
+                "type": "UPDATE",
+                "record": { "id": 1, "title": "write tests", "done": true },
+                "old_record": { "id": 1 },
+                "columns": columns(),
+                "errors": null
+            },
+            "ids": [1]
+        }),
+        event_type: Some("postgres_changes".to_string()),
+        timestamp: None,
+        synthetic: false,
+    };
+
+    let change = payload.postgres_changes::<Todo>().unwrap();
+    assert_eq!(change.event_type, "UPDATE");
+    assert_eq!(
+        change.new,
+        Some(Todo {
+            id: 1,
+            title: "write tests".to_string(),
+            done: true
+        })
+    );
+    assert!(change.old.is_partial());
+    assert!(change.old.record().is_none());
+    assert_eq!(change.old.get("id"), Some(&json!(1)));
+    assert_eq!(change.old.missing_columns(), ["title", "done"]);
+    assert!(!change.has_errors());
+}
+
+#[test]
+fn test_delete_with_full_replica_identity() {
+    let value = json!({
+        "schema": "public",
+        "table": "todos",
+        "eventType": "DELETE",
+        "new": {},
+        "old": { "id": 2, "title": "old", "done": false },
+        "columns": columns()
+    });
+
+    let change = PostgresChangesPayload::<Todo>::from_value(&value).unwrap();
+    assert_eq!(change.event_type, "DELETE");
+    assert!(change.new.is_none());
+    assert!(!change.old.is_partial());
+    assert_eq!(change.old.record().map(|todo| todo.id), Some(2));
+}
+
+#[test]
+fn test_payload_with_errors() {
+    let value = json!({
+        "data": {
+            "schema": "public",
+            "table": "todos",
+            "type": "INSERT",
+            "record": {},
+            "old_record": {},
+            "columns": columns(),
+            "errors": ["Error 401: Unauthorized"]
+        },
+        "ids": [3]
+    });
+
+    let change = PostgresChangesPayload::<Todo>::from_value(&value).unwrap();
+    assert!(change.has_errors());
+    assert_eq!(change.errors, vec!["Error 401: Unauthorized".to_string()]);
+    assert!(change.new.is_none());
+    assert!(change.old.is_empty());
+}
@@ -0,0 +1,762 @@
+//! ディレクトリ単位のアップロード・ダウンロード
+//!
+//! リモートのキーは OS に関係なく `/` 区切りにする。空のディレクトリは転送しない。
+//! 個々のファイルの失敗は既定では [`DirTransferReport::failed`] に集約し、残りの転送を続ける。
+
+use crate::{FileOptions, ListOptions, Result, StorageBucketClient, StorageError};
+use futures_util::stream::{self, StreamExt};
+use glob::Pattern;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+// 一覧取得の1ページあたりの件数
+pub(crate) const LIST_PAGE_SIZE: i32 = 1000;
+
+// 同期モードで一度に削除するファイル数
+const REMOVE_BATCH_SIZE: usize = 1000;
+
+// 空フォルダーを表すために Storage が作成するファイル
+pub(crate) const EMPTY_FOLDER_PLACEHOLDER: &str = ".emptyFolderPlaceholder";
+
+/// 進捗コールバック
+pub type DirTransferProgressCallback = Arc<dyn Fn(DirTransferProgress) + Send + Sync>;
+
+/// ディレクトリ転送の進捗
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DirTransferProgress {
+    /// 完了したファイル数 (失敗を含む)
+    pub files_done: usize,
+    pub files_total: usize,
+    /// 転送済みのバイト数
+    pub bytes_done: u64,
+}
+
+/// ディレクトリ転送のオプション
+///
+/// `include` / `exclude` の glob パターンはディレクトリからの相対パス (`/` 区切り) に対して
+/// 照合する。`include` が空の場合はすべてのファイルが対象になる。
+#[derive(Clone)]
+pub struct DirTransferOptions {
+    /// 同時に転送するファイル数の上限
+    pub concurrency: usize,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// 転送元にないファイルを転送先から削除する (同期モード)
+    pub delete_missing: bool,
+    /// 最初の失敗で中断してそのエラーを返す
+    pub fail_fast: bool,
+    /// アップロード時のオプション (再デプロイでは `UploadMode::Overwrite` にする)
+    pub file_options: Option<FileOptions>,
+    pub on_progress: Option<DirTransferProgressCallback>,
+}
+
+impl Default for DirTransferOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            delete_missing: false,
+            fail_fast: false,
+            file_options: None,
+            on_progress: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for DirTransferOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DirTransferOptions")
+            .field("concurrency", &self.concurrency)
+            .field("include", &self.include)
+            .field("exclude", &self.exclude)
+            .field("delete_missing", &self.delete_missing)
+            .field("fail_fast", &self.fail_fast)
+            .field("file_options", &self.file_options)
+            .field("on_progress", &self.on_progress.is_some())
+            .finish()
+    }
+}
+
+impl DirTransferOptions {
+    /// デフォルトのオプションを作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 同時転送数を設定 (0 は 1 として扱う)
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// 対象にするファイルの glob パターンを追加
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.include.push(pattern.to_string());
+        self
+    }
+
+    /// 除外するファイルの glob パターンを追加
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.exclude.push(pattern.to_string());
+        self
+    }
+
+    /// 同期モードを設定
+    pub fn with_delete_missing(mut self, delete_missing: bool) -> Self {
+        self.delete_missing = delete_missing;
+        self
+    }
+
+    /// 最初の失敗で中断するかを設定
+    pub fn with_fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// アップロード時のオプションを設定
+    pub fn with_file_options(mut self, file_options: FileOptions) -> Self {
+        self.file_options = Some(file_options);
+        self
+    }
+
+    /// ファイルが1つ完了するたびに呼ばれるコールバックを設定
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(DirTransferProgress) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Arc::new(callback));
+        self
+    }
+}
+
+/// 転送に失敗したファイル
+#[derive(Debug)]
+pub struct FailedTransfer {
+    /// リモートのキー
+    pub path: String,
+    pub error: StorageError,
+}
+
+/// ディレクトリ転送の結果
+#[derive(Debug, Default)]
+pub struct DirTransferReport {
+    /// 転送したファイルのリモートのキー
+    pub transferred: Vec<String>,
+    /// 同期モードで削除したファイル (アップロードではリモートのキー、ダウンロードではローカルの相対パス)
+    pub deleted: Vec<String>,
+    pub failed: Vec<FailedTransfer>,
+    /// 転送したバイト数
+    pub bytes: u64,
+}
+
+impl DirTransferReport {
+    /// 失敗したファイルがなければ true
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    // 1ファイルの結果を記録 (fail_fast の場合は失敗をそのまま返す)
+    fn record(&mut self, key: String, result: Result<u64>, fail_fast: bool) -> Result<()> {
+        match result {
+            Ok(bytes) => {
+                self.bytes += bytes;
+                self.transferred.push(key);
+            }
+            Err(error) if fail_fast => return Err(error),
+            Err(error) => self.failed.push(FailedTransfer { path: key, error }),
+        }
+        Ok(())
+    }
+
+    fn sort(&mut self) {
+        self.transferred.sort();
+        self.deleted.sort();
+        self.failed.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+}
+
+struct PathFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl PathFilter {
+    fn new(options: &DirTransferOptions) -> Result<Self> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    Pattern::new(pattern).map_err(|e| {
+                        StorageError::new(format!("Invalid glob pattern '{}': {}", pattern, e))
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            include: compile(&options.include)?,
+            exclude: compile(&options.exclude)?,
+        })
+    }
+
+    fn matches(&self, relative_path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(relative_path)))
+            && !self.exclude.iter().any(|p| p.matches(relative_path))
+    }
+}
+
+struct LocalFile {
+    path: PathBuf,
+    // ディレクトリからの相対パス (`/` 区切り)
+    relative_path: String,
+    size: u64,
+}
+
+// 一覧のエントリ (フォルダーは id が null)
+//
+// タイムスタンプは `chrono` フィーチャーに関係なく文字列のまま読む。
+#[derive(Deserialize)]
+pub(crate) struct ListEntry {
+    pub(crate) name: String,
+    #[serde(default)]
+    pub(crate) id: Option<String>,
+    #[serde(default)]
+    pub(crate) created_at: Option<String>,
+    #[serde(default)]
+    pub(crate) updated_at: Option<String>,
+    #[serde(default)]
+    pub(crate) metadata: Option<serde_json::Value>,
+    #[serde(default)]
+    pub(crate) size: Option<u64>,
+}
+
+// `prefix` と相対パスを `/` でつなぐ
+pub(crate) fn join_key(prefix: &str, relative_path: &str) -> String {
+    let prefix = prefix.trim_matches('/');
+    match (prefix.is_empty(), relative_path.is_empty()) {
+        (true, _) => relative_path.to_string(),
+        (false, true) => prefix.to_string(),
+        (false, false) => format!("{}/{}", prefix, relative_path),
+    }
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// リモートの相対パスからローカルのパスを作る (ディレクトリの外を指すパスは拒否する)
+fn local_path(root: &Path, relative_path: &str) -> Result<PathBuf> {
+    let relative = Path::new(relative_path);
+    let is_safe = relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    if !is_safe || relative_path.is_empty() {
+        return Err(StorageError::new(format!(
+            "Refusing to write outside the target directory: {}",
+            relative_path
+        )));
+    }
+    Ok(root.join(relative))
+}
+
+// ディレクトリ以下のファイルを相対パス順に列挙 (ディレクトリへのシンボリックリンクはたどらない)
+async fn walk_local(root: &Path) -> Result<Vec<LocalFile>> {
+    let mut files = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        let mut entries = tokio::fs::read_dir(&directory).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                directories.push(path);
+                continue;
+            }
+            let metadata = tokio::fs::metadata(&path).await?;
+            if metadata.is_file() {
+                files.push(LocalFile {
+                    relative_path: relative_path(root, &path),
+                    path,
+                    size: metadata.len(),
+                });
+            }
+        }
+    }
+    files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(files)
+}
+
+struct ProgressReporter {
+    callback: Option<DirTransferProgressCallback>,
+    files_done: usize,
+    files_total: usize,
+}
+
+impl ProgressReporter {
+    fn new(options: &DirTransferOptions, files_total: usize) -> Self {
+        Self {
+            callback: options.on_progress.clone(),
+            files_done: 0,
+            files_total,
+        }
+    }
+
+    fn file_done(&mut self, bytes_done: u64) {
+        self.files_done += 1;
+        if let Some(callback) = &self.callback {
+            callback(DirTransferProgress {
+                files_done: self.files_done,
+                files_total: self.files_total,
+                bytes_done,
+            });
+        }
+    }
+}
+
+impl<'a> StorageBucketClient<'a> {
+    /// ローカルのディレクトリを `remote_prefix` 以下に再帰的にアップロード
+    pub async fn upload_directory(
+        &self,
+        local_dir: &Path,
+        remote_prefix: &str,
+        options: DirTransferOptions,
+    ) -> Result<DirTransferReport> {
+        let filter = PathFilter::new(&options)?;
+        let files: Vec<LocalFile> = walk_local(local_dir)
+            .await?
+            .into_iter()
+            .filter(|file| filter.matches(&file.relative_path))
+            .collect();
+
+        let mut report = DirTransferReport::default();
+        let mut progress = ProgressReporter::new(&options, files.len());
+        let mut uploads = stream::iter(&files)
+            .map(|file| {
+                let key = join_key(remote_prefix, &file.relative_path);
+                let file_options = options.file_options.clone();
+                async move {
+                    let result = self
+                        .upload(&key, &file.path, file_options)
+                        .await
+                        .map(|_| file.size);
+                    (key, result)
+                }
+            })
+            .buffer_unordered(options.concurrency.max(1));
+        while let Some((key, result)) = uploads.next().await {
+            report.record(key, result, options.fail_fast)?;
+            progress.file_done(report.bytes);
+        }
+        drop(uploads);
+
+        if options.delete_missing {
+            let local: HashSet<&str> = files
+                .iter()
+                .map(|file| file.relative_path.as_str())
+                .collect();
+            let stale: Vec<String> = self
+                .list_recursive(remote_prefix)
+                .await?
+                .into_iter()
+                .filter(|path| filter.matches(path) && !local.contains(path.as_str()))
+                .map(|path| join_key(remote_prefix, &path))
+                .collect();
+            for batch in stale.chunks(REMOVE_BATCH_SIZE) {
+                match self
+                    .remove(batch.iter().map(String::as_str).collect())
+                    .await
+                {
+                    Ok(()) => report.deleted.extend_from_slice(batch),
+                    Err(error) if options.fail_fast => return Err(error),
+                    Err(error) => {
+                        for key in batch {
+                            report.failed.push(FailedTransfer {
+                                path: key.clone(),
+                                error: StorageError::new(format!("Failed to delete: {}", error)),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        report.sort();
+        Ok(report)
+    }
+
+    /// `remote_prefix` 以下のファイルをローカルのディレクトリに再帰的にダウンロード
+    pub async fn download_directory(
+        &self,
+        remote_prefix: &str,
+        local_dir: &Path,
+        options: DirTransferOptions,
+    ) -> Result<DirTransferReport> {
+        let filter = PathFilter::new(&options)?;
+        let paths: Vec<String> = self
+            .list_recursive(remote_prefix)
+            .await?
+            .into_iter()
+            .filter(|path| filter.matches(path))
+            .collect();
+        tokio::fs::create_dir_all(local_dir).await?;
+
+        let mut report = DirTransferReport::default();
+        let mut progress = ProgressReporter::new(&options, paths.len());
+        let mut downloads = stream::iter(&paths)
+            .map(|path| {
+                let key = join_key(remote_prefix, path);
+                async move {
+                    let result = async {
+                        let destination = local_path(local_dir, path)?;
+                        if let Some(parent) = destination.parent() {
+                            tokio::fs::create_dir_all(parent).await?;
+                        }
+                        let download = self.download_to_file(&key, &destination, None).await?;
+                        Ok(download.size)
+                    }
+                    .await;
+                    (key, result)
+                }
+            })
+            .buffer_unordered(options.concurrency.max(1));
+        while let Some((key, result)) = downloads.next().await {
+            report.record(key, result, options.fail_fast)?;
+            progress.file_done(report.bytes);
+        }
+        drop(downloads);
+
+        if options.delete_missing {
+            let remote: HashSet<&str> = paths.iter().map(String::as_str).collect();
+            for file in walk_local(local_dir).await? {
+                if !filter.matches(&file.relative_path)
+                    || remote.contains(file.relative_path.as_str())
+                {
+                    continue;
+                }
+                match tokio::fs::remove_file(&file.path).await {
+                    Ok(()) => report.deleted.push(file.relative_path),
+                    Err(error) if options.fail_fast => return Err(error.into()),
+                    Err(error) => report.failed.push(FailedTransfer {
+                        path: file.relative_path,
+                        error: error.into(),
+                    }),
+                }
+            }
+        }
+
+        report.sort();
+        Ok(report)
+    }
+
+    // `prefix` 以下のファイルを再帰的に一覧し、`prefix` からの相対パスを返す
+    async fn list_recursive(&self, prefix: &str) -> Result<Vec<String>> {
+        Ok(self
+            .list_entries_recursive(prefix)
+            .await?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect())
+    }
+
+    // `prefix` 以下のファイルのエントリを、`prefix` からの相対パス順に返す
+    pub(crate) async fn list_entries_recursive(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(String, ListEntry)>> {
+        let mut paths = Vec::new();
+        let mut folders = vec![String::new()];
+        while let Some(folder) = folders.pop() {
+            let folder_prefix = join_key(prefix, &folder);
+            let mut offset = 0;
+            loop {
+                let options = ListOptions::new().limit(LIST_PAGE_SIZE).offset(offset);
+                let entries: Vec<ListEntry> = self.list_as(&folder_prefix, Some(options)).await?;
+                let count = entries.len();
+                for entry in entries {
+                    let path = join_key(&folder, &entry.name);
+                    match entry.id {
+                        Some(_) if entry.name == EMPTY_FOLDER_PLACEHOLDER => {}
+                        Some(_) => paths.push((path, entry)),
+                        None => folders.push(path),
+                    }
+                }
+                if count < LIST_PAGE_SIZE as usize {
+                    break;
+                }
+                offset += LIST_PAGE_SIZE;
+            }
+        }
+        paths.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StorageClient;
+    use reqwest::Client;
+    use serde_json::json;
+    use std::sync::Mutex;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    fn file_object(name: &str) -> serde_json::Value {
+        json!({
+            "name": name,
+            "bucket_id": "site",
+            "owner": "owner-uuid",
+            "id": "file-id",
+            "updated_at": "2024-01-05T00:00:00Z",
+            "created_at": "2024-01-05T00:00:00Z",
+            "last_accessed_at": "2024-01-05T00:00:00Z",
+            "metadata": {},
+            "size": 0,
+            "mime_type": null,
+        })
+    }
+
+    fn folder(name: &str) -> serde_json::Value {
+        json!({ "name": name, "id": null, "metadata": null })
+    }
+
+    async fn write_tree(root: &Path, files: &[(&str, &str)]) {
+        for (relative_path, contents) in files {
+            let path = root.join(relative_path);
+            tokio::fs::create_dir_all(path.parent().unwrap())
+                .await
+                .unwrap();
+            tokio::fs::write(path, contents).await.unwrap();
+        }
+    }
+
+    #[test]
+    fn test_join_key_and_local_path() {
+        assert_eq!(join_key("/assets/", "css/app.css"), "assets/css/app.css");
+        assert_eq!(join_key("", "index.html"), "index.html");
+        assert_eq!(join_key("assets", ""), "assets");
+        let root = Path::new("out");
+        assert_eq!(
+            local_path(root, "css/app.css").unwrap(),
+            root.join("css").join("app.css")
+        );
+        assert!(local_path(root, "../etc/passwd").is_err());
+        assert!(local_path(root, "/etc/passwd").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upload_directory_keys_and_exclusion() {
+        let mock_server = MockServer::start().await;
+        let uploaded = Arc::new(Mutex::new(Vec::new()));
+        let recorded = uploaded.clone();
+        Mock::given(method("POST"))
+            .respond_with(move |request: &Request| {
+                let key = request
+                    .url
+                    .path()
+                    .trim_start_matches("/storage/v1/object/site/")
+                    .to_string();
+                recorded.lock().unwrap().push(key.clone());
+                ResponseTemplate::new(200).set_body_json(file_object(&key))
+            })
+            .mount(&mock_server)
+            .await;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_tree(
+            temp_dir.path(),
+            &[
+                ("index.html", "<html></html>"),
+                ("css/app.css", "body {}"),
+                ("js/vendor/lib.js", "lib()"),
+                ("js/app.js.map", "{}"),
+                (".DS_Store", ""),
+            ],
+        )
+        .await;
+        tokio::fs::create_dir_all(temp_dir.path().join("empty/nested"))
+            .await
+            .unwrap();
+
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let progress_seen = progress.clone();
+        let options = DirTransferOptions::new()
+            .with_concurrency(2)
+            .exclude("**/*.map")
+            .exclude(".DS_Store")
+            .on_progress(move |p| progress_seen.lock().unwrap().push(p));
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let report = storage_client
+            .from("site")
+            .upload_directory(temp_dir.path(), "/v2/", options)
+            .await
+            .unwrap();
+
+        assert!(report.is_success());
+        let expected = vec![
+            "v2/css/app.css".to_string(),
+            "v2/index.html".to_string(),
+            "v2/js/vendor/lib.js".to_string(),
+        ];
+        assert_eq!(report.transferred, expected);
+        assert_eq!(report.bytes, 7 + 13 + 5);
+        let mut uploaded = uploaded.lock().unwrap().clone();
+        uploaded.sort();
+        assert_eq!(uploaded, expected);
+
+        let progress = progress.lock().unwrap();
+        assert_eq!(progress.len(), 3);
+        assert_eq!(
+            progress.last(),
+            Some(&DirTransferProgress {
+                files_done: 3,
+                files_total: 3,
+                bytes_done: 25,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_directory_reports_failures_and_syncs() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/object/site/broken.txt"))
+            .respond_with(ResponseTemplate::new(413).set_body_string("Payload too large"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/object/site/ok.txt"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(file_object("ok.txt")))
+            .mount(&mock_server)
+            .await;
+        // リモートには古い old.txt と、空フォルダーのプレースホルダーがある
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/site"))
+            .and(query_param("prefix", ""))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!([file_object("ok.txt"), folder("old"),])),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/site"))
+            .and(query_param("prefix", "old"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                file_object("old.txt"),
+                file_object(EMPTY_FOLDER_PLACEHOLDER),
+            ])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/storage/v1/object/site"))
+            .and(wiremock::matchers::body_json(
+                json!({ "prefixes": ["old/old.txt"] }),
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_tree(
+            temp_dir.path(),
+            &[("ok.txt", "ok"), ("broken.txt", "broken")],
+        )
+        .await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let bucket = storage_client.from("site");
+        let options = DirTransferOptions::new().with_delete_missing(true);
+        let report = bucket
+            .upload_directory(temp_dir.path(), "", options.clone())
+            .await
+            .unwrap();
+
+        assert!(!report.is_success());
+        assert_eq!(report.transferred, vec!["ok.txt".to_string()]);
+        assert_eq!(report.deleted, vec!["old/old.txt".to_string()]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].path, "broken.txt");
+        assert!(matches!(
+            &report.failed[0].error,
+            StorageError::ApiError { message, .. } if message.contains("Payload too large")
+        ));
+
+        // fail_fast では最初の失敗がそのまま返る
+        let error = bucket
+            .upload_directory(
+                temp_dir.path(),
+                "",
+                options.with_delete_missing(false).with_fail_fast(true),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, StorageError::ApiError { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_download_directory() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/site"))
+            .and(query_param("prefix", "v2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                file_object("index.html"),
+                file_object("notes.md"),
+                folder("css"),
+            ])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/site"))
+            .and(query_param("prefix", "v2/css"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([file_object("app.css")])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/site/v2/index.html"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<html></html>"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/site/v2/css/app.css"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("Object not found"))
+            .mount(&mock_server)
+            .await;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        write_tree(temp_dir.path(), &[("stale.html", "old")]).await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let options = DirTransferOptions::new()
+            .exclude("*.md")
+            .with_delete_missing(true);
+        let report = storage_client
+            .from("site")
+            .download_directory("v2", temp_dir.path(), options)
+            .await
+            .unwrap();
+
+        assert_eq!(report.transferred, vec!["v2/index.html".to_string()]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].path, "v2/css/app.css");
+        assert_eq!(report.deleted, vec!["stale.html".to_string()]);
+        assert_eq!(
+            tokio::fs::read_to_string(temp_dir.path().join("index.html"))
+                .await
+                .unwrap(),
+            "<html></html>"
+        );
+        assert!(!temp_dir.path().join("stale.html").exists());
+        assert!(!temp_dir.path().join("css/app.css").exists());
+    }
+}
@@ -3,25 +3,61 @@
 //! This crate provides storage functionality for Supabase,
 //! allowing for uploading, downloading, and managing files.
 
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use bytes::Bytes;
+use md5::{Digest, Md5};
+use reqwest::header::HeaderMap;
 use reqwest::multipart::{Form, Part};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use supabase_rust_core::{
+    HeaderedClient, RequestIds, SendError, SendGate, SendRejected, SendThroughGate,
+};
 use thiserror::Error;
 use tokio::fs::File;
-use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
 use url::Url;
 
+pub use supabase_rust_core::{Page, Paged, Redacted};
+
+mod directory;
+mod lifecycle;
+mod progress;
+mod range;
+mod signed_url;
+mod upload_body;
+mod usage;
+
+pub use directory::{
+    DirTransferOptions, DirTransferProgress, DirTransferProgressCallback, DirTransferReport,
+    FailedTransfer,
+};
+pub use lifecycle::{PurgeCandidate, PurgeOptions, PurgeReport};
+pub use progress::{ProgressGranularity, ProgressHandler, TransferDirection, TransferProgress};
+pub use range::RangePart;
+pub use signed_url::{verify_signed_url, SignedUrlClaims, SignedUrlError};
+pub use upload_body::{IntoUploadBody, UploadBody};
+pub use usage::BucketUsage;
+
 /// 結果型
 pub type Result<T> = std::result::Result<T, StorageError>;
 
 /// エラー型
 #[derive(Error, Debug)]
 pub enum StorageError {
-    #[error("API error: {0}")]
-    ApiError(String),
+    #[error("API error: {message}{request_ids}")]
+    ApiError {
+        message: String,
+        /// エラーレスポンスの HTTP ステータス (エラーステータス以外で起きたエラーでは `None`)
+        status: Option<u16>,
+        /// `x-client-request-id` とサーバーの `sb-request-id` / `cf-ray`
+        request_ids: Box<RequestIds>,
+    },
 
     #[error("Network error: {0}")]
     NetworkError(#[from] reqwest::Error),
@@ -38,6 +74,18 @@ pub enum StorageError {
     #[error("File not found: {0}")]
     FileNotFound(String),
 
+    /// アップロード先のパスにオブジェクトがある (`UploadMode::Fail` / `FailIfExists`)
+    #[error("Object already exists: {path}{request_ids}")]
+    AlreadyExists {
+        path: String,
+        /// `x-client-request-id` とサーバーの `sb-request-id` / `cf-ray`
+        request_ids: Box<RequestIds>,
+    },
+
+    /// 送信のゲート (統合クライアントのサーキットブレーカーなど) が止めた (リクエストは送信していない)
+    #[error("Request not sent: {0}")]
+    Rejected(SendRejected),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -46,6 +94,49 @@ pub enum StorageError {
 
     #[error("Deserialization error: {0}")]
     DeserializationError(String),
+
+    #[error("Invalid image transform options: {0}")]
+    InvalidTransformOptions(String),
+
+    #[error("Integrity check failed: expected {expected}, got {actual}")]
+    IntegrityError { expected: String, actual: String },
+
+    #[error(
+        "Multipart upload failed at part {part_number} ({bytes_uploaded} bytes uploaded): {source}"
+    )]
+    MultipartPartFailed {
+        part_number: u32,
+        bytes_uploaded: u64,
+        source: Box<StorageError>,
+    },
+
+    /// 要求した範囲がオブジェクトの外 (416 Range Not Satisfiable)
+    #[error("Range not satisfiable (object size: {size:?})")]
+    RangeNotSatisfiable { size: Option<u64> },
+
+    #[error("Invalid range: {0}")]
+    InvalidRange(String),
+
+    #[error(transparent)]
+    SignedUrl(#[from] SignedUrlError),
+
+    /// S3 プロトコルのエラーレスポンス (`<Error><Code>...</Code></Error>`)
+    #[error("S3 error {code}: {message} (Status: {status}){request_ids}")]
+    S3Error {
+        status: u16,
+        code: String,
+        message: String,
+        request_ids: Box<RequestIds>,
+    },
+}
+
+impl From<SendError> for StorageError {
+    fn from(error: SendError) -> Self {
+        match error {
+            SendError::Rejected(rejected) => StorageError::Rejected(rejected),
+            SendError::Http(e) => StorageError::NetworkError(e),
+        }
+    }
 }
 
 impl StorageError {
@@ -54,11 +145,105 @@ impl StorageError {
     }
 }
 
+/// 拡張子が不明な場合のコンテンツタイプ
+pub const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// パスの拡張子からコンテンツタイプを推測 (不明な場合は None)
+///
+/// `archive.tar.gz` のような複数の拡張子は最後の拡張子で判定する。
+pub fn guess_content_type(path: &str) -> Option<&'static str> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    if stem.is_empty() {
+        // `.env` のようなドットファイルは拡張子なしとして扱う
+        return None;
+    }
+    let content_type = match extension.to_ascii_lowercase().as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "heic" => "image/heic",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "m4a" => "audio/mp4",
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "md" => "text/markdown",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "geojson" => "application/geo+json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "bz2" => "application/x-bzip2",
+        "xz" => "application/x-xz",
+        "7z" => "application/x-7z-compressed",
+        "wasm" => "application/wasm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "ppt" => "application/vnd.ms-powerpoint",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+/// アップロード先に同じパスのオブジェクトがある場合の扱い
+///
+/// 通常のアップロードでは `x-upsert` ヘッダー、マルチパートアップロードでは初期化リクエストの
+/// `upsert` として送る。マルチパートではサーバーが衝突を完了時まで検出しないことがあり、
+/// その場合は全チャンクを送った後で `AlreadyExists` になる。チャンクを送る前に失敗させるには
+/// `FailIfExists` を使う。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadMode {
+    /// 上書きせず、サーバーが衝突を返したら [`StorageError::AlreadyExists`] にする (既定)
+    #[default]
+    Fail,
+    /// 既存のオブジェクトを上書きする (`x-upsert: true`)
+    Overwrite,
+    /// 本文を送る前に HEAD でオブジェクトの有無を確認し、あれば `AlreadyExists` にする
+    ///
+    /// 確認とアップロードの間に作られたオブジェクトは `Fail` と同じくサーバーの衝突で検出する。
+    FailIfExists,
+}
+
+impl UploadMode {
+    fn upsert(self) -> bool {
+        self == UploadMode::Overwrite
+    }
+}
+
 /// ファイルアップロードオプション
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct FileOptions {
     pub cache_control: Option<String>,
     pub content_type: Option<String>,
+    pub mode: UploadMode,
+    /// 旧形式のアップサート指定 (`Some(true)` は `UploadMode::Overwrite`、`Some(false)` は
+    /// `UploadMode::Fail`)。指定した場合は `mode` より優先する。
+    #[deprecated(note = "use `mode` / `with_mode`")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub upsert: Option<bool>,
 }
 
@@ -80,11 +265,81 @@ impl FileOptions {
         self
     }
 
-    /// アップサートを設定
+    /// 同じパスのオブジェクトがある場合の扱いを設定
+    #[allow(deprecated)]
+    pub fn with_mode(mut self, mode: UploadMode) -> Self {
+        self.mode = mode;
+        self.upsert = None;
+        self
+    }
+
+    /// アップサートを設定 (`true` は `UploadMode::Overwrite`、`false` は `UploadMode::Fail`)
+    #[deprecated(note = "use `with_mode(UploadMode::Overwrite)`")]
+    #[allow(deprecated)]
     pub fn with_upsert(mut self, upsert: bool) -> Self {
         self.upsert = Some(upsert);
         self
     }
+
+    // 旧形式の `upsert` を反映した `mode`
+    #[allow(deprecated)]
+    fn upload_mode(&self) -> UploadMode {
+        match self.upsert {
+            Some(true) => UploadMode::Overwrite,
+            Some(false) => UploadMode::Fail,
+            None => self.mode,
+        }
+    }
+
+    // 明示的な指定がなければ `path` の拡張子から推測したコンテンツタイプ
+    fn resolve_content_type(&self, path: &str) -> String {
+        self.resolve_content_type_with_hint(path, None)
+    }
+
+    // 拡張子からも推測できなければアップロード元のヒントを使う
+    fn resolve_content_type_with_hint(&self, path: &str, hint: Option<&str>) -> String {
+        self.content_type
+            .clone()
+            .or_else(|| guess_content_type(path).map(str::to_string))
+            .or_else(|| hint.map(str::to_string))
+            .unwrap_or_else(|| DEFAULT_CONTENT_TYPE.to_string())
+    }
+}
+
+/// オブジェクトのメタデータ更新オプション
+///
+/// 指定しなかった `cache_control` / `content_type` は現在の値を引き継ぐ。
+/// `metadata` は既存のカスタムメタデータを置き換える (空なら削除される)。
+#[derive(Debug, Clone, Default)]
+pub struct UpdateMetadataOptions {
+    pub cache_control: Option<String>,
+    pub content_type: Option<String>,
+    pub metadata: HashMap<String, String>,
+}
+
+impl UpdateMetadataOptions {
+    /// 新しいメタデータ更新オプションを作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// キャッシュコントロールを設定
+    pub fn with_cache_control(mut self, cache_control: &str) -> Self {
+        self.cache_control = Some(cache_control.to_string());
+        self
+    }
+
+    /// コンテンツタイプを設定
+    pub fn with_content_type(mut self, content_type: &str) -> Self {
+        self.content_type = Some(content_type.to_string());
+        self
+    }
+
+    /// カスタムメタデータを追加
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
 }
 
 /// ファイル一覧取得オプション
@@ -114,6 +369,12 @@ impl ListOptions {
         self
     }
 
+    /// ページを設定 (`limit`/`offset` に変換)
+    pub fn page(self, page: Page) -> Self {
+        self.limit(page.size.min(i32::MAX as u32) as i32)
+            .offset(page.offset().min(i32::MAX as u64) as i32)
+    }
+
     /// ソート順を設定
     pub fn sort_by(mut self, column: &str, order: SortOrder) -> Self {
         self.sort_by = Some(SortBy {
@@ -151,13 +412,94 @@ pub enum SortOrder {
     Desc,
 }
 
+/// 画像のリサイズモード
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeMode {
+    Cover,
+    Contain,
+    Fill,
+}
+
+impl std::fmt::Display for ResizeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            ResizeMode::Cover => "cover",
+            ResizeMode::Contain => "contain",
+            ResizeMode::Fill => "fill",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+impl std::str::FromStr for ResizeMode {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "cover" => Ok(ResizeMode::Cover),
+            "contain" => Ok(ResizeMode::Contain),
+            "fill" => Ok(ResizeMode::Fill),
+            _ => Err(StorageError::InvalidTransformOptions(format!(
+                "unknown resize mode: {:?} (expected cover, contain or fill)",
+                s
+            ))),
+        }
+    }
+}
+
+/// 画像の出力フォーマット
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Origin,
+    Webp,
+    Png,
+    Jpeg,
+    Avif,
+}
+
+impl std::fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self {
+            ImageFormat::Origin => "origin",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Avif => "avif",
+        };
+        write!(f, "{}", value)
+    }
+}
+
+impl std::str::FromStr for ImageFormat {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "origin" => Ok(ImageFormat::Origin),
+            "webp" => Ok(ImageFormat::Webp),
+            "png" => Ok(ImageFormat::Png),
+            "jpeg" | "jpg" => Ok(ImageFormat::Jpeg),
+            "avif" => Ok(ImageFormat::Avif),
+            _ => Err(StorageError::InvalidTransformOptions(format!(
+                "unknown image format: {:?} (expected origin, webp, png, jpeg or avif)",
+                s
+            ))),
+        }
+    }
+}
+
+/// 画質として指定できる範囲
+pub const IMAGE_QUALITY_RANGE: std::ops::RangeInclusive<u32> = 20..=100;
+
 /// 画像変換オプション
 #[derive(Debug, Clone, Serialize, Default)]
 pub struct ImageTransformOptions {
     pub width: Option<u32>,
     pub height: Option<u32>,
-    pub resize: Option<String>,
-    pub format: Option<String>,
+    pub resize: Option<ResizeMode>,
+    pub format: Option<ImageFormat>,
     pub quality: Option<u32>,
 }
 
@@ -179,24 +521,78 @@ impl ImageTransformOptions {
         self
     }
 
-    /// リサイズモードを設定 (cover, contain, fill)
-    pub fn with_resize(mut self, resize: &str) -> Self {
-        self.resize = Some(resize.to_string());
+    /// リサイズモードを設定
+    pub fn with_resize_mode(mut self, resize: ResizeMode) -> Self {
+        self.resize = Some(resize);
         self
     }
 
-    /// 出力フォーマットを設定 (webp, png, jpeg, etc)
-    pub fn with_format(mut self, format: &str) -> Self {
-        self.format = Some(format.to_string());
+    /// リサイズモードを文字列で設定 (cover, contain, fill)
+    ///
+    /// 解釈できない値は警告をログに出して無視する。
+    #[deprecated(note = "use `with_resize_mode` or `try_with_resize`")]
+    pub fn with_resize(self, resize: &str) -> Self {
+        match resize.parse() {
+            Ok(resize) => self.with_resize_mode(resize),
+            Err(e) => {
+                log::warn!("Ignoring image resize mode: {}", e);
+                self
+            }
+        }
+    }
+
+    /// リサイズモードを文字列で設定 (cover, contain, fill 以外はエラー)
+    pub fn try_with_resize(self, resize: &str) -> Result<Self> {
+        Ok(self.with_resize_mode(resize.parse()?))
+    }
+
+    /// 出力フォーマットを設定
+    pub fn with_image_format(mut self, format: ImageFormat) -> Self {
+        self.format = Some(format);
         self
     }
 
-    /// 画質を設定 (1-100)
+    /// 出力フォーマットを文字列で設定 (origin, webp, png, jpeg, avif)
+    ///
+    /// 解釈できない値は警告をログに出して無視する。
+    #[deprecated(note = "use `with_image_format` or `try_with_format`")]
+    pub fn with_format(self, format: &str) -> Self {
+        match format.parse() {
+            Ok(format) => self.with_image_format(format),
+            Err(e) => {
+                log::warn!("Ignoring image format: {}", e);
+                self
+            }
+        }
+    }
+
+    /// 出力フォーマットを文字列で設定 (origin, webp, png, jpeg, avif 以外はエラー)
+    pub fn try_with_format(self, format: &str) -> Result<Self> {
+        Ok(self.with_image_format(format.parse()?))
+    }
+
+    /// 画質を設定 (20-100 の範囲に丸める)
+    #[deprecated(note = "use `try_with_quality`, which rejects values outside 20-100")]
     pub fn with_quality(mut self, quality: u32) -> Self {
-        self.quality = Some(quality.min(100));
+        self.quality =
+            Some(quality.clamp(*IMAGE_QUALITY_RANGE.start(), *IMAGE_QUALITY_RANGE.end()));
         self
     }
 
+    /// 画質を設定 (20-100 以外はエラー)
+    pub fn try_with_quality(mut self, quality: u32) -> Result<Self> {
+        if !IMAGE_QUALITY_RANGE.contains(&quality) {
+            return Err(StorageError::InvalidTransformOptions(format!(
+                "quality must be between {} and {}, got {}",
+                IMAGE_QUALITY_RANGE.start(),
+                IMAGE_QUALITY_RANGE.end(),
+                quality
+            )));
+        }
+        self.quality = Some(quality);
+        Ok(self)
+    }
+
     /// URLクエリパラメータに変換 (No leading '?')
     fn to_query_params(&self) -> String {
         let mut params = Vec::new();
@@ -209,11 +605,11 @@ impl ImageTransformOptions {
             params.push(format!("height={}", height));
         }
 
-        if let Some(resize) = &self.resize {
+        if let Some(resize) = self.resize {
             params.push(format!("resize={}", resize));
         }
 
-        if let Some(format) = &self.format {
+        if let Some(format) = self.format {
             params.push(format!("format={}", format));
         }
 
@@ -225,6 +621,142 @@ impl ImageTransformOptions {
     }
 }
 
+/// 整合性検証付きダウンロードの結果
+#[derive(Debug, Clone)]
+pub struct VerifiedBytes {
+    pub data: Bytes,
+    pub etag: Option<String>,
+    /// MD5 (etag) との照合まで行えた場合は true
+    pub verified: bool,
+}
+
+/// 整合性検証付きファイルダウンロードの結果
+#[derive(Debug, Clone)]
+pub struct VerifiedDownload {
+    pub size: u64,
+    pub etag: Option<String>,
+    /// MD5 (etag) との照合まで行えた場合は true
+    pub verified: bool,
+}
+
+/// ダウンロード時の整合性検証オプション
+#[derive(Debug, Clone, Copy)]
+pub struct VerifyOptions {
+    /// マルチパートアップロードの etag (`-` を含む) は MD5 照合をスキップする
+    /// (false の場合は照合できないものとしてエラーにする)
+    pub skip_multipart_etag: bool,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            skip_multipart_etag: true,
+        }
+    }
+}
+
+// ダウンロード中にサイズと MD5 を逐次検証する
+struct IntegrityVerifier {
+    hasher: Option<Md5>,
+    etag: Option<String>,
+    expected_len: Option<u64>,
+    received: u64,
+}
+
+impl IntegrityVerifier {
+    fn new(response: &reqwest::Response, options: VerifyOptions) -> Result<Self> {
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim_start_matches("W/").trim_matches('"').to_string());
+
+        let hasher = match &etag {
+            Some(tag) if tag.contains('-') => {
+                if !options.skip_multipart_etag {
+                    return Err(StorageError::IntegrityError {
+                        expected: tag.clone(),
+                        actual: "multipart etag (cannot be verified with MD5)".to_string(),
+                    });
+                }
+                None
+            }
+            Some(tag) if tag.len() == 32 && tag.chars().all(|c| c.is_ascii_hexdigit()) => {
+                Some(Md5::new())
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            hasher,
+            etag,
+            expected_len: response.content_length(),
+            received: 0,
+        })
+    }
+
+    fn update(&mut self, chunk: &[u8]) {
+        self.received += chunk.len() as u64;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(chunk);
+        }
+    }
+
+    // 受信途中で接続が切れた場合、サイズ不一致として扱えるかを判定
+    fn truncated(&self) -> Option<StorageError> {
+        match self.expected_len {
+            Some(expected) if self.received < expected => Some(StorageError::IntegrityError {
+                expected: format!("{} bytes", expected),
+                actual: format!("{} bytes", self.received),
+            }),
+            _ => None,
+        }
+    }
+
+    fn finish(self) -> Result<bool> {
+        if let Some(expected) = self.expected_len {
+            if expected != self.received {
+                return Err(StorageError::IntegrityError {
+                    expected: format!("{} bytes", expected),
+                    actual: format!("{} bytes", self.received),
+                });
+            }
+        }
+
+        match (self.hasher, self.etag) {
+            (Some(hasher), Some(etag)) => {
+                let actual = hasher
+                    .finalize()
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>();
+                if !actual.eq_ignore_ascii_case(&etag) {
+                    return Err(StorageError::IntegrityError {
+                        expected: etag,
+                        actual,
+                    });
+                }
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+/// タイムスタンプ型
+///
+/// `chrono` フィーチャーを有効にすると `chrono::DateTime<Utc>` になり、RFC 3339 としてパースされる。
+/// 無効の場合は API が返した文字列のまま保持する (従来の挙動)。
+#[cfg(feature = "chrono")]
+pub type Timestamp = chrono::DateTime<chrono::Utc>;
+
+/// タイムスタンプ型
+///
+/// `chrono` フィーチャーを有効にすると `chrono::DateTime<Utc>` になり、RFC 3339 としてパースされる。
+/// 無効の場合は API が返した文字列のまま保持する (従来の挙動)。
+#[cfg(not(feature = "chrono"))]
+pub type Timestamp = String;
+
 /// ファイル情報
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileObject {
@@ -232,9 +764,9 @@ pub struct FileObject {
     pub bucket_id: String,
     pub owner: String,
     pub id: String,
-    pub updated_at: String,
-    pub created_at: String,
-    pub last_accessed_at: String,
+    pub updated_at: Timestamp,
+    pub created_at: Timestamp,
+    pub last_accessed_at: Timestamp,
     pub metadata: Option<serde_json::Value>,
     pub mime_type: Option<String>,
     pub size: i64,
@@ -247,8 +779,77 @@ pub struct Bucket {
     pub name: String,
     pub owner: String,
     pub public: bool,
-    pub created_at: String,
-    pub updated_at: String,
+    /// アップロード可能な最大サイズ (バイト、未設定なら None)
+    #[serde(default)]
+    pub file_size_limit: Option<i64>,
+    /// 許可された MIME タイプ (未設定なら None)
+    #[serde(default)]
+    pub allowed_mime_types: Option<Vec<String>>,
+    pub created_at: Timestamp,
+    pub updated_at: Timestamp,
+}
+
+/// バケット一覧取得オプション
+#[derive(Debug, Clone, Default)]
+pub struct ListBucketsOptions {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    pub search: Option<String>,
+    /// 指定したオーナーのバケットのみ返す (API にフィルタがないためクライアント側で絞り込む)
+    pub owner: Option<String>,
+}
+
+impl ListBucketsOptions {
+    /// 新しい一覧オプションを作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 取得上限を設定
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// オフセットを設定
+    pub fn offset(mut self, offset: u32) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// ページを設定 (`limit`/`offset` に変換)
+    pub fn page(self, page: Page) -> Self {
+        self.limit(page.size)
+            .offset(page.offset().min(u32::MAX as u64) as u32)
+    }
+
+    /// バケット名で検索
+    pub fn search(mut self, search: &str) -> Self {
+        self.search = Some(search.to_string());
+        self
+    }
+
+    /// オーナーで絞り込む
+    ///
+    /// 絞り込みは取得後に行うため、1ページあたりの件数が `limit` より少なくなることがある。
+    pub fn owner(mut self, owner: &str) -> Self {
+        self.owner = Some(owner.to_string());
+        self
+    }
+
+    fn query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+        if let Some(limit) = self.limit {
+            query.push(("limit", limit.to_string()));
+        }
+        if let Some(offset) = self.offset {
+            query.push(("offset", offset.to_string()));
+        }
+        if let Some(search) = &self.search {
+            query.push(("search", search.clone()));
+        }
+        query
+    }
 }
 
 /// チャンクアップロードの初期化結果
@@ -267,6 +868,9 @@ pub struct UploadedPartInfo {
     #[serde(rename = "partNumber")]
     pub part_number: u32,
     pub etag: String,
+    /// チャンクの MD5 (16進数、`Content-MD5` ヘッダーで送信したもの)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
 }
 
 /// チャンクアップロードの完了リクエスト
@@ -274,20 +878,74 @@ pub struct UploadedPartInfo {
 struct CompleteMultipartUploadRequest {
     #[serde(rename = "uploadId")]
     pub upload_id: String,
-    pub parts: Vec<UploadedPartInfo>,
+    pub parts: Vec<CompletedPart>,
+}
+
+/// 完了リクエストに含めるチャンク (チェックサムは送信しない)
+#[derive(Debug, Clone, Serialize)]
+struct CompletedPart {
+    #[serde(rename = "partNumber")]
+    part_number: u32,
+    etag: String,
+}
+
+/// チャンクごとのリトライ設定
+#[derive(Debug, Clone)]
+pub struct PartRetryPolicy {
+    /// 1チャンクあたりの最大試行回数 (初回を含む)
+    pub max_attempts: u32,
+    /// 最初のリトライまでの待ち時間 (以降は倍々で増加)
+    pub initial_backoff: Duration,
+    /// 待ち時間の上限
+    pub max_backoff: Duration,
+}
+
+impl Default for PartRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl PartRetryPolicy {
+    /// リトライしない設定
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// チャンクのアップロード失敗 (リトライ可能かどうか)
+enum PartAttemptError {
+    Retryable(StorageError),
+    Fatal(StorageError),
 }
 
 /// ストレージバケットクライアント
 pub struct StorageBucketClient<'a> {
     parent: &'a StorageClient,
     bucket_id: String,
+    progress: Option<ProgressHandler>,
+    progress_granularity: ProgressGranularity,
 }
 
 /// ストレージクライアント
 pub struct StorageClient {
     base_url: String,
-    api_key: String,
-    http_client: Client,
+    api_key: Redacted<String>,
+    http_client: HeaderedClient,
 }
 
 impl StorageClient {
@@ -295,36 +953,79 @@ impl StorageClient {
     pub fn new(base_url: &str, api_key: &str, http_client: Client) -> Self {
         Self {
             base_url: base_url.to_string(),
-            api_key: api_key.to_string(),
-            http_client,
+            api_key: api_key.into(),
+            http_client: http_client.into(),
         }
     }
 
+    /// すべてのリクエストに付けるヘッダー (マルチテナント構成の `x-tenant-id` など)
+    ///
+    /// `from()` のバケットクライアントと `s3_compatible()` の S3 クライアントにも適用する。
+    pub fn with_default_headers(mut self, headers: HeaderMap) -> Self {
+        self.http_client = self.http_client.with_default_headers(headers);
+        self
+    }
+
+    /// 送信前にリクエストを通すかを決めるゲート (統合クライアントのサーキットブレーカーなど)
+    ///
+    /// ゲートが止めたリクエストは送信せず `StorageError::Rejected` を返す。
+    /// `from()` のバケットクライアントと `s3_compatible()` の S3 クライアントにも適用する。
+    pub fn with_send_gate(mut self, gate: Arc<dyn SendGate>) -> Self {
+        self.http_client = self.http_client.with_send_gate(gate);
+        self
+    }
+
+    /// エラー本文から API キーと Bearer トークンを取り除く
+    fn scrub_secrets(&self, text: &str) -> String {
+        supabase_rust_core::scrub(text, &[self.api_key.expose()])
+    }
+
     /// バケットを指定
     pub fn from<'a>(&'a self, bucket_id: &str) -> StorageBucketClient<'a> {
         StorageBucketClient {
             parent: self,
             bucket_id: bucket_id.to_string(),
+            progress: None,
+            progress_granularity: ProgressGranularity::default(),
         }
     }
 
     /// バケット一覧を取得
     pub async fn list_buckets(&self) -> Result<Vec<Bucket>> {
+        self.list_buckets_with_options(ListBucketsOptions::default())
+            .await
+    }
+
+    /// オプションを指定してバケット一覧を取得 (ページングや検索、オーナーでの絞り込み)
+    pub async fn list_buckets_with_options(
+        &self,
+        options: ListBucketsOptions,
+    ) -> Result<Vec<Bucket>> {
         let url = format!("{}/storage/v1/bucket", self.base_url);
 
         let response = self
             .http_client
             .get(&url)
-            .header("apikey", &self.api_key)
-            .send()
+            .header("apikey", self.api_key.expose())
+            .query(&options.query())
+            .send_through(self.http_client.send_gate())
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+            let status = response.status();
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(&response.text().await?);
+            return Err(StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            });
         }
 
-        let buckets = response.json::<Vec<Bucket>>().await?;
+        let mut buckets = response.json::<Vec<Bucket>>().await?;
+        if let Some(owner) = &options.owner {
+            buckets.retain(|bucket| &bucket.owner == owner);
+        }
 
         Ok(buckets)
     }
@@ -342,15 +1043,21 @@ impl StorageClient {
         let response = self
             .http_client
             .post(&url)
-            .header("apikey", &self.api_key)
+            .header("apikey", self.api_key.expose())
             .header("Content-Type", "application/json")
             .json(&payload)
-            .send()
+            .send_through(self.http_client.send_gate())
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+            let status = response.status();
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(&response.text().await?);
+            return Err(StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            });
         }
 
         let bucket = response.json::<Bucket>().await?;
@@ -365,13 +1072,19 @@ impl StorageClient {
         let response = self
             .http_client
             .delete(&url)
-            .header("apikey", &self.api_key)
-            .send()
+            .header("apikey", self.api_key.expose())
+            .send_through(self.http_client.send_gate())
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+            let status = response.status();
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(&response.text().await?);
+            return Err(StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            });
         }
 
         Ok(())
@@ -389,15 +1102,21 @@ impl StorageClient {
         let response = self
             .http_client
             .put(&url)
-            .header("apikey", &self.api_key)
+            .header("apikey", self.api_key.expose())
             .header("Content-Type", "application/json")
             .json(&payload)
-            .send()
+            .send_through(self.http_client.send_gate())
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+            let status = response.status();
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(&response.text().await?);
+            return Err(StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            });
         }
 
         let bucket = response.json::<Bucket>().await?;
@@ -408,34 +1127,85 @@ impl StorageClient {
 
 impl<'a> StorageBucketClient<'a> {
     /// ファイルをアップロード
+    ///
+    /// `body` はファイルパス、`Bytes`、`Vec<u8>`、`tokio::fs::File`、[`UploadBody`] のいずれか
+    /// ([`IntoUploadBody`] を参照)。コンテンツタイプは `FileOptions` の指定、`path` の拡張子、
+    /// アップロード元のヒントの順に決める。`with_progress` を指定した場合は本文をバッファせずに送る。
     pub async fn upload(
         &self,
         path: &str,
-        file_path: &Path,
+        body: impl IntoUploadBody,
+        options: Option<FileOptions>,
+    ) -> Result<FileObject> {
+        let body = body.into_upload_body().await?;
+        let options = options.unwrap_or_default();
+        let content_type = options.resolve_content_type_with_hint(path, body.content_type());
+        let file_name = body
+            .file_name()
+            .unwrap_or_else(|| path.rsplit('/').next().unwrap_or(path))
+            .to_string();
+        let total = body.len();
+        let (part, tracker) = match self.progress_tracker(TransferDirection::Upload, total) {
+            Some(tracker) => {
+                let tracker = Arc::new(Mutex::new(tracker));
+                let stream = progress::upload_stream(body, tracker.clone());
+                let part = match total {
+                    Some(len) => Part::stream_with_length(stream, len),
+                    None => Part::stream(stream),
+                };
+                (part, Some(tracker))
+            }
+            None => (Part::bytes(Vec::from(body.into_bytes().await?)), None),
+        };
+        let result = self
+            .upload_form(path, part, file_name, content_type, options)
+            .await;
+        if let Some(tracker) = tracker {
+            let mut tracker = progress::lock(&tracker);
+            match &result {
+                Ok(_) => tracker.complete(),
+                Err(_) => tracker.fail(),
+            }
+        }
+        result
+    }
+
+    /// メモリ上のデータをアップロード
+    #[deprecated(note = "use `upload`, which accepts `Bytes` directly")]
+    pub async fn upload_bytes(
+        &self,
+        path: &str,
+        data: Bytes,
         options: Option<FileOptions>,
     ) -> Result<FileObject> {
+        self.upload(path, data, options).await
+    }
+
+    async fn upload_form(
+        &self,
+        path: &str,
+        part: Part,
+        file_name: String,
+        content_type: String,
+        options: FileOptions,
+    ) -> Result<FileObject> {
+        if options.upload_mode() == UploadMode::FailIfExists {
+            self.ensure_absent(path).await?;
+        }
         let mut url = Url::parse(&self.parent.base_url)?;
         url.set_path(&format!("/storage/v1/object/{}/{}", self.bucket_id, path));
 
         // オプションをURLクエリとして設定
-        if let Some(opts) = &options {
-            let mut query_pairs = url.query_pairs_mut();
-            if let Some(cache_control) = &opts.cache_control {
-                query_pairs.append_pair("cache_control", cache_control);
-            }
-            if let Some(upsert) = &opts.upsert {
-                query_pairs.append_pair("upsert", &upsert.to_string());
-            }
+        if let Some(cache_control) = &options.cache_control {
+            url.query_pairs_mut()
+                .append_pair("cache_control", cache_control);
         }
 
-        // ファイルの内容を読み込む
-        let mut file = File::open(file_path).await?;
-        let mut contents = Vec::new();
-        file.read_to_end(&mut contents).await?;
-
         // マルチパートフォームデータの作成
-        let part = Part::bytes(contents)
-            .file_name(file_path.file_name().unwrap().to_string_lossy().to_string());
+        let part = part
+            .file_name(file_name)
+            .mime_str(&content_type)
+            .map_err(|e| StorageError::new(format!("Invalid content type: {}", e)))?;
 
         let form = Form::new().part("file", part);
 
@@ -443,52 +1213,263 @@ impl<'a> StorageBucketClient<'a> {
             .parent
             .http_client
             .post(url)
-            .header("apikey", &self.parent.api_key)
-            .header("Authorization", format!("Bearer {}", &self.parent.api_key))
+            .header("apikey", self.parent.api_key.expose())
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.parent.api_key.expose()),
+            )
+            .header("x-upsert", options.upload_mode().upsert().to_string())
             .multipart(form)
-            .send()
+            .send_through(self.parent.http_client.send_gate())
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+            return Err(self.upload_error(path, response).await);
         }
 
-        let file_object = response.json::<FileObject>().await?;
+        let mut file_object = response.json::<FileObject>().await?;
+        file_object.mime_type.get_or_insert(content_type);
 
         Ok(file_object)
     }
 
-    /// ファイルをダウンロード
-    pub async fn download(&self, path: &str) -> Result<Bytes> {
+    // `UploadMode::FailIfExists` の事前確認
+    //
+    // HEAD が 404 ならオブジェクトはない。成功ならあり、それ以外 (認証エラーなど) は
+    // 有無が分からないのでエラーにする。
+    async fn ensure_absent(&self, path: &str) -> Result<()> {
         let mut url = Url::parse(&self.parent.base_url)?;
         url.set_path(&format!("/storage/v1/object/{}/{}", self.bucket_id, path));
-
         let response = self
             .parent
             .http_client
-            .get(url)
-            .header("apikey", &self.parent.api_key)
-            .header("Authorization", format!("Bearer {}", &self.parent.api_key))
-            .send()
+            .head(url)
+            .header("apikey", self.parent.api_key.expose())
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.parent.api_key.expose()),
+            )
+            .send_through(self.parent.http_client.send_gate())
             .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(());
         }
-
-        let bytes = response.bytes().await?;
-
-        Ok(bytes)
+        let request_ids = Box::new(RequestIds::from_response(&response));
+        if status.is_success() {
+            return Err(StorageError::AlreadyExists {
+                path: path.to_string(),
+                request_ids,
+            });
+        }
+        // HEAD のレスポンスには本文がない
+        Err(StorageError::ApiError {
+            message: format!("Failed to check whether {} exists", path),
+            status: Some(status.as_u16()),
+            request_ids,
+        })
     }
 
-    /// ファイル一覧を取得
+    // 失敗したアップロードのレスポンスをエラーに変換する
+    //
+    // 既存のオブジェクトとの衝突は、409 のほか本文の `statusCode` が "409" の 400 としても
+    // 返るため、どちらも `AlreadyExists` にする。
+    async fn upload_error(&self, path: &str, response: reqwest::Response) -> StorageError {
+        let status = response.status();
+        let request_ids = Box::new(RequestIds::from_response(&response));
+        let error_text = match response.text().await {
+            Ok(text) => text,
+            Err(e) => return e.into(),
+        };
+        let duplicate = status == reqwest::StatusCode::CONFLICT
+            || serde_json::from_str::<serde_json::Value>(&error_text)
+                .ok()
+                .and_then(|body| body.get("statusCode").cloned())
+                .is_some_and(|code| code == "409" || code == 409);
+        if duplicate {
+            return StorageError::AlreadyExists {
+                path: path.to_string(),
+                request_ids,
+            };
+        }
+        StorageError::ApiError {
+            message: self.parent.scrub_secrets(&error_text),
+            status: Some(status.as_u16()),
+            request_ids,
+        }
+    }
+
+    // オブジェクト取得リクエストを送信
+    fn object_request(&self, path: &str) -> Result<reqwest::RequestBuilder> {
+        let mut url = Url::parse(&self.parent.base_url)?;
+        url.set_path(&format!("/storage/v1/object/{}/{}", self.bucket_id, path));
+
+        Ok(self
+            .parent
+            .http_client
+            .get(url)
+            .header("apikey", self.parent.api_key.expose())
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.parent.api_key.expose()),
+            ))
+    }
+
+    async fn get_object(&self, path: &str) -> Result<reqwest::Response> {
+        let response = self
+            .object_request(path)?
+            .send_through(self.parent.http_client.send_gate())
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.parent.scrub_secrets(&response.text().await?);
+            return Err(StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            });
+        }
+
+        Ok(response)
+    }
+
+    /// ファイルをダウンロード
+    pub async fn download(&self, path: &str) -> Result<Bytes> {
+        let mut response = self.get_object(path).await?;
+        let Some(mut tracker) =
+            self.progress_tracker(TransferDirection::Download, response.content_length())
+        else {
+            return Ok(response.bytes().await?);
+        };
+
+        let mut data = Vec::new();
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    tracker.advance(chunk.len());
+                    data.extend_from_slice(&chunk);
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    tracker.fail();
+                    return Err(err.into());
+                }
+            }
+        }
+        tracker.complete();
+
+        Ok(Bytes::from(data))
+    }
+
+    /// ファイルをダウンロードし、content-length と etag (MD5) で整合性を検証
+    pub async fn download_verified(&self, path: &str) -> Result<VerifiedBytes> {
+        self.download_verified_with_options(path, VerifyOptions::default())
+            .await
+    }
+
+    /// 検証オプションを指定してファイルをダウンロード
+    pub async fn download_verified_with_options(
+        &self,
+        path: &str,
+        options: VerifyOptions,
+    ) -> Result<VerifiedBytes> {
+        let mut response = self.get_object(path).await?;
+        let mut verifier = IntegrityVerifier::new(&response, options)?;
+        let mut data = Vec::new();
+
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    verifier.update(&chunk);
+                    data.extend_from_slice(&chunk);
+                }
+                Ok(None) => break,
+                Err(err) => return Err(verifier.truncated().unwrap_or(err.into())),
+            }
+        }
+
+        let etag = verifier.etag.clone();
+        let verified = verifier.finish()?;
+
+        Ok(VerifiedBytes {
+            data: Bytes::from(data),
+            etag,
+            verified,
+        })
+    }
+
+    /// ファイルをストリーミングでローカルファイルに保存し、書き込みながら整合性を検証
+    ///
+    /// 検証に失敗した場合、書き込み途中のファイルは削除される。
+    pub async fn download_to_file(
+        &self,
+        path: &str,
+        destination: &Path,
+        options: Option<VerifyOptions>,
+    ) -> Result<VerifiedDownload> {
+        let mut response = self.get_object(path).await?;
+        let mut verifier = IntegrityVerifier::new(&response, options.unwrap_or_default())?;
+        let mut tracker =
+            self.progress_tracker(TransferDirection::Download, response.content_length());
+        let mut file = File::create(destination).await?;
+
+        let result = async {
+            loop {
+                match response.chunk().await {
+                    Ok(Some(chunk)) => {
+                        verifier.update(&chunk);
+                        file.write_all(&chunk).await?;
+                        if let Some(tracker) = tracker.as_mut() {
+                            tracker.advance(chunk.len());
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(err) => return Err(verifier.truncated().unwrap_or(err.into())),
+                }
+            }
+            file.flush().await?;
+
+            let size = verifier.received;
+            let etag = verifier.etag.clone();
+            let verified = verifier.finish()?;
+            Ok(VerifiedDownload {
+                size,
+                etag,
+                verified,
+            })
+        }
+        .await;
+
+        if let Some(tracker) = tracker.as_mut() {
+            match &result {
+                Ok(_) => tracker.complete(),
+                Err(_) => tracker.fail(),
+            }
+        }
+        if result.is_err() {
+            drop(file);
+            let _ = tokio::fs::remove_file(destination).await;
+        }
+        result
+    }
+
+    /// ファイル一覧を取得
     pub async fn list(
         &self,
         prefix: &str,
         options: Option<ListOptions>,
     ) -> Result<Vec<FileObject>> {
+        self.list_as(prefix, options).await
+    }
+
+    // 一覧を任意の型で取得 (フォルダーのエントリは FileObject として読めないため)
+    async fn list_as<T: serde::de::DeserializeOwned>(
+        &self,
+        prefix: &str,
+        options: Option<ListOptions>,
+    ) -> Result<Vec<T>> {
         let mut url = Url::parse(&self.parent.base_url)?;
         url.set_path(&format!("/storage/v1/object/list/{}", self.bucket_id));
 
@@ -517,21 +1498,49 @@ impl<'a> StorageBucketClient<'a> {
             .parent
             .http_client
             .get(url)
-            .header("apikey", &self.parent.api_key)
-            .header("Authorization", format!("Bearer {}", &self.parent.api_key))
-            .send()
+            .header("apikey", self.parent.api_key.expose())
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.parent.api_key.expose()),
+            )
+            .send_through(self.parent.http_client.send_gate())
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+            let status = response.status();
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.parent.scrub_secrets(&response.text().await?);
+            return Err(StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            });
         }
 
-        let files = response.json::<Vec<FileObject>>().await?;
+        let files = response.json::<Vec<T>>().await?;
 
         Ok(files)
     }
 
+    /// ファイル一覧をページ単位で取得
+    ///
+    /// Storage API は全件数を返さないため、1件多く取得して `has_more` を判定する。
+    pub async fn list_paged(
+        &self,
+        prefix: &str,
+        page: Page,
+        options: Option<ListOptions>,
+    ) -> Result<Paged<FileObject>> {
+        let mut options = options.unwrap_or_default().page(page);
+        options.limit = options.limit.map(|limit| limit.saturating_add(1));
+
+        let mut files = self.list(prefix, Some(options)).await?;
+        let has_more = files.len() > page.size as usize;
+        files.truncate(page.size as usize);
+
+        Ok(Paged::new(files, page, None).with_has_more(has_more))
+    }
+
     /// ファイルを削除
     pub async fn remove(&self, paths: Vec<&str>) -> Result<()> {
         let url = format!(
@@ -547,15 +1556,21 @@ impl<'a> StorageBucketClient<'a> {
             .parent
             .http_client
             .delete(&url)
-            .header("apikey", &self.parent.api_key)
+            .header("apikey", self.parent.api_key.expose())
             .header("Content-Type", "application/json")
             .json(&payload)
-            .send()
+            .send_through(self.parent.http_client.send_gate())
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+            let status = response.status();
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.parent.scrub_secrets(&response.text().await?);
+            return Err(StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            });
         }
 
         Ok(())
@@ -584,15 +1599,21 @@ impl<'a> StorageBucketClient<'a> {
             .parent
             .http_client
             .post(&url)
-            .header("apikey", &self.parent.api_key)
+            .header("apikey", self.parent.api_key.expose())
             .header("Content-Type", "application/json")
             .json(&payload)
-            .send()
+            .send_through(self.parent.http_client.send_gate())
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+            let status = response.status();
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.parent.scrub_secrets(&response.text().await?);
+            return Err(StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            });
         }
 
         #[derive(Deserialize)]
@@ -615,13 +1636,15 @@ impl<'a> StorageBucketClient<'a> {
 
         let options = options.unwrap_or_default();
 
+        let content_type = options.resolve_content_type(path);
+        let mode = options.upload_mode();
         let cache_control = options
             .cache_control
             .unwrap_or_else(|| "max-age=3600".to_string());
-        let content_type = options
-            .content_type
-            .unwrap_or_else(|| "application/octet-stream".to_string());
-        let upsert = options.upsert.unwrap_or(false);
+        if mode == UploadMode::FailIfExists {
+            self.ensure_absent(path).await?;
+        }
+        let upsert = mode.upsert();
 
         let payload = serde_json::json!({
             "bucket": self.bucket_id,
@@ -635,15 +1658,14 @@ impl<'a> StorageBucketClient<'a> {
             .parent
             .http_client
             .post(&url)
-            .header("apikey", &self.parent.api_key)
+            .header("apikey", self.parent.api_key.expose())
             .header("Content-Type", "application/json")
             .json(&payload)
-            .send()
+            .send_through(self.parent.http_client.send_gate())
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+            return Err(self.upload_error(path, response).await);
         }
 
         let initiate_response: InitiateMultipartUploadResponse = response.json().await?;
@@ -652,46 +1674,130 @@ impl<'a> StorageBucketClient<'a> {
     }
 
     /// チャンクをアップロード
+    ///
+    /// チャンクの MD5 を `Content-MD5` ヘッダーで送信し、サーバー側で検証できるようにする。
     pub async fn upload_part(
         &self,
         upload_id: &str,
         part_number: u32,
         data: Bytes,
     ) -> Result<UploadedPartInfo> {
+        self.try_upload_part(upload_id, part_number, data)
+            .await
+            .map_err(|e| match e {
+                PartAttemptError::Retryable(e) | PartAttemptError::Fatal(e) => e,
+            })
+    }
+
+    /// リトライ設定に従ってチャンクをアップロード
+    pub async fn upload_part_with_retry(
+        &self,
+        upload_id: &str,
+        part_number: u32,
+        data: Bytes,
+        retry: &PartRetryPolicy,
+    ) -> Result<UploadedPartInfo> {
+        let max_attempts = retry.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match self
+                .try_upload_part(upload_id, part_number, data.clone())
+                .await
+            {
+                Ok(part_info) => return Ok(part_info),
+                Err(PartAttemptError::Retryable(e)) if attempt < max_attempts => {
+                    log::warn!(
+                        "Upload of part {} failed (attempt {}/{}), retrying: {}",
+                        part_number,
+                        attempt,
+                        max_attempts,
+                        e
+                    );
+                    tokio::time::sleep(retry.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(PartAttemptError::Retryable(e)) | Err(PartAttemptError::Fatal(e)) => {
+                    return Err(e)
+                }
+            }
+        }
+    }
+
+    async fn try_upload_part(
+        &self,
+        upload_id: &str,
+        part_number: u32,
+        data: Bytes,
+    ) -> std::result::Result<UploadedPartInfo, PartAttemptError> {
         let url = format!("{}/storage/v1/upload/part", self.parent.base_url);
 
+        let digest = Md5::digest(&data);
+        let checksum = digest
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+
         let body = reqwest::Body::from(data);
 
         let response = self
             .parent
             .http_client
             .post(&url)
-            .header("apikey", &self.parent.api_key)
+            .header("apikey", self.parent.api_key.expose())
+            .header("Content-MD5", BASE64.encode(digest))
             .query(&[
                 ("uploadId", upload_id),
                 ("partNumber", &part_number.to_string()),
                 ("bucket", &self.bucket_id),
             ])
             .body(body)
-            .send()
-            .await?;
+            .send_through(self.parent.http_client.send_gate())
+            .await
+            .map_err(|e| match e {
+                SendError::Http(e) => PartAttemptError::Retryable(StorageError::NetworkError(e)),
+                // 送信しておらず、再試行してもゲートは開かない
+                rejected => PartAttemptError::Fatal(rejected.into()),
+            })?;
 
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+        let status = response.status();
+        if !status.is_success() {
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self
+                .parent
+                .scrub_secrets(&response.text().await.unwrap_or_default());
+            let error = StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            };
+            return Err(
+                if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                    PartAttemptError::Retryable(error)
+                } else {
+                    PartAttemptError::Fatal(error)
+                },
+            );
         }
 
         let etag = response
             .headers()
             .get("etag")
-            .ok_or_else(|| StorageError::new("ETag header not found in response".to_string()))?
+            .ok_or_else(|| {
+                PartAttemptError::Fatal(StorageError::new(
+                    "ETag header not found in response".to_string(),
+                ))
+            })?
             .to_str()
-            .map_err(|e| StorageError::new(format!("Invalid ETag header: {}", e)))?
+            .map_err(|e| {
+                PartAttemptError::Fatal(StorageError::new(format!("Invalid ETag header: {}", e)))
+            })?
             .to_string();
 
-        let part_info = UploadedPartInfo { part_number, etag };
-
-        Ok(part_info)
+        Ok(UploadedPartInfo {
+            part_number,
+            etag,
+            checksum: Some(checksum),
+        })
     }
 
     /// マルチパートアップロードを完了
@@ -705,24 +1811,29 @@ impl<'a> StorageBucketClient<'a> {
 
         let payload = CompleteMultipartUploadRequest {
             upload_id: upload_id.to_string(),
-            parts,
+            parts: parts
+                .into_iter()
+                .map(|part| CompletedPart {
+                    part_number: part.part_number,
+                    etag: part.etag,
+                })
+                .collect(),
         };
 
         let response = self
             .parent
             .http_client
             .post(&url)
-            .header("apikey", &self.parent.api_key)
+            .header("apikey", self.parent.api_key.expose())
             .header("Content-Type", "application/json")
             .query(&[("bucket", &self.bucket_id), ("key", &path.to_string())])
             .json(&payload)
-            .send()
+            .send_through(self.parent.http_client.send_gate())
             .await
-            .map_err(StorageError::NetworkError)?;
+            .map_err(StorageError::from)?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+            return Err(self.upload_error(path, response).await);
         }
 
         let file_object: FileObject = response.json().await?;
@@ -744,15 +1855,21 @@ impl<'a> StorageBucketClient<'a> {
             .parent
             .http_client
             .post(&url)
-            .header("apikey", &self.parent.api_key)
+            .header("apikey", self.parent.api_key.expose())
             .header("Content-Type", "application/json")
             .json(&payload)
-            .send()
+            .send_through(self.parent.http_client.send_gate())
             .await?;
 
         if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(StorageError::ApiError(error_text));
+            let status = response.status();
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.parent.scrub_secrets(&response.text().await?);
+            return Err(StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            });
         }
 
         Ok(())
@@ -761,64 +1878,162 @@ impl<'a> StorageBucketClient<'a> {
     /// 大容量ファイルをチャンクでアップロード
     ///
     /// このメソッドは大きなファイルを自動的にチャンクに分割してアップロードします。
-    /// 各チャンクは非同期でアップロードされ、すべてのチャンクがアップロードされると
-    /// 自動的にマルチパートアップロードを完了します。
+    /// 失敗したチャンクはデフォルトの [`PartRetryPolicy`] に従ってリトライされ、
+    /// すべてのチャンクがアップロードされると自動的にマルチパートアップロードを完了します。
     pub async fn upload_large_file(
         &self,
         path: &str,
-        file_path: &Path,
+        body: impl IntoUploadBody,
         chunk_size: usize,
         options: Option<FileOptions>,
     ) -> Result<FileObject> {
-        // ファイルを開く
-        let mut file = File::open(file_path).await?;
-
-        // ファイルサイズを取得
-        let file_size = file.metadata().await?.len() as usize;
-
-        // チャンク数を計算
-        let chunk_count = file_size.div_ceil(chunk_size);
+        self.upload_large_file_with_retry(
+            path,
+            body,
+            chunk_size,
+            options,
+            PartRetryPolicy::default(),
+        )
+        .await
+    }
 
-        if chunk_count == 0 {
+    /// リトライ設定を指定して大容量ファイルをチャンクでアップロード
+    ///
+    /// リトライしても失敗したチャンクがある場合はマルチパートアップロードを中止し、
+    /// [`StorageError::MultipartPartFailed`] を返します。
+    pub async fn upload_large_file_with_retry(
+        &self,
+        path: &str,
+        body: impl IntoUploadBody,
+        chunk_size: usize,
+        options: Option<FileOptions>,
+        retry: PartRetryPolicy,
+    ) -> Result<FileObject> {
+        let mut body = body.into_upload_body().await?;
+        if body.is_empty() {
             return Err(StorageError::new("File is empty".to_string()));
         }
 
         // マルチパートアップロードを初期化
-        let init_response = self.initiate_multipart_upload(path, options).await?;
+        let mut options = options.unwrap_or_default();
+        let content_type = options.resolve_content_type_with_hint(path, body.content_type());
+        options.content_type = Some(content_type.clone());
+        let init_response = self.initiate_multipart_upload(path, Some(options)).await?;
+
+        let mut file_object = self
+            .upload_remaining_parts(
+                &init_response.upload_id,
+                path,
+                &mut body,
+                chunk_size,
+                Vec::new(),
+                &retry,
+            )
+            .await?;
+        file_object.mime_type.get_or_insert(content_type);
 
-        // 部分アップロード情報を保持するベクター
-        let mut uploaded_parts = Vec::with_capacity(chunk_count);
+        Ok(file_object)
+    }
 
-        // バッファを準備
-        let mut buffer = vec![0u8; chunk_size];
+    /// 中断したマルチパートアップロードを再開
+    ///
+    /// `already_uploaded` に含まれるチャンクは送信せず、残りのチャンクのみをアップロードして
+    /// 完了します。`chunk_size` は最初のアップロードと同じ値を指定してください。
+    /// `body` は最初のアップロードと同じ内容を先頭から渡します。
+    pub async fn resume_large_file_upload(
+        &self,
+        upload_id: &str,
+        path: &str,
+        body: impl IntoUploadBody,
+        chunk_size: usize,
+        already_uploaded: Vec<UploadedPartInfo>,
+        retry: Option<PartRetryPolicy>,
+    ) -> Result<FileObject> {
+        let mut body = body.into_upload_body().await?;
+        self.upload_remaining_parts(
+            upload_id,
+            path,
+            &mut body,
+            chunk_size,
+            already_uploaded,
+            &retry.unwrap_or_default(),
+        )
+        .await
+    }
 
-        // 各チャンクをアップロード
-        for part_number in 1..=chunk_count as u32 {
-            // バッファにデータを読み込む
-            let n = file.read(&mut buffer).await?;
+    async fn upload_remaining_parts(
+        &self,
+        upload_id: &str,
+        path: &str,
+        body: &mut UploadBody,
+        chunk_size: usize,
+        mut uploaded_parts: Vec<UploadedPartInfo>,
+        retry: &PartRetryPolicy,
+    ) -> Result<FileObject> {
+        if chunk_size == 0 {
+            return Err(StorageError::new(
+                "Chunk size must be greater than zero".to_string(),
+            ));
+        }
 
-            if n == 0 {
-                break;
+        // 長さが分からないアップロード元もあるので、終端まで順に読む
+        let mut bytes_uploaded: u64 = 0;
+        for part_number in 1.. {
+            let already_uploaded = uploaded_parts
+                .iter()
+                .any(|part| part.part_number == part_number);
+            let result = async {
+                if already_uploaded {
+                    return Ok(Some((None, body.skip(chunk_size).await?)));
+                }
+                let chunk = body.read_chunk(chunk_size).await?;
+                if chunk.is_empty() {
+                    return Ok(None);
+                }
+                let len = chunk.len() as u64;
+                self.upload_part_with_retry(upload_id, part_number, chunk, retry)
+                    .await
+                    .map(|part_info| Some((Some(part_info), len)))
             }
+            .await;
 
-            // 実際に読み込んだサイズに合わせてバッファを調整
-            let chunk_data = Bytes::from(buffer[0..n].to_vec());
-
-            // チャンクをアップロード
-            let part_info = self
-                .upload_part(&init_response.upload_id, part_number, chunk_data)
-                .await?;
+            match result {
+                Ok(Some((_, 0))) | Ok(None) => break,
+                Ok(Some((part_info, len))) => {
+                    bytes_uploaded += len;
+                    uploaded_parts.extend(part_info);
+                }
+                Err(error) => {
+                    self.abort_after_failure(upload_id, path).await;
+                    return Err(StorageError::MultipartPartFailed {
+                        part_number,
+                        bytes_uploaded,
+                        source: Box::new(error),
+                    });
+                }
+            }
+        }
 
-            // アップロードした部分情報を保存
-            uploaded_parts.push(part_info);
+        if bytes_uploaded == 0 {
+            self.abort_after_failure(upload_id, path).await;
+            return Err(StorageError::new("File is empty".to_string()));
         }
 
+        uploaded_parts.sort_by_key(|part| part.part_number);
+
         // マルチパートアップロードを完了
-        let file_object = self
-            .complete_multipart_upload(&init_response.upload_id, path, uploaded_parts)
-            .await?;
+        self.complete_multipart_upload(upload_id, path, uploaded_parts)
+            .await
+    }
 
-        Ok(file_object)
+    async fn abort_after_failure(&self, upload_id: &str, path: &str) {
+        if let Err(abort_error) = self.abort_multipart_upload(upload_id, path).await {
+            log::warn!(
+                "Failed to abort multipart upload {}: {}",
+                upload_id,
+                abort_error
+            );
+        }
     }
 
     /// 画像に変換を適用して取得する
@@ -844,24 +2059,33 @@ impl<'a> StorageBucketClient<'a> {
             .parent
             .http_client
             .get(&request_url)
-            .header("apikey", &self.parent.api_key)
-            .header("Authorization", format!("Bearer {}", self.parent.api_key))
-            .send()
+            .header("apikey", self.parent.api_key.expose())
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.parent.api_key.expose()),
+            )
+            .send_through(self.parent.http_client.send_gate())
             .await
-            .map_err(StorageError::NetworkError)?;
+            .map_err(StorageError::from)?;
 
         // ステータスコードを事前に取得
         let status = res.status();
 
         if !status.is_success() {
-            let error_text = res
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(StorageError::ApiError(format!(
-                "Failed to transform image: {} (Status: {})",
-                error_text, status
-            )));
+            let request_ids = Box::new(RequestIds::from_response(&res));
+            let error_text = self.parent.scrub_secrets(
+                &res.text()
+                    .await
+                    .unwrap_or_else(|_| "Unknown error".to_string()),
+            );
+            return Err(StorageError::ApiError {
+                message: format!(
+                    "Failed to transform image: {} (Status: {})",
+                    error_text, status
+                ),
+                status: Some(status.as_u16()),
+                request_ids,
+            });
         }
 
         let bytes = res.bytes().await.map_err(StorageError::NetworkError)?;
@@ -908,25 +2132,34 @@ impl<'a> StorageBucketClient<'a> {
             .parent
             .http_client
             .post(&url)
-            .header("apikey", &self.parent.api_key)
-            .header("Authorization", format!("Bearer {}", self.parent.api_key))
+            .header("apikey", self.parent.api_key.expose())
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.parent.api_key.expose()),
+            )
             .json(&payload)
-            .send()
+            .send_through(self.parent.http_client.send_gate())
             .await
-            .map_err(StorageError::NetworkError)?;
+            .map_err(StorageError::from)?;
 
         // ステータスコードを事前に取得
         let status = res.status();
 
         if !status.is_success() {
-            let error_text = res
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(StorageError::ApiError(format!(
-                "Failed to create signed transform URL: {} (Status: {})",
-                error_text, status
-            )));
+            let request_ids = Box::new(RequestIds::from_response(&res));
+            let error_text = self.parent.scrub_secrets(
+                &res.text()
+                    .await
+                    .unwrap_or_else(|_| "Unknown error".to_string()),
+            );
+            return Err(StorageError::ApiError {
+                message: format!(
+                    "Failed to create signed transform URL: {} (Status: {})",
+                    error_text, status
+                ),
+                status: Some(status.as_u16()),
+                request_ids,
+            });
         }
 
         #[derive(Debug, Deserialize)]
@@ -944,13 +2177,16 @@ impl<'a> StorageBucketClient<'a> {
 
     /// S3互換クライアントを作成
     pub fn s3_compatible(&self, options: s3::S3Options) -> s3::S3BucketClient {
-        s3::S3BucketClient::new(
+        let mut client = s3::S3BucketClient::new(
             &self.parent.base_url,
-            &self.parent.api_key,
+            self.parent.api_key.expose(),
             &self.bucket_id,
-            self.parent.http_client.clone(),
+            self.parent.http_client.client().clone(),
             options,
-        )
+        );
+        // 既定のヘッダーと送信のゲートを引き継ぐ
+        client.http_client = self.parent.http_client.clone();
+        client
     }
 
     /// オブジェクトをバケット内で移動または名前変更します。
@@ -977,48 +2213,173 @@ impl<'a> StorageBucketClient<'a> {
             .parent
             .http_client
             .post(url)
-            .header("apikey", &self.parent.api_key)
-            .header("Authorization", format!("Bearer {}", self.parent.api_key))
+            .header("apikey", self.parent.api_key.expose())
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.parent.api_key.expose()),
+            )
             .header("Content-Type", "application/json")
             .json(&body)
-            .send()
+            .send_through(self.parent.http_client.send_gate())
             .await
-            .map_err(StorageError::NetworkError)?;
+            .map_err(StorageError::from)?;
 
         if response.status().is_success() {
             Ok(())
         } else {
-            let status = response.status();
-            let error_text = response
+            Err(self.api_error("move object", response).await)
+        }
+    }
+
+    /// アップロード済みオブジェクトのキャッシュコントロール・コンテンツタイプ・カスタムメタデータを更新
+    ///
+    /// Storage API にはメタデータだけを更新するエンドポイントがないため、
+    /// 新しいメタデータを指定してオブジェクトを同じパスへコピー (`POST /object/copy`、`x-upsert: true`)
+    /// する。データはサーバー内でコピーされ、ダウンロードや再アップロードは発生しない。
+    /// カスタムメタデータは `x-metadata` ヘッダー (JSON の Base64) で送る。
+    ///
+    /// 古い Storage API がカスタムメタデータなどに対応していない場合は、
+    /// サーバーのエラーメッセージとステータスを含む `ApiError` を返す。
+    pub async fn update_metadata(
+        &self,
+        path: &str,
+        options: UpdateMetadataOptions,
+    ) -> Result<FileObject> {
+        let (cache_control, content_type) = match (options.cache_control, options.content_type) {
+            (Some(cache_control), Some(content_type)) => (cache_control, content_type),
+            (cache_control, content_type) => {
+                let (current_cache_control, current_content_type) =
+                    self.current_headers(path).await?;
+                (
+                    cache_control.unwrap_or(current_cache_control),
+                    content_type.unwrap_or(current_content_type),
+                )
+            }
+        };
+
+        let mut url = Url::parse(&self.parent.base_url)?;
+        url.set_path("/storage/v1/object/copy");
+
+        let body = json!({
+            "bucketId": self.bucket_id,
+            "sourceKey": path,
+            "destinationKey": path,
+            "copyMetadata": false,
+            "metadata": {
+                "cacheControl": cache_control,
+                "mimetype": content_type,
+            }
+        });
+        let user_metadata = serde_json::to_vec(&options.metadata)?;
+
+        let response = self
+            .parent
+            .http_client
+            .post(url)
+            .header("apikey", self.parent.api_key.expose())
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.parent.api_key.expose()),
+            )
+            .header("x-upsert", "true")
+            .header("x-metadata", BASE64.encode(user_metadata))
+            .json(&body)
+            .send_through(self.parent.http_client.send_gate())
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(self.api_error("update metadata", response).await);
+        }
+
+        response
+            .json::<FileObject>()
+            .await
+            .map_err(|e| StorageError::DeserializationError(e.to_string()))
+    }
+
+    // オブジェクトの現在のキャッシュコントロールとコンテンツタイプ (HEAD リクエスト)
+    async fn current_headers(&self, path: &str) -> Result<(String, String)> {
+        let mut url = Url::parse(&self.parent.base_url)?;
+        url.set_path(&format!("/storage/v1/object/{}/{}", self.bucket_id, path));
+
+        let response = self
+            .parent
+            .http_client
+            .head(url)
+            .header("apikey", self.parent.api_key.expose())
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.parent.api_key.expose()),
+            )
+            .send_through(self.parent.http_client.send_gate())
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(self.api_error("read object headers", response).await);
+        }
+
+        let header = |name: reqwest::header::HeaderName, default: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or(default)
+                .to_string()
+        };
+        Ok((
+            header(reqwest::header::CACHE_CONTROL, "max-age=3600"),
+            header(reqwest::header::CONTENT_TYPE, DEFAULT_CONTENT_TYPE),
+        ))
+    }
+
+    // エラーレスポンスを、サーバーのメッセージとステータスを含む ApiError に変換
+    async fn api_error(&self, action: &str, response: reqwest::Response) -> StorageError {
+        let status = response.status();
+        let request_ids = Box::new(RequestIds::from_response(&response));
+        let error_text = self.parent.scrub_secrets(
+            &response
                 .text()
                 .await
-                .unwrap_or_else(|_| "Failed to read error body".to_string());
-            // Try parsing Supabase error format
-            let error_message =
-                if let Ok(json_err) = serde_json::from_str::<serde_json::Value>(&error_text) {
-                    json_err
-                        .get("message")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or(&error_text)
-                        .to_string()
-                } else {
-                    error_text
-                };
-            Err(StorageError::ApiError(format!(
-                "Failed to move object: {} (Status: {})",
-                error_message, status
-            )))
+                .unwrap_or_else(|_| "Failed to read error body".to_string()),
+        );
+        // Try parsing Supabase error format
+        let error_message =
+            if let Ok(json_err) = serde_json::from_str::<serde_json::Value>(&error_text) {
+                json_err
+                    .get("message")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or(&error_text)
+                    .to_string()
+            } else {
+                error_text
+            };
+        StorageError::ApiError {
+            message: format!(
+                "Failed to {}: {} (Status: {})",
+                action, error_message, status
+            ),
+            status: Some(status.as_u16()),
+            request_ids,
         }
     }
 }
 
 // S3互換API用のモジュールを追加
 pub mod s3 {
-    use crate::{Result, StorageError};
+    mod get_object;
+    mod multipart;
+    mod sigv4;
+
+    pub use get_object::{GetObjectOptions, GetObjectOutput};
+    pub use multipart::{CompletedMultipartUpload, CompletedPart};
+
+    use crate::{Redacted, Result, StorageError};
     use bytes::Bytes;
+    use reqwest::header::HeaderMap;
     use reqwest::Client;
     use serde::{Deserialize, Serialize};
     use std::collections::HashMap;
+    use supabase_rust_core::{HeaderedClient, RequestIds, SendThroughGate};
 
     /// S3互換APIのオプション
     #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1026,7 +2387,7 @@ pub mod s3 {
         /// アクセスキー
         pub access_key_id: String,
         /// シークレットキー
-        pub secret_access_key: String,
+        pub secret_access_key: Redacted<String>,
         /// リージョン（デフォルトは「auto」）
         #[serde(skip_serializing_if = "Option::is_none")]
         pub region: Option<String>,
@@ -1042,7 +2403,7 @@ pub mod s3 {
         fn default() -> Self {
             Self {
                 access_key_id: String::new(),
-                secret_access_key: String::new(),
+                secret_access_key: Redacted::default(),
                 region: Some("auto".to_string()),
                 endpoint: None,
                 force_path_style: Some(true),
@@ -1054,8 +2415,8 @@ pub mod s3 {
     pub struct S3Client {
         pub options: S3Options,
         pub base_url: String,
-        pub api_key: String,
-        pub http_client: Client,
+        pub api_key: Redacted<String>,
+        pub http_client: HeaderedClient,
     }
 
     impl S3Client {
@@ -1064,11 +2425,28 @@ pub mod s3 {
             Self {
                 options,
                 base_url: base_url.to_string(),
-                api_key: api_key.to_string(),
-                http_client,
+                api_key: api_key.into(),
+                http_client: http_client.into(),
             }
         }
 
+        /// すべてのリクエストに付けるヘッダー (`bucket()` のクライアントにも適用する)
+        pub fn with_default_headers(mut self, headers: HeaderMap) -> Self {
+            self.http_client = self.http_client.with_default_headers(headers);
+            self
+        }
+
+        /// エラー本文から API キー、シークレットキー、Bearer トークンを取り除く
+        fn scrub_secrets(&self, text: &str) -> String {
+            supabase_rust_core::scrub(
+                text,
+                &[
+                    self.api_key.expose(),
+                    self.options.secret_access_key.expose(),
+                ],
+            )
+        }
+
         /// バケットの作成
         pub async fn create_bucket(&self, bucket_name: &str, is_public: bool) -> Result<()> {
             let url = format!("{}/storage/v1/bucket", self.base_url);
@@ -1083,19 +2461,27 @@ pub mod s3 {
             let response = self
                 .http_client
                 .post(&url)
-                .header("apikey", &self.api_key)
-                .header("Authorization", format!("Bearer {}", &self.api_key))
+                .header("apikey", self.api_key.expose())
+                .header("Authorization", format!("Bearer {}", self.api_key.expose()))
                 .json(&payload)
-                .send()
+                .send_through(self.http_client.send_gate())
                 .await
                 .map_err(|e| StorageError::RequestError(e.to_string()))?;
 
             if !response.status().is_success() {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(StorageError::ApiError(error_text));
+                let status = response.status();
+                let request_ids = Box::new(RequestIds::from_response(&response));
+                let error_text = self.scrub_secrets(
+                    &response
+                        .text()
+                        .await
+                        .unwrap_or_else(|_| "Unknown error".to_string()),
+                );
+                return Err(StorageError::ApiError {
+                    message: error_text,
+                    status: Some(status.as_u16()),
+                    request_ids,
+                });
             }
 
             Ok(())
@@ -1108,18 +2494,26 @@ pub mod s3 {
             let response = self
                 .http_client
                 .delete(&url)
-                .header("apikey", &self.api_key)
-                .header("Authorization", format!("Bearer {}", &self.api_key))
-                .send()
+                .header("apikey", self.api_key.expose())
+                .header("Authorization", format!("Bearer {}", self.api_key.expose()))
+                .send_through(self.http_client.send_gate())
                 .await
                 .map_err(|e| StorageError::RequestError(e.to_string()))?;
 
             if !response.status().is_success() {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(StorageError::ApiError(error_text));
+                let status = response.status();
+                let request_ids = Box::new(RequestIds::from_response(&response));
+                let error_text = self.scrub_secrets(
+                    &response
+                        .text()
+                        .await
+                        .unwrap_or_else(|_| "Unknown error".to_string()),
+                );
+                return Err(StorageError::ApiError {
+                    message: error_text,
+                    status: Some(status.as_u16()),
+                    request_ids,
+                });
             }
 
             Ok(())
@@ -1132,18 +2526,26 @@ pub mod s3 {
             let response = self
                 .http_client
                 .get(&url)
-                .header("apikey", &self.api_key)
-                .header("Authorization", format!("Bearer {}", &self.api_key))
-                .send()
+                .header("apikey", self.api_key.expose())
+                .header("Authorization", format!("Bearer {}", self.api_key.expose()))
+                .send_through(self.http_client.send_gate())
                 .await
                 .map_err(|e| StorageError::RequestError(e.to_string()))?;
 
             if !response.status().is_success() {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(StorageError::ApiError(error_text));
+                let status = response.status();
+                let request_ids = Box::new(RequestIds::from_response(&response));
+                let error_text = self.scrub_secrets(
+                    &response
+                        .text()
+                        .await
+                        .unwrap_or_else(|_| "Unknown error".to_string()),
+                );
+                return Err(StorageError::ApiError {
+                    message: error_text,
+                    status: Some(status.as_u16()),
+                    request_ids,
+                });
             }
 
             let buckets = response
@@ -1156,22 +2558,25 @@ pub mod s3 {
 
         /// バケットを取得し、S3互換操作のためのクライアントを返す
         pub fn bucket(&self, bucket_name: &str) -> S3BucketClient {
-            S3BucketClient::new(
+            let mut client = S3BucketClient::new(
                 &self.base_url,
-                &self.api_key,
+                self.api_key.expose(),
                 bucket_name,
-                self.http_client.clone(),
+                self.http_client.client().clone(),
                 self.options.clone(),
-            )
+            );
+            // 既定のヘッダーと送信のゲートを引き継ぐ
+            client.http_client = self.http_client.clone();
+            client
         }
     }
 
     /// S3バケット操作用クライアント
     pub struct S3BucketClient {
         pub base_url: String,
-        pub api_key: String,
+        pub api_key: Redacted<String>,
         pub bucket_name: String,
-        pub http_client: Client,
+        pub http_client: HeaderedClient,
         pub options: S3Options,
     }
 
@@ -1186,21 +2591,41 @@ pub mod s3 {
         ) -> Self {
             Self {
                 base_url: base_url.to_string(),
-                api_key: api_key.to_string(),
+                api_key: api_key.into(),
                 bucket_name: bucket_name.to_string(),
-                http_client,
+                http_client: http_client.into(),
                 options,
             }
         }
 
+        /// すべてのリクエストに付けるヘッダー (マルチテナント構成の `x-tenant-id` など)
+        pub fn with_default_headers(mut self, headers: HeaderMap) -> Self {
+            self.http_client = self.http_client.with_default_headers(headers);
+            self
+        }
+
+        /// エラー本文から API キー、シークレットキー、Bearer トークンを取り除く
+        fn scrub_secrets(&self, text: &str) -> String {
+            supabase_rust_core::scrub(
+                text,
+                &[
+                    self.api_key.expose(),
+                    self.options.secret_access_key.expose(),
+                ],
+            )
+        }
+
         /// オブジェクトをアップロード（S3互換API）
+        ///
+        /// `data` は [`IntoUploadBody`](crate::IntoUploadBody) を実装した任意のアップロード元。
         pub async fn put_object(
             &self,
             path: &str,
-            data: Bytes,
+            data: impl crate::IntoUploadBody,
             content_type: Option<String>,
             metadata: Option<HashMap<String, String>>,
         ) -> Result<()> {
+            let body = data.into_upload_body().await?;
             let url = format!(
                 "{}/storage/v1/object/{}/{}",
                 self.base_url,
@@ -1208,14 +2633,18 @@ pub mod s3 {
                 path.trim_start_matches('/')
             );
 
-            let content_type =
-                content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+            // 指定がなければ拡張子、アップロード元のヒントの順に推測
+            let content_type = content_type
+                .or_else(|| crate::guess_content_type(path).map(str::to_string))
+                .or_else(|| body.content_type().map(str::to_string))
+                .unwrap_or_else(|| crate::DEFAULT_CONTENT_TYPE.to_string());
+            let data = body.into_bytes().await?;
 
             let mut request = self
                 .http_client
                 .put(&url)
-                .header("apikey", &self.api_key)
-                .header("Authorization", format!("Bearer {}", &self.api_key))
+                .header("apikey", self.api_key.expose())
+                .header("Authorization", format!("Bearer {}", self.api_key.expose()))
                 .header("Content-Type", content_type)
                 .body(data);
 
@@ -1227,16 +2656,24 @@ pub mod s3 {
             }
 
             let response = request
-                .send()
+                .send_through(self.http_client.send_gate())
                 .await
                 .map_err(|e| StorageError::RequestError(e.to_string()))?;
 
             if !response.status().is_success() {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(StorageError::ApiError(error_text));
+                let status = response.status();
+                let request_ids = Box::new(RequestIds::from_response(&response));
+                let error_text = self.scrub_secrets(
+                    &response
+                        .text()
+                        .await
+                        .unwrap_or_else(|_| "Unknown error".to_string()),
+                );
+                return Err(StorageError::ApiError {
+                    message: error_text,
+                    status: Some(status.as_u16()),
+                    request_ids,
+                });
             }
 
             Ok(())
@@ -1254,18 +2691,26 @@ pub mod s3 {
             let response = self
                 .http_client
                 .get(&url)
-                .header("apikey", &self.api_key)
-                .header("Authorization", format!("Bearer {}", &self.api_key))
-                .send()
+                .header("apikey", self.api_key.expose())
+                .header("Authorization", format!("Bearer {}", self.api_key.expose()))
+                .send_through(self.http_client.send_gate())
                 .await
                 .map_err(|e| StorageError::RequestError(e.to_string()))?;
 
             if !response.status().is_success() {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(StorageError::ApiError(error_text));
+                let status = response.status();
+                let request_ids = Box::new(RequestIds::from_response(&response));
+                let error_text = self.scrub_secrets(
+                    &response
+                        .text()
+                        .await
+                        .unwrap_or_else(|_| "Unknown error".to_string()),
+                );
+                return Err(StorageError::ApiError {
+                    message: error_text,
+                    status: Some(status.as_u16()),
+                    request_ids,
+                });
             }
 
             let data = response
@@ -1288,14 +2733,18 @@ pub mod s3 {
             let response = self
                 .http_client
                 .head(&url)
-                .header("apikey", &self.api_key)
-                .header("Authorization", format!("Bearer {}", &self.api_key))
-                .send()
+                .header("apikey", self.api_key.expose())
+                .header("Authorization", format!("Bearer {}", self.api_key.expose()))
+                .send_through(self.http_client.send_gate())
                 .await
                 .map_err(|e| StorageError::RequestError(e.to_string()))?;
 
             if !response.status().is_success() {
-                return Err(StorageError::ApiError("Object not found".to_string()));
+                return Err(StorageError::ApiError {
+                    message: "Object not found".to_string(),
+                    status: Some(response.status().as_u16()),
+                    request_ids: Box::new(RequestIds::from_response(&response)),
+                });
             }
 
             let mut metadata = HashMap::new();
@@ -1324,18 +2773,26 @@ pub mod s3 {
             let response = self
                 .http_client
                 .delete(&url)
-                .header("apikey", &self.api_key)
-                .header("Authorization", format!("Bearer {}", &self.api_key))
-                .send()
+                .header("apikey", self.api_key.expose())
+                .header("Authorization", format!("Bearer {}", self.api_key.expose()))
+                .send_through(self.http_client.send_gate())
                 .await
                 .map_err(|e| StorageError::RequestError(e.to_string()))?;
 
             if !response.status().is_success() {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(StorageError::ApiError(error_text));
+                let status = response.status();
+                let request_ids = Box::new(RequestIds::from_response(&response));
+                let error_text = self.scrub_secrets(
+                    &response
+                        .text()
+                        .await
+                        .unwrap_or_else(|_| "Unknown error".to_string()),
+                );
+                return Err(StorageError::ApiError {
+                    message: error_text,
+                    status: Some(status.as_u16()),
+                    request_ids,
+                });
             }
 
             Ok(())
@@ -1375,18 +2832,26 @@ pub mod s3 {
             let response = self
                 .http_client
                 .get(&url)
-                .header("apikey", &self.api_key)
-                .header("Authorization", format!("Bearer {}", &self.api_key))
-                .send()
+                .header("apikey", self.api_key.expose())
+                .header("Authorization", format!("Bearer {}", self.api_key.expose()))
+                .send_through(self.http_client.send_gate())
                 .await
                 .map_err(|e| StorageError::RequestError(e.to_string()))?;
 
             if !response.status().is_success() {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(StorageError::ApiError(error_text));
+                let status = response.status();
+                let request_ids = Box::new(RequestIds::from_response(&response));
+                let error_text = self.scrub_secrets(
+                    &response
+                        .text()
+                        .await
+                        .unwrap_or_else(|_| "Unknown error".to_string()),
+                );
+                return Err(StorageError::ApiError {
+                    message: error_text,
+                    status: Some(status.as_u16()),
+                    request_ids,
+                });
             }
 
             let objects = response
@@ -1410,19 +2875,27 @@ pub mod s3 {
             let response = self
                 .http_client
                 .post(&url)
-                .header("apikey", &self.api_key)
-                .header("Authorization", format!("Bearer {}", &self.api_key))
+                .header("apikey", self.api_key.expose())
+                .header("Authorization", format!("Bearer {}", self.api_key.expose()))
                 .json(&payload)
-                .send()
+                .send_through(self.http_client.send_gate())
                 .await
                 .map_err(|e| StorageError::RequestError(e.to_string()))?;
 
             if !response.status().is_success() {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                return Err(StorageError::ApiError(error_text));
+                let status = response.status();
+                let request_ids = Box::new(RequestIds::from_response(&response));
+                let error_text = self.scrub_secrets(
+                    &response
+                        .text()
+                        .await
+                        .unwrap_or_else(|_| "Unknown error".to_string()),
+                );
+                return Err(StorageError::ApiError {
+                    message: error_text,
+                    status: Some(status.as_u16()),
+                    request_ids,
+                });
             }
 
             Ok(())
@@ -1434,7 +2907,8 @@ pub mod s3 {
 mod tests {
     use super::*;
     use serde_json::json;
-    use wiremock::matchers::{method, path};
+    use tokio::io::AsyncReadExt;
+    use wiremock::matchers::{body_json, header, method, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     #[tokio::test]
@@ -1494,7 +2968,7 @@ mod tests {
         // list_buckets を呼び出し、エラーになることを確認
         let result = storage_client.list_buckets().await;
         assert!(result.is_err());
-        if let Err(StorageError::ApiError(msg)) = result {
+        if let Err(StorageError::ApiError { message: msg, .. }) = result {
             assert!(msg.contains("Unauthorized"));
         } else {
             panic!("Expected ApiError, got {:?}", result);
@@ -1502,8 +2976,219 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_create_bucket() {
-        // モックサーバーを起動
+    async fn test_request_ids_are_sent_and_attached_to_errors() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/storage/v1/bucket/photos"))
+            .and(wiremock::matchers::header_exists("x-client-request-id"))
+            .respond_with(
+                ResponseTemplate::new(409)
+                    .insert_header("sb-request-id", "sb-storage-1")
+                    .insert_header("cf-ray", "8a1b2c3d-NRT")
+                    .set_body_json(json!({ "message": "Bucket not empty" })),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let storage_client =
+            StorageClient::new(&mock_server.uri(), "fake-key", reqwest::Client::new());
+        let error = storage_client.delete_bucket("photos").await.unwrap_err();
+
+        let sent = mock_server.received_requests().await.unwrap();
+        let client_request_id = sent[0].headers.get(&"x-client-request-id".into()).unwrap()[0]
+            .as_str()
+            .to_string();
+        let StorageError::ApiError { request_ids, .. } = &error else {
+            panic!("Expected ApiError, got {:?}", error);
+        };
+        assert_eq!(
+            request_ids.client_request_id.as_deref(),
+            Some(client_request_id.as_str())
+        );
+        assert_eq!(request_ids.sb_request_id.as_deref(), Some("sb-storage-1"));
+        assert_eq!(request_ids.cf_ray.as_deref(), Some("8a1b2c3d-NRT"));
+        assert!(error.to_string().contains(&client_request_id));
+    }
+
+    #[allow(deprecated)]
+    #[tokio::test]
+    async fn test_error_messages_never_contain_secrets() {
+        const SECRET: &str = "service-role-secret-0123456789";
+        let mock_server = MockServer::start().await;
+        // 一部のプロキシは Authorization ヘッダーをそのまま本文に含めて返す
+        let reflected = format!(
+            r#"{{"message":"invalid key {}","headers":{{"authorization":"Bearer {}"}}}}"#,
+            SECRET, SECRET
+        );
+        Mock::given(wiremock::matchers::any())
+            .respond_with(ResponseTemplate::new(401).set_body_string(reflected))
+            .mount(&mock_server)
+            .await;
+
+        let client = StorageClient::new(&mock_server.uri(), SECRET, reqwest::Client::new());
+        let bucket = client.from("avatars");
+        let s3_options = s3::S3Options {
+            secret_access_key: "s3-secret-access-key-0123".into(),
+            ..Default::default()
+        };
+        let s3_client = s3::S3Client::new(
+            &mock_server.uri(),
+            SECRET,
+            reqwest::Client::new(),
+            s3_options.clone(),
+        );
+        let s3_bucket = bucket.s3_compatible(s3_options);
+        let part_policy = PartRetryPolicy::none();
+
+        let errors = vec![
+            client.list_buckets().await.unwrap_err(),
+            client.create_bucket("b", false).await.unwrap_err(),
+            client.update_bucket("b", true).await.unwrap_err(),
+            client.delete_bucket("b").await.unwrap_err(),
+            bucket.download("a.png").await.unwrap_err(),
+            bucket
+                .upload_bytes("a.png", Bytes::from_static(b"png!"), None)
+                .await
+                .unwrap_err(),
+            bucket.list("", None).await.unwrap_err(),
+            bucket.remove(vec!["a.png"]).await.unwrap_err(),
+            bucket.create_signed_url("a.png", 60).await.unwrap_err(),
+            bucket
+                .initiate_multipart_upload("a.bin", None)
+                .await
+                .unwrap_err(),
+            bucket
+                .upload_part_with_retry("upload", 1, Bytes::from_static(b"x"), &part_policy)
+                .await
+                .unwrap_err(),
+            bucket
+                .complete_multipart_upload("upload", "a.bin", Vec::new())
+                .await
+                .unwrap_err(),
+            bucket
+                .abort_multipart_upload("upload", "a.bin")
+                .await
+                .unwrap_err(),
+            bucket
+                .transform_image("a.png", ImageTransformOptions::new().with_width(10))
+                .await
+                .unwrap_err(),
+            bucket.move_object("a.png", "b.png").await.unwrap_err(),
+            s3_client.list_buckets().await.unwrap_err(),
+            s3_client.create_bucket("b", false).await.unwrap_err(),
+            s3_client.delete_bucket("b").await.unwrap_err(),
+            s3_bucket.get_object("a.png").await.unwrap_err(),
+            s3_bucket.delete_object("a.png").await.unwrap_err(),
+            s3_bucket
+                .put_object("a.png", Bytes::from_static(b"png!"), None, None)
+                .await
+                .unwrap_err(),
+            s3_bucket.copy_object("a.png", "b.png").await.unwrap_err(),
+        ];
+
+        for error in errors {
+            for output in [error.to_string(), format!("{:?}", error)] {
+                assert!(!output.contains(SECRET), "secret leaked: {}", output);
+                assert!(
+                    !output.contains("s3-secret-access-key"),
+                    "secret leaked: {}",
+                    output
+                );
+            }
+        }
+        assert!(!format!("{:?}", s3_client.options).contains("s3-secret-access-key"));
+    }
+
+    // Storage API が実際に返すバケットのペイロード
+    fn real_bucket_payload() -> serde_json::Value {
+        json!({
+            "id": "avatars",
+            "name": "avatars",
+            "owner": "5f1f0c2a-0000-4000-8000-000000000001",
+            "public": true,
+            "file_size_limit": 5242880,
+            "allowed_mime_types": ["image/png", "image/jpeg"],
+            "created_at": "2024-03-01T12:34:56.789Z",
+            "updated_at": "2024-03-02T08:00:00.000Z"
+        })
+    }
+
+    #[tokio::test]
+    async fn test_list_buckets_with_options() {
+        let mock_server = MockServer::start().await;
+        let mut other = real_bucket_payload();
+        other["id"] = json!("documents");
+        other["owner"] = json!("someone-else");
+        other["file_size_limit"] = json!(null);
+        other["allowed_mime_types"] = json!(null);
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/bucket"))
+            .and(query_param("limit", "2"))
+            .and(query_param("offset", "2"))
+            .and(query_param("search", "a"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!([real_bucket_payload(), other])),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let storage_client =
+            StorageClient::new(&mock_server.uri(), "fake-key", reqwest::Client::new());
+        let buckets = storage_client
+            .list_buckets_with_options(
+                ListBucketsOptions::new()
+                    .page(Page::new(2, 2))
+                    .search("a")
+                    .owner("5f1f0c2a-0000-4000-8000-000000000001"),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].id, "avatars");
+        assert_eq!(buckets[0].file_size_limit, Some(5242880));
+        assert_eq!(
+            buckets[0].allowed_mime_types.as_deref(),
+            Some(&["image/png".to_string(), "image/jpeg".to_string()][..])
+        );
+    }
+
+    #[cfg(not(feature = "chrono"))]
+    #[test]
+    fn test_bucket_timestamps_are_strings_without_chrono() {
+        let bucket: Bucket = serde_json::from_value(real_bucket_payload()).unwrap();
+        assert_eq!(bucket.created_at, "2024-03-01T12:34:56.789Z");
+        assert_eq!(bucket.updated_at, "2024-03-02T08:00:00.000Z");
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_bucket_timestamps_parse_with_chrono() {
+        use chrono::{TimeZone, Utc};
+
+        let bucket: Bucket = serde_json::from_value(real_bucket_payload()).unwrap();
+        assert_eq!(
+            bucket.created_at,
+            Utc.with_ymd_and_hms(2024, 3, 1, 12, 34, 56).unwrap()
+                + chrono::Duration::milliseconds(789)
+        );
+        assert!(bucket.updated_at > bucket.created_at);
+
+        // 後方互換: 旧形式 (file_size_limit などなし) もパースできる
+        let legacy: Bucket = serde_json::from_value(json!({
+            "id": "b", "name": "b", "owner": "", "public": false,
+            "created_at": "2024-01-01T00:00:00Z", "updated_at": "2024-01-01T00:00:00+00:00"
+        }))
+        .unwrap();
+        assert_eq!(legacy.file_size_limit, None);
+        assert_eq!(legacy.created_at, legacy.updated_at);
+    }
+
+    #[tokio::test]
+    async fn test_create_bucket() {
+        // モックサーバーを起動
         let mock_server = MockServer::start().await;
 
         // --- 成功ケースのモック ---
@@ -1553,7 +3238,7 @@ mod tests {
         // create_bucket を呼び出し、エラーになることを確認
         let result = storage_client.create_bucket(bucket_id, true).await;
         assert!(result.is_err());
-        if let Err(StorageError::ApiError(msg)) = result {
+        if let Err(StorageError::ApiError { message: msg, .. }) = result {
             assert!(msg.contains("Bucket already exists"));
         } else {
             panic!("Expected ApiError, got {:?}", result);
@@ -1599,7 +3284,7 @@ mod tests {
         // delete_bucket を呼び出し、エラーになることを確認
         let result = storage_client.delete_bucket(bucket_id).await;
         assert!(result.is_err());
-        if let Err(StorageError::ApiError(msg)) = result {
+        if let Err(StorageError::ApiError { message: msg, .. }) = result {
             assert!(msg.contains("Bucket not found"));
         } else {
             panic!("Expected ApiError, got {:?}", result);
@@ -1662,7 +3347,7 @@ mod tests {
             .update_bucket(bucket_id, updated_public_status)
             .await;
         assert!(result.is_err());
-        if let Err(StorageError::ApiError(msg)) = result {
+        if let Err(StorageError::ApiError { message: msg, .. }) = result {
             assert!(msg.contains("Bucket not found for update"));
         } else {
             panic!("Expected ApiError, got {:?}", result);
@@ -1736,7 +3421,7 @@ mod tests {
         // upload を呼び出し、エラーになることを確認
         let result = bucket_client.upload(object_path, &file_path, None).await;
         assert!(result.is_err());
-        if let Err(StorageError::ApiError(msg)) = result {
+        if let Err(StorageError::ApiError { message: msg, .. }) = result {
             assert!(msg.contains("Invalid upload parameters"));
         } else {
             panic!("Expected ApiError, got {:?}", result);
@@ -1792,7 +3477,7 @@ mod tests {
         // download を呼び出し、エラーになることを確認
         let result = bucket_client.download(object_path).await;
         assert!(result.is_err());
-        if let Err(StorageError::ApiError(msg)) = result {
+        if let Err(StorageError::ApiError { message: msg, .. }) = result {
             assert!(msg.contains("File not found"));
         } else {
             panic!("Expected ApiError, got {:?}", result);
@@ -1886,7 +3571,7 @@ mod tests {
         // list を呼び出し、エラーになることを確認
         let result = bucket_client.list(prefix, Some(ListOptions::new())).await; // エラーケース用のオプション
         assert!(result.is_err());
-        if let Err(StorageError::ApiError(msg)) = result {
+        if let Err(StorageError::ApiError { message: msg, .. }) = result {
             assert!(msg.contains("Invalid list parameters"));
         } else {
             panic!("Expected ApiError, got {:?}", result);
@@ -1937,7 +3622,7 @@ mod tests {
         let paths_for_error = vec!["file_a.txt", "folder/file_b.log"]; // エラーケース用
         let result = bucket_client.remove(paths_for_error).await;
         assert!(result.is_err());
-        if let Err(StorageError::ApiError(msg)) = result {
+        if let Err(StorageError::ApiError { message: msg, .. }) = result {
             assert!(msg.contains("Invalid paths provided"));
         } else {
             panic!("Expected ApiError, got {:?}", result);
@@ -2014,7 +3699,7 @@ mod tests {
             .create_signed_url(object_path, expires_in)
             .await;
         assert!(result.is_err());
-        if let Err(StorageError::ApiError(msg)) = result {
+        if let Err(StorageError::ApiError { message: msg, .. }) = result {
             assert!(msg.contains("Object not found"));
         } else {
             panic!("Expected ApiError, got {:?}", result);
@@ -2157,6 +3842,372 @@ mod tests {
         assert_eq!(file_object.size, file_content.len() as i64);
     }
 
+    fn complete_response(object_path: &str, bucket_id: &str, size: usize) -> serde_json::Value {
+        json!({
+            "name": object_path,
+            "bucket_id": bucket_id,
+            "owner": "owner-uuid",
+            "id": "file-id-multi-complete",
+            "updated_at": "2024-01-07T00:00:00Z",
+            "created_at": "2024-01-07T00:00:00Z",
+            "last_accessed_at": "2024-01-07T00:00:00Z",
+            "metadata": { "size": size, "mimetype": "application/octet-stream" },
+            "size": size,
+            "mime_type": "application/octet-stream",
+        })
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_retries_transient_part_failure() {
+        let mock_server = MockServer::start().await;
+        let bucket_id = "multipart-bucket";
+        let object_path = "retry.dat";
+        let file_content = b"0123456789abcdef";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join(object_path);
+        tokio::fs::write(&file_path, file_content).await.unwrap();
+        let upload_id = "retry-upload-id";
+
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/initiate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "file-id",
+                "uploadId": upload_id,
+                "key": object_path,
+                "bucket": bucket_id
+            })))
+            .mount(&mock_server)
+            .await;
+
+        // 2つ目のチャンクは最初の1回だけ 503 を返す
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/part"))
+            .and(wiremock::matchers::query_param("partNumber", "2"))
+            .respond_with(ResponseTemplate::new(503).set_body_string("unavailable"))
+            .up_to_n_times(1)
+            .with_priority(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        // "89abcdef" の MD5
+        let part2_md5 = BASE64.encode(Md5::digest(b"89abcdef"));
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/part"))
+            .and(wiremock::matchers::query_param("partNumber", "2"))
+            .and(wiremock::matchers::header(
+                "Content-MD5",
+                part2_md5.as_str(),
+            ))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "etag-2"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/part"))
+            .and(wiremock::matchers::query_param("partNumber", "1"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "etag-1"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/complete"))
+            .and(wiremock::matchers::body_json(json!({
+                "uploadId": upload_id,
+                "parts": [
+                    { "partNumber": 1, "etag": "etag-1" },
+                    { "partNumber": 2, "etag": "etag-2" }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(complete_response(
+                object_path,
+                bucket_id,
+                file_content.len(),
+            )))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let retry = PartRetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        };
+        let file_object = storage_client
+            .from(bucket_id)
+            .upload_large_file_with_retry(object_path, &file_path, 8, None, retry)
+            .await
+            .unwrap();
+        assert_eq!(file_object.name, object_path);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_aborts_after_retries_exhausted() {
+        let mock_server = MockServer::start().await;
+        let bucket_id = "multipart-bucket";
+        let object_path = "failing.dat";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join(object_path);
+        tokio::fs::write(&file_path, b"0123456789").await.unwrap();
+        let upload_id = "failing-upload-id";
+
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/initiate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "file-id",
+                "uploadId": upload_id,
+                "key": object_path,
+                "bucket": bucket_id
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/part"))
+            .and(wiremock::matchers::query_param("partNumber", "1"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "etag-1"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/part"))
+            .and(wiremock::matchers::query_param("partNumber", "2"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/abort"))
+            .and(wiremock::matchers::body_json(json!({
+                "uploadId": upload_id,
+                "bucket": bucket_id,
+                "key": object_path
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let retry = PartRetryPolicy {
+            max_attempts: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        };
+        let result = storage_client
+            .from(bucket_id)
+            .upload_large_file_with_retry(object_path, &file_path, 6, None, retry)
+            .await;
+        match result {
+            Err(StorageError::MultipartPartFailed {
+                part_number,
+                bytes_uploaded,
+                ..
+            }) => {
+                assert_eq!(part_number, 2);
+                assert_eq!(bytes_uploaded, 6);
+            }
+            other => panic!("Expected MultipartPartFailed, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_large_file_upload_skips_uploaded_parts() {
+        let mock_server = MockServer::start().await;
+        let bucket_id = "multipart-bucket";
+        let object_path = "resume.dat";
+        let file_content = b"aaaabbbbccccdd";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join(object_path);
+        tokio::fs::write(&file_path, file_content).await.unwrap();
+        let upload_id = "resume-upload-id";
+
+        // 4つ目のチャンクだけがアップロードされる
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/part"))
+            .and(wiremock::matchers::query_param("uploadId", upload_id))
+            .and(wiremock::matchers::query_param("partNumber", "4"))
+            .and(wiremock::matchers::header(
+                "Content-MD5",
+                BASE64.encode(Md5::digest(b"dd")).as_str(),
+            ))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "etag-4"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/complete"))
+            .and(wiremock::matchers::body_json(json!({
+                "uploadId": upload_id,
+                "parts": [
+                    { "partNumber": 1, "etag": "etag-1" },
+                    { "partNumber": 2, "etag": "etag-2" },
+                    { "partNumber": 3, "etag": "etag-3" },
+                    { "partNumber": 4, "etag": "etag-4" }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(complete_response(
+                object_path,
+                bucket_id,
+                file_content.len(),
+            )))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let already_uploaded = [3, 1, 2]
+            .iter()
+            .map(|n| UploadedPartInfo {
+                part_number: *n,
+                etag: format!("etag-{}", n),
+                checksum: None,
+            })
+            .collect();
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let file_object = storage_client
+            .from(bucket_id)
+            .resume_large_file_upload(
+                upload_id,
+                object_path,
+                &file_path,
+                4,
+                already_uploaded,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(file_object.size, file_content.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn test_list_paged() {
+        let mock_server = MockServer::start().await;
+        let file = |name: &str| {
+            json!({
+                "name": name,
+                "bucket_id": "docs",
+                "owner": "owner-uuid",
+                "id": name,
+                "updated_at": "2024-01-07T00:00:00Z",
+                "created_at": "2024-01-07T00:00:00Z",
+                "last_accessed_at": "2024-01-07T00:00:00Z",
+                "metadata": {},
+                "mime_type": "text/plain",
+                "size": 1
+            })
+        };
+        // ページサイズ 2 の 2 ページ目 => limit=3 (1件多く取得), offset=2
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/docs"))
+            .and(wiremock::matchers::query_param("limit", "3"))
+            .and(wiremock::matchers::query_param("offset", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                file("c.txt"),
+                file("d.txt"),
+                file("e.txt")
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let paged = storage_client
+            .from("docs")
+            .list_paged("", Page::new(2, 2), None)
+            .await
+            .unwrap();
+        assert_eq!(paged.items.len(), 2);
+        assert_eq!(paged.items[1].name, "d.txt");
+        assert_eq!(paged.total, None);
+        assert_eq!(paged.has_more, Some(true));
+
+        let options = ListOptions::new().page(Page::new(3, 10));
+        assert_eq!(options.limit, Some(10));
+        assert_eq!(options.offset, Some(20));
+    }
+
+    #[test]
+    fn test_guess_content_type() {
+        assert_eq!(guess_content_type("images/logo.png"), Some("image/png"));
+        assert_eq!(guess_content_type("LOGO.PNG"), Some("image/png"));
+        assert_eq!(
+            guess_content_type("data/config.json"),
+            Some("application/json")
+        );
+        assert_eq!(
+            guess_content_type("backups/archive.tar.gz"),
+            Some("application/gzip")
+        );
+        assert_eq!(guess_content_type("file.unknownext"), None);
+        assert_eq!(guess_content_type("no_extension"), None);
+        assert_eq!(guess_content_type(".env"), None);
+
+        let options = FileOptions::new();
+        assert_eq!(
+            options.resolve_content_type("blob.bin"),
+            DEFAULT_CONTENT_TYPE
+        );
+        let options = FileOptions::new().with_content_type("text/plain");
+        assert_eq!(options.resolve_content_type("logo.png"), "text/plain");
+    }
+
+    #[allow(deprecated)]
+    #[tokio::test]
+    async fn test_upload_bytes_detects_content_type() {
+        let mock_server = MockServer::start().await;
+        let bucket_id = "images";
+        let object_path = "avatars/me.png";
+
+        Mock::given(method("POST"))
+            .and(path(format!(
+                "/storage/v1/object/{}/{}",
+                bucket_id, object_path
+            )))
+            .and(wiremock::matchers::body_string_contains(
+                "Content-Type: image/png",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "name": object_path,
+                "bucket_id": bucket_id,
+                "owner": "owner-uuid",
+                "id": "file-id",
+                "updated_at": "2024-01-05T00:00:00Z",
+                "created_at": "2024-01-05T00:00:00Z",
+                "last_accessed_at": "2024-01-05T00:00:00Z",
+                "metadata": null,
+                "mime_type": null,
+                "size": 4
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // S3 互換 API でも同じ推測が使われる
+        Mock::given(method("PUT"))
+            .and(path(format!("/storage/v1/object/{}/data.json", bucket_id)))
+            .and(wiremock::matchers::header(
+                "Content-Type",
+                "application/json",
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let bucket_client = storage_client.from(bucket_id);
+        let file_object = bucket_client
+            .upload_bytes(object_path, Bytes::from_static(b"png!"), None)
+            .await
+            .unwrap();
+        assert_eq!(file_object.mime_type.as_deref(), Some("image/png"));
+
+        bucket_client
+            .s3_compatible(s3::S3Options::default())
+            .put_object("data.json", Bytes::from_static(b"{}"), None, None)
+            .await
+            .unwrap();
+    }
+
     #[tokio::test]
     async fn test_transform_image() {
         // モックサーバーを起動
@@ -2166,8 +4217,8 @@ mod tests {
         let transform_options = ImageTransformOptions::new()
             .with_width(100)
             .with_height(100)
-            .with_resize("contain")
-            .with_format("webp");
+            .with_resize_mode(ResizeMode::Contain)
+            .with_image_format(ImageFormat::Webp);
         let expected_image_bytes = Bytes::from_static(b"transformed_image_data");
 
         // クライアントを作成
@@ -2217,7 +4268,7 @@ mod tests {
             .transform_image(object_path, transform_options) // transform_options を再利用
             .await;
         assert!(result.is_err());
-        if let Err(StorageError::ApiError(msg)) = result {
+        if let Err(StorageError::ApiError { message: msg, .. }) = result {
             assert!(msg.contains("Invalid transform options") || msg.contains("BadRequest"));
         } else {
             panic!("Expected ApiError, got {:?}", result);
@@ -2235,9 +4286,10 @@ mod tests {
         let transform_options = ImageTransformOptions::new()
             .with_width(50)
             .with_height(50)
-            .with_resize("cover")
-            .with_format("jpeg")
-            .with_quality(80);
+            .with_resize_mode(ResizeMode::Cover)
+            .with_image_format(ImageFormat::Jpeg)
+            .try_with_quality(80)
+            .unwrap();
 
         // クライアントを作成
         let http_client = reqwest::Client::new();
@@ -2314,7 +4366,7 @@ mod tests {
             .create_signed_transform_url(object_path, transform_options, expires_in) // transform_options を再利用
             .await;
         assert!(result.is_err());
-        if let Err(StorageError::ApiError(msg)) = result {
+        if let Err(StorageError::ApiError { message: msg, .. }) = result {
             // エラーメッセージはAPIの実装により異なる可能性がある
             assert!(
                 msg.contains("Invalid transform parameters") || msg.contains("BadRequest") // statusCode や error フィールドを含むか確認
@@ -2323,4 +4375,616 @@ mod tests {
             panic!("Expected ApiError, got {:?}", result);
         }
     }
+
+    #[test]
+    fn test_image_transform_query_params_empty() {
+        let options = ImageTransformOptions::new();
+        assert_eq!(options.to_query_params(), "");
+
+        let storage_client = StorageClient::new("http://localhost", "key", Client::new());
+        let url = storage_client
+            .from("images")
+            .get_public_transform_url("a.png", options);
+        assert!(!url.contains('?'));
+    }
+
+    #[test]
+    fn test_image_transform_query_params_all_combinations() {
+        for mask in 0u8..32 {
+            let mut options = ImageTransformOptions::new();
+            let mut expected = Vec::new();
+            if mask & 1 != 0 {
+                options = options.with_width(120);
+                expected.push("width=120");
+            }
+            if mask & 2 != 0 {
+                options = options.with_height(80);
+                expected.push("height=80");
+            }
+            if mask & 4 != 0 {
+                options = options.with_resize_mode(ResizeMode::Fill);
+                expected.push("resize=fill");
+            }
+            if mask & 8 != 0 {
+                options = options.with_image_format(ImageFormat::Avif);
+                expected.push("format=avif");
+            }
+            if mask & 16 != 0 {
+                options = options.try_with_quality(20).unwrap();
+                expected.push("quality=20");
+            }
+            assert_eq!(
+                options.to_query_params(),
+                expected.join("&"),
+                "mask {}",
+                mask
+            );
+        }
+    }
+
+    #[test]
+    fn test_image_transform_string_setters() {
+        let options = ImageTransformOptions::new()
+            .try_with_resize(" Contain")
+            .unwrap()
+            .try_with_format("jpg ")
+            .unwrap();
+        assert_eq!(options.resize, Some(ResizeMode::Contain));
+        assert_eq!(options.format, Some(ImageFormat::Jpeg));
+        assert_eq!(options.to_query_params(), "resize=contain&format=jpeg");
+
+        assert!(matches!(
+            ImageTransformOptions::new().try_with_resize("convert"),
+            Err(StorageError::InvalidTransformOptions(_))
+        ));
+        assert!(matches!(
+            ImageTransformOptions::new().try_with_format("gif"),
+            Err(StorageError::InvalidTransformOptions(_))
+        ));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_image_transform_setters_ignore_bad_input() {
+        let options = ImageTransformOptions::new()
+            .with_resize("fill")
+            .with_format("webp")
+            .with_quality(150);
+        assert_eq!(
+            options.to_query_params(),
+            "resize=fill&format=webp&quality=100"
+        );
+
+        // 解釈できない値は無視し、それまでの設定を残す
+        let options = options.with_resize("convert").with_format("gif");
+        assert_eq!(options.resize, Some(ResizeMode::Fill));
+        assert_eq!(options.format, Some(ImageFormat::Webp));
+        assert_eq!(
+            ImageTransformOptions::new().with_quality(0).quality,
+            Some(20)
+        );
+    }
+
+    #[test]
+    fn test_image_transform_quality_range() {
+        assert!(ImageTransformOptions::new().try_with_quality(0).is_err());
+        assert!(ImageTransformOptions::new().try_with_quality(19).is_err());
+        assert!(ImageTransformOptions::new().try_with_quality(101).is_err());
+        assert_eq!(
+            ImageTransformOptions::new()
+                .try_with_quality(100)
+                .unwrap()
+                .quality,
+            Some(100)
+        );
+    }
+
+    #[test]
+    fn test_image_transform_enum_serde() {
+        assert_eq!(serde_json::to_value(ResizeMode::Cover).unwrap(), "cover");
+        assert_eq!(serde_json::to_value(ImageFormat::Webp).unwrap(), "webp");
+        assert_eq!(ImageFormat::Origin.to_string(), "origin");
+    }
+
+    #[tokio::test]
+    async fn test_download_verified_matching_etag() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/docs/report.txt"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("etag", "\"5d41402abc4b2a76b9719d911017c592\"")
+                    .set_body_bytes(b"hello".to_vec()),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let result = storage_client
+            .from("docs")
+            .download_verified("report.txt")
+            .await
+            .unwrap();
+
+        assert_eq!(result.data, Bytes::from_static(b"hello"));
+        assert_eq!(
+            result.etag.as_deref(),
+            Some("5d41402abc4b2a76b9719d911017c592")
+        );
+        assert!(result.verified);
+    }
+
+    #[tokio::test]
+    async fn test_download_verified_etag_mismatch() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/docs/report.txt"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("etag", "\"00000000000000000000000000000000\"")
+                    .set_body_bytes(b"hello".to_vec()),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let bucket = storage_client.from("docs");
+
+        match bucket.download_verified("report.txt").await {
+            Err(StorageError::IntegrityError { expected, actual }) => {
+                assert_eq!(expected, "00000000000000000000000000000000");
+                assert_eq!(actual, "5d41402abc4b2a76b9719d911017c592");
+            }
+            other => panic!("Expected IntegrityError, got {:?}", other),
+        }
+
+        // ストリーミング保存でも検証に失敗したファイルは残さない
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("report.txt");
+        let result = bucket
+            .download_to_file("report.txt", &destination, None)
+            .await;
+        assert!(matches!(result, Err(StorageError::IntegrityError { .. })));
+        assert!(!destination.exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_verified_truncated_body() {
+        // content-length より短いボディを返して切断するサーバー
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await;
+            socket
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\nConnection: close\r\n\r\nshort",
+                )
+                .await
+                .unwrap();
+        });
+
+        let storage_client =
+            StorageClient::new(&format!("http://{}", addr), "fake-key", Client::new());
+        match storage_client
+            .from("docs")
+            .download_verified("report.txt")
+            .await
+        {
+            Err(StorageError::IntegrityError { expected, actual }) => {
+                assert_eq!(expected, "100 bytes");
+                assert_eq!(actual, "5 bytes");
+            }
+            other => panic!("Expected IntegrityError, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_multipart_etag_and_file() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/docs/large.bin"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("etag", "\"9b2cf535f27731c974343645a3985328-2\"")
+                    .set_body_bytes(b"multipart body".to_vec()),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/docs/report.txt"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("etag", "\"5d41402abc4b2a76b9719d911017c592\"")
+                    .set_body_bytes(b"hello".to_vec()),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let bucket = storage_client.from("docs");
+
+        let result = bucket.download_verified("large.bin").await.unwrap();
+        assert!(!result.verified);
+
+        let strict = VerifyOptions {
+            skip_multipart_etag: false,
+        };
+        let result = bucket
+            .download_verified_with_options("large.bin", strict)
+            .await;
+        assert!(matches!(result, Err(StorageError::IntegrityError { .. })));
+
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("report.txt");
+        let download = bucket
+            .download_to_file("report.txt", &destination, None)
+            .await
+            .unwrap();
+        assert_eq!(download.size, 5);
+        assert!(download.verified);
+        assert_eq!(std::fs::read(&destination).unwrap(), b"hello");
+    }
+
+    fn archived_file_object() -> serde_json::Value {
+        json!({
+            "name": "reports/q1.pdf",
+            "bucket_id": "docs",
+            "owner": "owner-uuid",
+            "id": "file-id",
+            "updated_at": "2024-02-01T00:00:00Z",
+            "created_at": "2024-01-01T00:00:00Z",
+            "last_accessed_at": "2024-02-01T00:00:00Z",
+            "metadata": { "cacheControl": "no-cache", "mimetype": "application/pdf", "size": 10 },
+            "mime_type": "application/pdf",
+            "size": 10
+        })
+    }
+
+    #[tokio::test]
+    async fn test_update_metadata() {
+        let mock_server = MockServer::start().await;
+
+        // content_type は指定しないので現在の値を HEAD で取得する
+        Mock::given(method("HEAD"))
+            .and(path("/storage/v1/object/docs/reports/q1.pdf"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Cache-Control", "max-age=3600")
+                    .insert_header("Content-Type", "application/pdf"),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let expected_metadata = BASE64.encode(br#"{"status":"archived"}"#);
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/object/copy"))
+            .and(header("x-upsert", "true"))
+            .and(header("x-metadata", expected_metadata.as_str()))
+            .and(body_json(json!({
+                "bucketId": "docs",
+                "sourceKey": "reports/q1.pdf",
+                "destinationKey": "reports/q1.pdf",
+                "copyMetadata": false,
+                "metadata": { "cacheControl": "no-cache", "mimetype": "application/pdf" }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(archived_file_object()))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let storage = StorageClient::new(&mock_server.uri(), "test-key", Client::new());
+        let options = UpdateMetadataOptions::new()
+            .with_cache_control("no-cache")
+            .with_metadata("status", "archived");
+        let file = storage
+            .from("docs")
+            .update_metadata("reports/q1.pdf", options)
+            .await
+            .unwrap();
+
+        assert_eq!(file.name, "reports/q1.pdf");
+        assert_eq!(file.mime_type.as_deref(), Some("application/pdf"));
+        assert_eq!(file.metadata.unwrap()["cacheControl"], "no-cache");
+    }
+
+    #[tokio::test]
+    async fn test_update_metadata_passes_through_server_error() {
+        let mock_server = MockServer::start().await;
+
+        // cache_control と content_type を両方指定した場合は HEAD しない
+        Mock::given(method("HEAD"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/object/copy"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "statusCode": "400",
+                "error": "invalid_header",
+                "message": "Invalid x-metadata header"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let storage = StorageClient::new(&mock_server.uri(), "test-key", Client::new());
+        let options = UpdateMetadataOptions::new()
+            .with_cache_control("no-cache")
+            .with_content_type("application/pdf");
+        let error = storage
+            .from("docs")
+            .update_metadata("reports/q1.pdf", options)
+            .await
+            .unwrap_err();
+
+        let StorageError::ApiError { message, .. } = error else {
+            panic!("expected ApiError, got {:?}", error);
+        };
+        assert!(message.contains("Invalid x-metadata header"), "{}", message);
+        assert!(message.contains("400"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_default_headers_reach_bucket_and_s3_clients() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/bucket"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/avatars/a.png"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"png".to_vec()))
+            .mount(&mock_server)
+            .await;
+
+        let mut tenant = HeaderMap::new();
+        tenant.insert("x-tenant-id", "tenant-a".parse().unwrap());
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new())
+            .with_default_headers(tenant);
+        storage_client.list_buckets().await.unwrap();
+        let bucket = storage_client.from("avatars");
+        bucket.download("a.png").await.unwrap();
+        bucket
+            .s3_compatible(s3::S3Options::default())
+            .get_object("a.png")
+            .await
+            .unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        assert_eq!(requests.len(), 3);
+        for request in requests {
+            assert_eq!(
+                request.headers.get(&"x-tenant-id".into()).unwrap()[0].as_str(),
+                "tenant-a",
+                "{}",
+                request.url
+            );
+        }
+    }
+
+    fn uploaded_object(path: &str) -> serde_json::Value {
+        json!({
+            "name": path,
+            "bucket_id": "docs",
+            "owner": "owner-uuid",
+            "id": "file-id",
+            "updated_at": "2024-01-05T00:00:00Z",
+            "created_at": "2024-01-05T00:00:00Z",
+            "last_accessed_at": "2024-01-05T00:00:00Z",
+            "metadata": {},
+            "size": 5
+        })
+    }
+
+    #[tokio::test]
+    async fn test_upload_modes_against_existing_objects() {
+        // 古いサーバーは 400 の本文に statusCode "409" を入れて返す
+        let duplicate_400 = || {
+            ResponseTemplate::new(400).set_body_json(json!({
+                "statusCode": "409",
+                "error": "Duplicate",
+                "message": "The resource already exists"
+            }))
+        };
+        let created = || ResponseTemplate::new(200).set_body_json(uploaded_object("a.txt"));
+        let cases = [
+            (UploadMode::Overwrite, "true", created(), true),
+            (UploadMode::Fail, "false", created(), true),
+            (UploadMode::Fail, "false", duplicate_response(), false),
+            (UploadMode::Fail, "false", duplicate_400(), false),
+            (UploadMode::FailIfExists, "false", created(), true),
+            (
+                UploadMode::FailIfExists,
+                "false",
+                duplicate_response(),
+                false,
+            ),
+        ];
+
+        for (mode, x_upsert, response, succeeds) in cases {
+            let mock_server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path("/storage/v1/object/docs/a.txt"))
+                .and(header("x-upsert", x_upsert))
+                .respond_with(response)
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+            // FailIfExists の事前確認ではオブジェクトがない
+            Mock::given(method("HEAD"))
+                .and(path("/storage/v1/object/docs/a.txt"))
+                .respond_with(ResponseTemplate::new(404))
+                .expect(u64::from(mode == UploadMode::FailIfExists))
+                .mount(&mock_server)
+                .await;
+
+            let client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+            let result = client
+                .from("docs")
+                .upload(
+                    "a.txt",
+                    Bytes::from_static(b"hello"),
+                    Some(FileOptions::new().with_mode(mode)),
+                )
+                .await;
+            if succeeds {
+                assert!(result.is_ok(), "{:?}: {:?}", mode, result);
+            } else {
+                assert!(
+                    matches!(&result, Err(StorageError::AlreadyExists { path, .. }) if path == "a.txt"),
+                    "{:?}: {:?}",
+                    mode,
+                    result
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fail_if_exists_checks_before_sending() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/storage/v1/object/docs/big.bin"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let bucket = client.from("docs");
+        let options = FileOptions::new().with_mode(UploadMode::FailIfExists);
+        let result = bucket
+            .upload(
+                "big.bin",
+                Bytes::from_static(b"hello"),
+                Some(options.clone()),
+            )
+            .await;
+        assert!(matches!(result, Err(StorageError::AlreadyExists { .. })));
+        let result = bucket
+            .upload_large_file("big.bin", Bytes::from_static(b"hello"), 2, Some(options))
+            .await;
+        assert!(matches!(result, Err(StorageError::AlreadyExists { .. })));
+        // HEAD のほかは何も送っていない
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fail_if_exists_reports_other_head_errors() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/storage/v1/object/docs/a.txt"))
+            .respond_with(ResponseTemplate::new(403))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let result = client
+            .from("docs")
+            .upload(
+                "a.txt",
+                Bytes::from_static(b"hello"),
+                Some(FileOptions::new().with_mode(UploadMode::FailIfExists)),
+            )
+            .await;
+        // 有無が分からないのでアップロードせずにエラーにする
+        assert!(
+            matches!(
+                result,
+                Err(StorageError::ApiError {
+                    status: Some(403),
+                    ..
+                })
+            ),
+            "{:?}",
+            result
+        );
+        assert_eq!(mock_server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_upsert_maps_onto_mode() {
+        let options = FileOptions {
+            upsert: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(options.upload_mode(), UploadMode::Overwrite);
+        let options = FileOptions {
+            mode: UploadMode::Overwrite,
+            upsert: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(options.upload_mode(), UploadMode::Fail);
+        assert_eq!(
+            FileOptions::new().with_upsert(true).upload_mode(),
+            UploadMode::Overwrite
+        );
+        // 後から呼んだ方が優先
+        assert_eq!(
+            FileOptions::new()
+                .with_upsert(true)
+                .with_mode(UploadMode::FailIfExists)
+                .upload_mode(),
+            UploadMode::FailIfExists
+        );
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_sends_mode_and_maps_conflicts() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/initiate"))
+            .and(body_json(json!({
+                "bucket": "docs",
+                "name": "big.bin",
+                "cacheControl": "max-age=3600",
+                "contentType": "application/octet-stream",
+                "upsert": true
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "file-id",
+                "uploadId": "upload-1",
+                "key": "big.bin",
+                "bucket": "docs"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/complete"))
+            .respond_with(duplicate_response())
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let bucket = client.from("docs");
+        let options = FileOptions::new().with_mode(UploadMode::Overwrite);
+        let initiated = bucket
+            .initiate_multipart_upload("big.bin", Some(options))
+            .await
+            .unwrap();
+        let result = bucket
+            .complete_multipart_upload(&initiated.upload_id, "big.bin", Vec::new())
+            .await;
+        assert!(
+            matches!(&result, Err(StorageError::AlreadyExists { path, request_ids }) if path == "big.bin" && request_ids.client_request_id.is_some()),
+            "{:?}",
+            result
+        );
+    }
+
+    fn duplicate_response() -> ResponseTemplate {
+        ResponseTemplate::new(409).set_body_json(json!({ "error": "Duplicate" }))
+    }
 }
@@ -0,0 +1,424 @@
+//! 保持期間を過ぎたオブジェクトの削除
+//!
+//! [`StorageBucketClient::purge_older_than`] はプレフィックス以下を再帰的に一覧し、
+//! 最終更新時刻 (`updated_at`、なければ `created_at`) が期限より古いオブジェクトをまとめて削除する。
+//! タイムスタンプを解釈できないオブジェクトは削除せず [`PurgeReport::skipped`] に入れる。
+
+use crate::directory::{join_key, ListEntry};
+use crate::{FailedTransfer, Result, StorageBucketClient, StorageError};
+use futures_util::stream::{self, StreamExt};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// `purge_older_than` のオプション
+#[derive(Debug, Clone, Copy)]
+pub struct PurgeOptions {
+    /// 削除せずに対象だけを報告する
+    pub dry_run: bool,
+    /// 1回の削除リクエストに含めるオブジェクト数
+    pub batch_size: usize,
+    /// 同時に送る削除リクエスト数の上限
+    pub concurrency: usize,
+}
+
+impl Default for PurgeOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            batch_size: 1000,
+            concurrency: 4,
+        }
+    }
+}
+
+impl PurgeOptions {
+    /// デフォルトのオプションを作成
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+}
+
+/// 削除対象のオブジェクト
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PurgeCandidate {
+    /// オブジェクトのキー
+    pub path: String,
+    /// 判定に使ったタイムスタンプ (API が返した文字列のまま)
+    pub modified_at: String,
+    /// サイズ (一覧に含まれていない場合は None)
+    pub size: Option<u64>,
+}
+
+/// `purge_older_than` の結果
+#[derive(Debug, Default)]
+pub struct PurgeReport {
+    /// 期限より古いオブジェクト (キー順)
+    pub candidates: Vec<PurgeCandidate>,
+    /// 削除したオブジェクトのキー (dry run では空)
+    pub deleted: Vec<String>,
+    pub failed: Vec<FailedTransfer>,
+    /// タイムスタンプがない、または解釈できなかったオブジェクトのキー
+    pub skipped: Vec<String>,
+    /// 解放したバイト数 (dry run では解放される予定のバイト数、サイズ不明のものは含まない)
+    pub bytes_freed: u64,
+    pub dry_run: bool,
+}
+
+impl PurgeReport {
+    /// 削除に失敗したオブジェクトがなければ true
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl<'a> StorageBucketClient<'a> {
+    /// `prefix` 以下で `max_age` より前に更新されたオブジェクトを削除
+    pub async fn purge_older_than(
+        &self,
+        prefix: &str,
+        max_age: Duration,
+        options: PurgeOptions,
+    ) -> Result<PurgeReport> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let cutoff = now.as_millis() as i64 - max_age.as_millis() as i64;
+
+        let mut report = PurgeReport {
+            dry_run: options.dry_run,
+            ..Default::default()
+        };
+        for (path, entry) in self.list_entries_recursive(prefix).await? {
+            let path = join_key(prefix, &path);
+            let Some(modified_at) = entry
+                .updated_at
+                .as_ref()
+                .or(entry.created_at.as_ref())
+                .cloned()
+            else {
+                report.skipped.push(path);
+                continue;
+            };
+            match parse_timestamp_millis(&modified_at) {
+                Some(modified) if modified < cutoff => report.candidates.push(PurgeCandidate {
+                    path,
+                    modified_at,
+                    size: entry_size(&entry),
+                }),
+                Some(_) => {}
+                None => report.skipped.push(path),
+            }
+        }
+
+        if options.dry_run {
+            report.bytes_freed = report.candidates.iter().filter_map(|c| c.size).sum();
+            return Ok(report);
+        }
+
+        let mut removals = stream::iter(report.candidates.chunks(options.batch_size.max(1)))
+            .map(|batch| async move {
+                let result = self
+                    .remove(batch.iter().map(|c| c.path.as_str()).collect())
+                    .await;
+                (batch, result)
+            })
+            .buffer_unordered(options.concurrency.max(1));
+        let mut deleted = Vec::new();
+        let mut failed = Vec::new();
+        let mut bytes_freed = 0;
+        while let Some((batch, result)) = removals.next().await {
+            match result {
+                Ok(()) => {
+                    bytes_freed += batch.iter().filter_map(|c| c.size).sum::<u64>();
+                    deleted.extend(batch.iter().map(|c| c.path.clone()));
+                }
+                Err(error) => {
+                    for candidate in batch {
+                        failed.push(FailedTransfer {
+                            path: candidate.path.clone(),
+                            error: StorageError::new(format!("Failed to delete: {}", error)),
+                        });
+                    }
+                }
+            }
+        }
+        drop(removals);
+
+        deleted.sort();
+        failed.sort_by(|a, b| a.path.cmp(&b.path));
+        report.deleted = deleted;
+        report.failed = failed;
+        report.bytes_freed = bytes_freed;
+        Ok(report)
+    }
+}
+
+// Storage API は `metadata.size` にサイズを入れる
+fn entry_size(entry: &ListEntry) -> Option<u64> {
+    entry
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.get("size"))
+        .and_then(|size| size.as_u64())
+        .or(entry.size)
+}
+
+/// ISO 8601 のタイムスタンプを UNIX 時刻 (ミリ秒) に変換
+///
+/// 小数秒の有無、`Z` / `+09:00` / `+0900` のオフセット、`T` の代わりの空白を受け付ける。
+/// オフセットがない場合は UTC とみなす。
+pub(crate) fn parse_timestamp_millis(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let date = value.get(..10)?;
+    if !matches!(value.get(10..11)?, "T" | "t" | " ") {
+        return None;
+    }
+    let time_and_offset = value.get(11..)?;
+
+    let mut date_parts = date.split('-');
+    let year: i64 = parse_digits(date_parts.next()?, 4)?;
+    let month = parse_digits(date_parts.next()?, 2)?;
+    let day = parse_digits(date_parts.next()?, 2)?;
+    if date_parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let (time, offset_minutes) = if let Some(time) = time_and_offset
+        .strip_suffix('Z')
+        .or_else(|| time_and_offset.strip_suffix('z'))
+    {
+        (time, 0)
+    } else if let Some(position) = time_and_offset.rfind(['+', '-']) {
+        let (time, offset) = time_and_offset.split_at(position);
+        (time, parse_offset_minutes(offset)?)
+    } else {
+        (time_and_offset, 0)
+    };
+
+    let (hms, fraction) = time.split_once('.').unwrap_or((time, ""));
+    let mut hms_parts = hms.split(':');
+    let hour = parse_digits(hms_parts.next()?, 2)?;
+    let minute = parse_digits(hms_parts.next()?, 2)?;
+    let second = parse_digits(hms_parts.next()?, 2)?;
+    if hms_parts.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    if time.contains('.') && (fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return None;
+    }
+    let millis: i64 = format!("{:0<3}", &fraction[..fraction.len().min(3)])
+        .parse()
+        .ok()?;
+
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second
+        - offset_minutes * 60;
+    Some(seconds * 1_000 + millis)
+}
+
+fn parse_digits(value: &str, len: usize) -> Option<i64> {
+    if value.len() != len || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+// `+09:00` / `-0500` / `+09` を分に変換
+fn parse_offset_minutes(offset: &str) -> Option<i64> {
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let digits = offset[1..].replace(':', "");
+    let (hours, minutes) = match digits.len() {
+        2 => (parse_digits(&digits, 2)?, 0),
+        4 => (
+            parse_digits(&digits[..2], 2)?,
+            parse_digits(&digits[2..], 2)?,
+        ),
+        _ => return None,
+    };
+    Some(sign * (hours * 60 + minutes))
+}
+
+// 1970-01-01 からの日数 (proleptic Gregorian)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StorageClient;
+    use reqwest::Client;
+    use serde_json::json;
+    use wiremock::matchers::{body_json, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const DAY: Duration = Duration::from_secs(86_400);
+
+    fn object(name: &str, updated_at: &str, size: u64) -> serde_json::Value {
+        json!({
+            "name": name,
+            "id": format!("id-{}", name),
+            "updated_at": updated_at,
+            "created_at": updated_at,
+            "last_accessed_at": updated_at,
+            "metadata": { "size": size, "mimetype": "application/json" },
+        })
+    }
+
+    #[test]
+    fn test_parse_timestamp_millis() {
+        assert_eq!(parse_timestamp_millis("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(
+            parse_timestamp_millis("2024-03-01T12:34:56Z"),
+            Some(1_709_296_496_000)
+        );
+        assert_eq!(
+            parse_timestamp_millis("2024-03-01T12:34:56.789123Z"),
+            Some(1_709_296_496_789)
+        );
+        assert_eq!(
+            parse_timestamp_millis("2024-03-01T21:34:56.7+09:00"),
+            Some(1_709_296_496_700)
+        );
+        assert_eq!(
+            parse_timestamp_millis("2024-03-01 07:34:56-0500"),
+            Some(1_709_296_496_000)
+        );
+        assert_eq!(
+            parse_timestamp_millis("2024-03-01T12:34:56"),
+            Some(1_709_296_496_000)
+        );
+        for invalid in [
+            "",
+            "yesterday",
+            "2024-13-01T00:00:00Z",
+            "2024-03-01T12:34Z",
+            "2024-03-01T12:34:56.Z",
+        ] {
+            assert_eq!(parse_timestamp_millis(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_purge_older_than() {
+        let mock_server = MockServer::start().await;
+        // 1ページ目は新しいオブジェクトだけで埋まっている
+        let first_page: Vec<_> = (0..1000)
+            .map(|i| {
+                object(
+                    &format!("fresh-{:04}.json", i),
+                    "2099-01-01T00:00:00.000000Z",
+                    1,
+                )
+            })
+            .collect();
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/exports"))
+            .and(query_param("prefix", "tmp"))
+            .and(query_param("offset", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(first_page))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/exports"))
+            .and(query_param("prefix", "tmp"))
+            .and(query_param("offset", "1000"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                object("a.json", "2020-01-01T00:00:00Z", 10),
+                object("b.json", "2020-01-01T00:00:00.123456+00:00", 20),
+                object("c.json", "2020-01-01 00:00:00", 30),
+                object("broken.json", "not a date", 40),
+                { "name": "nested", "id": null, "metadata": null },
+            ])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/exports"))
+            .and(query_param("prefix", "tmp/nested"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([object(
+                "d.json",
+                "2021-06-30T23:59:59.5Z",
+                40
+            )])))
+            .mount(&mock_server)
+            .await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let bucket = storage_client.from("exports");
+
+        // dry run では何も削除しない
+        let delete = Mock::given(method("DELETE"))
+            .and(path("/storage/v1/object/exports"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount_as_scoped(&mock_server)
+            .await;
+        let report = bucket
+            .purge_older_than("tmp", 30 * DAY, PurgeOptions::new().with_dry_run(true))
+            .await
+            .unwrap();
+        drop(delete);
+        assert!(report.dry_run);
+        let candidates: Vec<_> = report.candidates.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(
+            candidates,
+            vec![
+                "tmp/a.json",
+                "tmp/b.json",
+                "tmp/c.json",
+                "tmp/nested/d.json"
+            ]
+        );
+        assert_eq!(report.skipped, vec!["tmp/broken.json".to_string()]);
+        assert!(report.deleted.is_empty());
+        assert_eq!(report.bytes_freed, 100);
+
+        Mock::given(method("DELETE"))
+            .and(path("/storage/v1/object/exports"))
+            .and(body_json(
+                json!({ "prefixes": ["tmp/a.json", "tmp/b.json", "tmp/c.json"] }),
+            ))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/storage/v1/object/exports"))
+            .and(body_json(json!({ "prefixes": ["tmp/nested/d.json"] })))
+            .respond_with(ResponseTemplate::new(500).set_body_string("boom"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let report = bucket
+            .purge_older_than("tmp", 30 * DAY, PurgeOptions::new().with_batch_size(3))
+            .await
+            .unwrap();
+        assert!(!report.is_success());
+        assert_eq!(
+            report.deleted,
+            vec!["tmp/a.json", "tmp/b.json", "tmp/c.json"]
+        );
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].path, "tmp/nested/d.json");
+        assert_eq!(report.bytes_freed, 60);
+    }
+}
@@ -0,0 +1,333 @@
+//! 単一ファイルのアップロード / ダウンロードの進捗通知
+//!
+//! [`StorageBucketClient::with_progress`] を指定すると、`upload` / `download` / `download_to_file`
+//! が送受信した本文のバイト数を [`TransferProgress`] で通知する。本文はバッファせず、
+//! リクエスト / レスポンスのストリームを包んで流れたバイト数を数える。
+//! 完了時には最後の値 (全体のバイト数) を必ず通知し、完了後やエラー後には通知しない。
+
+use crate::{StorageBucketClient, UploadBody};
+use bytes::Bytes;
+use futures_util::Stream;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// 進捗を受け取るコールバック
+pub type ProgressHandler = Arc<dyn Fn(TransferProgress) + Send + Sync>;
+
+/// 転送の向き
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+/// 転送の進捗
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferProgress {
+    pub bytes_transferred: u64,
+    /// ファイルサイズ (アップロード) や Content-Length (ダウンロード) から分かる場合のみ
+    pub total_bytes: Option<u64>,
+    pub direction: TransferDirection,
+}
+
+/// 進捗を通知する間隔 (どちらかに達したら通知する)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressGranularity {
+    pub bytes: u64,
+    pub interval: Duration,
+}
+
+impl Default for ProgressGranularity {
+    fn default() -> Self {
+        Self {
+            bytes: 64 * 1024,
+            interval: Duration::from_millis(100),
+        }
+    }
+}
+
+// アップロード時にストリームから読む大きさ
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+pub(crate) struct ProgressTracker {
+    handler: ProgressHandler,
+    granularity: ProgressGranularity,
+    direction: TransferDirection,
+    total: Option<u64>,
+    transferred: u64,
+    reported: Option<u64>,
+    last_report: Instant,
+    finished: bool,
+}
+
+impl ProgressTracker {
+    pub(crate) fn advance(&mut self, len: usize) {
+        if self.finished {
+            return;
+        }
+        self.transferred += len as u64;
+        let unreported = self.transferred - self.reported.unwrap_or(0);
+        if unreported >= self.granularity.bytes
+            || self.last_report.elapsed() >= self.granularity.interval
+        {
+            self.report();
+        }
+    }
+
+    // 最後の値をまだ通知していなければ通知し、以降は通知しない
+    pub(crate) fn complete(&mut self) {
+        if self.finished {
+            return;
+        }
+        if self.reported != Some(self.transferred) {
+            self.report();
+        }
+        self.finished = true;
+    }
+
+    pub(crate) fn fail(&mut self) {
+        self.finished = true;
+    }
+
+    fn report(&mut self) {
+        self.reported = Some(self.transferred);
+        self.last_report = Instant::now();
+        (self.handler)(TransferProgress {
+            bytes_transferred: self.transferred,
+            total_bytes: self.total,
+            direction: self.direction,
+        });
+    }
+}
+
+pub(crate) type SharedTracker = Arc<Mutex<ProgressTracker>>;
+
+pub(crate) fn lock(tracker: &SharedTracker) -> std::sync::MutexGuard<'_, ProgressTracker> {
+    tracker
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// `reqwest::Body::wrap_stream` は `Sync` を要求するため、ストリームを Mutex で包む
+struct SyncStream<S>(Mutex<Pin<Box<S>>>);
+
+impl<S: Stream> Stream for SyncStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut()
+            .0
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .as_mut()
+            .poll_next(cx)
+    }
+}
+
+// アップロード本文を読みながら進捗を数えるリクエスト本文
+pub(crate) fn upload_stream(body: UploadBody, tracker: SharedTracker) -> reqwest::Body {
+    let stream = futures_util::stream::try_unfold((body, tracker), |(mut body, tracker)| async {
+        let chunk: Bytes = body.read_chunk(UPLOAD_CHUNK_SIZE).await?;
+        if chunk.is_empty() {
+            return Ok::<_, io::Error>(None);
+        }
+        lock(&tracker).advance(chunk.len());
+        Ok(Some((chunk, (body, tracker))))
+    });
+    reqwest::Body::wrap_stream(SyncStream(Mutex::new(Box::pin(stream))))
+}
+
+impl StorageBucketClient<'_> {
+    /// `upload` / `download` / `download_to_file` の進捗を `handler` に通知する
+    ///
+    /// 既定では 64 KiB ごと、または 100ms ごとのどちらか早い方で通知する
+    /// (`with_progress_granularity` で変更できる)。
+    pub fn with_progress<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(TransferProgress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(handler));
+        self
+    }
+
+    /// 進捗を通知する間隔を設定
+    pub fn with_progress_granularity(mut self, bytes: u64, interval: Duration) -> Self {
+        self.progress_granularity = ProgressGranularity { bytes, interval };
+        self
+    }
+
+    pub(crate) fn progress_tracker(
+        &self,
+        direction: TransferDirection,
+        total: Option<u64>,
+    ) -> Option<ProgressTracker> {
+        self.progress.as_ref().map(|handler| ProgressTracker {
+            handler: handler.clone(),
+            granularity: self.progress_granularity,
+            direction,
+            total,
+            transferred: 0,
+            reported: None,
+            last_report: Instant::now(),
+            finished: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StorageClient;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const SIZE: usize = 200 * 1024;
+
+    fn recorder() -> (
+        Arc<Mutex<Vec<TransferProgress>>>,
+        impl Fn(TransferProgress) + Send + Sync + 'static,
+    ) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        (seen, move |progress| sink.lock().unwrap().push(progress))
+    }
+
+    fn assert_progress(
+        seen: &[TransferProgress],
+        direction: TransferDirection,
+        total: Option<u64>,
+    ) {
+        assert!(!seen.is_empty());
+        assert!(seen
+            .windows(2)
+            .all(|pair| pair[0].bytes_transferred < pair[1].bytes_transferred));
+        assert_eq!(seen.last().unwrap().bytes_transferred, SIZE as u64);
+        assert!(seen
+            .iter()
+            .all(|progress| progress.direction == direction && progress.total_bytes == total));
+    }
+
+    #[tokio::test]
+    async fn test_upload_reports_progress_per_chunk() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/object/media/video.bin"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "name": "video.bin",
+                "bucket_id": "media",
+                "owner": "owner-uuid",
+                "id": "file-id",
+                "updated_at": "2024-01-05T00:00:00Z",
+                "created_at": "2024-01-05T00:00:00Z",
+                "last_accessed_at": "2024-01-05T00:00:00Z",
+                "metadata": null,
+                "mime_type": null,
+                "size": SIZE
+            })))
+            .mount(&mock_server)
+            .await;
+        let storage_client =
+            StorageClient::new(&mock_server.uri(), "fake-key", reqwest::Client::new());
+        let data = (0..SIZE).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+        let (seen, handler) = recorder();
+        storage_client
+            .from("media")
+            .with_progress(handler)
+            .with_progress_granularity(64 * 1024, Duration::from_secs(3600))
+            .upload("video.bin", data.clone(), None)
+            .await
+            .unwrap();
+
+        let seen = seen.lock().unwrap().clone();
+        assert_progress(&seen, TransferDirection::Upload, Some(SIZE as u64));
+        assert_eq!(
+            seen.iter()
+                .map(|progress| progress.bytes_transferred / 1024)
+                .collect::<Vec<_>>(),
+            [64, 128, 192, 200]
+        );
+        let request = &mock_server.received_requests().await.unwrap()[0];
+        assert!(request
+            .body
+            .windows(data.len())
+            .any(|window| window == data.as_slice()));
+
+        // ストリームで長さが分からない場合は total_bytes が None
+        let (seen, handler) = recorder();
+        let stream = futures_util::stream::iter(
+            data.chunks(50 * 1024)
+                .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+                .collect::<Vec<_>>(),
+        );
+        storage_client
+            .from("media")
+            .with_progress(handler)
+            .upload("video.bin", UploadBody::from_stream(stream), None)
+            .await
+            .unwrap();
+        assert_progress(&seen.lock().unwrap(), TransferDirection::Upload, None);
+    }
+
+    #[tokio::test]
+    async fn test_download_reports_progress_until_content_length() {
+        let mock_server = MockServer::start().await;
+        let data = vec![7u8; SIZE];
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/media/video.bin"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(data.clone()))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/media/missing.bin"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("not found"))
+            .mount(&mock_server)
+            .await;
+        let storage_client =
+            StorageClient::new(&mock_server.uri(), "fake-key", reqwest::Client::new());
+
+        let (seen, handler) = recorder();
+        let downloaded = storage_client
+            .from("media")
+            .with_progress(handler)
+            .with_progress_granularity(16 * 1024, Duration::from_secs(3600))
+            .download("video.bin")
+            .await
+            .unwrap();
+        assert_eq!(downloaded.len(), SIZE);
+        assert_progress(
+            &seen.lock().unwrap(),
+            TransferDirection::Download,
+            Some(SIZE as u64),
+        );
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().join("video.bin");
+        let (seen, handler) = recorder();
+        storage_client
+            .from("media")
+            .with_progress(handler)
+            .download_to_file("video.bin", &destination, None)
+            .await
+            .unwrap();
+        assert_progress(
+            &seen.lock().unwrap(),
+            TransferDirection::Download,
+            Some(SIZE as u64),
+        );
+
+        // エラーでは通知しない
+        let (seen, handler) = recorder();
+        assert!(storage_client
+            .from("media")
+            .with_progress(handler)
+            .download("missing.bin")
+            .await
+            .is_err());
+        assert!(seen.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+//! バイト範囲を指定したダウンロード (HTTP Range リクエスト)
+//!
+//! [`StorageBucketClient::download_range`] は `Range: bytes=start-end` を送り、
+//! 206 Partial Content をそのまま返す。`Range` を無視して 200 で全体を返すプロキシに対しては、
+//! 受け取った本文から要求範囲を切り出す。
+
+use crate::{Result, StorageBucketClient, StorageError};
+use bytes::Bytes;
+use reqwest::header::{CONTENT_RANGE, CONTENT_TYPE, RANGE};
+use reqwest::StatusCode;
+use std::ops::{Bound, RangeBounds};
+use supabase_rust_core::{RequestIds, SendThroughGate};
+
+/// 範囲ダウンロードの結果
+#[derive(Debug, Clone)]
+pub struct RangePart {
+    pub data: Bytes,
+    /// (先頭, 末尾 (含む), オブジェクト全体のサイズ (不明なら None))
+    pub content_range: (u64, u64, Option<u64>),
+    /// 206、または `Range` が無視された場合は 200
+    pub status: StatusCode,
+}
+
+impl<'a> StorageBucketClient<'a> {
+    /// ファイルの一部 (`range` のバイト範囲) をダウンロード
+    ///
+    /// `start..` のような終端なしの範囲はファイルの最後まで。範囲がファイルの外なら
+    /// `RangeNotSatisfiable`、サーバーが複数範囲 (`multipart/byteranges`) で応答した場合は
+    /// `InvalidRange` を返す。
+    pub async fn download_range(
+        &self,
+        path: &str,
+        range: impl RangeBounds<u64>,
+    ) -> Result<RangePart> {
+        let (start, end) = resolve_range(&range)?;
+        let header = match end {
+            Some(end) => format!("bytes={}-{}", start, end),
+            None => format!("bytes={}-", start),
+        };
+
+        let response = self
+            .object_request(path)?
+            .header(RANGE, header)
+            .send_through(self.parent.http_client.send_gate())
+            .await?;
+        let status = response.status();
+        match status {
+            StatusCode::PARTIAL_CONTENT => {
+                let headers = response.headers();
+                if headers
+                    .get(CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|value| value.starts_with("multipart/byteranges"))
+                {
+                    return Err(StorageError::InvalidRange(
+                        "multi-range responses (multipart/byteranges) are not supported"
+                            .to_string(),
+                    ));
+                }
+                let content_range = headers
+                    .get(CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_content_range)
+                    .ok_or_else(|| {
+                        StorageError::InvalidRange(
+                            "206 response without a valid Content-Range header".to_string(),
+                        )
+                    })?;
+                Ok(RangePart {
+                    data: response.bytes().await?,
+                    content_range,
+                    status,
+                })
+            }
+            StatusCode::RANGE_NOT_SATISFIABLE => Err(StorageError::RangeNotSatisfiable {
+                size: response
+                    .headers()
+                    .get(CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_unsatisfied_size),
+            }),
+            status if status.is_success() => {
+                // `Range` が無視されたので全体から切り出す
+                let body = response.bytes().await?;
+                let size = body.len() as u64;
+                if start >= size {
+                    return Err(StorageError::RangeNotSatisfiable { size: Some(size) });
+                }
+                let last = end.map_or(size - 1, |end| end.min(size - 1));
+                Ok(RangePart {
+                    data: body.slice(start as usize..=last as usize),
+                    content_range: (start, last, Some(size)),
+                    status,
+                })
+            }
+            _ => {
+                let status = response.status();
+                let request_ids = Box::new(RequestIds::from_response(&response));
+                let error_text = self.parent.scrub_secrets(&response.text().await?);
+                Err(StorageError::ApiError {
+                    message: error_text,
+                    status: Some(status.as_u16()),
+                    request_ids,
+                })
+            }
+        }
+    }
+}
+
+// (先頭, 末尾 (含む、終端なしなら None))
+fn resolve_range(range: &impl RangeBounds<u64>) -> Result<(u64, Option<u64>)> {
+    let empty = || StorageError::InvalidRange("range is empty".to_string());
+    let start = match range.start_bound() {
+        Bound::Included(start) => *start,
+        Bound::Excluded(start) => start.checked_add(1).ok_or_else(empty)?,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(end) => Some(*end),
+        Bound::Excluded(end) => Some(end.checked_sub(1).ok_or_else(empty)?),
+        Bound::Unbounded => None,
+    };
+    if end.is_some_and(|end| end < start) {
+        return Err(empty());
+    }
+    Ok((start, end))
+}
+
+// `bytes 0-99/1234` または `bytes 0-99/*`
+pub(crate) fn parse_content_range(value: &str) -> Option<(u64, u64, Option<u64>)> {
+    let (range, size) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+    let size = match size.trim() {
+        "*" => None,
+        size => Some(size.parse().ok()?),
+    };
+    (start <= end).then_some((start, end, size))
+}
+
+// 416 の `bytes */1234`
+pub(crate) fn parse_unsatisfied_size(value: &str) -> Option<u64> {
+    value.trim().strip_prefix("bytes */")?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StorageClient;
+    use reqwest::Client;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const OBJECT_PATH: &str = "/storage/v1/object/media/song.mp3";
+
+    #[test]
+    fn test_resolve_and_parse_ranges() {
+        assert_eq!(resolve_range(&(10..20)).unwrap(), (10, Some(19)));
+        assert_eq!(resolve_range(&(10..=20)).unwrap(), (10, Some(20)));
+        assert_eq!(resolve_range(&(100..)).unwrap(), (100, None));
+        assert_eq!(resolve_range(&(..5)).unwrap(), (0, Some(4)));
+        assert!(matches!(
+            resolve_range(&(5..5)),
+            Err(StorageError::InvalidRange(_))
+        ));
+
+        assert_eq!(
+            parse_content_range("bytes 0-99/1234"),
+            Some((0, 99, Some(1234)))
+        );
+        assert_eq!(parse_content_range("bytes 5-9/*"), Some((5, 9, None)));
+        assert_eq!(parse_content_range("bytes 9-5/10"), None);
+        assert_eq!(parse_unsatisfied_size("bytes */1234"), Some(1234));
+    }
+
+    #[tokio::test]
+    async fn test_download_range_partial_content() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(OBJECT_PATH))
+            .and(header("range", "bytes=100-"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .insert_header("content-range", "bytes 100-104/105")
+                    .set_body_bytes(b"tail!".to_vec()),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(OBJECT_PATH))
+            .and(header("range", "bytes=0-9"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .set_body_raw("--x--", "multipart/byteranges; boundary=x"),
+            )
+            .mount(&server)
+            .await;
+
+        let client = StorageClient::new(&server.uri(), "test-key", Client::new());
+        let bucket = client.from("media");
+
+        let part = bucket.download_range("song.mp3", 100..).await.unwrap();
+        assert_eq!(part.status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(part.content_range, (100, 104, Some(105)));
+        assert_eq!(&part.data[..], b"tail!");
+
+        assert!(matches!(
+            bucket.download_range("song.mp3", 0..10).await,
+            Err(StorageError::InvalidRange(message)) if message.contains("multipart/byteranges")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_download_range_full_body_fallback() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(OBJECT_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"0123456789".to_vec()))
+            .mount(&server)
+            .await;
+
+        let client = StorageClient::new(&server.uri(), "test-key", Client::new());
+        let bucket = client.from("media");
+
+        let part = bucket.download_range("song.mp3", 2..=5).await.unwrap();
+        assert_eq!(part.status, StatusCode::OK);
+        assert_eq!(part.content_range, (2, 5, Some(10)));
+        assert_eq!(&part.data[..], b"2345");
+
+        // 終端がファイルを超える場合は最後まで
+        let part = bucket.download_range("song.mp3", 7..100).await.unwrap();
+        assert_eq!(part.content_range, (7, 9, Some(10)));
+        assert_eq!(&part.data[..], b"789");
+
+        assert!(matches!(
+            bucket.download_range("song.mp3", 10..).await,
+            Err(StorageError::RangeNotSatisfiable { size: Some(10) })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_download_range_not_satisfiable() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(OBJECT_PATH))
+            .respond_with(ResponseTemplate::new(416).insert_header("content-range", "bytes */1234"))
+            .mount(&server)
+            .await;
+
+        let client = StorageClient::new(&server.uri(), "test-key", Client::new());
+        let result = client
+            .from("media")
+            .download_range("song.mp3", 5000..)
+            .await;
+        assert!(matches!(
+            result,
+            Err(StorageError::RangeNotSatisfiable { size: Some(1234) })
+        ));
+    }
+}
@@ -0,0 +1,349 @@
+//! 条件付き・範囲指定のオブジェクト取得
+//!
+//! [`S3BucketClient::get_object_with`] は `If-None-Match` / `If-Modified-Since` と `Range` を送る。
+//! 304 Not Modified はエラーにせず本文なしの結果を返し、206 Partial Content では
+//! `Content-Range` を結果に含める。`ETag` などのヘッダーがない応答でも失敗しない。
+
+use super::S3BucketClient;
+use crate::range::{parse_content_range, parse_unsatisfied_size};
+use crate::{Result, StorageError};
+use bytes::Bytes;
+use reqwest::header::{
+    CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE,
+};
+use reqwest::StatusCode;
+use std::time::SystemTime;
+use supabase_rust_core::{RequestIds, SendThroughGate};
+
+/// [`S3BucketClient::get_object_with`] の設定
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GetObjectOptions {
+    /// (先頭, 末尾 (含む、None なら最後まで)) のバイト範囲
+    pub range: Option<(u64, Option<u64>)>,
+    /// この ETag と一致すれば 304 (前回の `GetObjectOutput::etag` を渡す)
+    pub if_none_match: Option<String>,
+    /// この日時以降に更新されていなければ 304
+    pub if_modified_since: Option<SystemTime>,
+}
+
+/// [`S3BucketClient::get_object_with`] の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetObjectOutput {
+    /// 本文 (304 Not Modified では None)
+    pub body: Option<Bytes>,
+    /// 200、206、304 のいずれか
+    pub status: StatusCode,
+    /// `ETag` ヘッダー (引用符を含む)
+    pub etag: Option<String>,
+    /// `Last-Modified` ヘッダー (ないか解釈できなければ None)
+    pub last_modified: Option<SystemTime>,
+    /// 206 の `Content-Range` (先頭, 末尾 (含む), 全体のサイズ (不明なら None))
+    pub content_range: Option<(u64, u64, Option<u64>)>,
+}
+
+impl GetObjectOutput {
+    /// 304 Not Modified (手元のコピーが最新) かどうか
+    pub fn is_not_modified(&self) -> bool {
+        self.status == StatusCode::NOT_MODIFIED
+    }
+}
+
+impl S3BucketClient {
+    /// 条件や範囲を指定してオブジェクトをダウンロード（S3互換API）
+    ///
+    /// 変更がなければ (`304`) `body: None` を返す。範囲を指定してもサーバーが `Range` を
+    /// 無視した場合は、200 と全体の本文 (`content_range: None`) になる。
+    pub async fn get_object_with(
+        &self,
+        path: &str,
+        options: GetObjectOptions,
+    ) -> Result<GetObjectOutput> {
+        let url = format!(
+            "{}/storage/v1/object/{}/{}",
+            self.base_url,
+            self.bucket_name,
+            path.trim_start_matches('/')
+        );
+
+        let mut request = self
+            .http_client
+            .get(&url)
+            .header("apikey", self.api_key.expose())
+            .header("Authorization", format!("Bearer {}", self.api_key.expose()));
+        if let Some((start, end)) = options.range {
+            if let Some(end) = end.filter(|end| *end < start) {
+                return Err(StorageError::InvalidRange(format!(
+                    "range end {} is before start {}",
+                    end, start
+                )));
+            }
+            let range = match end {
+                Some(end) => format!("bytes={}-{}", start, end),
+                None => format!("bytes={}-", start),
+            };
+            request = request.header(RANGE, range);
+        }
+        if let Some(etag) = &options.if_none_match {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(since) = options.if_modified_since {
+            request = request.header(IF_MODIFIED_SINCE, httpdate::fmt_http_date(since));
+        }
+
+        let response = request
+            .send_through(self.http_client.send_gate())
+            .await
+            .map_err(|e| StorageError::RequestError(e.to_string()))?;
+
+        let status = response.status();
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        if status == StatusCode::RANGE_NOT_SATISFIABLE {
+            return Err(StorageError::RangeNotSatisfiable {
+                size: header(CONTENT_RANGE)
+                    .as_deref()
+                    .and_then(parse_unsatisfied_size),
+            });
+        }
+        if status != StatusCode::NOT_MODIFIED && !status.is_success() {
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(
+                &response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Unknown error".to_string()),
+            );
+            return Err(StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            });
+        }
+
+        let etag = header(ETAG);
+        let last_modified =
+            header(LAST_MODIFIED).and_then(|value| httpdate::parse_http_date(value.trim()).ok());
+        let content_range = if status == StatusCode::PARTIAL_CONTENT {
+            header(CONTENT_RANGE)
+                .as_deref()
+                .and_then(parse_content_range)
+        } else {
+            None
+        };
+        let body = if status == StatusCode::NOT_MODIFIED {
+            None
+        } else {
+            Some(
+                response
+                    .bytes()
+                    .await
+                    .map_err(|e| StorageError::RequestError(e.to_string()))?,
+            )
+        };
+
+        Ok(GetObjectOutput {
+            body,
+            status,
+            etag,
+            last_modified,
+            content_range,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s3::S3Options;
+    use reqwest::Client;
+    use std::time::Duration;
+    use wiremock::matchers::{header, headers, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const OBJECT: &str = "/storage/v1/object/sync/data/file.bin";
+
+    fn bucket(base_url: &str) -> S3BucketClient {
+        S3BucketClient::new(
+            base_url,
+            "anon-key",
+            "sync",
+            Client::new(),
+            S3Options::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_not_modified_has_no_body() {
+        let server = MockServer::start().await;
+        let since = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        Mock::given(method("GET"))
+            .and(path(OBJECT))
+            .and(header("if-none-match", "\"abc\""))
+            // `header()` はカンマで値を分けるので、日付は分かれた値として比べる
+            .and(headers(
+                "if-modified-since",
+                vec!["Tue", "14 Nov 2023 22:13:20 GMT"],
+            ))
+            .respond_with(ResponseTemplate::new(304).insert_header("etag", "\"abc\""))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let output = bucket(&server.uri())
+            .get_object_with(
+                "data/file.bin",
+                GetObjectOptions {
+                    if_none_match: Some("\"abc\"".to_string()),
+                    if_modified_since: Some(since),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(output.is_not_modified());
+        assert_eq!(output.body, None);
+        assert_eq!(output.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(output.last_modified, None);
+        assert_eq!(output.content_range, None);
+    }
+
+    #[tokio::test]
+    async fn test_range_returns_partial_content() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(OBJECT))
+            .and(header("range", "bytes=100-104"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .insert_header("content-range", "bytes 100-104/5000")
+                    .insert_header("etag", "\"v2\"")
+                    .insert_header("last-modified", "Tue, 14 Nov 2023 22:13:20 GMT")
+                    .set_body_bytes(b"hello".to_vec()),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(OBJECT))
+            .and(header("range", "bytes=4000-"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .insert_header("content-range", "bytes 4000-4999/*")
+                    .set_body_bytes(vec![0u8; 1000]),
+            )
+            .mount(&server)
+            .await;
+
+        let client = bucket(&server.uri());
+        let output = client
+            .get_object_with(
+                "data/file.bin",
+                GetObjectOptions {
+                    range: Some((100, Some(104))),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(output.status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(output.body.as_deref(), Some(&b"hello"[..]));
+        assert_eq!(output.content_range, Some((100, 104, Some(5000))));
+        assert_eq!(output.etag.as_deref(), Some("\"v2\""));
+        assert_eq!(
+            output.last_modified,
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+        );
+
+        let tail = client
+            .get_object_with(
+                "data/file.bin",
+                GetObjectOptions {
+                    range: Some((4000, None)),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(tail.content_range, Some((4000, 4999, None)));
+        assert_eq!(tail.etag, None);
+
+        assert!(matches!(
+            client
+                .get_object_with(
+                    "data/file.bin",
+                    GetObjectOptions {
+                        range: Some((10, Some(5))),
+                        ..Default::default()
+                    },
+                )
+                .await,
+            Err(StorageError::InvalidRange(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_plain_get_and_malformed_headers() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(OBJECT))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("etag", "\"v1\"")
+                    .insert_header("last-modified", "yesterday")
+                    .insert_header("content-range", "garbage")
+                    .set_body_bytes(b"whole file".to_vec()),
+            )
+            .mount(&server)
+            .await;
+
+        let output = bucket(&server.uri())
+            .get_object_with("/data/file.bin", GetObjectOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(output.status, StatusCode::OK);
+        assert_eq!(output.body.as_deref(), Some(&b"whole file"[..]));
+        assert_eq!(output.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(output.last_modified, None);
+        assert_eq!(output.content_range, None);
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(OBJECT))
+            .and(header("range", "bytes=9000-"))
+            .respond_with(ResponseTemplate::new(416).insert_header("content-range", "bytes */5000"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/sync/missing"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("not found"))
+            .mount(&server)
+            .await;
+
+        let client = bucket(&server.uri());
+        assert!(matches!(
+            client
+                .get_object_with(
+                    "data/file.bin",
+                    GetObjectOptions {
+                        range: Some((9000, None)),
+                        ..Default::default()
+                    },
+                )
+                .await,
+            Err(StorageError::RangeNotSatisfiable { size: Some(5000) })
+        ));
+        assert!(matches!(
+            client
+                .get_object_with("missing", GetObjectOptions::default())
+                .await,
+            Err(StorageError::ApiError { .. })
+        ));
+    }
+}
@@ -0,0 +1,538 @@
+//! S3 プロトコルでのマルチパートアップロード
+//!
+//! `/storage/v1/s3` エンドポイントへ SigV4 で署名したリクエストを送る。
+//! `presign_upload_part` はパートのアップロード用の署名付きURLを作成するので、
+//! ブラウザなどから直接パートを送れる (サーバーはファイルの中身を扱わずに済む)。
+
+use super::sigv4::{self, Signer};
+use super::S3BucketClient;
+use crate::{Result, StorageError};
+use bytes::Bytes;
+use reqwest::{Method, Response};
+use std::time::{Duration, SystemTime};
+use supabase_rust_core::{RequestIds, SendThroughGate};
+use url::Url;
+
+// 署名付きURLの有効期限の上限 (SigV4 の仕様で7日)
+const MAX_PRESIGN_EXPIRES: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+// S3 のパート番号の範囲
+const PART_NUMBERS: std::ops::RangeInclusive<u32> = 1..=10_000;
+
+/// アップロード済みのパート (`complete_multipart_upload` に渡す)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletedPart {
+    pub part_number: u32,
+    /// `UploadPart` のレスポンスの `ETag` (引用符を含む)
+    pub etag: String,
+}
+
+/// `CompleteMultipartUpload` の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompletedMultipartUpload {
+    pub location: Option<String>,
+    pub bucket: Option<String>,
+    pub key: String,
+    pub etag: Option<String>,
+}
+
+impl S3BucketClient {
+    // パス形式の S3 URL (`<endpoint>/<bucket>/<key>?<query>`)
+    fn s3_url(&self, key: &str, query: &[(&str, &str)]) -> Result<Url> {
+        let endpoint =
+            self.options.endpoint.clone().unwrap_or_else(|| {
+                format!("{}/storage/v1/s3", self.base_url.trim_end_matches('/'))
+            });
+        let mut url = Url::parse(&format!(
+            "{}/{}/{}",
+            endpoint.trim_end_matches('/'),
+            sigv4::uri_encode(&self.bucket_name, false),
+            sigv4::uri_encode(key.trim_start_matches('/'), true)
+        ))?;
+        if !query.is_empty() {
+            url.set_query(Some(&sigv4::canonical_query(query)));
+        }
+        Ok(url)
+    }
+
+    fn signer(&self, time: SystemTime) -> Signer<'_> {
+        Signer {
+            access_key_id: &self.options.access_key_id,
+            secret_access_key: self.options.secret_access_key.expose(),
+            region: self.options.region.as_deref().unwrap_or("auto"),
+            time,
+        }
+    }
+
+    // SigV4 で署名して送信し、S3 のエラーは `StorageError::S3Error` にする
+    async fn send_signed(
+        &self,
+        method: Method,
+        url: Url,
+        body: Bytes,
+        content_type: Option<&str>,
+    ) -> Result<Response> {
+        let now = SystemTime::now();
+        let payload_hash = sigv4::sha256_hex(&body);
+        let date = sigv4::amz_date(now);
+        let mut headers = vec![
+            ("x-amz-content-sha256", payload_hash.as_str()),
+            ("x-amz-date", date.as_str()),
+        ];
+        if let Some(content_type) = content_type {
+            headers.push(("content-type", content_type));
+        }
+        let authorization =
+            self.signer(now)
+                .authorization(method.as_str(), &url, &headers, &payload_hash);
+
+        let mut request = self
+            .http_client
+            .request(method, url)
+            .header("Authorization", authorization)
+            .body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request
+            .send_through(self.http_client.send_gate())
+            .await
+            .map_err(|e| StorageError::RequestError(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let body = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(self.s3_error(status.as_u16(), &body, request_ids));
+        }
+        Ok(response)
+    }
+
+    fn s3_error(&self, status: u16, body: &str, request_ids: Box<RequestIds>) -> StorageError {
+        match xml_text(body, "Code") {
+            Some(code) => StorageError::S3Error {
+                status,
+                code,
+                message: self.scrub_secrets(&xml_text(body, "Message").unwrap_or_default()),
+                request_ids,
+            },
+            None => StorageError::ApiError {
+                message: format!("{} (Status: {})", self.scrub_secrets(body), status),
+                status: Some(status),
+                request_ids,
+            },
+        }
+    }
+
+    /// マルチパートアップロードを開始し、アップロードIDを返す (`CreateMultipartUpload`)
+    ///
+    /// `content_type` を省略した場合はキーの拡張子から推測する。
+    pub async fn create_multipart_upload(
+        &self,
+        key: &str,
+        content_type: Option<&str>,
+    ) -> Result<String> {
+        let content_type = content_type
+            .or_else(|| crate::guess_content_type(key))
+            .unwrap_or(crate::DEFAULT_CONTENT_TYPE);
+        let url = self.s3_url(key, &[("uploads", "")])?;
+        let body = self
+            .send_signed(Method::POST, url, Bytes::new(), Some(content_type))
+            .await?
+            .text()
+            .await
+            .map_err(|e| StorageError::RequestError(e.to_string()))?;
+        xml_text(&body, "UploadId").ok_or_else(|| {
+            StorageError::DeserializationError(format!(
+                "CreateMultipartUpload response has no UploadId: {}",
+                body
+            ))
+        })
+    }
+
+    /// パートをアップロード (`UploadPart`、`part_number` は 1〜10000)
+    pub async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        data: Bytes,
+    ) -> Result<CompletedPart> {
+        check_part_number(part_number)?;
+        let part = part_number.to_string();
+        let url = self.s3_url(key, &[("partNumber", &part), ("uploadId", upload_id)])?;
+        let response = self.send_signed(Method::PUT, url, data, None).await?;
+        let etag = response
+            .headers()
+            .get("etag")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| {
+                StorageError::DeserializationError(format!(
+                    "UploadPart response for part {} has no ETag",
+                    part_number
+                ))
+            })?;
+        Ok(CompletedPart {
+            part_number,
+            etag: etag.to_string(),
+        })
+    }
+
+    /// アップロード済みのパートを結合して完了する (`CompleteMultipartUpload`)
+    ///
+    /// `parts` はそのままの順序で送る。順序の誤りや不足は
+    /// `StorageError::S3Error` (`InvalidPartOrder` / `InvalidPart` など) になる。
+    pub async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+    ) -> Result<CompletedMultipartUpload> {
+        let url = self.s3_url(key, &[("uploadId", upload_id)])?;
+        let response = self
+            .send_signed(
+                Method::POST,
+                url,
+                Bytes::from(complete_multipart_upload_xml(parts)),
+                Some("application/xml"),
+            )
+            .await?;
+        let request_ids = Box::new(RequestIds::from_response(&response));
+        let body = response
+            .text()
+            .await
+            .map_err(|e| StorageError::RequestError(e.to_string()))?;
+
+        // S3 は 200 のレスポンス本文でエラーを返すことがある
+        if body.contains("<Error>") {
+            return Err(self.s3_error(200, &body, request_ids));
+        }
+        Ok(CompletedMultipartUpload {
+            location: xml_text(&body, "Location"),
+            bucket: xml_text(&body, "Bucket"),
+            key: xml_text(&body, "Key").unwrap_or_else(|| key.to_string()),
+            etag: xml_text(&body, "ETag"),
+        })
+    }
+
+    /// マルチパートアップロードを中止し、アップロード済みのパートを破棄する (`AbortMultipartUpload`)
+    pub async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<()> {
+        let url = self.s3_url(key, &[("uploadId", upload_id)])?;
+        self.send_signed(Method::DELETE, url, Bytes::new(), None)
+            .await?;
+        Ok(())
+    }
+
+    /// パートのアップロード用の署名付きURLを作成 (`expires_in` は最大7日)
+    ///
+    /// 受け取った側は本文をそのまま `PUT` し、レスポンスの `ETag` を返してもらう。
+    pub fn presign_upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        expires_in: Duration,
+    ) -> Result<String> {
+        self.presign_upload_part_at(key, upload_id, part_number, expires_in, SystemTime::now())
+    }
+
+    pub(crate) fn presign_upload_part_at(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        expires_in: Duration,
+        time: SystemTime,
+    ) -> Result<String> {
+        check_part_number(part_number)?;
+        if expires_in.is_zero() || expires_in > MAX_PRESIGN_EXPIRES {
+            return Err(StorageError::new(format!(
+                "expires_in must be between 1 second and 7 days, got {:?}",
+                expires_in
+            )));
+        }
+        let part = part_number.to_string();
+        let url = self.s3_url(key, &[("partNumber", &part), ("uploadId", upload_id)])?;
+        Ok(self
+            .signer(time)
+            .presign(Method::PUT.as_str(), &url, expires_in)
+            .to_string())
+    }
+}
+
+fn check_part_number(part_number: u32) -> Result<()> {
+    if PART_NUMBERS.contains(&part_number) {
+        Ok(())
+    } else {
+        Err(StorageError::new(format!(
+            "part_number must be between 1 and 10000, got {}",
+            part_number
+        )))
+    }
+}
+
+fn complete_multipart_upload_xml(parts: &[CompletedPart]) -> String {
+    let parts = parts
+        .iter()
+        .map(|part| {
+            format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                part.part_number,
+                xml_escape(&part.etag)
+            )
+        })
+        .collect::<String>();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+         <CompleteMultipartUpload xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">{}</CompleteMultipartUpload>",
+        parts
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// 最初の `<tag>...</tag>` のテキスト (S3 のレスポンスは入れ子が浅いので単純に探す)
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some(
+        xml[start..end]
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::s3::S3Options;
+    use reqwest::Client;
+    use std::time::UNIX_EPOCH;
+    use wiremock::matchers::{body_string, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const KEY: &str = "videos/clip 1.mp4";
+    const OBJECT_PATH: &str = "/storage/v1/s3/uploads/videos/clip%201.mp4";
+
+    fn bucket(base_url: &str) -> S3BucketClient {
+        S3BucketClient::new(
+            base_url,
+            "anon-key",
+            "uploads",
+            Client::new(),
+            S3Options {
+                access_key_id: "access-key".to_string(),
+                secret_access_key: "secret-key".to_string().into(),
+                region: Some("local".to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_presign_upload_part() {
+        let bucket = bucket("https://project.supabase.co");
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_296_496);
+        let presigned = bucket
+            .presign_upload_part_at(KEY, "upload/1", 2, Duration::from_secs(900), time)
+            .unwrap();
+        let url = Url::parse(&presigned).unwrap();
+        assert_eq!(url.host_str(), Some("project.supabase.co"));
+        assert_eq!(url.path(), OBJECT_PATH);
+
+        let query = url.query().unwrap();
+        let params = query.split('&').collect::<Vec<_>>();
+        assert_eq!(
+            params[..7],
+            [
+                "X-Amz-Algorithm=AWS4-HMAC-SHA256",
+                "X-Amz-Credential=access-key%2F20240301%2Flocal%2Fs3%2Faws4_request",
+                "X-Amz-Date=20240301T123456Z",
+                "X-Amz-Expires=900",
+                "X-Amz-SignedHeaders=host",
+                "partNumber=2",
+                "uploadId=upload%2F1",
+            ]
+        );
+        let signature = params[7].strip_prefix("X-Amz-Signature=").unwrap();
+        assert_eq!(signature.len(), 64);
+        assert!(signature.chars().all(|c| c.is_ascii_hexdigit()));
+
+        // 同じ入力なら同じ署名、有効期限が変われば別の署名
+        let again = bucket
+            .presign_upload_part_at(KEY, "upload/1", 2, Duration::from_secs(900), time)
+            .unwrap();
+        assert_eq!(again, presigned);
+        let longer = bucket
+            .presign_upload_part_at(KEY, "upload/1", 2, Duration::from_secs(901), time)
+            .unwrap();
+        assert!(!longer.ends_with(signature));
+
+        assert!(bucket
+            .presign_upload_part(KEY, "upload/1", 0, Duration::from_secs(900))
+            .is_err());
+        assert!(bucket
+            .presign_upload_part(KEY, "upload/1", 1, MAX_PRESIGN_EXPIRES * 2)
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_upload_three_parts() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(OBJECT_PATH))
+            .and(query_param("uploads", ""))
+            .and(header("content-type", "video/mp4"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<InitiateMultipartUploadResult><Bucket>uploads</Bucket>\
+                 <Key>videos/clip 1.mp4</Key><UploadId>upload-123</UploadId>\
+                 </InitiateMultipartUploadResult>",
+            ))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        for part_number in 1..=3 {
+            Mock::given(method("PUT"))
+                .and(path(OBJECT_PATH))
+                .and(query_param("partNumber", part_number.to_string()))
+                .and(query_param("uploadId", "upload-123"))
+                .and(header(
+                    "x-amz-content-sha256",
+                    sigv4::sha256_hex(format!("part {}", part_number).as_bytes()).as_str(),
+                ))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .insert_header("ETag", format!("\"etag-{}\"", part_number).as_str()),
+                )
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .and(path(OBJECT_PATH))
+            .and(query_param("uploadId", "upload-123"))
+            .and(body_string(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+                 <CompleteMultipartUpload xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\">\
+                 <Part><PartNumber>1</PartNumber><ETag>&quot;etag-1&quot;</ETag></Part>\
+                 <Part><PartNumber>2</PartNumber><ETag>&quot;etag-2&quot;</ETag></Part>\
+                 <Part><PartNumber>3</PartNumber><ETag>&quot;etag-3&quot;</ETag></Part>\
+                 </CompleteMultipartUpload>",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<CompleteMultipartUploadResult><Location>http://localhost/uploads/videos/clip%201.mp4</Location>\
+                 <Bucket>uploads</Bucket><Key>videos/clip 1.mp4</Key><ETag>&quot;final-3&quot;</ETag>\
+                 </CompleteMultipartUploadResult>",
+            ))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let bucket = bucket(&mock_server.uri());
+        let upload_id = bucket.create_multipart_upload(KEY, None).await.unwrap();
+        assert_eq!(upload_id, "upload-123");
+
+        let mut parts = Vec::new();
+        for part_number in 1..=3 {
+            let data = Bytes::from(format!("part {}", part_number));
+            parts.push(
+                bucket
+                    .upload_part(KEY, &upload_id, part_number, data)
+                    .await
+                    .unwrap(),
+            );
+        }
+        assert_eq!(parts[1].etag, "\"etag-2\"");
+
+        let completed = bucket
+            .complete_multipart_upload(KEY, &upload_id, &parts)
+            .await
+            .unwrap();
+        assert_eq!(completed.key, KEY);
+        assert_eq!(completed.bucket.as_deref(), Some("uploads"));
+        assert_eq!(completed.etag.as_deref(), Some("\"final-3\""));
+
+        // すべてのリクエストが SigV4 で署名されている
+        for request in mock_server.received_requests().await.unwrap() {
+            let authorization = request.headers.get(&"authorization".into()).unwrap();
+            let authorization = authorization.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+            let authorization = authorization.join(",");
+            assert!(
+                authorization.starts_with("AWS4-HMAC-SHA256 Credential=access-key/"),
+                "{}",
+                authorization
+            );
+            assert!(authorization.contains("/local/s3/aws4_request"));
+            assert!(authorization.contains("host;x-amz-content-sha256;x-amz-date"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_complete_multipart_upload_surfaces_s3_error_code() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(OBJECT_PATH))
+            .and(query_param("uploadId", "out-of-order"))
+            .respond_with(ResponseTemplate::new(400).set_body_string(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Error><Code>InvalidPartOrder</Code>\
+                 <Message>The list of parts was not in ascending order.</Message></Error>",
+            ))
+            .mount(&mock_server)
+            .await;
+        // 200 でも本文がエラーの場合がある
+        Mock::given(method("POST"))
+            .and(path(OBJECT_PATH))
+            .and(query_param("uploadId", "missing"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "<Error><Code>InvalidPart</Code>\
+                 <Message>One or more of the specified parts could not be found.</Message></Error>",
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let bucket = bucket(&mock_server.uri());
+        let parts = [
+            CompletedPart {
+                part_number: 2,
+                etag: "\"etag-2\"".to_string(),
+            },
+            CompletedPart {
+                part_number: 1,
+                etag: "\"etag-1\"".to_string(),
+            },
+        ];
+        match bucket
+            .complete_multipart_upload(KEY, "out-of-order", &parts)
+            .await
+        {
+            Err(StorageError::S3Error { status, code, .. }) => {
+                assert_eq!(status, 400);
+                assert_eq!(code, "InvalidPartOrder");
+            }
+            other => panic!("expected S3Error, got {:?}", other),
+        }
+        match bucket
+            .complete_multipart_upload(KEY, "missing", &parts[..1])
+            .await
+        {
+            Err(StorageError::S3Error { code, message, .. }) => {
+                assert_eq!(code, "InvalidPart");
+                assert!(message.contains("could not be found"));
+            }
+            other => panic!("expected S3Error, got {:?}", other),
+        }
+    }
+}
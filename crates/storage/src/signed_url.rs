@@ -0,0 +1,250 @@
+//! 署名付きURLの検証
+//!
+//! 署名付きURLの `token` はプロジェクトの JWT シークレットで署名された HS256 の JWT で、
+//! `url` クレームに `<bucket>/<path>`、`exp` クレームに有効期限を持つ。
+//! Supabase に問い合わせずに、署名・有効期限・URL のパスとクレームの一致を確認する。
+
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use url::Url;
+
+// 署名付きURLのパスの接頭辞 (`/storage/v1` の有無は問わない)
+const SIGN_PATH_PREFIX: &str = "/object/sign/";
+
+/// 署名付きURLの検証エラー
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SignedUrlError {
+    /// トークンがない、または JWT として解釈できない
+    #[error("Malformed signed URL: {0}")]
+    Malformed(String),
+
+    /// 署名が JWT シークレットと一致しない
+    #[error("Invalid signed URL signature")]
+    InvalidSignature,
+
+    /// 有効期限切れ
+    #[error("Signed URL expired at {exp}")]
+    Expired { exp: i64 },
+
+    /// URL のパスがトークンのクレームと一致しない
+    #[error("Signed URL path mismatch: token is for {expected}, URL is for {actual}")]
+    PathMismatch { expected: String, actual: String },
+}
+
+/// 署名付きURLのトークンのクレーム
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedUrlClaims {
+    pub bucket: String,
+    /// バケット内のオブジェクトのパス
+    pub path: String,
+    /// 有効期限 (UNIX 時間の秒)
+    pub exp: i64,
+}
+
+impl SignedUrlClaims {
+    /// 有効期限を過ぎているか
+    pub fn is_expired(&self) -> bool {
+        self.exp <= now()
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenClaims {
+    url: String,
+    exp: i64,
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+/// 署名付きURL (またはその `token`) を検証してクレームを返す
+///
+/// `url_or_token` には完全な URL、`/object/sign/...?token=...` のような相対 URL、
+/// またはトークンそのものを渡せる。URL の場合は URL のパスがトークンの `url` クレームと
+/// 一致することも確認する。
+pub fn verify_signed_url(
+    url_or_token: &str,
+    jwt_secret: &str,
+) -> Result<SignedUrlClaims, SignedUrlError> {
+    let (token, url_path) = split_signed_url(url_or_token)?;
+
+    let mut validation = Validation::new(Algorithm::HS256);
+    // 有効期限は Expired を返すために自前で確認する
+    validation.validate_exp = false;
+    validation.set_required_spec_claims(&["exp"]);
+    let claims = jsonwebtoken::decode::<TokenClaims>(
+        &token,
+        &DecodingKey::from_secret(jwt_secret.as_bytes()),
+        &validation,
+    )
+    .map_err(|e| match e.kind() {
+        ErrorKind::InvalidSignature | ErrorKind::InvalidAlgorithm => {
+            SignedUrlError::InvalidSignature
+        }
+        _ => SignedUrlError::Malformed(e.to_string()),
+    })?
+    .claims;
+
+    if let Some(actual) = url_path {
+        if actual != claims.url {
+            return Err(SignedUrlError::PathMismatch {
+                expected: claims.url,
+                actual,
+            });
+        }
+    }
+    let (bucket, path) = claims
+        .url
+        .split_once('/')
+        .filter(|(bucket, path)| !bucket.is_empty() && !path.is_empty())
+        .ok_or_else(|| SignedUrlError::Malformed(format!("Invalid url claim: {}", claims.url)))?;
+    let claims = SignedUrlClaims {
+        bucket: bucket.to_string(),
+        path: path.to_string(),
+        exp: claims.exp,
+    };
+    if claims.is_expired() {
+        return Err(SignedUrlError::Expired { exp: claims.exp });
+    }
+    Ok(claims)
+}
+
+// トークンと、URL の場合は `<bucket>/<path>` (デコード済み) を取り出す
+fn split_signed_url(url_or_token: &str) -> Result<(String, Option<String>), SignedUrlError> {
+    let url_or_token = url_or_token.trim();
+    if !url_or_token.contains('?') && !url_or_token.contains('/') {
+        return Ok((url_or_token.to_string(), None));
+    }
+
+    let url = match Url::parse(url_or_token) {
+        Ok(url) => url,
+        Err(url::ParseError::RelativeUrlWithoutBase) => Url::parse("http://localhost")
+            .and_then(|base| base.join(url_or_token))
+            .map_err(|e| SignedUrlError::Malformed(e.to_string()))?,
+        Err(e) => return Err(SignedUrlError::Malformed(e.to_string())),
+    };
+    let token = url
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .map(|(_, value)| value.into_owned())
+        .ok_or_else(|| SignedUrlError::Malformed("Missing token parameter".to_string()))?;
+    let path = url
+        .path()
+        .find(SIGN_PATH_PREFIX)
+        .map(|start| &url.path()[start + SIGN_PATH_PREFIX.len()..])
+        .ok_or_else(|| {
+            SignedUrlError::Malformed(format!("Not a signed URL path: {}", url.path()))
+        })?;
+    let path = percent_decode_str(path)
+        .decode_utf8()
+        .map_err(|e| SignedUrlError::Malformed(e.to_string()))?;
+    Ok((token, Some(path.into_owned())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    const SECRET: &str = "super-secret-jwt-token";
+
+    fn token(url: &str, exp: i64, secret: &str) -> String {
+        jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &json!({ "url": url, "iat": now() - 10, "exp": exp }),
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn signed_url(path: &str, token: &str) -> String {
+        format!(
+            "https://example.supabase.co/storage/v1/object/sign/{}?token={}",
+            path, token
+        )
+    }
+
+    #[test]
+    fn test_verify_signed_url() {
+        let exp = now() + 60;
+        let token = token("avatars/users/me 1.png", exp, SECRET);
+        let expected = SignedUrlClaims {
+            bucket: "avatars".to_string(),
+            path: "users/me 1.png".to_string(),
+            exp,
+        };
+
+        let url = signed_url("avatars/users/me%201.png", &token);
+        assert_eq!(verify_signed_url(&url, SECRET).unwrap(), expected);
+        assert!(!expected.is_expired());
+
+        // 相対 URL とトークンのみ
+        let relative = format!("/object/sign/avatars/users/me%201.png?token={}", token);
+        assert_eq!(verify_signed_url(&relative, SECRET).unwrap(), expected);
+        assert_eq!(verify_signed_url(&token, SECRET).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_verify_signed_url_errors() {
+        let exp = now() + 60;
+        let valid = token("avatars/me.png", exp, SECRET);
+
+        let expired_at = now() - 1;
+        let expired = token("avatars/me.png", expired_at, SECRET);
+        assert_eq!(
+            verify_signed_url(&signed_url("avatars/me.png", &expired), SECRET),
+            Err(SignedUrlError::Expired { exp: expired_at })
+        );
+
+        assert_eq!(
+            verify_signed_url(&valid, "another-secret"),
+            Err(SignedUrlError::InvalidSignature)
+        );
+
+        // URL のパスの書き換え
+        assert_eq!(
+            verify_signed_url(&signed_url("avatars/admin.png", &valid), SECRET),
+            Err(SignedUrlError::PathMismatch {
+                expected: "avatars/me.png".to_string(),
+                actual: "avatars/admin.png".to_string(),
+            })
+        );
+
+        // トークンのクレームの書き換え (署名はそのまま)
+        let forged = token("avatars/admin.png", exp, "attacker-secret");
+        let parts: Vec<&str> = valid.split('.').collect();
+        let forged_payload = forged.split('.').nth(1).unwrap();
+        let tampered = format!("{}.{}.{}", parts[0], forged_payload, parts[2]);
+        assert_eq!(
+            verify_signed_url(&signed_url("avatars/admin.png", &tampered), SECRET),
+            Err(SignedUrlError::InvalidSignature)
+        );
+
+        for malformed in [
+            "not-a-jwt".to_string(),
+            signed_url("avatars/me.png", "abc.def.ghi"),
+            "https://example.supabase.co/storage/v1/object/sign/avatars/me.png".to_string(),
+            format!(
+                "https://example.supabase.co/storage/v1/object/public/avatars/me.png?token={}",
+                valid
+            ),
+        ] {
+            assert!(
+                matches!(
+                    verify_signed_url(&malformed, SECRET),
+                    Err(SignedUrlError::Malformed(_))
+                ),
+                "{}",
+                malformed
+            );
+        }
+    }
+}
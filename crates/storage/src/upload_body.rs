@@ -0,0 +1,507 @@
+//! アップロード元の共通化
+//!
+//! `upload` / `upload_large_file` / `resume_large_file_upload` / `s3::S3BucketClient::put_object`
+//! は [`IntoUploadBody`] を実装した型を受け取る。ファイルパス、`Bytes`、`Vec<u8>`、
+//! `tokio::fs::File`、`UploadBody::from_stream` で包んだストリームに対応する。
+//! 新しい種類のアップロード元は `IntoUploadBody` を実装するだけで、すべてのメソッドで使える。
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures_util::stream::{BoxStream, Stream, StreamExt, TryStreamExt};
+use std::io::{self, SeekFrom};
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// アップロードする内容 (本文、分かっていれば長さ、コンテンツタイプのヒント)
+pub struct UploadBody {
+    source: Source,
+    len: Option<u64>,
+    content_type: Option<String>,
+    file_name: Option<String>,
+}
+
+enum Source {
+    Bytes(Bytes),
+    File(File),
+    Stream {
+        stream: BoxStream<'static, io::Result<Bytes>>,
+        buffered: BytesMut,
+    },
+}
+
+impl UploadBody {
+    fn new(source: Source, len: Option<u64>) -> Self {
+        Self {
+            source,
+            len,
+            content_type: None,
+            file_name: None,
+        }
+    }
+
+    /// メモリ上のデータ
+    pub fn from_bytes(data: impl Into<Bytes>) -> Self {
+        let data = data.into();
+        let len = data.len() as u64;
+        Self::new(Source::Bytes(data), Some(len))
+    }
+
+    /// 開いているファイル (現在の位置から読む)
+    pub async fn from_file(mut file: File) -> io::Result<Self> {
+        let len = file.metadata().await?.len();
+        let position = file.stream_position().await?;
+        Ok(Self::new(
+            Source::File(file),
+            Some(len.saturating_sub(position)),
+        ))
+    }
+
+    /// ファイルパス (ファイル名と拡張子から推測したコンテンツタイプも使う)
+    pub async fn from_path(path: &Path) -> io::Result<Self> {
+        let mut body = Self::from_file(File::open(path).await?).await?;
+        if let Some(file_name) = path.file_name() {
+            let file_name = file_name.to_string_lossy();
+            body.content_type = crate::guess_content_type(&file_name).map(str::to_string);
+            body.file_name = Some(file_name.into_owned());
+        }
+        Ok(body)
+    }
+
+    /// チャンクのストリーム (長さは `with_len` で指定できる)
+    ///
+    /// `Bytes` などとの実装の重複を避けるため、ストリームはこの関数で包んで渡す。
+    pub fn from_stream<S>(stream: S) -> Self
+    where
+        S: Stream<Item = io::Result<Bytes>> + Send + 'static,
+    {
+        Self::new(
+            Source::Stream {
+                stream: stream.boxed(),
+                buffered: BytesMut::new(),
+            },
+            None,
+        )
+    }
+
+    /// 長さを設定 (ストリームなど、自動で分からない場合)
+    pub fn with_len(mut self, len: u64) -> Self {
+        self.len = Some(len);
+        self
+    }
+
+    /// コンテンツタイプのヒントを設定
+    ///
+    /// `FileOptions` の指定やアップロード先のパスの拡張子から推測できない場合に使われる。
+    pub fn with_content_type(mut self, content_type: &str) -> Self {
+        self.content_type = Some(content_type.to_string());
+        self
+    }
+
+    /// マルチパートフォームで送るファイル名を設定
+    pub fn with_file_name(mut self, file_name: &str) -> Self {
+        self.file_name = Some(file_name.to_string());
+        self
+    }
+
+    /// 長さ (分からない場合は None)
+    pub fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// 長さが 0 と分かっているか
+    pub fn is_empty(&self) -> bool {
+        self.len == Some(0)
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// 残りをすべて読み込む
+    pub async fn into_bytes(self) -> io::Result<Bytes> {
+        match self.source {
+            Source::Bytes(data) => Ok(data),
+            Source::File(mut file) => {
+                let mut contents = Vec::new();
+                file.read_to_end(&mut contents).await?;
+                Ok(contents.into())
+            }
+            Source::Stream {
+                stream,
+                mut buffered,
+            } => {
+                stream
+                    .try_for_each(|chunk| {
+                        buffered.extend_from_slice(&chunk);
+                        futures_util::future::ready(Ok(()))
+                    })
+                    .await?;
+                Ok(buffered.freeze())
+            }
+        }
+    }
+
+    /// 最大 `size` バイトを読む (終端なら空)
+    pub(crate) async fn read_chunk(&mut self, size: usize) -> io::Result<Bytes> {
+        match &mut self.source {
+            Source::Bytes(data) => Ok(data.split_to(size.min(data.len()))),
+            Source::File(file) => {
+                let mut chunk = Vec::with_capacity(size);
+                (&mut *file)
+                    .take(size as u64)
+                    .read_to_end(&mut chunk)
+                    .await?;
+                Ok(chunk.into())
+            }
+            Source::Stream { stream, buffered } => {
+                while buffered.len() < size {
+                    match stream.next().await {
+                        Some(chunk) => buffered.extend_from_slice(&chunk?),
+                        None => break,
+                    }
+                }
+                let len = size.min(buffered.len());
+                Ok(buffered.split_to(len).freeze())
+            }
+        }
+    }
+
+    /// 最大 `size` バイトを読み飛ばし、読み飛ばしたバイト数を返す
+    pub(crate) async fn skip(&mut self, size: usize) -> io::Result<u64> {
+        match &mut self.source {
+            Source::File(file) => {
+                let position = file.stream_position().await?;
+                let len = file.metadata().await?.len();
+                let skipped = (size as u64).min(len.saturating_sub(position));
+                file.seek(SeekFrom::Start(position + skipped)).await?;
+                Ok(skipped)
+            }
+            _ => Ok(self.read_chunk(size).await?.len() as u64),
+        }
+    }
+}
+
+impl std::fmt::Debug for UploadBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = match self.source {
+            Source::Bytes(_) => "bytes",
+            Source::File(_) => "file",
+            Source::Stream { .. } => "stream",
+        };
+        f.debug_struct("UploadBody")
+            .field("source", &source)
+            .field("len", &self.len)
+            .field("content_type", &self.content_type)
+            .field("file_name", &self.file_name)
+            .finish()
+    }
+}
+
+/// アップロード元に変換できる型
+#[async_trait]
+pub trait IntoUploadBody: Send {
+    async fn into_upload_body(self) -> io::Result<UploadBody>;
+}
+
+#[async_trait]
+impl IntoUploadBody for UploadBody {
+    async fn into_upload_body(self) -> io::Result<UploadBody> {
+        Ok(self)
+    }
+}
+
+#[async_trait]
+impl IntoUploadBody for Bytes {
+    async fn into_upload_body(self) -> io::Result<UploadBody> {
+        Ok(UploadBody::from_bytes(self))
+    }
+}
+
+#[async_trait]
+impl IntoUploadBody for Vec<u8> {
+    async fn into_upload_body(self) -> io::Result<UploadBody> {
+        Ok(UploadBody::from_bytes(self))
+    }
+}
+
+#[async_trait]
+impl IntoUploadBody for File {
+    async fn into_upload_body(self) -> io::Result<UploadBody> {
+        UploadBody::from_file(self).await
+    }
+}
+
+#[async_trait]
+impl IntoUploadBody for &Path {
+    async fn into_upload_body(self) -> io::Result<UploadBody> {
+        UploadBody::from_path(self).await
+    }
+}
+
+#[async_trait]
+impl IntoUploadBody for &PathBuf {
+    async fn into_upload_body(self) -> io::Result<UploadBody> {
+        UploadBody::from_path(self).await
+    }
+}
+
+#[async_trait]
+impl IntoUploadBody for PathBuf {
+    async fn into_upload_body(self) -> io::Result<UploadBody> {
+        UploadBody::from_path(&self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{s3, StorageClient};
+    use futures_util::stream;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use wiremock::matchers::{method, path, path_regex, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn file_object(name: &str) -> serde_json::Value {
+        json!({
+            "name": name,
+            "bucket_id": "bodies",
+            "owner": "owner-uuid",
+            "id": "file-id",
+            "updated_at": "2024-01-05T00:00:00Z",
+            "created_at": "2024-01-05T00:00:00Z",
+            "last_accessed_at": "2024-01-05T00:00:00Z",
+            "metadata": null,
+            "mime_type": null,
+            "size": 5
+        })
+    }
+
+    fn chunks(parts: &[&'static [u8]]) -> UploadBody {
+        UploadBody::from_stream(stream::iter(
+            parts
+                .iter()
+                .map(|part| Ok(Bytes::from_static(part)))
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_read_chunk_and_skip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("data.bin");
+        tokio::fs::write(&file_path, b"0123456789").await.unwrap();
+
+        let mut file = UploadBody::from_path(&file_path).await.unwrap();
+        assert_eq!(file.len(), Some(10));
+        assert_eq!(file.file_name(), Some("data.bin"));
+        assert_eq!(file.skip(4).await.unwrap(), 4);
+        assert_eq!(file.read_chunk(4).await.unwrap(), "4567");
+        assert_eq!(file.skip(4).await.unwrap(), 2);
+        assert!(file.read_chunk(4).await.unwrap().is_empty());
+
+        let mut stream = chunks(&[b"ab", b"cde", b"f"]);
+        assert_eq!(stream.len(), None);
+        assert_eq!(stream.read_chunk(4).await.unwrap(), "abcd");
+        assert_eq!(stream.skip(1).await.unwrap(), 1);
+        assert_eq!(stream.read_chunk(4).await.unwrap(), "f");
+        assert!(stream.read_chunk(4).await.unwrap().is_empty());
+
+        let mut bytes = UploadBody::from_bytes(vec![1, 2, 3]);
+        assert_eq!(bytes.read_chunk(2).await.unwrap(), vec![1u8, 2]);
+        assert_eq!(bytes.into_bytes().await.unwrap(), vec![3u8]);
+    }
+
+    #[tokio::test]
+    async fn test_upload_accepts_every_body_source() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path_regex("^/storage/v1/object/bodies/.+$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(file_object("f")))
+            .expect(7)
+            .mount(&mock_server)
+            .await;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file_path = temp_dir.path().join("local.csv");
+        tokio::fs::write(&file_path, b"a,b,c").await.unwrap();
+
+        let storage_client =
+            StorageClient::new(&mock_server.uri(), "fake-key", reqwest::Client::new());
+        let bucket = storage_client.from("bodies");
+        bucket
+            .upload("path", file_path.as_path(), None)
+            .await
+            .unwrap();
+        bucket.upload("pathbuf", &file_path, None).await.unwrap();
+        bucket
+            .upload("owned", file_path.clone(), None)
+            .await
+            .unwrap();
+        bucket
+            .upload("bytes", Bytes::from_static(b"bytes"), None)
+            .await
+            .unwrap();
+        bucket.upload("vec", b"vec!!".to_vec(), None).await.unwrap();
+        let file = File::open(&file_path).await.unwrap();
+        bucket.upload("file", file, None).await.unwrap();
+        let stream = chunks(&[b"str", b"eam"]).with_content_type("text/plain");
+        bucket.upload("stream", stream, None).await.unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let bodies = requests
+            .iter()
+            .map(|request| {
+                (
+                    request.url.path().rsplit('/').next().unwrap().to_string(),
+                    String::from_utf8_lossy(&request.body).into_owned(),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        // パスから開いた場合はローカルのファイル名と拡張子を使う
+        for key in ["path", "pathbuf", "owned"] {
+            assert!(bodies[key].contains("filename=\"local.csv\""), "{}", key);
+            assert!(bodies[key].contains("Content-Type: text/csv"), "{}", key);
+            assert!(bodies[key].contains("a,b,c"), "{}", key);
+        }
+        assert!(bodies["bytes"].contains("filename=\"bytes\""));
+        assert!(bodies["bytes"].contains("Content-Type: application/octet-stream"));
+        assert!(bodies["vec"].contains("vec!!"));
+        assert!(bodies["file"].contains("a,b,c"));
+        assert!(bodies["stream"].contains("stream"));
+        assert!(bodies["stream"].contains("Content-Type: text/plain"));
+    }
+
+    #[tokio::test]
+    async fn test_upload_large_file_from_stream() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/initiate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "file-id",
+                "uploadId": "stream-upload",
+                "key": "streamed.bin",
+                "bucket": "bodies"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/part"))
+            .and(query_param("uploadId", "stream-upload"))
+            .respond_with(ResponseTemplate::new(200).insert_header("ETag", "etag"))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/complete"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(file_object("streamed.bin")))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let storage_client =
+            StorageClient::new(&mock_server.uri(), "fake-key", reqwest::Client::new());
+        storage_client
+            .from("bodies")
+            .upload_large_file("streamed.bin", chunks(&[b"abc", b"defgh", b"ij"]), 4, None)
+            .await
+            .unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let parts = requests
+            .iter()
+            .filter(|request| request.url.path() == "/storage/v1/upload/part")
+            .map(|request| String::from_utf8_lossy(&request.body).into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(parts.len(), 3);
+        assert!(parts[0].contains("abcd"));
+        assert!(parts[1].contains("efgh"));
+        assert!(parts[2].contains("ij"));
+    }
+
+    #[tokio::test]
+    async fn test_upload_large_file_rejects_empty_stream() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/initiate"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "id": "file-id",
+                "uploadId": "empty-upload",
+                "key": "empty.bin",
+                "bucket": "bodies"
+            })))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/storage/v1/upload/abort"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let storage_client =
+            StorageClient::new(&mock_server.uri(), "fake-key", reqwest::Client::new());
+        let bucket = storage_client.from("bodies");
+        let error = bucket
+            .upload_large_file("empty.bin", chunks(&[]), 4, None)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("File is empty"), "{}", error);
+
+        // 長さが 0 と分かっていれば初期化もしない
+        let error = bucket
+            .upload_large_file("empty.bin", Vec::new(), 4, None)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("File is empty"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_put_object_accepts_upload_body() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/storage/v1/object/bodies/report"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let storage_client =
+            StorageClient::new(&mock_server.uri(), "fake-key", reqwest::Client::new());
+        let s3_bucket = storage_client
+            .from("bodies")
+            .s3_compatible(s3::S3Options::default());
+        s3_bucket
+            .put_object("report", b"vec".to_vec(), None, None)
+            .await
+            .unwrap();
+        s3_bucket
+            .put_object(
+                "report",
+                chunks(&[b"<p>", b"</p>"]).with_content_type("text/html"),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        assert_eq!(requests[0].body, b"vec");
+        assert_eq!(
+            requests[0].headers.get(&"content-type".into()).unwrap(),
+            "application/octet-stream"
+        );
+        assert_eq!(requests[1].body, b"<p></p>");
+        assert_eq!(
+            requests[1].headers.get(&"content-type".into()).unwrap(),
+            "text/html"
+        );
+    }
+}
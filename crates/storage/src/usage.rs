@@ -0,0 +1,283 @@
+//! バケットごとの使用量
+//!
+//! [`StorageClient::bucket_usage`] はまずバケットのレコード (`GET /bucket/{id}`) を取得し、
+//! `object_count` と `total_size` が含まれていればその値を使う ([`BucketUsage::exact`] が true)。
+//! 含まれていない古い Storage API では、オブジェクトを再帰的に一覧してサイズを合計する。
+//! この場合は一覧の間に追加・削除されたオブジェクトを数え損ねることがあり、`exact` は false になる。
+
+use crate::directory::{join_key, EMPTY_FOLDER_PLACEHOLDER, LIST_PAGE_SIZE};
+use crate::{ListOptions, Result, StorageClient, StorageError};
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use serde::Deserialize;
+use supabase_rust_core::{RequestIds, SendThroughGate};
+
+/// バケットの使用量
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BucketUsage {
+    pub bucket_id: String,
+    pub object_count: u64,
+    /// オブジェクトの合計サイズ (バイト)
+    pub total_bytes: u64,
+    /// サーバー側の集計値なら true、クライアントで一覧を合計した値なら false
+    pub exact: bool,
+}
+
+// バケットのレコードのうち使用量に関するフィールド
+#[derive(Deserialize)]
+struct BucketRecord {
+    #[serde(default)]
+    object_count: Option<u64>,
+    #[serde(default)]
+    total_size: Option<u64>,
+}
+
+// 一覧のエントリのうち集計に必要なフィールドのみ
+#[derive(Deserialize)]
+struct SizeEntry {
+    name: String,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    metadata: Option<SizeMetadata>,
+}
+
+#[derive(Deserialize)]
+struct SizeMetadata {
+    #[serde(default)]
+    size: Option<u64>,
+}
+
+impl StorageClient {
+    /// バケットのオブジェクト数と合計サイズを取得
+    ///
+    /// サーバーが使用量を返さない場合は一覧を合計する (モジュールの説明を参照)。
+    pub async fn bucket_usage(&self, bucket_id: &str) -> Result<BucketUsage> {
+        let url = format!("{}/storage/v1/bucket/{}", self.base_url, bucket_id);
+
+        let response = self
+            .http_client
+            .get(&url)
+            .header("apikey", self.api_key.expose())
+            .header("Authorization", format!("Bearer {}", self.api_key.expose()))
+            .send_through(self.http_client.send_gate())
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let request_ids = Box::new(RequestIds::from_response(&response));
+            let error_text = self.scrub_secrets(&response.text().await?);
+            return Err(StorageError::ApiError {
+                message: error_text,
+                status: Some(status.as_u16()),
+                request_ids,
+            });
+        }
+
+        let record = response.json::<BucketRecord>().await?;
+        if let (Some(object_count), Some(total_bytes)) = (record.object_count, record.total_size) {
+            return Ok(BucketUsage {
+                bucket_id: bucket_id.to_string(),
+                object_count,
+                total_bytes,
+                exact: true,
+            });
+        }
+
+        self.aggregate_usage(bucket_id).await
+    }
+
+    /// すべてのバケットの使用量を、最大 `concurrency` 件ずつ並行して取得する
+    ///
+    /// 結果は `list_buckets` の順。いずれかのバケットで失敗した場合はそのエラーを返す。
+    pub async fn usage_all(&self, concurrency: usize) -> Result<Vec<BucketUsage>> {
+        let buckets = self.list_buckets().await?;
+        stream::iter(buckets.iter().map(|bucket| self.bucket_usage(&bucket.id)))
+            .buffered(concurrency.max(1))
+            .try_collect()
+            .await
+    }
+
+    // バケット全体を再帰的に一覧してサイズを合計する (エントリは保持しない)
+    async fn aggregate_usage(&self, bucket_id: &str) -> Result<BucketUsage> {
+        let bucket = self.from(bucket_id);
+        let mut usage = BucketUsage {
+            bucket_id: bucket_id.to_string(),
+            object_count: 0,
+            total_bytes: 0,
+            exact: false,
+        };
+        let mut folders = vec![String::new()];
+        while let Some(folder) = folders.pop() {
+            let mut offset = 0;
+            loop {
+                let options = ListOptions::new().limit(LIST_PAGE_SIZE).offset(offset);
+                let entries: Vec<SizeEntry> = bucket.list_as(&folder, Some(options)).await?;
+                let count = entries.len();
+                for entry in entries {
+                    match entry.id {
+                        Some(_) if entry.name == EMPTY_FOLDER_PLACEHOLDER => {}
+                        Some(_) => {
+                            usage.object_count += 1;
+                            usage.total_bytes += entry
+                                .metadata
+                                .and_then(|metadata| metadata.size)
+                                .unwrap_or(0);
+                        }
+                        None => folders.push(join_key(&folder, &entry.name)),
+                    }
+                }
+                if count < LIST_PAGE_SIZE as usize {
+                    break;
+                }
+                offset += LIST_PAGE_SIZE;
+            }
+        }
+        Ok(usage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn bucket(id: &str) -> serde_json::Value {
+        json!({
+            "id": id,
+            "name": id,
+            "owner": "",
+            "public": false,
+            "created_at": "2024-01-05T00:00:00Z",
+            "updated_at": "2024-01-05T00:00:00Z",
+        })
+    }
+
+    fn object(name: &str, size: u64) -> serde_json::Value {
+        json!({
+            "name": name,
+            "id": format!("id-{}", name),
+            "updated_at": "2024-01-05T00:00:00Z",
+            "created_at": "2024-01-05T00:00:00Z",
+            "last_accessed_at": "2024-01-05T00:00:00Z",
+            "metadata": { "size": size, "mimetype": "image/png" },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_bucket_usage_uses_bucket_record_sizes() {
+        let mock_server = MockServer::start().await;
+        let mut record = bucket("tenant-a");
+        record["object_count"] = json!(1234);
+        record["total_size"] = json!(98_765_432);
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/bucket/tenant-a"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(record))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/tenant-a"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let usage = storage_client.bucket_usage("tenant-a").await.unwrap();
+        assert_eq!(
+            usage,
+            BucketUsage {
+                bucket_id: "tenant-a".to_string(),
+                object_count: 1234,
+                total_bytes: 98_765_432,
+                exact: true,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bucket_usage_aggregates_listing_pages() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/bucket/tenant-b"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(bucket("tenant-b")))
+            .mount(&mock_server)
+            .await;
+        let first_page: Vec<_> = (0..1000)
+            .map(|i| object(&format!("img-{:04}.png", i), 10))
+            .collect();
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/tenant-b"))
+            .and(query_param("prefix", ""))
+            .and(query_param("offset", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(first_page))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/tenant-b"))
+            .and(query_param("prefix", ""))
+            .and(query_param("offset", "1000"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                object("big.bin", 5_000),
+                { "name": "avatars", "id": null, "metadata": null },
+            ])))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/object/list/tenant-b"))
+            .and(query_param("prefix", "avatars"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+                object("u1.png", 300),
+                object(".emptyFolderPlaceholder", 0),
+                { "name": "no-size.png", "id": "id-no-size", "metadata": null },
+            ])))
+            .mount(&mock_server)
+            .await;
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let usage = storage_client.bucket_usage("tenant-b").await.unwrap();
+        assert_eq!(
+            usage,
+            BucketUsage {
+                bucket_id: "tenant-b".to_string(),
+                object_count: 1003,
+                total_bytes: 10_000 + 5_000 + 300,
+                exact: false,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_usage_all_keeps_bucket_order() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/storage/v1/bucket"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!([bucket("a"), bucket("b")])),
+            )
+            .mount(&mock_server)
+            .await;
+        for (id, size) in [("a", 1), ("b", 2)] {
+            let mut record = bucket(id);
+            record["object_count"] = json!(size);
+            record["total_size"] = json!(size * 100);
+            Mock::given(method("GET"))
+                .and(path(format!("/storage/v1/bucket/{}", id)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(record))
+                .mount(&mock_server)
+                .await;
+        }
+
+        let storage_client = StorageClient::new(&mock_server.uri(), "fake-key", Client::new());
+        let usage = storage_client.usage_all(4).await.unwrap();
+        assert_eq!(
+            usage
+                .iter()
+                .map(|u| (u.bucket_id.as_str(), u.object_count, u.total_bytes))
+                .collect::<Vec<_>>(),
+            vec![("a", 1, 100), ("b", 2, 200)]
+        );
+    }
+}
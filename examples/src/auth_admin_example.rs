@@ -20,11 +20,7 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let http_client = Client::new();
 
     // AdminAuthクライアントを直接初期化
-    let admin = AdminAuth::new(
-        &format!("{}/auth/v1", supabase_url),
-        &service_role_key,
-        http_client,
-    );
+    let admin = AdminAuth::new(&supabase_url, &service_role_key, http_client);
 
     println!("Auth Admin API の例を開始します");
 
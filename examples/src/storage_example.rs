@@ -4,14 +4,18 @@ use std::env;
 use std::fs::File as StdFile;
 use std::io::{Read, Write};
 use std::path::Path;
-use supabase_rust_gftd::storage::{FileObject, FileOptions, ImageTransformOptions, ListOptions};
+use supabase_rust_gftd::storage::{
+    FileObject, FileOptions, ImageFormat, ImageTransformOptions, ListOptions, ResizeMode,
+};
 use supabase_rust_gftd::Supabase;
 use tempfile::NamedTempFile;
 
 mod image_transform_examples {
     use std::env;
     use std::io::Write;
-    use supabase_rust_gftd::storage::{FileOptions, ImageTransformOptions};
+    use supabase_rust_gftd::storage::{
+        FileOptions, ImageFormat, ImageTransformOptions, ResizeMode,
+    };
     use supabase_rust_gftd::Supabase;
     use tempfile::NamedTempFile;
 
@@ -76,22 +80,22 @@ mod image_transform_examples {
                 ImageTransformOptions::new()
                     .with_width(100)
                     .with_height(100)
-                    .with_resize("cover"),
+                    .with_resize_mode(ResizeMode::Cover),
             ),
             (
                 "中サイズ (WebP)",
                 ImageTransformOptions::new()
                     .with_width(300)
                     .with_height(200)
-                    .with_resize("contain")
-                    .with_format("webp"),
+                    .with_resize_mode(ResizeMode::Contain)
+                    .with_image_format(ImageFormat::Webp),
             ),
             (
                 "大サイズ (低画質)",
                 ImageTransformOptions::new()
                     .with_width(800)
                     .with_height(600)
-                    .with_quality(50),
+                    .try_with_quality(50)?,
             ),
         ];
 
@@ -573,7 +577,7 @@ async fn test_image_transformations(
     let transform_options = ImageTransformOptions::new()
         .with_width(100)
         .with_height(100)
-        .with_resize("cover");
+        .with_resize_mode(ResizeMode::Cover);
     println!("Getting public transform URL for: {}", image_path);
     let transform_url = storage
         .from(bucket_name)
@@ -584,8 +588,8 @@ async fn test_image_transformations(
     // Since bucket is public, let's test creating it anyway
     let signed_transform_options = ImageTransformOptions::new()
         .with_width(50)
-        .with_quality(75)
-        .with_format("webp");
+        .try_with_quality(75)?
+        .with_image_format(ImageFormat::Webp);
     println!("Creating signed transform URL for: {}", image_path);
     let signed_transform_result = storage
         .from(bucket_name)
@@ -0,0 +1,7 @@
+// `cargo xtask e2e functions` が呼び出す関数: 受け取った JSON をそのまま返す
+Deno.serve(async (req) => {
+  const body = await req.json().catch(() => null);
+  return new Response(JSON.stringify({ echo: body, method: req.method }), {
+    headers: { "Content-Type": "application/json" },
+  });
+});
@@ -0,0 +1,24 @@
+-- `cargo xtask e2e` のシナリオが使うテーブル
+-- 行は作成したユーザーだけが読み書きでき、realtime で INSERT を配信する
+CREATE TABLE IF NOT EXISTS public.e2e_notes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL DEFAULT auth.uid() REFERENCES auth.users (id) ON DELETE CASCADE,
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE public.e2e_notes ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY "e2e_notes_select_own" ON public.e2e_notes
+FOR SELECT TO authenticated USING (auth.uid() = user_id);
+
+CREATE POLICY "e2e_notes_insert_own" ON public.e2e_notes
+FOR INSERT TO authenticated WITH CHECK (auth.uid() = user_id);
+
+CREATE POLICY "e2e_notes_delete_own" ON public.e2e_notes
+FOR DELETE TO authenticated USING (auth.uid() = user_id);
+
+GRANT SELECT, INSERT, DELETE ON public.e2e_notes TO authenticated;
+GRANT SELECT, INSERT, UPDATE, DELETE ON public.e2e_notes TO service_role;
+
+ALTER PUBLICATION supabase_realtime ADD TABLE public.e2e_notes;
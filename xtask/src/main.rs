@@ -0,0 +1,178 @@
+// xtask/src/main.rs
+//
+// Workspace tasks, run with `cargo xtask <task>`.
+//
+//   cargo xtask e2e [--list] [scenario...]
+//
+// `e2e` runs the end-to-end scenarios in `crates/client/examples/e2e_*.rs`
+// against a local stack (`supabase start`). Each scenario is a separate
+// example binary, so it can also be run on its own with
+// `cargo run -p supabase-rust-client --example e2e_<scenario>`.
+
+use std::env;
+use std::process::{Command, ExitCode};
+
+/// Environment variables every scenario needs (`supabase status` prints them).
+const REQUIRED_ENV: &[&str] = &["SUPABASE_URL", "SUPABASE_ANON_KEY", "SUPABASE_SERVICE_KEY"];
+
+struct Scenario {
+    name: &'static str,
+    description: &'static str,
+}
+
+impl Scenario {
+    fn example(&self) -> String {
+        format!("e2e_{}", self.name)
+    }
+}
+
+const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "auth_postgrest",
+        description: "sign-up, then RLS-protected insert/select on e2e_notes",
+    },
+    Scenario {
+        name: "storage",
+        description: "upload, signed URL and download in a throwaway bucket",
+    },
+    Scenario {
+        name: "functions",
+        description: "invoke the e2e-echo edge function",
+    },
+    Scenario {
+        name: "realtime",
+        description: "receive the INSERT for a row over realtime",
+    },
+];
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("e2e") => e2e(&args[1..]),
+        _ => {
+            eprintln!("usage: cargo xtask e2e [--list] [scenario...]");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn e2e(args: &[String]) -> ExitCode {
+    if args.iter().any(|arg| arg == "--list") {
+        for scenario in SCENARIOS {
+            println!("{:<16} {}", scenario.name, scenario.description);
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let selected = match select(args) {
+        Ok(selected) => selected,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::from(2);
+        }
+    };
+    let missing = missing_env(|name| env::var_os(name).is_some());
+    if !missing.is_empty() {
+        eprintln!(
+            "missing {}; start a local stack with `supabase start` and export the values from `supabase status`",
+            missing.join(", ")
+        );
+        return ExitCode::from(2);
+    }
+
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut failed = Vec::new();
+    for scenario in &selected {
+        println!("==> {}: {}", scenario.name, scenario.description);
+        let status = Command::new(&cargo)
+            .args(["run", "--quiet", "--package", "supabase-rust-client"])
+            .args(["--example", &scenario.example()])
+            .status();
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => {
+                eprintln!("<== {} failed ({})", scenario.name, status);
+                failed.push(scenario.name);
+            }
+            Err(error) => {
+                eprintln!("<== {} could not be started: {}", scenario.name, error);
+                failed.push(scenario.name);
+            }
+        }
+    }
+
+    println!(
+        "\n{} passed, {} failed",
+        selected.len() - failed.len(),
+        failed.len()
+    );
+    if failed.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        eprintln!("failed: {}", failed.join(", "));
+        ExitCode::FAILURE
+    }
+}
+
+// No names selects every scenario; unknown names are an error.
+fn select(names: &[String]) -> Result<Vec<&'static Scenario>, String> {
+    if names.is_empty() {
+        return Ok(SCENARIOS.iter().collect());
+    }
+    names
+        .iter()
+        .map(|name| {
+            SCENARIOS
+                .iter()
+                .find(|scenario| scenario.name == name)
+                .ok_or_else(|| {
+                    let known: Vec<_> = SCENARIOS.iter().map(|scenario| scenario.name).collect();
+                    format!("unknown scenario `{}` (known: {})", name, known.join(", "))
+                })
+        })
+        .collect()
+}
+
+fn missing_env(is_set: impl Fn(&str) -> bool) -> Vec<&'static str> {
+    REQUIRED_ENV
+        .iter()
+        .copied()
+        .filter(|name| !is_set(name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_scenarios() {
+        assert_eq!(select(&[]).unwrap().len(), SCENARIOS.len());
+
+        let selected = select(&["realtime".to_string(), "storage".to_string()]).unwrap();
+        let names: Vec<_> = selected.iter().map(|scenario| scenario.name).collect();
+        assert_eq!(names, ["realtime", "storage"]);
+        assert_eq!(selected[0].example(), "e2e_realtime");
+
+        let error = select(&["nope".to_string()]).err().unwrap();
+        assert!(error.contains("unknown scenario `nope`"), "{}", error);
+    }
+
+    #[test]
+    fn test_every_scenario_has_an_example() {
+        let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/../crates/client/examples");
+        for scenario in SCENARIOS {
+            let path = format!("{}/{}.rs", examples, scenario.example());
+            assert!(std::path::Path::new(&path).exists(), "missing {}", path);
+        }
+    }
+
+    #[test]
+    fn test_missing_env() {
+        assert_eq!(missing_env(|_| true), Vec::<&str>::new());
+        assert_eq!(
+            missing_env(|name| name == "SUPABASE_URL"),
+            ["SUPABASE_ANON_KEY", "SUPABASE_SERVICE_KEY"]
+        );
+    }
+}